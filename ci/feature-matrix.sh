@@ -0,0 +1,16 @@
+#!/bin/sh
+# builds the library and its tests for every supported feature combination,
+# the default build (all features) also covers the binary and the examples
+set -e
+
+check() {
+    echo "== $*"
+    cargo check --lib --tests "$@"
+}
+
+check --no-default-features
+for feature in network file-loader earth-model ui-panels export; do
+    check --no-default-features --features "$feature"
+done
+echo "== default"
+cargo check --all-targets
@@ -0,0 +1,33 @@
+//minimal embedding of the simulation, loads the galileo constellation from the local files
+use bevy::prelude::*;
+use game::global::{InGameSettings, PropagationSettings};
+use game::propagation::{ConstFileClient, DataSource, ElementsFormat, LoadElements};
+use game::SkytracioPlugins;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(SkytracioPlugins::new()
+            .with_client(ConstFileClient::new("assets/".into()))
+            .with_settings(InGameSettings {
+                simulation_speed: 100.0,
+                propagation: PropagationSettings { batch_size: 50, numeric_fallback: true, ..default() },
+                ..default()
+            })
+            .without_demo_bodies())
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut load_elements: EventWriter<LoadElements>) {
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.0, 0.0, 500.0).looking_at(Vec3::ZERO, Vec3::X),
+        ..default()
+    });
+    commands.spawn(PointLightBundle {
+        transform: Transform::from_xyz(4.0, 90.0, 4.0),
+        point_light: PointLight { intensity: 15_000_000.0, range: 500.0, ..default() },
+        ..default()
+    });
+    load_elements.send(LoadElements { source: DataSource::File, ..LoadElements::group("galileo", ElementsFormat::Json) });
+}
@@ -0,0 +1,316 @@
+use std::time::Duration;
+
+use bevy::{color::palettes::css::*, prelude::*};
+
+use crate::global::InGameSettings;
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::orbit::SatelliteOrbit;
+use crate::plot::{downsample, AxisRange, PlotMarkerKind, TimeSeries};
+use crate::prediction_window::predict_window;
+use crate::propagation::{ElementsDiff, InGameElements, PropagatableDuration, Propageted, EARTH_RADIUS_KM};
+use crate::selection::SelectionSet;
+
+//WGS84
+const EARTH_FLATTENING: f64 = 1.0 / 298.257_223_563;
+//samples further apart than this many propagation intervals are a jump in time
+const MAX_GAP_INTERVALS: f64 = 3.0;
+const VALUE_TICKS: usize = 4;
+
+/// Panel plotting the altitude of the primary selected satellite over the recent and the predicted simulation time
+pub struct AltitudePlotPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlotQuantity {
+    #[default]
+    GeodeticAltitude,
+    GeocentricRadius
+}
+
+impl PlotQuantity {
+    /// Value of the quantity in kilometers for a position in the TEME frame
+    pub fn of(&self, position: [f64; 3]) -> f64 {
+        match self {
+            PlotQuantity::GeodeticAltitude => geodetic_altitude(position),
+            PlotQuantity::GeocentricRadius => position.iter().map(|c| c * c).sum::<f64>().sqrt()
+        }
+    }
+
+    //perigee and apogee are radii, shown as the altitude above the equator
+    fn of_radius(&self, radius: f64) -> f64 {
+        match self {
+            PlotQuantity::GeodeticAltitude => radius - EARTH_RADIUS_KM as f64,
+            PlotQuantity::GeocentricRadius => radius
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            PlotQuantity::GeodeticAltitude => "Altitude",
+            PlotQuantity::GeocentricRadius => "Radius"
+        }
+    }
+}
+
+/// Height above the WGS84 ellipsoid, in kilometers
+pub fn geodetic_altitude([x, y, z]: [f64; 3]) -> f64 {
+    let a = EARTH_RADIUS_KM as f64;
+    let e2 = EARTH_FLATTENING * (2.0 - EARTH_FLATTENING);
+    let p = (x * x + y * y).sqrt();
+    let mut latitude = z.atan2(p * (1.0 - e2));
+    for _ in 0..5 {
+        let n = a / (1.0 - e2 * latitude.sin().powi(2)).sqrt();
+        let altitude = p * latitude.cos() + z * latitude.sin() - a * a / n;
+        latitude = z.atan2(p * (1.0 - e2 * n / (n + altitude)));
+    }
+    //the form stays accurate close to the poles, unlike dividing by the cosine of the latitude
+    p * latitude.cos() + z * latitude.sin() - a * (1.0 - e2 * latitude.sin().powi(2)).sqrt()
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AltitudePlotSettings {
+    pub enabled: bool,
+    pub quantity: PlotQuantity,
+    /// Simulation time of the history kept
+    pub history: Duration,
+    /// Predicted simulation time after the latest sample, `None` plots only the history
+    pub prediction: Option<Duration>,
+    pub capacity: usize,
+    /// Time buckets the plotted series are reduced to
+    pub buckets: usize,
+    /// Size of the panel, in logical pixels
+    pub size: Vec2
+}
+
+impl Default for AltitudePlotSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quantity: PlotQuantity::default(),
+            history: Duration::from_secs(6 * 60 * 60),
+            prediction: Some(Duration::from_secs(3 * 60 * 60)),
+            capacity: 4096,
+            buckets: 80,
+            size: Vec2::new(320.0, 140.0)
+        }
+    }
+}
+
+/// Data plotted for the satellite `of`, times are minutes since J2000
+#[derive(Resource, Debug)]
+pub struct AltitudePlot {
+    pub of: Option<Entity>,
+    pub history: TimeSeries,
+    pub prediction: Vec<(f64, f64)>,
+    pub perigee: Option<f64>,
+    pub apogee: Option<f64>
+}
+
+impl Default for AltitudePlot {
+    fn default() -> Self {
+        Self { of: None, history: TimeSeries::new(1), prediction: vec![], perigee: None, apogee: None }
+    }
+}
+
+impl AltitudePlot {
+    /// Time span of the plot, from the oldest sample to the end of the prediction
+    pub fn time_range(&self) -> Option<(f64, f64)> {
+        let start = self.history.samples().next()?.0;
+        let end = self.prediction.last().or(self.history.last().as_ref()).map(|s| s.0)?;
+        Some((start, end.max(start + 1.0)))
+    }
+
+    /// Value axis of everything plotted, the reference lines included
+    pub fn value_range(&self) -> Option<AxisRange> {
+        let values = self.history.samples().chain(self.prediction.iter().copied()).map(|(_, v)| v);
+        AxisRange::fit(values.chain(self.perigee).chain(self.apogee), VALUE_TICKS)
+    }
+}
+
+impl MemoryFootprint for AltitudePlot {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() - size_of::<TimeSeries>() + self.history.estimated_bytes() + self.prediction.len() * size_of::<(f64, f64)>()
+    }
+}
+
+impl Plugin for AltitudePlotPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AltitudePlotSettings>()
+            .init_resource::<AltitudePlot>()
+            .account_resource::<AltitudePlot>("histories")
+            .init_resource::<SelectionSet>()
+            .add_event::<Propageted>()
+            .add_event::<ElementsDiff>()
+            .add_systems(Update, (track_plotted_satellite, record_altitude, mark_element_refreshes).chain().run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, render_altitude_plot.after(mark_element_refreshes).run_if(resource_changed::<AltitudePlot>));
+    }
+}
+
+fn track_plotted_satellite(
+    plot_settings: Res<AltitudePlotSettings>,
+    settings: Res<InGameSettings>,
+    selection: Res<SelectionSet>,
+    mut plot: ResMut<AltitudePlot>
+) {
+    let focused = selection.primary().filter(|_| plot_settings.enabled);
+    if focused == plot.of && !plot_settings.is_changed() {
+        return;
+    }
+    let interval_minutes = settings.propagation.real_time_interval.as_secs_f64() * settings.simulation_speed as f64 / 60.0;
+    *plot = AltitudePlot {
+        of: focused,
+        history: TimeSeries::new(plot_settings.capacity)
+            .with_span(plot_settings.history.as_secs_f64() / 60.0)
+            .with_max_gap(interval_minutes * MAX_GAP_INTERVALS),
+        ..default()
+    };
+}
+
+fn record_altitude(
+    mut propagated: EventReader<Propageted>,
+    plot_settings: Res<AltitudePlotSettings>,
+    settings: Res<InGameSettings>,
+    mut plot: ResMut<AltitudePlot>,
+    satellites: Query<(&InGameElements, &PropagatableDuration, &SatelliteOrbit)>
+) {
+    let Some(entity) = plot.of else {
+        propagated.clear();
+        return;
+    };
+    let Some(prediction) = propagated.read().flat_map(|p| p.data()).filter(|(e, _)| *e == entity).last().map(|(_, p)| p.position) else {
+        return;
+    };
+    let Ok((elements, elapsed, orbit)) = satellites.get(entity) else {
+        return;
+    };
+    let quantity = plot_settings.quantity;
+    let now = elapsed.minutes_since_j2000(&elements.0);
+    plot.history.push(now, quantity.of(prediction));
+    plot.perigee = Some(quantity.of_radius(orbit.semi_major_axis * (1.0 - orbit.eccentricity)));
+    plot.apogee = Some(quantity.of_radius(orbit.semi_major_axis * (1.0 + orbit.eccentricity)));
+    plot.prediction = match plot_settings.prediction {
+        Some(window) => {
+            let step = window / plot_settings.buckets.max(1) as u32;
+            let from = elapsed.minutes_since_epoch();
+            predict_window(&elements.0, from, window, step, &settings.propagation).into_iter()
+                .enumerate()
+                .map(|(i, p)| (now + (i as f64 * step.as_secs_f64() / 60.0).min(window.as_secs_f64() / 60.0), quantity.of(p.to_array().map(|c| c as f64))))
+                .collect()
+        },
+        None => vec![]
+    };
+}
+
+fn mark_element_refreshes(mut diffs: EventReader<ElementsDiff>, mut plot: ResMut<AltitudePlot>) {
+    for diff in diffs.read() {
+        if Some(diff.entity) == plot.of {
+            plot.history.mark(diff.minutes_since_j2000(), PlotMarkerKind::ElementsRefresh);
+        }
+    }
+}
+
+#[derive(Component)]
+struct AltitudePlotNode;
+
+//the plot is a panel of absolutely positioned dots and lines, respawned whenever the data changes
+fn render_altitude_plot(
+    plot: Res<AltitudePlot>,
+    plot_settings: Res<AltitudePlotSettings>,
+    nodes: Query<Entity, With<AltitudePlotNode>>,
+    mut commands: Commands
+) {
+    for entity in nodes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (Some((start, end)), Some(range)) = (plot.time_range(), plot.value_range()) else {
+        return;
+    };
+    let size = plot_settings.size;
+    let to_panel = |(t, v): (f64, f64)| Vec2::new(
+        ((t - start) / (end - start)) as f32 * size.x,
+        (1.0 - range.normalize(v)) as f32 * size.y
+    );
+    let rect = |position: Vec2, extent: Vec2, color: Srgba| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(position.x),
+            top: Val::Px(position.y),
+            width: Val::Px(extent.x),
+            height: Val::Px(extent.y),
+            ..default()
+        },
+        background_color: Color::from(color).into(),
+        ..default()
+    };
+
+    let history: Vec<_> = plot.history.samples().collect();
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    width: Val::Px(size.x),
+                    height: Val::Px(size.y),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            AltitudePlotNode
+        ))
+        .with_children(|parent| {
+            for (reference, color) in [(plot.perigee, LIME), (plot.apogee, ORANGE)] {
+                if let Some(value) = reference {
+                    parent.spawn(rect(Vec2::new(0.0, to_panel((start, value)).y), Vec2::new(size.x, 1.0), color));
+                }
+            }
+            for marker in plot.history.markers() {
+                let color = match marker.kind {
+                    PlotMarkerKind::ElementsRefresh => GOLD,
+                    PlotMarkerKind::TimeJump => RED
+                };
+                parent.spawn(rect(Vec2::new(to_panel((marker.time, range.min)).x, 0.0), Vec2::new(1.0, size.y), color));
+            }
+            for (samples, color) in [(downsample(&history, plot_settings.buckets), WHITE), (downsample(&plot.prediction, plot_settings.buckets), AQUA)] {
+                for sample in samples {
+                    parent.spawn(rect(to_panel(sample) - Vec2::ONE, Vec2::splat(2.0), color));
+                }
+            }
+            let label = format!("{} {:.0} - {:.0} km", plot_settings.quantity.label(), range.min, range.max);
+            parent.spawn(TextBundle::from_section(label, TextStyle { font_size: 12.0, ..default() }));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_geodetic_altitude_on_the_ellipsoid() {
+        let polar_radius = EARTH_RADIUS_KM as f64 * (1.0 - EARTH_FLATTENING);
+        assert_abs_diff_eq!(geodetic_altitude([EARTH_RADIUS_KM as f64 + 550.0, 0.0, 0.0]), 550.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(geodetic_altitude([0.0, 0.0, polar_radius + 550.0]), 550.0, epsilon = 1e-6);
+        //at 45° the radius is between the two, the altitude isn't
+        let direction = [1.0, 0.0, 1.0].map(|c: f64| c / 2f64.sqrt());
+        let (a, b) = (EARTH_RADIUS_KM as f64, polar_radius);
+        let radius = (2.0 / (1.0 / (a * a) + 1.0 / (b * b))).sqrt() + 400.0;
+        assert_abs_diff_eq!(geodetic_altitude(direction.map(|c| c * radius)), 400.0, epsilon = 0.5);
+        assert_abs_diff_eq!(PlotQuantity::GeocentricRadius.of(direction.map(|c| c * radius)), radius, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_plot_ranges_include_the_references() {
+        let mut plot = AltitudePlot { history: TimeSeries::new(16), perigee: Some(500.0), apogee: Some(39_700.0), ..default() };
+        assert_eq!(plot.time_range(), None);
+        for (t, v) in [(0.0, 900.0), (30.0, 2_000.0), (60.0, 4_500.0)] {
+            plot.history.push(t, v);
+        }
+        plot.prediction = vec![(60.0, 4_500.0), (180.0, 20_000.0)];
+        assert_eq!(plot.time_range(), Some((0.0, 180.0)));
+        assert_eq!(plot.value_range(), Some(AxisRange { min: 0.0, max: 40_000.0, step: 10_000.0 }));
+    }
+}
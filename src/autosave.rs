@@ -0,0 +1,659 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::{Action, ActionCategory, ActionTriggered};
+use crate::notes::{Annotation, Annotations};
+use crate::observer::Observer;
+use crate::propagation::{ElementsFormat, GroupLoadStatus, InGameElements, LoadElements, LoadStatus};
+use crate::selection::{SelectionSet, Watchlist};
+use crate::tour::{Tour, TourPlayer};
+
+const HEADER: &str = "skytracio-session-v1";
+const MARKER: &str = "clean-shutdown";
+const PREFIX: &str = "autosave-";
+const EXTENSION: &str = "session";
+
+/// Writes the session to a rolling autosave every few minutes and on a graceful exit.
+/// When the last session didn't exit cleanly, its autosave is offered on startup
+pub struct AutosavePlugin {
+    settings: AutosaveSettings,
+    restore: bool
+}
+
+impl AutosavePlugin {
+    pub fn new(settings: AutosaveSettings) -> Self {
+        Self { settings, restore: false }
+    }
+
+    /// Restores the autosave of a crashed session without asking, `--restore-autosave` on the command line
+    pub fn restoring(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct AutosaveSettings {
+    pub directory: PathBuf,
+    /// Real time between autosaves
+    pub interval: Duration,
+    /// Autosaves kept, older ones are pruned
+    pub keep: usize
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self { directory: PathBuf::from("autosave"), interval: Duration::from_secs(5 * 60), keep: 5 }
+    }
+}
+
+/// What a session consists of beyond the loaded data, satellites are referenced by NORAD id
+/// A session saved with its tour is the shareable scenario of the app, there's no separate scenario file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionSnapshot {
+    pub groups: Vec<String>,
+    pub watchlist: Vec<u64>,
+    pub focused: Option<u64>,
+    pub simulation_speed: f32,
+    /// Notes and tags, also of satellites not loaded in the session
+    pub annotations: BTreeMap<u64, Annotation>,
+    /// Ground stations, without their horizon profiles
+    pub observers: Vec<Observer>,
+    /// Played when the session is opened, only sessions saved by hand carry the tour being played
+    pub tour: Option<Tour>
+}
+
+#[derive(Debug)]
+pub enum AutosaveError {
+    IO(io::Error),
+    Corrupt(String)
+}
+
+impl From<io::Error> for AutosaveError {
+    fn from(value: io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+impl SessionSnapshot {
+    //header line, then one `key value` line per entry
+    pub fn serialize(&self) -> String {
+        let mut out = format!("{HEADER}\nspeed {}\n", self.simulation_speed);
+        if let Some(focused) = self.focused {
+            let _ = writeln!(out, "focused {focused}");
+        }
+        for group in &self.groups {
+            let _ = writeln!(out, "group {group}");
+        }
+        for norad_id in &self.watchlist {
+            let _ = writeln!(out, "watch {norad_id}");
+        }
+        for (norad_id, annotation) in &self.annotations {
+            if !annotation.note.is_empty() {
+                let _ = writeln!(out, "note {norad_id} {}", escape_line(&annotation.note));
+            }
+            for tag in &annotation.tags {
+                let _ = writeln!(out, "tag {norad_id} {tag}");
+            }
+        }
+        for observer in &self.observers {
+            let _ = writeln!(
+                out, "observer {} {} {} {} {}",
+                observer.latitude, observer.longitude, observer.altitude_km, observer.min_elevation, escape_line(&observer.name)
+            );
+        }
+        if let Some(tour) = &self.tour {
+            let _ = writeln!(out, "tour {}", tour.to_json());
+        }
+        out
+    }
+
+    pub fn parse(content: &str) -> Result<Self, AutosaveError> {
+        let corrupt = |what: &str| AutosaveError::Corrupt(what.to_owned());
+        let norad_id = |value: &str| value.parse::<u64>().map_err(|_| corrupt("NORAD id"));
+        let mut lines = content.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(corrupt("unknown header"));
+        }
+        let mut snapshot = SessionSnapshot::default();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let (key, value) = line.split_once(' ').ok_or_else(|| corrupt(line))?;
+            match key {
+                "speed" => snapshot.simulation_speed = value.parse().map_err(|_| corrupt("speed"))?,
+                "focused" => snapshot.focused = Some(norad_id(value)?),
+                "group" => snapshot.groups.push(value.to_owned()),
+                "watch" => snapshot.watchlist.push(norad_id(value)?),
+                "note" | "tag" => {
+                    let (id, text) = value.split_once(' ').ok_or_else(|| corrupt(line))?;
+                    let annotation = snapshot.annotations.entry(norad_id(id)?).or_default();
+                    match key {
+                        "note" => annotation.note = unescape_line(text),
+                        _ => annotation.tags.push(text.to_owned())
+                    }
+                },
+                "observer" => {
+                    let fields: Vec<&str> = value.splitn(5, ' ').collect();
+                    let [latitude, longitude, altitude_km, min_elevation, name] = fields[..] else {
+                        return Err(corrupt(line));
+                    };
+                    let number = |value: &str| value.parse::<f64>().map_err(|_| corrupt("observer"));
+                    snapshot.observers.push(
+                        Observer::new(unescape_line(name), number(latitude)?, number(longitude)?, number(altitude_km)?)
+                            .with_min_elevation(number(min_elevation)?)
+                    );
+                },
+                "tour" => snapshot.tour = Some(Tour::parse(value).map_err(|err| corrupt(&err.to_string()))?),
+                _ => return Err(corrupt(key))
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+//notes span lines, the session keeps one entry per line
+fn escape_line(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_line(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\')
+        }
+    }
+    out
+}
+
+/// Rolling autosaves numbered by a growing sequence, with the marker of the last clean shutdown
+#[derive(Debug, Clone)]
+pub struct AutosaveStore {
+    directory: PathBuf,
+    keep: usize
+}
+
+impl AutosaveStore {
+    pub fn new(directory: PathBuf, keep: usize) -> Self {
+        Self { directory, keep: keep.max(1) }
+    }
+
+    fn path(&self, sequence: u64) -> PathBuf {
+        self.directory.join(format!("{PREFIX}{sequence:08}.{EXTENSION}"))
+    }
+
+    /// Sequence numbers of the stored autosaves, oldest first
+    pub fn autosaves(&self) -> Vec<u64> {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return vec![];
+        };
+        let mut sequences: Vec<u64> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| name.strip_prefix(PREFIX)?.strip_suffix(EXTENSION)?.strip_suffix('.')?.parse().ok())
+            .collect();
+        sequences.sort_unstable();
+        sequences
+    }
+
+    /// Writes the next autosave and prunes the old ones, returns its sequence number
+    pub fn write(&self, snapshot: &SessionSnapshot) -> Result<u64, AutosaveError> {
+        fs::create_dir_all(&self.directory)?;
+        let sequence = self.autosaves().last().map_or(1, |last| last + 1);
+        //a crash in the middle of writing must not leave a truncated autosave behind
+        let partial = self.directory.join(format!("{PREFIX}{sequence:08}.partial"));
+        fs::write(&partial, snapshot.serialize())?;
+        fs::rename(&partial, self.path(sequence))?;
+        self.prune()?;
+        Ok(sequence)
+    }
+
+    /// Removes all but the newest `keep` autosaves
+    pub fn prune(&self) -> io::Result<()> {
+        let autosaves = self.autosaves();
+        for sequence in &autosaves[..autosaves.len().saturating_sub(self.keep)] {
+            fs::remove_file(self.path(*sequence))?;
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, sequence: u64) -> Result<SessionSnapshot, AutosaveError> {
+        SessionSnapshot::parse(&fs::read_to_string(self.path(sequence))?)
+    }
+
+    /// Records that the session ended cleanly right after writing the autosave `sequence`
+    pub fn mark_clean_shutdown(&self, sequence: u64) -> io::Result<()> {
+        fs::write(self.directory.join(MARKER), sequence.to_string())
+    }
+
+    /// Sequence of the last autosave written before a clean shutdown
+    pub fn clean_shutdown(&self) -> Option<u64> {
+        fs::read_to_string(self.directory.join(MARKER)).ok()?.trim().parse().ok()
+    }
+
+    /// The autosave worth restoring, if any
+    pub fn restore_candidate(&self) -> Option<u64> {
+        restore_candidate(&self.autosaves(), self.clean_shutdown())
+    }
+}
+
+/// Autosaves written after the last clean shutdown come from a session that crashed, the newest one is restored
+pub fn restore_candidate(autosaves: &[u64], clean_shutdown: Option<u64>) -> Option<u64> {
+    autosaves.last().copied().filter(|latest| clean_shutdown.is_none_or(|clean| *latest > clean))
+}
+
+/// Autosave of a crashed session waiting for the user to restore or dismiss it
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RestorePrompt(pub Option<u64>);
+
+//selection of a restored session, resolved as its satellites appear
+#[derive(Resource, Debug)]
+struct PendingRestore {
+    groups: Vec<String>,
+    watchlist: Vec<u64>,
+    focused: Option<u64>
+}
+
+#[derive(Resource)]
+struct AutosaveTimer(Timer);
+
+#[derive(Resource)]
+struct RestoreOnStart(bool);
+
+#[derive(Component)]
+struct RestorePromptText;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(self.settings.clone())
+            .insert_resource(AutosaveTimer(Timer::new(self.settings.interval, TimerMode::Repeating)))
+            .insert_resource(RestoreOnStart(self.restore))
+            .init_resource::<RestorePrompt>()
+            .init_resource::<GroupLoadStatus>()
+            .add_event::<LoadElements>()
+            .add_event::<ActionTriggered>()
+            .register_command(CommandDescriptor::new("Restore autosave", ActionCategory::General, |_, world| {
+                if let Some(sequence) = world.resource_mut::<RestorePrompt>().0.take() {
+                    restore(world, sequence);
+                }
+            }))
+            .register_command(
+                CommandDescriptor::new("Save session", ActionCategory::General, |params, world| {
+                    if let Some(path) = params[0].as_path() {
+                        save_session(world, path);
+                    }
+                })
+                .with_param("file", ParamKind::Path)
+            )
+            .register_command(
+                CommandDescriptor::new("Open session", ActionCategory::General, |params, world| {
+                    if let Some(path) = params[0].as_path() {
+                        open_session(world, path);
+                    }
+                })
+                .with_param("file", ParamKind::Path)
+            )
+            .add_systems(Startup, decide_restore)
+            .add_systems(Update, (autosave_periodically, apply_pending_restore.run_if(resource_exists::<PendingRestore>)))
+            .add_systems(Update, (answer_prompt, update_prompt_text).chain())
+            .add_systems(Last, autosave_on_exit);
+    }
+}
+
+fn snapshot(world: &mut World) -> SessionSnapshot {
+    let mut groups: Vec<String> = world.resource::<GroupLoadStatus>().iter()
+        .filter(|(_, status)| **status == LoadStatus::Loaded)
+        .map(|(group, _)| group.clone())
+        .collect();
+    groups.sort();
+    let norad_id = |world: &World, entity: Entity| world.get::<InGameElements>(entity).map(|el| el.0.norad_id);
+    let watchlist = world.get_resource::<Watchlist>()
+        .map(|watchlist| watchlist.iter().filter_map(|e| norad_id(world, e)).collect())
+        .unwrap_or_default();
+    let focused = world.get_resource::<SelectionSet>().and_then(SelectionSet::primary).and_then(|e| norad_id(world, e));
+    let simulation_speed = world.get_resource::<InGameSettings>().map_or(1.0, |s| s.simulation_speed);
+    let annotations = world.get_resource::<Annotations>().map(|a| a.0.clone()).unwrap_or_default();
+    let mut observers: Vec<Observer> = world.query::<&Observer>().iter(world).map(|o| Observer { horizon: None, ..o.clone() }).collect();
+    observers.sort_by(|a, b| a.name.cmp(&b.name));
+    SessionSnapshot { groups, watchlist, focused, simulation_speed, annotations, observers, tour: None }
+}
+
+fn store(world: &World) -> AutosaveStore {
+    let settings = world.resource::<AutosaveSettings>();
+    AutosaveStore::new(settings.directory.clone(), settings.keep)
+}
+
+fn autosave(world: &mut World) -> Option<u64> {
+    let snapshot = snapshot(world);
+    match store(world).write(&snapshot) {
+        Ok(sequence) => Some(sequence),
+        Err(err) => {
+            warn!("Autosave failed: {err:?}");
+            None
+        }
+    }
+}
+
+fn decide_restore(world: &mut World) {
+    let Some(sequence) = store(world).restore_candidate() else {
+        return;
+    };
+    if world.resource::<RestoreOnStart>().0 {
+        restore(world, sequence);
+    } else {
+        info!("The last session didn't exit cleanly, its autosave {sequence} can be restored");
+        world.resource_mut::<RestorePrompt>().0 = Some(sequence);
+    }
+}
+
+fn restore(world: &mut World, sequence: u64) {
+    match store(world).read(sequence) {
+        Ok(snapshot) => {
+            info!("Restoring autosave {sequence}");
+            restore_snapshot(world, snapshot);
+        },
+        Err(err) => warn!("Autosave {sequence} can't be restored: {err:?}")
+    }
+}
+
+/// Writes the session with the tour being played to a file to share, a reproducible fly-through
+pub fn save_session(world: &mut World, path: &Path) {
+    let tour = world.get_resource::<TourPlayer>().map(|player| player.tour().clone());
+    let snapshot = SessionSnapshot { tour, ..snapshot(world) };
+    match fs::write(path, snapshot.serialize()) {
+        Ok(()) => info!("Session saved to {}", path.display()),
+        Err(err) => warn!("Failed to save the session to {}: {err}", path.display())
+    }
+}
+
+/// Restores a saved session and starts its tour, if it has one
+pub fn open_session(world: &mut World, path: &Path) {
+    let snapshot = fs::read_to_string(path).map_err(AutosaveError::from).and_then(|content| SessionSnapshot::parse(&content));
+    match snapshot {
+        Ok(snapshot) => {
+            info!("Opening the session {}", path.display());
+            restore_snapshot(world, snapshot);
+        },
+        Err(err) => warn!("Session {} can't be opened: {err:?}", path.display())
+    }
+}
+
+fn restore_snapshot(world: &mut World, snapshot: SessionSnapshot) {
+    for group in &snapshot.groups {
+        world.send_event(LoadElements::group(group.clone(), ElementsFormat::Json));
+    }
+    if let Some(mut settings) = world.get_resource_mut::<InGameSettings>() {
+        settings.simulation_speed = snapshot.simulation_speed;
+    }
+    //observers of the same name are already there, the session only adds the missing ones
+    let existing: Vec<String> = world.query::<&Observer>().iter(world).map(|o| o.name.clone()).collect();
+    for observer in snapshot.observers.into_iter().filter(|o| !existing.contains(&o.name)) {
+        world.spawn(observer);
+    }
+    //attached to the satellites as their groups load
+    world.insert_resource(Annotations(snapshot.annotations));
+    world.insert_resource(PendingRestore { groups: snapshot.groups, watchlist: snapshot.watchlist, focused: snapshot.focused });
+    //played with the TourPlugin, a tour waits for the groups it needs itself
+    if let Some(tour) = snapshot.tour {
+        world.insert_resource(TourPlayer::new(tour));
+    }
+}
+
+fn apply_pending_restore(world: &mut World) {
+    let satellites: Vec<(Entity, u64)> = world.query::<(Entity, &InGameElements)>().iter(world).map(|(e, el)| (e, el.0.norad_id)).collect();
+    let mut pending = world.remove_resource::<PendingRestore>().unwrap();
+    for (entity, norad_id) in satellites {
+        if pending.focused == Some(norad_id) {
+            if let Some(mut selection) = world.get_resource_mut::<SelectionSet>() {
+                selection.select_single(entity);
+            }
+            pending.focused = None;
+        }
+        if pending.watchlist.contains(&norad_id) {
+            if let Some(mut watchlist) = world.get_resource_mut::<Watchlist>() {
+                watchlist.insert(entity);
+            }
+            pending.watchlist.retain(|id| *id != norad_id);
+        }
+    }
+    //satellites missing once their groups are loaded aren't coming anymore
+    let status = world.resource::<GroupLoadStatus>();
+    let loaded = pending.groups.iter().all(|group| status.get(group) == Some(LoadStatus::Loaded));
+    let resolved = pending.focused.is_none() && pending.watchlist.is_empty();
+    if !loaded && !resolved {
+        world.insert_resource(pending);
+    }
+}
+
+fn autosave_periodically(world: &mut World) {
+    let delta = world.resource::<Time<Real>>().delta();
+    if world.resource_mut::<AutosaveTimer>().0.tick(delta).just_finished() {
+        autosave(world);
+    }
+}
+
+fn autosave_on_exit(world: &mut World, mut exits: Local<ManualEventReader<AppExit>>) {
+    if exits.read(world.resource::<Events<AppExit>>()).count() == 0 {
+        return;
+    }
+    if let Some(sequence) = autosave(world) {
+        if let Err(err) = store(world).mark_clean_shutdown(sequence) {
+            warn!("Failed to mark the clean shutdown: {err:?}");
+        }
+    }
+}
+
+fn answer_prompt(
+    mut actions: EventReader<ActionTriggered>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut prompt: ResMut<RestorePrompt>,
+    mut commands: Commands
+) {
+    let dismissed = actions.read().any(|ActionTriggered(action)| *action == Action::CloseOverlay);
+    let Some(sequence) = prompt.0 else {
+        return;
+    };
+    if keys.is_some_and(|keys| keys.just_pressed(KeyCode::Enter)) {
+        prompt.0 = None;
+        commands.add(move |world: &mut World| restore(world, sequence));
+    } else if dismissed {
+        prompt.0 = None;
+    }
+}
+
+fn update_prompt_text(prompt: Res<RestorePrompt>, texts: Query<Entity, With<RestorePromptText>>, mut commands: Commands) {
+    if !prompt.is_changed() {
+        return;
+    }
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if prompt.0.is_none() {
+        return;
+    }
+    commands.spawn((
+        TextBundle::from_section(
+            "The last session didn't exit cleanly.\nEnter restores its autosave, Esc dismisses it",
+            TextStyle { font_size: 16.0, ..default() }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(48.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        RestorePromptText
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::commands::{CommandsPlugin, InvokeCommand};
+    use crate::notes::{CustomTags, Notes, NotesPlugin, SetSatelliteNote, TagSatellite};
+    use crate::propagation::LoadElementsPlugin;
+    use crate::selection::{FocusSatellite, SelectionPlugin};
+    use crate::tour::TourPlugin;
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    fn settings(name: &str) -> AutosaveSettings {
+        let directory = std::env::temp_dir().join(format!("skytracio-autosave-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        AutosaveSettings { directory, interval: Duration::from_secs(60), keep: 3 }
+    }
+
+    fn session(settings: &AutosaveSettings) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AutosavePlugin::new(settings.clone())));
+        app.update();
+        app
+    }
+
+    #[test]
+    fn test_rolling_autosaves_are_pruned() {
+        let settings = settings("prune");
+        let store = AutosaveStore::new(settings.directory.clone(), settings.keep);
+        let snapshot = SessionSnapshot {
+            groups: vec!["galileo".to_owned(), "gps-ops".to_owned()],
+            watchlist: vec![25544, 48274],
+            focused: Some(25544),
+            simulation_speed: 600.0,
+            annotations: BTreeMap::from([
+                (25544, Annotation { note: "Crewed\\reboost \\n planned\nnext week".to_owned(), tags: vec!["crewed".to_owned(), "watch list".to_owned()] }),
+                (48274, Annotation { note: String::new(), tags: vec!["crewed".to_owned()] })
+            ]),
+            observers: vec![
+                Observer::new("Kiruna, Esrange", 67.8833, 21.0667, 0.341).with_min_elevation(5.0),
+                Observer::new("Marker 1", -33.9, 18.4, 0.0)
+            ],
+            tour: Some(Tour::parse(r#"[{"wait_for_group": "galileo"}, {"focus": "GSAT0101 (GALILEO-PFM)"}, {"wait": 2.5}]"#).unwrap())
+        };
+        for expected in 1..=7 {
+            assert_eq!(store.write(&snapshot).unwrap(), expected);
+        }
+        assert_eq!(store.autosaves(), vec![5, 6, 7]);
+        assert_eq!(store.read(7).unwrap(), snapshot);
+        assert!(matches!(SessionSnapshot::parse("something else"), Err(AutosaveError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_notes_reattach_after_restore() {
+        let settings = settings("notes");
+        let session = || {
+            let mut app = App::new();
+            app
+                .add_plugins((MinimalPlugins, AutosavePlugin::new(settings.clone()), LoadElementsPlugin::<SyntheticClient>::new(), NotesPlugin))
+                .insert_resource(SyntheticClient(starlink_like_elements(10, 1486)));
+            app
+        };
+        let satellite = |app: &mut App, norad_id: u64| app.world_mut().query::<(Entity, &InGameElements)>().iter(app.world())
+            .find(|(_, elements)| elements.0.norad_id == norad_id)
+            .map(|(entity, _)| entity);
+
+        let mut first = session();
+        first.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+        for _ in 0..100 {
+            first.update();
+            if satellite(&mut first, 44003).is_some() {
+                break;
+            }
+        }
+        let entity = satellite(&mut first, 44003).unwrap();
+        first.world_mut().send_event(SetSatelliteNote { entity, note: "Lost attitude control\nsince 12:40".to_owned() });
+        first.world_mut().send_event(TagSatellite { entity, tag: "anomaly".to_owned(), remove: false });
+        first.update();
+        let sequence = autosave(first.world_mut()).unwrap();
+
+        //a new session loads the group again, the satellite is a new entity
+        let mut second = session();
+        restore(second.world_mut(), sequence);
+        let mut restored = None;
+        for _ in 0..100 {
+            second.update();
+            restored = satellite(&mut second, 44003).filter(|e| second.world().get::<Notes>(*e).is_some());
+            if restored.is_some() {
+                break;
+            }
+        }
+        let restored = restored.unwrap();
+        assert_eq!(second.world().get::<Notes>(restored), Some(&Notes("Lost attitude control\nsince 12:40".to_owned())));
+        assert_eq!(second.world().get::<CustomTags>(restored), Some(&CustomTags(vec!["anomaly".to_owned()])));
+        let other = satellite(&mut second, 44004).unwrap();
+        assert_eq!(second.world().get::<Notes>(other), None);
+    }
+
+    #[test]
+    fn test_restore_offered_after_crash() {
+        let settings = settings("crash");
+        let store = AutosaveStore::new(settings.directory.clone(), settings.keep);
+        //a fresh install has nothing to restore
+        assert_eq!(session(&settings).world().resource::<RestorePrompt>().0, None);
+
+        //the previous session autosaved, then crashed before writing the marker
+        store.write(&SessionSnapshot::default()).unwrap();
+        let mut app = session(&settings);
+        assert_eq!(app.world().resource::<RestorePrompt>().0, Some(1));
+
+        //this one exits cleanly, the next session starts without a prompt
+        app.world_mut().send_event(AppExit::Success);
+        app.update();
+        assert_eq!(store.clean_shutdown(), Some(2));
+        assert_eq!(session(&settings).world().resource::<RestorePrompt>().0, None);
+
+        assert_eq!(restore_candidate(&[3, 4], Some(2)), Some(4));
+        assert_eq!(restore_candidate(&[3, 4], Some(4)), None);
+        assert_eq!(restore_candidate(&[], None), None);
+    }
+
+    #[test]
+    fn test_opened_session_starts_its_tour() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, CommandsPlugin, AutosavePlugin::new(settings("tour")), LoadElementsPlugin::<SyntheticClient>::new(), SelectionPlugin, TourPlugin::default()))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+            .insert_resource(SyntheticClient(starlink_like_elements(10, 1450)))
+            .insert_resource(InGameSettings::default());
+        app.update();
+        let session = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/fixtures/tour.session");
+        app.world_mut().send_event(InvokeCommand { name: "Open session".to_owned(), arguments: vec![session.display().to_string()] });
+
+        let mut focused = vec![];
+        let mut reader = app.world().resource::<Events<FocusSatellite>>().get_reader();
+        let mut finished = false;
+        //the last commands run in the frame after the tour finishes
+        for _ in 0..200 {
+            app.update();
+            let events = app.world().resource::<Events<FocusSatellite>>();
+            focused.extend(reader.read(events).map(|ev| app.world().get::<InGameElements>(ev.entity).unwrap().0.norad_id));
+            if finished {
+                break;
+            }
+            finished = app.world().get_resource::<TourPlayer>().is_some_and(|player| player.is_finished());
+        }
+        assert_eq!(app.world_mut().query::<&InGameElements>().iter(app.world()).count(), 10);
+        assert_eq!(app.world().resource::<InGameSettings>().simulation_speed, 60.0);
+        let watched: Vec<u64> = app.world().resource::<Watchlist>().iter()
+            .map(|entity| app.world().get::<InGameElements>(entity).unwrap().0.norad_id)
+            .collect();
+        assert_eq!(watched, vec![44002]);
+        //the camera visits the targets of the tour in order
+        assert_eq!(focused, vec![44003, 44005]);
+    }
+}
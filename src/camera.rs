@@ -1,6 +1,10 @@
 use std::fmt::Debug;
 
-use bevy::{log::info, math::{Quat, Vec3}, prelude::Transform};
+use bevy::{log::info, math::{Quat, Vec2, Vec3}, prelude::*};
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::input::ActionCategory;
+use crate::world_frame::WORLD_FRAME;
 
 
 #[derive(Default, Debug)]
@@ -9,7 +13,173 @@ pub struct CameraLock<I>  {
     pub lock_transform: Transform,
     pub distance: f32,
     pub is_default: bool,
-    pub is_locked: bool
+    pub is_locked: bool,
+    pub mode: CameraLockMode,
+    /// Smoothed direction of motion of the locked body, from its consecutive positions
+    pub track: Option<Vec3>,
+    pub smoothing: CameraSmoothing,
+    /// Smoothed position of the locked body, `lock_transform` follows it instead of the body
+    pub target: TargetSmoother,
+    /// Accumulated mouse drags, rotates the camera around the locked body away from the placement of the mode
+    pub orbital: Quat
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraLockMode {
+    /// Radially outward from the body, looking at the planet through it
+    #[default]
+    Radial,
+    /// Behind the body along its velocity, looking forward along the track with the orbit normal up
+    Chase
+}
+
+impl CameraLockMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            CameraLockMode::Radial => CameraLockMode::Chase,
+            CameraLockMode::Chase => CameraLockMode::Radial
+        }
+    }
+}
+
+//how quickly (per second) the chase direction catches up with the velocity, filters out jitter of the propagation
+const TRACK_SMOOTHING: f32 = 5.0;
+/// Rotation of a mouse drag around the locked body per pixel
+pub const DRAG_RADIANS_PER_PIXEL: f32 = 0.005;
+//dragging stops short of the poles, the camera would flip over them
+const MAX_ELEVATION_DEGREES: f32 = 89.0;
+//a drag is applied in steps small enough not to jump over a pole between two clamps
+const MAX_DRAG_STEP_DEGREES: f32 = 0.5;
+
+/// How much the camera smooths the position of the locked body, corrections of the dead reckoning would shake it otherwise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraSmoothing {
+    /// In real seconds, at `full_speed` and faster
+    pub time_constant: f32,
+    /// Slower simulations move the bodies less per frame and get a proportionally shorter time constant, so the
+    /// camera doesn't lag behind at normal speeds
+    pub full_speed: f32
+}
+
+impl Default for CameraSmoothing {
+    fn default() -> Self {
+        Self { time_constant: 0.25, full_speed: 100.0 }
+    }
+}
+
+impl CameraSmoothing {
+    pub fn time_constant_at(&self, simulation_speed: f32) -> f32 {
+        self.time_constant * (simulation_speed.abs() / self.full_speed).min(1.0)
+    }
+}
+
+/// Exponential moving average of a position, independent of the frame rate. After a step of the input the output
+/// approaches it monotonically, never overshooting, and covers 95% of the step in 3 time constants
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TargetSmoother {
+    value: Option<Vec3>
+}
+
+impl TargetSmoother {
+    pub fn value(&self) -> Option<Vec3> {
+        self.value
+    }
+
+    /// Next smoothed position, a time constant of 0 passes the input through
+    pub fn update(&mut self, input: Vec3, dt: f32, time_constant: f32) -> Vec3 {
+        let value = match self.value {
+            Some(value) if time_constant > 0.0 => value.lerp(input, 1.0 - (-dt / time_constant).exp()),
+            _ => input
+        };
+        self.value = Some(value);
+        value
+    }
+
+    /// The next update starts from its input
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Camera of an overlay scene (like the HUD axes), systems working with the game camera skip it
+#[derive(Component, Debug, Default)]
+pub struct OverlayCamera;
+
+/// Vertical field of view of the game camera in degrees, narrowing it zooms without moving the camera
+#[derive(Resource, Debug, Clone)]
+pub struct CameraFov {
+    pub degrees: f32,
+    pub min_degrees: f32,
+    pub max_degrees: f32
+}
+
+impl Default for CameraFov {
+    fn default() -> Self {
+        Self { degrees: 60.0, min_degrees: 1.0, max_degrees: 120.0 }
+    }
+}
+
+impl CameraFov {
+    pub fn radians(&self) -> f32 {
+        self.degrees.to_radians()
+    }
+
+    //multiplicative, so steps feel the same at binocular and wide angles
+    pub fn narrow(&mut self, factor: f32) {
+        self.degrees = (self.degrees / factor).max(self.min_degrees);
+    }
+
+    pub fn widen(&mut self, factor: f32) {
+        self.degrees = (self.degrees * factor).min(self.max_degrees);
+    }
+
+    pub fn set(&mut self, degrees: f32) {
+        self.degrees = degrees.clamp(self.min_degrees, self.max_degrees);
+    }
+}
+
+/// Placement and field of view of the game camera, to put it back exactly where it was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraBookmark {
+    pub transform: Transform,
+    pub fov_degrees: f32
+}
+
+impl CameraBookmark {
+    pub fn capture(transform: &Transform, fov: &CameraFov) -> Self {
+        Self { transform: *transform, fov_degrees: fov.degrees }
+    }
+
+    pub fn restore(&self, transform: &mut Transform, fov: &mut CameraFov) {
+        *transform = self.transform;
+        fov.set(self.fov_degrees);
+    }
+}
+
+pub struct CameraFovPlugin;
+
+impl Plugin for CameraFovPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CameraFov>()
+            .register_command(
+                CommandDescriptor::new("Set field of view", ActionCategory::Camera, |params, world| {
+                    if let Some(degrees) = params[0].as_number() {
+                        world.resource_mut::<CameraFov>().set(degrees as f32);
+                    }
+                })
+                .with_param("degrees", ParamKind::Number)
+            )
+            .add_systems(Update, apply_camera_fov.run_if(resource_changed::<CameraFov>));
+    }
+}
+
+fn apply_camera_fov(fov: Res<CameraFov>, mut projections: Query<&mut Projection, (With<Camera>, Without<OverlayCamera>)>) {
+    for mut projection in projections.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov.radians();
+        }
+    }
 }
 
 #[derive(Default, Clone)]
@@ -27,6 +197,60 @@ impl <I: Debug> CameraLock<I> {
         self.lock_transform = transform;
         self.is_default = is_default;
         self.is_locked = false;
+        self.track = None;
+        self.target.reset();
+        self.orbital = Quat::IDENTITY;
+    }
+
+    pub fn set_mode(&mut self, mode: CameraLockMode) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.is_locked = false;
+            self.orbital = Quat::IDENTITY;
+        }
+    }
+
+    /// Yaw around the up axis of the lock and pitch around the right axis of the camera for a drag of `delta` pixels
+    pub fn drag_rotation(&self, delta: Vec2, camera: &Transform) -> Quat {
+        let yaw = Quat::from_axis_angle(self.up_axis(), -delta.x * DRAG_RADIANS_PER_PIXEL);
+        let pitch = Quat::from_axis_angle(*camera.right(), -delta.y * DRAG_RADIANS_PER_PIXEL);
+        yaw * pitch
+    }
+
+    /// Updates the locked body position, its velocity is estimated from the previous one `dt` seconds ago.
+    /// Once locked the camera follows a smoothed position, the time constant depends on the simulation speed
+    pub fn follow(&mut self, transform: Transform, dt: f32, simulation_speed: f32) {
+        //a transition moves the camera gradually anyway, the smoothing would only delay it
+        if !self.is_locked {
+            self.target.reset();
+        }
+        let translation = self.target.update(transform.translation, dt, self.smoothing.time_constant_at(simulation_speed));
+        let displacement = translation - self.lock_transform.translation;
+        self.lock_transform = Transform { translation, ..transform };
+        //a paused simulation keeps the last direction
+        let Some(direction) = (dt > 0.0).then(|| displacement.try_normalize()).flatten() else {
+            return;
+        };
+        self.track = Some(match self.track {
+            Some(track) => track.lerp(direction, (dt * TRACK_SMOOTHING).min(1.0)).try_normalize().unwrap_or(direction),
+            None => direction
+        });
+    }
+
+    /// The body moved discontinuously (a time jump), the camera goes straight to its next position
+    pub fn skip_smoothing(&mut self) {
+        self.target.reset();
+        self.track = None;
+    }
+
+    //velocity direction and orbit normal, once the body moved
+    fn chase_frame(&self) -> Option<(Vec3, Vec3)> {
+        if self.mode != CameraLockMode::Chase || self.is_default {
+            return None;
+        }
+        let track = self.track?;
+        let normal = self.lock_transform.translation.cross(track).try_normalize()?;
+        Some((track, normal))
     }
 
     pub fn zoom_in(&mut self, by_step: f32, min: f32) {
@@ -41,14 +265,51 @@ impl <I: Debug> CameraLock<I> {
         self.distance = distance.min(max);
     }
 
-    pub fn move_towards_lock(&mut self, settings: &StaticLockSettings, location: &mut Transform, dt: f32) {
+    //what the camera is placed around and looks at, the planet for the default lock
+    fn lock_origin(&self) -> Vec3 {
+        if self.is_default { Vec3::ZERO } else { self.lock_transform.translation }
+    }
+
+    fn up_axis(&self) -> Vec3 {
+        match self.chase_frame() {
+            Some((_, normal)) => normal,
+            None if self.is_default => WORLD_FRAME.overview(1.0).1,
+            None => WORLD_FRAME.north
+        }
+    }
+
+    /// `orbital_delta` (of a mouse drag) rotates the camera further around the lock origin, up to 89° above or below
+    /// its horizon
+    pub fn move_towards_lock(&mut self, settings: &StaticLockSettings, location: &mut Transform, dt: f32, orbital_delta: Option<Quat>) {
         const SPEED: f32 = 1.0;
-        let target_location = if self.lock_transform.translation.length() < 0.1 || self.is_default {
+        let chase_frame = self.chase_frame();
+        let target_location = if let Some((track, _)) = chase_frame {
+            self.lock_transform.translation - track * self.distance
+        } else if self.lock_transform.translation.length() < 0.1 || self.is_default {
             settings.default_orientation * self.distance
         } else {
             let lock_translation = self.lock_transform.translation;
             lock_translation + lock_translation.normalize() * self.distance
         };
+        let origin = self.lock_origin();
+        let offset = target_location - origin;
+        if let Some(delta) = orbital_delta {
+            let up = self.up_axis();
+            //by the axis and the angle, a drag of more than half a turn doesn't take the shorter way
+            let (axis, angle) = delta.to_axis_angle();
+            let steps = (angle.abs() / MAX_DRAG_STEP_DEGREES.to_radians()).ceil().max(1.0) as u32;
+            let step = Quat::from_axis_angle(axis, angle / steps as f32);
+            for _ in 0..steps {
+                self.orbital = clamp_elevation(step * self.orbital, self.orbital, offset, up);
+            }
+        }
+        let rotated = self.orbital != Quat::IDENTITY;
+        let target_location = if rotated {
+            let distance = offset.length().clamp(settings.distance_min, settings.distance_max);
+            origin + (self.orbital * offset).normalize_or_zero() * distance
+        } else {
+            target_location
+        };
 
         if self.is_locked {
             location.translation = target_location;
@@ -56,7 +317,8 @@ impl <I: Debug> CameraLock<I> {
             let transfer_vector = target_location - location.translation;
             let speed = SPEED * settings.distance_max / transfer_vector.length() + 0.1;
             let change = transfer_vector * speed * dt;
-            if transfer_vector.length() < settings.tolerance {
+            //a step past the target would overshoot, moving targets could be chased forever
+            if transfer_vector.length() < settings.tolerance.max(change.length()) {
                 self.is_locked = true;
                 info!("Locking onto {:?}", self);
                 location.translation = target_location;
@@ -66,13 +328,21 @@ impl <I: Debug> CameraLock<I> {
         }
 
         
-        self.rotate_to_position(target_location, &mut location.rotation, dt);
+        let target_rotation = match chase_frame {
+            _ if rotated => Transform::from_translation(target_location).looking_at(origin, self.up_axis()).rotation,
+            Some((track, normal)) => Transform::default().looking_to(track, normal).rotation,
+            None => self.radial_rotation(target_location)
+        };
+        self.rotate_to(target_rotation, &mut location.rotation, dt);
+    }
+
+    //default rotation is looking at the planet through the satelite, north up unless looking along it
+    fn radial_rotation(&self, target_location: Vec3) -> Quat {
+        let up_vector = if self.is_default { WORLD_FRAME.overview(1.0).1 } else { WORLD_FRAME.north };
+        Transform::from_translation(target_location).looking_at(Vec3::ZERO, up_vector).rotation
     }
 
-    //default rotation is looking at the planet through the satelite
-    fn rotate_to_position(&mut self, target_location: Vec3, rotation: &mut Quat, dt: f32) {
-        let up_vector = if self.is_default { Vec3::X } else { Vec3::Z };
-        let target_rotation = Transform::from_translation(target_location).looking_at(Vec3::ZERO, up_vector).rotation;
+    fn rotate_to(&mut self, target_rotation: Quat, rotation: &mut Quat, dt: f32) {
         if self.is_locked {
             *rotation = target_rotation;
         } else {
@@ -81,4 +351,180 @@ impl <I: Debug> CameraLock<I> {
         }
     }
 
-}
\ No newline at end of file
+}
+
+//pulls the rotated `offset` back to the maximal elevation above or below the plane perpendicular to `up`. The azimuth
+//is the one of the `previous` rotation, close to the pole the new one would swing around with every step
+fn clamp_elevation(rotation: Quat, previous: Quat, offset: Vec3, up: Vec3) -> Quat {
+    let Some(direction) = (rotation * offset).try_normalize() else {
+        return rotation;
+    };
+    let max = MAX_ELEVATION_DEGREES.to_radians();
+    let elevation = direction.dot(up).clamp(-1.0, 1.0).asin();
+    if elevation.abs() <= max {
+        return rotation;
+    }
+    let horizontal = |direction: Vec3| (direction - up * direction.dot(up)).try_normalize();
+    let horizontal = horizontal(previous * offset).or_else(|| horizontal(direction)).unwrap_or_else(|| up.any_orthonormal_vector());
+    let clamped = horizontal * max.cos() + up * max.sin().copysign(elevation);
+    (Quat::from_rotation_arc(direction, clamped) * rotation).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_chase_camera_stays_behind_moving_satellite() {
+        let settings = StaticLockSettings { distance_min: 10.0, distance_max: 700.0, default_orientation: Vec3::Z, tolerance: 1.0 };
+        //circular orbit in an inclined plane, 200 units radius, a revolution every 60 s
+        let plane = Quat::from_rotation_x(0.9);
+        let position = |t: f32| plane * Vec3::new(200.0 * (t * std::f32::consts::TAU / 60.0).cos(), 200.0 * (t * std::f32::consts::TAU / 60.0).sin(), 0.0);
+        let mut lock = CameraLock { distance: 50.0, ..default() };
+        lock.lock_on((), Transform::from_translation(position(0.0)), false);
+        lock.set_mode(CameraLockMode::Chase);
+        let mut camera = Transform::from_xyz(0.0, 0.0, 500.0);
+
+        let dt = 1.0 / 60.0;
+        for frame in 1..=600 {
+            let t = frame as f32 * dt;
+            lock.follow(Transform::from_translation(position(t)), dt, 1.0);
+            lock.move_towards_lock(&settings, &mut camera, dt, None);
+            if frame < 300 {
+                continue;
+            }
+            assert!(lock.is_locked);
+            let satellite = position(t);
+            let velocity = (position(t + 1e-3) - position(t - 1e-3)).normalize();
+            let offset = camera.translation - satellite;
+            //behind along the velocity, at the lock distance
+            assert!(offset.normalize().dot(velocity) < -0.99, "frame {frame}: {offset:?} {velocity:?}");
+            assert_abs_diff_eq!(offset.length(), 50.0, epsilon = 1.0);
+            assert!(camera.forward().dot(velocity) > 0.99);
+            assert!(camera.up().dot(plane * Vec3::Z) > 0.99);
+        }
+
+        //back to the radial lock
+        lock.set_mode(CameraLockMode::Radial);
+        assert!(!lock.is_locked);
+    }
+
+    #[test]
+    fn test_smoother_approaches_a_step_without_overshoot() {
+        let mut smoother = TargetSmoother::default();
+        assert_eq!(smoother.update(Vec3::ZERO, 0.1, 0.5), Vec3::ZERO);
+        let step = Vec3::new(10.0, -4.0, 2.0);
+        let mut previous = 0.0;
+        for frame in 1..=90 {
+            let progress = smoother.update(step, 1.0 / 60.0, 0.5).dot(step) / step.length_squared();
+            assert!(progress > previous && progress < 1.0, "frame {frame}: {progress}");
+            previous = progress;
+        }
+        //1.5 s are 3 time constants, regardless of the frame rate
+        assert_abs_diff_eq!(previous, 1.0 - (-3.0f32).exp(), epsilon = 1e-4);
+        let mut coarse = TargetSmoother::default();
+        coarse.update(Vec3::ZERO, 0.1, 0.5);
+        assert!(coarse.update(step, 1.5, 0.5).distance(smoother.value().unwrap()) < 1e-3);
+
+        assert_eq!(smoother.update(-step, 0.1, 0.0), -step);
+        smoother.reset();
+        assert_eq!(smoother.update(step, 0.1, 0.5), step);
+    }
+
+    #[test]
+    fn test_locked_camera_smooths_position_steps() {
+        let settings = StaticLockSettings { distance_min: 10.0, distance_max: 700.0, default_orientation: Vec3::Z, tolerance: 1.0 };
+        let smoothing = CameraSmoothing::default();
+        let mut lock = CameraLock { distance: 50.0, smoothing, ..default() };
+        let satellite = Vec3::new(200.0, 0.0, 0.0);
+        lock.lock_on((), Transform::from_translation(satellite), false);
+        let mut camera = Transform::from_xyz(0.0, 0.0, 500.0);
+        let dt = 1.0 / 60.0;
+        //the transition isn't delayed by the smoothing
+        for _ in 0..600 {
+            lock.follow(Transform::from_translation(satellite), dt, 1000.0);
+            lock.move_towards_lock(&settings, &mut camera, dt, None);
+        }
+        assert!(lock.is_locked);
+        assert_eq!(lock.lock_transform.translation, satellite);
+
+        //a correction of the dead reckoning moves the body 5 units at once
+        let corrected = satellite + Vec3::new(0.0, 5.0, 0.0);
+        let frames = (3.0 * smoothing.time_constant / dt).ceil() as usize;
+        let mut previous = 0.0;
+        for _ in 0..frames {
+            lock.follow(Transform::from_translation(corrected), dt, 1000.0);
+            lock.move_towards_lock(&settings, &mut camera, dt, None);
+            let progress = lock.lock_transform.translation.y / 5.0;
+            assert!(progress > previous && progress < 1.0);
+            let target = lock.lock_transform.translation;
+            assert!(camera.translation.distance(target + target.normalize() * 50.0) < 1e-3);
+            previous = progress;
+        }
+        assert!(previous > 0.95);
+
+        //at normal speed the camera keeps up right away
+        lock.follow(Transform::from_translation(satellite), dt, 1.0);
+        assert!(lock.lock_transform.translation.distance(satellite) < 0.01);
+
+        //time jumps skip the smoothing
+        let jumped = Vec3::new(0.0, 200.0, 0.0);
+        lock.skip_smoothing();
+        lock.follow(Transform::from_translation(jumped), dt, 1000.0);
+        assert_eq!(lock.lock_transform.translation, jumped);
+    }
+
+    #[test]
+    fn test_drag_orbits_around_the_lock() {
+        let settings = StaticLockSettings { distance_min: 10.0, distance_max: 700.0, default_orientation: Vec3::Z, tolerance: 1.0 };
+        let mut lock = CameraLock { distance: 300.0, is_default: true, is_locked: true, ..default() };
+        let mut camera = Transform::default();
+        lock.move_towards_lock(&settings, &mut camera, 0.1, None);
+        let initial = camera.translation;
+        assert_eq!(initial, Vec3::Z * 300.0);
+
+        //a quarter turn to the side
+        let delta = lock.drag_rotation(Vec2::new(std::f32::consts::FRAC_PI_2 / DRAG_RADIANS_PER_PIXEL, 0.0), &camera);
+        lock.move_towards_lock(&settings, &mut camera, 0.1, Some(delta));
+        assert_abs_diff_eq!(camera.translation.normalize().dot(initial.normalize()), 0.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(camera.translation.length(), 300.0, epsilon = 1e-3);
+        assert!(camera.forward().dot(-camera.translation.normalize()) > 0.999);
+
+        //dragging far past the pole stops just short of it
+        let delta = lock.drag_rotation(Vec2::new(0.0, 1000.0), &camera);
+        lock.move_towards_lock(&settings, &mut camera, 0.1, Some(delta));
+        let elevation = camera.translation.normalize().dot(lock.up_axis()).asin().to_degrees().abs();
+        assert_abs_diff_eq!(elevation, 89.0, epsilon = 1e-2);
+        assert!(camera.translation.is_finite());
+
+        //locking onto something else starts from its placement again
+        lock.lock_on((), Transform::default(), true);
+        lock.move_towards_lock(&settings, &mut camera, 0.1, None);
+        assert_eq!(lock.orbital, Quat::IDENTITY);
+    }
+
+    #[test]
+    fn test_fov_resource_updates_projection() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, CameraFovPlugin));
+        let camera = app.world_mut().spawn((Camera::default(), Projection::Perspective(PerspectiveProjection::default()))).id();
+        app.update();
+
+        let fov = |app: &App| match app.world().get::<Projection>(camera).unwrap() {
+            Projection::Perspective(perspective) => perspective.fov,
+            Projection::Orthographic(_) => panic!("perspective projection expected")
+        };
+        assert_abs_diff_eq!(fov(&app), 60.0f32.to_radians(), epsilon = 1e-6);
+
+        app.world_mut().resource_mut::<CameraFov>().narrow(2.0);
+        app.update();
+        assert_abs_diff_eq!(fov(&app), 30.0f32.to_radians(), epsilon = 1e-6);
+
+        app.world_mut().resource_mut::<CameraFov>().widen(100.0);
+        app.update();
+        assert_abs_diff_eq!(fov(&app), 120.0f32.to_radians(), epsilon = 1e-6);
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::{
+    color::palettes::css::*,
+    input::{keyboard::{Key, KeyboardInput}, ButtonState},
+    prelude::*
+};
+
+use crate::commands::{CommandDescriptor, CommandRegistry, InvokeCommand, ParamKind};
+use crate::input::{Action, ActionTriggered, InputCapture, InputMap};
+
+//matches listed at once, the query narrows them down
+const MAX_MATCHES: usize = 10;
+
+/// Searchable list of every registered command, opened with [`Action::OpenCommandPalette`]
+pub struct CommandPalettePlugin;
+
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct CommandPalette {
+    pub open: bool,
+    pub query: String,
+    /// Index into the matches of the query
+    pub highlighted: usize,
+    prompt: Option<Prompt>,
+    /// Why the last prompt input was rejected
+    pub error: Option<String>
+}
+
+//collects the arguments of the chosen command one parameter at a time
+#[derive(Debug, Clone, PartialEq)]
+struct Prompt {
+    command: String,
+    arguments: Vec<String>,
+    input: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteInput {
+    Text(String),
+    Backspace,
+    Up,
+    Down,
+    Confirm,
+    Cancel
+}
+
+impl CommandPalette {
+    pub fn open(&mut self) {
+        *self = Self { open: true, ..default() };
+    }
+
+    /// Opened straight at the parameter prompt of the command
+    pub fn open_prompt(&mut self, command: impl Into<String>) {
+        *self = Self { open: true, prompt: Some(Prompt { command: command.into(), arguments: vec![], input: String::new() }), ..default() };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn matches<'a>(&self, registry: &'a CommandRegistry) -> Vec<&'a CommandDescriptor> {
+        let mut matches = registry.search(&self.query);
+        matches.truncate(MAX_MATCHES);
+        matches
+    }
+
+    /// Name and type of the parameter being prompted for, with the input so far
+    pub fn prompt_line(&self, registry: &CommandRegistry) -> Option<String> {
+        let prompt = self.prompt.as_ref()?;
+        let spec = registry.get(&prompt.command)?.params.get(prompt.arguments.len())?;
+        let kind = match spec.kind {
+            ParamKind::Text => "text",
+            ParamKind::Number => "number",
+            ParamKind::DateTime => "YYYY-MM-DD[THH:MM[:SS]] UTC",
+            ParamKind::Path => "file path"
+        };
+        Some(format!("{} > {} ({kind}): {}", prompt.command, spec.name, prompt.input))
+    }
+
+    /// Applies a key of the user, returns the command to invoke once it's chosen with all its arguments
+    pub fn handle(&mut self, input: PaletteInput, registry: &CommandRegistry) -> Option<InvokeCommand> {
+        let edited = match &mut self.prompt {
+            Some(prompt) => &mut prompt.input,
+            None => &mut self.query
+        };
+        match input {
+            PaletteInput::Text(text) => {
+                edited.push_str(&text);
+                self.highlighted = 0;
+                self.error = None;
+            },
+            PaletteInput::Backspace => {
+                edited.pop();
+                self.highlighted = 0;
+            },
+            PaletteInput::Up | PaletteInput::Down => {
+                let count = self.matches(registry).len().max(1);
+                let step = if input == PaletteInput::Up { count - 1 } else { 1 };
+                self.highlighted = (self.highlighted + step) % count;
+            },
+            PaletteInput::Cancel if self.prompt.is_some() => {
+                self.prompt = None;
+                self.error = None;
+            },
+            PaletteInput::Cancel => self.close(),
+            PaletteInput::Confirm => return self.confirm(registry)
+        }
+        None
+    }
+
+    fn confirm(&mut self, registry: &CommandRegistry) -> Option<InvokeCommand> {
+        let prompt = match self.prompt.take() {
+            Some(mut prompt) => {
+                let spec = registry.get(&prompt.command)?.params.get(prompt.arguments.len())?;
+                if let Err(err) = spec.kind.parse(spec.name, &prompt.input) {
+                    self.error = Some(format!("{err:?}"));
+                    self.prompt = Some(prompt);
+                    return None;
+                }
+                prompt.arguments.push(std::mem::take(&mut prompt.input));
+                prompt
+            },
+            None => {
+                let descriptor = self.matches(registry).get(self.highlighted).copied()?;
+                Prompt { command: descriptor.name.clone(), arguments: vec![], input: String::new() }
+            }
+        };
+        self.error = None;
+        if prompt.arguments.len() < registry.get(&prompt.command)?.params.len() {
+            self.prompt = Some(prompt);
+            return None;
+        }
+        self.close();
+        Some(InvokeCommand { name: prompt.command, arguments: prompt.arguments })
+    }
+}
+
+#[derive(Component)]
+struct CommandPaletteNode;
+
+impl Plugin for CommandPalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CommandPalette>()
+            .init_resource::<CommandRegistry>()
+            .init_resource::<InputMap>()
+            .init_resource::<InputCapture>()
+            .add_event::<ActionTriggered>()
+            .add_event::<InvokeCommand>()
+            .add_systems(Update, (
+                open_command_palette,
+                command_palette_keyboard.run_if(resource_exists::<Events<KeyboardInput>>),
+                capture_keyboard
+            ).chain())
+            .add_systems(Update, render_command_palette.after(capture_keyboard).run_if(resource_changed::<CommandPalette>));
+    }
+}
+
+fn open_command_palette(mut actions: EventReader<ActionTriggered>, mut palette: ResMut<CommandPalette>) {
+    if actions.read().any(|ActionTriggered(action)| *action == Action::OpenCommandPalette) && !palette.open {
+        palette.open();
+    }
+}
+
+fn command_palette_keyboard(
+    mut keyboard: EventReader<KeyboardInput>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    registry: Res<CommandRegistry>,
+    mut palette: ResMut<CommandPalette>,
+    mut invocations: EventWriter<InvokeCommand>
+) {
+    if !palette.open {
+        keyboard.clear();
+        return;
+    }
+    //the key of a shortcut (the P of Ctrl+P) isn't typed
+    let ctrl = keys.is_some_and(|keys| keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]));
+    for event in keyboard.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        let input = match &event.logical_key {
+            Key::Enter => PaletteInput::Confirm,
+            Key::Escape => PaletteInput::Cancel,
+            Key::ArrowUp => PaletteInput::Up,
+            Key::ArrowDown => PaletteInput::Down,
+            Key::Backspace => PaletteInput::Backspace,
+            Key::Space if !ctrl => PaletteInput::Text(" ".to_owned()),
+            Key::Character(text) if !ctrl => PaletteInput::Text(text.to_string()),
+            _ => continue
+        };
+        if let Some(invocation) = palette.handle(input, &registry) {
+            invocations.send(invocation);
+        }
+        if !palette.open {
+            break;
+        }
+    }
+}
+
+//key bindings stay quiet while typing
+fn capture_keyboard(palette: Res<CommandPalette>, mut capture: ResMut<InputCapture>) {
+    if capture.captured != palette.open {
+        capture.captured = palette.open;
+    }
+}
+
+fn render_command_palette(
+    palette: Res<CommandPalette>,
+    registry: Res<CommandRegistry>,
+    map: Res<InputMap>,
+    nodes: Query<Entity, With<CommandPaletteNode>>,
+    mut commands: Commands
+) {
+    for entity in nodes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !palette.open {
+        return;
+    }
+
+    let style = |color: Srgba, font_size: f32| TextStyle { font_size, color: color.into(), ..default() };
+    let mut text_sections = vec![];
+    match palette.prompt_line(&registry) {
+        Some(prompt) => text_sections.push(TextSection::new(format!("{prompt}_\n"), style(GOLD, 20.0))),
+        None => {
+            text_sections.push(TextSection::new(format!("> {}_\n", palette.query), style(GOLD, 20.0)));
+            for (i, descriptor) in palette.matches(&registry).into_iter().enumerate() {
+                let keys: Vec<_> = descriptor.shortcut.into_iter().flat_map(|action| map.bindings_for(action)).map(|b| b.display()).collect();
+                let line = format!("{:<32} {:<10} {}\n", descriptor.name, format!("{:?}", descriptor.category), keys.join(" / "));
+                let color = if i == palette.highlighted { WHITE } else { GRAY };
+                text_sections.push(TextSection::new(line, style(color, 16.0)));
+            }
+        }
+    }
+    if let Some(error) = &palette.error {
+        text_sections.push(TextSection::new(format!("{error}\n"), style(RED, 16.0)));
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(40.0),
+                    left: Val::Percent(30.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.85).into(),
+                ..default()
+            },
+            CommandPaletteNode
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(text_sections));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::CommandDescriptor;
+    use crate::input::ActionCategory;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        registry.register(CommandDescriptor::new("Zoom in", ActionCategory::Camera, |_, _| {}));
+        registry.register(CommandDescriptor::new("Load group", ActionCategory::General, |_, _| {}).with_param("group", ParamKind::Text));
+        registry.register(CommandDescriptor::new("Set simulation speed", ActionCategory::Time, |_, _| {}).with_param("speed", ParamKind::Number));
+        registry
+    }
+
+    fn type_text(palette: &mut CommandPalette, registry: &CommandRegistry, text: &str) {
+        for c in text.chars() {
+            assert_eq!(palette.handle(PaletteInput::Text(c.to_string()), registry), None);
+        }
+    }
+
+    #[test]
+    fn test_navigation_and_parameter_prompts() {
+        let registry = registry();
+        let mut palette = CommandPalette::default();
+        palette.open();
+        assert_eq!(palette.matches(&registry).len(), 3);
+        palette.handle(PaletteInput::Up, &registry);
+        assert_eq!(palette.highlighted, 2);
+
+        type_text(&mut palette, &registry, "speed");
+        assert_eq!(palette.highlighted, 0);
+        assert_eq!(palette.matches(&registry)[0].name, "Set simulation speed");
+        assert_eq!(palette.handle(PaletteInput::Confirm, &registry), None);
+        assert_eq!(palette.prompt_line(&registry).unwrap(), "Set simulation speed > speed (number): ");
+
+        //rejected input stays for correction
+        type_text(&mut palette, &registry, "fast");
+        assert_eq!(palette.handle(PaletteInput::Confirm, &registry), None);
+        assert!(palette.error.is_some());
+        for _ in 0..4 {
+            palette.handle(PaletteInput::Backspace, &registry);
+        }
+        type_text(&mut palette, &registry, "500");
+        assert_eq!(
+            palette.handle(PaletteInput::Confirm, &registry),
+            Some(InvokeCommand { name: "Set simulation speed".to_owned(), arguments: vec!["500".to_owned()] })
+        );
+        assert_eq!(palette, CommandPalette::default());
+    }
+
+    #[test]
+    fn test_cancel_leaves_prompt_then_palette() {
+        let registry = registry();
+        let mut palette = CommandPalette::default();
+        palette.open();
+        type_text(&mut palette, &registry, "load");
+        palette.handle(PaletteInput::Confirm, &registry);
+        assert!(palette.prompt_line(&registry).is_some());
+        palette.handle(PaletteInput::Cancel, &registry);
+        assert!(palette.open && palette.prompt_line(&registry).is_none());
+        assert_eq!(palette.query, "load");
+        palette.handle(PaletteInput::Cancel, &registry);
+        assert!(!palette.open);
+        //commands without parameters run right away
+        palette.open();
+        type_text(&mut palette, &registry, "zi");
+        assert_eq!(palette.handle(PaletteInput::Confirm, &registry), Some(InvokeCommand { name: "Zoom in".to_owned(), arguments: vec![] }));
+    }
+}
@@ -0,0 +1,387 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use crate::input::{Action, ActionCategory, ActionTriggered};
+use crate::simtime::{days_from_civil, days_in_month};
+
+/// Type of a command parameter, how the palette prompt input is parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+    Text,
+    Number,
+    /// UTC, `YYYY-MM-DD` optionally followed by `THH:MM` or `THH:MM:SS` (a space works as well)
+    DateTime,
+    Path
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: ParamKind
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    Text(String),
+    Number(f64),
+    DateTime(SystemTime),
+    Path(PathBuf)
+}
+
+impl ParamValue {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ParamValue::Text(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            ParamValue::Number(number) => Some(*number),
+            _ => None
+        }
+    }
+
+    pub fn as_date_time(&self) -> Option<SystemTime> {
+        match self {
+            ParamValue::DateTime(time) => Some(*time),
+            _ => None
+        }
+    }
+
+    pub fn as_path(&self) -> Option<&PathBuf> {
+        match self {
+            ParamValue::Path(path) => Some(path),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    MissingParameter(&'static str),
+    InvalidParameter { name: &'static str, reason: String },
+    TooManyParameters(usize)
+}
+
+impl ParamKind {
+    pub fn parse(&self, name: &'static str, input: &str) -> Result<ParamValue, CommandError> {
+        let input = input.trim();
+        let invalid = |reason: &str| CommandError::InvalidParameter { name, reason: reason.to_owned() };
+        if input.is_empty() {
+            return Err(CommandError::MissingParameter(name));
+        }
+        match self {
+            ParamKind::Text => Ok(ParamValue::Text(input.to_owned())),
+            ParamKind::Number => input.parse::<f64>().ok().filter(|n| n.is_finite()).map(ParamValue::Number).ok_or_else(|| invalid("not a number")),
+            ParamKind::DateTime => parse_date_time(input).map(ParamValue::DateTime).ok_or_else(|| invalid("expected YYYY-MM-DD[THH:MM[:SS]]")),
+            ParamKind::Path => Ok(ParamValue::Path(PathBuf::from(input)))
+        }
+    }
+}
+
+/// Parses a UTC date with an optional time of day, see [`ParamKind::DateTime`]
+pub fn parse_date_time(input: &str) -> Option<SystemTime> {
+    let (date, time) = match input.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (input, None)
+    };
+    let mut date_parts = date.split('-');
+    let (year, month, day): (i64, u32, u32) = (date_parts.next()?.parse().ok()?, date_parts.next()?.parse().ok()?, date_parts.next()?.parse().ok()?);
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let seconds_of_day = match time {
+        Some(time) => {
+            let parts: Vec<u32> = time.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+            let (hours, minutes, seconds) = match parts[..] {
+                [hours, minutes] => (hours, minutes, 0),
+                [hours, minutes, seconds] => (hours, minutes, seconds),
+                _ => return None
+            };
+            if hours > 23 || minutes > 59 || seconds > 59 {
+                return None;
+            }
+            (hours * 3600 + minutes * 60 + seconds) as u64
+        },
+        None => 0
+    };
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + seconds_of_day))
+}
+
+/// Subsequence match of the query in the candidate, ignoring case and spaces of the query.
+/// Consecutive matches and matches at word starts score higher, `None` when not every character is found
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_lowercase()).collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, c) in candidate.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if c.to_ascii_lowercase() != query[matched] {
+            continue;
+        }
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        //skipped characters since the previous match, or before the first one
+        score -= (i - previous.map_or(0, |p| p + 1)).min(3) as i32;
+        previous = Some(i);
+        matched += 1;
+    }
+    (matched == query.len()).then_some(score)
+}
+
+pub type CommandHandler = Arc<dyn Fn(&[ParamValue], &mut World) + Send + Sync>;
+
+/// Something the app can do, invoked with its parsed parameters
+#[derive(Clone)]
+pub struct CommandDescriptor {
+    pub name: String,
+    pub category: ActionCategory,
+    pub params: Vec<ParamSpec>,
+    /// Action whose key bindings invoke the command
+    pub shortcut: Option<Action>,
+    /// Acts on the selected satellites, offered in their context menu
+    pub satellite_scoped: bool,
+    handler: CommandHandler
+}
+
+impl std::fmt::Debug for CommandDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandDescriptor").field("name", &self.name).field("category", &self.category).field("params", &self.params).finish()
+    }
+}
+
+impl CommandDescriptor {
+    pub fn new(name: impl Into<String>, category: ActionCategory, handler: impl Fn(&[ParamValue], &mut World) + Send + Sync + 'static) -> Self {
+        Self { name: name.into(), category, params: vec![], shortcut: None, satellite_scoped: false, handler: Arc::new(handler) }
+    }
+
+    /// Command of a bound action, triggers it like its keys do
+    pub fn action(action: Action) -> Self {
+        let mut descriptor = Self::new(action.label(), action.category(), move |_, world| { world.send_event(ActionTriggered(action)); });
+        descriptor.shortcut = Some(action);
+        descriptor.satellite_scoped = action.category() == ActionCategory::Selection;
+        descriptor
+    }
+
+    /// Command sending the event built from its parameters
+    pub fn event<E: Event>(name: impl Into<String>, category: ActionCategory, build: impl Fn(&[ParamValue]) -> E + Send + Sync + 'static) -> Self {
+        Self::new(name, category, move |params, world| { world.send_event(build(params)); })
+    }
+
+    pub fn satellite_scoped(mut self) -> Self {
+        self.satellite_scoped = true;
+        self
+    }
+
+    pub fn with_param(mut self, name: &'static str, kind: ParamKind) -> Self {
+        self.params.push(ParamSpec { name, kind });
+        self
+    }
+
+    pub fn parse_arguments(&self, arguments: &[String]) -> Result<Vec<ParamValue>, CommandError> {
+        if arguments.len() > self.params.len() {
+            return Err(CommandError::TooManyParameters(arguments.len()));
+        }
+        self.params.iter().enumerate()
+            .map(|(i, spec)| match arguments.get(i) {
+                Some(argument) => spec.kind.parse(spec.name, argument),
+                None => Err(CommandError::MissingParameter(spec.name))
+            })
+            .collect()
+    }
+}
+
+/// Every command of the app, features register theirs with [`RegisterCommand::register_command`]
+#[derive(Resource, Default, Clone, Debug)]
+pub struct CommandRegistry {
+    commands: Vec<CommandDescriptor>
+}
+
+impl CommandRegistry {
+    /// Replaces a command of the same name
+    pub fn register(&mut self, descriptor: CommandDescriptor) {
+        match self.commands.iter_mut().find(|c| c.name == descriptor.name) {
+            Some(existing) => *existing = descriptor,
+            None => self.commands.push(descriptor)
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandDescriptor> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandDescriptor> {
+        self.commands.iter()
+    }
+
+    /// Commands matching the query, best first, ties in registration order
+    pub fn search(&self, query: &str) -> Vec<&CommandDescriptor> {
+        let mut matches: Vec<_> = self.commands.iter().filter_map(|c| fuzzy_score(query, &c.name).map(|score| (score, c))).collect();
+        matches.sort_by_key(|(score, _)| -score);
+        matches.into_iter().map(|(_, c)| c).collect()
+    }
+
+    pub fn invoke(&self, invocation: &InvokeCommand, world: &mut World) -> Result<(), CommandError> {
+        let descriptor = self.get(&invocation.name).ok_or_else(|| CommandError::UnknownCommand(invocation.name.clone()))?;
+        let values = descriptor.parse_arguments(&invocation.arguments)?;
+        (descriptor.handler)(&values, world);
+        Ok(())
+    }
+}
+
+/// Invokes the named command with unparsed arguments, in the order of its parameters
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct InvokeCommand {
+    pub name: String,
+    pub arguments: Vec<String>
+}
+
+pub trait RegisterCommand {
+    fn register_command(&mut self, descriptor: CommandDescriptor) -> &mut Self;
+}
+
+impl RegisterCommand for App {
+    fn register_command(&mut self, descriptor: CommandDescriptor) -> &mut Self {
+        self.world_mut().get_resource_or_insert_with(CommandRegistry::default).register(descriptor);
+        self
+    }
+}
+
+/// The [`CommandRegistry`] with a command for every [`Action`], runs [`InvokeCommand`]s
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CommandRegistry>()
+            .add_event::<ActionTriggered>()
+            .add_event::<InvokeCommand>()
+            .add_systems(PreUpdate, run_invoked_commands);
+        for action in Action::ALL {
+            app.register_command(CommandDescriptor::action(action));
+        }
+    }
+}
+
+fn run_invoked_commands(world: &mut World) {
+    let invocations: Vec<_> = world.resource_mut::<Events<InvokeCommand>>().drain().collect();
+    if invocations.is_empty() {
+        return;
+    }
+    //handlers get the whole world, the registry is only cheap to clone
+    let registry = world.resource::<CommandRegistry>().clone();
+    for invocation in invocations {
+        if let Err(err) = registry.invoke(&invocation, world) {
+            warn!("Command {} failed: {err:?}", invocation.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Event, Debug, Clone, PartialEq)]
+    struct Jump {
+        group: String,
+        minutes: f64
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, CommandsPlugin))
+            .add_event::<Jump>()
+            .register_command(
+                CommandDescriptor::event("Jump group ahead", ActionCategory::Time, |params| Jump {
+                    group: params[0].as_text().unwrap().to_owned(),
+                    minutes: params[1].as_number().unwrap()
+                })
+                .with_param("group", ParamKind::Text)
+                .with_param("minutes", ParamKind::Number)
+            );
+        app
+    }
+
+    fn invoke(app: &mut App, name: &str, arguments: &[&str]) {
+        app.world_mut().send_event(InvokeCommand { name: name.to_owned(), arguments: arguments.iter().map(|a| a.to_string()).collect() });
+        app.update();
+    }
+
+    fn sent<E: Event + Clone>(app: &App) -> Vec<E> {
+        let events = app.world().resource::<Events<E>>();
+        events.get_reader().read(events).cloned().collect()
+    }
+
+    #[test]
+    fn test_parameterized_command_emits_event() {
+        let mut app = app();
+        invoke(&mut app, "Jump group ahead", &["starlink", " 90 "]);
+        assert_eq!(sent::<Jump>(&app), vec![Jump { group: "starlink".to_owned(), minutes: 90.0 }]);
+
+        //invalid or missing arguments emit nothing
+        let mut app = self::app();
+        invoke(&mut app, "Jump group ahead", &["starlink", "soon"]);
+        invoke(&mut app, "Jump group ahead", &["starlink"]);
+        invoke(&mut app, "Jump nowhere", &[]);
+        assert!(sent::<Jump>(&app).is_empty());
+    }
+
+    #[test]
+    fn test_actions_are_commands() {
+        let mut app = app();
+        let registry = app.world().resource::<CommandRegistry>();
+        assert!(Action::ALL.iter().all(|action| registry.get(action.label()).is_some_and(|c| c.shortcut == Some(*action))));
+        invoke(&mut app, Action::ToggleChaseCamera.label(), &[]);
+        assert_eq!(sent::<ActionTriggered>(&app), vec![ActionTriggered(Action::ToggleChaseCamera)]);
+    }
+
+    #[test]
+    fn test_argument_parsing() {
+        let descriptor = CommandDescriptor::new("Set", ActionCategory::Time, |_, _| {})
+            .with_param("at", ParamKind::DateTime)
+            .with_param("file", ParamKind::Path);
+        let values = descriptor.parse_arguments(&["2024-03-20T03:06".to_owned(), "out/states.csv".to_owned()]).unwrap();
+        assert_eq!(values[0].as_date_time(), Some(UNIX_EPOCH + Duration::from_secs(1710903960)));
+        assert_eq!(values[1].as_path(), Some(&PathBuf::from("out/states.csv")));
+        assert_eq!(descriptor.parse_arguments(&["2024-03-20".to_owned()]), Err(CommandError::MissingParameter("file")));
+        assert_eq!(descriptor.parse_arguments(&["a".to_owned(), "b".to_owned(), "c".to_owned()]), Err(CommandError::TooManyParameters(3)));
+
+        assert_eq!(parse_date_time("1970-01-01"), Some(UNIX_EPOCH));
+        assert_eq!(parse_date_time("2000-01-01 12:00:00Z"), Some(UNIX_EPOCH + Duration::from_secs(946728000)));
+        assert_eq!(parse_date_time("2024-02-29"), Some(UNIX_EPOCH + Duration::from_secs(1709164800)));
+        for invalid in ["2023-02-29", "2024-13-01", "2024-01-01T24:00", "2024-01", "yesterday", "1969-12-31"] {
+            assert_eq!(parse_date_time(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_matching_ranks_word_starts() {
+        assert!(fuzzy_score("tch", "Toggle chase camera").is_some());
+        assert!(fuzzy_score("xyz", "Toggle chase camera").is_none());
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+
+        let registry = app().world().resource::<CommandRegistry>().clone();
+        let names = |query| registry.search(query).iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names("zoom in")[0], "Zoom in");
+        assert_eq!(names("chase")[0], "Toggle chase camera");
+        assert_eq!(names("jga")[0], "Jump group ahead");
+        assert_eq!(names("").len(), Action::ALL.len() + 1);
+    }
+}
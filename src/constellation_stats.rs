@@ -0,0 +1,484 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task}
+};
+use sgp4::Elements;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::input::ActionCategory;
+use crate::propagation::{Despawning, ElementsExt, InGameElements, SatelliteGroup};
+
+/// One-shot characterization of a loaded group: distributions of the orbit parameters of its members
+/// and the RAAN planes they are spread over, written to a report file
+pub struct ConstellationStatsPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv
+}
+
+impl ReportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv"
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct GroupAnalysisSettings {
+    /// Bins of every histogram, spread over the range of the values
+    pub bins: usize,
+    /// Members further apart in RAAN than this (in degrees) are in different planes
+    pub plane_gap_degrees: f64,
+    /// Smaller clusters aren't counted as planes, a few stragglers shouldn't add one
+    pub min_plane_members: usize,
+    pub format: ReportFormat,
+    pub directory: PathBuf,
+    /// The headline numbers are logged when the analysis completes
+    pub log_summary: bool
+}
+
+impl Default for GroupAnalysisSettings {
+    fn default() -> Self {
+        Self { bins: 20, plane_gap_degrees: 2.0, min_plane_members: 3, format: ReportFormat::Json, directory: PathBuf::from("reports"), log_summary: true }
+    }
+}
+
+/// Analyzes the satellites of the group as they are loaded now
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct AnalyzeGroup {
+    pub group: String
+}
+
+/// Sent when the analysis of a group is done
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct GroupAnalyzed {
+    pub group: String,
+    /// Written report, `None` when it couldn't be written
+    pub path: Option<PathBuf>,
+    pub statistics: OrbitStatistics
+}
+
+/// Orbit parameters taken straight from the element sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrbitQuantity {
+    SemiMajorAxis,
+    Eccentricity,
+    Inclination,
+    Raan,
+    Period
+}
+
+impl OrbitQuantity {
+    pub const ALL: [OrbitQuantity; 5] = [
+        OrbitQuantity::SemiMajorAxis, OrbitQuantity::Eccentricity, OrbitQuantity::Inclination, OrbitQuantity::Raan, OrbitQuantity::Period
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrbitQuantity::SemiMajorAxis => "semi_major_axis_km",
+            OrbitQuantity::Eccentricity => "eccentricity",
+            OrbitQuantity::Inclination => "inclination_deg",
+            OrbitQuantity::Raan => "raan_deg",
+            OrbitQuantity::Period => "period_minutes"
+        }
+    }
+
+    //angles are binned over the whole circle, the rest over the range of the values
+    fn range(&self) -> Option<(f64, f64)> {
+        match self {
+            OrbitQuantity::Raan => Some((0.0, 360.0)),
+            _ => None
+        }
+    }
+
+    pub fn value(&self, elements: &Elements) -> f64 {
+        match self {
+            OrbitQuantity::SemiMajorAxis => elements.semi_major_axis_km(),
+            OrbitQuantity::Eccentricity => elements.eccentricity,
+            OrbitQuantity::Inclination => elements.inclination,
+            OrbitQuantity::Raan => elements.right_ascension.rem_euclid(360.0),
+            OrbitQuantity::Period => elements.period_minutes()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub median: f64
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        let median = match count % 2 {
+            0 => (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0,
+            _ => sorted[count / 2]
+        };
+        Some(Self { count, min: sorted[0], max: sorted[count - 1], mean, std_dev: variance.sqrt(), median })
+    }
+}
+
+/// Equal width bins between `min` and `max`, the last bin includes `max`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<usize>
+}
+
+impl Histogram {
+    pub fn new(values: &[f64], min: f64, max: f64, bins: usize) -> Self {
+        let bins = bins.max(1);
+        let mut counts = vec![0; bins];
+        let width = (max - min) / bins as f64;
+        for value in values.iter().filter(|v| (min..=max).contains(*v)) {
+            //a single valued range puts everything in the first bin
+            let bin = if width > 0.0 { ((value - min) / width) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Self { min, max, counts }
+    }
+
+    /// Over the range of the values
+    pub fn of(values: &[f64], bins: usize) -> Self {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        match min <= max {
+            true => Self::new(values, min, max, bins),
+            false => Self::new(values, 0.0, 0.0, bins)
+        }
+    }
+
+    /// Lower and upper bound of the bin
+    pub fn bounds(&self, bin: usize) -> (f64, f64) {
+        let width = (self.max - self.min) / self.counts.len() as f64;
+        (self.min + width * bin as f64, self.min + width * (bin + 1) as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub quantity: OrbitQuantity,
+    pub summary: Summary,
+    pub histogram: Histogram
+}
+
+/// Cluster of satellites sharing the right ascension of the ascending node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    /// Circular mean of the members (in degrees, 0..360)
+    pub raan: f64,
+    pub members: usize
+}
+
+/// Planes of the RAAN values (in degrees): runs of values without a gap wider than `gap_degrees` between neighbours,
+/// going around the circle. Values spread evenly all around have no distinct planes
+pub fn detect_planes(raans: &[f64], gap_degrees: f64, min_members: usize) -> Vec<Plane> {
+    let mut sorted: Vec<f64> = raans.iter().map(|r| r.rem_euclid(360.0)).collect();
+    sorted.sort_by(f64::total_cmp);
+    //the widest gap is where the circle is cut open, the runs don't wrap after it
+    let gap_after = |i: usize| match i + 1 < sorted.len() {
+        true => sorted[i + 1] - sorted[i],
+        false => sorted[0] + 360.0 - sorted[i]
+    };
+    let Some(widest) = (0..sorted.len()).max_by(|a, b| gap_after(*a).total_cmp(&gap_after(*b))) else {
+        return vec![];
+    };
+    if gap_after(widest) <= gap_degrees {
+        return vec![];
+    }
+
+    let mut planes = vec![];
+    let mut run = vec![];
+    for offset in 1..=sorted.len() {
+        let i = (widest + offset) % sorted.len();
+        run.push(sorted[i]);
+        if gap_after(i) > gap_degrees {
+            if run.len() >= min_members {
+                planes.push(Plane { raan: circular_mean_degrees(&run), members: run.len() });
+            }
+            run.clear();
+        }
+    }
+    planes.sort_by(|a, b| a.raan.total_cmp(&b.raan));
+    planes
+}
+
+fn circular_mean_degrees(values: &[f64]) -> f64 {
+    let (sin, cos) = values.iter().fold((0.0, 0.0), |(s, c), v| (s + v.to_radians().sin(), c + v.to_radians().cos()));
+    sin.atan2(cos).to_degrees().rem_euclid(360.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitStatistics {
+    pub satellites: usize,
+    pub distributions: Vec<Distribution>,
+    pub planes: Vec<Plane>
+}
+
+impl OrbitStatistics {
+    pub fn of(elements: &[Arc<Elements>], settings: &GroupAnalysisSettings) -> Self {
+        let distributions = OrbitQuantity::ALL.iter().filter_map(|quantity| {
+            let values: Vec<f64> = elements.iter().map(|e| quantity.value(e)).filter(|v| v.is_finite()).collect();
+            let histogram = match quantity.range() {
+                Some((min, max)) => Histogram::new(&values, min, max, settings.bins),
+                None => Histogram::of(&values, settings.bins)
+            };
+            Summary::of(&values).map(|summary| Distribution { quantity: *quantity, summary, histogram })
+        }).collect();
+        let raans: Vec<f64> = elements.iter().map(|e| e.right_ascension).collect();
+        Self { satellites: elements.len(), distributions, planes: detect_planes(&raans, settings.plane_gap_degrees, settings.min_plane_members) }
+    }
+
+    pub fn distribution(&self, quantity: OrbitQuantity) -> Option<&Distribution> {
+        self.distributions.iter().find(|d| d.quantity == quantity)
+    }
+
+    fn to_json(&self, group: &str) -> serde_json::Value {
+        let quantities: serde_json::Map<_, _> = self.distributions.iter().map(|d| (d.quantity.name().to_owned(), serde_json::json!({
+            "count": d.summary.count,
+            "min": d.summary.min,
+            "max": d.summary.max,
+            "mean": d.summary.mean,
+            "std_dev": d.summary.std_dev,
+            "median": d.summary.median,
+            "histogram": { "min": d.histogram.min, "max": d.histogram.max, "counts": d.histogram.counts }
+        }))).collect();
+        serde_json::json!({
+            "group": group,
+            "satellites": self.satellites,
+            "planes": self.planes.iter().map(|p| serde_json::json!({ "raan_deg": p.raan, "members": p.members })).collect::<Vec<_>>(),
+            "quantities": quantities
+        })
+    }
+
+    //one value per row, summaries and bins of all quantities in the same table
+    fn to_csv(&self) -> String {
+        let mut csv = "quantity,statistic,lower,upper,value\n".to_owned();
+        for d in &self.distributions {
+            let name = d.quantity.name();
+            let s = d.summary;
+            for (statistic, value) in [("count", s.count as f64), ("min", s.min), ("max", s.max), ("mean", s.mean), ("std_dev", s.std_dev), ("median", s.median)] {
+                csv += &format!("{name},{statistic},,,{value}\n");
+            }
+            for (bin, count) in d.histogram.counts.iter().enumerate() {
+                let (lower, upper) = d.histogram.bounds(bin);
+                csv += &format!("{name},bin,{lower},{upper},{count}\n");
+            }
+        }
+        for plane in &self.planes {
+            csv += &format!("plane,members,{0},{0},{1}\n", plane.raan, plane.members);
+        }
+        csv
+    }
+
+    pub fn write(&self, group: &str, path: &Path, format: ReportFormat) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&self.to_json(group)).map_err(io::Error::other)?,
+            ReportFormat::Csv => self.to_csv()
+        };
+        fs::write(path, contents)
+    }
+}
+
+impl fmt::Display for OrbitStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} satellites, {} RAAN planes detected", self.satellites, self.planes.len())?;
+        if let Some(a) = self.distribution(OrbitQuantity::SemiMajorAxis) {
+            write!(f, ", a {:.0} ± {:.1} km", a.summary.mean, a.summary.std_dev)?;
+        }
+        if let Some(i) = self.distribution(OrbitQuantity::Inclination) {
+            write!(f, ", i {:.2}°", i.summary.mean)?;
+        }
+        Ok(())
+    }
+}
+
+/// Analyses running on the compute pool
+#[derive(Resource, Default)]
+pub struct GroupAnalyses(Vec<Task<GroupAnalyzed>>);
+
+impl Plugin for ConstellationStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GroupAnalysisSettings>()
+            .init_resource::<GroupAnalyses>()
+            .add_event::<AnalyzeGroup>()
+            .add_event::<GroupAnalyzed>()
+            .register_command(
+                CommandDescriptor::event("Analyze group", ActionCategory::General, |params| AnalyzeGroup {
+                    group: params[0].as_text().unwrap_or_default().to_owned()
+                })
+                .with_param("group", ParamKind::Text)
+            )
+            .add_systems(Update, (start_analyses, collect_analyses).chain());
+    }
+}
+
+//group names come from the palette, only the safe characters end up in the file name
+fn report_path(settings: &GroupAnalysisSettings, group: &str) -> PathBuf {
+    let name: String = group.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    settings.directory.join(format!("{name}-orbits.{}", settings.format.extension()))
+}
+
+fn start_analyses(
+    mut requests: EventReader<AnalyzeGroup>,
+    settings: Res<GroupAnalysisSettings>,
+    satellites: Query<(&InGameElements, &SatelliteGroup), Without<Despawning>>,
+    mut analyses: ResMut<GroupAnalyses>
+) {
+    for request in requests.read() {
+        let elements: Vec<_> = satellites.iter()
+            .filter(|(_, group)| group.0 == request.group)
+            .map(|(elements, _)| elements.0.clone())
+            .collect();
+        if elements.is_empty() {
+            warn!("No satellites of group {} to analyze", request.group);
+            continue;
+        }
+        let (group, settings) = (request.group.clone(), settings.clone());
+        analyses.0.push(AsyncComputeTaskPool::get().spawn(async move {
+            let statistics = OrbitStatistics::of(&elements, &settings);
+            let path = report_path(&settings, &group);
+            let path = match statistics.write(&group, &path, settings.format) {
+                Ok(()) => Some(path),
+                Err(e) => {
+                    warn!("Failed to write the report of group {group} to {}: {e}", path.display());
+                    None
+                }
+            };
+            GroupAnalyzed { group, path, statistics }
+        }));
+    }
+}
+
+fn collect_analyses(settings: Res<GroupAnalysisSettings>, mut analyses: ResMut<GroupAnalyses>, mut completed: EventWriter<GroupAnalyzed>) {
+    let mut finished = vec![];
+    analyses.0.retain_mut(|task| match block_on(future::poll_once(task)) {
+        Some(analyzed) => {
+            finished.push(analyzed);
+            false
+        },
+        None => true
+    });
+    for analyzed in finished {
+        if settings.log_summary {
+            info!("Group {}: {}", analyzed.group, analyzed.statistics);
+        }
+        completed.send(analyzed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::propagation::{ElementsFormat, LoadElements, LoadElementsPlugin};
+    use crate::stress::SyntheticClient;
+    use crate::test_support::MEO;
+
+    //Walker delta 24/3/1 at 55°, the nodes of the planes 120° apart and slightly scattered
+    fn walker() -> Vec<Arc<Elements>> {
+        (0..24).map(|i| {
+            let (plane, slot) = (i / 8, i % 8);
+            let raan = 120.0 * plane as f64 + 0.1 * slot as f64;
+            let mean_motion = 2.0 + 0.001 * slot as f64;
+            MEO.builder()
+                .name(format!("WALKER-{i}")).norad_id(50000 + i)
+                .mean_motion(mean_motion).eccentricity(0.001).inclination(55.0)
+                .raan(raan).arg_of_pericenter(0.0).mean_anomaly(45.0 * slot as f64 + 15.0 * plane as f64)
+                .build()
+        }).collect()
+    }
+
+    #[test]
+    fn test_walker_statistics() {
+        let settings = GroupAnalysisSettings { bins: 6, ..default() };
+        let statistics = OrbitStatistics::of(&walker(), &settings);
+        assert_eq!(statistics.satellites, 24);
+        assert_eq!(statistics.planes.len(), 3);
+        for (plane, expected) in statistics.planes.iter().zip([0.35, 120.35, 240.35]) {
+            assert_eq!(plane.members, 8);
+            assert_abs_diff_eq!(plane.raan, expected, epsilon = 1e-6);
+        }
+        let raan = statistics.distribution(OrbitQuantity::Raan).unwrap();
+        assert_eq!(raan.histogram.counts, vec![8, 0, 8, 0, 8, 0]);
+        let inclination = statistics.distribution(OrbitQuantity::Inclination).unwrap();
+        assert_eq!(inclination.histogram.counts, vec![24, 0, 0, 0, 0, 0]);
+        assert_abs_diff_eq!(inclination.summary.std_dev, 0.0);
+        let period = statistics.distribution(OrbitQuantity::Period).unwrap();
+        assert_abs_diff_eq!(period.summary.median, (1440.0 / 2.003 + 1440.0 / 2.004) / 2.0, epsilon = 1e-9);
+        //two revolutions a solar day, a bit above the GPS semi-major axis
+        let a = statistics.distribution(OrbitQuantity::SemiMajorAxis).unwrap();
+        assert_abs_diff_eq!(a.summary.max, 26610.2, epsilon = 0.1);
+
+        //a plane straddling 0° is a single one, evenly spread nodes and stragglers have no planes
+        let planes = detect_planes(&[359.0, 359.8, 0.2, 0.4, 180.0, 180.1, 180.2], 2.0, 3);
+        assert_eq!(planes.iter().map(|p| p.members).collect::<Vec<_>>(), vec![3, 4]);
+        assert_abs_diff_eq!(planes[1].raan, 359.85, epsilon = 1e-3);
+        let spread: Vec<f64> = (0..360).map(|d| d as f64).collect();
+        assert_eq!(detect_planes(&spread, 2.0, 3), vec![]);
+        assert_eq!(detect_planes(&[10.0, 10.5, 200.0], 2.0, 3), vec![]);
+    }
+
+    #[test]
+    fn test_analysis_writes_the_report() {
+        let directory = std::env::temp_dir().join(format!("skytracio-analysis-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<SyntheticClient>::new(), ConstellationStatsPlugin))
+            .insert_resource(SyntheticClient(walker()))
+            .insert_resource(GroupAnalysisSettings { directory: directory.clone(), format: ReportFormat::Csv, ..default() });
+        app.world_mut().send_event(LoadElements::group("walker", ElementsFormat::Json));
+        for _ in 0..100 {
+            app.update();
+            if app.world_mut().query::<&InGameElements>().iter(app.world()).count() == 24 {
+                break;
+            }
+        }
+
+        app.world_mut().send_event(AnalyzeGroup { group: "walker".to_owned() });
+        let mut reader = app.world().resource::<Events<GroupAnalyzed>>().get_reader();
+        let mut analyzed = vec![];
+        for _ in 0..100 {
+            app.update();
+            analyzed.extend(reader.read(app.world().resource::<Events<GroupAnalyzed>>()).cloned());
+            if !analyzed.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(analyzed.len(), 1);
+        assert_eq!(analyzed[0].statistics.planes.len(), 3);
+        assert!(analyzed[0].statistics.to_string().starts_with("24 satellites, 3 RAAN planes detected"));
+        let path = analyzed[0].path.clone().unwrap();
+        assert_eq!(path, directory.join("walker-orbits.csv"));
+        let report = fs::read_to_string(path).unwrap();
+        assert!(report.contains("inclination_deg,mean,,,55\n"));
+        assert_eq!(report.lines().filter(|l| l.starts_with("plane,members")).count(), 3);
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,329 @@
+use bevy::{color::palettes::css::*, prelude::*, window::PrimaryWindow};
+
+use crate::command_palette::CommandPalette;
+use crate::commands::{CommandDescriptor, CommandRegistry, InvokeCommand};
+use crate::selection::{HoveredSatellite, SelectionSet};
+
+/// Popup of the satellite scoped commands, opened by right clicking a satellite or with the menu key for the primary
+/// selected one. Choosing an entry runs it on the satellite, entries with parameters continue in the command palette
+pub struct ContextMenuPlugin;
+
+/// Opens the menu for the satellite at the screen position, the satellite becomes the selection unless it's already in it
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct OpenContextMenu {
+    pub entity: Entity,
+    pub position: Vec2
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Confirm,
+    /// Entry clicked
+    Choose(usize),
+    Cancel
+}
+
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct ContextMenu {
+    /// Satellite of the open menu, `None` when closed
+    pub target: Option<Entity>,
+    /// Top left corner on the screen
+    pub position: Vec2,
+    /// Command names
+    pub entries: Vec<String>,
+    pub highlighted: usize
+}
+
+/// The satellite scoped commands in registration order
+pub fn menu_entries(registry: &CommandRegistry) -> Vec<&CommandDescriptor> {
+    registry.iter().filter(|c| c.satellite_scoped).collect()
+}
+
+impl ContextMenu {
+    pub fn is_open(&self) -> bool {
+        self.target.is_some()
+    }
+
+    pub fn open(&mut self, entity: Entity, position: Vec2, registry: &CommandRegistry) {
+        let entries = menu_entries(registry).into_iter().map(|c| c.name.clone()).collect();
+        *self = Self { target: Some(entity), position, entries, highlighted: 0 };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Applies the input, returns the name of the chosen command and closes the menu then
+    pub fn handle(&mut self, input: MenuInput) -> Option<String> {
+        let count = self.entries.len().max(1);
+        let chosen = match input {
+            MenuInput::Up => {
+                self.highlighted = (self.highlighted + count - 1) % count;
+                return None;
+            },
+            MenuInput::Down => {
+                self.highlighted = (self.highlighted + 1) % count;
+                return None;
+            },
+            MenuInput::Confirm => self.entries.get(self.highlighted).cloned(),
+            MenuInput::Choose(index) => self.entries.get(index).cloned(),
+            MenuInput::Cancel => None
+        };
+        self.close();
+        chosen
+    }
+}
+
+/// How a chosen entry is run
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuDispatch {
+    Invoke(InvokeCommand),
+    /// The command needs parameters, they are asked for by the palette
+    Prompt(String)
+}
+
+pub fn dispatch(command: &str, registry: &CommandRegistry) -> Option<MenuDispatch> {
+    let descriptor = registry.get(command)?;
+    Some(match descriptor.params.is_empty() {
+        true => MenuDispatch::Invoke(InvokeCommand { name: descriptor.name.clone(), arguments: vec![] }),
+        false => MenuDispatch::Prompt(descriptor.name.clone())
+    })
+}
+
+#[derive(Component)]
+struct ContextMenuNode;
+
+#[derive(Component)]
+struct ContextMenuEntry(usize);
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        let input_condition = resource_exists::<ButtonInput<MouseButton>>
+            .and_then(resource_exists::<ButtonInput<KeyCode>>);
+        app
+            .init_resource::<ContextMenu>()
+            .init_resource::<CommandRegistry>()
+            .init_resource::<SelectionSet>()
+            .init_resource::<HoveredSatellite>()
+            .add_event::<OpenContextMenu>()
+            .add_event::<MenuInput>()
+            .add_event::<InvokeCommand>()
+            .add_systems(Update, (
+                (open_on_right_click, menu_keys_and_clicks).run_if(input_condition),
+                open_context_menu,
+                handle_menu_input
+            ).chain())
+            .add_systems(Update, render_context_menu.after(handle_menu_input).run_if(resource_changed::<ContextMenu>));
+    }
+}
+
+fn open_on_right_click(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    hovered: Res<HoveredSatellite>,
+    selection: Res<SelectionSet>,
+    mut opens: EventWriter<OpenContextMenu>
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let cursor = window.cursor_position();
+    if buttons.just_pressed(MouseButton::Right) {
+        if let (Some(entity), Some(position)) = (hovered.0, cursor) {
+            opens.send(OpenContextMenu { entity, position });
+        }
+    }
+    //the menu key opens it for the focused satellite, at the cursor or in the middle of the window
+    if keys.just_pressed(KeyCode::ContextMenu) {
+        if let Some(entity) = selection.primary() {
+            let position = cursor.unwrap_or(Vec2::new(window.width(), window.height()) / 2.0);
+            opens.send(OpenContextMenu { entity, position });
+        }
+    }
+}
+
+fn menu_keys_and_clicks(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    menu: Res<ContextMenu>,
+    entries: Query<(&Interaction, &ContextMenuEntry)>,
+    mut inputs: EventWriter<MenuInput>
+) {
+    if !menu.is_open() {
+        return;
+    }
+    for (key, input) in [(KeyCode::ArrowUp, MenuInput::Up), (KeyCode::ArrowDown, MenuInput::Down), (KeyCode::Enter, MenuInput::Confirm), (KeyCode::Escape, MenuInput::Cancel)] {
+        if keys.just_pressed(key) {
+            inputs.send(input);
+        }
+    }
+    //a click outside of the entries closes the menu
+    if buttons.just_pressed(MouseButton::Left) || buttons.just_pressed(MouseButton::Right) {
+        let clicked = entries.iter().find(|(interaction, _)| **interaction != Interaction::None).map(|(_, entry)| entry.0);
+        inputs.send(clicked.map_or(MenuInput::Cancel, MenuInput::Choose));
+    }
+}
+
+fn open_context_menu(
+    mut opens: EventReader<OpenContextMenu>,
+    registry: Res<CommandRegistry>,
+    mut selection: ResMut<SelectionSet>,
+    mut menu: ResMut<ContextMenu>
+) {
+    let Some(open) = opens.read().last() else {
+        return;
+    };
+    //the commands act on the selection, a satellite outside of it is selected alone
+    if !selection.contains(open.entity) {
+        selection.select_single(open.entity);
+    }
+    menu.open(open.entity, open.position, &registry);
+}
+
+fn handle_menu_input(
+    mut inputs: EventReader<MenuInput>,
+    registry: Res<CommandRegistry>,
+    mut menu: ResMut<ContextMenu>,
+    mut palette: Option<ResMut<CommandPalette>>,
+    mut invocations: EventWriter<InvokeCommand>
+) {
+    for input in inputs.read() {
+        if !menu.is_open() {
+            break;
+        }
+        let Some(command) = menu.handle(*input) else {
+            continue;
+        };
+        match dispatch(&command, &registry) {
+            Some(MenuDispatch::Invoke(invocation)) => {
+                invocations.send(invocation);
+            },
+            Some(MenuDispatch::Prompt(command)) => match palette.as_mut() {
+                Some(palette) => palette.open_prompt(command),
+                None => warn!("{command} needs parameters, the command palette is not there to ask for them")
+            },
+            None => {}
+        }
+    }
+}
+
+fn render_context_menu(menu: Res<ContextMenu>, nodes: Query<Entity, With<ContextMenuNode>>, mut commands: Commands) {
+    for entity in nodes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !menu.is_open() {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(menu.position.y),
+                    left: Val::Px(menu.position.x),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.85).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            ContextMenuNode
+        ))
+        .with_children(|parent| {
+            for (i, entry) in menu.entries.iter().enumerate() {
+                let color = if i == menu.highlighted { WHITE } else { GRAY };
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style { padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)), ..default() },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        },
+                        ContextMenuEntry(i)
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(entry.clone(), TextStyle { font_size: 15.0, color: color.into(), ..default() }));
+                    });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_palette::CommandPalettePlugin;
+    use crate::commands::CommandsPlugin;
+    use crate::input::{Action, ActionTriggered};
+    use crate::notes::NotesPlugin;
+    use crate::selection::{FocusSatellite, SelectionPlugin};
+
+    #[test]
+    fn test_entries_and_dispatch() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, CommandsPlugin, SelectionPlugin, NotesPlugin));
+        let registry = app.world().resource::<CommandRegistry>();
+        let names: Vec<_> = menu_entries(registry).into_iter().map(|c| c.name.as_str()).collect();
+        for name in ["Add selection to watchlist", "Hide selected orbits", "Remove selected satellites", "Focus selection", "Remove selection from watchlist", "Set note"] {
+            assert!(names.contains(&name), "{name} missing from {names:?}");
+        }
+        //commands of the whole app stay out
+        for name in ["Zoom in", "Open command palette", "Select matching"] {
+            assert!(!names.contains(&name), "{name} in {names:?}");
+        }
+
+        assert_eq!(dispatch("Focus selection", registry), Some(MenuDispatch::Invoke(InvokeCommand { name: "Focus selection".to_owned(), arguments: vec![] })));
+        assert_eq!(dispatch("Set note", registry), Some(MenuDispatch::Prompt("Set note".to_owned())));
+
+        let mut menu = ContextMenu::default();
+        menu.open(Entity::from_raw(7), Vec2::ZERO, registry);
+        let last = menu.entries.len() - 1;
+        assert_eq!(menu.handle(MenuInput::Up), None);
+        assert_eq!(menu.highlighted, last);
+        assert_eq!(menu.handle(MenuInput::Down), None);
+        assert_eq!(menu.handle(MenuInput::Down), None);
+        assert_eq!(menu.handle(MenuInput::Confirm), Some(menu_entries(registry)[1].name.clone()));
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn test_menu_runs_entries_on_the_satellite() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, CommandsPlugin, SelectionPlugin, NotesPlugin, CommandPalettePlugin, ContextMenuPlugin));
+        let other = app.world_mut().spawn_empty().id();
+        let satellite = app.world_mut().spawn_empty().id();
+        app.world_mut().resource_mut::<SelectionSet>().select_single(other);
+        let mut actions = app.world().resource::<Events<ActionTriggered>>().get_reader();
+        let mut focused = app.world().resource::<Events<FocusSatellite>>().get_reader();
+
+        let choose = |app: &mut App, command: &str| {
+            app.world_mut().send_event(OpenContextMenu { entity: satellite, position: Vec2::new(200.0, 100.0) });
+            app.update();
+            let menu = app.world().resource::<ContextMenu>();
+            assert_eq!(menu.target, Some(satellite));
+            let index = menu.entries.iter().position(|e| e == command).unwrap();
+            app.world_mut().send_event(MenuInput::Choose(index));
+            app.update();
+            app.update();
+        };
+        choose(&mut app, "Focus selection");
+        assert_eq!(app.world().resource::<SelectionSet>().iter().collect::<Vec<_>>(), vec![satellite]);
+        assert_eq!(focused.read(app.world().resource::<Events<FocusSatellite>>()).cloned().collect::<Vec<_>>(), vec![FocusSatellite { entity: satellite }]);
+        assert!(!app.world().resource::<ContextMenu>().is_open());
+
+        choose(&mut app, "Add selection to watchlist");
+        let triggered: Vec<_> = actions.read(app.world().resource::<Events<ActionTriggered>>()).map(|a| a.0).collect();
+        assert_eq!(triggered, vec![Action::AddToWatchlist]);
+
+        //parameters are asked for by the palette
+        choose(&mut app, "Set note");
+        let palette = app.world().resource::<CommandPalette>();
+        assert!(palette.open);
+        assert_eq!(palette.prompt_line(app.world().resource::<CommandRegistry>()), Some("Set note > note (text): ".to_owned()));
+    }
+}
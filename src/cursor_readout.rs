@@ -0,0 +1,212 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use bevy::{math::{DQuat, DVec3}, prelude::*, window::PrimaryWindow};
+
+use crate::camera::OverlayCamera;
+use crate::commands::InvokeCommand;
+use crate::ephemeris::SimulationDate;
+use crate::floating_origin::FloatingOrigin;
+use crate::global::InGameSettings;
+use crate::node_drift::gmst_ut;
+use crate::observer::{ecef_to_geodetic, geodetic_to_ecef, ray_ellipsoid_intersection, right_ascension_declination, Geodetic, Observer};
+use crate::propagation::EARTH_RADIUS_KM;
+use crate::simtime::SimInstant;
+use crate::world_frame::WORLD_FRAME;
+
+//observers placed with alt + click are named with this prefix and a number
+const MARKER_PREFIX: &str = "Marker";
+
+/// What the cursor points at: the latitude and longitude of the Earth under it, turned to the simulated time,
+/// or the right ascension and declination of the sky behind it. Alt + click on the Earth places an observer there
+pub struct CursorReadoutPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct CursorReadoutSettings {
+    /// Real time between updates of the readout
+    pub interval: Duration,
+    /// Observers closer to the pointed point than this are named in the readout (in kilometers)
+    pub nearby_km: f64
+}
+
+impl Default for CursorReadoutSettings {
+    fn default() -> Self {
+        Self { interval: Duration::from_millis(100), nearby_km: 250.0 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorTarget {
+    Ground {
+        position: Geodetic,
+        /// Nearest observer within [`CursorReadoutSettings::nearby_km`] and its distance (in kilometers)
+        nearby: Option<(String, f64)>
+    },
+    Sky {
+        /// In degrees, 0..360
+        right_ascension: f64,
+        declination: f64
+    }
+}
+
+/// Latest target of the cursor, `None` while it's outside of the window
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct CursorReadout(pub Option<CursorTarget>);
+
+impl Plugin for CursorReadoutPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CursorReadoutSettings>()
+            .init_resource::<CursorReadout>()
+            .add_event::<InvokeCommand>()
+            .add_systems(Update, (
+                update_cursor_readout.run_if(resource_exists::<InGameSettings>.and_then(resource_exists::<FloatingOrigin>)),
+                place_marker.run_if(resource_exists::<ButtonInput<MouseButton>>.and_then(resource_exists::<ButtonInput<KeyCode>>))
+            ).chain());
+    }
+}
+
+/// Target of a ray in inertial coordinates (in kilometers) with the Earth turned by `gmst` (in radians)
+pub fn cursor_target<'a>(origin: DVec3, direction: DVec3, gmst: f64, observers: impl Iterator<Item = &'a Observer>, nearby_km: f64) -> CursorTarget {
+    let to_earth_fixed = DQuat::from_rotation_z(-gmst);
+    let Some(hit) = ray_ellipsoid_intersection(to_earth_fixed * origin, to_earth_fixed * direction) else {
+        let (right_ascension, declination) = right_ascension_declination(direction);
+        return CursorTarget::Sky { right_ascension, declination };
+    };
+    let position = ecef_to_geodetic(hit);
+    let nearby = observers
+        .map(|observer| (observer, surface_distance_km(hit, geodetic_to_ecef(observer.latitude, observer.longitude, 0.0))))
+        .filter(|(_, distance)| *distance <= nearby_km)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(observer, distance)| (observer.name.clone(), distance));
+    CursorTarget::Ground { position, nearby }
+}
+
+//great circle distance on the mean sphere, plenty for naming the closest observer
+fn surface_distance_km(a: DVec3, b: DVec3) -> f64 {
+    a.angle_between(b) * EARTH_RADIUS_KM as f64
+}
+
+fn hemisphere(value: f64, positive: char, negative: char) -> String {
+    format!("{:.4}° {}", value.abs(), if value < 0.0 { negative } else { positive })
+}
+
+impl fmt::Display for CursorTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CursorTarget::Ground { position, nearby } => {
+                write!(f, "{}  {}", hemisphere(position.latitude, 'N', 'S'), hemisphere(position.longitude, 'E', 'W'))?;
+                if let Some((name, distance)) = nearby {
+                    write!(f, "  · {name} ({distance:.0} km)")?;
+                }
+                Ok(())
+            },
+            CursorTarget::Sky { right_ascension, declination } => {
+                //rounded to whole seconds and arc minutes first, so 59.9 s doesn't show as 60
+                let seconds = (right_ascension / 15.0 * 3600.0).round() as i64 % (24 * 3600);
+                let arc_minutes = (declination.abs() * 60.0).round() as i64;
+                let sign = if *declination < 0.0 { '-' } else { '+' };
+                write!(
+                    f, "RA {:02}h {:02}m {:02}s  Dec {sign}{:02}° {:02}′",
+                    seconds / 3600, seconds / 60 % 60, seconds % 60, arc_minutes / 60, arc_minutes % 60
+                )
+            }
+        }
+    }
+}
+
+/// Greenwich mean sidereal time (in radians) of the simulated date, of the wall clock without the ephemeris
+pub fn earth_rotation(date: Option<&SimulationDate>) -> f64 {
+    let days = match date {
+        Some(date) => date.days_since_j2000(),
+        None => SimInstant::from_system_time(SystemTime::now()).ut_minutes_since_j2000() / 1440.0
+    };
+    gmst_ut(days)
+}
+
+fn update_cursor_readout(
+    time: Res<Time<Real>>,
+    mut next_update: Local<Duration>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    observers: Query<&Observer>,
+    (settings, game_settings, origin, date): (Res<CursorReadoutSettings>, Res<InGameSettings>, Res<FloatingOrigin>, Option<Res<SimulationDate>>),
+    mut readout: ResMut<CursorReadout>
+) {
+    if time.elapsed() < *next_update {
+        return;
+    }
+    *next_update = time.elapsed() + settings.interval;
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let ray = cursor.zip(cameras.iter().next()).and_then(|(cursor, (camera, transform))| camera.viewport_to_world(transform, cursor));
+    let Some(ray) = ray else {
+        readout.set_if_neq(CursorReadout(None));
+        return;
+    };
+    //the camera renders around the floating origin, the world is the scaled inertial frame
+    let origin_km = WORLD_FRAME.to_inertial(origin.to_world(ray.origin)).as_dvec3() / game_settings.scale as f64;
+    let direction = WORLD_FRAME.to_inertial(*ray.direction).as_dvec3();
+    let target = cursor_target(origin_km, direction, earth_rotation(date.as_deref()), observers.iter(), settings.nearby_km);
+    readout.set_if_neq(CursorReadout(Some(target)));
+}
+
+//through the palette command, so the placement is undoable
+fn place_marker(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    readout: Res<CursorReadout>,
+    observers: Query<&Observer>,
+    mut invocations: EventWriter<InvokeCommand>
+) {
+    if !(buttons.just_pressed(MouseButton::Left) && keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])) {
+        return;
+    }
+    let Some(CursorTarget::Ground { position, .. }) = &readout.0 else {
+        return;
+    };
+    let number = observers.iter().filter(|o| o.name.starts_with(MARKER_PREFIX)).count() + 1;
+    invocations.send(InvokeCommand {
+        name: "Place observer".to_owned(),
+        arguments: vec![format!("{MARKER_PREFIX} {number}"), format!("{:.4}", position.latitude), format!("{:.4}", position.longitude)]
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_cursor_target_on_the_turned_earth_and_the_sky() {
+        let gmst = 1.3;
+        let observers = [Observer::new("Cape Town", -33.93, 18.42, 0.0), Observer::new("Perth", -31.95, 115.86, 0.0)];
+        //looking down at Cape Town, the Earth-fixed point is turned into the inertial frame by the sidereal time
+        let ground = DQuat::from_rotation_z(gmst) * geodetic_to_ecef(-34.5, 18.9, 0.0);
+        let eye = ground * 2.0;
+        let CursorTarget::Ground { position, nearby } = cursor_target(eye, ground - eye, gmst, observers.iter(), 250.0) else {
+            panic!("the ray hits the Earth");
+        };
+        assert_abs_diff_eq!(position.latitude, -34.5, epsilon = 1e-9);
+        assert_abs_diff_eq!(position.longitude, 18.9, epsilon = 1e-9);
+        let (name, distance) = nearby.unwrap();
+        assert_eq!(name, "Cape Town");
+        assert!((60.0..90.0).contains(&distance), "{distance}");
+        assert_eq!(
+            CursorTarget::Ground { position, nearby: None }.to_string(),
+            "34.5000° S  18.9000° E"
+        );
+        let other = cursor_target(eye, ground - eye, gmst + 0.5, observers.iter(), 250.0);
+        assert!(matches!(other, CursorTarget::Ground { nearby: None, .. }));
+
+        //away from the Earth, the direction on the equatorial grid
+        let sky = cursor_target(eye, DVec3::new(-1.0, 1.0, 0.0), gmst, observers.iter(), 250.0);
+        let CursorTarget::Sky { right_ascension, declination } = sky else {
+            panic!("the ray misses the Earth");
+        };
+        assert_abs_diff_eq!(right_ascension, 135.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(declination, 0.0, epsilon = 1e-9);
+        let vega = CursorTarget::Sky { right_ascension: 279.2347, declination: 38.7837 };
+        assert_eq!(vega.to_string(), "RA 18h 36m 56s  Dec +38° 47′");
+    }
+}
@@ -0,0 +1,343 @@
+use bevy::{color::palettes::css::*, math::{DQuat, DVec3}, prelude::*};
+
+use crate::commands::{CommandDescriptor, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::orbit::SatelliteOrbit;
+use crate::propagation::{is_plausible_prediction, Propageted};
+use crate::selection::{FocusSatellite, SelectionSet};
+
+const MAX_BISECTIONS: usize = 256;
+//upper edges of the histogram bins, in kilometers, the last bin is open
+const BIN_EDGES: [f64; 9] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0];
+const LISTED_OUTLIERS: usize = 8;
+
+/// Consistency of the SGP4 positions with the Keplerian ellipses rendered for the satellites, a large distance
+/// between the two points at bad elements or at a frame mismatch
+pub struct DataQualityPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct DataQualitySettings {
+    pub enabled: bool,
+    /// Satellites further from their ellipse are outliers, in kilometers
+    pub outlier_threshold: f64
+}
+
+impl Default for DataQualitySettings {
+    fn default() -> Self {
+        Self { enabled: false, outlier_threshold: 50.0 }
+    }
+}
+
+/// Ellipse of an orbit in the inertial frame, cached for every satellite with a [`SatelliteOrbit`]
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EllipseFrame {
+    /// From the perifocal frame (X towards periapsis, Z along the angular momentum) to the inertial one
+    pub rotation: DQuat,
+    /// Kilometers
+    pub semi_major_axis: f64,
+    pub eccentricity: f64
+}
+
+impl From<&SatelliteOrbit> for EllipseFrame {
+    fn from(orbit: &SatelliteOrbit) -> Self {
+        Self {
+            rotation: orbit.perifocal_to_eci().as_dquat().normalize(),
+            semi_major_axis: orbit.semi_major_axis,
+            eccentricity: orbit.eccentricity
+        }
+    }
+}
+
+/// Shortest distance from an inertial position to the ellipse, both in kilometers
+pub fn distance_to_ellipse(position: DVec3, ellipse: &EllipseFrame) -> f64 {
+    let a = ellipse.semi_major_axis;
+    let b = a * (1.0 - ellipse.eccentricity.powi(2)).max(0.0).sqrt();
+    let perifocal = ellipse.rotation.inverse() * position;
+    //the center of the ellipse is opposite to the periapsis from the focus
+    let in_plane = distance_to_planar_ellipse(a, b, perifocal.x + a * ellipse.eccentricity, perifocal.y);
+    in_plane.hypot(perifocal.z)
+}
+
+//distance to x²/a² + y²/b² = 1 with a >= b, following Eberly's robust bisection of the closest point parameter
+fn distance_to_planar_ellipse(a: f64, b: f64, x: f64, y: f64) -> f64 {
+    //symmetric in both axes, solved in the first quadrant. Coordinates negligible next to the ellipse are on an axis,
+    //the bisection bracket collapses for them
+    let negligible = |c: f64| if c.abs() < a * 1e-12 { 0.0 } else { c.abs() };
+    let (y0, y1) = (negligible(x), negligible(y));
+    if y1 > 0.0 {
+        if y0 == 0.0 {
+            return (y1 - b).abs();
+        }
+        let (z0, z1) = (y0 / a, y1 / b);
+        let g = z0 * z0 + z1 * z1 - 1.0;
+        if g == 0.0 {
+            return 0.0;
+        }
+        let r0 = (a / b).powi(2);
+        let s = closest_point_parameter(r0, z0, z1, g);
+        let (x0, x1) = (r0 * y0 / (s + r0), y1 / (s + 1.0));
+        return (x0 - y0).hypot(x1 - y1);
+    }
+    //on the major axis, inside the evolute the closest point is off the axis
+    let (numerator, denominator) = (a * y0, a * a - b * b);
+    if numerator < denominator {
+        let x0 = numerator / denominator;
+        (a * x0 - y0).hypot(b * (1.0 - x0 * x0).max(0.0).sqrt())
+    } else {
+        (y0 - a).abs()
+    }
+}
+
+//root of (r0·z0 / (s + r0))² + (z1 / (s + 1))² = 1, the function is monotonic on the bracket
+fn closest_point_parameter(r0: f64, z0: f64, z1: f64, g: f64) -> f64 {
+    let n0 = r0 * z0;
+    let mut s0 = z1 - 1.0;
+    let mut s1 = if g < 0.0 { 0.0 } else { n0.hypot(z1) - 1.0 };
+    let mut s = s0;
+    for _ in 0..MAX_BISECTIONS {
+        s = (s0 + s1) / 2.0;
+        if s == s0 || s == s1 {
+            break;
+        }
+        let value = (n0 / (s + r0)).powi(2) + (z1 / (s + 1.0)).powi(2) - 1.0;
+        if value > 0.0 {
+            s0 = s;
+        } else if value < 0.0 {
+            s1 = s;
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+/// Distance of the latest SGP4 position of the satellite from its rendered ellipse, in kilometers
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EllipseDistance(pub f64);
+
+/// Counts of `values` in the bins closed by `edges`, the last bin counts everything beyond the last edge
+pub fn histogram(values: impl Iterator<Item = f64>, edges: &[f64]) -> Vec<usize> {
+    let mut bins = vec![0; edges.len() + 1];
+    for value in values {
+        bins[edges.iter().position(|edge| value < *edge).unwrap_or(edges.len())] += 1;
+    }
+    bins
+}
+
+/// Aggregate over all satellites, recomputed on every propagation tick
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct DataQuality {
+    pub histogram: Vec<usize>,
+    /// Satellites beyond the outlier threshold, the furthest first
+    pub outliers: Vec<(Entity, f64)>
+}
+
+impl Plugin for DataQualityPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = |settings: Option<Res<DataQualitySettings>>| settings.is_some_and(|s| s.enabled);
+        app
+            .init_resource::<DataQualitySettings>()
+            .init_resource::<DataQuality>()
+            .init_resource::<SelectionSet>()
+            .add_event::<Propageted>()
+            .add_event::<FocusSatellite>()
+            .register_command(CommandDescriptor::new("Toggle data quality dashboard", ActionCategory::General, |_, world| {
+                let mut settings = world.resource_mut::<DataQualitySettings>();
+                settings.enabled = !settings.enabled;
+            }))
+            .add_systems(Update, (cache_ellipse_frames, measure_ellipse_distances).chain().run_if(enabled).run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, (render_data_quality_panel, focus_clicked_outlier).after(measure_ellipse_distances))
+            .add_systems(Update, clear_when_disabled.run_if(resource_changed::<DataQualitySettings>).run_if(not(enabled)));
+    }
+}
+
+fn cache_ellipse_frames(orbits: Query<(Entity, &SatelliteOrbit), Changed<SatelliteOrbit>>, mut commands: Commands) {
+    for (entity, orbit) in orbits.iter() {
+        commands.entity(entity).insert(EllipseFrame::from(orbit));
+    }
+}
+
+fn measure_ellipse_distances(
+    mut propagated: EventReader<Propageted>,
+    quality_settings: Res<DataQualitySettings>,
+    settings: Res<InGameSettings>,
+    mut quality: ResMut<DataQuality>,
+    mut satellites: Query<(Entity, &EllipseFrame, Option<&mut EllipseDistance>)>,
+    mut commands: Commands
+) {
+    let mut ticked = false;
+    for prediction in propagated.read().flat_map(|p| p.data()) {
+        ticked = true;
+        let (entity, prediction) = prediction;
+        let Ok((_, ellipse, current)) = satellites.get_mut(*entity) else {
+            continue;
+        };
+        if !is_plausible_prediction(prediction, &settings.propagation.envelope) {
+            continue;
+        }
+        let distance = EllipseDistance(distance_to_ellipse(DVec3::from_array(prediction.position), ellipse));
+        match current {
+            Some(mut current) => *current = distance,
+            None => { commands.entity(*entity).insert(distance); }
+        }
+    }
+    if !ticked {
+        return;
+    }
+    //distances inserted in this tick are counted on the next one
+    let threshold = quality_settings.outlier_threshold;
+    let distances: Vec<_> = satellites.iter().filter_map(|(entity, _, distance)| distance.map(|d| (entity, d.0))).collect();
+    let mut outliers: Vec<_> = distances.iter().copied().filter(|(_, d)| *d > threshold).collect();
+    outliers.sort_by(|a, b| b.1.total_cmp(&a.1));
+    *quality = DataQuality { histogram: histogram(distances.iter().map(|(_, d)| *d), &BIN_EDGES), outliers };
+}
+
+fn clear_when_disabled(mut quality: ResMut<DataQuality>, distances: Query<Entity, With<EllipseDistance>>, mut commands: Commands) {
+    *quality = DataQuality::default();
+    for entity in distances.iter() {
+        commands.entity(entity).remove::<EllipseDistance>();
+    }
+}
+
+#[derive(Component)]
+struct DataQualityPanel;
+
+#[derive(Component)]
+struct OutlierEntry(Entity);
+
+fn render_data_quality_panel(
+    quality: Res<DataQuality>,
+    selection: Res<SelectionSet>,
+    distances: Query<&EllipseDistance>,
+    panels: Query<Entity, With<DataQualityPanel>>,
+    mut commands: Commands
+) {
+    if !quality.is_changed() && !selection.is_changed() {
+        return;
+    }
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if quality.histogram.is_empty() {
+        return;
+    }
+    let text = |value: String, color: Srgba| TextBundle::from_section(value, TextStyle { font_size: 13.0, color: color.into(), ..default() });
+    let largest = quality.histogram.iter().copied().max().unwrap_or(0).max(1);
+    let focused = selection.primary().and_then(|e| distances.get(e).ok());
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(80.0),
+                    right: Val::Px(12.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            DataQualityPanel
+        ))
+        .with_children(|parent| {
+            parent.spawn(text("Distance from the rendered ellipse".to_owned(), WHITE));
+            if let Some(distance) = focused {
+                parent.spawn(text(format!("focused: {:.2} km", distance.0), AQUA));
+            }
+            for (index, count) in quality.histogram.iter().enumerate() {
+                let from = if index == 0 { 0.0 } else { BIN_EDGES[index - 1] };
+                let range = match BIN_EDGES.get(index) {
+                    Some(to) => format!("{from:>4}-{to:<4}"),
+                    None => format!("{from:>4}+    ")
+                };
+                let bar = "#".repeat((count * 20).div_ceil(largest));
+                parent.spawn(text(format!("{range} km {bar} {count}"), LIGHT_GRAY));
+            }
+            for (entity, distance) in quality.outliers.iter().take(LISTED_OUTLIERS) {
+                parent
+                    .spawn((ButtonBundle { background_color: Color::srgba(0.4, 0.1, 0.1, 0.6).into(), ..default() }, OutlierEntry(*entity)))
+                    .with_children(|button| {
+                        button.spawn(text(format!("outlier {entity}: {distance:.1} km"), ORANGE));
+                    });
+            }
+        });
+}
+
+fn focus_clicked_outlier(
+    entries: Query<(&Interaction, &OutlierEntry), Changed<Interaction>>,
+    mut selection: ResMut<SelectionSet>,
+    mut focus: EventWriter<FocusSatellite>
+) {
+    for (interaction, OutlierEntry(entity)) in entries.iter() {
+        if *interaction == Interaction::Pressed {
+            selection.select_single(*entity);
+            focus.send(FocusSatellite { entity: *entity });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    //closest distance by dense sampling of the eccentric anomaly, the reference for the solver
+    fn sampled_distance(position: DVec3, ellipse: &EllipseFrame) -> f64 {
+        let a = ellipse.semi_major_axis;
+        let b = a * (1.0 - ellipse.eccentricity.powi(2)).sqrt();
+        (0..200_000)
+            .map(|i| {
+                let anomaly = std::f64::consts::TAU * i as f64 / 200_000.0;
+                let point = DVec3::new(a * (anomaly.cos() - ellipse.eccentricity), b * anomaly.sin(), 0.0);
+                (ellipse.rotation * point).distance(position)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn test_distance_to_known_ellipses() {
+        let orbit = SatelliteOrbit::new(26_560.0, 0.7, 63.4, 120.0, 270.0, 0.0, 0.0);
+        let ellipse = EllipseFrame::from(&orbit);
+        let (a, e) = (ellipse.semi_major_axis, ellipse.eccentricity);
+
+        //points of the orbit itself
+        for true_anomaly in (0..360).step_by(15) {
+            let position = SatelliteOrbit { true_anomaly: true_anomaly as f64, ..orbit.clone() }.position();
+            assert_abs_diff_eq!(distance_to_ellipse(position, &ellipse), 0.0, epsilon = 0.05);
+        }
+
+        //straight out of the periapsis and the apoapsis, and above the plane
+        let periapsis = ellipse.rotation * DVec3::X;
+        assert_abs_diff_eq!(distance_to_ellipse(periapsis * (a * (1.0 - e) + 10.0), &ellipse), 10.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(distance_to_ellipse(-periapsis * (a * (1.0 + e) - 25.0), &ellipse), 25.0, epsilon = 1e-6);
+        let normal = ellipse.rotation * DVec3::Z;
+        assert_abs_diff_eq!(distance_to_ellipse(periapsis * a * (1.0 - e) + normal * 7.0, &ellipse), 7.0, epsilon = 1e-6);
+
+        //anywhere else, the focus and the center of the ellipse included
+        let center = -periapsis * a * e;
+        let positions = [
+            DVec3::ZERO,
+            center,
+            center + ellipse.rotation * DVec3::new(1_000.0, 0.0, 0.0),
+            DVec3::new(12_000.0, -30_000.0, 8_000.0),
+            ellipse.rotation * DVec3::new(-20_000.0, 15_000.0, -3_000.0)
+        ];
+        for position in positions {
+            assert_abs_diff_eq!(distance_to_ellipse(position, &ellipse), sampled_distance(position, &ellipse), epsilon = 0.05);
+        }
+    }
+
+    #[test]
+    fn test_circle_and_histogram() {
+        let ellipse = EllipseFrame::from(&SatelliteOrbit::new(7_000.0, 0.0, 51.6, 30.0, 0.0, 0.0, 0.0));
+        let normal = ellipse.rotation * DVec3::Z;
+        assert_abs_diff_eq!(distance_to_ellipse(DVec3::ZERO, &ellipse), 7_000.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(distance_to_ellipse(ellipse.rotation * DVec3::new(0.0, 6_990.0, 0.0) + normal * 5.0, &ellipse), 125f64.sqrt(), epsilon = 1e-6);
+
+        let bins = histogram([0.5, 1.0, 3.0, 49.0, 75.0, 10_000.0].into_iter(), &BIN_EDGES);
+        assert_eq!(bins, vec![1, 1, 1, 0, 0, 1, 1, 0, 0, 1]);
+    }
+}
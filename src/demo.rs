@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use bevy::{color::palettes::css::*, prelude::*};
+use sgp4::Elements;
+
+use crate::camera::OverlayCamera;
+use crate::orbit::{EARTH_EQUATORIAL_RADIUS, GRAVITATIONAL_CONSTANT};
+use crate::propagation::{ElementsFormat, EpochDataLoader, GroupColors, InGameElements, LoadElements, OrbitalData, SatelliteSpawned};
+use crate::tour::{Tour, TourPlayer};
+use crate::world_frame::WORLD_FRAME;
+
+const EPOCH: &str = "2025-01-01T00:00:00.000000";
+
+/// Groups of the demo scene, served by [`DemoClient`]
+pub const DEMO_GROUPS: [&str; 4] = ["demo-leo", "demo-gps", "demo-geo", "demo-named"];
+/// Satellites of the demo labeled on the screen
+pub const DEMO_LABELED: [&str; 5] = ["DEMO-ISS", "DEMO-MOLNIYA", "DEMO-GEO-1", "DEMO-GEO-2", "DEMO-GEO-3"];
+
+//slow enough to follow, the speed steps show the three regimes
+const DEMO_TOUR: &str = r#"[
+    {"wait_for_group": "demo-named"},
+    {"speed": 60},
+    {"focus": "DEMO-ISS"},
+    {"wait": 30},
+    {"focus": "DEMO-MOLNIYA"},
+    {"speed": 900},
+    {"wait": 30},
+    {"focus": "DEMO-GEO-2"},
+    {"speed": 3600},
+    {"wait": 30},
+    {"focus": "DEMO-GPS-1"},
+    {"speed": 600}
+]"#;
+
+/// Mean elements of a synthetic satellite, angles in degrees and the mean motion in revolutions per day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeanElements {
+    pub mean_motion: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub raan: f64,
+    pub argument_of_perigee: f64,
+    pub mean_anomaly: f64
+}
+
+impl MeanElements {
+    /// Near-circular orbit at the altitude (in kilometers)
+    pub fn circular(altitude_km: f64, inclination: f64) -> Self {
+        let semi_major_axis = EARTH_EQUATORIAL_RADIUS + altitude_km;
+        let mean_motion = (GRAVITATIONAL_CONSTANT / semi_major_axis.powi(3)).sqrt() * 86400.0 / std::f64::consts::TAU;
+        Self { mean_motion, eccentricity: 0.0001, inclination, raan: 0.0, argument_of_perigee: 0.0, mean_anomaly: 0.0 }
+    }
+}
+
+/// Element set of a synthetic satellite of the demo epoch, without drag
+pub fn synthetic_elements(name: &str, norad_id: u64, elements: MeanElements) -> Arc<Elements> {
+    synthetic_elements_at(name, norad_id, EPOCH, 0.0, elements)
+}
+
+/// Element set of a synthetic satellite, `epoch` in the OMM format and `bstar` the drag term of SGP4
+pub fn synthetic_elements_at(name: &str, norad_id: u64, epoch: &str, bstar: f64, elements: MeanElements) -> Arc<Elements> {
+    let MeanElements { mean_motion, eccentricity, inclination, raan, argument_of_perigee, mean_anomaly } = elements;
+    let json = format!(
+        r#"{{"OBJECT_NAME":"{name}","OBJECT_ID":"2025-001A","EPOCH":"{epoch}","MEAN_MOTION":{mean_motion},"ECCENTRICITY":{eccentricity},"INCLINATION":{inclination},"RA_OF_ASC_NODE":{raan},"ARG_OF_PERICENTER":{argument_of_perigee},"MEAN_ANOMALY":{mean_anomaly},"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":{norad_id},"ELEMENT_SET_NO":999,"REV_AT_EPOCH":1,"BSTAR":{bstar},"MEAN_MOTION_DOT":0,"MEAN_MOTION_DDOT":0}}"#
+    );
+    Arc::new(serde_json::from_str(&json).expect("synthetic elements must parse"))
+}
+
+/// Walker delta pattern `total/planes/phasing` of circular orbits, named `<prefix>-<n>` from 1 and numbered from `first_norad_id`
+pub fn walker_delta(prefix: &str, first_norad_id: u64, total: usize, planes: usize, phasing: usize, inclination: f64, altitude_km: f64) -> OrbitalData {
+    let per_plane = total / planes;
+    let circular = MeanElements::circular(altitude_km, inclination);
+    (0..planes * per_plane)
+        .map(|i| {
+            let (plane, slot) = (i / per_plane, i % per_plane);
+            let raan = 360.0 * plane as f64 / planes as f64;
+            let mean_anomaly = (360.0 * slot as f64 / per_plane as f64 + 360.0 * (phasing * plane) as f64 / total as f64) % 360.0;
+            synthetic_elements(&format!("{prefix}-{}", i + 1), first_norad_id + i as u64, MeanElements { raan, mean_anomaly, ..circular })
+        })
+        .collect()
+}
+
+/// Element sets of a demo group, `None` for other groups
+pub fn demo_group(group: &str) -> Option<OrbitalData> {
+    match group {
+        "demo-leo" => Some(walker_delta("DEMO-LEO", 90000, 36, 6, 1, 53.0, 550.0)),
+        "demo-gps" => Some(walker_delta("DEMO-GPS", 90100, 24, 6, 1, 55.0, 20180.0)),
+        "demo-geo" => Some((0..3)
+            .map(|i| synthetic_elements(&format!("DEMO-GEO-{}", i + 1), 90200 + i, MeanElements {
+                mean_motion: 1.0027, eccentricity: 0.0002, inclination: 0.05, raan: 0.0, argument_of_perigee: 0.0, mean_anomaly: 120.0 * i as f64
+            }))
+            .collect()),
+        "demo-named" => Some(vec![
+            synthetic_elements("DEMO-ISS", 90300, MeanElements {
+                mean_motion: 15.4995, eccentricity: 0.0005, inclination: 51.64, raan: 210.0, argument_of_perigee: 90.0, mean_anomaly: 0.0
+            }),
+            synthetic_elements("DEMO-MOLNIYA", 90301, MeanElements {
+                mean_motion: 2.0059, eccentricity: 0.72, inclination: 63.4, raan: 60.0, argument_of_perigee: 270.0, mean_anomaly: 0.0
+            })
+        ]),
+        _ => None
+    }
+}
+
+/// Serves the demo groups from elements built into the binary, no network and no files
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct DemoClient;
+
+#[async_trait::async_trait]
+impl EpochDataLoader for DemoClient {
+    type Error = String;
+
+    async fn load(&self, group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        demo_group(&group).ok_or_else(|| format!("{group} is not a demo group"))
+    }
+}
+
+/// Initial view of the demo, over the northern hemisphere with the GEO ring in sight
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct DemoCamera {
+    /// From the center of the Earth, in the world frame
+    pub direction: Vec3,
+    /// In world units
+    pub distance: f32
+}
+
+impl Default for DemoCamera {
+    fn default() -> Self {
+        Self { direction: WORLD_FRAME.to_world(Vec3::new(0.55, -0.6, 0.58).normalize()), distance: 650.0 }
+    }
+}
+
+/// Name of a satellite drawn next to it
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SatelliteLabel(pub Entity);
+
+/// Offline demo scene (`--demo`): loads the demo groups from [`DemoClient`], colors them, labels the named
+/// satellites and plays a slow tour. The app provides the client, the procedural Earth and the tour player
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DemoCamera>()
+            .insert_resource(GroupColors::default()
+                .with("demo-leo", DEEP_SKY_BLUE)
+                .with("demo-gps", LIMEGREEN)
+                .with("demo-geo", GOLD)
+                .with("demo-named", ORANGE_RED))
+            .insert_resource(TourPlayer::new(Tour::parse(DEMO_TOUR).expect("the demo tour must parse")))
+            .add_event::<LoadElements>()
+            .add_event::<SatelliteSpawned>()
+            .add_systems(Startup, load_demo_groups)
+            .add_systems(Update, (label_named_satellites, place_labels).chain());
+    }
+}
+
+//the named satellites are launched, the constellations are already up
+fn load_demo_groups(mut loads: EventWriter<LoadElements>) {
+    loads.send_batch(DEMO_GROUPS.map(|group| LoadElements {
+        groups: vec![group.to_owned()],
+        format: ElementsFormat::Json,
+        launch_animation: group == "demo-named",
+        ..default()
+    }));
+}
+
+fn label_named_satellites(mut spawned: EventReader<SatelliteSpawned>, satellites: Query<&InGameElements>, mut commands: Commands) {
+    for SatelliteSpawned { entity, .. } in spawned.read() {
+        let Some(name) = satellites.get(*entity).ok().and_then(|el| el.0.object_name.clone()) else {
+            continue;
+        };
+        if !DEMO_LABELED.contains(&name.as_str()) {
+            continue;
+        }
+        commands.spawn((
+            TextBundle::from_section(name, TextStyle { font_size: 13.0, color: WHITE.into(), ..default() })
+                .with_style(Style { position_type: PositionType::Absolute, ..default() }),
+            SatelliteLabel(*entity)
+        ));
+    }
+}
+
+//next to the satellite on the screen, hidden behind the camera and once the satellite is gone
+fn place_labels(
+    cameras: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    satellites: Query<&GlobalTransform>,
+    mut labels: Query<(Entity, &SatelliteLabel, &mut Style, &mut Visibility)>,
+    mut commands: Commands
+) {
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    for (entity, SatelliteLabel(target), mut style, mut visibility) in labels.iter_mut() {
+        let Ok(target) = satellites.get(*target) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        match camera.world_to_viewport(camera_transform, target.translation()) {
+            Some(position) => {
+                style.left = Val::Px(position.x + 8.0);
+                style.top = Val::Px(position.y - 8.0);
+                *visibility = Visibility::Inherited;
+            },
+            None => *visibility = Visibility::Hidden
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::global::{InGameSettings, PropagationSettings};
+    use crate::propagation::{BecameUnreliable, Propageted, SatelliteGroup, Unreliable};
+    use crate::simulation_clock::SimulationClock;
+    use crate::tour::TourPlugin;
+    use crate::SkytracioPlugins;
+
+    #[test]
+    fn test_demo_scene_boots_and_propagates() {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+            .add_plugins(SkytracioPlugins::new().with_client(DemoClient).with_settings(InGameSettings {
+                propagation: PropagationSettings { real_time_interval: Duration::from_millis(500), batch_size: 50, numeric_fallback: true, ..default() },
+                ..default()
+            }).headless().without_demo_bodies())
+            .add_plugins((TourPlugin::default(), DemoPlugin));
+
+        let mut propagated = HashSet::new();
+        let mut propagations = app.world().resource::<Events<Propageted>>().get_reader();
+        let mut unreliable = app.world().resource::<Events<BecameUnreliable>>().get_reader();
+        for _ in 0..400 {
+            app.update();
+            for event in propagations.read(app.world().resource::<Events<Propageted>>()) {
+                assert!(event.fallback().is_empty());
+                propagated.extend(event.data().iter().map(|(entity, _)| *entity));
+            }
+            assert_eq!(unreliable.read(app.world().resource::<Events<BecameUnreliable>>()).count(), 0);
+            if app.world().resource::<SimulationClock>().elapsed_seconds() > 600.0 {
+                break;
+            }
+        }
+        assert!(app.world().resource::<SimulationClock>().elapsed_seconds() > 600.0);
+        //the tour sped the clock up once the named satellites were there
+        assert_eq!(app.world().resource::<InGameSettings>().simulation_speed, 60.0);
+
+        let mut per_group = std::collections::BTreeMap::new();
+        for group in app.world_mut().query::<&SatelliteGroup>().iter(app.world()) {
+            *per_group.entry(group.0.clone()).or_insert(0) += 1;
+        }
+        assert_eq!(per_group.into_iter().collect::<Vec<_>>(), vec![
+            ("demo-geo".to_owned(), 3), ("demo-gps".to_owned(), 24), ("demo-leo".to_owned(), 36), ("demo-named".to_owned(), 2)
+        ]);
+        let satellites: Vec<Entity> = app.world_mut().query_filtered::<Entity, With<InGameElements>>().iter(app.world()).collect();
+        assert!(satellites.iter().all(|entity| propagated.contains(entity)));
+        assert_eq!(app.world_mut().query::<&Unreliable>().iter(app.world()).count(), 0);
+
+        //each label reads the name of its satellite
+        let mut names = vec![];
+        for (text, SatelliteLabel(target)) in app.world_mut().query::<(&Text, &SatelliteLabel)>().iter(app.world()) {
+            let name = app.world().get::<InGameElements>(*target).unwrap().0.object_name.clone().unwrap();
+            assert_eq!(text.sections[0].value, name);
+            names.push(name);
+        }
+        let mut expected = DEMO_LABELED.to_vec();
+        names.sort();
+        expected.sort();
+        assert_eq!(names, expected);
+    }
+}
@@ -2,8 +2,18 @@ use std::marker::PhantomData;
 
 use bevy::{gltf::GltfMesh, math::Vec3A, prelude::*, render::primitives::Aabb};
 
+use crate::world_frame::WORLD_FRAME;
+
+/// Model loaded when no other is given, relative to the assets folder
+pub const DEFAULT_EARTH_MODEL: &str = "3d/Earth_1_12756.glb";
+
+/// Marker of the spawned Earth model
+#[derive(Component, Default)]
+pub struct Earth;
+
 pub struct LoadAndScaleEarthModelPlugin<T> {
     pub target_in_game_radius: f32,
+    pub asset_path: String,
     phantom_data: PhantomData<T>    
 }
 
@@ -11,9 +21,46 @@ impl <T> LoadAndScaleEarthModelPlugin<T> {
     pub fn new(target_in_game_diameter: f32) -> Self {
         Self {
             target_in_game_radius: target_in_game_diameter,
+            asset_path: DEFAULT_EARTH_MODEL.to_owned(),
             phantom_data: PhantomData
         }
     }
+
+    pub fn with_asset_path(mut self, asset_path: impl Into<String>) -> Self {
+        self.asset_path = asset_path.into();
+        self
+    }
+}
+
+/// Plain sphere in place of the model, for running without the assets
+pub struct ProceduralEarthPlugin<T> {
+    pub target_in_game_diameter: f32,
+    phantom_data: PhantomData<T>
+}
+
+impl <T> ProceduralEarthPlugin<T> {
+    pub fn new(target_in_game_diameter: f32) -> Self {
+        Self { target_in_game_diameter, phantom_data: PhantomData }
+    }
+}
+
+impl <T: Component + Default> Plugin for ProceduralEarthPlugin<T> {
+    fn build(&self, app: &mut App) {
+        let radius = self.target_in_game_diameter / 2.0;
+        app
+          .add_event::<AssetPrepared>()
+          .add_systems(Startup, move |mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, mut ev_done: EventWriter<AssetPrepared>| {
+              let entity_id = commands.spawn((
+                  PbrBundle {
+                      mesh: meshes.add(Sphere::new(radius).mesh().uv(64, 32)),
+                      material: materials.add(StandardMaterial { base_color: Color::srgb(0.15, 0.3, 0.6), perceptual_roughness: 0.8, ..default() }),
+                      ..default()
+                  },
+                  T::default()
+              )).id();
+              ev_done.send(AssetPrepared { entity_id });
+          });
+    }
 }
 
 #[derive(Event)]
@@ -33,6 +80,7 @@ enum InternalState {
 #[derive(Resource)]
 struct ScaleResource {
     target_in_game_radius: f32,
+    asset_path: String,
     spawned_earth: Option<Entity>
 }
 
@@ -41,7 +89,7 @@ impl <T: Component + Default> Plugin for LoadAndScaleEarthModelPlugin<T> {
         app
           .add_event::<AssetPrepared>()
           .init_state::<InternalState>()
-          .insert_resource(ScaleResource { target_in_game_radius: self.target_in_game_radius, spawned_earth: None })
+          .insert_resource(ScaleResource { target_in_game_radius: self.target_in_game_radius, asset_path: self.asset_path.clone(), spawned_earth: None })
           .add_systems(Startup, EarthAssets::load_model)
           .add_systems(Update, EarthAssets::transition_to_loaded.run_if(in_state(InternalState::Loading)))
           .add_systems(OnEnter(InternalState::Loaded), LoadedEarthAssets::spawn_earth_system::<T>)
@@ -58,8 +106,8 @@ pub struct EarthAssets {
 }
 
 impl EarthAssets {
-    fn load_model(mut commands: Commands, ass: Res<AssetServer>) {
-        let assets = ass.load("3d/Earth_1_12756.glb");
+    fn load_model(mut commands: Commands, ass: Res<AssetServer>, scale_resource: Res<ScaleResource>) {
+        let assets = ass.load(scale_resource.asset_path.clone());
         commands.insert_resource(Self { assets });
     }
 
@@ -138,7 +186,6 @@ impl LoadedEarthAssets {
         fn recursive(
             entity: Entity, 
             world: &World,
-            resource: &Res<ScaleResource>,
             children: &Query<&Children>
         ) {
             let components = world.inspect_entity(entity);
@@ -154,11 +201,11 @@ impl LoadedEarthAssets {
                 }
             };
             for child in children_val {
-                recursive(*child, world, resource, children);
+                recursive(*child, world, children);
             }
         }
         if let Some(earth) = resource.spawned_earth {
-            recursive(earth, world, &resource, &children);
+            recursive(earth, world, &children);
         };
     }
 
@@ -204,7 +251,7 @@ impl LoadedEarthAssets {
 
         for mut scene_transform in scene.iter_mut() {
             scene_transform.scale = scale;
-            scene_transform.rotation = Quat::from_rotation_x(std::f32::consts::PI / 2.0);
+            scene_transform.rotation = WORLD_FRAME.y_up_model_rotation();
         }
 
         ev_done.send(AssetPrepared { entity_id: resource.spawned_earth.expect("earth instance must be present here") });
@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use bevy::{ecs::world::Command, prelude::*};
+use sgp4::Elements;
+
+use crate::commands::{CommandDescriptor, ParamKind, ParamValue, RegisterCommand};
+use crate::input::{Action, ActionCategory, ActionTriggered};
+use crate::observer::Observer;
+use crate::orbit::GRAVITATIONAL_CONSTANT;
+use crate::propagation::{Despawning, InGameElements, InvalidateDerivedState, InvalidationReason};
+use crate::selection::{OrbitHidden, SelectionSet, Watchlist};
+
+//edits kept for undo, the oldest are forgotten
+const HISTORY_LIMIT: usize = 100;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Interactive edits of the scene, recorded so they can be undone (Ctrl+Z) and redone (Ctrl+Shift+Z).
+/// Propagation and the simulation time are not edits, they are never undone
+pub struct EditsPlugin;
+
+/// Single reversible change, holds the state on both sides so it can be applied in either direction
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// Elements of a satellite replaced, by an element edit or a maneuver
+    Elements { entity: Entity, before: Arc<Elements>, after: Arc<Elements> },
+    Watchlist { entity: Entity, added: bool },
+    OrbitDisplay { entity: Entity, hidden: bool },
+    /// Observer placed (without `before`), moved or removed (without `after`)
+    Observer { entity: Entity, before: Option<Observer>, after: Option<Observer> }
+}
+
+impl Change {
+    pub fn entity(&self) -> Entity {
+        match self {
+            Change::Elements { entity, .. } | Change::Watchlist { entity, .. } | Change::OrbitDisplay { entity, .. } | Change::Observer { entity, .. } => *entity
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        match self {
+            Change::Elements { entity, before, after } => Change::Elements { entity, before: after, after: before },
+            Change::Watchlist { entity, added } => Change::Watchlist { entity, added: !added },
+            Change::OrbitDisplay { entity, hidden } => Change::OrbitDisplay { entity, hidden: !hidden },
+            Change::Observer { entity, before, after } => Change::Observer { entity, before: after, after: before }
+        }
+    }
+
+    //false when the target no longer exists, nothing is changed then
+    fn apply(&self, world: &mut World) -> bool {
+        let entity = self.entity();
+        let exists = world.get_entity(entity).is_some_and(|e| !e.contains::<Despawning>());
+        if !exists {
+            return false;
+        }
+        match self {
+            Change::Elements { after, .. } => {
+                let Some(mut elements) = world.get_mut::<InGameElements>(entity) else {
+                    return false;
+                };
+                elements.0 = after.clone();
+                world.send_event(InvalidateDerivedState { reason: InvalidationReason::Elements(entity) });
+            },
+            Change::Watchlist { added, .. } => {
+                let Some(mut watchlist) = world.get_resource_mut::<Watchlist>() else {
+                    return false;
+                };
+                if *added {
+                    watchlist.insert(entity);
+                } else {
+                    watchlist.remove(entity);
+                }
+            },
+            Change::OrbitDisplay { hidden: true, .. } => {
+                world.entity_mut(entity).insert(OrbitHidden);
+            },
+            Change::OrbitDisplay { hidden: false, .. } => {
+                world.entity_mut(entity).remove::<OrbitHidden>();
+            },
+            Change::Observer { after: Some(observer), .. } => {
+                world.entity_mut(entity).insert(observer.clone());
+            },
+            Change::Observer { after: None, .. } => {
+                world.entity_mut(entity).remove::<Observer>();
+            }
+        }
+        true
+    }
+}
+
+/// User action, its changes are undone together. Applied as a [`Command`], so features route their mutations
+/// through it instead of changing the world themselves, and it lands in the [`EditHistory`]
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub label: String,
+    pub changes: Vec<Change>
+}
+
+impl Edit {
+    pub fn new(label: impl Into<String>, changes: Vec<Change>) -> Self {
+        Self { label: label.into(), changes }
+    }
+}
+
+impl Command for Edit {
+    fn apply(self, world: &mut World) {
+        let changes = apply_changes(world, &self.label, self.changes);
+        if changes.is_empty() {
+            return;
+        }
+        if let Some(mut history) = world.get_resource_mut::<EditHistory>() {
+            history.record(Edit { label: self.label, changes });
+        }
+    }
+}
+
+/// Sent when changes of an edit were skipped, their satellite or observer no longer exists
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct EditSkipped {
+    pub label: String,
+    pub skipped: usize
+}
+
+/// Applied edits, newest last, and the undone ones that can still be redone
+#[derive(Resource, Default, Debug)]
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>
+}
+
+impl EditHistory {
+    //a new edit forks the history, what was undone before can't be redone anymore
+    fn record(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push_back(edit);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn next_undo(&self) -> Option<&Edit> {
+        self.undo.back()
+    }
+
+    pub fn next_redo(&self) -> Option<&Edit> {
+        self.redo.last()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HistoryStep {
+    Undo,
+    Redo
+}
+
+impl Command for HistoryStep {
+    fn apply(self, world: &mut World) {
+        let Some(mut history) = world.get_resource_mut::<EditHistory>() else {
+            return;
+        };
+        let edit = match self {
+            HistoryStep::Undo => history.undo.pop_back(),
+            HistoryStep::Redo => history.redo.pop()
+        };
+        let Some(Edit { label, changes }) = edit else {
+            return;
+        };
+        let changes: Vec<_> = match self {
+            //in reverse, a later change may depend on an earlier one
+            HistoryStep::Undo => apply_changes(world, &label, changes.into_iter().rev().map(Change::inverse).collect())
+                .into_iter().rev().map(Change::inverse).collect(),
+            HistoryStep::Redo => apply_changes(world, &label, changes)
+        };
+        if changes.is_empty() {
+            return;
+        }
+        let mut history = world.resource_mut::<EditHistory>();
+        let edit = Edit { label, changes };
+        match self {
+            HistoryStep::Undo => history.redo.push(edit),
+            HistoryStep::Redo => history.undo.push_back(edit)
+        }
+    }
+}
+
+//the changes actually applied, the ones with a missing target are dropped with a notice
+fn apply_changes(world: &mut World, label: &str, changes: Vec<Change>) -> Vec<Change> {
+    let (applied, skipped): (Vec<_>, Vec<_>) = changes.into_iter().partition(|change| change.apply(world));
+    if !skipped.is_empty() {
+        warn!("Skipped {} changes of {:?}, their targets no longer exist", skipped.len(), label);
+        world.send_event(EditSkipped { label: label.to_owned(), skipped: skipped.len() });
+    }
+    applied
+}
+
+impl Plugin for EditsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EditHistory>()
+            .init_resource::<SelectionSet>()
+            .add_event::<EditSkipped>()
+            .add_event::<InvalidateDerivedState>()
+            .add_event::<ActionTriggered>()
+            .register_command(
+                CommandDescriptor::new("Edit orbit element", ActionCategory::Selection, edit_element)
+                    .with_param("element", ParamKind::Text)
+                    .with_param("value", ParamKind::Number)
+                    .satellite_scoped()
+            )
+            .register_command(
+                CommandDescriptor::new("Apply maneuver", ActionCategory::Selection, apply_maneuver)
+                    .with_param("along-track delta-v (m/s)", ParamKind::Number)
+                    .satellite_scoped()
+            )
+            .register_command(
+                CommandDescriptor::new("Place observer", ActionCategory::General, place_observer)
+                    .with_param("name", ParamKind::Text)
+                    .with_param("latitude", ParamKind::Number)
+                    .with_param("longitude", ParamKind::Number)
+            )
+            .add_systems(Update, walk_history);
+    }
+}
+
+fn walk_history(mut actions: EventReader<ActionTriggered>, mut commands: Commands) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::Undo => commands.add(HistoryStep::Undo),
+            Action::Redo => commands.add(HistoryStep::Redo),
+            _ => continue
+        };
+    }
+}
+
+//elements of the primary selected satellite, with the entity
+fn primary_elements(world: &World) -> Option<(Entity, Arc<Elements>)> {
+    let entity = world.get_resource::<SelectionSet>()?.primary()?;
+    let elements = world.get::<InGameElements>(entity)?;
+    Some((entity, elements.0.clone()))
+}
+
+fn replace_elements(world: &mut World, label: String, modify: impl FnOnce(&mut Elements) -> Result<(), String>) {
+    let Some((entity, before)) = primary_elements(world) else {
+        warn!("{label}: no satellite selected");
+        return;
+    };
+    let mut after = before.as_ref().clone();
+    if let Err(reason) = modify(&mut after) {
+        warn!("{label}: {reason}");
+        return;
+    }
+    Edit::new(label, vec![Change::Elements { entity, before, after: Arc::new(after) }]).apply(world);
+}
+
+fn edit_element(params: &[ParamValue], world: &mut World) {
+    let (Some(element), Some(value)) = (params[0].as_text(), params[1].as_number()) else {
+        return;
+    };
+    let element = element.to_lowercase();
+    replace_elements(world, format!("Edit {element}"), |elements| set_element(elements, &element, value));
+}
+
+/// Sets an element by its name, angles in degrees and the mean motion in revolutions per day
+pub fn set_element(elements: &mut Elements, name: &str, value: f64) -> Result<(), String> {
+    let angle = |range: std::ops::RangeInclusive<f64>| if range.contains(&value) { Ok(value) } else { Err(format!("{name} must be within {range:?} degrees")) };
+    match name {
+        "inclination" => elements.inclination = angle(0.0..=180.0)?,
+        "raan" | "right ascension" => elements.right_ascension = angle(0.0..=360.0)?,
+        "argument of perigee" => elements.argument_of_perigee = angle(0.0..=360.0)?,
+        "mean anomaly" => elements.mean_anomaly = angle(0.0..=360.0)?,
+        "eccentricity" if (0.0..1.0).contains(&value) => elements.eccentricity = value,
+        "mean motion" if value > 0.0 => elements.mean_motion = value,
+        "eccentricity" | "mean motion" => return Err(format!("{value} is not a valid {name}")),
+        _ => return Err(format!("unknown element {name:?}"))
+    }
+    Ok(())
+}
+
+fn apply_maneuver(params: &[ParamValue], world: &mut World) {
+    let Some(delta_v) = params[0].as_number() else {
+        return;
+    };
+    replace_elements(world, format!("Maneuver {delta_v:+} m/s"), |elements| along_track_maneuver(elements, delta_v / 1000.0));
+}
+
+/// Impulsive burn along the velocity (in km/s) at the epoch of the elements, for near-circular orbits:
+/// the semi-major axis grows by `2a·Δv/v`, so the mean motion drops by `3n·Δv/v`
+pub fn along_track_maneuver(elements: &mut Elements, delta_v: f64) -> Result<(), String> {
+    let mean_motion = elements.mean_motion * std::f64::consts::TAU / SECONDS_PER_DAY;
+    let speed = (GRAVITATIONAL_CONSTANT * mean_motion).cbrt();
+    let factor = 1.0 - 3.0 * delta_v / speed;
+    if factor <= 0.0 {
+        return Err(format!("{} km/s is more than the orbit can take", delta_v));
+    }
+    elements.mean_motion *= factor;
+    Ok(())
+}
+
+//moves the observer of that name, places a new one when there is none
+fn place_observer(params: &[ParamValue], world: &mut World) {
+    let (Some(name), Some(latitude), Some(longitude)) = (params[0].as_text(), params[1].as_number(), params[2].as_number()) else {
+        return;
+    };
+    if !(-90.0..=90.0).contains(&latitude) {
+        warn!("Latitude {latitude} is out of range");
+        return;
+    }
+    let existing = world.query::<(Entity, &Observer)>().iter(world)
+        .find(|(_, o)| o.name == name)
+        .map(|(e, o)| (e, o.clone()));
+    let longitude = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+    let (entity, before, after) = match existing {
+        Some((entity, observer)) => (entity, Some(observer.clone()), Observer { latitude, longitude, ..observer }),
+        None => (world.spawn_empty().id(), None, Observer::new(name, latitude, longitude, 0.0))
+    };
+    let label = if before.is_some() { format!("Move observer {name}") } else { format!("Place observer {name}") };
+    Edit::new(label, vec![Change::Observer { entity, before, after: Some(after) }]).apply(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{CommandsPlugin, InvokeCommand};
+    use crate::orbit::SatelliteOrbit;
+    use crate::selection::{BulkOperation, SelectionPlugin};
+    use crate::stress::starlink_like_elements;
+
+    fn app() -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, CommandsPlugin, SelectionPlugin, EditsPlugin));
+        let entities = starlink_like_elements(3, 1481).into_iter()
+            .map(|el| app.world_mut().spawn((SatelliteOrbit::from(el.as_ref()), InGameElements(el), Transform::default())).id())
+            .collect();
+        app.update();
+        (app, entities)
+    }
+
+    fn invoke(app: &mut App, name: &str, arguments: &[&str]) {
+        app.world_mut().send_event(InvokeCommand { name: name.to_owned(), arguments: arguments.iter().map(|a| a.to_string()).collect() });
+        app.update();
+    }
+
+    fn trigger(app: &mut App, action: Action) {
+        app.world_mut().send_event(ActionTriggered(action));
+        app.update();
+    }
+
+    fn elements(app: &App, entity: Entity) -> Arc<Elements> {
+        app.world().get::<InGameElements>(entity).unwrap().0.clone()
+    }
+
+    fn watched(app: &App) -> Vec<Entity> {
+        app.world().resource::<Watchlist>().iter().collect()
+    }
+
+    #[test]
+    fn test_undo_restores_the_state_before_the_edits() {
+        let (mut app, entities) = app();
+        let snapshot: Vec<_> = entities.iter().map(|e| elements(&app, *e)).collect();
+        app.world_mut().resource_mut::<SelectionSet>().select_single(entities[0]);
+
+        invoke(&mut app, "Edit orbit element", &["Inclination", "60"]);
+        assert_eq!(elements(&app, entities[0]).inclination, 60.0);
+        invoke(&mut app, "Apply maneuver", &["10"]);
+        assert!(elements(&app, entities[0]).mean_motion < snapshot[0].mean_motion);
+        app.world_mut().resource_mut::<SelectionSet>().extend([entities[1]]);
+        app.world_mut().send_event(BulkOperation::AddToWatchlist);
+        app.update();
+        assert_eq!(watched(&app), vec![entities[0], entities[1]]);
+
+        for _ in 0..3 {
+            trigger(&mut app, Action::Undo);
+        }
+        assert!(watched(&app).is_empty());
+        for (entity, before) in entities.iter().zip(&snapshot) {
+            assert!(Arc::ptr_eq(&elements(&app, *entity), before));
+        }
+        //nothing left to undo
+        trigger(&mut app, Action::Undo);
+        assert!(app.world().resource::<EditHistory>().next_undo().is_none());
+
+        trigger(&mut app, Action::Redo);
+        assert_eq!(elements(&app, entities[0]).inclination, 60.0);
+        assert_eq!(app.world().resource::<EditHistory>().next_redo().map(|e| e.label.as_str()), Some("Maneuver +10 m/s"));
+        //a new edit drops what could have been redone
+        invoke(&mut app, "Edit orbit element", &["mean anomaly", "10"]);
+        assert!(app.world().resource::<EditHistory>().next_redo().is_none());
+    }
+
+    #[test]
+    fn test_changes_of_despawned_targets_are_skipped() {
+        let (mut app, entities) = app();
+        invoke(&mut app, "Place observer", &["Home", "50", "20"]);
+        invoke(&mut app, "Place observer", &["Home", "51", "21"]);
+        let (observer, moved) = app.world_mut().query::<(Entity, &Observer)>().single(app.world());
+        assert_eq!((moved.latitude, moved.longitude), (51.0, 21.0));
+
+        app.world_mut().resource_mut::<SelectionSet>().extend([entities[0], entities[1]]);
+        app.world_mut().send_event(BulkOperation::SetOrbitDisplay(false));
+        app.update();
+        app.world_mut().entity_mut(entities[1]).insert(Despawning { t: 0.0 });
+
+        trigger(&mut app, Action::Undo);
+        assert!(app.world().get::<OrbitHidden>(entities[0]).is_none());
+        assert!(app.world().get::<OrbitHidden>(entities[1]).is_some());
+        let skipped: Vec<_> = app.world_mut().resource_mut::<Events<EditSkipped>>().drain().collect();
+        assert_eq!(skipped, vec![EditSkipped { label: "Hide orbits".to_owned(), skipped: 1 }]);
+
+        trigger(&mut app, Action::Undo);
+        assert_eq!(app.world().get::<Observer>(observer).map(|o| o.latitude), Some(50.0));
+        trigger(&mut app, Action::Undo);
+        assert!(app.world().get::<Observer>(observer).is_none());
+        trigger(&mut app, Action::Redo);
+        assert_eq!(app.world().get::<Observer>(observer).map(|o| o.latitude), Some(50.0));
+    }
+}
@@ -0,0 +1,274 @@
+use std::time::SystemTime;
+
+use bevy::{color::palettes::css::*, math::DVec3, prelude::*};
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::orbit::EARTH_EQUATORIAL_RADIUS;
+use crate::selectable::SelectableCelestialBody;
+use crate::simtime::SimInstant;
+use crate::simulation_clock::{ensure_simulation_clock, SimulationClock};
+use crate::world_frame::WORLD_FRAME;
+
+const J2000: f64 = 2451545.0;
+const AU_KM: f64 = 149_597_870.7;
+const MOON_RADIUS_KM: f32 = 1737.4;
+
+/// Simulated date as a Julian date (UTC, the difference to TT is below the accuracy of the ephemerides)
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct SimulationDate(pub f64);
+
+impl SimulationDate {
+    pub fn from_system_time(time: SystemTime) -> Self {
+        Self::from_instant(SimInstant::from_system_time(time))
+    }
+
+    pub fn from_instant(instant: SimInstant) -> Self {
+        Self(J2000 + instant.ut_minutes_since_j2000() / 1440.0)
+    }
+
+    /// Days since J2000 on the UT scale, what the Earth rotation follows
+    pub fn days_since_j2000(&self) -> f64 {
+        self.0 - J2000
+    }
+}
+
+//days since J2000 and the obliquity of the ecliptic (degrees) at that time
+fn days_and_obliquity(julian_date: f64) -> (f64, f64) {
+    let days = julian_date - J2000;
+    (days, 23.439 - 0.0000004 * days)
+}
+
+fn ecliptic_to_equatorial(longitude: f64, latitude: f64, distance: f64, obliquity: f64) -> DVec3 {
+    let (longitude, latitude, obliquity) = (longitude.to_radians(), latitude.to_radians(), obliquity.to_radians());
+    let ecliptic = DVec3::new(latitude.cos() * longitude.cos(), latitude.cos() * longitude.sin(), latitude.sin()) * distance;
+    DVec3::new(
+        ecliptic.x,
+        ecliptic.y * obliquity.cos() - ecliptic.z * obliquity.sin(),
+        ecliptic.y * obliquity.sin() + ecliptic.z * obliquity.cos()
+    )
+}
+
+/// Geocentric position of the Sun in the inertial frame of the satellites (in kilometers).
+/// Low precision formulae of the Astronomical Almanac, about 0.01° between 1950 and 2050
+pub fn sun_position(julian_date: f64) -> DVec3 {
+    let (days, obliquity) = days_and_obliquity(julian_date);
+    let mean_longitude = 280.460 + 0.9856474 * days;
+    let mean_anomaly = (357.528 + 0.9856003 * days).to_radians();
+    let longitude = mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin();
+    let distance_au = 1.00014 - 0.01671 * mean_anomaly.cos() - 0.00014 * (2.0 * mean_anomaly).cos();
+    ecliptic_to_equatorial(longitude, 0.0, distance_au * AU_KM, obliquity)
+}
+
+/// Geocentric position of the Moon in the inertial frame of the satellites (in kilometers).
+/// Low precision formulae of the Astronomical Almanac, about 0.3° in direction and 0.2% in distance
+pub fn moon_position(julian_date: f64) -> DVec3 {
+    let (days, obliquity) = days_and_obliquity(julian_date);
+    let t = days / 36525.0;
+    let sin = |degrees: f64| degrees.to_radians().sin();
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let longitude = 218.32 + 481267.881 * t
+        + 6.29 * sin(135.0 + 477198.87 * t) - 1.27 * sin(259.3 - 413335.36 * t)
+        + 0.66 * sin(235.7 + 890534.22 * t) + 0.21 * sin(269.9 + 954397.74 * t)
+        - 0.19 * sin(357.5 + 35999.05 * t) - 0.11 * sin(186.5 + 966404.03 * t);
+    let latitude = 5.13 * sin(93.3 + 483202.02 * t) + 0.28 * sin(228.2 + 960400.89 * t)
+        - 0.28 * sin(318.3 + 6003.15 * t) - 0.17 * sin(217.6 - 407332.21 * t);
+    let parallax = 0.9508 + 0.0518 * cos(135.0 + 477198.87 * t) + 0.0095 * cos(259.3 - 413335.38 * t)
+        + 0.0078 * cos(235.7 + 890534.22 * t) + 0.0028 * cos(269.9 + 954397.70 * t);
+    ecliptic_to_equatorial(longitude, latitude, EARTH_EQUATORIAL_RADIUS / sin(parallax), obliquity)
+}
+
+/// Normal of the instantaneous orbit plane of the Moon, from its motion over two hours of [`moon_position`].
+/// Shares its accuracy, the plane is inclined about 5° to the ecliptic
+pub fn moon_orbit_normal(julian_date: f64) -> DVec3 {
+    const HOUR: f64 = 1.0 / 24.0;
+    let motion = moon_position(julian_date + HOUR) - moon_position(julian_date - HOUR);
+    moon_position(julian_date).cross(motion).normalize()
+}
+
+/// Pick target of the rendered Moon at `transform`, so that the camera can lock onto it
+pub fn moon_selectable(transform: Transform, date: &SimulationDate, scale: f32) -> SelectableCelestialBody<()> {
+    SelectableCelestialBody {
+        transform,
+        orbital_plane: InfinitePlane3d::new(WORLD_FRAME.to_world(moon_orbit_normal(date.0).as_vec3())),
+        radius: MOON_RADIUS_KM * scale,
+        data: ()
+    }
+}
+
+/// The real Sun and Moon, enabled by [`InGameSettings::ephemeris`]
+pub struct EphemerisPlugin;
+
+#[derive(Component, Debug)]
+pub struct Sun;
+
+#[derive(Component, Debug)]
+pub struct Moon;
+
+pub fn ephemeris_enabled(settings: Res<InGameSettings>) -> bool {
+    settings.ephemeris.is_some()
+}
+
+impl Plugin for EphemerisPlugin {
+    fn build(&self, app: &mut App) {
+        let rendering_condition = resource_exists::<Assets<Mesh>>.and_then(resource_exists::<Assets<StandardMaterial>>);
+        ensure_simulation_clock(app);
+        app
+            .register_command(
+                CommandDescriptor::new("Set Sun and Moon date", ActionCategory::Time, |params, world| {
+                    if let Some(time) = params[0].as_date_time() {
+                        world.insert_resource(SimulationDate::from_system_time(time));
+                    }
+                })
+                .with_param("date", ParamKind::DateTime)
+            )
+            .add_systems(Startup, setup_simulation_date.run_if(ephemeris_enabled))
+            //bodies are respawned after a teardown of the scene
+            .add_systems(Update, spawn_bodies.run_if(ephemeris_enabled.and_then(rendering_condition).and_then(not(any_with_component::<Sun>))))
+            .add_systems(Update, (advance_simulation_date, place_bodies).chain().after(spawn_bodies).run_if(resource_exists::<SimulationDate>));
+    }
+}
+
+fn setup_simulation_date(settings: Res<InGameSettings>, mut commands: Commands) {
+    if let Some(ephemeris) = &settings.ephemeris {
+        commands.insert_resource(SimulationDate::from_system_time(ephemeris.start));
+    }
+}
+
+fn spawn_bodies(mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, mut commands: Commands) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Sphere { radius: 12.0 }.mesh()),
+            material: materials.add(StandardMaterial { base_color: YELLOW.into(), emissive: LinearRgba::rgb(20.0, 18.0, 8.0), unlit: true, ..default() }),
+            ..default()
+        },
+        Sun
+    ));
+    //the Sun is far enough to light everything with parallel rays
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight { illuminance: light_consts::lux::FULL_DAYLIGHT, shadows_enabled: true, ..default() },
+            ..default()
+        },
+        Sun
+    ));
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Sphere { radius: MOON_RADIUS_KM }.mesh()),
+            material: materials.add(Color::from(LIGHT_GRAY)),
+            ..default()
+        },
+        Moon
+    ));
+}
+
+fn advance_simulation_date(clock: Res<SimulationClock>, mut date: ResMut<SimulationDate>) {
+    date.0 += clock.delta_seconds() / 86400.0;
+}
+
+//the Sun is the light of the scene as well, when it has one
+type SunBody<'a> = (&'a mut Transform, Has<DirectionalLight>);
+
+//true distances are impractical to render, bodies are clamped to the configured distance keeping their direction
+fn place_bodies(
+    date: Res<SimulationDate>,
+    settings: Res<InGameSettings>,
+    mut suns: Query<SunBody, (With<Sun>, Without<Moon>)>,
+    mut moons: Query<&mut Transform, (With<Moon>, Without<Sun>)>
+) {
+    let Some(ephemeris) = &settings.ephemeris else {
+        return;
+    };
+    let max_distance = ephemeris.max_display_distance_km as f64;
+    let sun = sun_position(date.0);
+    for (mut transform, is_light) in suns.iter_mut() {
+        let position = WORLD_FRAME.to_world((sun.normalize() * max_distance * settings.scale as f64).as_vec3());
+        *transform = if is_light {
+            Transform::from_translation(position).looking_at(Vec3::ZERO, WORLD_FRAME.north)
+        } else {
+            Transform::from_translation(position)
+        };
+    }
+
+    let moon = moon_position(date.0);
+    let moon_distance = moon.length().min(max_distance);
+    for mut transform in moons.iter_mut() {
+        transform.translation = WORLD_FRAME.to_world((moon.normalize() * moon_distance * settings.scale as f64).as_vec3());
+        transform.scale = Vec3::splat(settings.scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::selectable::Selectable;
+
+    fn angle_degrees(a: DVec3, b: DVec3) -> f64 {
+        a.angle_between(b).to_degrees()
+    }
+
+    fn direction(right_ascension: f64, declination: f64) -> DVec3 {
+        let (ra, dec) = (right_ascension.to_radians(), declination.to_radians());
+        DVec3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin())
+    }
+
+    #[test]
+    fn test_sun_direction_matches_almanac() {
+        //J2000.0, RA 18h45m09s, declination -23°02'
+        assert!(angle_degrees(sun_position(J2000), direction(281.29, -23.03)) < 1.0);
+        //March equinox 2024-03-20 03:06 UTC, the Sun crosses the equator towards the vernal point
+        let equinox = SimulationDate::from_system_time(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1710903960));
+        assert!(angle_degrees(sun_position(equinox.0), DVec3::X) < 1.0);
+        assert_abs_diff_eq!(sun_position(J2000).length() / AU_KM, 0.9833, epsilon = 0.001);
+    }
+
+    fn date(unix_seconds: u64) -> f64 {
+        SimulationDate::from_system_time(std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_seconds)).0
+    }
+
+    #[test]
+    fn test_moon_direction_at_eclipses() {
+        //greatest eclipses, the geocentric separation from the Sun or the shadow axis is below 0.4°
+        //total solar eclipse 2024-04-08 18:17 UTC
+        let solar = date(1712600236);
+        assert!(angle_degrees(moon_position(solar), sun_position(solar)) < 1.0);
+        //total lunar eclipse 2022-11-08 10:59 UTC
+        let lunar = date(1667905151);
+        assert!(angle_degrees(moon_position(lunar), -sun_position(lunar)) < 1.0);
+    }
+
+    #[test]
+    fn test_moon_orbit_inclined_to_ecliptic() {
+        let (_, obliquity) = days_and_obliquity(J2000);
+        let ecliptic_pole = DVec3::new(0.0, -obliquity.to_radians().sin(), obliquity.to_radians().cos());
+        for day in [0.0, 7.0, 3000.0, 6000.0] {
+            let inclination = angle_degrees(moon_orbit_normal(J2000 + day), ecliptic_pole);
+            assert!((4.5..6.0).contains(&inclination), "{inclination}");
+        }
+    }
+
+    #[test]
+    fn test_moon_is_selectable() {
+        let date = SimulationDate(J2000);
+        let position = (moon_position(date.0).normalize() * 4000.0).as_vec3();
+        let moon = moon_selectable(Transform::from_translation(position), &date, 0.01);
+        let camera = position + Vec3::new(300.0, -200.0, 150.0);
+        let towards = |target: Vec3| Ray3d::new(camera, target - camera);
+        assert!(moon.is_selected(towards(position)));
+        assert!(moon.is_selected(towards(position + Vec3::splat(10.0))));
+        assert!(!moon.is_selected(towards(position + Vec3::splat(100.0))));
+    }
+
+    #[test]
+    fn test_moon_within_orbit_bounds() {
+        for day in 0..60 {
+            let distance = moon_position(J2000 + day as f64 * 0.5).length();
+            assert!((356_000.0..407_000.0).contains(&distance), "{distance}");
+        }
+        //J2000.0, RA 14h27m, declination -10.9°
+        assert!(angle_degrees(moon_position(J2000), direction(222.0, -10.9)) < 1.0);
+    }
+}
@@ -0,0 +1,183 @@
+//Errors of the crate, every failure reaching the user or a downstream crate is a `SkytracioError`.
+//`Display` describes a single level, `SkytracioError::report` the whole chain for logs and notifications
+use std::{convert::Infallible, error::Error, fmt, io, path::PathBuf};
+
+use crate::propagation::DataSource;
+
+/// What failed to parse, the source of [`SkytracioError::Parse`]
+#[derive(Debug)]
+pub enum ParseError {
+    Json(serde_json::Error),
+    /// `block` are the lines of the element set
+    Tle { block: String, source: sgp4::TleError },
+    /// A byte that can't be a part of a JSON array of element sets
+    UnexpectedByte(u8)
+}
+
+#[derive(Debug)]
+pub enum SkytracioError {
+    /// Reading or writing a file, `path` when it's known
+    Io { path: Option<PathBuf>, source: io::Error },
+    /// A request that failed, `status` when the server answered
+    Http { url: String, status: Option<u16>, message: String, source: Option<Box<dyn Error + Send + Sync>> },
+    /// Element sets (or a scenario) that don't parse. `record` is the index of the failing one in the file or the body,
+    /// `offset` the byte it starts at
+    Parse { format: String, record: Option<usize>, offset: Option<usize>, source: ParseError },
+    /// The body ended before its announced length, or before the end of the element array without one
+    Incomplete { expected: Option<u64>, received: u64 },
+    UnsupportedFormat(String),
+    UnsupportedSource(DataSource),
+    /// SGP4 rejected the elements
+    Constants { norad_id: u64, source: sgp4::ElementsError },
+    Propagation { norad_id: u64, minutes: f64, source: sgp4::Error },
+    /// The orbit decayed before the time it was propagated to
+    Decayed { norad_id: u64, minutes: f64 },
+    /// Elements the simulation can't use, whatever SGP4 would say
+    InvalidElements { norad_id: u64, reason: String },
+    Export { path: PathBuf, source: io::Error },
+    /// A step of a tour that can't be played
+    Scenario { step: usize, reason: String },
+    /// Loading the group failed because of the source
+    Load { group: String, source: Box<SkytracioError> },
+    /// Failure of a loader without a more specific variant
+    Other(String)
+}
+
+impl SkytracioError {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io { path: Some(path.into()), source }
+    }
+
+    /// The whole chain of sources, separated by colons
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = self.source();
+        while let Some(error) = source {
+            report.push_str(": ");
+            report.push_str(&error.to_string());
+            source = error.source();
+        }
+        report
+    }
+
+    /// NORAD id of the satellite the error is about
+    pub fn norad_id(&self) -> Option<u64> {
+        match self {
+            Self::Constants { norad_id, .. } | Self::Propagation { norad_id, .. } | Self::Decayed { norad_id, .. } | Self::InvalidElements { norad_id, .. } => Some(*norad_id),
+            Self::Load { source, .. } => source.norad_id(),
+            _ => None
+        }
+    }
+
+    /// Group the error happened while loading
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            Self::Load { group, .. } => Some(group),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for SkytracioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path: Some(path), .. } => write!(f, "cannot access {}", path.display()),
+            Self::Io { path: None, .. } => write!(f, "input or output failed"),
+            Self::Http { url, status: Some(status), message, .. } => write!(f, "{url} answered {status} {message}"),
+            Self::Http { url, status: None, message, .. } => write!(f, "request to {url} failed, {message}"),
+            Self::Parse { format, record, offset, source } => {
+                match record {
+                    Some(record) => write!(f, "record {record} of the {format} data")?,
+                    None => write!(f, "the {format} data")?
+                }
+                if let Some(offset) = offset {
+                    write!(f, " at byte {offset}")?;
+                }
+                match source {
+                    ParseError::UnexpectedByte(byte) => write!(f, " has an unexpected byte {byte:#04x}"),
+                    ParseError::Tle { block, .. } => write!(f, " doesn't parse ({})", block.lines().collect::<Vec<_>>().join(" | ")),
+                    ParseError::Json(_) => write!(f, " doesn't parse")
+                }
+            },
+            Self::Incomplete { expected: Some(expected), received } => write!(f, "the body ended after {received} of {expected} bytes"),
+            Self::Incomplete { expected: None, received } => write!(f, "the body ended after {received} bytes, before the end of the element sets"),
+            Self::UnsupportedFormat(format) => write!(f, "{format} is not a supported elements format"),
+            Self::UnsupportedSource(source) => write!(f, "the client doesn't load {} element sets", source.label()),
+            Self::Constants { norad_id, .. } => write!(f, "SGP4 rejected the elements of {norad_id}"),
+            Self::Propagation { norad_id, minutes, .. } => write!(f, "propagation of {norad_id} to {minutes:.1} min after its epoch failed"),
+            Self::Decayed { norad_id, minutes } => write!(f, "{norad_id} decayed before {minutes:.1} min after its epoch"),
+            Self::InvalidElements { norad_id, reason } => write!(f, "elements of {norad_id} are invalid, {reason}"),
+            Self::Export { path, .. } => write!(f, "export to {} failed", path.display()),
+            Self::Scenario { step, reason } => write!(f, "step {step} of the tour is invalid, {reason}"),
+            Self::Load { group, .. } => write!(f, "loading {group} failed"),
+            Self::Other(message) => write!(f, "{message}")
+        }
+    }
+}
+
+impl Error for SkytracioError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } | Self::Export { source, .. } => Some(source),
+            Self::Http { source, .. } => source.as_deref().map(|source| source as &(dyn Error + 'static)),
+            Self::Parse { source: ParseError::Json(source), .. } => Some(source),
+            Self::Parse { source: ParseError::Tle { source, .. }, .. } => Some(source),
+            Self::Constants { source, .. } => Some(source),
+            Self::Propagation { source, .. } => Some(source),
+            Self::Load { source, .. } => Some(source.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for SkytracioError {
+    fn from(value: io::Error) -> Self {
+        Self::Io { path: None, source: value }
+    }
+}
+
+impl From<String> for SkytracioError {
+    fn from(value: String) -> Self {
+        Self::Other(value)
+    }
+}
+
+//loaders that can't fail
+impl From<Infallible> for SkytracioError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
+}
+
+impl From<DataSource> for SkytracioError {
+    fn from(value: DataSource) -> Self {
+        Self::UnsupportedSource(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_sources() {
+        let io = SkytracioError::io("assets/data/galileo.json", io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let load = SkytracioError::Load { group: "galileo".to_owned(), source: Box::new(io) };
+        assert_eq!(load.to_string(), "loading galileo failed");
+        assert_eq!(load.report(), "loading galileo failed: cannot access assets/data/galileo.json: no such file");
+        assert_eq!(load.group(), Some("galileo"));
+        let io = load.source().unwrap().downcast_ref::<SkytracioError>().unwrap();
+        assert_eq!(io.source().unwrap().downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::NotFound);
+
+        let decayed = SkytracioError::Decayed { norad_id: 70005, minutes: 1440.0 };
+        assert_eq!(decayed.to_string(), "70005 decayed before 1440.0 min after its epoch");
+        assert_eq!(decayed.norad_id(), Some(70005));
+        assert!(decayed.source().is_none());
+
+        let http = SkytracioError::Http { url: "https://celestrak.com/x".to_owned(), status: Some(404), message: "Not Found".to_owned(), source: None };
+        assert_eq!(http.report(), "https://celestrak.com/x answered 404 Not Found");
+        let parse = SkytracioError::Parse { format: "JSON".to_owned(), record: Some(2), offset: Some(17), source: ParseError::UnexpectedByte(b'x') };
+        assert_eq!(parse.report(), "record 2 of the JSON data at byte 17 has an unexpected byte 0x78");
+        assert_eq!(SkytracioError::from(DataSource::File).to_string(), "the client doesn't load file element sets");
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::{
+    math::DVec3,
+    pbr::SimulationLightSystems,
+    prelude::*,
+    render::view::VisibilitySystems,
+    transform::TransformSystem
+};
+
+use crate::camera::OverlayCamera;
+
+/// Renders the world relative to a point close to the camera. `Transform`s stay the authoritative (scaled ECI) positions,
+/// only the `GlobalTransform`s used for rendering are shifted, so far from the Earth the GPU works with small coordinates
+pub struct FloatingOriginPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct FloatingOrigin {
+    /// World position rendered at the origin
+    pub offset: DVec3,
+    /// The origin moves to the camera once it's further than this from it, in world units
+    pub recenter_distance: f64,
+    pub enabled: bool
+}
+
+impl Default for FloatingOrigin {
+    fn default() -> Self {
+        Self { offset: DVec3::ZERO, recenter_distance: 50.0, enabled: true }
+    }
+}
+
+impl FloatingOrigin {
+    /// World position to the shifted frame everything is rendered in, gizmos are drawn there as well
+    pub fn to_render(&self, world: Vec3) -> Vec3 {
+        (world.as_dvec3() - self.offset).as_vec3()
+    }
+
+    /// Position in the shifted frame (e.g. of a ray from a `GlobalTransform` of a camera) back to the world
+    pub fn to_world(&self, render: Vec3) -> Vec3 {
+        (render.as_dvec3() + self.offset).as_vec3()
+    }
+
+    //snaps instead of following every frame, the origin stays put while the camera hovers around a satellite
+    fn recenter(&mut self, focus: Vec3) -> bool {
+        let focus = focus.as_dvec3();
+        let far = focus.distance(self.offset) > self.recenter_distance;
+        if far {
+            self.offset = focus;
+        }
+        far
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShiftGlobalTransforms;
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FloatingOrigin>()
+            //the origin is settled before anything draws with it
+            .add_systems(PreUpdate, recenter_on_camera)
+            .configure_sets(PostUpdate, ShiftGlobalTransforms
+                .after(TransformSystem::TransformPropagate)
+                .before(VisibilitySystems::UpdateFrusta)
+                .before(VisibilitySystems::CheckVisibility)
+                .before(SimulationLightSystems::AssignLightsToClusters))
+            .add_systems(PostUpdate, shift_global_transforms.in_set(ShiftGlobalTransforms));
+    }
+}
+
+fn recenter_on_camera(mut origin: ResMut<FloatingOrigin>, cameras: Query<&Transform, (With<Camera3d>, Without<OverlayCamera>)>) {
+    let focus = match cameras.iter().next() {
+        Some(camera) if origin.enabled => camera.translation,
+        _ => Vec3::ZERO
+    };
+    if origin.bypass_change_detection().recenter(focus) {
+        origin.set_changed();
+    }
+}
+
+//propagation only rewrites the global transforms of moved entities, the others still carry the previously applied offset.
+//overlay scenes are rendered on their own, around their own origin
+fn shift_global_transforms(origin: Res<FloatingOrigin>, mut applied: Local<DVec3>, mut transforms: Query<&mut GlobalTransform, (Without<Node>, Without<OverlayCamera>)>) {
+    let moved = origin.offset - *applied;
+    if moved == DVec3::ZERO {
+        for mut transform in transforms.iter_mut().filter(|t| t.is_changed()) {
+            *transform = shifted(&transform, origin.offset);
+        }
+    } else {
+        for mut transform in transforms.iter_mut() {
+            let shift = if transform.is_changed() { origin.offset } else { moved };
+            *transform = shifted(&transform, shift);
+        }
+    }
+    *applied = origin.offset;
+}
+
+fn shifted(transform: &GlobalTransform, offset: DVec3) -> GlobalTransform {
+    let mut affine = transform.affine();
+    affine.translation = (Vec3::from(affine.translation).as_dvec3() - offset).as_vec3().into();
+    GlobalTransform::from(affine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_far_satellite_stable_in_shifted_frame() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TransformPlugin, FloatingOriginPlugin));
+        //geostationary radius at a scale of 1 world unit per kilometer
+        let radius = 42_164.0;
+        let position = |t: f32| Vec3::new(radius * (t * 1e-4).cos(), radius * (t * 1e-4).sin(), 0.0);
+        let behind = Vec3::new(0.0, 0.0, 10.0);
+        let satellite = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_translation(position(0.0)))).id();
+        let camera = app.world_mut().spawn((Camera3d::default(), TransformBundle::from_transform(Transform::from_translation(position(0.0) + behind)))).id();
+
+        for frame in 0..40 {
+            //the satellite doesn't move on every frame, its global transform isn't propagated then
+            let t = (frame / 2) as f32;
+            if frame % 2 == 0 {
+                app.world_mut().get_mut::<Transform>(satellite).unwrap().translation = position(t);
+                app.world_mut().get_mut::<Transform>(camera).unwrap().translation = position(t) + behind;
+            }
+            app.update();
+
+            let render = |entity: Entity| app.world().get::<GlobalTransform>(entity).unwrap().translation();
+            let (satellite_render, camera_render) = (render(satellite), render(camera));
+            assert!(camera_render.length() < 60.0, "{camera_render:?}");
+            assert!((satellite_render - camera_render + behind).length() < 1e-2, "frame {frame}: {:?}", satellite_render - camera_render);
+            //the authoritative position is kept
+            assert_eq!(app.world().get::<Transform>(satellite).unwrap().translation, position(t));
+            let origin = app.world().resource::<FloatingOrigin>();
+            assert!(origin.to_world(satellite_render).distance(position(t)) < 1e-2);
+        }
+    }
+}
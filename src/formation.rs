@@ -0,0 +1,375 @@
+use std::f64::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::{math::{DQuat, DVec3}, prelude::*};
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::propagation::{is_plausible_prediction, predict_at, Despawning, InGameElements, PropagatableDuration, Propageted};
+use crate::selection::find_satellite;
+use crate::simtime::SimInstant;
+
+/// Watches formation pairs (like GRACE-FO or the Sentinel pairs), how far the follower is from its slot behind the leader.
+/// Pairs are added with [`AddFormation`] or the "Add formation" command, in a tour file
+/// `{"command": "Add formation", "arguments": ["GRACE-FO 1", "GRACE-FO 2", "220 km"]}`
+pub struct FormationPlugin;
+
+/// Desired along-track separation of the follower behind the leader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Separation {
+    Seconds(f64),
+    Km(f64)
+}
+
+impl fmt::Display for Separation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Separation::Seconds(seconds) => write!(f, "{seconds} s"),
+            Separation::Km(km) => write!(f, "{km} km")
+        }
+    }
+}
+
+/// `30 s`, `30s` or `220 km`
+impl FromStr for Separation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (value, unit) = s.find(|c: char| c.is_ascii_alphabetic())
+            .map(|i| s.split_at(i))
+            .ok_or_else(|| format!("{s} has no unit, expected s or km"))?;
+        let value: f64 = value.trim().parse().map_err(|_| format!("{} is not a number", value.trim()))?;
+        if !value.is_finite() {
+            return Err(format!("{value} is not a separation"));
+        }
+        match unit {
+            "s" => Ok(Separation::Seconds(value)),
+            "km" => Ok(Separation::Km(value)),
+            unit => Err(format!("unknown unit {unit}, expected s or km"))
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct FormationSettings {
+    /// Distance from the slot a [`FormationDeviation`] is sent at
+    pub threshold_km: f64
+}
+
+impl Default for FormationSettings {
+    fn default() -> Self {
+        Self { threshold_km: 1.0 }
+    }
+}
+
+/// Starts watching the pair, replaces the separation of a pair already watched
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct AddFormation {
+    pub leader: Entity,
+    pub follower: Entity,
+    pub desired_separation: Separation
+}
+
+/// Sent when the follower gets further than the threshold from its slot, once until it's back within it
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct FormationDeviation {
+    pub leader: Entity,
+    pub follower: Entity,
+    /// Offset from the slot in the leader's RIC frame (in km)
+    pub deviation: DVec3
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formation {
+    pub leader: Entity,
+    pub follower: Entity,
+    pub desired_separation: Separation,
+    /// Offset from the slot in the leader's RIC frame (in km), from the latest propagation of the pair
+    pub deviation: Option<DVec3>,
+    exceeded: bool
+}
+
+/// Watched pairs, a pair is dropped once either satellite is gone
+#[derive(Resource, Debug, Default)]
+pub struct Formations(pub Vec<Formation>);
+
+/// Angle (in radians) the slot trails the leader by, a time separation at the mean motion (in radians per second),
+/// a distance along the arc at the leader's radius
+pub fn anomaly_offset(separation: Separation, mean_motion: f64, radius_km: f64) -> f64 {
+    match separation {
+        Separation::Seconds(seconds) => mean_motion * seconds,
+        Separation::Km(km) => km / radius_km
+    }
+}
+
+/// Components of `vector` along the radial, in-track and cross-track axes of a body at `position` moving with `velocity`
+pub fn to_ric(position: DVec3, velocity: DVec3, vector: DVec3) -> DVec3 {
+    let radial = position.normalize();
+    let cross_track = position.cross(velocity).normalize();
+    let in_track = cross_track.cross(radial);
+    DVec3::new(vector.dot(radial), vector.dot(in_track), vector.dot(cross_track))
+}
+
+/// Slot trailing the leader by `anomaly_offset` radians in its orbital plane, at its radius
+pub fn desired_slot(leader_position: DVec3, leader_velocity: DVec3, anomaly_offset: f64) -> DVec3 {
+    let normal = leader_position.cross(leader_velocity).normalize();
+    DQuat::from_axis_angle(normal, -anomaly_offset) * leader_position
+}
+
+/// Offset of the follower from its slot in the leader's RIC frame, `mean_motion` of the leader in radians per second
+pub fn formation_deviation(leader_position: DVec3, leader_velocity: DVec3, follower_position: DVec3, separation: Separation, mean_motion: f64) -> DVec3 {
+    let offset = anomaly_offset(separation, mean_motion, leader_position.length());
+    let slot = desired_slot(leader_position, leader_velocity, offset);
+    to_ric(leader_position, leader_velocity, follower_position - slot)
+}
+
+impl Plugin for FormationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FormationSettings>()
+            .init_resource::<Formations>()
+            .add_event::<AddFormation>()
+            .add_event::<FormationDeviation>()
+            .add_event::<Propageted>()
+            .register_command(
+                CommandDescriptor::new("Add formation", ActionCategory::General, |params, world| {
+                    let (Some(leader), Some(follower), Some(separation)) = (params[0].as_text(), params[1].as_text(), params[2].as_text()) else {
+                        return;
+                    };
+                    let desired_separation = match separation.parse() {
+                        Ok(separation) => separation,
+                        Err(err) => {
+                            warn!("Invalid formation separation: {err}");
+                            return;
+                        }
+                    };
+                    match (find_satellite(world, leader), find_satellite(world, follower)) {
+                        (Some(leader), Some(follower)) => {
+                            world.send_event(AddFormation { leader, follower, desired_separation });
+                        },
+                        _ => warn!("No satellites named {leader} and {follower}")
+                    }
+                })
+                .with_param("leader", ParamKind::Text)
+                .with_param("follower", ParamKind::Text)
+                .with_param("separation (s or km)", ParamKind::Text)
+            )
+            .add_systems(Update, (add_formations, monitor_formations).chain().run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, render_formations.after(monitor_formations).run_if(resource_changed::<Formations>));
+    }
+}
+
+fn add_formations(mut events: EventReader<AddFormation>, mut formations: ResMut<Formations>) {
+    for event in events.read() {
+        if event.leader == event.follower {
+            warn!("A formation needs two satellites, got {} twice", event.leader);
+            continue;
+        }
+        formations.0.retain(|f| (f.leader, f.follower) != (event.leader, event.follower));
+        formations.0.push(Formation {
+            leader: event.leader,
+            follower: event.follower,
+            desired_separation: event.desired_separation,
+            deviation: None,
+            exceeded: false
+        });
+    }
+}
+
+//recomputed whenever either satellite of a pair gets a new prediction
+fn monitor_formations(
+    mut propagated: EventReader<Propageted>,
+    settings: Res<InGameSettings>,
+    formation_settings: Res<FormationSettings>,
+    mut formations: ResMut<Formations>,
+    satellites: Query<(&InGameElements, &PropagatableDuration), Without<Despawning>>,
+    mut deviations: EventWriter<FormationDeviation>
+) {
+    let updated: Vec<Entity> = propagated.read().flat_map(|p| p.data().iter().map(|(entity, _)| *entity)).collect();
+    if formations.0.is_empty() {
+        return;
+    }
+    let before = formations.0.len();
+    formations.bypass_change_detection().0.retain(|f| satellites.contains(f.leader) && satellites.contains(f.follower));
+    if formations.0.len() != before {
+        formations.set_changed();
+    }
+    if updated.is_empty() {
+        return;
+    }
+
+    let numeric_fallback = settings.propagation.numeric_fallback;
+    let state = |entity: Entity, at: Option<SimInstant>| {
+        let (elements, elapsed) = satellites.get(entity).ok()?;
+        let at = at.unwrap_or_else(|| elapsed.instant(&elements.0));
+        predict_at(&elements.0, at.minutes_since(SimInstant::epoch(&elements.0)), numeric_fallback)
+            .filter(|p| is_plausible_prediction(p, &settings.propagation.envelope))
+            .map(|p| (at, DVec3::from_array(p.position), DVec3::from_array(p.velocity), elements.0.mean_motion * TAU / 86400.0))
+    };
+    for formation in formations.0.iter_mut().filter(|f| updated.contains(&f.leader) || updated.contains(&f.follower)) {
+        //both at the leader's time, the pair is compared at the same instant whatever their epochs
+        let Some((at, position, velocity, mean_motion)) = state(formation.leader, None) else {
+            continue;
+        };
+        let Some((_, follower, _, _)) = state(formation.follower, Some(at)) else {
+            continue;
+        };
+        let deviation = formation_deviation(position, velocity, follower, formation.desired_separation, mean_motion);
+        let exceeded = deviation.length() > formation_settings.threshold_km;
+        if exceeded && !formation.exceeded {
+            info!("Follower {} is {:.3} km from its slot behind {}", formation.follower, deviation.length(), formation.leader);
+            deviations.send(FormationDeviation { leader: formation.leader, follower: formation.follower, deviation });
+        }
+        formation.exceeded = exceeded;
+        formation.deviation = Some(deviation);
+    }
+}
+
+#[derive(Component)]
+struct FormationPanel;
+
+fn render_formations(
+    formations: Res<Formations>,
+    names: Query<&InGameElements>,
+    panels: Query<Entity, With<FormationPanel>>,
+    mut commands: Commands
+) {
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if formations.0.is_empty() {
+        return;
+    }
+    let name = |entity: Entity| names.get(entity).ok()
+        .and_then(|elements| elements.0.object_name.clone())
+        .unwrap_or_else(|| format!("{entity}"));
+    let lines: Vec<_> = formations.0.iter().map(|formation| {
+        let deviation = match formation.deviation {
+            Some(d) => format!("R {:+.3} I {:+.3} C {:+.3} km", d.x, d.y, d.z),
+            None => "waiting for propagation".to_owned()
+        };
+        format!("{} behind {} by {}: {deviation}", name(formation.follower), name(formation.leader), formation.desired_separation)
+    }).collect();
+    commands.spawn((
+        TextBundle::from_section(lines.join("\n"), TextStyle { font_size: 14.0, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(12.0),
+                right: Val::Px(12.0),
+                ..default()
+            }),
+        FormationPanel
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use approx::assert_abs_diff_eq;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::commands::{CommandsPlugin, InvokeCommand};
+    use crate::orbit::SatelliteOrbit;
+    use crate::propagation::{ElementsFormat, LoadElements, LoadElementsPlugin, PropagateElementsPlugin, PropagateInGamePlugin};
+    use crate::stress::SyntheticClient;
+    use crate::test_support::LEO;
+
+    #[test]
+    fn test_deviation_from_slot_of_coplanar_orbits() {
+        let leader = SatelliteOrbit::new(7000.0, 0.0, 51.6, 80.0, 0.0, 0.0, 0.0).propagate(1200.0);
+        let state = |orbit: &SatelliteOrbit| (orbit.position(), orbit.velocity());
+        let (position, velocity) = state(&leader);
+        let mean_motion = TAU / leader.orbital_period();
+
+        //a follower 30 s behind on the same orbit is in its slot
+        let (follower, _) = state(&leader.propagate(-30.0));
+        let deviation = formation_deviation(position, velocity, follower, Separation::Seconds(30.0), mean_motion);
+        assert!(deviation.length() < 0.01, "{deviation:?}");
+        //the same slot 30 s behind is about 226 km away along the track
+        let slot = desired_slot(position, velocity, anomaly_offset(Separation::Seconds(30.0), mean_motion, 7000.0));
+        assert_abs_diff_eq!(slot.distance(position), 7000.0 * mean_motion * 30.0, epsilon = 0.5);
+        let deviation = formation_deviation(position, velocity, follower, Separation::Km(slot.distance(position)), mean_motion);
+        assert!(deviation.length() < 0.05, "{deviation:?}");
+
+        //lagging 10 s more than desired is behind the slot in the plane, the slot is turned from the leader's frame
+        //so the gap isn't purely in-track
+        let (follower, _) = state(&leader.propagate(-40.0));
+        let deviation = formation_deviation(position, velocity, follower, Separation::Seconds(30.0), mean_motion);
+        assert_abs_diff_eq!(deviation.length(), 7000.0 * mean_motion * 10.0, epsilon = 0.5);
+        assert!(deviation.y < -75.0, "{deviation:?}");
+        assert_abs_diff_eq!(deviation.z, 0.0, epsilon = 0.01);
+
+        //a slightly higher and tilted orbit shows up in the radial and cross-track components
+        let higher = SatelliteOrbit::new(7002.0, 0.0, 51.61, 80.0, 0.0, 0.0, 0.0);
+        let (follower, _) = state(&SatelliteOrbit { true_anomaly: leader.true_anomaly, ..higher });
+        let deviation = formation_deviation(position, velocity, follower, Separation::Seconds(0.0), mean_motion);
+        assert_abs_diff_eq!(deviation.x, 2.0, epsilon = 0.05);
+        assert!(deviation.z.abs() > 0.1, "{deviation:?}");
+
+        assert_eq!("30 s".parse(), Ok(Separation::Seconds(30.0)));
+        assert_eq!("220km".parse(), Ok(Separation::Km(220.0)));
+        assert!("220".parse::<Separation>().is_err());
+        assert!("2 h".parse::<Separation>().is_err());
+    }
+
+    #[test]
+    fn test_deviation_events_per_propagation() {
+        //the follower trails by 30 s of mean motion
+        let lag = 360.0 * 15.5 / 86400.0 * 30.0;
+        //near circular and without drag, the lag in mean anomaly stays a constant lag in time
+        let pair = |norad_id: u64, mean_anomaly: f64| LEO.builder()
+            .name(format!("PAIR-{norad_id}")).norad_id(norad_id)
+            .mean_motion(15.5).eccentricity(0.0001).inclination(89.0).mean_anomaly(mean_anomaly).bstar(0.0)
+            .build();
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, CommandsPlugin, LoadElementsPlugin::<SyntheticClient>::new(), PropagateElementsPlugin, PropagateInGamePlugin, FormationPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)))
+            .insert_resource(SyntheticClient(vec![pair(40001, 40.0), pair(40002, 40.0 - lag)]))
+            .insert_resource(InGameSettings::default());
+        app.world_mut().send_event(LoadElements::group("pair", ElementsFormat::Json));
+        for _ in 0..100 {
+            app.update();
+            if app.world_mut().query::<&InGameElements>().iter(app.world()).count() == 2 {
+                break;
+            }
+        }
+        app.world_mut().send_event(InvokeCommand { name: "Add formation".to_owned(), arguments: vec!["PAIR-40001".to_owned(), "40002".to_owned(), "30 s".to_owned()] });
+        app.update();
+        let (leader, follower) = {
+            let formation = &app.world().resource::<Formations>().0[0];
+            (formation.leader, formation.follower)
+        };
+        assert_eq!(app.world().get::<InGameElements>(follower).unwrap().0.norad_id, 40002);
+        let mut reader = app.world().resource::<Events<FormationDeviation>>().get_reader();
+        let mut run = |app: &mut App| {
+            let mut events = vec![];
+            for _ in 0..20 {
+                app.update();
+                events.extend(reader.read(app.world().resource::<Events<FormationDeviation>>()).copied());
+            }
+            events
+        };
+        assert!(run(&mut app).is_empty());
+        let deviation = app.world().resource::<Formations>().0[0].deviation.unwrap();
+        assert!(deviation.length() < 0.5, "{deviation:?}");
+
+        //a desired separation 5 s shorter puts the slot about 38 km ahead, reported once
+        app.world_mut().send_event(AddFormation { leader, follower, desired_separation: Separation::Seconds(25.0) });
+        let events = run(&mut app);
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert_eq!((events[0].leader, events[0].follower), (leader, follower));
+        assert!(events[0].deviation.y < -30.0, "{events:?}");
+        assert_eq!(app.world().resource::<Formations>().0.len(), 1);
+
+        //the pair is dropped with its follower
+        app.world_mut().despawn(follower);
+        app.update();
+        assert!(app.world().resource::<Formations>().0.is_empty());
+    }
+}
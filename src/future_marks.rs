@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use bevy::{color::palettes::css::*, prelude::*};
+
+use crate::camera::OverlayCamera;
+use crate::floating_origin::FloatingOrigin;
+use crate::global::InGameSettings;
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::orbit::SatelliteOrbit;
+use crate::propagation::{is_plausible_prediction, predict_at, InGameElements, PropagatableDuration, EARTH_RADIUS_KM};
+use crate::selection::SelectionSet;
+use crate::simtime;
+use crate::simulation_clock::{ensure_simulation_clock, SimulationClock};
+use crate::world_frame::WORLD_FRAME;
+
+//marks are recomputed at least once per this many simulated seconds
+const RECOMPUTE_INTERVAL: f32 = 60.0;
+
+/// Marks the predicted positions of the primary selected satellite at fixed times ahead
+pub struct FutureMarksPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct FutureMarksSettings {
+    pub enabled: bool,
+    pub offsets: Vec<Duration>,
+    /// Marks further ahead are omitted, predictions lose their meaning long before SGP4 fails
+    pub horizon: Duration,
+    pub color: Color,
+    pub size: f32
+}
+
+impl Default for FutureMarksSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            offsets: [10, 30, 60].map(|minutes| Duration::from_secs(minutes * 60)).to_vec(),
+            horizon: Duration::from_secs(24 * 60 * 60),
+            color: ORANGE.into(),
+            size: 0.6
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FutureMark {
+    pub offset: Duration,
+    /// Scaled, in world space
+    pub position: Vec3
+}
+
+/// Marks of the satellite `of`, as computed at the last recompute
+#[derive(Resource, Debug, Default)]
+pub struct FutureMarks {
+    pub of: Option<Entity>,
+    pub marks: Vec<FutureMark>
+}
+
+impl MemoryFootprint for FutureMarks {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.marks.len() * size_of::<FutureMark>()
+    }
+}
+
+#[derive(Component)]
+struct FutureMarkLabel(usize);
+
+/// Positive offsets within the horizon, sorted and without duplicates
+pub fn mark_times(offsets: &[Duration], horizon: Duration) -> Vec<Duration> {
+    let mut times: Vec<_> = offsets.iter().copied().filter(|o| !o.is_zero() && *o <= horizon).collect();
+    times.sort();
+    times.dedup();
+    times
+}
+
+/// Short label of a mark, e.g. "+10m", "+1h", "+1h30m"
+pub fn mark_label(offset: Duration) -> String {
+    let seconds = offset.as_secs();
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => format!("+{}s", seconds),
+        (0, minutes) => format!("+{}m", minutes),
+        (hours, 0) => format!("+{}h", hours),
+        (hours, minutes) => format!("+{}h{:02}m", hours, minutes)
+    }
+}
+
+impl Plugin for FutureMarksPlugin {
+    fn build(&self, app: &mut App) {
+        ensure_simulation_clock(app);
+        app
+            .init_resource::<FutureMarksSettings>()
+            .init_resource::<FutureMarks>()
+            .account_resource::<FutureMarks>("look_ahead")
+            .init_resource::<SelectionSet>()
+            .init_resource::<FloatingOrigin>()
+            .add_systems(Update, update_future_marks.run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, (draw_future_marks, place_future_mark_labels).after(update_future_marks).run_if(resource_exists::<GizmoConfigStore>));
+    }
+}
+
+fn update_future_marks(
+    clock: Res<SimulationClock>,
+    marks_settings: Res<FutureMarksSettings>,
+    settings: Res<InGameSettings>,
+    selection: Res<SelectionSet>,
+    (mut marks, mut since_update): (ResMut<FutureMarks>, Local<f32>),
+    loaded: Query<(Ref<InGameElements>, &PropagatableDuration)>,
+    keplerian: Query<(&Transform, &SatelliteOrbit)>
+) {
+    let focused = selection.primary().filter(|_| marks_settings.enabled);
+    *since_update += clock.delta_seconds() as f32;
+    let elements_changed = focused.and_then(|e| loaded.get(e).ok()).is_some_and(|(elements, _)| elements.is_changed());
+    let stale = *since_update >= RECOMPUTE_INTERVAL || marks_settings.is_changed() || settings.is_changed();
+    if focused == marks.of && !elements_changed && !stale {
+        return;
+    }
+
+    marks.of = focused;
+    *since_update = 0.0;
+    marks.marks.clear();
+    let Some(entity) = focused else {
+        return;
+    };
+    let times = mark_times(&marks_settings.offsets, marks_settings.horizon);
+    //loaded satellites follow SGP4, the orbit they carry is only the epoch one
+    if let Ok((elements, elapsed)) = loaded.get(entity) {
+        for offset in times {
+            let minutes = elapsed.minutes_since_epoch() + simtime::minutes(offset);
+            let prediction = predict_at(&elements.0, minutes, settings.propagation.numeric_fallback)
+                .filter(|p| is_plausible_prediction(p, &settings.propagation.envelope));
+            //decayed or diverged, later marks are not any better
+            let Some(prediction) = prediction else {
+                break;
+            };
+            let position = WORLD_FRAME.to_world(Vec3::from_array(prediction.position.map(|c| c as f32))) * settings.scale;
+            marks.marks.push(FutureMark { offset, position });
+        }
+    } else if let Ok((transform, orbit)) = keplerian.get(entity) {
+        let orbit = SatelliteOrbit { true_anomaly: orbit.true_anomaly_at(WORLD_FRAME.to_inertial(transform.translation).as_dvec3()), ..orbit.clone() };
+        for offset in times {
+            let position = WORLD_FRAME.to_world(orbit.propagate(offset.as_secs_f64()).to_translation_and_rotation().position);
+            if position.length() < EARTH_RADIUS_KM {
+                break;
+            }
+            marks.marks.push(FutureMark { offset, position: position * settings.scale });
+        }
+    }
+}
+
+fn draw_future_marks(mut gizmos: Gizmos, marks_settings: Res<FutureMarksSettings>, marks: Res<FutureMarks>, origin: Res<FloatingOrigin>) {
+    let size = marks_settings.size;
+    for mark in &marks.marks {
+        let position = origin.to_render(mark.position);
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            gizmos.line(position - axis * size, position + axis * size, marks_settings.color);
+        }
+    }
+}
+
+type PlacedLabel<'a> = (Entity, &'a FutureMarkLabel, &'a mut Style, &'a mut Visibility);
+
+//labels are screen space text following the marks, respawned whenever the marks change
+fn place_future_mark_labels(
+    marks: Res<FutureMarks>,
+    origin: Res<FloatingOrigin>,
+    cameras: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    mut labels: Query<PlacedLabel>,
+    mut commands: Commands
+) {
+    if marks.is_changed() {
+        for (entity, _, _, _) in labels.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        for (index, mark) in marks.marks.iter().enumerate() {
+            commands.spawn((
+                TextBundle::from_section(mark_label(mark.offset), TextStyle { font_size: 12.0, ..default() })
+                    .with_style(Style { position_type: PositionType::Absolute, ..default() }),
+                FutureMarkLabel(index)
+            ));
+        }
+        return;
+    }
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    for (_, label, mut style, mut visibility) in labels.iter_mut() {
+        match marks.marks.get(label.0).and_then(|m| camera.world_to_viewport(camera_transform, origin.to_render(m.position))) {
+            Some(point) => {
+                style.left = Val::Px(point.x + 6.0);
+                style.top = Val::Px(point.y - 6.0);
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_mark_times_and_labels() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let times = mark_times(&[minutes(60), Duration::ZERO, minutes(10), minutes(30), minutes(10), minutes(3000)], minutes(24 * 60));
+        assert_eq!(times, vec![minutes(10), minutes(30), minutes(60)]);
+        let labels: Vec<_> = [minutes(10), minutes(60), minutes(90), Duration::from_secs(45)].map(mark_label).to_vec();
+        assert_eq!(labels, vec!["+10m", "+1h", "+1h30m", "+45s"]);
+    }
+
+    #[test]
+    fn test_half_period_mark_opposite_the_satellite() {
+        let orbit = SatelliteOrbit::new(7000.0, 0.0, 51.6, 40.0, 10.0, 30.0, 0.0);
+        let half_period = Duration::from_secs_f64(orbit.orbital_period() / 2.0);
+        let position = orbit.to_translation_and_rotation().position * 0.01;
+
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, FutureMarksPlugin))
+            .insert_resource(FutureMarksSettings { offsets: vec![half_period], ..default() })
+            .insert_resource(InGameSettings::default());
+        let satellite = app.world_mut().spawn((Transform::from_translation(position), orbit)).id();
+        app.world_mut().resource_mut::<SelectionSet>().select_single(satellite);
+        app.update();
+
+        let marks = app.world().resource::<FutureMarks>();
+        assert_eq!(marks.of, Some(satellite));
+        assert_eq!(marks.marks.len(), 1);
+        let mark = marks.marks[0].position;
+        assert_abs_diff_eq!(mark.x, -position.x, epsilon = 0.05);
+        assert_abs_diff_eq!(mark.y, -position.y, epsilon = 0.05);
+        assert_abs_diff_eq!(mark.z, -position.z, epsilon = 0.05);
+    }
+}
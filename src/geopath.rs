@@ -0,0 +1,276 @@
+use bevy::math::DVec3;
+
+//latitudes closer to a pole than this have no meaningful longitude (cosine of the latitude)
+const POLE_EPSILON: f64 = 1e-9;
+//a single step between samples is split into at most 2^MAX_DEPTH pieces
+const MAX_DEPTH: usize = 12;
+
+/// Point on the globe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    /// Geocentric latitude (in degrees)
+    pub latitude: f64,
+    /// Longitude (in degrees, east positive)
+    pub longitude: f64
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self { latitude, longitude }
+    }
+
+    /// Unit vector, X towards the prime meridian and Z towards the north pole
+    pub fn to_unit(&self) -> DVec3 {
+        let (latitude, longitude) = (self.latitude.to_radians(), self.longitude.to_radians());
+        DVec3::new(latitude.cos() * longitude.cos(), latitude.cos() * longitude.sin(), latitude.sin())
+    }
+
+    //at a pole the longitude is kept from the previous point, so it doesn't snap to 0
+    fn from_unit(unit: DVec3, longitude_at_pole: f64) -> Self {
+        let unit = unit.normalize();
+        let latitude = unit.z.clamp(-1.0, 1.0).asin().to_degrees();
+        let longitude = if unit.x.hypot(unit.y) < POLE_EPSILON { longitude_at_pole } else { unit.y.atan2(unit.x).to_degrees() };
+        Self { latitude, longitude }
+    }
+}
+
+/// Longitude difference from `from` to `to` the short way around (in degrees, -180..180)
+pub fn longitude_difference(from: f64, to: f64) -> f64 {
+    (to - from + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Splits paths on the globe into pieces that draw cleanly: on a 2D map they are cut at the anti-meridian of the
+/// central meridian, on the 3D surface they follow the sphere instead of cutting through it. Steps between samples are
+/// densified along great circles, near the poles the longitude changes quickly and gets more points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPath {
+    /// Longitude in the middle of the 2D map (in degrees), paths are cut 180° away from it
+    pub central_meridian: f64,
+    /// Longest great-circle step between points (in degrees)
+    pub max_step: f64,
+    /// Largest change of longitude between points (in degrees)
+    pub max_longitude_step: f64
+}
+
+impl Default for GeoPath {
+    fn default() -> Self {
+        Self { central_meridian: 0.0, max_step: 2.0, max_longitude_step: 5.0 }
+    }
+}
+
+impl GeoPath {
+    pub fn centered_on(central_meridian: f64) -> Self {
+        Self { central_meridian, ..Self::default() }
+    }
+
+    /// Inserts great-circle points between samples further apart than the steps allow
+    pub fn densify(&self, points: &[GeoPoint]) -> Vec<GeoPoint> {
+        let mut dense = Vec::with_capacity(points.len());
+        let Some(first) = points.first() else {
+            return dense;
+        };
+        dense.push(*first);
+        for pair in points.windows(2) {
+            self.bisect(pair[0], pair[1], 0, &mut dense);
+        }
+        dense
+    }
+
+    //halves the step until it's short enough, the longitude changes unevenly along a great circle near the poles
+    fn bisect(&self, a: GeoPoint, b: GeoPoint, depth: usize, dense: &mut Vec<GeoPoint>) {
+        let (unit_a, unit_b) = (a.to_unit(), b.to_unit());
+        let angle = unit_a.angle_between(unit_b);
+        let short = angle.to_degrees() <= self.max_step && longitude_difference(a.longitude, b.longitude).abs() <= self.max_longitude_step;
+        //antipodal points have no single great circle between them, there's nothing better than the chord
+        if short || depth >= MAX_DEPTH || angle.sin() < 1e-12 {
+            dense.push(b);
+            return;
+        }
+        let middle = GeoPoint::from_unit(unit_a + unit_b, a.longitude);
+        self.bisect(a, middle, depth + 1, dense);
+        self.bisect(middle, b, depth + 1, dense);
+    }
+
+    //longitude relative to the central meridian, -180..180
+    fn offset(&self, longitude: f64) -> f64 {
+        longitude_difference(self.central_meridian, longitude)
+    }
+
+    /// Polylines for the 2D map with longitudes within 180° of the central meridian. A path crossing the
+    /// anti-meridian ends on it and continues from the other edge of the map, at the interpolated latitude
+    pub fn map_segments(&self, points: &[GeoPoint]) -> Vec<Vec<GeoPoint>> {
+        let mut segments: Vec<Vec<GeoPoint>> = vec![];
+        let mut current: Vec<GeoPoint> = vec![];
+        let mut previous: Option<(f64, f64)> = None;
+        for point in self.densify(points) {
+            let x = self.offset(point.longitude);
+            if let Some((latitude, previous_x)) = previous {
+                if (x - previous_x).abs() > 180.0 {
+                    let edge = if previous_x > 0.0 { 180.0 } else { -180.0 };
+                    let unwrapped = x + 2.0 * edge;
+                    let t = (edge - previous_x) / (unwrapped - previous_x);
+                    let crossing = latitude + t * (point.latitude - latitude);
+                    let end = GeoPoint::new(crossing, self.central_meridian + edge);
+                    if current.last() != Some(&end) {
+                        current.push(end);
+                    }
+                    segments.push(std::mem::take(&mut current));
+                    current.push(GeoPoint::new(crossing, self.central_meridian - edge));
+                }
+            }
+            let mapped = GeoPoint::new(point.latitude, self.central_meridian + x);
+            if current.last() != Some(&mapped) {
+                current.push(mapped);
+            }
+            previous = Some((point.latitude, x));
+        }
+        if !current.is_empty() {
+            segments.push(current);
+        }
+        segments.retain(|segment| segment.len() > 1);
+        segments
+    }
+
+    /// Closed outlines of a region on the 2D map, each implicitly closed. A region straddling the anti-meridian
+    /// is split in one outline per side, closed along the edge. A region enclosing a pole crosses it an odd number
+    /// of times, its outline runs from edge to edge and is closed through the pole
+    pub fn map_outline(&self, ring: &[GeoPoint]) -> Vec<Vec<GeoPoint>> {
+        let Some(first) = ring.first() else {
+            return vec![];
+        };
+        let closed: Vec<GeoPoint> = ring.iter().chain([first]).copied().collect();
+        let mut pieces = self.map_segments(&closed);
+        if pieces.len() < 2 {
+            return pieces;
+        }
+        //the path starts and ends at the same point, the last piece continues into the first
+        let mut head = pieces.remove(0);
+        let mut joined = pieces.pop().unwrap();
+        if joined.last() == head.first() {
+            head.remove(0);
+        }
+        joined.append(&mut head);
+        let crossings = pieces.len() + 1;
+        if crossings % 2 == 1 {
+            let mean_latitude = ring.iter().map(|p| p.latitude).sum::<f64>() / ring.len() as f64;
+            let pole = if mean_latitude >= 0.0 { 90.0 } else { -90.0 };
+            let (start, end) = (joined[0].longitude, joined[joined.len() - 1].longitude);
+            joined.extend([GeoPoint::new(pole, end), GeoPoint::new(pole, start)]);
+        }
+        pieces.insert(0, joined);
+        pieces
+    }
+
+    /// Points on a sphere of `radius`, dense enough that the chords between them stay near the surface
+    pub fn surface(&self, points: &[GeoPoint], radius: f64) -> Vec<DVec3> {
+        self.densify(points).iter().map(|point| point.to_unit() * radius).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    //circular orbit over a turning Earth, `days` is the period in sidereal days
+    fn track(inclination: f64, days: f64, start_longitude: f64, samples: usize, orbits: f64) -> Vec<GeoPoint> {
+        let inclination = inclination.to_radians();
+        (0..=samples)
+            .map(|i| {
+                let phase = TAU * orbits * i as f64 / samples as f64;
+                let position = DVec3::new(phase.cos(), phase.sin() * inclination.cos(), phase.sin() * inclination.sin());
+                let turned = position.y.atan2(position.x) - phase * days;
+                GeoPoint::new(position.z.asin().to_degrees(), longitude_difference(0.0, start_longitude + turned.to_degrees()))
+            })
+            .collect()
+    }
+
+    //no step jumps across the map, the bogus horizontal lines
+    fn assert_continuous(segments: &[Vec<GeoPoint>], path: &GeoPath) {
+        for segment in segments {
+            for pair in segment.windows(2) {
+                assert!((pair[1].longitude - pair[0].longitude).abs() <= path.max_longitude_step + 1e-9, "{pair:?}");
+                assert!((path.central_meridian - 180.0 - 1e-9..=path.central_meridian + 180.0 + 1e-9).contains(&pair[0].longitude));
+            }
+        }
+    }
+
+    #[test]
+    fn test_retrograde_track_is_cut_at_the_seam_twice_per_orbit() {
+        //a retrograde orbit with a period of a sidereal day runs 720° west over the ground each orbit
+        let samples = track(150.0, 1.0, 10.0, 97, 1.0);
+        let path = GeoPath::default();
+        let segments = path.map_segments(&samples);
+        assert_eq!(segments.len(), 3);
+        assert_continuous(&segments, &path);
+        for pair in segments.windows(2) {
+            let (end, start) = (pair[0].last().unwrap(), pair[1].first().unwrap());
+            assert_eq!(end.latitude, start.latitude);
+            assert_eq!((end.longitude, start.longitude), (-180.0, 180.0));
+        }
+
+        //a map centered on the Pacific cuts the same track elsewhere
+        let pacific = GeoPath::centered_on(150.0);
+        let segments = pacific.map_segments(&samples);
+        assert_eq!(segments.len(), 3);
+        assert_continuous(&segments, &pacific);
+        assert_eq!(segments[0].last().unwrap().longitude, -30.0);
+        assert_eq!(segments[1].first().unwrap().longitude, 330.0);
+    }
+
+    #[test]
+    fn test_track_near_the_pole_is_densified() {
+        //sparse samples over a polar orbit passing within 0.5° of the pole
+        let samples = track(89.6, 1.0 / 15.0, 0.0, 26, 1.0);
+        let coarse_max = samples.iter().map(|p| p.latitude).fold(f64::MIN, f64::max);
+        assert!(coarse_max < 89.0, "{coarse_max}");
+
+        let path = GeoPath::default();
+        let dense = path.densify(&samples);
+        let max_latitude = dense.iter().map(|p| p.latitude).fold(f64::MIN, f64::max);
+        assert!(max_latitude > 89.5 && max_latitude < 90.0, "{max_latitude}");
+        for pair in dense.windows(2) {
+            assert!(pair[0].to_unit().angle_between(pair[1].to_unit()).to_degrees() <= path.max_step + 1e-9);
+            assert!(longitude_difference(pair[0].longitude, pair[1].longitude).abs() <= path.max_longitude_step + 1e-9);
+        }
+        assert_continuous(&path.map_segments(&samples), &path);
+
+        //on the globe the chords between the points stay close to the surface
+        let radius = 6378.0;
+        let surface = path.surface(&samples, radius);
+        assert_eq!(surface.len(), dense.len());
+        for pair in surface.windows(2) {
+            assert_abs_diff_eq!(pair[0].length(), radius, epsilon = 1e-6);
+            assert!(((pair[0] + pair[1]) / 2.0).length() > radius * 0.9998);
+        }
+    }
+
+    #[test]
+    fn test_outline_enclosing_a_pole_is_closed_through_it() {
+        let ring: Vec<_> = (0..12).map(|i| GeoPoint::new(-80.0, 30.0 * i as f64 + 15.0)).collect();
+        let path = GeoPath::default();
+        let outlines = path.map_outline(&ring);
+        assert_eq!(outlines.len(), 1);
+        let outline = &outlines[0];
+        //but for the edge along the pole
+        assert_continuous(&[outline[..outline.len() - 2].to_vec()], &path);
+        assert_eq!(outline.first().unwrap().longitude, -180.0);
+        assert_eq!(&outline[outline.len() - 3..], &[
+            GeoPoint::new(outline[outline.len() - 3].latitude, 180.0),
+            GeoPoint::new(-90.0, 180.0),
+            GeoPoint::new(-90.0, -180.0)
+        ]);
+
+        //a box across the seam makes one outline per side, neither reaches a pole
+        let ring = [GeoPoint::new(10.0, 170.0), GeoPoint::new(10.0, -170.0), GeoPoint::new(20.0, -170.0), GeoPoint::new(20.0, 170.0)];
+        let outlines = path.map_outline(&ring);
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines[0].iter().all(|p| p.longitude >= 170.0 - 1e-9));
+        assert!(outlines[1].iter().all(|p| p.longitude <= -170.0 + 1e-9));
+        //the great circles bulge a little poleward of the corners
+        assert!(outlines.iter().flatten().all(|p| (10.0 - 1e-9..20.5).contains(&p.latitude)));
+    }
+}
@@ -1,16 +1,102 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use bevy::prelude::Resource;
+use bevy::prelude::{Color, Resource};
+
+use crate::orbit::EARTH_EQUATORIAL_RADIUS;
 
 
 #[derive(Resource)]
 pub struct InGameSettings {
     pub scale: f32,
     pub simulation_speed: f32,
-    pub propagation: PropagationSettings
+    pub propagation: PropagationSettings,
+    pub altitude_bands: Vec<AltitudeBand>,
+    /// Real Sun and Moon from analytic ephemerides, `None` keeps the static light
+    pub ephemeris: Option<EphemerisSettings>
+}
+
+impl Default for InGameSettings {
+    fn default() -> Self {
+        Self { scale: 0.01, simulation_speed: 1.0, propagation: PropagationSettings::default(), altitude_bands: vec![], ephemeris: None }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EphemerisSettings {
+    /// Date the simulation starts at
+    pub start: SystemTime,
+    /// Sun and Moon are drawn at most this far, keeping their direction (in kilometers)
+    pub max_display_distance_km: f32
 }
 
 pub struct PropagationSettings {
     pub real_time_interval: Duration,
-    pub batch_size: usize
-}
\ No newline at end of file
+    pub batch_size: usize,
+    /// Integrate a two-body orbit for satellites SGP4 fails on instead of dropping them
+    pub numeric_fallback: bool,
+    /// Spread large corrections over several frames instead of snapping to the new prediction
+    pub smoothing: Option<CorrectionSmoothing>,
+    pub envelope: PredictionEnvelope,
+    /// Real time predictions are computed ahead of the simulation, the displayed position is interpolated
+    /// towards them instead of extrapolated from the last one. Usually the interval, zero disables it
+    pub lookahead: Duration,
+    pub staleness: StalenessGuard
+}
+
+impl Default for PropagationSettings {
+    fn default() -> Self {
+        Self {
+            real_time_interval: Duration::from_secs(1), batch_size: 10, numeric_fallback: false, smoothing: None,
+            envelope: PredictionEnvelope::default(), lookahead: Duration::ZERO, staleness: StalenessGuard::default()
+        }
+    }
+}
+
+/// Satellites left without predictions (decayed, failing or raced by an unload), in multiples of the interval
+#[derive(Clone, Copy, Debug)]
+pub struct StalenessGuard {
+    /// The dead reckoning stops and the satellite is shown as stale
+    pub freeze_after: f32,
+    /// The satellite can't be followed anymore, a camera locked on it goes back to the Earth
+    pub release_after: f32
+}
+
+impl Default for StalenessGuard {
+    fn default() -> Self {
+        Self { freeze_after: 3.0, release_after: 10.0 }
+    }
+}
+
+/// Predictions outside of the envelope are rejected as corrupt
+#[derive(Clone, Copy, Debug)]
+pub struct PredictionEnvelope {
+    pub min_radius_km: f64,
+    pub max_radius_km: f64,
+    /// Consecutive rejections after which the satellite is marked unreliable
+    pub max_rejections: u32
+}
+
+impl Default for PredictionEnvelope {
+    fn default() -> Self {
+        Self { min_radius_km: EARTH_EQUATORIAL_RADIUS, max_radius_km: 2.0e6, max_rejections: 3 }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CorrectionSmoothing {
+    /// Corrections up to this distance still snap
+    pub max_jump_km: f32,
+    pub frames: u32
+}
+
+/// Named altitude shell, altitudes are measured above the Earth surface (in kilometers)
+#[derive(Clone, Debug)]
+pub struct AltitudeBand {
+    pub name: String,
+    pub min_km: f32,
+    pub max_km: f32,
+    /// Margin applied on both edges before a member is considered to have left the band
+    pub hysteresis_km: f32,
+    /// Optional color applied to satellites while they are inside the band
+    pub tint: Option<Color>
+}
@@ -0,0 +1,125 @@
+use bevy::{color::palettes::css::*, prelude::*};
+
+use crate::floating_origin::FloatingOrigin;
+use crate::geopath::{GeoPath, GeoPoint};
+use crate::global::InGameSettings;
+use crate::orbit::SatelliteOrbit;
+use crate::propagation::EARTH_RADIUS_KM;
+use crate::selection::SelectionSet;
+use crate::world_frame::WORLD_FRAME;
+
+/// Draws the predicted ground track of the primary selected satellite for its next orbit
+pub struct GroundTrackPlugin;
+
+#[derive(Resource, Debug, Clone)]
+pub struct GroundTrackSettings {
+    pub enabled: bool,
+    /// Samples over one period, dense enough that the chords stay close to the surface
+    pub samples: usize,
+    pub color: Color,
+    /// Relative lift above the globe surface, avoids z-fighting with the model
+    pub lift: f32,
+    /// Densifies the samples near the poles, where the track turns quickly
+    pub path: GeoPath
+}
+
+impl Default for GroundTrackSettings {
+    fn default() -> Self {
+        Self { enabled: true, samples: 256, color: YELLOW.into(), lift: 1.005, path: GeoPath::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroundPoint {
+    /// Geocentric latitude (in degrees)
+    pub latitude: f32,
+    /// Longitude relative to the globe as it is now (in degrees, -180..180)
+    pub longitude: f32
+}
+
+impl GroundPoint {
+    pub fn on_globe(&self, radius: f32) -> Vec3 {
+        let (latitude, longitude) = (self.latitude.to_radians(), self.longitude.to_radians());
+        Vec3::new(latitude.cos() * longitude.cos(), latitude.cos() * longitude.sin(), latitude.sin()) * radius
+    }
+}
+
+impl From<GroundPoint> for GeoPoint {
+    fn from(value: GroundPoint) -> Self {
+        GeoPoint::new(value.latitude as f64, value.longitude as f64)
+    }
+}
+
+/// Sub-satellite points over the next period, starting at the current position of the orbit.
+/// The globe turns under the orbit, so every sample is rotated back by the Earth rotation since now
+pub fn predict_ground_track(orbit: &SatelliteOrbit, samples: usize) -> Vec<GroundPoint> {
+    let step = orbit.orbital_period() / samples.max(1) as f64;
+    (0..=samples)
+        .map(|i| orbit.sub_satellite_point(step * i as f64))
+        .map(|(latitude, longitude)| GroundPoint { latitude, longitude })
+        .collect()
+}
+
+impl Plugin for GroundTrackPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GroundTrackSettings>()
+            .init_resource::<SelectionSet>()
+            .init_resource::<FloatingOrigin>()
+            .add_systems(Update, draw_ground_track.run_if(resource_exists::<GizmoConfigStore>));
+    }
+}
+
+fn draw_ground_track(
+    mut gizmos: Gizmos,
+    track_settings: Res<GroundTrackSettings>,
+    settings: Res<InGameSettings>,
+    selection: Res<SelectionSet>,
+    origin: Res<FloatingOrigin>,
+    satellites: Query<(&Transform, &SatelliteOrbit)>
+) {
+    if !track_settings.enabled {
+        return;
+    }
+    let Some((transform, orbit)) = selection.primary().and_then(|e| satellites.get(e).ok()) else {
+        return;
+    };
+    //loaded satellites keep the epoch orbit, align it with where the satellite actually is
+    let orbit = SatelliteOrbit { true_anomaly: orbit.true_anomaly_at(WORLD_FRAME.to_inertial(transform.translation).as_dvec3()), ..orbit.clone() };
+    let radius = EARTH_RADIUS_KM * settings.scale * track_settings.lift;
+    let track: Vec<GeoPoint> = predict_ground_track(&orbit, track_settings.samples).into_iter().map(GeoPoint::from).collect();
+    let points = track_settings.path.surface(&track, radius as f64).into_iter().map(|p| origin.to_render(WORLD_FRAME.to_world(p.as_vec3())));
+    gizmos.linestrip(points, track_settings.color);
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::orbit::EARTH_ROTATION_RATE;
+
+    #[test]
+    fn test_track_max_latitude_matches_inclination() {
+        for inclination in [28.5, 51.6, 63.4] {
+            let orbit = SatelliteOrbit::new(6778.0, 0.0005, inclination, 40.0, 10.0, 0.0, 0.0);
+            let track = predict_ground_track(&orbit, 720);
+            let max_latitude = track.iter().map(|p| p.latitude).fold(f32::MIN, f32::max);
+            let min_latitude = track.iter().map(|p| p.latitude).fold(f32::MAX, f32::min);
+            assert_abs_diff_eq!(max_latitude as f64, inclination, epsilon = 0.1);
+            assert_abs_diff_eq!(min_latitude as f64, -inclination, epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn test_track_drifts_west_by_earth_rotation() {
+        let orbit = SatelliteOrbit::new(6778.0, 0.0005, 51.6, 40.0, 10.0, 0.0, 0.0);
+        let track = predict_ground_track(&orbit, 360);
+        let (first, last) = (track.first().unwrap(), track.last().unwrap());
+        let drift = (last.longitude - first.longitude + 540.0).rem_euclid(360.0) - 180.0;
+        assert_abs_diff_eq!(drift as f64, -(EARTH_ROTATION_RATE * orbit.orbital_period()).to_degrees(), epsilon = 0.1);
+        assert_abs_diff_eq!(last.latitude, first.latitude, epsilon = 0.1);
+        //points are on the globe
+        assert_abs_diff_eq!(first.on_globe(63.78).length(), 63.78, epsilon = 1e-4);
+    }
+}
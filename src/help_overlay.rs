@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use bevy::{color::palettes::css::*, prelude::*};
+
+use crate::input::{Action, ActionCategory, ActionTriggered, InputMap};
+use crate::selection::SelectionSet;
+
+/// Keybinding overlay generated from the [`InputMap`]
+pub struct HelpOverlayPlugin;
+
+#[derive(Resource, Default, Debug)]
+pub struct HelpOverlay {
+    pub visible: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HelpEntry {
+    pub action: Action,
+    pub keys: Vec<String>,
+    /// Other actions sharing one of the keys
+    pub conflicts: Vec<Action>,
+    /// Set when the action currently does nothing, explains why
+    pub disabled_hint: Option<&'static str>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HelpSection {
+    pub category: ActionCategory,
+    pub entries: Vec<HelpEntry>
+}
+
+impl HelpEntry {
+    pub fn format(&self) -> String {
+        let mut line = format!("{:<28} {}", self.action.label(), self.keys.join(" / "));
+        for conflict in &self.conflicts {
+            line.push_str(&format!("  [conflicts with {}]", conflict.label()));
+        }
+        if let Some(hint) = self.disabled_hint {
+            line.push_str(&format!("  ({hint})"));
+        }
+        line
+    }
+}
+
+/// Bound actions grouped by category, in binding order within each category
+pub fn help_sections(map: &InputMap, disabled: &HashMap<Action, &'static str>) -> Vec<HelpSection> {
+    let mut sections: Vec<HelpSection> = vec![];
+    for (action, _) in map.bindings() {
+        let section = match sections.iter_mut().position(|s| s.category == action.category()) {
+            Some(position) => &mut sections[position],
+            None => {
+                sections.push(HelpSection { category: action.category(), entries: vec![] });
+                sections.last_mut().unwrap()
+            }
+        };
+        if section.entries.iter().any(|e| e.action == *action) {
+            continue;
+        }
+
+        let bindings: Vec<_> = map.bindings_for(*action).collect();
+        let mut conflicts = vec![];
+        for (other, binding) in map.bindings() {
+            if other != action && bindings.contains(&binding) && !conflicts.contains(other) {
+                conflicts.push(*other);
+            }
+        }
+        section.entries.push(HelpEntry {
+            action: *action,
+            keys: bindings.iter().map(|b| b.display()).collect(),
+            conflicts,
+            disabled_hint: disabled.get(action).copied()
+        });
+    }
+    sections.sort_by_key(|s| s.category);
+    sections
+}
+
+pub fn disabled_actions(selection: &SelectionSet) -> HashMap<Action, &'static str> {
+    let mut disabled = HashMap::new();
+    if selection.is_empty() {
+        for action in [Action::AddToWatchlist, Action::HideOrbits, Action::ShowOrbits, Action::OverrideColor, Action::ExportSelection, Action::DespawnSelection] {
+            disabled.insert(action, "select satellites first");
+        }
+    }
+    disabled
+}
+
+#[derive(Component)]
+struct HelpOverlayNode;
+
+impl Plugin for HelpOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputMap>()
+            .init_resource::<HelpOverlay>()
+            .init_resource::<SelectionSet>()
+            .add_event::<ActionTriggered>()
+            .add_systems(Update, toggle_help_overlay)
+            .add_systems(Update, render_help_overlay
+                .after(toggle_help_overlay)
+                .run_if(resource_changed::<HelpOverlay>.or_else(resource_changed::<InputMap>).or_else(resource_changed::<SelectionSet>)));
+    }
+}
+
+fn toggle_help_overlay(mut actions: EventReader<ActionTriggered>, mut overlay: ResMut<HelpOverlay>) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::ToggleHelp => overlay.visible = !overlay.visible,
+            Action::CloseOverlay if overlay.visible => overlay.visible = false,
+            _ => {}
+        }
+    }
+}
+
+fn render_help_overlay(
+    overlay: Res<HelpOverlay>,
+    map: Res<InputMap>,
+    selection: Res<SelectionSet>,
+    nodes: Query<Entity, With<HelpOverlayNode>>,
+    mut commands: Commands
+) {
+    for entity in nodes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !overlay.visible {
+        return;
+    }
+
+    let mut text_sections = vec![];
+    for section in help_sections(&map, &disabled_actions(&selection)) {
+        text_sections.push(TextSection::new(format!("{:?}\n", section.category), TextStyle { font_size: 20.0, color: GOLD.into(), ..default() }));
+        for entry in section.entries {
+            let color = if entry.disabled_hint.is_some() { GRAY } else { WHITE };
+            text_sections.push(TextSection::new(format!("  {}\n", entry.format()), TextStyle { font_size: 16.0, color: color.into(), ..default() }));
+        }
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.8).into(),
+                ..default()
+            },
+            HelpOverlayNode
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(text_sections));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::input::KeyBinding;
+
+    fn section(sections: &[HelpSection], category: ActionCategory) -> &HelpSection {
+        sections.iter().find(|s| s.category == category).unwrap()
+    }
+
+    #[test]
+    fn test_sections_grouped_by_category() {
+        let map = InputMap::empty()
+            .with(Action::ZoomIn, KeyBinding::key(KeyCode::KeyI))
+            .with(Action::ToggleHelp, KeyBinding::key(KeyCode::F1))
+            .with(Action::ZoomOut, KeyBinding::key(KeyCode::KeyO))
+            .with(Action::ToggleHelp, KeyBinding::shift(KeyCode::Slash));
+
+        let sections = help_sections(&map, &HashMap::new());
+        assert_eq!(sections.iter().map(|s| s.category).collect::<Vec<_>>(), vec![ActionCategory::General, ActionCategory::Camera]);
+        assert_eq!(sections[0].entries.len(), 1);
+        assert_eq!(sections[0].entries[0].keys, vec!["F1".to_owned(), "Shift+Slash".to_owned()]);
+        assert_eq!(sections[1].entries.iter().map(|e| e.action).collect::<Vec<_>>(), vec![Action::ZoomIn, Action::ZoomOut]);
+    }
+
+    #[test]
+    fn test_rebind_conflicts_are_annotated() {
+        let mut map = InputMap::default();
+        map.rebind(Action::ZoomIn, KeyBinding::key(KeyCode::KeyW));
+
+        let sections = help_sections(&map, &HashMap::new());
+        let zoom_in = section(&sections, ActionCategory::Camera).entries.iter().find(|e| e.action == Action::ZoomIn).unwrap();
+        assert_eq!(zoom_in.keys, vec!["W".to_owned()]);
+        assert_eq!(zoom_in.conflicts, vec![Action::AddToWatchlist]);
+        assert!(zoom_in.format().contains("[conflicts with Add selection to watchlist]"));
+
+        let watchlist = section(&sections, ActionCategory::Selection).entries.iter().find(|e| e.action == Action::AddToWatchlist).unwrap();
+        assert_eq!(watchlist.conflicts, vec![Action::ZoomIn]);
+        //modifiers make a different binding
+        let hide = section(&sections, ActionCategory::Selection).entries.iter().find(|e| e.action == Action::HideOrbits).unwrap();
+        assert!(hide.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_actions_are_annotated() {
+        let map = InputMap::default();
+        let mut selection = SelectionSet::default();
+        let sections = help_sections(&map, &disabled_actions(&selection));
+        let selection_entries = &section(&sections, ActionCategory::Selection).entries;
+        let disabled: Vec<_> = selection_entries.iter().filter(|e| e.disabled_hint.is_some()).map(|e| e.action).collect();
+        assert!(disabled.contains(&Action::ExportSelection));
+        assert!(!disabled.contains(&Action::SelectGroup));
+        assert!(selection_entries.iter().find(|e| e.action == Action::DespawnSelection).unwrap().format().ends_with("(select satellites first)"));
+
+        selection.add(Entity::from_raw(1));
+        let sections = help_sections(&map, &disabled_actions(&selection));
+        assert!(sections.iter().flat_map(|s| &s.entries).all(|e| e.disabled_hint.is_none()));
+    }
+}
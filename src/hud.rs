@@ -0,0 +1,472 @@
+use bevy::{
+    color::palettes::css::*,
+    prelude::*,
+    render::{camera::{ScalingMode, Viewport}, view::RenderLayers},
+    window::PrimaryWindow
+};
+
+use crate::camera::OverlayCamera;
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::cursor_readout::CursorReadout;
+use crate::global::InGameSettings;
+use crate::input::{Action, ActionCategory, ActionTriggered};
+use crate::memory::{format_bytes, MemoryAccounting};
+use crate::observer::{Observer, SelectedObserver};
+use crate::propagation::{SatelliteId, StalePrediction};
+use crate::selection::SelectionSet;
+use crate::world_frame::WORLD_FRAME;
+
+//render layer of the axes scene, nothing else is drawn on it
+const HUD_LAYER: usize = 7;
+//side of the axes viewport and the margin to the window corner (logical px)
+const TRIAD_SIZE: f32 = 96.0;
+const MARGIN: f32 = 12.0;
+//the scale bar shows 1,000 km while that's between these lengths on the screen (logical px)
+const SCALE_BAR_MIN: f32 = 40.0;
+const SCALE_BAR_MAX: f32 = 200.0;
+const PREFERRED_SCALE_KM: f32 = 1000.0;
+//below this share of the direction across the screen the pole is (nearly) towards or away from the viewer
+const MIN_NORTH_SCREEN_COMPONENT: f32 = 0.1;
+
+/// Indicators in the bottom left corner: the world axes as seen by the game camera, a needle pointing to the
+/// celestial north pole, a bar with a round length at the depth of the focus, the [`CursorReadout`], the [`SelectedObserver`],
+/// the satellites with a [`StalePrediction`] and the [`MemoryAccounting`] total
+pub struct HudPlugin;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HudTheme {
+    /// Colors of the world X, Y and Z axes
+    pub axes: [Color; 3],
+    pub north: Color,
+    pub scale_bar: Color,
+    pub text: Color
+}
+
+impl HudTheme {
+    pub fn dark() -> Self {
+        Self { axes: [TOMATO.into(), LIMEGREEN.into(), DODGER_BLUE.into()], north: GOLD.into(), scale_bar: WHITE_SMOKE.into(), text: WHITE_SMOKE.into() }
+    }
+
+    pub fn light() -> Self {
+        Self { axes: [DARK_RED.into(), DARK_GREEN.into(), NAVY.into()], north: DARK_ORANGE.into(), scale_bar: DARK_SLATE_GRAY.into(), text: BLACK.into() }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct HudSettings {
+    pub visible: bool,
+    pub theme: HudTheme
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self { visible: true, theme: HudTheme::dark() }
+    }
+}
+
+/// World position the scale bar is measured at, kept up to date by the app (the Earth at the origin by default)
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct HudFocus(pub Vec3);
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+struct HudGizmos;
+
+#[derive(Component)]
+struct HudRoot;
+
+//rotated towards the pole, with the "N" and the line
+#[derive(Component)]
+struct NorthNeedle;
+
+#[derive(Component)]
+struct NeedleLine;
+
+#[derive(Component)]
+struct HudText;
+
+#[derive(Component)]
+struct ScaleBar;
+
+#[derive(Component)]
+struct ScaleLabel;
+
+#[derive(Component)]
+struct CursorLabel;
+
+#[derive(Component)]
+struct ObserverLabel;
+
+#[derive(Component)]
+struct StaleLabel;
+
+#[derive(Component)]
+struct MemoryLabel;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HudSettings>()
+            .init_resource::<HudFocus>()
+            .add_event::<ActionTriggered>()
+            .register_command(
+                CommandDescriptor::new("Set HUD theme", ActionCategory::General, |params, world| {
+                    let theme = match params[0].as_text() {
+                        Some("dark") => HudTheme::dark(),
+                        Some("light") => HudTheme::light(),
+                        other => {
+                            warn!("Unknown HUD theme {:?}, expected dark or light", other);
+                            return;
+                        }
+                    };
+                    world.get_resource_or_insert_with(HudSettings::default).theme = theme;
+                })
+                .with_param("dark or light", ParamKind::Text)
+            )
+            .add_systems(Update, toggle_hud);
+        //the overlay is only built with rendering
+        if !app.world().contains_resource::<GizmoConfigStore>() {
+            return;
+        }
+        app
+            .init_gizmo_group::<HudGizmos>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(Update, (show_hud, place_triad_viewport, draw_triad, update_north_needle).run_if(any_with_component::<HudRoot>))
+            .add_systems(Update, update_scale_bar.run_if(any_with_component::<HudRoot>.and_then(resource_exists::<InGameSettings>)))
+            .add_systems(Update, update_cursor_label.run_if(any_with_component::<HudRoot>.and_then(resource_exists_and_changed::<CursorReadout>)))
+            .add_systems(Update, update_observer_label.run_if(any_with_component::<HudRoot>.and_then(resource_exists::<SelectedObserver>)))
+            .add_systems(Update, update_stale_label.run_if(any_with_component::<HudRoot>))
+            .add_systems(Update, update_memory_label.run_if(any_with_component::<HudRoot>.and_then(resource_exists::<MemoryAccounting>)));
+    }
+}
+
+/// On-screen length (in logical px) of a world length at `depth` in front of the camera, `None` behind the near plane
+pub fn screen_length(projection: &Projection, viewport_height: f32, depth: f32, length: f32) -> Option<f32> {
+    match projection {
+        Projection::Perspective(perspective) => {
+            (depth > perspective.near).then(|| length * viewport_height / (2.0 * depth * (perspective.fov / 2.0).tan()))
+        },
+        Projection::Orthographic(orthographic) => {
+            let height = orthographic.area.height();
+            (height > 0.0).then(|| length * viewport_height / height)
+        }
+    }
+}
+
+/// Length of the scale bar (in km) and its on-screen length (in px) at `pixels_per_km`.
+/// 1,000 km while it fits the bar limits, the longest 1-2-5 length that isn't too long otherwise
+pub fn scale_bar(pixels_per_km: f32) -> Option<(f32, f32)> {
+    if !(pixels_per_km.is_finite() && pixels_per_km > 0.0) {
+        return None;
+    }
+    if (SCALE_BAR_MIN..=SCALE_BAR_MAX).contains(&(PREFERRED_SCALE_KM * pixels_per_km)) {
+        return Some((PREFERRED_SCALE_KM, PREFERRED_SCALE_KM * pixels_per_km));
+    }
+    (-2..=8)
+        .flat_map(|exponent| [1.0, 2.0, 5.0].map(|mantissa| mantissa * 10f32.powi(exponent)))
+        .map(|km| (km, km * pixels_per_km))
+        .take_while(|(_, px)| *px <= SCALE_BAR_MAX)
+        .last()
+}
+
+/// Clockwise angle (in radians) from the screen up to the projection of `north`, `None` when it points (nearly) along the view
+pub fn north_screen_angle(camera_rotation: Quat, north: Vec3) -> Option<f32> {
+    let local = camera_rotation.inverse() * north.normalize();
+    (local.truncate().length() >= MIN_NORTH_SCREEN_COMPONENT).then(|| local.x.atan2(local.y))
+}
+
+fn format_length(km: f32) -> String {
+    if km >= 1.0 {
+        format!("{km} km")
+    } else {
+        format!("{} m", km * 1000.0)
+    }
+}
+
+fn spawn_hud(mut commands: Commands, mut config_store: ResMut<GizmoConfigStore>, settings: Res<HudSettings>) {
+    let (config, _) = config_store.config_mut::<HudGizmos>();
+    config.render_layers = RenderLayers::layer(HUD_LAYER);
+    config.line_width = 3.0;
+
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera { order: 1, clear_color: ClearColorConfig::None, ..default() },
+            projection: OrthographicProjection { scaling_mode: ScalingMode::FixedVertical(2.6), ..default() }.into(),
+            ..default()
+        },
+        RenderLayers::layer(HUD_LAYER),
+        OverlayCamera
+    ));
+
+    let theme = &settings.theme;
+    let text_style = TextStyle { font_size: 13.0, color: theme.text, ..default() };
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(MARGIN * 2.0 + TRIAD_SIZE),
+                bottom: Val::Px(MARGIN),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexStart,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        },
+        HudRoot
+    )).with_children(|root| {
+        root.spawn((
+            NodeBundle {
+                style: Style { flex_direction: FlexDirection::Column, align_items: AlignItems::Center, width: Val::Px(24.0), ..default() },
+                ..default()
+            },
+            NorthNeedle
+        )).with_children(|compass| {
+            compass.spawn((TextBundle::from_section("N", text_style.clone()), HudText));
+            compass.spawn((
+                NodeBundle {
+                    style: Style { width: Val::Px(2.0), height: Val::Px(20.0), ..default() },
+                    background_color: theme.north.into(),
+                    ..default()
+                },
+                NeedleLine
+            ));
+        });
+        root.spawn((
+            NodeBundle {
+                style: Style { height: Val::Px(3.0), ..default() },
+                background_color: theme.scale_bar.into(),
+                ..default()
+            },
+            ScaleBar
+        ));
+        root.spawn((TextBundle::from_section("", text_style.clone()), ScaleLabel, HudText));
+        root.spawn((TextBundle::from_section("", text_style.clone()), CursorLabel, HudText));
+        root.spawn((TextBundle::from_section("", text_style.clone()), ObserverLabel, HudText));
+        root.spawn((TextBundle::from_section("", text_style.clone()), StaleLabel, HudText));
+        root.spawn((TextBundle::from_section("", text_style), MemoryLabel, HudText));
+    });
+}
+
+fn toggle_hud(mut actions: EventReader<ActionTriggered>, mut settings: ResMut<HudSettings>) {
+    for ActionTriggered(action) in actions.read() {
+        if *action == Action::ToggleHud {
+            settings.visible = !settings.visible;
+        }
+    }
+}
+
+fn show_hud(
+    settings: Res<HudSettings>,
+    mut roots: Query<&mut Visibility, With<HudRoot>>,
+    mut cameras: Query<&mut Camera, With<OverlayCamera>>,
+    mut texts: Query<&mut Text, With<HudText>>,
+    mut bars: Query<&mut BackgroundColor, With<ScaleBar>>,
+    mut needles: Query<&mut BackgroundColor, (With<NeedleLine>, Without<ScaleBar>)>
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut visibility in roots.iter_mut() {
+        *visibility = if settings.visible { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    for mut camera in cameras.iter_mut() {
+        camera.is_active = settings.visible;
+    }
+    let theme = &settings.theme;
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.text;
+        }
+    }
+    for mut color in bars.iter_mut() {
+        *color = theme.scale_bar.into();
+    }
+    for mut color in needles.iter_mut() {
+        *color = theme.north.into();
+    }
+}
+
+fn place_triad_viewport(windows: Query<&Window, With<PrimaryWindow>>, mut cameras: Query<&mut Camera, With<OverlayCamera>>) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let scale = window.scale_factor();
+    let (size, margin) = ((TRIAD_SIZE * scale) as u32, (MARGIN * scale) as u32);
+    if window.physical_width() < size + 2 * margin || window.physical_height() < size + 2 * margin {
+        return;
+    }
+    let viewport = Viewport {
+        physical_position: UVec2::new(margin, window.physical_height() - size - margin),
+        physical_size: UVec2::splat(size),
+        ..default()
+    };
+    for mut camera in cameras.iter_mut() {
+        if camera.viewport.as_ref().map(|v| (v.physical_position, v.physical_size)) != Some((viewport.physical_position, viewport.physical_size)) {
+            camera.viewport = Some(viewport.clone());
+        }
+    }
+}
+
+//the axes stay at the origin of their scene, the overlay camera looks at them with the rotation of the game camera
+fn draw_triad(
+    mut gizmos: Gizmos<HudGizmos>,
+    settings: Res<HudSettings>,
+    game_cameras: Query<&Transform, (With<Camera3d>, Without<OverlayCamera>)>,
+    mut overlay_cameras: Query<&mut Transform, With<OverlayCamera>>
+) {
+    let Some(game_camera) = game_cameras.iter().next() else {
+        return;
+    };
+    for mut transform in overlay_cameras.iter_mut() {
+        *transform = Transform::from_translation(game_camera.rotation * Vec3::Z * 5.0).with_rotation(game_camera.rotation);
+    }
+    if !settings.visible {
+        return;
+    }
+    for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().zip(settings.theme.axes) {
+        gizmos.arrow(Vec3::ZERO, axis, color);
+    }
+}
+
+//the floating origin only shifts global translations, the rotation is the world one
+fn update_north_needle(
+    game_cameras: Query<&GlobalTransform, (With<Camera3d>, Without<OverlayCamera>)>,
+    mut needles: Query<(&mut Transform, &mut Visibility), With<NorthNeedle>>
+) {
+    let Some(game_camera) = game_cameras.iter().next() else {
+        return;
+    };
+    let (_, rotation, _) = game_camera.to_scale_rotation_translation();
+    let angle = north_screen_angle(rotation, WORLD_FRAME.north);
+    for (mut transform, mut visibility) in needles.iter_mut() {
+        *visibility = if angle.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        //UI rotation is counterclockwise on the screen
+        transform.rotation = Quat::from_rotation_z(-angle.unwrap_or(0.0));
+    }
+}
+
+fn update_scale_bar(
+    focus: Res<HudFocus>,
+    settings: Res<InGameSettings>,
+    game_cameras: Query<(&Camera, &Transform, &Projection), Without<OverlayCamera>>,
+    mut bars: Query<(&mut Style, &mut Visibility), With<ScaleBar>>,
+    mut labels: Query<&mut Text, With<ScaleLabel>>
+) {
+    let Some((camera, transform, projection)) = game_cameras.iter().next() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    let depth = (focus.0 - transform.translation).dot(*transform.forward());
+    let bar = screen_length(projection, viewport.y, depth, settings.scale).and_then(scale_bar);
+    for (mut style, mut visibility) in bars.iter_mut() {
+        *visibility = if bar.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        style.width = Val::Px(bar.map_or(0.0, |(_, px)| px));
+    }
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = bar.map(|(km, _)| format_length(km)).unwrap_or_default();
+    }
+}
+
+fn update_cursor_label(readout: Res<CursorReadout>, mut labels: Query<&mut Text, With<CursorLabel>>) {
+    let value = readout.0.as_ref().map(ToString::to_string).unwrap_or_default();
+    for mut label in labels.iter_mut() {
+        label.sections[0].value.clone_from(&value);
+    }
+}
+
+//the observer may be renamed, the label is compared instead of tracking changes
+fn update_observer_label(selected: Res<SelectedObserver>, observers: Query<&Observer>, mut labels: Query<&mut Text, With<ObserverLabel>>) {
+    let value = selected.0.and_then(|e| observers.get(e).ok()).map(|o| format!("Observer: {}", o.name)).unwrap_or_default();
+    for mut label in labels.iter_mut() {
+        if label.sections[0].value != value {
+            label.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+//the focused satellite is named, the others are only counted
+fn update_stale_label(selection: Option<Res<SelectionSet>>, stale: Query<&SatelliteId, With<StalePrediction>>, mut labels: Query<&mut Text, With<StaleLabel>>) {
+    let focused = selection.and_then(|selection| selection.primary()).and_then(|e| stale.get(e).ok());
+    let value = match (focused, stale.iter().count()) {
+        (_, 0) => String::new(),
+        (Some(id), 1) => format!("Stale prediction: {}", stale_name(id)),
+        (Some(id), count) => format!("Stale predictions: {} and {} more", stale_name(id), count - 1),
+        (None, count) => format!("Stale predictions: {count}")
+    };
+    for mut label in labels.iter_mut() {
+        if label.sections[0].value != value {
+            label.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+fn stale_name(id: &SatelliteId) -> String {
+    id.name.clone().unwrap_or_else(|| id.norad_id.to_string())
+}
+
+//the estimates are committed every frame, the label only changes with the rounded text
+fn update_memory_label(accounting: Res<MemoryAccounting>, mut labels: Query<&mut Text, With<MemoryLabel>>) {
+    let over: Vec<&str> = accounting.iter().filter(|(_, usage)| usage.over_budget()).map(|(subsystem, _)| subsystem).collect();
+    let mut value = format!("Memory: {}", format_bytes(accounting.total()));
+    if !over.is_empty() {
+        value.push_str(&format!(", over budget: {}", over.join(", ")));
+    }
+    for mut label in labels.iter_mut() {
+        if label.sections[0].value != value {
+            label.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_screen_length_at_depth() {
+        let perspective = Projection::Perspective(PerspectiveProjection { fov: 90f32.to_radians(), ..default() });
+        //at depth d a 90° frustum is 2d high
+        assert_abs_diff_eq!(screen_length(&perspective, 800.0, 50.0, 10.0).unwrap(), 80.0, epsilon = 1e-3);
+        //twice as far is half as long
+        assert_abs_diff_eq!(screen_length(&perspective, 800.0, 100.0, 10.0).unwrap(), 40.0, epsilon = 1e-3);
+        assert_eq!(screen_length(&perspective, 800.0, -5.0, 10.0), None);
+
+        let orthographic = OrthographicProjection { area: Rect::new(-100.0, -50.0, 100.0, 50.0), ..default() };
+        //the same at any depth
+        assert_abs_diff_eq!(screen_length(&Projection::Orthographic(orthographic), 600.0, 1e6, 10.0).unwrap(), 60.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_scale_bar_prefers_a_thousand_kilometers() {
+        assert_eq!(scale_bar(0.1), Some((1000.0, 100.0)));
+        //zoomed in, 1,000 km would run off the bar
+        let (km, px) = scale_bar(1.5).unwrap();
+        assert_eq!(km, 100.0);
+        assert_abs_diff_eq!(px, 150.0, epsilon = 1e-3);
+        //zoomed out, it would be a dot
+        let (km, px) = scale_bar(0.003).unwrap();
+        assert_eq!(km, 50000.0);
+        assert_abs_diff_eq!(px, 150.0, epsilon = 1e-3);
+        assert_eq!(scale_bar(0.0), None);
+        assert_eq!(format_length(0.5), "500 m");
+    }
+
+    #[test]
+    fn test_north_needle_follows_the_camera() {
+        let north = WORLD_FRAME.north;
+        //from the equinox with the pole straight up
+        let side = Transform::from_translation(WORLD_FRAME.equinox * 500.0).looking_at(Vec3::ZERO, north);
+        assert_abs_diff_eq!(north_screen_angle(side.rotation, north).unwrap(), 0.0, epsilon = 1e-4);
+        //rolled by 90° to the left the pole is to the right
+        let rolled = side.rotation * Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        assert_abs_diff_eq!(north_screen_angle(rolled, north).unwrap(), std::f32::consts::FRAC_PI_2, epsilon = 1e-4);
+        //the overview looks down at the pole, there's no direction to point to
+        let (position, up) = WORLD_FRAME.overview(500.0);
+        let overview = Transform::from_translation(position).looking_at(Vec3::ZERO, up);
+        assert_eq!(north_screen_angle(overview.rotation, north), None);
+    }
+}
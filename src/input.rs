@@ -0,0 +1,269 @@
+use bevy::{input::InputSystem, prelude::*};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ActionCategory {
+    General,
+    Camera,
+    Selection,
+    Time
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    ToggleHelp,
+    CloseOverlay,
+    Restart,
+    ZoomIn,
+    ZoomOut,
+    NarrowFov,
+    WidenFov,
+    SelectGroup,
+    AddToWatchlist,
+    HideOrbits,
+    ShowOrbits,
+    OverrideColor,
+    ExportSelection,
+    DespawnSelection,
+    ToggleGhosts,
+    TimeOfInterestLater,
+    TimeOfInterestEarlier,
+    ToggleSpeedHeatmap,
+    ToggleChaseCamera,
+    OpenCommandPalette,
+    RealTimeSpeed,
+    MinutePerSecondSpeed,
+    TenMinutesPerSecondSpeed,
+    HourPerSecondSpeed,
+    DayPerSecondSpeed,
+    Undo,
+    Redo,
+    ToggleHud,
+    NextObserver,
+    PreviousObserver,
+    ToggleProtractor,
+    ClearMeasurements,
+    TogglePause
+}
+
+impl Action {
+    pub const ALL: [Action; 33] = [
+        Action::ToggleHelp, Action::CloseOverlay, Action::Restart, Action::ZoomIn, Action::ZoomOut, Action::NarrowFov, Action::WidenFov,
+        Action::SelectGroup, Action::AddToWatchlist, Action::HideOrbits, Action::ShowOrbits, Action::OverrideColor, Action::ExportSelection,
+        Action::DespawnSelection, Action::ToggleGhosts, Action::TimeOfInterestLater, Action::TimeOfInterestEarlier, Action::ToggleSpeedHeatmap,
+        Action::ToggleChaseCamera, Action::OpenCommandPalette, Action::RealTimeSpeed, Action::MinutePerSecondSpeed, Action::TenMinutesPerSecondSpeed,
+        Action::HourPerSecondSpeed, Action::DayPerSecondSpeed, Action::Undo, Action::Redo, Action::ToggleHud, Action::NextObserver,
+        Action::PreviousObserver, Action::ToggleProtractor, Action::ClearMeasurements, Action::TogglePause
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::ToggleHelp => "Toggle this help",
+            Action::CloseOverlay => "Close overlay",
+            Action::Restart => "Restart",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::NarrowFov => "Narrow field of view",
+            Action::WidenFov => "Widen field of view",
+            Action::SelectGroup => "Select focused group",
+            Action::AddToWatchlist => "Add selection to watchlist",
+            Action::HideOrbits => "Hide selected orbits",
+            Action::ShowOrbits => "Show selected orbits",
+            Action::OverrideColor => "Highlight selection color",
+            Action::ExportSelection => "Export selected states",
+            Action::DespawnSelection => "Remove selected satellites",
+            Action::ToggleGhosts => "Toggle time of interest ghosts",
+            Action::TimeOfInterestLater => "Move time of interest later",
+            Action::TimeOfInterestEarlier => "Move time of interest earlier",
+            Action::ToggleSpeedHeatmap => "Toggle orbit speed heatmap",
+            Action::ToggleChaseCamera => "Toggle chase camera",
+            Action::OpenCommandPalette => "Open command palette",
+            Action::RealTimeSpeed => "Real time speed",
+            Action::MinutePerSecondSpeed => "Speed 60x",
+            Action::TenMinutesPerSecondSpeed => "Speed 600x",
+            Action::HourPerSecondSpeed => "Speed 3600x",
+            Action::DayPerSecondSpeed => "Speed 86400x",
+            Action::Undo => "Undo last edit",
+            Action::Redo => "Redo last undone edit",
+            Action::ToggleHud => "Toggle compass and scale bar",
+            Action::NextObserver => "Select next observer",
+            Action::PreviousObserver => "Select previous observer",
+            Action::ToggleProtractor => "Toggle protractor",
+            Action::ClearMeasurements => "Clear angle measurements",
+            Action::TogglePause => "Pause or resume simulation"
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        match self {
+            Action::ToggleHelp | Action::CloseOverlay | Action::Restart | Action::ToggleSpeedHeatmap | Action::OpenCommandPalette
+                | Action::Undo | Action::Redo | Action::NextObserver | Action::PreviousObserver => ActionCategory::General,
+            Action::ZoomIn | Action::ZoomOut | Action::NarrowFov | Action::WidenFov | Action::ToggleChaseCamera | Action::ToggleHud
+                | Action::ToggleProtractor | Action::ClearMeasurements => ActionCategory::Camera,
+            Action::SelectGroup | Action::AddToWatchlist | Action::HideOrbits | Action::ShowOrbits | Action::OverrideColor
+                | Action::ExportSelection | Action::DespawnSelection => ActionCategory::Selection,
+            Action::ToggleGhosts | Action::TimeOfInterestLater | Action::TimeOfInterestEarlier | Action::RealTimeSpeed | Action::MinutePerSecondSpeed
+                | Action::TenMinutesPerSecondSpeed | Action::HourPerSecondSpeed | Action::DayPerSecondSpeed | Action::TogglePause => ActionCategory::Time
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub shift: bool,
+    pub ctrl: bool
+}
+
+impl KeyBinding {
+    pub fn key(key: KeyCode) -> Self {
+        Self { key, shift: false, ctrl: false }
+    }
+
+    pub fn shift(key: KeyCode) -> Self {
+        Self { key, shift: true, ctrl: false }
+    }
+
+    pub fn ctrl(key: KeyCode) -> Self {
+        Self { key, shift: false, ctrl: true }
+    }
+
+    pub fn ctrl_shift(key: KeyCode) -> Self {
+        Self { key, shift: true, ctrl: true }
+    }
+
+    //modifiers must match exactly, so H and Shift+H can be bound to different actions
+    pub fn just_pressed(&self, keys: &ButtonInput<KeyCode>) -> bool {
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        keys.just_pressed(self.key) && shift == self.shift && ctrl == self.ctrl
+    }
+
+    pub fn display(&self) -> String {
+        let key = format!("{:?}", self.key);
+        let key = key.strip_prefix("Key").or_else(|| key.strip_prefix("Digit")).unwrap_or(&key);
+        let mut result = String::new();
+        if self.ctrl {
+            result.push_str("Ctrl+");
+        }
+        if self.shift {
+            result.push_str("Shift+");
+        }
+        result.push_str(key);
+        result
+    }
+}
+
+/// All keyboard bindings, an action may be bound to several keys
+#[derive(Resource, Clone, Debug)]
+pub struct InputMap {
+    bindings: Vec<(Action, KeyBinding)>
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::empty()
+            .with(Action::ToggleHelp, KeyBinding::key(KeyCode::F1))
+            .with(Action::ToggleHelp, KeyBinding::shift(KeyCode::Slash))
+            .with(Action::CloseOverlay, KeyBinding::key(KeyCode::Escape))
+            .with(Action::Restart, KeyBinding::key(KeyCode::Space))
+            .with(Action::ZoomIn, KeyBinding::key(KeyCode::KeyI))
+            .with(Action::ZoomOut, KeyBinding::key(KeyCode::KeyO))
+            .with(Action::NarrowFov, KeyBinding::key(KeyCode::BracketLeft))
+            .with(Action::WidenFov, KeyBinding::key(KeyCode::BracketRight))
+            .with(Action::SelectGroup, KeyBinding::ctrl(KeyCode::KeyA))
+            .with(Action::AddToWatchlist, KeyBinding::key(KeyCode::KeyW))
+            .with(Action::HideOrbits, KeyBinding::key(KeyCode::KeyH))
+            .with(Action::ShowOrbits, KeyBinding::shift(KeyCode::KeyH))
+            .with(Action::OverrideColor, KeyBinding::key(KeyCode::KeyC))
+            .with(Action::ExportSelection, KeyBinding::key(KeyCode::KeyE))
+            .with(Action::DespawnSelection, KeyBinding::key(KeyCode::Delete))
+            .with(Action::ToggleGhosts, KeyBinding::key(KeyCode::KeyG))
+            .with(Action::TimeOfInterestLater, KeyBinding::key(KeyCode::Period))
+            .with(Action::TimeOfInterestEarlier, KeyBinding::key(KeyCode::Comma))
+            .with(Action::ToggleSpeedHeatmap, KeyBinding::key(KeyCode::KeyV))
+            .with(Action::ToggleChaseCamera, KeyBinding::key(KeyCode::KeyF))
+            .with(Action::OpenCommandPalette, KeyBinding::ctrl(KeyCode::KeyP))
+            .with(Action::RealTimeSpeed, KeyBinding::key(KeyCode::Digit1))
+            .with(Action::MinutePerSecondSpeed, KeyBinding::key(KeyCode::Digit2))
+            .with(Action::TenMinutesPerSecondSpeed, KeyBinding::key(KeyCode::Digit3))
+            .with(Action::HourPerSecondSpeed, KeyBinding::key(KeyCode::Digit4))
+            .with(Action::DayPerSecondSpeed, KeyBinding::key(KeyCode::Digit5))
+            .with(Action::Undo, KeyBinding::ctrl(KeyCode::KeyZ))
+            .with(Action::Redo, KeyBinding::ctrl_shift(KeyCode::KeyZ))
+            .with(Action::ToggleHud, KeyBinding::key(KeyCode::F2))
+            .with(Action::NextObserver, KeyBinding::key(KeyCode::KeyN))
+            .with(Action::PreviousObserver, KeyBinding::shift(KeyCode::KeyN))
+            .with(Action::ToggleProtractor, KeyBinding::key(KeyCode::KeyM))
+            .with(Action::ClearMeasurements, KeyBinding::shift(KeyCode::KeyM))
+            .with(Action::TogglePause, KeyBinding::key(KeyCode::KeyP))
+    }
+}
+
+impl InputMap {
+    pub fn empty() -> Self {
+        Self { bindings: vec![] }
+    }
+
+    pub fn with(mut self, action: Action, binding: KeyBinding) -> Self {
+        self.bind(action, binding);
+        self
+    }
+
+    pub fn bind(&mut self, action: Action, binding: KeyBinding) {
+        if !self.bindings.contains(&(action, binding)) {
+            self.bindings.push((action, binding));
+        }
+    }
+
+    /// Replaces every binding of the action
+    pub fn rebind(&mut self, action: Action, binding: KeyBinding) {
+        self.bindings.retain(|(a, _)| *a != action);
+        self.bindings.push((action, binding));
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = &(Action, KeyBinding)> {
+        self.bindings.iter()
+    }
+
+    pub fn bindings_for(&self, action: Action) -> impl Iterator<Item = &KeyBinding> {
+        self.bindings.iter().filter(move |(a, _)| *a == action).map(|(_, b)| b)
+    }
+
+    pub fn just_pressed(&self, action: Action, keys: &ButtonInput<KeyCode>) -> bool {
+        self.bindings_for(action).any(|b| b.just_pressed(keys))
+    }
+}
+
+/// Sent for every triggered action, by its key bindings or through the command palette.
+/// Systems react to these instead of reading the keyboard, so both stay in sync
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActionTriggered(pub Action);
+
+/// Keyboard taken over by a text field, key bindings don't trigger their actions
+#[derive(Resource, Default, Debug)]
+pub struct InputCapture {
+    pub captured: bool
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputMap>()
+            .init_resource::<InputCapture>()
+            .add_event::<ActionTriggered>()
+            .add_systems(PreUpdate, trigger_key_bindings.after(InputSystem).run_if(resource_exists::<ButtonInput<KeyCode>>));
+    }
+}
+
+fn trigger_key_bindings(keys: Res<ButtonInput<KeyCode>>, map: Res<InputMap>, capture: Res<InputCapture>, mut triggered: EventWriter<ActionTriggered>) {
+    if capture.captured {
+        return;
+    }
+    for action in Action::ALL {
+        if map.just_pressed(action, &keys) {
+            triggered.send(ActionTriggered(action));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use bevy::{math::{cubic_splines::{CubicBezier, CubicCurve, CubicGenerator}, DQuat}, prelude::*, transform::TransformSystem};
+
+use crate::cursor_readout::earth_rotation;
+use crate::ephemeris::SimulationDate;
+use crate::global::InGameSettings;
+use crate::observer::geodetic_to_ecef;
+use crate::propagation::{SatelliteSpawned, Unreliable};
+use crate::world_frame::WORLD_FRAME;
+
+/// Stylized orbit insertion of satellites loaded with `launch_animation`: a stand-in marker rises from the launch site
+/// along an ascent arc and hands over to the satellite where it is in its orbit, at its speed
+pub struct LaunchPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct LaunchSettings {
+    /// Geodetic latitude of the launch site (in degrees)
+    pub latitude: f64,
+    /// Longitude of the launch site (in degrees, east positive)
+    pub longitude: f64,
+    /// Real time of the ascent
+    pub duration: Duration,
+    /// Height of the vertical rise off the pad, as a fraction of the distance to the insertion point
+    pub rise: f32
+}
+
+impl Default for LaunchSettings {
+    //Cape Canaveral
+    fn default() -> Self {
+        Self { latitude: 28.5, longitude: -80.6, duration: Duration::from_secs(4), rise: 0.5 }
+    }
+}
+
+//share of the launch spent coasting along the orbit, the arc turns sharply at its end and would jump between frames
+const COAST: f32 = 0.2;
+
+/// Path of a launch in world units and real seconds: a Bézier arc straight up off the `site` first, then turning over
+/// onto the orbit, and a coast along it reaching `insertion` with `velocity` after `duration_seconds`
+#[derive(Debug, Clone)]
+pub struct InsertionArc {
+    ascent: CubicCurve<Vec3>,
+    ascent_seconds: f32,
+    insertion: Vec3,
+    velocity: Vec3,
+    duration_seconds: f32
+}
+
+impl InsertionArc {
+    pub fn new(site: Vec3, insertion: Vec3, velocity: Vec3, duration_seconds: f32, rise: f32) -> Self {
+        let ascent_seconds = duration_seconds * (1.0 - COAST);
+        let burnout = insertion - velocity * (duration_seconds - ascent_seconds);
+        let lift = site + site.normalize_or_zero() * site.distance(burnout) * rise;
+        //the end tangent of a cubic Bézier is 3 (P3 - P2) per unit of its parameter
+        let approach = burnout - velocity * ascent_seconds / 3.0;
+        let ascent = CubicBezier::new([[site, lift, approach, burnout]]).to_curve();
+        Self { ascent, ascent_seconds, insertion, velocity, duration_seconds }
+    }
+
+    /// Position and velocity (per real second) after `elapsed`, the insertion state once it's over
+    pub fn state(&self, elapsed: f32) -> (Vec3, Vec3) {
+        if elapsed < self.ascent_seconds {
+            let t = elapsed.max(0.0) / self.ascent_seconds;
+            return (self.ascent.position(t), self.ascent.velocity(t) / self.ascent_seconds);
+        }
+        let remaining = (self.duration_seconds - elapsed).max(0.0);
+        (self.insertion - self.velocity * remaining, self.velocity)
+    }
+}
+
+/// Satellite hidden while its stand-in is on the way
+#[derive(Component, Debug)]
+pub struct Launching;
+
+/// Stand-in marker of a launched satellite, despawned at the handover
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct LaunchArc {
+    pub satellite: Entity,
+    /// Launch site in world units, fixed at the liftoff
+    pub site: Vec3,
+    /// Real seconds since the liftoff
+    pub elapsed: f32,
+    //position of the satellite in the previous frame, for its velocity
+    previous: Option<Vec3>
+}
+
+impl Plugin for LaunchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LaunchSettings>()
+            .add_event::<SatelliteSpawned>()
+            .add_systems(Update, start_launches.run_if(resource_exists::<InGameSettings>))
+            //after the propagation moved the satellites, the arc ends where they are in this frame
+            .add_systems(PostUpdate, animate_launches.before(TransformSystem::TransformPropagate));
+    }
+}
+
+type Markers<'w, 's> = Query<'w, 's, (Option<&'static Handle<Mesh>>, Option<&'static Handle<StandardMaterial>>)>;
+
+//where the site is now, in world units
+fn launch_site(settings: &LaunchSettings, gmst: f64, scale: f32) -> Vec3 {
+    let inertial = DQuat::from_rotation_z(gmst) * geodetic_to_ecef(settings.latitude, settings.longitude, 0.0);
+    WORLD_FRAME.to_world(inertial.as_vec3()) * scale
+}
+
+fn start_launches(
+    mut spawned: EventReader<SatelliteSpawned>,
+    settings: Res<LaunchSettings>,
+    game: Res<InGameSettings>,
+    date: Option<Res<SimulationDate>>,
+    markers: Markers,
+    mut commands: Commands
+) {
+    let mut site = None;
+    for ev in spawned.read().filter(|ev| ev.launch_animation) {
+        let Ok((mesh, material)) = markers.get(ev.entity) else {
+            continue;
+        };
+        let site = *site.get_or_insert_with(|| launch_site(&settings, earth_rotation(date.as_deref()), game.scale));
+        commands.entity(ev.entity).insert((Launching, Visibility::Hidden));
+        let mut arc = commands.spawn((
+            LaunchArc { satellite: ev.entity, site, elapsed: 0.0, previous: None },
+            SpatialBundle::from_transform(Transform::from_translation(site))
+        ));
+        //the same marker as the satellite, without a renderer there's nothing to show
+        if let (Some(mesh), Some(material)) = (mesh, material) {
+            arc.insert((mesh.clone(), material.clone()));
+        }
+    }
+}
+
+//the arc is rebuilt every frame towards where the satellite will be at the handover, from its current state
+fn animate_launches(
+    time: Res<Time>,
+    settings: Res<LaunchSettings>,
+    mut arcs: Query<(Entity, &mut LaunchArc, &mut Transform), Without<Launching>>,
+    satellites: Query<(&Transform, Has<Unreliable>), With<Launching>>,
+    mut commands: Commands
+) {
+    let duration = settings.duration.as_secs_f32();
+    let dt = time.delta_seconds();
+    for (entity, mut arc, mut transform) in arcs.iter_mut() {
+        let Ok((satellite, unreliable)) = satellites.get(arc.satellite) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let velocity = match arc.previous {
+            Some(previous) if dt > 0.0 => (satellite.translation - previous) / dt,
+            _ => Vec3::ZERO
+        };
+        arc.previous = Some(satellite.translation);
+        arc.elapsed += dt;
+        if arc.elapsed >= duration {
+            commands.entity(entity).despawn_recursive();
+            let visibility = if unreliable { Visibility::Hidden } else { Visibility::Inherited };
+            commands.entity(arc.satellite).remove::<Launching>().insert(visibility);
+            continue;
+        }
+        let insertion = satellite.translation + velocity * (duration - arc.elapsed);
+        transform.translation = InsertionArc::new(arc.site, insertion, velocity, duration, settings.rise).state(arc.elapsed).0;
+        transform.scale = satellite.scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::test_support::{app_with, FRAME};
+
+    #[test]
+    fn test_arc_meets_the_orbit_at_the_handover() {
+        let site = Vec3::new(0.0, 63.7, 0.0);
+        let insertion = Vec3::new(40.0, 55.0, 10.0);
+        let velocity = Vec3::new(3.0, -2.0, 0.5);
+        let arc = InsertionArc::new(site, insertion, velocity, 4.0, 0.5);
+
+        let (start, ascent) = arc.state(0.0);
+        assert_eq!(start, site);
+        //straight up off the pad
+        assert!(ascent.normalize().dot(site.normalize()) > 0.999);
+        let (end, end_velocity) = arc.state(4.0);
+        assert_abs_diff_eq!(end.distance(insertion), 0.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(end_velocity.distance(velocity), 0.0, epsilon = 1e-4);
+        //no kink where the ascent turns into the coast
+        let ascent_end = 4.0 * (1.0 - COAST);
+        let (before, before_velocity) = arc.state(ascent_end - 1e-3);
+        let (after, after_velocity) = arc.state(ascent_end);
+        assert!(before.distance(after) < 1e-2);
+        assert!(before_velocity.distance(after_velocity) < 0.05 * velocity.length());
+        //the marker doesn't go through the Earth on the way
+        for i in 0..=40 {
+            assert!(arc.state(i as f32 / 10.0).0.length() >= site.length() - 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_launch_hands_over_and_cleans_up() {
+        let mut app = app_with(LaunchPlugin);
+        app.insert_resource(LaunchSettings { duration: Duration::from_secs(2), ..default() });
+        //circular orbit of a tenth of a radian per second, about 500 km up at the scale of 0.01
+        let position = |t: f32| Vec3::new(t.cos(), t.sin(), 0.0) * 69.0;
+        let satellite = app.world_mut().spawn((Transform::from_translation(position(0.0)), Visibility::Inherited)).id();
+        let other = app.world_mut().spawn((Transform::default(), Visibility::Inherited)).id();
+        app.world_mut().send_event(SatelliteSpawned { entity: satellite, norad_id: 1, launch_animation: true });
+        app.world_mut().send_event(SatelliteSpawned { entity: other, norad_id: 2, launch_animation: false });
+
+        let arcs = |app: &mut App| app.world_mut().query::<&LaunchArc>().iter(app.world()).count();
+        //positions of the marker and of the satellite in every frame of the ascent
+        let mut frames: Vec<(Vec3, Vec3)> = vec![];
+        for frame in 0.. {
+            let t = frame as f32 * FRAME.as_secs_f32() * 0.1;
+            app.world_mut().get_mut::<Transform>(satellite).unwrap().translation = position(t);
+            app.update();
+            if app.world().get::<Launching>(satellite).is_none() {
+                break;
+            }
+            assert_eq!(app.world().get::<Visibility>(satellite), Some(&Visibility::Hidden));
+            let marker = app.world_mut().query::<(&LaunchArc, &Transform)>().single(app.world()).1.translation;
+            frames.push((marker, position(t)));
+            assert!(frame < 40);
+        }
+        assert!(frames.len() >= 18, "{}", frames.len());
+        //coasting, the marker is where the satellite is and moves with it, so the handover doesn't jump
+        let [(before_marker, before_satellite), (marker, satellite_position)] = frames[frames.len() - 2..] else {
+            unreachable!()
+        };
+        assert!(marker.distance(satellite_position) < 1e-3, "{marker:?} {satellite_position:?}");
+        let (marker_step, satellite_step) = (marker - before_marker, satellite_position - before_satellite);
+        assert!(marker_step.distance(satellite_step) < 0.05 * satellite_step.length(), "{marker_step:?} {satellite_step:?}");
+        assert_eq!(app.world().get::<Visibility>(satellite), Some(&Visibility::Inherited));
+        assert_eq!(arcs(&mut app), 0);
+        assert!(app.world().get::<Launching>(other).is_none());
+
+        //a satellite removed during its launch takes the marker with it
+        app.world_mut().send_event(SatelliteSpawned { entity: other, norad_id: 2, launch_animation: true });
+        app.update();
+        assert_eq!(arcs(&mut app), 1);
+        app.world_mut().despawn(other);
+        app.update();
+        assert_eq!(arcs(&mut app), 0);
+    }
+}
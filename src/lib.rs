@@ -0,0 +1,78 @@
+//! Satellite tracking simulation on Bevy.
+//!
+//! The core (orbit math, propagation plugins and their events, ephemeris, selection, camera) is always built,
+//! the rest is behind cargo features, all enabled by default:
+//! - `network`: [`propagation::DefaultClient`] loading from Celestrak, the only user of `ureq`
+//! - `file-loader`: [`propagation::ConstFileClient`] loading local files, without it
+//!   [`SkytracioPlugins`] defaults to [`propagation::InjectedOnly`]
+//! - `earth-model`: the `earth` module and [`SkytracioPlugins::with_earth`], the glTF Earth scaled into the scene
+//! - `ui-panels`: the `help_overlay`, `command_palette`, `context_menu`, `altitude_plot`, `data_quality` and `hud` modules and their plugins
+//! - `export`: `BulkOperation::ExportStates` and the "Export selection to" command
+//!
+//! Features only gate the code of this crate, Bevy is built with its default features either way.
+//! `ci/feature-matrix.sh` builds the supported combinations.
+
+pub mod selectable;
+pub mod orbit;
+pub mod orbit_lines;
+pub mod launch;
+pub mod camera;
+#[cfg(feature = "earth-model")]
+pub mod earth;
+pub mod propagation;
+pub mod error;
+pub mod selection;
+pub mod input;
+#[cfg(feature = "ui-panels")]
+pub mod help_overlay;
+pub mod ground_track;
+pub mod geopath;
+pub mod future_marks;
+pub mod speed_heatmap;
+pub mod ephemeris;
+pub mod commands;
+#[cfg(feature = "ui-panels")]
+pub mod command_palette;
+#[cfg(feature = "ui-panels")]
+pub mod context_menu;
+pub mod prediction_window;
+pub mod timed_history;
+pub mod memory;
+pub mod plot;
+#[cfg(feature = "ui-panels")]
+pub mod altitude_plot;
+pub mod floating_origin;
+pub mod world_frame;
+pub mod simulation_clock;
+pub mod node_drift;
+#[cfg(feature = "ui-panels")]
+pub mod data_quality;
+pub mod past_ghosts;
+pub mod autosave;
+pub mod observer;
+pub mod observer_import;
+pub mod tour;
+pub mod demo;
+pub mod edits;
+pub mod simtime;
+pub mod formation;
+pub mod notes;
+pub mod quality;
+pub mod cursor_readout;
+pub mod protractor;
+pub mod style_function;
+pub mod screensaver;
+pub mod constellation_stats;
+pub mod startup_check;
+#[cfg(feature = "ui-panels")]
+pub mod hud;
+#[cfg(test)]
+mod stress;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod scenarios;
+pub mod global;
+mod plugins;
+
+pub use plugins::{SkytracioPlugins, SkytracioOptions};
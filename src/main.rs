@@ -1,19 +1,28 @@
-
-mod selectable;
-mod orbit;
-mod camera;
-mod earth;
-mod propagation;
-pub mod global;
-
-use std::time::Duration;
-
-use bevy::{color::palettes::css::*, prelude::*};
-use camera::{CameraLock, StaticLockSettings};
-use earth::{AssetPrepared, LoadAndScaleEarthModelPlugin};
-use global::{InGameSettings, PropagationSettings};
-use orbit::{Propagatable, SatelliteOrbit};
-use selectable::*;
+use std::time::{Duration, SystemTime};
+
+use bevy::{color::palettes::css::*, input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, math::DVec3, prelude::*};
+use game::autosave::{AutosavePlugin, AutosaveSettings};
+use game::demo::{DemoCamera, DemoClient, DemoPlugin};
+use game::camera::{CameraFov, CameraLock, OverlayCamera, StaticLockSettings};
+use game::earth::{AssetPrepared, DEFAULT_EARTH_MODEL};
+use game::ephemeris::{moon_selectable, sun_position, Moon, SimulationDate};
+use game::floating_origin::FloatingOrigin;
+use game::hud::HudFocus;
+use game::world_frame::WORLD_FRAME;
+use game::input::{Action, ActionTriggered};
+use game::global::{AltitudeBand, CorrectionSmoothing, EphemerisSettings, InGameSettings, PropagationSettings};
+use game::orbit::{circular_eclipse_fraction, in_earth_shadow, Propagatable, SatelliteOrbit};
+use game::quality::{OrbitDetail, QualityLevel};
+use game::orbit_lines::{OrbitLineSettings, OrbitLineStyle};
+use game::screensaver::screensaver_active;
+use game::tour::TourPlugin;
+use game::selectable::*;
+use game::speed_heatmap::{self, OrbitRenderMode};
+use game::{propagation, SkytracioOptions, SkytracioPlugins};
+use game::simulation_clock::SimulationClock;
+use game::startup_check::StartupCheckSettings;
+use game::style_function::StyleFunction;
+use game::selection::{BulkOperation, FocusSatellite, OrbitHidden, PickRequest, SelectionSet};
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 enum GameState {
@@ -24,26 +33,71 @@ enum GameState {
 }
 
 fn main() {
-    App::new()
-        .insert_resource(InGameSettings { scale: 0.01, simulation_speed: 1000.0, propagation: PropagationSettings { real_time_interval: Duration::from_secs(2), batch_size: 50 } })
-        .insert_resource(propagation::ConstFileClient::new("assets/".into()))
-        .add_plugins(DefaultPlugins)
-        .add_plugins(LoadAndScaleEarthModelPlugin::<Earth>::new(127.56))
-        .add_plugins(propagation::LoadElementsPlugin::<propagation::ConstFileClient>::new())
-        .add_plugins(propagation::PropagateElementsPlugin)
-        .add_plugins(propagation::PropagateInGamePlugin)
+    //`--assets <dir>` runs from elsewhere than the repository, `--offline` skips the network probe of the startup check
+    //and `--procedural-earth` replaces the model with a plain sphere. `--demo` needs none of the assets nor the network
+    let assets = std::env::args().skip_while(|arg| arg != "--assets").nth(1).unwrap_or_else(|| "assets".to_owned());
+    let demo = std::env::args().any(|arg| arg == "--demo");
+    let offline = demo || std::env::args().any(|arg| arg == "--offline");
+    let procedural_earth = demo || std::env::args().any(|arg| arg == "--procedural-earth");
+    let startup_check = StartupCheckSettings {
+        asset_root: assets.clone().into(),
+        earth_model: (!procedural_earth).then(|| DEFAULT_EARTH_MODEL.into()),
+        network_probe: StartupCheckSettings::default().network_probe.filter(|_| !offline),
+        bundled_elements: demo,
+        ..default()
+    };
+    let plugins = SkytracioPlugins::new()
+        .with_settings(InGameSettings {
+            simulation_speed: 1000.0,
+            propagation: PropagationSettings { real_time_interval: Duration::from_secs(2), batch_size: 50, numeric_fallback: true,
+                smoothing: Some(CorrectionSmoothing { max_jump_km: 20.0, frames: 15 }),
+                lookahead: Duration::from_secs(2),
+                ..default()
+            },
+            altitude_bands: vec![
+                AltitudeBand { name: "ISS band".to_owned(), min_km: 370.0, max_km: 460.0, hysteresis_km: 5.0, tint: Some(ORANGE.into()) },
+                AltitudeBand { name: "GEO belt".to_owned(), min_km: 35586.0, max_km: 35986.0, hysteresis_km: 20.0, tint: Some(GOLD.into()) }
+            ],
+            ephemeris: Some(EphemerisSettings { start: SystemTime::now(), max_display_distance_km: 60000.0 }),
+            ..default()
+        });
+    let plugins = if procedural_earth { plugins.with_procedural_earth() } else { plugins };
+    let mut app = App::new();
+    app
+        .add_plugins(DefaultPlugins.set(AssetPlugin { file_path: assets.clone(), ..default() }))
+        .insert_resource(startup_check);
+    if demo {
+        app.add_plugins(plugins.with_client(DemoClient)).add_plugins(DemoPlugin);
+    } else {
+        app
+            .add_plugins(plugins.with_client(propagation::ConstFileClient::new(assets.into())))
+            .insert_resource(propagation::GroupColors::default().with("galileo", DEEP_SKY_BLUE).with("gps-ops", LIMEGREEN))
+            .add_systems(Startup, load_data);
+    }
+    //`--style <file>` colors the satellites with the style expression in the file
+    if let Some(path) = std::env::args().skip_while(|arg| arg != "--style").nth(1) {
+        match std::fs::read_to_string(&path) {
+            Ok(expression) => { app.insert_resource(StyleFunction::from_expression_or_default(&expression)); },
+            Err(err) => warn!("Can't read the style expression {path}: {err}")
+        }
+    }
+    app
+        .add_plugins(AutosavePlugin::new(AutosaveSettings::default()).restoring(std::env::args().any(|arg| arg == "--restore-autosave")))
+        .add_plugins(TourPlugin::default().starting(std::env::args().skip_while(|arg| arg != "--tour").nth(1).map(Into::into)))
+        .init_resource::<OrbitRenderMode>()
         .init_resource::<Game>()
         .init_state::<GameState>()
-        .add_systems(Startup, (setup_cameras, load_data))
+        .add_systems(Startup, setup_cameras)
         .add_systems(Update, transition_to_playing.run_if(in_state(GameState::Loading)))
         .add_systems(OnEnter(GameState::Playing), setup)
         .add_systems(Update, change_focus.run_if(in_state(GameState::Playing)))
         .add_systems(Update, 
-            (propagete_actual_orbit, move_camera, draw_orbits)
+            (propagete_actual_orbit, follow_locked_entity, move_camera.run_if(not(screensaver_active)), draw_orbits, draw_eclipsed_arc, update_hud_focus, draw_satellite_info_panel)
+                .chain()
                 .run_if(in_state(GameState::Playing)))
         .add_systems(
             Update,
-            (gameover_keyboard, scroll_update).run_if(in_state(GameState::Playing)),
+            (gameover_keyboard, scroll_update, scroll_zoom, fov_update, bulk_operation_keyboard, time_of_interest_keyboard, orbit_render_keyboard, focus_requested).run_if(in_state(GameState::Playing)),
         )
         .add_systems(OnExit(GameState::GameOver), teardown)
         .run();
@@ -57,56 +111,60 @@ struct GlobalSettings {
 #[derive(Default)]
 struct Planet {
     entity: Option<Entity>,
-    celestial: SelectableCelestialBody<u8>,
+    celestial: SelectableCelestialBody<()>,
     color: Color
 }
 
 #[derive(Default, Debug, Component)]
 struct Satelite {
-    celestial: SelectableCelestialBody<u8>,
+    celestial: SelectableCelestialBody<()>,
     color: Color,
 }
 
+//elements of the locked satellite in the top left corner
+#[derive(Component)]
+struct SatelliteInfoPanel;
+
+#[derive(Component)]
+struct SatelliteInfoText;
+
 #[derive(Resource, Default)]
 struct Game {
     planet: Planet,
     settings: GlobalSettings,
     camera_transform: Transform,
-    camera_lock: CameraLock<u8>
+    //locked entity, `None` is the planet
+    camera_lock: CameraLock<Option<Entity>>
 }
 
-#[derive(Component, Default)]
-struct Earth;
-
 fn load_data(mut load_elements: EventWriter<propagation::LoadElements>) {
-    load_elements.send(propagation::LoadElements { group: "galileo".to_owned(), format: "JSON".to_owned() });
+    load_elements.send(propagation::LoadElements { source: propagation::DataSource::File, ..propagation::LoadElements::group("galileo", propagation::ElementsFormat::Json) });
 }
 
-fn setup_cameras(mut commands: Commands, mut game: ResMut<Game>) {
+fn setup_cameras(mut commands: Commands, mut game: ResMut<Game>, fov: Res<CameraFov>, demo: Option<Res<DemoCamera>>) {
+    //the demo starts from its own view
+    let (position, up) = match demo {
+        Some(demo) => (demo.direction * demo.distance, WORLD_FRAME.north),
+        None => WORLD_FRAME.overview(500.0)
+    };
     game.settings.lock_settings = StaticLockSettings {
         distance_min: 100.0,
         distance_max: 700.0,
-        default_orientation: Vec3::Z,
+        default_orientation: position.normalize(),
         tolerance: 1.0
     };
-    game.camera_transform = Transform::from_xyz(
-        0.0,
-          0.0,
-        500.0,
-    )
-    .looking_at(Vec3::ZERO, Vec3::X);
+    game.camera_transform = Transform::from_translation(position).looking_at(Vec3::ZERO, up);
     let camera = Camera3dBundle {
         transform: game.camera_transform,
         projection: PerspectiveProjection {
-            // We must specify the FOV in radians.
-            // Rust can convert degrees to radians for us.
-            fov: 60.0_f32.to_radians(),
+            fov: fov.radians(),
             ..default()
         }.into(),
         ..default()
     };
 
-    commands.spawn(camera);
+    //the HUD axes are drawn by a camera with a higher order, the UI stays on this one
+    commands.spawn((camera, IsDefaultUiCamera));
 }
 
 fn transition_to_playing(
@@ -115,7 +173,7 @@ fn transition_to_playing(
     mut game: ResMut<Game>
 ) {
     for ev in ev_levelup.read() {
-        game.planet.entity = Some(ev.entity_id.clone());
+        game.planet.entity = Some(ev.entity_id);
         next_state.set(GameState::Playing);
     }
 }
@@ -125,20 +183,26 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>, 
     mut materials: ResMut<Assets<StandardMaterial>>, 
     mut game: ResMut<Game>,
-    settings: Res<InGameSettings>
+    mut mesh_cache: ResMut<propagation::MarkerMeshCache>,
+    settings: Res<InGameSettings>,
+    options: Res<SkytracioOptions>
 ) {
 
-    let plane = InfinitePlane3d::new(Vec3::Y);
-    commands.spawn(PointLightBundle {
-        transform: Transform::from_xyz(4.0, 90.0, 4.0),
-        point_light: PointLight {
-            intensity: 15_000_000.0,
-            shadows_enabled: true,
-            range: 500.0,
+    //the planet is picked in its equatorial plane
+    let plane = InfinitePlane3d::new(WORLD_FRAME.north);
+    //with the ephemeris the Sun lights the scene
+    if settings.ephemeris.is_none() {
+        commands.spawn(PointLightBundle {
+            transform: Transform::from_xyz(4.0, 90.0, 4.0),
+            point_light: PointLight {
+                intensity: 15_000_000.0,
+                shadows_enabled: true,
+                range: 500.0,
+                ..default()
+            },
             ..default()
-        },
-        ..default()
-    });
+        });
+    }
 
     let moon_orbit = SatelliteOrbit {
         semi_major_axis: 20000.0,
@@ -150,7 +214,7 @@ fn setup(
         epoch: 0.0,
     };
     let moon = Satelite {
-        celestial: SelectableCelestialBody::initialize_from_orbit(1000.0, 1, &moon_orbit, settings.scale),
+        celestial: SelectableCelestialBody::initialize_from_orbit(1000.0, (), &moon_orbit, settings.scale),
         color: WHITE_SMOKE.into(),
     };
 
@@ -165,7 +229,7 @@ fn setup(
     };
 
     let moon_2 = Satelite {
-        celestial: SelectableCelestialBody::initialize_from_orbit(1500.0, 2, &moon_2_orbit, settings.scale),
+        celestial: SelectableCelestialBody::initialize_from_orbit(1500.0, (), &moon_2_orbit, settings.scale),
         color: GREEN_YELLOW.into(),
     };
 
@@ -173,25 +237,23 @@ fn setup(
     game.planet.celestial.radius = 6600.0 * settings.scale;
     game.planet.celestial.transform = Transform::from_translation(Vec3::ZERO);
     game.planet.celestial.orbital_plane = plane;
-    game.planet.celestial.data = 0;
-
-    let default_transform = Transform::from_xyz(
-        0.0,
-          0.0,
-        500.0,
-    )
-    .looking_at(Vec3::ZERO, Vec3::Y);
-    
+
+    let distance = game.camera_transform.translation.length();
     game.camera_lock = CameraLock {
-        locked_on: 0, //planet
+        locked_on: None, //planet
         lock_transform: Transform::default(),
-        distance: default_transform.translation.length(),
+        distance,
         is_default: true,
-        is_locked: true
+        is_locked: true,
+        ..default()
     };
 
-    let moon_shape = meshes.add(moon.celestial.get_mesh().mesh());
-    let moon_2_shape = meshes.add(moon_2.celestial.get_mesh().mesh());
+    //the demo moons stand in for the real Moon
+    if settings.ephemeris.is_some() || !options.demo_bodies {
+        return;
+    }
+    let moon_shape = mesh_cache.get_or_create(propagation::MarkerSize::new(propagation::MarkerShape::Sphere, moon.celestial.radius), &mut meshes);
+    let moon_2_shape = mesh_cache.get_or_create(propagation::MarkerSize::new(propagation::MarkerShape::Sphere, moon_2.celestial.radius), &mut meshes);
 
     let _ = commands.spawn(
         (PbrBundle {
@@ -201,7 +263,8 @@ fn setup(
             ..default()
         }, 
         moon_orbit, 
-        moon)
+        moon,
+        propagation::RevolutionCounter::default())
     ).id();
     let _ = commands.spawn(
         (PbrBundle {
@@ -211,78 +274,254 @@ fn setup(
             ..default()
         }, 
         moon_2_orbit,
-        moon_2)
+        moon_2,
+        propagation::RevolutionCounter::default())
     );
 }
 
-// remove all entities that are not a camera or window
-fn teardown(mut commands: Commands, entities: Query<Entity, (Without<Camera>, Without<Window>)>) {
+type TornDown<'w, 's> = Query<'w, 's, Entity, (Without<Camera>, Without<Window>, Without<SatelliteInfoPanel>, Without<SatelliteInfoText>)>;
+
+// remove all entities that are not a camera, window or the info panel
+fn teardown(mut commands: Commands, entities: TornDown) {
     for entity in &entities {
         commands.entity(entity).despawn();
     }
 }
 
 fn propagete_actual_orbit(
-    time: Res<Time>,
+    clock: Res<SimulationClock>,
     settings: Res<InGameSettings>,
-    mut game: ResMut<Game>,
-    mut satelites: Query<(&mut Transform, &mut SatelliteOrbit, &mut Satelite)>
+    mut satelites: Query<(&mut Transform, &mut SatelliteOrbit, &mut Satelite, &mut propagation::RevolutionCounter)>
 ) {
-    let dt = time.delta_seconds() * settings.simulation_speed;
-    for (mut transform, mut orbit, mut satelite) in satelites.iter_mut() {
-        let data = satelite.celestial.data;
-        *orbit = orbit.propagate(dt);
-        satelite.celestial.position_for(&*orbit, settings.scale);
+    if clock.paused {
+        return;
+    }
+    let dt = clock.delta_seconds();
+    for (mut transform, mut orbit, mut satelite, mut revolutions) in satelites.iter_mut() {
+        let propagated = orbit.propagate(dt);
+        revolutions.advance_keplerian(&orbit, &propagated, dt);
+        *orbit = propagated;
+        satelite.celestial.position_for(&orbit, settings.scale);
         *transform = satelite.celestial.transform;
         // info!("Propagating orbit: {:?}, {:?} by {:?}", &orbit, &satelite.celestial, dt);
-        if game.camera_lock.locked_on == data {
-            game.camera_lock.lock_transform = transform.clone();
-        }
     }
 }
 
-fn change_focus(
-    q_window: Query<&Window>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
-    q_satelites: Query<(&Transform, &Satelite)>,
-    buttons: Res<ButtonInput<MouseButton>>,
-    mut game: ResMut<Game>
+//keeps the lock on moving bodies, falls back to the planet once the locked entity is gone, unreliable or without predictions
+fn follow_locked_entity(
+    time: Res<Time>,
+    clock: Res<SimulationClock>,
+    mut invalidations: EventReader<propagation::InvalidateDerivedState>,
+    mut lost: EventReader<propagation::TrackLost>,
+    mut game: ResMut<Game>,
+    transforms: Query<&Transform, (Without<propagation::Unreliable>, Without<propagation::LostTrack>)>
 ) {
-
-    if !buttons.pressed(MouseButton::Left) {        
-        return;
+    if invalidations.read().any(|event| event.reason == propagation::InvalidationReason::TimeJump) {
+        game.camera_lock.skip_smoothing();
     }
-    let (camera, camera_transform) = q_camera.single();
-    let window = q_window.single();
-
-    let Some(cursor_position) = window.cursor_position() else {
+    let Some(entity) = game.camera_lock.locked_on else {
         return;
     };
-    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
+    if let Some(lost) = lost.read().find(|lost| lost.entity == entity) {
+        info!("Lost track of {}, the camera is back on the Earth", lost.norad_id);
+    }
+    match transforms.get(entity) {
+        Ok(transform) => game.camera_lock.follow(*transform, time.delta_seconds(), clock.speed() as f32),
+        Err(_) => game.camera_lock.lock_on(None, Transform::default(), true)
+    }
+}
+
+//the scale bar measures lengths at the locked body
+fn update_hud_focus(game: Res<Game>, mut focus: ResMut<HudFocus>) {
+    let target = match game.camera_lock.locked_on {
+        Some(_) => game.camera_lock.lock_transform.translation,
+        None => Vec3::ZERO
     };
+    if focus.0 != target {
+        focus.0 = target;
+    }
+}
 
-    let selectables = q_satelites.iter().map(|(t, s)| (t.clone(), s.celestial.clone())).chain(vec![(Transform::from_translation(Vec3::ZERO), game.planet.celestial.clone())]).collect();
+//below this eccentricity the eclipse of the orbit is taken as of a circular one
+const CIRCULAR_ECCENTRICITY: f64 = 0.01;
+//times (and points of the drawn arc) the eclipse of other orbits is sampled at
+const ECLIPSE_SAMPLES: usize = 360;
+
+//share of the period in the shadow
+fn eclipse_fraction(orbit: &SatelliteOrbit, sun: DVec3) -> f64 {
+    if orbit.eccentricity < CIRCULAR_ECCENTRICITY {
+        circular_eclipse_fraction(orbit.semi_major_axis, orbit.beta_angle(sun))
+    } else {
+        orbit.eclipse_fraction(sun, ECLIPSE_SAMPLES)
+    }
+}
 
-    let selectables = ManySelectables::new(selectables);
+fn satellite_info(
+    orbit: &SatelliteOrbit, id: Option<&propagation::SatelliteId>, classification: Option<&propagation::OrbitClassification>, sun: Option<DVec3>
+) -> String {
+    let name = id.and_then(|id| id.name.as_deref()).unwrap_or("Unnamed body");
+    let norad_id = id.map_or_else(|| "-".to_owned(), |id| id.norad_id.to_string());
+    let true_anomaly = orbit.true_anomaly.rem_euclid(360.0);
+    let altitude = |radius: f32| radius - propagation::EARTH_RADIUS_KM;
+    //the elements give the accurate period, a disagreeing ellipse is worth pointing out
+    let period = match classification {
+        Some(classification) if classification.has_period_mismatch() => format!(
+            "period {:.1} min ({:+.0} s on the ellipse)", classification.period_minutes, classification.period_discrepancy_seconds()
+        ),
+        Some(classification) => format!("period {:.1} min", classification.period_minutes),
+        None => format!("period {:.1} min", orbit.period_minutes())
+    };
+    let info = format!(
+        "{name}\nNORAD {norad_id}\na {:.0} km, e {:.5}, i {:.2}°\n{period}, true anomaly {true_anomaly:.1}°\naltitude {:.0} km (perigee {:.0}, apogee {:.0})",
+        orbit.semi_major_axis, orbit.eccentricity, orbit.inclination,
+        altitude(orbit.altitude_at_true_anomaly(true_anomaly as f32)), altitude(orbit.periapsis_km()), altitude(orbit.apoapsis_km())
+    );
+    //the power of the solar panels follows the Sun over the orbit, known with the ephemeris
+    match sun {
+        Some(sun) => format!("{info}\nbeta {:+.1}°, in the shadow {:.0}% of the orbit", orbit.beta_angle(sun), eclipse_fraction(orbit, sun) * 100.0),
+        None => info
+    }
+}
 
-    let Some((selected_transform, selected)) = selectables.select_with_context(ray) else {
+//rebuilt for every new lock target, refreshed while the lock stays. Nothing is shown with the planet locked
+fn draw_satellite_info_panel(
+    game: Res<Game>,
+    date: Option<Res<SimulationDate>>,
+    satellites: Query<(&SatelliteOrbit, Option<&propagation::SatelliteId>, Option<&propagation::OrbitClassification>)>,
+    panels: Query<Entity, With<SatelliteInfoPanel>>,
+    mut texts: Query<&mut Text, With<SatelliteInfoText>>,
+    mut shown: Local<Option<Entity>>,
+    mut commands: Commands
+) {
+    let target = game.camera_lock.locked_on.filter(|entity| satellites.contains(*entity));
+    let info = target.and_then(|entity| satellites.get(entity).ok()).map(|(orbit, id, classification)| {
+        satellite_info(orbit, id, classification, date.as_deref().map(|date| sun_position(date.0)))
+    });
+    if target == *shown && !(target.is_some() && panels.is_empty()) {
+        if let Some(info) = info {
+            for mut text in texts.iter_mut() {
+                text.sections[0].value.clone_from(&info);
+            }
+        }
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    *shown = target;
+    let Some(info) = info else {
         return;
     };
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                left: Val::Px(12.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        },
+        SatelliteInfoPanel
+    )).with_children(|panel| {
+        panel.spawn((TextBundle::from_section(info, TextStyle { font_size: 14.0, color: WHITE_SMOKE.into(), ..default() }), SatelliteInfoText));
+    });
+}
+
+//marker meshes of loaded satellites are 1.5 units, regardless of scale
+const LOADED_SATELLITE_RADIUS: f32 = 1.5;
+//clicks this close to a body on the screen still pick it, however small it's drawn
+const PICK_TOLERANCE_PX: f32 = 6.0;
+//simulation time a single key press moves the time of interest by
+const TIME_OF_INTEREST_STEP: Duration = Duration::from_secs(15 * 60);
+
+//unreliable satellites and the ones being unloaded can't take the focus
+type Focusable = (With<propagation::InGameElements>, Without<propagation::Unreliable>, Without<propagation::Despawning>, Without<propagation::PendingUnload>);
 
-    game.camera_lock.lock_on(selected.data, selected_transform, selected.data == 0);
+fn change_focus(
+    mut picks: EventReader<PickRequest>,
+    q_camera: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    q_satelites: Query<(Entity, &Transform, &Satelite)>,
+    q_loaded: Query<(Entity, &Transform, &SatelliteOrbit), Focusable>,
+    q_moon: Query<(Entity, &Transform), With<Moon>>,
+    (date, settings, origin): (Option<Res<SimulationDate>>, Res<InGameSettings>, Res<FloatingOrigin>),
+    (mut selection, mut game): (ResMut<SelectionSet>, ResMut<Game>)
+) {
+    for pick in picks.read() {
+        let (camera, camera_transform) = q_camera.single();
+        let Some(ray) = camera.viewport_to_world(camera_transform, pick.cursor) else {
+            continue;
+        };
+        //the camera is rendered relative to the floating origin, the bodies are picked in the world
+        let ray = Ray3d { origin: origin.to_world(ray.origin), ..ray };
+
+        let loaded = q_loaded.iter().map(|(e, t, orbit)| ((*t, Some(e)), SelectableCelestialBody {
+            transform: *t,
+            orbital_plane: InfinitePlane3d::new(WORLD_FRAME.to_world(orbit.orbit_normal().as_vec3())),
+            radius: LOADED_SATELLITE_RADIUS,
+            data: ()
+        }));
+        //the ephemeris Moon is only a lock target, the selection is for satellites
+        let scale = settings.scale;
+        let moon = date.as_deref().into_iter()
+            .flat_map(|date| q_moon.iter().map(move |(e, t)| ((*t, Some(e)), moon_selectable(*t, date, scale))));
+        let selectables = q_satelites.iter().map(|(e, t, s)| ((*t, Some(e)), s.celestial.clone()))
+            .chain(loaded)
+            .chain(moon)
+            .chain(vec![((Transform::from_translation(Vec3::ZERO), None), game.planet.celestial.clone())])
+            .collect();
+
+        let selectables = ManySelectables::new(selectables);
+
+        let min_angle = pixel_tolerance_angle(pick.cursor, PICK_TOLERANCE_PX, |cursor| {
+            camera.viewport_to_world(camera_transform, cursor).map(|ray| Ray3d { origin: origin.to_world(ray.origin), ..ray })
+        });
+        let Some(((selected_transform, selected), _)) = selectables.select_with_context(ray, min_angle) else {
+            continue;
+        };
+
+        let is_moon = selected.is_some_and(|entity| q_moon.contains(entity));
+        match (selected, pick.additive) {
+            _ if is_moon => {},
+            (Some(entity), true) => selection.add(entity),
+            (Some(entity), false) => selection.select_single(entity),
+            (None, true) => {},
+            (None, false) => selection.clear()
+        }
+        game.camera_lock.lock_on(selected, selected_transform, selected.is_none());
+    }
 }
 
 fn draw_orbits(
     mut gizmos: Gizmos,
-    orbits: Query<(&Transform, &SatelliteOrbit)>,
-    settings: Res<InGameSettings>
+    orbits: Query<(Entity, &SatelliteOrbit, Option<&propagation::OrbitColor>), Without<OrbitHidden>>,
+    settings: Res<InGameSettings>,
+    origin: Res<FloatingOrigin>,
+    (render_mode, detail): (Res<OrbitRenderMode>, Res<OrbitDetail>),
+    selection: Res<SelectionSet>,
+    (lines, quality): (Res<OrbitLineSettings>, Res<QualityLevel>)
 ) {
-    gizmos.arrow(Vec3::ZERO, Vec3::Z * 70.0, DARK_GRAY);
-    gizmos.arrow(Vec3::ZERO, Vec3::Y * 70.0, DARK_GRAY);
-    gizmos.arrow(Vec3::ZERO, Vec3::X * 70.0, WHEAT);
-    for (pos, orbit) in orbits.iter() {
-        let (position, rotation, half_size) = orbit.bevy_elipse_parameters(settings.scale);
+    //inertial axes, the vernal equinox stands out
+    let center = origin.to_render(Vec3::ZERO);
+    gizmos.arrow(center, center + WORLD_FRAME.north * 70.0, DARK_GRAY);
+    gizmos.arrow(center, center + WORLD_FRAME.north.cross(WORLD_FRAME.equinox) * 70.0, DARK_GRAY);
+    gizmos.arrow(center, center + WORLD_FRAME.equinox * 70.0, WHEAT);
+    for (entity, orbit, color) in orbits.iter() {
+        if detail.focused_only && selection.primary() != Some(entity) {
+            continue;
+        }
+        if *render_mode == OrbitRenderMode::SpeedHeatmap {
+            let points = speed_heatmap::speed_heatmap(orbit, detail.resolution, settings.scale);
+            gizmos.linestrip_gradient(points.into_iter().map(|p| (origin.to_render(p.position), p.color)));
+            continue;
+        }
+        //drawn as meshes by the OrbitLinesPlugin
+        if lines.style(quality.level) != OrbitLineStyle::Gizmo {
+            continue;
+        }
+        let (position, rotation, half_size) = orbit.bevy_elipse_parameters(settings.scale as f64);
         
         // let true_anomaly_adjusted = orbit.true_anomaly as i32;
         // if (true_anomaly_adjusted % 360).abs() < 10 {
@@ -291,45 +530,174 @@ fn draw_orbits(
         //     gizmos.arrow(Vec3::ZERO, pos.translation, Color::BLACK);
         // }
 
-        gizmos.ellipse(position, rotation, half_size, Color::linear_rgb(1.0, 0.0, 0.0))
-            .resolution(64);
+        let color = color.map(|c| c.0).unwrap_or(Color::linear_rgb(1.0, 0.0, 0.0));
+        gizmos.ellipse(origin.to_render(position), rotation, half_size, color)
+            .resolution(detail.resolution);
     }
 }
 
+//the part of the locked orbit in the shadow of the Earth, over its ellipse
+fn draw_eclipsed_arc(
+    mut gizmos: Gizmos,
+    game: Res<Game>,
+    date: Option<Res<SimulationDate>>,
+    orbits: Query<&SatelliteOrbit, Without<OrbitHidden>>,
+    settings: Res<InGameSettings>,
+    origin: Res<FloatingOrigin>
+) {
+    let (Some(date), Some(Ok(orbit))) = (date, game.camera_lock.locked_on.map(|entity| orbits.get(entity))) else {
+        return;
+    };
+    let sun = sun_position(date.0);
+    let points: Vec<(Vec3, bool)> = orbit.sample_points(ECLIPSE_SAMPLES)
+        .map(|point| {
+            let position = point.position();
+            (origin.to_render(WORLD_FRAME.to_world(position.as_vec3()) * settings.scale), in_earth_shadow(position, sun))
+        })
+        .collect();
+    for (i, (start, eclipsed)) in points.iter().enumerate() {
+        let (end, next_eclipsed) = points[(i + 1) % points.len()];
+        if *eclipsed && next_eclipsed {
+            gizmos.line(*start, end, DARK_SLATE_BLUE);
+        }
+    }
+}
+
+//dragging with the right button held orbits the camera around the locked body
 fn move_camera(
     time: Res<Time>,
     mut game: ResMut<Game>,
-    mut my_camera: Query<&mut Transform, With<Camera>>,
+    mut my_camera: Query<&mut Transform, (With<Camera>, Without<OverlayCamera>)>,
+    (mut motion, buttons): (EventReader<MouseMotion>, Option<Res<ButtonInput<MouseButton>>>)
 ) {    
+    let drag: Vec2 = motion.read().map(|motion| motion.delta).sum();
     if time.delta_seconds() == 0.0 {
         return;
     }
+    let dragging = buttons.is_some_and(|buttons| buttons.pressed(MouseButton::Right)) && drag != Vec2::ZERO;
     for mut camera in my_camera.iter_mut() {
         let settings = game.settings.lock_settings.clone();
-        game.camera_lock.move_towards_lock(&settings, &mut *camera, time.delta_seconds());
-        game.camera_transform = camera.clone();
+        let orbital_delta = dragging.then(|| game.camera_lock.drag_rotation(drag, &camera));
+        game.camera_lock.move_towards_lock(&settings, &mut camera, time.delta_seconds(), orbital_delta);
+        game.camera_transform = *camera;
     }
 }
 
 // restart the game when pressing spacebar
 fn gameover_keyboard(
     mut next_state: ResMut<NextState<GameState>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut actions: EventReader<ActionTriggered>
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
+    if actions.read().any(|ActionTriggered(action)| *action == Action::Restart) {
         next_state.set(GameState::Playing);
     }
 }
 
 fn scroll_update(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut actions: EventReader<ActionTriggered>,
+    mut game: ResMut<Game>
+) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::ZoomIn => {
+                let min = game.settings.lock_settings.distance_min;
+                game.camera_lock.zoom_in(50.0, min);
+            },
+            Action::ZoomOut => {
+                let max = game.settings.lock_settings.distance_max;
+                game.camera_lock.zoom_out(50.0, max);
+            },
+            Action::ToggleChaseCamera => {
+                let mode = game.camera_lock.mode.toggled();
+                game.camera_lock.set_mode(mode);
+            },
+            _ => {}
+        }
+    }
+}
+
+//wheel zoom, proportional to the scroll so touchpads zoom smoothly. Up zooms in
+fn scroll_zoom(
+    mut wheel: EventReader<MouseWheel>,
     mut game: ResMut<Game>
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyI) {
-        let min = game.settings.lock_settings.distance_min;
-        game.camera_lock.zoom_in(50.0, min);
-    } else if keyboard_input.just_pressed(KeyCode::KeyO) {
-        let max = game.settings.lock_settings.distance_max;
-        game.camera_lock.zoom_out(50.0, max);
+    //touchpads report pixels, a line of a wheel is about 16 of them
+    const STEP_PER_LINE: f32 = 20.0;
+    const PIXELS_PER_LINE: f32 = 16.0;
+    let delta_y: f32 = wheel.read()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / PIXELS_PER_LINE
+        })
+        .sum();
+    let (min, max) = (game.settings.lock_settings.distance_min, game.settings.lock_settings.distance_max);
+    if delta_y > 0.0 {
+        game.camera_lock.zoom_in(delta_y * STEP_PER_LINE, min);
+    } else if delta_y < 0.0 {
+        game.camera_lock.zoom_out(-delta_y * STEP_PER_LINE, max);
+    }
+}
+
+//binocular zoom, complements the distance based zoom
+fn fov_update(
+    mut actions: EventReader<ActionTriggered>,
+    mut fov: ResMut<CameraFov>
+) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::NarrowFov => fov.narrow(1.5),
+            Action::WidenFov => fov.widen(1.5),
+            _ => {}
+        }
+    }
+}
+
+fn bulk_operation_keyboard(
+    mut actions: EventReader<ActionTriggered>,
+    mut operations: EventWriter<BulkOperation>
+) {
+    for ActionTriggered(action) in actions.read() {
+        let operation = match action {
+            Action::AddToWatchlist => BulkOperation::AddToWatchlist,
+            Action::HideOrbits => BulkOperation::SetOrbitDisplay(false),
+            Action::ShowOrbits => BulkOperation::SetOrbitDisplay(true),
+            Action::OverrideColor => BulkOperation::OverrideColor(ORANGE_RED.into()),
+            Action::ExportSelection => BulkOperation::ExportStates("selection.csv".into()),
+            Action::DespawnSelection => BulkOperation::Despawn,
+            _ => continue
+        };
+        operations.send(operation);
+    }
+}
+
+fn time_of_interest_keyboard(
+    mut actions: EventReader<ActionTriggered>,
+    mut time_of_interest: ResMut<propagation::TimeOfInterest>
+) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::ToggleGhosts => time_of_interest.show_ghosts = !time_of_interest.show_ghosts,
+            Action::TimeOfInterestLater => time_of_interest.later(TIME_OF_INTEREST_STEP),
+            Action::TimeOfInterestEarlier => time_of_interest.earlier(TIME_OF_INTEREST_STEP),
+            _ => {}
+        }
+    }
+}
+
+fn orbit_render_keyboard(
+    mut actions: EventReader<ActionTriggered>,
+    mut render_mode: ResMut<OrbitRenderMode>
+) {
+    if actions.read().any(|ActionTriggered(action)| *action == Action::ToggleSpeedHeatmap) {
+        *render_mode = render_mode.toggled();
+    }
+}
+
+//satellites focused through the command palette
+fn focus_requested(mut focus: EventReader<FocusSatellite>, transforms: Query<&Transform>, mut game: ResMut<Game>) {
+    for FocusSatellite { entity } in focus.read() {
+        if let Ok(transform) = transforms.get(*entity) {
+            game.camera_lock.lock_on(Some(*entity), *transform, false);
+        }
     }
 }
@@ -0,0 +1,236 @@
+//Rough accounting of the memory held by the bounded structures of the simulation (histories, caches, queues),
+//every structure estimates itself, the accounting only sums the estimates up by subsystem
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*
+};
+
+pub const MEMORY_TOTAL: DiagnosticPath = DiagnosticPath::const_new("memory/total_kb");
+
+/// Estimate of the bytes a structure holds, its own size and what it owns on the heap.
+/// Data shared through `Arc`s is counted where it's owned, allocator and collection overhead isn't counted
+pub trait MemoryFootprint {
+    fn estimated_bytes(&self) -> usize;
+}
+
+/// Current and peak estimate of a subsystem (in bytes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubsystemMemory {
+    pub bytes: usize,
+    pub peak: usize,
+    /// Soft budget, exceeding it only notifies, evicting is up to the subsystem
+    pub budget: Option<usize>
+}
+
+impl SubsystemMemory {
+    pub fn over_budget(&self) -> bool {
+        self.budget.is_some_and(|budget| self.bytes > budget)
+    }
+}
+
+/// Estimates of every registered subsystem, refreshed in `Last` every frame.
+/// Structures are registered with [`AccountMemory`], several structures may add up to one subsystem
+#[derive(Resource, Debug, Default)]
+pub struct MemoryAccounting {
+    subsystems: BTreeMap<&'static str, SubsystemMemory>,
+    //estimates added in this frame
+    measured: HashMap<&'static str, usize>
+}
+
+impl MemoryAccounting {
+    pub fn register(&mut self, subsystem: &'static str) {
+        self.subsystems.entry(subsystem).or_default();
+    }
+
+    pub fn set_budget(&mut self, subsystem: &'static str, budget: Option<usize>) {
+        self.subsystems.entry(subsystem).or_default().budget = budget;
+    }
+
+    pub fn add(&mut self, subsystem: &'static str, bytes: usize) {
+        self.register(subsystem);
+        *self.measured.entry(subsystem).or_default() += bytes;
+    }
+
+    /// Takes the estimates added since the last commit as the current ones, subsystems nothing was added to hold nothing.
+    /// Returns the subsystems that went over their budget with them
+    pub fn commit(&mut self) -> Vec<&'static str> {
+        let mut exceeded = vec![];
+        for (subsystem, usage) in self.subsystems.iter_mut() {
+            let was_over = usage.over_budget();
+            usage.bytes = self.measured.remove(subsystem).unwrap_or(0);
+            usage.peak = usage.peak.max(usage.bytes);
+            if usage.over_budget() && !was_over {
+                exceeded.push(*subsystem);
+            }
+        }
+        exceeded
+    }
+
+    pub fn get(&self, subsystem: &str) -> Option<&SubsystemMemory> {
+        self.subsystems.get(subsystem)
+    }
+
+    /// Subsystems in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &SubsystemMemory)> {
+        self.subsystems.iter().map(|(subsystem, usage)| (*subsystem, usage))
+    }
+
+    pub fn total(&self) -> usize {
+        self.subsystems.values().map(|usage| usage.bytes).sum()
+    }
+
+    /// One line with every subsystem, for the log at the end of the session
+    pub fn summary(&self) -> String {
+        let subsystems: Vec<String> = self.iter()
+            .map(|(subsystem, usage)| format!("{subsystem} {} (peak {})", format_bytes(usage.bytes), format_bytes(usage.peak)))
+            .collect();
+        format!("Memory: {}, total {}", subsystems.join(", "), format_bytes(self.total()))
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+    }
+}
+
+/// A subsystem went over its soft budget, sent once until it's back under it
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct MemoryBudgetExceeded {
+    pub subsystem: &'static str,
+    pub bytes: usize,
+    pub budget: usize
+}
+
+/// Systems adding the estimates of the frame, before they're committed
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MeasureMemory;
+
+pub trait AccountMemory {
+    /// Adds the estimate of the resource `R`, while it exists, to `subsystem`
+    fn account_resource<R: Resource + MemoryFootprint>(&mut self, subsystem: &'static str) -> &mut Self;
+    /// Adds the estimates of every `C` to `subsystem`
+    fn account_components<C: Component + MemoryFootprint>(&mut self, subsystem: &'static str) -> &mut Self;
+}
+
+impl AccountMemory for App {
+    fn account_resource<R: Resource + MemoryFootprint>(&mut self, subsystem: &'static str) -> &mut Self {
+        register_subsystem(self, subsystem);
+        self.add_systems(Last, (move |resource: Option<Res<R>>, mut accounting: ResMut<MemoryAccounting>| {
+            if let Some(resource) = resource {
+                accounting.add(subsystem, resource.estimated_bytes());
+            }
+        }).in_set(MeasureMemory))
+    }
+
+    fn account_components<C: Component + MemoryFootprint>(&mut self, subsystem: &'static str) -> &mut Self {
+        register_subsystem(self, subsystem);
+        self.add_systems(Last, (move |components: Query<&C>, mut accounting: ResMut<MemoryAccounting>| {
+            accounting.add(subsystem, components.iter().map(MemoryFootprint::estimated_bytes).sum());
+        }).in_set(MeasureMemory))
+    }
+}
+
+fn register_subsystem(app: &mut App, subsystem: &'static str) {
+    if !app.is_plugin_added::<MemoryAccountingPlugin>() {
+        app.add_plugins(MemoryAccountingPlugin);
+    }
+    app.world_mut().resource_mut::<MemoryAccounting>().register(subsystem);
+    app.register_diagnostic(Diagnostic::new(subsystem_path(subsystem)));
+}
+
+fn subsystem_path(subsystem: &str) -> DiagnosticPath {
+    DiagnosticPath::new(format!("memory/{subsystem}_kb"))
+}
+
+/// Commits the [`MemoryAccounting`] every frame into diagnostics, notifies about exceeded budgets
+/// and logs the summary when the app exits
+pub struct MemoryAccountingPlugin;
+
+impl Plugin for MemoryAccountingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MemoryAccounting>()
+            .add_event::<MemoryBudgetExceeded>()
+            .register_diagnostic(Diagnostic::new(MEMORY_TOTAL))
+            .add_systems(Last, (commit_memory, summarize_on_exit).chain().after(MeasureMemory));
+    }
+}
+
+fn commit_memory(mut accounting: ResMut<MemoryAccounting>, mut exceeded: EventWriter<MemoryBudgetExceeded>, mut diagnostics: Diagnostics) {
+    for subsystem in accounting.commit() {
+        let usage = accounting.subsystems[subsystem];
+        let budget = usage.budget.unwrap_or_default();
+        warn!("Memory of {subsystem} is {}, over its budget of {}", format_bytes(usage.bytes), format_bytes(budget));
+        exceeded.send(MemoryBudgetExceeded { subsystem, bytes: usage.bytes, budget });
+    }
+    diagnostics.add_measurement(&MEMORY_TOTAL, || accounting.total() as f64 / 1024.0);
+    for (subsystem, usage) in accounting.iter() {
+        diagnostics.add_measurement(&subsystem_path(subsystem), || usage.bytes as f64 / 1024.0);
+    }
+}
+
+fn summarize_on_exit(mut exits: EventReader<AppExit>, accounting: Res<MemoryAccounting>) {
+    if exits.read().count() > 0 {
+        info!("{}", accounting.summary());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::diagnostic::DiagnosticsStore;
+
+    use super::*;
+    use crate::future_marks::{FutureMark, FutureMarks};
+    use crate::prediction_window::PredictionWindow;
+    use crate::test_support::{app_with, EventLog};
+
+    #[test]
+    fn test_subsystems_add_up_and_notify_over_budget() {
+        let mut app = app_with(MemoryAccountingPlugin);
+        app.account_resource::<FutureMarks>("look_ahead").account_resource::<PredictionWindow>("look_ahead");
+        app.init_resource::<FutureMarks>().init_resource::<PredictionWindow>();
+        let mut exceeded = EventLog::<MemoryBudgetExceeded>::new(&app);
+        app.update();
+        let empty = app.world().resource::<MemoryAccounting>().get("look_ahead").unwrap().bytes;
+        assert_eq!(empty, size_of::<FutureMarks>() + size_of::<PredictionWindow>());
+
+        app.world_mut().resource_mut::<MemoryAccounting>().set_budget("look_ahead", Some(empty + 1000));
+        app.world_mut().resource_mut::<PredictionWindow>().points = vec![Vec3::ZERO; 100];
+        app.world_mut().resource_mut::<FutureMarks>().marks = vec![FutureMark { offset: default(), position: Vec3::ZERO }; 10];
+        app.update();
+        let accounting = app.world().resource::<MemoryAccounting>();
+        let expected = empty + 100 * size_of::<Vec3>() + 10 * size_of::<FutureMark>();
+        assert_eq!(accounting.get("look_ahead").unwrap().bytes, expected);
+        assert_eq!(accounting.total(), expected);
+        let diagnostic = app.world().resource::<DiagnosticsStore>().get(&MEMORY_TOTAL).and_then(|d| d.value());
+        assert_eq!(diagnostic, Some(expected as f64 / 1024.0));
+        assert_eq!(exceeded.read(&app), vec![MemoryBudgetExceeded { subsystem: "look_ahead", bytes: expected, budget: empty + 1000 }]);
+
+        //notified once while over, the peak stays after the eviction
+        app.update();
+        assert!(exceeded.read(&app).is_empty());
+        app.world_mut().resource_mut::<PredictionWindow>().points.clear();
+        app.update();
+        let usage = *app.world().resource::<MemoryAccounting>().get("look_ahead").unwrap();
+        assert_eq!((usage.bytes, usage.peak), (empty + 10 * size_of::<FutureMark>(), expected));
+        assert!(!usage.over_budget());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut accounting = MemoryAccounting::default();
+        accounting.add("trails", 3 * 1_048_576);
+        accounting.add("element_cache", 2048);
+        accounting.add("element_cache", 512);
+        accounting.commit();
+        accounting.add("trails", 100);
+        accounting.commit();
+        assert_eq!(accounting.total(), 100);
+        assert_eq!(accounting.summary(), "Memory: element_cache 0 B (peak 2.5 KB), trails 100 B (peak 3.0 MB), total 100 B");
+    }
+}
@@ -0,0 +1,332 @@
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+use bevy::{math::DVec3, prelude::*};
+
+use crate::global::InGameSettings;
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::orbit::SatelliteOrbit;
+use crate::propagation::{crosses_ascending_node, is_plausible_prediction, InGameElements, PropagatableDuration, Propageted};
+use crate::selection::SelectionSet;
+use crate::simtime::SimInstant;
+
+/// Mean motion of the Sun, a sun-synchronous node drifts at this rate (in degrees per day)
+pub const SOLAR_RATE: f64 = 360.0 / 365.2422;
+
+/// Panel with the Earth-fixed longitude of the ascending node of the primary selected satellite and the drift of its node
+pub struct NodeDriftPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct NodeDriftSettings {
+    pub enabled: bool,
+    /// Crossings kept for the drift fit
+    pub history: usize,
+    /// States further apart in simulation time aren't interpolated, the crossing between them is missed
+    pub max_gap: Duration
+}
+
+impl Default for NodeDriftSettings {
+    fn default() -> Self {
+        Self { enabled: true, history: 32, max_gap: Duration::from_secs(5 * 60) }
+    }
+}
+
+/// Greenwich mean sidereal time in radians, `minutes_since_j2000` on the scale of [`PropagatableDuration::minutes_since_j2000`].
+/// The Earth turns with UT, the leap seconds elapsed since J2000 are taken out first
+pub fn gmst(minutes_since_j2000: f64) -> f64 {
+    gmst_ut(SimInstant::J2000.after_minutes(minutes_since_j2000).ut_minutes_since_j2000() / 1440.0)
+}
+
+/// Greenwich mean sidereal time in radians, `days` since J2000 already on the UT scale (like a `SimulationDate`)
+pub fn gmst_ut(days: f64) -> f64 {
+    (280.460_618_37 + 360.985_647_366_29 * days).to_radians().rem_euclid(TAU)
+}
+
+fn wrap_degrees(angle: f64) -> f64 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Earth-fixed longitude (in degrees, -180..180) of a node at right ascension `right_ascension`
+pub fn node_longitude(right_ascension: f64, minutes_since_j2000: f64) -> f64 {
+    wrap_degrees(right_ascension - gmst(minutes_since_j2000).to_degrees())
+}
+
+/// State of a satellite in the TEME frame at a time in minutes since J2000
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedState {
+    pub time: f64,
+    pub position: DVec3,
+    pub velocity: DVec3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeCrossing {
+    /// Minutes since J2000
+    pub time: f64,
+    /// Right ascension of the node (in degrees, 0..360)
+    pub right_ascension: f64,
+    /// Where on the equator the satellite crossed northbound (in degrees, -180..180)
+    pub longitude: f64
+}
+
+/// Ascending node crossing between consecutive states, interpolated linearly through the equatorial plane
+pub fn node_crossing(before: &TimedState, after: &TimedState) -> Option<NodeCrossing> {
+    if after.time <= before.time || !crosses_ascending_node(before.position.z, after.position.z, after.velocity.z) {
+        return None;
+    }
+    let fraction = -before.position.z / (after.position.z - before.position.z);
+    let time = before.time + fraction * (after.time - before.time);
+    let node = before.position.lerp(after.position, fraction);
+    let right_ascension = node.y.atan2(node.x).to_degrees().rem_euclid(360.0);
+    Some(NodeCrossing { time, right_ascension, longitude: node_longitude(right_ascension, time) })
+}
+
+/// Least squares drift of the node right ascension (in degrees per day), with the wrap around 360° removed
+pub fn fit_drift(crossings: &[NodeCrossing]) -> Option<f64> {
+    if crossings.len() < 2 {
+        return None;
+    }
+    let mut unwrapped = Vec::with_capacity(crossings.len());
+    let mut previous: Option<(f64, f64)> = None;
+    for crossing in crossings {
+        let value = match previous {
+            Some((raw, value)) => value + wrap_degrees(crossing.right_ascension - raw),
+            None => crossing.right_ascension
+        };
+        unwrapped.push((crossing.time / 1440.0, value));
+        previous = Some((crossing.right_ascension, value));
+    }
+    let n = unwrapped.len() as f64;
+    let (mean_t, mean_v) = unwrapped.iter().fold((0.0, 0.0), |(t, v), (ti, vi)| (t + ti / n, v + vi / n));
+    let (covariance, variance) = unwrapped.iter()
+        .fold((0.0, 0.0), |(c, s), (t, v)| (c + (t - mean_t) * (v - mean_v), s + (t - mean_t).powi(2)));
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+/// Detects node crossings in a stream of states, a state back in time or after a gap starts over
+#[derive(Debug, Clone)]
+pub struct NodeCrossingTracker {
+    last: Option<TimedState>,
+    crossings: Vec<NodeCrossing>,
+    capacity: usize,
+    max_gap: f64
+}
+
+impl NodeCrossingTracker {
+    /// `max_gap` in minutes
+    pub fn new(capacity: usize, max_gap: f64) -> Self {
+        Self { last: None, crossings: vec![], capacity: capacity.max(2), max_gap }
+    }
+
+    pub fn observe(&mut self, state: TimedState) -> Option<NodeCrossing> {
+        let crossing = self.last
+            .filter(|last| state.time - last.time <= self.max_gap)
+            .and_then(|last| node_crossing(&last, &state));
+        if self.last.is_some_and(|last| state.time < last.time) {
+            self.crossings.clear();
+        }
+        self.last = Some(state);
+        if let Some(crossing) = crossing {
+            if self.crossings.len() == self.capacity {
+                self.crossings.remove(0);
+            }
+            self.crossings.push(crossing);
+        }
+        crossing
+    }
+
+    pub fn last_state(&self) -> Option<&TimedState> {
+        self.last.as_ref()
+    }
+
+    pub fn latest(&self) -> Option<&NodeCrossing> {
+        self.crossings.last()
+    }
+
+    pub fn crossings(&self) -> impl Iterator<Item = &NodeCrossing> {
+        self.crossings.iter()
+    }
+
+    /// Measured drift, in degrees per day
+    pub fn drift(&self) -> Option<f64> {
+        fit_drift(&self.crossings)
+    }
+}
+
+/// Node of the satellite `of`, refreshed on every detected crossing
+#[derive(Resource, Debug, Clone)]
+pub struct NodeDrift {
+    pub of: Option<Entity>,
+    pub tracker: NodeCrossingTracker,
+    /// Longitude of the node from the secular RAAN at the latest state and the J2 drift, until crossings are measured
+    pub analytic: Option<(f64, f64)>
+}
+
+impl Default for NodeDrift {
+    fn default() -> Self {
+        Self { of: None, tracker: NodeCrossingTracker::new(2, 0.0), analytic: None }
+    }
+}
+
+impl MemoryFootprint for NodeDrift {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.tracker.crossings.len() * size_of::<NodeCrossing>()
+    }
+}
+
+impl Plugin for NodeDriftPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NodeDriftSettings>()
+            .init_resource::<NodeDrift>()
+            .account_resource::<NodeDrift>("histories")
+            .init_resource::<SelectionSet>()
+            .add_event::<Propageted>()
+            .add_systems(Update, (track_node_of_focused, observe_node_crossings).chain().run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, render_node_drift.after(observe_node_crossings).run_if(resource_changed::<NodeDrift>));
+    }
+}
+
+fn track_node_of_focused(drift_settings: Res<NodeDriftSettings>, selection: Res<SelectionSet>, mut drift: ResMut<NodeDrift>) {
+    let focused = selection.primary().filter(|_| drift_settings.enabled);
+    if focused == drift.of && !drift_settings.is_changed() {
+        return;
+    }
+    *drift = NodeDrift {
+        of: focused,
+        tracker: NodeCrossingTracker::new(drift_settings.history, drift_settings.max_gap.as_secs_f64() / 60.0),
+        analytic: None
+    };
+}
+
+//the panel only changes on a crossing, or on every state while they are too far apart to detect one
+fn observe_node_crossings(
+    mut propagated: EventReader<Propageted>,
+    settings: Res<InGameSettings>,
+    mut drift: ResMut<NodeDrift>,
+    satellites: Query<(&InGameElements, &PropagatableDuration, &SatelliteOrbit)>
+) {
+    let Some(entity) = drift.of else {
+        propagated.clear();
+        return;
+    };
+    let Ok((elements, elapsed, orbit)) = satellites.get(entity) else {
+        propagated.clear();
+        return;
+    };
+    let predictions = propagated.read()
+        .flat_map(|p| p.data())
+        .filter(|(e, p)| *e == entity && is_plausible_prediction(p, &settings.propagation.envelope))
+        .map(|(_, p)| p)
+        .last();
+    let Some(prediction) = predictions else {
+        return;
+    };
+    let state = TimedState {
+        time: elapsed.minutes_since_j2000(&elements.0),
+        position: DVec3::from_array(prediction.position),
+        velocity: DVec3::from_array(prediction.velocity)
+    };
+    let node = drift.bypass_change_detection();
+    let gap = node.tracker.last_state().is_none_or(|last| state.time - last.time > node.tracker.max_gap);
+    let crossing = node.tracker.observe(state);
+    if crossing.is_none() && !gap && node.analytic.is_some() {
+        return;
+    }
+    let rate = orbit.nodal_regression_rate();
+    let right_ascension = orbit.raan + rate * elapsed.minutes_since_epoch() / 1440.0;
+    node.analytic = Some((node_longitude(right_ascension, state.time), rate));
+    drift.set_changed();
+}
+
+#[derive(Component)]
+struct NodeDriftPanel;
+
+fn render_node_drift(drift: Res<NodeDrift>, panels: Query<Entity, With<NodeDriftPanel>>, mut commands: Commands) {
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some((analytic_longitude, analytic_rate)) = drift.analytic else {
+        return;
+    };
+    let east_west = |longitude: f64| format!("{:.2}° {}", longitude.abs(), if longitude < 0.0 { "W" } else { "E" });
+    let crossings = drift.tracker.crossings().count();
+    let (longitude, rate) = match (drift.tracker.latest(), drift.tracker.drift()) {
+        (Some(latest), Some(rate)) => (east_west(latest.longitude), format!("{rate:+.4}°/day over {crossings} crossings")),
+        (Some(latest), None) => (east_west(latest.longitude), format!("{analytic_rate:+.4}°/day (J2 estimate)")),
+        (None, _) => (format!("{} (J2 estimate)", east_west(analytic_longitude)), format!("{analytic_rate:+.4}°/day (J2 estimate)"))
+    };
+    let text = format!("Ascending node {longitude}\nNode drift {rate}\nSolar rate {SOLAR_RATE:+.4}°/day");
+    commands.spawn((
+        TextBundle::from_section(text, TextStyle { font_size: 14.0, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                ..default()
+            }),
+        NodeDriftPanel
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_node_crossing_interpolation() {
+        //at the J2000 epoch Greenwich is 280.46° east of the equinox
+        assert_abs_diff_eq!(gmst(0.0).to_degrees(), 280.460_618_37, epsilon = 1e-9);
+        let before = TimedState { time: 0.0, position: DVec3::new(7000.0, 0.0, -100.0), velocity: DVec3::new(0.0, 5.0, 5.0) };
+        let after = TimedState { time: 1.0, position: DVec3::new(6990.0, 300.0, 200.0), velocity: DVec3::new(0.0, 5.0, 5.0) };
+        let crossing = node_crossing(&before, &after).unwrap();
+        assert_abs_diff_eq!(crossing.time, 1.0 / 3.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(crossing.right_ascension, 100.0f64.atan2(7000.0 - 10.0 / 3.0).to_degrees(), epsilon = 1e-9);
+        assert_abs_diff_eq!(crossing.longitude, node_longitude(crossing.right_ascension, crossing.time), epsilon = 1e-12);
+        //southbound, and back in time
+        assert_eq!(node_crossing(&after, &before), None);
+        let descending = TimedState { position: DVec3::new(6990.0, 300.0, -200.0), ..after };
+        assert_eq!(node_crossing(&after, &TimedState { time: 2.0, ..descending }), None);
+
+        //the drift is unaffected by the wrap around 0°
+        let wrapped: Vec<_> = [(0.0, 359.0), (1440.0, 0.5), (2880.0, 2.0)].into_iter()
+            .map(|(time, right_ascension)| NodeCrossing { time, right_ascension, longitude: 0.0 })
+            .collect();
+        assert_abs_diff_eq!(fit_drift(&wrapped).unwrap(), 1.5, epsilon = 1e-9);
+        assert_eq!(fit_drift(&wrapped[..1]), None);
+    }
+
+    #[test]
+    fn test_sun_synchronous_node_drift() {
+        //700 km sun-synchronous orbit, the node regresses secularly while the satellite goes around
+        let orbit = SatelliteOrbit::new(7078.0, 0.001, 98.19, 40.0, 0.0, 0.0, 0.0);
+        let rate = orbit.nodal_regression_rate();
+        let start = 9_000_000.0;
+        let mut tracker = NodeCrossingTracker::new(100, 5.0);
+        let mut crossings = 0;
+        for minute in 0..(3 * 1440) {
+            let days = minute as f64 / 1440.0;
+            let moved = SatelliteOrbit { raan: 40.0 + rate * days, ..orbit.propagate(minute as f64 * 60.0) };
+            let state = TimedState {
+                time: start + minute as f64,
+                position: moved.position(),
+                velocity: moved.velocity()
+            };
+            if let Some(crossing) = tracker.observe(state) {
+                crossings += 1;
+                //the node is where the right ascension says, shifted by the Earth rotation
+                let expected = node_longitude(40.0 + rate * (crossing.time - start) / 1440.0, crossing.time);
+                assert_abs_diff_eq!(crossing.longitude, expected, epsilon = 0.05);
+            }
+        }
+        //about 14.6 revolutions a day
+        assert!((42..=45).contains(&crossings), "{crossings}");
+        assert_abs_diff_eq!(tracker.drift().unwrap(), SOLAR_RATE, epsilon = 0.05);
+
+        //a jump back in time starts over
+        tracker.observe(TimedState { time: start, position: DVec3::X, velocity: DVec3::Y });
+        assert_eq!(tracker.drift(), None);
+    }
+}
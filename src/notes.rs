@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::input::ActionCategory;
+use crate::propagation::{InGameElements, SatelliteSpawned};
+use crate::selection::SelectionSet;
+
+/// Notes and tags operators attach to satellites. They are kept by NORAD id in [`Annotations`], saved with the session
+/// and attached again whenever the satellite is loaded
+pub struct NotesPlugin;
+
+/// Free text note of a satellite
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct Notes(pub String);
+
+/// Tags of a satellite, searched like its name
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct CustomTags(pub Vec<String>);
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Annotation {
+    pub note: String,
+    pub tags: Vec<String>
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_empty() && self.tags.is_empty()
+    }
+}
+
+/// Annotations of every satellite by NORAD id, including the ones not loaded right now
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct Annotations(pub BTreeMap<u64, Annotation>);
+
+/// Replaces the note of the satellite, an empty one removes it
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct SetSatelliteNote {
+    pub entity: Entity,
+    pub note: String
+}
+
+/// Adds the tag to the satellite, or removes it
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TagSatellite {
+    pub entity: Entity,
+    pub tag: String,
+    pub remove: bool
+}
+
+impl Plugin for NotesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Annotations>()
+            .init_resource::<SelectionSet>()
+            .add_event::<SetSatelliteNote>()
+            .add_event::<TagSatellite>()
+            .add_event::<SatelliteSpawned>()
+            .register_command(
+                CommandDescriptor::new("Set note", ActionCategory::Selection, |params, world| {
+                    let Some(entity) = world.resource::<SelectionSet>().primary() else {
+                        return;
+                    };
+                    let note = params[0].as_text().unwrap_or_default().to_owned();
+                    world.send_event(SetSatelliteNote { entity, note });
+                })
+                .with_param("note", ParamKind::Text)
+                .satellite_scoped()
+            )
+            .register_command(
+                CommandDescriptor::new("Tag selection", ActionCategory::Selection, |params, world| tag_selection(params[0].as_text(), false, world))
+                    .with_param("tag", ParamKind::Text)
+                    .satellite_scoped()
+            )
+            .register_command(
+                CommandDescriptor::new("Untag selection", ActionCategory::Selection, |params, world| tag_selection(params[0].as_text(), true, world))
+                    .with_param("tag", ParamKind::Text)
+                    .satellite_scoped()
+            )
+            .add_systems(Update, (annotate, attach_annotations).chain());
+    }
+}
+
+fn tag_selection(tag: Option<&str>, remove: bool, world: &mut World) {
+    let Some(tag) = tag else {
+        return;
+    };
+    let selected: Vec<_> = world.resource::<SelectionSet>().iter().collect();
+    for entity in selected {
+        world.send_event(TagSatellite { entity, tag: tag.to_owned(), remove });
+    }
+}
+
+fn annotate(
+    mut notes: EventReader<SetSatelliteNote>,
+    mut tags: EventReader<TagSatellite>,
+    satellites: Query<&InGameElements>,
+    mut annotations: ResMut<Annotations>
+) {
+    let norad_id = |entity: Entity| satellites.get(entity).ok().map(|elements| elements.0.norad_id);
+    for event in notes.read() {
+        if let Some(norad_id) = norad_id(event.entity) {
+            annotations.0.entry(norad_id).or_default().note = event.note.trim().to_owned();
+        }
+    }
+    for event in tags.read() {
+        //a tag is a single line, saved sessions keep it on one
+        let tag = event.tag.split_whitespace().collect::<Vec<_>>().join(" ");
+        let Some(norad_id) = norad_id(event.entity).filter(|_| !tag.is_empty()) else {
+            continue;
+        };
+        let annotation = annotations.0.entry(norad_id).or_default();
+        annotation.tags.retain(|t| !t.eq_ignore_ascii_case(&tag));
+        if !event.remove {
+            annotation.tags.push(tag);
+        }
+    }
+    if annotations.is_changed() {
+        annotations.0.retain(|_, annotation| !annotation.is_empty());
+    }
+}
+
+//the components mirror the annotations, all of them after a change and the spawned satellites otherwise
+fn attach_annotations(
+    mut spawned: EventReader<SatelliteSpawned>,
+    annotations: Res<Annotations>,
+    satellites: Query<(Entity, &InGameElements)>,
+    mut commands: Commands
+) {
+    let targets: Vec<(Entity, u64)> = if annotations.is_changed() {
+        spawned.clear();
+        satellites.iter().map(|(entity, elements)| (entity, elements.0.norad_id)).collect()
+    } else {
+        spawned.read().map(|spawned| (spawned.entity, spawned.norad_id)).collect()
+    };
+    for (entity, norad_id) in targets {
+        let Some(mut satellite) = commands.get_entity(entity) else {
+            continue;
+        };
+        let annotation = annotations.0.get(&norad_id).cloned().unwrap_or_default();
+        match annotation.note.is_empty() {
+            true => satellite.remove::<Notes>(),
+            false => satellite.insert(Notes(annotation.note))
+        };
+        match annotation.tags.is_empty() {
+            true => satellite.remove::<CustomTags>(),
+            false => satellite.insert(CustomTags(annotation.tags))
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagation::{ElementsFormat, LoadElements, LoadElementsPlugin};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    #[test]
+    fn test_annotations_follow_the_norad_id() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<SyntheticClient>::new(), NotesPlugin))
+            .insert_resource(SyntheticClient(starlink_like_elements(5, 1486)));
+        app.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+        let satellite = |app: &mut App, norad_id: u64| app.world_mut().query::<(Entity, &InGameElements)>().iter(app.world())
+            .find(|(_, elements)| elements.0.norad_id == norad_id)
+            .map(|(entity, _)| entity);
+        for _ in 0..100 {
+            app.update();
+            if satellite(&mut app, 44002).is_some() {
+                break;
+            }
+        }
+        let entity = satellite(&mut app, 44002).unwrap();
+        app.world_mut().send_event(SetSatelliteNote { entity, note: " Deorbiting in May ".to_owned() });
+        app.world_mut().send_event(TagSatellite { entity, tag: "watch  list".to_owned(), remove: false });
+        app.world_mut().send_event(TagSatellite { entity, tag: "Watch list".to_owned(), remove: false });
+        app.world_mut().send_event(TagSatellite { entity, tag: "calibration".to_owned(), remove: false });
+        app.update();
+        assert_eq!(app.world().get::<Notes>(entity), Some(&Notes("Deorbiting in May".to_owned())));
+        assert_eq!(app.world().get::<CustomTags>(entity), Some(&CustomTags(vec!["Watch list".to_owned(), "calibration".to_owned()])));
+
+        app.world_mut().send_event(SetSatelliteNote { entity, note: String::new() });
+        app.world_mut().send_event(TagSatellite { entity, tag: "WATCH LIST".to_owned(), remove: true });
+        app.update();
+        assert_eq!(app.world().get::<Notes>(entity), None);
+        assert_eq!(app.world().get::<CustomTags>(entity), Some(&CustomTags(vec!["calibration".to_owned()])));
+        assert_eq!(app.world().resource::<Annotations>().0.keys().copied().collect::<Vec<_>>(), vec![44002]);
+    }
+}
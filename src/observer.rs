@@ -0,0 +1,460 @@
+use bevy::{math::{DQuat, DVec3}, prelude::*};
+use sgp4::Elements;
+
+use crate::input::{Action, ActionTriggered};
+use crate::node_drift::gmst;
+use crate::orbit::EARTH_EQUATORIAL_RADIUS;
+use crate::propagation::predict_at;
+use crate::simtime::SimInstant;
+
+//WGS84
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+
+//rise and set times are refined to this many minutes
+const CROSSING_TOLERANCE: f64 = 1e-3;
+
+/// Ground station satellites are observed from
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Observer {
+    pub name: String,
+    /// Geodetic latitude (in degrees)
+    pub latitude: f64,
+    /// Longitude (in degrees, east positive)
+    pub longitude: f64,
+    /// Height above the ellipsoid (in kilometers)
+    pub altitude_km: f64,
+    /// Elevation a satellite must clear everywhere (in degrees), the horizon profile only raises it
+    pub min_elevation: f64,
+    /// Terrain and buildings around the station
+    pub horizon: Option<HorizonProfile>
+}
+
+/// Observer the pass predictions and readouts are made for, cycled with [`Action::NextObserver`] and [`Action::PreviousObserver`]
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct SelectedObserver(pub Option<Entity>);
+
+/// Keeps the [`SelectedObserver`] pointing at an observer, the first one is selected as soon as there is one
+pub struct ObserversPlugin;
+
+impl Plugin for ObserversPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SelectedObserver>()
+            .add_event::<ActionTriggered>()
+            .add_systems(Update, (cycle_observers, keep_observer_selected).chain());
+    }
+}
+
+//by name, the order the user sees them in
+fn sorted_observers(observers: &Query<(Entity, &Observer)>) -> Vec<Entity> {
+    let mut sorted: Vec<_> = observers.iter().collect();
+    sorted.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    sorted.into_iter().map(|(entity, _)| entity).collect()
+}
+
+fn cycle_observers(mut actions: EventReader<ActionTriggered>, observers: Query<(Entity, &Observer)>, mut selected: ResMut<SelectedObserver>) {
+    for ActionTriggered(action) in actions.read() {
+        let step = match action {
+            Action::NextObserver => 1,
+            Action::PreviousObserver => -1,
+            _ => continue
+        };
+        let sorted = sorted_observers(&observers);
+        if sorted.is_empty() {
+            continue;
+        }
+        let next = match sorted.iter().position(|e| Some(*e) == selected.0) {
+            Some(current) => (current as isize + step).rem_euclid(sorted.len() as isize) as usize,
+            None if step > 0 => 0,
+            None => sorted.len() - 1
+        };
+        selected.0 = Some(sorted[next]);
+    }
+}
+
+fn keep_observer_selected(observers: Query<(Entity, &Observer)>, mut selected: ResMut<SelectedObserver>) {
+    if selected.0.is_some_and(|e| observers.contains(e)) {
+        return;
+    }
+    let first = sorted_observers(&observers).first().copied();
+    selected.set_if_neq(SelectedObserver(first));
+}
+
+/// Minimum elevation by azimuth, linear between breakpoints and across north between the last and the first one
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorizonProfile(Vec<(f64, f64)>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HorizonProfileError {
+    Malformed { line: usize },
+    Empty
+}
+
+impl HorizonProfile {
+    /// Breakpoints of `(azimuth, minimum elevation)` in degrees, in any order
+    pub fn new(breakpoints: impl IntoIterator<Item = (f64, f64)>) -> Result<Self, HorizonProfileError> {
+        let mut points: Vec<_> = breakpoints.into_iter().map(|(azimuth, elevation)| (azimuth.rem_euclid(360.0), elevation)).collect();
+        if points.is_empty() {
+            return Err(HorizonProfileError::Empty);
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self(points))
+    }
+
+    /// One `azimuth,elevation` breakpoint per line, blank lines and `#` comments are skipped
+    pub fn parse(content: &str) -> Result<Self, HorizonProfileError> {
+        let mut points = vec![];
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let malformed = HorizonProfileError::Malformed { line: index + 1 };
+            let (azimuth, elevation) = line.split_once(',').ok_or(malformed.clone())?;
+            let parse = |value: &str| value.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+            points.push((parse(azimuth).ok_or(malformed.clone())?, parse(elevation).ok_or(malformed)?));
+        }
+        Self::new(points)
+    }
+
+    pub fn breakpoints(&self) -> &[(f64, f64)] {
+        &self.0
+    }
+
+    pub fn min_elevation_at(&self, azimuth: f64) -> f64 {
+        let azimuth = azimuth.rem_euclid(360.0);
+        let points = &self.0;
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let (before, after) = match points.partition_point(|(a, _)| *a <= azimuth) {
+            0 => ((last.0 - 360.0, last.1), first),
+            i if i == points.len() => (last, (first.0 + 360.0, first.1)),
+            i => (points[i - 1], points[i])
+        };
+        let span = after.0 - before.0;
+        if span <= 0.0 {
+            return before.1;
+        }
+        before.1 + (after.1 - before.1) * (azimuth - before.0) / span
+    }
+}
+
+/// Direction and distance of a satellite as seen by an observer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookAngles {
+    /// Degrees from north, clockwise
+    pub azimuth: f64,
+    pub elevation: f64,
+    pub range_km: f64
+}
+
+/// Interval a satellite stays above the horizon mask of an observer, times in minutes since J2000
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pass {
+    pub rise: f64,
+    pub culmination: f64,
+    pub set: f64,
+    pub max_elevation: f64,
+    pub rise_azimuth: f64,
+    pub set_azimuth: f64
+}
+
+impl Observer {
+    pub fn new(name: impl Into<String>, latitude: f64, longitude: f64, altitude_km: f64) -> Self {
+        Self { name: name.into(), latitude, longitude, altitude_km, min_elevation: 0.0, horizon: None }
+    }
+
+    pub fn with_min_elevation(mut self, min_elevation: f64) -> Self {
+        self.min_elevation = min_elevation;
+        self
+    }
+
+    pub fn with_horizon(mut self, horizon: HorizonProfile) -> Self {
+        self.horizon = Some(horizon);
+        self
+    }
+
+    /// Earth-fixed position (in kilometers)
+    pub fn position_ecef(&self) -> DVec3 {
+        geodetic_to_ecef(self.latitude, self.longitude, self.altitude_km)
+    }
+
+    /// Look angles of a TEME position (in kilometers) at a time in minutes since J2000
+    pub fn look_angles(&self, position: DVec3, minutes_since_j2000: f64) -> LookAngles {
+        let ecef = DQuat::from_rotation_z(-gmst(minutes_since_j2000)) * position;
+        let relative = ecef - self.position_ecef();
+        let (lat, lon) = (self.latitude.to_radians(), self.longitude.to_radians());
+        let east = -lon.sin() * relative.x + lon.cos() * relative.y;
+        let north = -lat.sin() * lon.cos() * relative.x - lat.sin() * lon.sin() * relative.y + lat.cos() * relative.z;
+        let up = lat.cos() * lon.cos() * relative.x + lat.cos() * lon.sin() * relative.y + lat.sin() * relative.z;
+        let range_km = relative.length();
+        LookAngles {
+            azimuth: east.atan2(north).to_degrees().rem_euclid(360.0),
+            elevation: (up / range_km).asin().to_degrees(),
+            range_km
+        }
+    }
+
+    /// Elevation the satellite must clear in the direction of `azimuth`
+    pub fn mask_at(&self, azimuth: f64) -> f64 {
+        match &self.horizon {
+            Some(horizon) => horizon.min_elevation_at(azimuth).max(self.min_elevation),
+            None => self.min_elevation
+        }
+    }
+
+    /// Degrees above the mask, negative when the satellite is hidden
+    pub fn clearance(&self, look: &LookAngles) -> f64 {
+        look.elevation - self.mask_at(look.azimuth)
+    }
+}
+
+/// Point on the WGS84 ellipsoid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geodetic {
+    /// Geodetic latitude (in degrees)
+    pub latitude: f64,
+    /// Longitude (in degrees, east positive, -180..180)
+    pub longitude: f64,
+    /// Height above the ellipsoid (in kilometers)
+    pub altitude_km: f64
+}
+
+fn eccentricity_squared() -> f64 {
+    FLATTENING * (2.0 - FLATTENING)
+}
+
+/// Earth-fixed position (in kilometers) of geodetic coordinates (in degrees)
+pub fn geodetic_to_ecef(latitude: f64, longitude: f64, altitude_km: f64) -> DVec3 {
+    let (lat, lon) = (latitude.to_radians(), longitude.to_radians());
+    let e2 = eccentricity_squared();
+    let n = EARTH_EQUATORIAL_RADIUS / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    DVec3::new(
+        (n + altitude_km) * lat.cos() * lon.cos(),
+        (n + altitude_km) * lat.cos() * lon.sin(),
+        (n * (1.0 - e2) + altitude_km) * lat.sin()
+    )
+}
+
+/// Geodetic coordinates of an Earth-fixed position (in kilometers), the latitude is iterated to well below a millimeter
+pub fn ecef_to_geodetic(position: DVec3) -> Geodetic {
+    let e2 = eccentricity_squared();
+    let p = position.x.hypot(position.y);
+    let mut lat = position.z.atan2(p * (1.0 - e2));
+    for _ in 0..6 {
+        let n = EARTH_EQUATORIAL_RADIUS / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        lat = (position.z + e2 * n * lat.sin()).atan2(p);
+    }
+    //valid at the poles too, unlike p / cos(lat) - N
+    let altitude_km = p * lat.cos() + position.z * lat.sin() - EARTH_EQUATORIAL_RADIUS * (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    Geodetic { latitude: lat.to_degrees(), longitude: position.y.atan2(position.x).to_degrees(), altitude_km }
+}
+
+/// First point where the ray (in kilometers, any frame with Z along the Earth axis) meets the WGS84 ellipsoid
+pub fn ray_ellipsoid_intersection(origin: DVec3, direction: DVec3) -> Option<DVec3> {
+    //in coordinates where the ellipsoid is the unit sphere
+    let stretch = DVec3::new(EARTH_EQUATORIAL_RADIUS, EARTH_EQUATORIAL_RADIUS, EARTH_EQUATORIAL_RADIUS * (1.0 - FLATTENING));
+    let (o, d) = (origin / stretch, direction / stretch);
+    let (a, b, c) = (d.length_squared(), o.dot(d), o.length_squared() - 1.0);
+    let discriminant = b * b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = [(-b - discriminant.sqrt()) / a, (-b + discriminant.sqrt()) / a].into_iter().find(|t| *t >= 0.0)?;
+    Some(origin + direction * t)
+}
+
+/// Right ascension (0..360) and declination of an inertial direction (in degrees), the equatorial grid coordinates
+pub fn right_ascension_declination(direction: DVec3) -> (f64, f64) {
+    let direction = direction.normalize();
+    (direction.y.atan2(direction.x).to_degrees().rem_euclid(360.0), direction.z.clamp(-1.0, 1.0).asin().to_degrees())
+}
+
+/// Passes over the observer between `from` and `to` (minutes since J2000), sampled every `step` minutes.
+/// Passes shorter than the step may be missed, passes cut by the horizon profile are split or dropped,
+/// a pass in progress at either end of the window is truncated to it
+pub fn predict_passes(observer: &Observer, elements: &Elements, from: f64, to: f64, step: f64) -> Vec<Pass> {
+    let epoch = SimInstant::epoch(elements).minutes_since(SimInstant::J2000);
+    let look = |t: f64| predict_at(elements, t - epoch, false).map(|p| observer.look_angles(DVec3::from_array(p.position), t));
+    let clearance = |t: f64| look(t).map_or(f64::NEG_INFINITY, |l| observer.clearance(&l));
+    //bisection of the interval the clearance changes sign in
+    let crossing = |mut low: f64, mut high: f64| {
+        let rising = clearance(low) <= 0.0;
+        while high - low > CROSSING_TOLERANCE {
+            let middle = (low + high) / 2.0;
+            if (clearance(middle) > 0.0) == rising { high = middle } else { low = middle }
+        }
+        (low + high) / 2.0
+    };
+    let pass = |rise: f64, set: f64| {
+        //golden section search, the elevation has a single maximum during a pass
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let elevation = |t: f64| look(t).map_or(f64::NEG_INFINITY, |l| l.elevation);
+        let (mut low, mut high) = (rise, set);
+        while high - low > CROSSING_TOLERANCE {
+            let (a, b) = (high - ratio * (high - low), low + ratio * (high - low));
+            if elevation(a) < elevation(b) { low = a } else { high = b }
+        }
+        let culmination = (low + high) / 2.0;
+        let azimuth = |t: f64| look(t).map_or(0.0, |l| l.azimuth);
+        Pass { rise, culmination, set, max_elevation: elevation(culmination), rise_azimuth: azimuth(rise), set_azimuth: azimuth(set) }
+    };
+
+    let mut passes = vec![];
+    let mut rise = (clearance(from) > 0.0).then_some(from);
+    let mut t = from;
+    while t < to {
+        let next = (t + step).min(to);
+        match (rise, clearance(next) > 0.0) {
+            (None, true) => rise = Some(crossing(t, next)),
+            (Some(start), false) => {
+                passes.push(pass(start, crossing(t, next)));
+                rise = None;
+            },
+            _ => {}
+        }
+        t = next;
+    }
+    if let Some(start) = rise {
+        passes.push(pass(start, to));
+    }
+    passes
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::stress::starlink_like_elements;
+
+    #[test]
+    fn test_geodesy_against_known_points() {
+        let polar_radius = EARTH_EQUATORIAL_RADIUS * (1.0 - FLATTENING);
+        assert_abs_diff_eq!(polar_radius, 6356.752314, epsilon = 1e-6);
+        let origin = ecef_to_geodetic(DVec3::new(EARTH_EQUATORIAL_RADIUS, 0.0, 0.0));
+        assert_abs_diff_eq!(origin.latitude, 0.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(origin.altitude_km, 0.0, epsilon = 1e-9);
+        let pole = ecef_to_geodetic(DVec3::new(0.0, 0.0, polar_radius + 1.0));
+        assert_abs_diff_eq!(pole.latitude, 90.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(pole.altitude_km, 1.0, epsilon = 1e-9);
+        //the geodetic latitude of Greenwich is 0.19° north of the geocentric one
+        let greenwich = geodetic_to_ecef(51.4779, -0.0015, 0.046);
+        assert_abs_diff_eq!(greenwich.z.atan2(greenwich.x.hypot(greenwich.y)).to_degrees(), 51.2902, epsilon = 1e-3);
+        for (latitude, longitude, altitude_km) in [(51.4779, -0.0015, 0.046), (-33.9249, 18.4241, 0.0), (89.9, 170.0, 400.0), (-12.0, -77.0, -0.05)] {
+            let back = ecef_to_geodetic(geodetic_to_ecef(latitude, longitude, altitude_km));
+            assert_abs_diff_eq!(back.latitude, latitude, epsilon = 1e-9);
+            assert_abs_diff_eq!(back.longitude, longitude, epsilon = 1e-9);
+            assert_abs_diff_eq!(back.altitude_km, altitude_km, epsilon = 1e-6);
+        }
+
+        let hit = ray_ellipsoid_intersection(DVec3::new(20000.0, 0.0, 0.0), DVec3::NEG_X).unwrap();
+        assert_abs_diff_eq!(hit.x, EARTH_EQUATORIAL_RADIUS, epsilon = 1e-9);
+        let hit = ray_ellipsoid_intersection(DVec3::new(0.0, 0.0, 20000.0), DVec3::new(0.0, 0.0, -3.0)).unwrap();
+        assert_abs_diff_eq!(hit.z, polar_radius, epsilon = 1e-9);
+        //towards Cape Town, the hit is on the ellipsoid at its latitude
+        let cape_town = geodetic_to_ecef(-33.9249, 18.4241, 0.0);
+        let eye = cape_town * 3.0 + DVec3::new(0.0, 0.0, 500.0);
+        let hit = ray_ellipsoid_intersection(eye, cape_town - eye).unwrap();
+        assert_abs_diff_eq!(hit.distance(cape_town), 0.0, epsilon = 1e-6);
+        assert_eq!(ray_ellipsoid_intersection(DVec3::new(20000.0, 0.0, 0.0), DVec3::Y), None);
+        assert_eq!(ray_ellipsoid_intersection(DVec3::new(20000.0, 0.0, 0.0), DVec3::X), None);
+
+        let (ra, dec) = right_ascension_declination(DVec3::new(-1.0, 0.0, 1.0));
+        assert_abs_diff_eq!(ra, 180.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(dec, 45.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(right_ascension_declination(DVec3::new(0.0, -2.0, 0.0)).0, 270.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_profile_interpolation_wraps_around_north() {
+        let profile = HorizonProfile::new([(350.0, 10.0), (10.0, 30.0), (180.0, 0.0)]).unwrap();
+        assert_abs_diff_eq!(profile.min_elevation_at(0.0), 20.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(profile.min_elevation_at(355.0), 15.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(profile.min_elevation_at(365.0), 25.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(profile.min_elevation_at(95.0), 15.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(profile.min_elevation_at(265.0), 5.0, epsilon = 1e-9);
+
+        assert_eq!(HorizonProfile::parse("# station\n350, 10\n\n10,30 # mast\n180,0\n"), Ok(profile));
+        assert_eq!(HorizonProfile::parse("10,30\n90;20"), Err(HorizonProfileError::Malformed { line: 2 }));
+        assert_eq!(HorizonProfile::parse("# nothing"), Err(HorizonProfileError::Empty));
+    }
+
+    #[test]
+    fn test_mountain_in_the_east_blocks_eastern_passes() {
+        let flat = Observer::new("Ondřejov", 49.9, 14.8, 0.5).with_min_elevation(5.0);
+        let mountain = HorizonProfile::new([(10.0, 0.0), (20.0, 60.0), (160.0, 60.0), (170.0, 0.0)]).unwrap();
+        let masked = flat.clone().with_horizon(mountain);
+        let east = |azimuth: f64| (10.0..=170.0).contains(&azimuth);
+
+        let (mut rejected, mut survived) = (0, 0);
+        for elements in starlink_like_elements(20, 1476) {
+            let from = SimInstant::epoch(&elements).minutes_since(SimInstant::J2000);
+            let flat_passes = predict_passes(&flat, &elements, from, from + 1440.0, 0.5);
+            let masked_passes = predict_passes(&masked, &elements, from, from + 1440.0, 0.5);
+            for pass in &flat_passes {
+                let culmination = flat.look_angles(DVec3::from_array(predict_at(&elements, pass.culmination - from, false).unwrap().position), pass.culmination);
+                let overlapping: Vec<_> = masked_passes.iter().filter(|p| p.rise < pass.set && p.set > pass.rise).collect();
+                if east(culmination.azimuth) && (30.0..150.0).contains(&culmination.azimuth) && pass.max_elevation < 50.0 {
+                    //the highest point is behind the mountain, what's left can only be the ends of the pass
+                    assert!(overlapping.iter().all(|p| p.set < pass.culmination || p.rise > pass.culmination), "{pass:?} vs {overlapping:?}");
+                    rejected += 1;
+                } else if !east(pass.rise_azimuth) && !east(pass.set_azimuth) && !east(culmination.azimuth) && culmination.azimuth > 180.0 {
+                    assert_eq!(overlapping.len(), 1);
+                    assert_abs_diff_eq!(overlapping[0].rise, pass.rise, epsilon = 0.01);
+                    assert_abs_diff_eq!(overlapping[0].set, pass.set, epsilon = 0.01);
+                    survived += 1;
+                }
+            }
+            //nothing predicted with the profile is ever below it
+            for pass in &masked_passes {
+                let look = masked.look_angles(DVec3::from_array(predict_at(&elements, pass.culmination - from, false).unwrap().position), pass.culmination);
+                assert!(masked.clearance(&look) > -0.1, "{pass:?}");
+            }
+        }
+        assert!(rejected > 0 && survived > 0, "{rejected} rejected, {survived} survived");
+    }
+
+    #[test]
+    fn test_keys_cycle_the_selected_observer() {
+        use crate::input::{InputMap, InputPlugin};
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, ObserversPlugin));
+        app.init_resource::<ButtonInput<KeyCode>>();
+        app.update();
+        assert_eq!(app.world().resource::<SelectedObserver>().0, None);
+
+        let matera = app.world_mut().spawn(Observer::new("Matera", 40.65, 16.7, 0.5)).id();
+        let kiruna = app.world_mut().spawn(Observer::new("Kiruna", 67.88, 21.07, 0.3)).id();
+        let svalbard = app.world_mut().spawn(Observer::new("Svalbard", 78.23, 15.41, 0.5)).id();
+        app.update();
+        assert_eq!(app.world().resource::<SelectedObserver>().0, Some(kiruna));
+
+        let press = |app: &mut App, action: Action| {
+            let binding = *app.world().resource::<InputMap>().bindings_for(action).next().unwrap();
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keys.reset_all();
+            if binding.shift {
+                keys.press(KeyCode::ShiftLeft);
+            }
+            keys.press(binding.key);
+            app.update();
+            app.world_mut().resource_mut::<ButtonInput<KeyCode>>().release_all();
+            app.world().resource::<SelectedObserver>().0
+        };
+        //by name, wrapping around both ways
+        assert_eq!(press(&mut app, Action::NextObserver), Some(matera));
+        assert_eq!(press(&mut app, Action::NextObserver), Some(svalbard));
+        assert_eq!(press(&mut app, Action::NextObserver), Some(kiruna));
+        assert_eq!(press(&mut app, Action::PreviousObserver), Some(svalbard));
+
+        //a removed observer hands the selection over to the first one
+        app.world_mut().entity_mut(svalbard).remove::<Observer>();
+        app.update();
+        assert_eq!(app.world().resource::<SelectedObserver>().0, Some(kiruna));
+        app.world_mut().despawn(kiruna);
+        app.world_mut().despawn(matera);
+        app.update();
+        assert_eq!(app.world().resource::<SelectedObserver>().0, None);
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::edits::{Change, Edit};
+use crate::input::ActionCategory;
+use crate::observer::Observer;
+
+/// Imports ground stations from a CSV file (`name,latitude,longitude,altitude,min elevation`) or from the waypoints of a GPX file.
+/// The import is one edit, undone at once
+pub struct ObserverImportPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObserverFormat {
+    Csv,
+    Gpx
+}
+
+impl ObserverFormat {
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ObserverFormat::Csv),
+            "gpx" => Some(ObserverFormat::Gpx),
+            _ => None
+        }
+    }
+
+    pub fn parse(&self, content: &str) -> ParsedObservers {
+        match self {
+            ObserverFormat::Csv => parse_csv(content),
+            ObserverFormat::Gpx => parse_gpx(content)
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ImportObservers {
+    pub path: PathBuf,
+    pub format: ObserverFormat
+}
+
+/// Row of the file that didn't make an observer
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    /// 1-based, 0 when the whole file couldn't be read
+    pub line: usize,
+    pub reason: String
+}
+
+impl fmt::Display for RejectedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Summary of an import
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ObserversImported {
+    pub path: PathBuf,
+    /// Names of the created observers, duplicates suffixed
+    pub accepted: Vec<String>,
+    pub rejected: Vec<RejectedRow>
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParsedObservers {
+    pub accepted: Vec<Observer>,
+    pub rejected: Vec<RejectedRow>
+}
+
+impl ParsedObservers {
+    fn push(&mut self, line: usize, row: Result<Observer, String>) {
+        match row {
+            Ok(observer) => self.accepted.push(observer),
+            Err(reason) => self.rejected.push(RejectedRow { line, reason })
+        }
+    }
+}
+
+impl Plugin for ObserverImportPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ImportObservers>()
+            .add_event::<ObserversImported>()
+            .register_command(
+                CommandDescriptor::new("Import observers", ActionCategory::General, |params, world| {
+                    let Some(path) = params[0].as_path() else {
+                        return;
+                    };
+                    match ObserverFormat::of(path) {
+                        Some(format) => {
+                            world.send_event(ImportObservers { path: path.clone(), format });
+                        },
+                        None => warn!("{} is neither a CSV nor a GPX file", path.display())
+                    }
+                })
+                    .with_param("file", ParamKind::Path)
+            )
+            .add_systems(Update, import_observers);
+    }
+}
+
+//altitude in meters, elevation in degrees
+fn observer(name: &str, latitude: f64, longitude: f64, altitude: f64, min_elevation: f64) -> Result<Observer, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("missing name".to_owned());
+    }
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!("latitude {latitude} is out of range"));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("longitude {longitude} is out of range"));
+    }
+    if !altitude.is_finite() {
+        return Err(format!("altitude {altitude} is not a number"));
+    }
+    if !(-90.0..=90.0).contains(&min_elevation) {
+        return Err(format!("minimum elevation {min_elevation} is out of range"));
+    }
+    Ok(Observer::new(name, latitude, longitude, altitude / 1000.0).with_min_elevation(min_elevation))
+}
+
+fn number(field: Option<&str>, what: &str) -> Result<f64, String> {
+    let field = field.map(str::trim).filter(|f| !f.is_empty()).ok_or_else(|| format!("missing {what}"))?;
+    field.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(|| format!("{what} `{field}` is not a number"))
+}
+
+//commas inside double quotes don't split, a doubled quote is a quote
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c)
+        }
+    }
+    fields
+}
+
+/// `name,latitude,longitude[,altitude (m)[,min elevation (°)]]` per line, with an optional header.
+/// Blank lines and `#` comments are skipped
+pub fn parse_csv(content: &str) -> ParsedObservers {
+    let mut parsed = ParsedObservers::default();
+    let mut header_allowed = true;
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let fields = csv_fields(line);
+        if std::mem::take(&mut header_allowed) && fields[0].trim().eq_ignore_ascii_case("name") {
+            continue;
+        }
+        let field = |i: usize| fields.get(i).map(String::as_str);
+        let optional = |i: usize, what: &str| match field(i).map(str::trim) {
+            None | Some("") => Ok(0.0),
+            value => number(value, what)
+        };
+        let row = (|| {
+            if fields.len() > 5 {
+                return Err(format!("{} fields, at most 5 expected", fields.len()));
+            }
+            observer(
+                &fields[0],
+                number(field(1), "latitude")?,
+                number(field(2), "longitude")?,
+                optional(3, "altitude")?,
+                optional(4, "minimum elevation")?
+            )
+        })();
+        parsed.push(index + 1, row);
+    }
+    parsed
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    for quote in ['"', '\''] {
+        let start = format!(" {name}={quote}");
+        if let Some(position) = tag.find(&start) {
+            let value = &tag[position + start.len()..];
+            return Some(decode_entities(&value[..value.find(quote)?]));
+        }
+    }
+    None
+}
+
+fn element(body: &str, name: &str) -> Option<String> {
+    let start = body.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + body[start..].find(&format!("</{name}>"))?;
+    Some(decode_entities(body[start..end].trim()))
+}
+
+/// Waypoints (`<wpt lat lon>` with a `<name>` and an optional `<ele>` in meters), routes and tracks are ignored
+pub fn parse_gpx(content: &str) -> ParsedObservers {
+    let mut parsed = ParsedObservers::default();
+    let mut rest = 0;
+    while let Some(found) = content[rest..].find("<wpt") {
+        let start = rest + found;
+        let line = content[..start].matches('\n').count() + 1;
+        let Some(tag_end) = content[start..].find('>').map(|e| start + e) else {
+            parsed.push(line, Err("unterminated waypoint".to_owned()));
+            break;
+        };
+        let tag = &content[start..tag_end];
+        //a self-closing waypoint has no name
+        let (body, next) = if tag.ends_with('/') {
+            ("", tag_end + 1)
+        } else {
+            match content[tag_end..].find("</wpt>") {
+                Some(end) => (&content[tag_end + 1..tag_end + end], tag_end + end + "</wpt>".len()),
+                None => {
+                    parsed.push(line, Err("unterminated waypoint".to_owned()));
+                    break;
+                }
+            }
+        };
+        rest = next;
+        let coordinate = |name: &str, what: &str| number(attribute(tag, name).as_deref(), what);
+        let row = (|| {
+            let elevation = element(body, "ele").map_or(Ok(0.0), |ele| number(Some(&ele), "elevation"))?;
+            observer(
+                &element(body, "name").unwrap_or_default(),
+                coordinate("lat", "latitude")?,
+                coordinate("lon", "longitude")?,
+                elevation,
+                0.0
+            )
+        })();
+        parsed.push(line, row);
+    }
+    parsed
+}
+
+/// `name` if it's not taken, otherwise suffixed with the first free ` (n)`
+pub fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_owned();
+    }
+    (2..).map(|n| format!("{name} ({n})")).find(|candidate| !taken.contains(candidate)).unwrap()
+}
+
+fn import_observers(
+    mut imports: EventReader<ImportObservers>,
+    observers: Query<&Observer>,
+    mut imported: EventWriter<ObserversImported>,
+    mut commands: Commands
+) {
+    let mut taken: HashSet<String> = observers.iter().map(|o| o.name.clone()).collect();
+    for ImportObservers { path, format } in imports.read() {
+        let parsed = match fs::read_to_string(path) {
+            Ok(content) => format.parse(&content),
+            Err(err) => ParsedObservers { accepted: vec![], rejected: vec![RejectedRow { line: 0, reason: err.to_string() }] }
+        };
+        let mut changes = vec![];
+        let mut accepted = vec![];
+        for mut observer in parsed.accepted {
+            observer.name = unique_name(&observer.name, &taken);
+            taken.insert(observer.name.clone());
+            accepted.push(observer.name.clone());
+            changes.push(Change::Observer { entity: commands.spawn_empty().id(), before: None, after: Some(observer) });
+        }
+        info!("Imported {} observers from {}, {} rows rejected", accepted.len(), path.display(), parsed.rejected.len());
+        for row in &parsed.rejected {
+            warn!("{}: {row}", path.display());
+        }
+        if !changes.is_empty() {
+            commands.add(Edit::new(format!("Import {} observers", changes.len()), changes));
+        }
+        imported.send(ObserversImported { path: path.clone(), accepted, rejected: parsed.rejected });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/fixtures").join(name)
+    }
+
+    fn lines(parsed: &ParsedObservers) -> Vec<usize> {
+        parsed.rejected.iter().map(|row| row.line).collect()
+    }
+
+    #[test]
+    fn test_parsers_validate_rows() {
+        let csv = parse_csv(&fs::read_to_string(fixture("observers.csv")).unwrap());
+        let names: Vec<_> = csv.accepted.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["Svalbard", "Kiruna, Esrange", "Matera", "Matera", "Hartebeesthoek"]);
+        assert_eq!(csv.accepted[0], Observer::new("Svalbard", 78.2298, 15.4078, 0.458).with_min_elevation(5.0));
+        assert_eq!(csv.accepted[4].min_elevation, 0.0);
+        //the out of range latitude, the missing name, the text longitude and the extra field
+        assert_eq!(lines(&csv), vec![5, 6, 7, 9]);
+        assert_eq!(csv.rejected[0].reason, "latitude 91.5 is out of range");
+        assert_eq!(csv.rejected[2].reason, "longitude `east` is not a number");
+
+        let gpx = parse_gpx(&fs::read_to_string(fixture("observers.gpx")).unwrap());
+        let names: Vec<_> = gpx.accepted.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["Wallops & Chincoteague", "Goldstone"]);
+        assert_eq!(gpx.accepted[0], Observer::new("Wallops & Chincoteague", 37.9402, -75.4664, 0.012));
+        assert_eq!(gpx.accepted[1].altitude_km, 0.0);
+        assert_eq!(lines(&gpx), vec![11, 14, 15]);
+        assert_eq!(gpx.rejected[0].reason, "longitude 200.1 is out of range");
+        assert_eq!(gpx.rejected[1].reason, "missing name");
+
+        let taken = HashSet::from(["Matera".to_owned(), "Matera (2)".to_owned()]);
+        assert_eq!(unique_name("Matera", &taken), "Matera (3)");
+        assert_eq!(unique_name("Svalbard", &taken), "Svalbard");
+        assert_eq!(ObserverFormat::of(Path::new("stations.GPX")), Some(ObserverFormat::Gpx));
+        assert_eq!(ObserverFormat::of(Path::new("stations.txt")), None);
+    }
+
+    #[test]
+    fn test_import_is_one_undoable_edit() {
+        use crate::commands::{CommandsPlugin, InvokeCommand};
+        use crate::edits::{EditHistory, EditsPlugin};
+        use crate::input::{Action, ActionTriggered};
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, CommandsPlugin, EditsPlugin, ObserverImportPlugin));
+        app.world_mut().spawn(Observer::new("Matera", 40.65, 16.7, 0.5));
+        let summaries = |app: &mut App| -> Vec<ObserversImported> {
+            app.update();
+            app.update();
+            app.world_mut().resource_mut::<Events<ObserversImported>>().drain().collect()
+        };
+        app.world_mut().send_event(InvokeCommand { name: "Import observers".to_owned(), arguments: vec![fixture("observers.csv").display().to_string()] });
+        let summary = summaries(&mut app).pop().unwrap();
+        assert_eq!(summary.accepted, vec!["Svalbard", "Kiruna, Esrange", "Matera (2)", "Matera (3)", "Hartebeesthoek"]);
+        assert_eq!(summary.rejected.len(), 4);
+        let count = |app: &mut App| app.world_mut().query::<&Observer>().iter(app.world()).count();
+        assert_eq!(count(&mut app), 6);
+        assert_eq!(app.world().resource::<EditHistory>().next_undo().unwrap().label, "Import 5 observers");
+
+        app.world_mut().send_event(ActionTriggered(Action::Undo));
+        app.update();
+        assert_eq!(count(&mut app), 1);
+
+        app.world_mut().send_event(ImportObservers { path: fixture("missing.csv"), format: ObserverFormat::Csv });
+        let failed = summaries(&mut app).pop().unwrap();
+        assert!(failed.accepted.is_empty());
+        assert_eq!(failed.rejected[0].line, 0);
+    }
+}
@@ -7,32 +7,32 @@ pub trait Propagatable {
 #[derive(Debug, Clone, PartialEq, Component)]
 pub struct SatelliteOrbit {
     /// Semi-major axis (in kilometers)
-    pub semi_major_axis: f32,
+    pub semi_major_axis: f64,
     /// Eccentricity (dimensionless)
-    pub eccentricity: f32,
+    pub eccentricity: f64,
     /// Inclination (in degrees)
-    pub inclination: f32,
+    pub inclination: f64,
     /// Right Ascension of the Ascending Node (in degrees)
-    pub raan: f32,
+    pub raan: f64,
     /// Argument of Perigee (in degrees)
-    pub argument_of_perigee: f32,
+    pub argument_of_perigee: f64,
     /// True Anomaly at Epoch (in degrees)
-    pub true_anomaly: f32,
+    pub true_anomaly: f64,
     /// Epoch time (in Julian Date)
-    pub epoch: f32,
+    pub epoch: f64,
 }
 
 
 impl SatelliteOrbit {
     /// Creates a new SatelliteOrbit with given parameters
     pub fn new(
-        semi_major_axis: f32,
-        eccentricity: f32,
-        inclination: f32,
-        raan: f32,
-        argument_of_perigee: f32,
-        true_anomaly: f32,
-        epoch: f32,
+        semi_major_axis: f64,
+        eccentricity: f64,
+        inclination: f64,
+        raan: f64,
+        argument_of_perigee: f64,
+        true_anomaly: f64,
+        epoch: f64,
     ) -> Self {
         SatelliteOrbit {
             semi_major_axis,
@@ -46,15 +46,36 @@ impl SatelliteOrbit {
     }
 
     /// Returns the orbital period in seconds
-    pub fn orbital_period(&self) -> f32 {
+    pub fn orbital_period(&self) -> f64 {
         let a = self.semi_major_axis;
-        2.0 * std::f32::consts::PI * (a.powi(3) / GRAVITATIONAL_CONSTANT).sqrt()
+        2.0 * std::f64::consts::PI * (a.powi(3) / GRAVITATIONAL_CONSTANT).sqrt()
+    }
+
+    /// Returns the orbital period in minutes, derived from the semi-major axis
+    pub fn period_minutes(&self) -> f64 {
+        self.orbital_period() / 60.0
+    }
+
+    /// Distance of the closest approach from the Earth center (in kilometers)
+    pub fn periapsis_km(&self) -> f32 {
+        (self.semi_major_axis * (1.0 - self.eccentricity)) as f32
+    }
+
+    /// Distance of the farthest point from the Earth center (in kilometers)
+    pub fn apoapsis_km(&self) -> f32 {
+        (self.semi_major_axis * (1.0 + self.eccentricity)) as f32
+    }
+
+    /// Radius of the orbit (in kilometers, from the Earth center) at the true anomaly `ta_degrees`
+    pub fn altitude_at_true_anomaly(&self, ta_degrees: f32) -> f32 {
+        let e = self.eccentricity;
+        (self.semi_major_axis * (1.0 - e.powi(2)) / (1.0 + e * (ta_degrees as f64).to_radians().cos())) as f32
     }
 }
 
 impl SatelliteOrbit {
     /// Propagates the orbit by a given time `dt` (in seconds) and returns a new orbit with the updated true anomaly.
-    pub fn propagate(&self, dt: f32) -> Self {
+    pub fn propagate(&self, dt: f64) -> Self {
 
         let mean_motion = (GRAVITATIONAL_CONSTANT / self.semi_major_axis.powi(3)).sqrt();
 
@@ -78,8 +99,17 @@ impl SatelliteOrbit {
 
     }
 
+    /// Returns the orbit with the true anomaly corresponding to a mean anomaly (in degrees)
+    pub fn with_mean_anomaly(&self, mean_anomaly: f64) -> Self {
+        let eccentric_anomaly = self.solve_keplers_equation(mean_anomaly.to_radians());
+        SatelliteOrbit {
+            true_anomaly: self.eccentric_anomaly_to_true_anomaly(eccentric_anomaly),
+            ..*self
+        }
+    }
+
     /// Converts the true anomaly to mean anomaly for the current orbit
-    fn true_anomaly_to_mean_anomaly(&self) -> f32 {
+    fn true_anomaly_to_mean_anomaly(&self) -> f64 {
         let e = self.eccentricity;
         let ta_rad = self.true_anomaly.to_radians();
 
@@ -88,14 +118,14 @@ impl SatelliteOrbit {
     }
 
     /// Solves Kepler's equation: M = E - e * sin(E) to find the eccentric anomaly
-    fn solve_keplers_equation(&self, mean_anomaly: f32) -> f32 {
+    fn solve_keplers_equation(&self, mean_anomaly: f64) -> f64 {
         let e = self.eccentricity;
         let mut eccentric_anomaly = mean_anomaly; // Initial guess: mean anomaly
         for _ in 0..100 { // Iterative Newton-Raphson method
             let delta = (eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly)
                 / (1.0 - e * eccentric_anomaly.cos());
             eccentric_anomaly -= delta;
-            if delta.abs() < 1e-6 {
+            if delta.abs() < 1e-12 {
                 break;
             }
         }
@@ -103,7 +133,7 @@ impl SatelliteOrbit {
     }
 
     /// Converts the eccentric anomaly to true anomaly
-    fn eccentric_anomaly_to_true_anomaly(&self, eccentric_anomaly: f32) -> f32 {
+    fn eccentric_anomaly_to_true_anomaly(&self, eccentric_anomaly: f64) -> f64 {
         let e = self.eccentricity;
         let ea = eccentric_anomaly;
 
@@ -114,7 +144,9 @@ impl SatelliteOrbit {
     }
 }
 
-use bevy::{math::{Quat, Vec3, Vec2}, prelude::*};
+use bevy::{math::{DQuat, DVec3, Quat, Vec3, Vec2}, prelude::*};
+
+use crate::world_frame::WORLD_FRAME;
 
 /// Represents the translation and rotation of the satellite in a 3D coordinate system using Bevy types
 #[derive(Debug)]
@@ -125,19 +157,23 @@ pub struct SatellitePose {
 
 impl SatelliteOrbit {
 
-    pub fn get_encentricity_vector(&self) -> Vec3 {
-        let rotation = self.orbital_to_quaternion();
-        rotation * Vec3::Y
+    pub fn get_encentricity_vector(&self) -> DVec3 {
+        self.perifocal_to_eci_f64() * DVec3::X
     }
 
     pub fn get_right_ascention_vector(&self) -> Vec3 {
-        let raan = self.raan.to_radians();
+        let raan = self.raan.to_radians() as f32;
         let q_raan = Quat::from_axis_angle(Vec3::Z, raan);        // Rotate around Z-axis (RAAN)
         q_raan * Vec3::X
     }
 
     /// Converts the true anomaly to the satellite's translation and rotation in a 3D coordinate system.
     pub fn to_translation_and_rotation(&self) -> SatellitePose {
+        SatellitePose { position: self.position().as_vec3() }
+    }
+
+    /// Inertial position at the current true anomaly (in kilometers), in full precision
+    pub fn position(&self) -> DVec3 {
         // Constants
         let e = self.eccentricity;
         let a = self.semi_major_axis;
@@ -146,59 +182,156 @@ impl SatelliteOrbit {
         // Step 1: Calculate distance from Earth (radius vector in orbital plane)
         let r = a * (1.0 - e.powi(2)) / (1.0 + e * ta_rad.cos());
 
-        // Step 2: Calculate position in the orbital plane (pqw coordinates)
-        let x_pqw = r * ta_rad.cos();
-        let y_pqw = r * ta_rad.sin();
-        let z_pqw = 0.0; // Always zero in the orbital plane
+        // Step 2: Calculate position in the orbital plane (pqw coordinates), always zero off the plane
+        let position = DVec3::new(r * ta_rad.cos(), r * ta_rad.sin(), 0.0);
 
         // Step 3: Convert to the inertial frame (ECI: Earth-Centered Inertial)
-        let position = Vec3::new(x_pqw, y_pqw, z_pqw);
+        self.perifocal_to_eci_f64() * position
+    }
 
-        // Step 4: Define satellite rotation as a quaternion
-        let rotation = self.orbital_to_quaternion();
+    /// Inertial velocity at the current true anomaly (in kilometers per second)
+    pub fn velocity(&self) -> DVec3 {
+        let e = self.eccentricity;
+        let ta_rad = self.true_anomaly.to_radians();
+        let semi_latus_rectum = self.semi_major_axis * (1.0 - e.powi(2));
+        let speed_factor = (GRAVITATIONAL_CONSTANT / semi_latus_rectum).sqrt();
 
-        let position = rotation * position;
+        let velocity_pqw = DVec3::new(-speed_factor * ta_rad.sin(), speed_factor * (e + ta_rad.cos()), 0.0);
+        self.perifocal_to_eci_f64() * velocity_pqw
+    }
 
-        SatellitePose { position }
+    /// The orbit at `samples` evenly spaced true anomalies, starting at periapsis
+    pub fn sample_points(&self, samples: usize) -> impl Iterator<Item = SatelliteOrbit> + '_ {
+        let step = 360.0 / samples.max(1) as f64;
+        (0..samples.max(1)).map(move |i| SatelliteOrbit { true_anomaly: step * i as f64, ..*self })
     }
 
-    /// Converts the orbital elements to a quaternion representing the rotation
-    fn orbital_to_quaternion(&self) -> Quat {
-        use std::f32::consts::PI;
-        // Orbital elements
+    /// Rotation from the perifocal frame (X towards periapsis, Z along the angular momentum)
+    /// to the inertial frame, the classic 3-1-3 sequence Rz(RAAN)·Rx(inclination)·Rz(argument of perigee)
+    pub fn perifocal_to_eci(&self) -> Quat {
+        self.perifocal_to_eci_f64().as_quat()
+    }
+
+    fn perifocal_to_eci_f64(&self) -> DQuat {
         let inclination = self.inclination.to_radians();
         let raan = self.raan.to_radians();
-        let arg_perigee = (self.argument_of_perigee - 90.0).to_radians();
+        let arg_perigee = self.argument_of_perigee.to_radians();
 
-        // Quaternions for each rotation
-        let q_raan = Quat::from_axis_angle(Vec3::Z, raan);        // Rotate around Z-axis (RAAN)
-        let raan_vector = q_raan * Vec3::X;
-        let q_incl = Quat::from_axis_angle(raan_vector.normalize(), inclination); // Rotate raan vector
-        let normal_vector = raan_vector.cross(q_incl * Quat::from_axis_angle(Vec3::Z, PI / 2.0) * raan_vector);
-        let q_argp = Quat::from_axis_angle(normal_vector.normalize(), arg_perigee); // Rotate around X-axis (Argument of Perigee)
+        DQuat::from_rotation_z(raan) * DQuat::from_rotation_x(inclination) * DQuat::from_rotation_z(arg_perigee)
+    }
+
+    /// True anomaly (in degrees) of a position in the orbital plane, positions off the plane are projected onto it
+    pub fn true_anomaly_at(&self, position: DVec3) -> f64 {
+        let rotation = self.perifocal_to_eci_f64();
+        position.dot(rotation * DVec3::Y).atan2(position.dot(rotation * DVec3::X)).to_degrees()
+    }
 
-        // Combine rotations: RAAN -> Inclination -> Argument of Perigee
-        q_raan * q_incl * q_argp
+    /// Unit vector perpendicular to the orbital plane (direction of the angular momentum)
+    pub fn orbit_normal(&self) -> DVec3 {
+        self.perifocal_to_eci_f64() * DVec3::Z
     }
 
-    pub fn bevy_elipse_parameters(&self, scale: f32) -> (Vec3, Quat, Vec2) {
+    /// Secular drift of the RAAN caused by J2 (in degrees per day), positive means eastward
+    pub fn nodal_regression_rate(&self) -> f64 {
+        let (n, ratio, inclination) = self.j2_terms();
+        (-1.5 * n * J2 * ratio * inclination.cos() * SECONDS_PER_DAY).to_degrees()
+    }
+
+    /// Secular rotation of the argument of perigee caused by J2 (in degrees per day)
+    pub fn apsidal_precession_rate(&self) -> f64 {
+        let (n, ratio, inclination) = self.j2_terms();
+        (0.75 * n * J2 * ratio * (5.0 * inclination.cos().powi(2) - 1.0) * SECONDS_PER_DAY).to_degrees()
+    }
+
+    /// Beta angle (in degrees), the elevation of the Sun above the orbital plane, positive on the side of the angular
+    /// momentum. `sun_direction` is inertial, like [`crate::ephemeris::sun_position`]
+    pub fn beta_angle(&self, sun_direction: DVec3) -> f64 {
+        (self.perifocal_to_eci_f64() * DVec3::Z).dot(sun_direction.normalize()).clamp(-1.0, 1.0).asin().to_degrees()
+    }
+
+    /// Share of the period spent in the shadow of [`in_earth_shadow`], sampled at `samples` evenly spaced times.
+    /// Any eccentricity, [`circular_eclipse_fraction`] is exact for circular orbits
+    pub fn eclipse_fraction(&self, sun_direction: DVec3, samples: usize) -> f64 {
+        let samples = samples.max(1);
+        let eclipsed = (0..samples)
+            .filter(|i| in_earth_shadow(self.with_mean_anomaly(360.0 * *i as f64 / samples as f64).position(), sun_direction))
+            .count();
+        eclipsed as f64 / samples as f64
+    }
+
+    /// `(latitude, longitude)` in degrees below the satellite at `steps` equal increments over `duration_seconds`,
+    /// from the current position on, see [`SatelliteOrbit::sub_satellite_point`]
+    pub fn ground_track(&self, steps: usize, duration_seconds: f32) -> Vec<(f32, f32)> {
+        let step = duration_seconds as f64 / steps.max(1) as f64;
+        (0..steps).map(|i| self.sub_satellite_point(step * i as f64)).collect()
+    }
+
+    /// Geocentric `(latitude, longitude)` in degrees below the satellite `dt` seconds from now, the same for any radius
+    /// of a spherical Earth. The globe turns under the orbit, so the longitude (-180..180) is rotated back by the Earth
+    /// rotation since now
+    pub fn sub_satellite_point(&self, dt: f64) -> (f32, f32) {
+        let position = self.propagate(dt).position();
+        let longitude = position.y.atan2(position.x) - EARTH_ROTATION_RATE * dt;
+        let longitude = (longitude + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU) - std::f64::consts::PI;
+        ((position.z / position.length()).asin().to_degrees() as f32, longitude.to_degrees() as f32)
+    }
+
+    //mean motion (rad/s), (R/p)² and inclination (rad)
+    fn j2_terms(&self) -> (f64, f64, f64) {
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+        let n = (GRAVITATIONAL_CONSTANT / a.powi(3)).sqrt();
+        let semi_latus_rectum = a * (1.0 - e * e);
+        (n, (EARTH_EQUATORIAL_RADIUS / semi_latus_rectum).powi(2), self.inclination.to_radians())
+    }
+
+    pub fn bevy_elipse_parameters(&self, scale: f64) -> (Vec3, Quat, Vec2) {
         // Orbital elements
-        let full_rotation = self.orbital_to_quaternion();
+        let full_rotation = self.perifocal_to_eci_f64();
         let x = self.semi_major_axis * scale;
         let y = x * (1.0 - self.eccentricity * self.eccentricity).sqrt();
         let elipse_offset = self.semi_major_axis * self.eccentricity;
-        let elipse_offset = full_rotation * Vec3::new( -elipse_offset * scale, 0.0, 0.0);
+        let elipse_offset = (full_rotation * DVec3::new(-elipse_offset * scale, 0.0, 0.0)).as_vec3();
+        let full_rotation = full_rotation.as_quat();
 
-        (elipse_offset, full_rotation, Vec2 { x, y })
+        (WORLD_FRAME.to_world(elipse_offset), WORLD_FRAME.rotation() * full_rotation, Vec2::new(x as f32, y as f32))
     }
 }
 
-const GRAVITATIONAL_CONSTANT: f32 = 3.986004418e5; // Earth's gravitational parameter (km^3/s^2)
+/// Earth's gravitational parameter (km^3/s^2)
+pub const GRAVITATIONAL_CONSTANT: f64 = 3.986004418e5;
+const J2: f64 = 1.08262668e-3; // Earth's second zonal harmonic (dimensionless)
+/// Earth's equatorial radius, WGS84 (km)
+pub const EARTH_EQUATORIAL_RADIUS: f64 = 6378.137;
+const SECONDS_PER_DAY: f64 = 86400.0;
+/// Sidereal rotation rate of the Earth (rad/s)
+pub const EARTH_ROTATION_RATE: f64 = 7.292_115e-5;
+
+/// Whether an inertial position (in kilometers) is in the shadow of the Earth, a cylinder of the equatorial radius
+/// behind it. The penumbra and the flattening are neglected
+pub fn in_earth_shadow(position: DVec3, sun_direction: DVec3) -> bool {
+    let sun = sun_direction.normalize();
+    let along = position.dot(sun);
+    along < 0.0 && (position - sun * along).length() < EARTH_EQUATORIAL_RADIUS
+}
+
+/// Eclipse fraction of a circular orbit of `radius` (in kilometers) at the beta angle `beta` (in degrees) in the shadow
+/// of [`in_earth_shadow`], `acos(sqrt(h² + 2Rh) / (r cos β)) / π` below the critical beta `asin(R / r)` and zero above it.
+/// From Rickman, Introduction to Orbital Mechanics and Spacecraft Attitudes for Thermal Engineers (NASA, 2014)
+pub fn circular_eclipse_fraction(radius: f64, beta: f64) -> f64 {
+    let critical = (EARTH_EQUATORIAL_RADIUS / radius).clamp(-1.0, 1.0).asin();
+    let beta = beta.to_radians().abs();
+    if beta >= critical {
+        return 0.0;
+    }
+    let altitude = radius - EARTH_EQUATORIAL_RADIUS;
+    ((altitude * altitude + 2.0 * EARTH_EQUATORIAL_RADIUS * altitude).sqrt() / (radius * beta.cos())).min(1.0).acos() / std::f64::consts::PI
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use approx::assert_abs_diff_eq;
+    use bevy::math::DMat3;
 
     #[test]
     fn test_orbit_propagation() {
@@ -220,9 +353,75 @@ mod tests {
         let orbit_full = orbit.propagate(period);
 
 
-        for (orbit, expected_true_anomaly) in vec![(orbit_quater, 90.0), (orbit_half, 180.0), (orbit_three_quater, -90.0), (orbit_full, 0.)] {
+        //a quarter of the period is off a right angle by the eccentricity, half and full periods aren't
+        for (orbit, expected_true_anomaly) in [(orbit_quater, 90.0), (orbit_three_quater, -90.0)] {
             assert_abs_diff_eq!(orbit.true_anomaly, expected_true_anomaly, epsilon = 0.2);    
         }
+        for (orbit, expected_true_anomaly) in [(orbit_half, 180.0), (orbit_full, 0.)] {
+            assert_abs_diff_eq!(orbit.true_anomaly, expected_true_anomaly, epsilon = 0.01);
+        }
+    }
+
+    #[test]
+    fn test_apsides() {
+        let orbit = SatelliteOrbit::new(6771.0, 0.001, 51.6, 120.0, 80.0, 0.0, 2451545.0);
+        for distance in [orbit.periapsis_km(), orbit.apoapsis_km()] {
+            assert_abs_diff_eq!(distance, 6771.0, epsilon = 10.0);
+        }
+
+        let molniya = SatelliteOrbit::new(26560.0, 0.7, 63.4, 0.0, 270.0, 0.0, 0.0);
+        assert_abs_diff_eq!(molniya.apoapsis_km() / molniya.periapsis_km(), 1.7 / 0.3, epsilon = 1e-4);
+        //the radius runs from the periapsis to the apoapsis and back
+        assert_abs_diff_eq!(molniya.altitude_at_true_anomaly(0.0), molniya.periapsis_km(), epsilon = 1e-2);
+        assert_abs_diff_eq!(molniya.altitude_at_true_anomaly(180.0), molniya.apoapsis_km(), epsilon = 1e-2);
+        assert_abs_diff_eq!(molniya.altitude_at_true_anomaly(90.0), molniya.altitude_at_true_anomaly(-90.0), epsilon = 1e-2);
+        let position = SatelliteOrbit { true_anomaly: 37.0, ..molniya.clone() }.position();
+        assert_abs_diff_eq!(molniya.altitude_at_true_anomaly(37.0), position.length() as f32, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn test_beta_angle_and_eclipse_fraction() {
+        //equatorial, the Sun at the equinox is in the plane and at the solstice 23.44° above it
+        let leo = SatelliteOrbit::new(EARTH_EQUATORIAL_RADIUS + 500.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let solstice = DVec3::new(23.44_f64.to_radians().cos(), 0.0, 23.44_f64.to_radians().sin());
+        assert_abs_diff_eq!(leo.beta_angle(DVec3::X), 0.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(leo.beta_angle(solstice * 1e8), 23.44, epsilon = 1e-9);
+        assert_abs_diff_eq!(leo.beta_angle(-DVec3::Z), -90.0, epsilon = 1e-9);
+
+        //the textbook ~37% of a low orbit with the Sun in its plane
+        let fraction = circular_eclipse_fraction(leo.semi_major_axis, 0.0);
+        assert!((0.36..0.39).contains(&fraction), "{fraction}");
+        assert_abs_diff_eq!(leo.eclipse_fraction(DVec3::X, 3600), fraction, epsilon = 1e-3);
+        //above the critical beta of about 68° the orbit is in the Sun all the time
+        let critical = (EARTH_EQUATORIAL_RADIUS / leo.semi_major_axis).asin().to_degrees();
+        assert!((67.0..69.0).contains(&critical), "{critical}");
+        assert_eq!(circular_eclipse_fraction(leo.semi_major_axis, critical + 0.5), 0.0);
+        let sun = DVec3::new(70.0_f64.to_radians().cos(), 0.0, 70.0_f64.to_radians().sin());
+        assert_eq!(leo.eclipse_fraction(sun, 3600), 0.0);
+        //the shadow shrinks towards the critical beta, the same as sampled
+        let sun = DVec3::new(50.0_f64.to_radians().cos(), 0.0, 50.0_f64.to_radians().sin());
+        assert!(circular_eclipse_fraction(leo.semi_major_axis, 50.0) < fraction);
+        assert_abs_diff_eq!(leo.eclipse_fraction(sun, 3600), circular_eclipse_fraction(leo.semi_major_axis, 50.0), epsilon = 1e-3);
+
+        //an eccentric orbit with the apoapsis in the shadow spends longer in it than with the periapsis there
+        let molniya = SatelliteOrbit::new(26560.0, 0.7, 0.0, 0.0, 0.0, 0.0, 0.0);
+        assert!(molniya.eclipse_fraction(DVec3::X, 3600) > molniya.eclipse_fraction(-DVec3::X, 3600));
+        assert!(in_earth_shadow(DVec3::new(-7000.0, 100.0, 0.0), DVec3::X));
+        assert!(!in_earth_shadow(DVec3::new(7000.0, 100.0, 0.0), DVec3::X));
+        assert!(!in_earth_shadow(DVec3::new(-7000.0, 6400.0, 0.0), DVec3::X));
+    }
+
+    #[test]
+    fn test_large_orbits_return_after_many_periods() {
+        //geostationary and Galileo, a few periods at once and one period at a time
+        for orbit in [SatelliteOrbit::new(42164.0, 0.0002, 0.05, 0.0, 0.0, 37.0, 0.0), SatelliteOrbit::new(29600.0, 0.0003, 56.0, 120.0, 30.0, 200.0, 0.0)] {
+            let period = orbit.orbital_period();
+            let stepped = (0..10).fold(orbit.clone(), |orbit, _| orbit.propagate(period));
+            for propagated in [orbit.propagate(period * 10.0), stepped] {
+                assert_abs_diff_eq!(propagated.true_anomaly.rem_euclid(360.0), orbit.true_anomaly, epsilon = 0.01);
+                assert!(propagated.position().distance(orbit.position()) < 0.01, "{}", propagated.position().distance(orbit.position()));
+            }
+        }
     }
 
     #[test]
@@ -251,7 +450,7 @@ mod tests {
         println!("{:?}", half_axis);
         println!("{:?}", orbit.get_encentricity_vector());
         println!("{:?}", orbit.get_right_ascention_vector());
-        println!("{:?}", orbit.get_right_ascention_vector().cross(orbit.get_encentricity_vector()));
+        println!("{:?}", orbit.get_right_ascention_vector().as_dvec3().cross(orbit.get_encentricity_vector()));
         assert_abs_diff_eq!(offset.length(), 0.0, epsilon = 0.1);
 
     }
@@ -271,10 +470,63 @@ mod tests {
         // Compute translation and rotation
         let pose = orbit.to_translation_and_rotation();
 
-        // Expected position (calculated or derived from a reliable orbital simulation tool)
-        let expected_position = Vec3::new(4645.23, 4645.23, 2138.23); // Example values
+        // Expected position from the textbook perifocal to ECI transformation
+        let expected_position = Vec3::new(-4170.70, -1051.65, 5220.55);
         assert_abs_diff_eq!(pose.position.x, expected_position.x, epsilon = 1.0);
         assert_abs_diff_eq!(pose.position.y, expected_position.y, epsilon = 1.0);
         assert_abs_diff_eq!(pose.position.z, expected_position.z, epsilon = 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_perifocal_to_eci_matches_313_sequence() {
+        let element_sets = vec![
+            (51.6, 120.0, 80.0),
+            (0.0, 0.0, 0.0),
+            (98.7, 250.0, 10.0),
+            (63.4, 45.0, 270.0),
+            (5.0, 0.0, 20.0),
+        ];
+        for (inclination, raan, argument_of_perigee) in element_sets {
+            let orbit = SatelliteOrbit::new(7000.0, 0.01, inclination, raan, argument_of_perigee, 0.0, 0.0);
+            let (i, o, w) = (inclination.to_radians(), raan.to_radians(), argument_of_perigee.to_radians());
+            let expected = DMat3::from_cols(
+                DVec3::new(o.cos() * w.cos() - o.sin() * w.sin() * i.cos(), o.sin() * w.cos() + o.cos() * w.sin() * i.cos(), w.sin() * i.sin()),
+                DVec3::new(-o.cos() * w.sin() - o.sin() * w.cos() * i.cos(), -o.sin() * w.sin() + o.cos() * w.cos() * i.cos(), w.cos() * i.sin()),
+                DVec3::new(o.sin() * i.sin(), -o.cos() * i.sin(), i.cos()),
+            ).as_mat3();
+            let rotation = orbit.perifocal_to_eci();
+            for (axis, column) in [(Vec3::X, expected.x_axis), (Vec3::Y, expected.y_axis), (Vec3::Z, expected.z_axis)] {
+                let actual = rotation * axis;
+                assert_abs_diff_eq!(actual.x, column.x, epsilon = 1e-5);
+                assert_abs_diff_eq!(actual.y, column.y, epsilon = 1e-5);
+                assert_abs_diff_eq!(actual.z, column.z, epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_j2_secular_rates() {
+        //700 km sun-synchronous, the node follows the Sun at 360°/365.2422 days
+        let sun_synchronous = SatelliteOrbit::new(7078.0, 0.001, 98.19, 0.0, 0.0, 0.0, 0.0);
+        assert_abs_diff_eq!(sun_synchronous.nodal_regression_rate(), 0.9856, epsilon = 0.01);
+
+        let critical = SatelliteOrbit::new(26560.0, 0.74, 63.435, 0.0, 270.0, 0.0, 0.0);
+        assert_abs_diff_eq!(critical.apsidal_precession_rate(), 0.0, epsilon = 1e-4);
+
+        //prograde LEO regresses westwards while the perigee advances
+        let iss = SatelliteOrbit::new(6771.0, 0.0005, 51.6, 0.0, 0.0, 0.0, 0.0);
+        assert_abs_diff_eq!(iss.nodal_regression_rate(), -5.0, epsilon = 0.1);
+        assert!(iss.apsidal_precession_rate() > 3.0);
+    }
+
+    #[test]
+    fn test_equatorial_ground_track_stays_on_the_equator() {
+        let orbit = SatelliteOrbit::new(6778.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let track = orbit.ground_track(360, orbit.orbital_period() as f32);
+        assert_eq!(track.len(), 360);
+        for (latitude, longitude) in track {
+            assert_abs_diff_eq!(latitude, 0.0, epsilon = 0.5);
+            assert!((-180.0..180.0).contains(&longitude), "{longitude}");
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::NoFrustumCulling
+    }
+};
+
+use crate::camera::OverlayCamera;
+use crate::floating_origin::FloatingOrigin;
+use crate::global::InGameSettings;
+use crate::orbit::SatelliteOrbit;
+use crate::propagation::OrbitColor;
+use crate::quality::{OrbitDetail, QualityLevel};
+use crate::selection::{OrbitHidden, SelectionSet};
+use crate::speed_heatmap::OrbitRenderMode;
+use crate::world_frame::WORLD_FRAME;
+
+//joins sharper than this are clamped instead of growing into spikes
+const MITER_LIMIT: f32 = 4.0;
+//consecutive samples closer than this (in world units) are merged
+const MIN_SEGMENT: f32 = 1e-5;
+//reverse-Z, a bias this large puts the line in front of everything
+const ON_TOP_DEPTH_BIAS: f32 = 1e9;
+//rows of the edge texture, the outer ones fade out
+const EDGE_TEXELS: usize = 16;
+
+/// Draws the orbit ellipses as meshes when the [`OrbitLineSettings`] pick one for the quality level,
+/// the binary keeps drawing gizmos otherwise
+pub struct OrbitLinesPlugin;
+
+/// How orbit lines are built, from the cheapest to the most legible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrbitLineStyle {
+    /// Immediate mode gizmo lines, 1 px
+    Gizmo,
+    /// Line strip mesh, 1 px, with the depth modes of the meshes
+    ThinMesh,
+    /// Camera-facing quad strip of [`OrbitLineSettings::width_px`] with anti-aliased edges
+    WideMesh
+}
+
+/// How an orbit line is tested against the depth of the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OrbitDepthMode {
+    #[default]
+    Normal,
+    /// Never hidden, e.g. behind the Earth
+    AlwaysOnTop,
+    /// Hidden parts are drawn with [`OrbitLineSettings::occluded_alpha`], a second pass drawn on top
+    DimmedWhenOccluded
+}
+
+/// Depth mode of one orbit, over the ones of the settings
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct OrbitDepth(pub OrbitDepthMode);
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct OrbitLineSettings {
+    /// Style of each quality level, the last one for the levels past the end
+    pub styles: Vec<OrbitLineStyle>,
+    /// Width of the wide lines on the screen
+    pub width_px: f32,
+    pub depth: OrbitDepthMode,
+    /// Mode of the orbit of the primary selected satellite
+    pub focused_depth: OrbitDepthMode,
+    /// Alpha of the hidden parts of [`OrbitDepthMode::DimmedWhenOccluded`] lines
+    pub occluded_alpha: f32
+}
+
+impl Default for OrbitLineSettings {
+    fn default() -> Self {
+        Self {
+            styles: vec![OrbitLineStyle::WideMesh, OrbitLineStyle::WideMesh, OrbitLineStyle::ThinMesh, OrbitLineStyle::ThinMesh, OrbitLineStyle::Gizmo],
+            width_px: 2.5,
+            depth: OrbitDepthMode::DimmedWhenOccluded,
+            focused_depth: OrbitDepthMode::AlwaysOnTop,
+            occluded_alpha: 0.25
+        }
+    }
+}
+
+impl OrbitLineSettings {
+    pub fn style(&self, quality_level: usize) -> OrbitLineStyle {
+        self.styles.get(quality_level).or(self.styles.last()).copied().unwrap_or(OrbitLineStyle::Gizmo)
+    }
+}
+
+/// Where the strip is seen from, the width stays constant on the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StripView {
+    pub eye: Vec3,
+    /// Size of a pixel at a unit distance from the eye, `2 tan(fov / 2) / viewport height` for a perspective camera
+    pub pixel_scale: f32
+}
+
+/// Triangles of a wide line, two vertices per point. `uvs` run along the line (x) and across it (y, 0 on the left edge)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuadStrip {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub indices: Vec<u32>
+}
+
+impl QuadStrip {
+    pub fn into_mesh(self) -> Mesh {
+        let normals = vec![Vec3::Y; self.positions.len()];
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+            .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+//points without the ones closer than MIN_SEGMENT to the previous one, nor the closing duplicate of a loop
+fn distinct_points(points: &[Vec3], closed: bool) -> Vec<Vec3> {
+    let mut distinct: Vec<Vec3> = Vec::with_capacity(points.len());
+    for point in points {
+        match distinct.last() {
+            Some(last) if last.distance(*point) < MIN_SEGMENT => {},
+            _ => distinct.push(*point)
+        }
+    }
+    while closed && distinct.len() > 2 && distinct[0].distance(*distinct.last().unwrap()) < MIN_SEGMENT {
+        distinct.pop();
+    }
+    distinct
+}
+
+//unit vector across the segment and facing the eye, any perpendicular when the segment points at the eye
+fn side(direction: Vec3, to_eye: Vec3) -> Option<Vec3> {
+    direction.cross(to_eye).try_normalize()
+}
+
+/// Quad strip of the polyline, `closed` joins the last point back to the first one.
+/// Joins are mitered, the miter is clamped to [`MITER_LIMIT`] times the half width. Fewer than two distinct points make an empty strip
+pub fn quad_strip(points: &[Vec3], closed: bool, width_px: f32, view: StripView) -> QuadStrip {
+    let points = distinct_points(points, closed);
+    let count = points.len();
+    if count < 2 {
+        return QuadStrip::default();
+    }
+    let closed = closed && count > 2;
+    let segment = |i: usize| (points[(i + 1) % count] - points[i]).normalize();
+    let mut strip = QuadStrip::default();
+    let mut along = 0.0;
+    //the first point is repeated at the end of a loop so the texture coordinate keeps growing
+    let last = if closed { count } else { count - 1 };
+    for i in 0..=last {
+        let point = points[i % count];
+        if i > 0 {
+            along += point.distance(points[i - 1]);
+        }
+        let incoming = (closed || i > 0).then(|| segment((i + count - 1) % count));
+        let outgoing = (closed || i < count - 1).then(|| segment(i % count));
+        let to_eye = view.eye - point;
+        let sides = [incoming, outgoing].map(|direction| direction.and_then(|d| side(d, to_eye)));
+        let half_width = 0.5 * width_px * view.pixel_scale * to_eye.length();
+        let offset = match sides {
+            [Some(a), Some(b)] => {
+                let miter = (a + b).try_normalize().unwrap_or(b);
+                miter * half_width / miter.dot(b).max(1.0 / MITER_LIMIT)
+            },
+            [Some(s), None] | [None, Some(s)] => s * half_width,
+            //the line points right at the eye, it has no width on the screen anyway
+            [None, None] => to_eye.try_normalize().unwrap_or(Vec3::Z).any_orthonormal_vector() * half_width
+        };
+        strip.positions.extend([point + offset, point - offset]);
+        strip.uvs.extend([Vec2::new(along, 0.0), Vec2::new(along, 1.0)]);
+    }
+    for i in 0..last as u32 {
+        let (left, right, next_left, next_right) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+        strip.indices.extend([left, right, next_left, next_left, right, next_right]);
+    }
+    strip
+}
+
+/// 1 px line strip of the polyline
+pub fn line_strip(points: &[Vec3], closed: bool) -> Mesh {
+    let mut points = distinct_points(points, closed);
+    if closed && points.len() > 2 {
+        points.push(points[0]);
+    }
+    Mesh::new(PrimitiveTopology::LineStrip, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, points)
+}
+
+//texture across the wide lines, the alpha falls off over the outer texels of both edges
+fn edge_texture() -> Image {
+    let data = (0..EDGE_TEXELS)
+        .flat_map(|row| {
+            let from_edge = (row.min(EDGE_TEXELS - 1 - row) as f32 + 0.5) / (EDGE_TEXELS as f32 * 0.25);
+            [255, 255, 255, (from_edge.min(1.0) * 255.0) as u8]
+        })
+        .collect();
+    Image::new(
+        Extent3d { width: 1, height: EDGE_TEXELS as u32, depth_or_array_layers: 1 },
+        TextureDimension::D2, data, TextureFormat::Rgba8UnormSrgb, RenderAssetUsages::RENDER_WORLD
+    )
+}
+
+#[derive(Resource)]
+struct EdgeTexture(Handle<Image>);
+
+//the line entities of an orbit, the second one is the on-top pass of dimmed lines
+#[derive(Component)]
+struct OrbitLines {
+    mesh: Handle<Mesh>,
+    passes: [(Entity, Handle<StandardMaterial>); 2]
+}
+
+/// Line entity drawing the orbit of the satellite
+#[derive(Component, Debug)]
+pub struct OrbitLine {
+    pub orbit: Entity
+}
+
+impl Plugin for OrbitLinesPlugin {
+    fn build(&self, app: &mut App) {
+        let rendering = resource_exists::<Assets<Mesh>>.and_then(resource_exists::<Assets<StandardMaterial>>).and_then(resource_exists::<Assets<Image>>);
+        app
+            .init_resource::<OrbitLineSettings>()
+            .add_systems(Startup, create_edge_texture.run_if(rendering.clone()))
+            .add_systems(PostUpdate, (remove_orphan_lines, update_orbit_lines).chain().run_if(rendering.and_then(resource_exists::<InGameSettings>)));
+    }
+}
+
+fn create_edge_texture(mut images: ResMut<Assets<Image>>, mut commands: Commands) {
+    commands.insert_resource(EdgeTexture(images.add(edge_texture())));
+}
+
+fn remove_orphan_lines(lines: Query<(Entity, &OrbitLine)>, orbits: Query<(), With<OrbitLines>>, mut commands: Commands) {
+    for (entity, line) in lines.iter() {
+        if !orbits.contains(line.orbit) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+type ActiveCameras<'w, 's> = Query<'w, 's, (&'static Camera, &'static GlobalTransform, &'static Projection), (With<Camera3d>, Without<OverlayCamera>)>;
+type LineStyle<'w> = (Res<'w, OrbitLineSettings>, Option<Res<'w, OrbitDetail>>, Option<Res<'w, QualityLevel>>, Option<Res<'w, OrbitRenderMode>>);
+type Scene<'w> = (Res<'w, InGameSettings>, Option<Res<'w, FloatingOrigin>>, Option<Res<'w, SelectionSet>>, Option<Res<'w, EdgeTexture>>);
+type Drawable<'a> = (Entity, &'a SatelliteOrbit, Option<&'a OrbitColor>, Option<&'a OrbitDepth>, Option<&'a OrbitLines>, Has<OrbitHidden>);
+
+fn update_orbit_lines(
+    orbits: Query<Drawable>,
+    cameras: ActiveCameras,
+    mut line_visibility: Query<&mut Visibility, With<OrbitLine>>,
+    (settings, detail, quality, mode): LineStyle,
+    (game, origin, selection, edges): Scene,
+    (mut meshes, mut materials): (ResMut<Assets<Mesh>>, ResMut<Assets<StandardMaterial>>),
+    mut commands: Commands
+) {
+    let style = settings.style(quality.map_or(0, |q| q.level));
+    let detail = detail.as_deref().copied().unwrap_or_default();
+    let meshes_drawn = style != OrbitLineStyle::Gizmo && mode.is_none_or(|mode| *mode == OrbitRenderMode::Ellipse);
+    let view = cameras.iter().find(|(camera, ..)| camera.is_active).map(|(camera, transform, projection)| {
+        let fov = match projection {
+            Projection::Perspective(perspective) => perspective.fov,
+            _ => PerspectiveProjection::default().fov
+        };
+        let height = camera.logical_viewport_size().map_or(720.0, |size| size.y.max(1.0));
+        StripView { eye: transform.translation(), pixel_scale: 2.0 * (fov / 2.0).tan() / height }
+    });
+    let primary = selection.as_ref().and_then(|s| s.primary());
+    //materials are shared by the orbits of the same color and pass
+    let mut shared: HashMap<([u8; 4], usize, bool), Handle<StandardMaterial>> = HashMap::new();
+
+    for (entity, orbit, color, depth, lines, hidden) in orbits.iter() {
+        let drawn = meshes_drawn && !hidden && view.is_some() && (!detail.focused_only || primary == Some(entity));
+        let Some(view) = view.filter(|_| drawn) else {
+            for (line, _) in lines.iter().flat_map(|lines| &lines.passes) {
+                if let Ok(mut visibility) = line_visibility.get_mut(*line) {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+            continue;
+        };
+        let points: Vec<Vec3> = orbit.sample_points(detail.resolution)
+            .map(|sample| {
+                let world = WORLD_FRAME.to_world(sample.to_translation_and_rotation().position) * game.scale;
+                origin.as_ref().map_or(world, |origin| origin.to_render(world))
+            })
+            .collect();
+        let mesh = match style {
+            OrbitLineStyle::WideMesh => quad_strip(&points, true, settings.width_px, view).into_mesh(),
+            _ => line_strip(&points, true)
+        };
+        let depth = match depth {
+            Some(OrbitDepth(mode)) => *mode,
+            None if primary == Some(entity) => settings.focused_depth,
+            None => settings.depth
+        };
+        let color = color.map_or(Color::linear_rgb(1.0, 0.0, 0.0), |c| c.0);
+        let wide = style == OrbitLineStyle::WideMesh;
+        let mut material = |pass: usize| {
+            let on_top = pass == 1 || depth == OrbitDepthMode::AlwaysOnTop;
+            let alpha = if pass == 1 { settings.occluded_alpha } else { 1.0 };
+            let key = (color.to_srgba().to_u8_array(), pass, wide);
+            shared.entry(key).or_insert_with(|| materials.add(StandardMaterial {
+                base_color: color.with_alpha(color.alpha() * alpha),
+                base_color_texture: edges.as_ref().filter(|_| wide).map(|edges| edges.0.clone()),
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                cull_mode: None,
+                depth_bias: if on_top { ON_TOP_DEPTH_BIAS } else { 0.0 },
+                ..default()
+            })).clone()
+        };
+        let (main, occluded) = (material(0), material(1));
+        let visibility = [Visibility::Inherited, if depth == OrbitDepthMode::DimmedWhenOccluded { Visibility::Inherited } else { Visibility::Hidden }];
+
+        match lines {
+            Some(lines) => {
+                meshes.insert(&lines.mesh, mesh);
+                for ((line, _), (material, visibility)) in lines.passes.iter().zip([(main, visibility[0]), (occluded, visibility[1])]) {
+                    commands.entity(*line).insert((material, visibility));
+                }
+            },
+            None => {
+                let mesh = meshes.add(mesh);
+                let passes = [(main, visibility[0]), (occluded, visibility[1])].map(|(material, visibility)| {
+                    let line = commands.spawn((
+                        PbrBundle { mesh: mesh.clone(), material: material.clone(), visibility, ..default() },
+                        OrbitLine { orbit: entity }, NoFrustumCulling
+                    )).id();
+                    (line, material)
+                });
+                commands.entity(entity).insert(OrbitLines { mesh, passes });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    const VIEW: StripView = StripView { eye: Vec3::new(0.0, 0.0, 10.0), pixel_scale: 0.01 };
+
+    fn circle(samples: usize) -> Vec<Vec3> {
+        (0..samples).map(|i| {
+            let angle = i as f32 / samples as f32 * std::f32::consts::TAU;
+            Vec3::new(angle.cos(), angle.sin(), 0.0)
+        }).collect()
+    }
+
+    #[test]
+    fn test_vertex_and_index_counts() {
+        let open = quad_strip(&circle(16), false, 2.0, VIEW);
+        assert_eq!((open.positions.len(), open.uvs.len(), open.indices.len()), (32, 32, 15 * 6));
+        let closed = quad_strip(&circle(16), true, 2.0, VIEW);
+        assert_eq!((closed.positions.len(), closed.indices.len()), (34, 16 * 6));
+        assert!(closed.indices.iter().all(|&i| (i as usize) < closed.positions.len()));
+        //the repeated first point carries the length of the whole loop
+        assert_abs_diff_eq!(closed.uvs[32].x, std::f32::consts::TAU, epsilon = 0.05);
+        assert_eq!(closed.positions[32], closed.positions[0]);
+    }
+
+    #[test]
+    fn test_degenerate_segments_are_merged() {
+        assert_eq!(quad_strip(&[], false, 2.0, VIEW), QuadStrip::default());
+        assert_eq!(quad_strip(&[Vec3::X; 5], true, 2.0, VIEW), QuadStrip::default());
+        //duplicates and a closing copy of the first point don't add vertices
+        let (a, b) = (Vec3::ZERO, Vec3::X);
+        let strip = quad_strip(&[a, a, b, b + Vec3::splat(1e-7)], false, 2.0, VIEW);
+        assert_eq!((strip.positions.len(), strip.indices.len()), (4, 6));
+        assert!(strip.positions.iter().all(|p| p.is_finite()));
+        let mut loop_points = circle(8);
+        loop_points.push(loop_points[0]);
+        assert_eq!(quad_strip(&loop_points, true, 2.0, VIEW).positions.len(), 18);
+        //a two point loop is a single segment
+        assert_eq!(quad_strip(&[a, b], true, 2.0, VIEW).indices.len(), 6);
+        //a segment pointing at the eye still gets finite vertices
+        let strip = quad_strip(&[Vec3::ZERO, Vec3::Z], false, 2.0, VIEW);
+        assert!(strip.positions.iter().all(|p| p.is_finite()));
+    }
+
+    #[test]
+    fn test_width_is_constant_on_the_screen() {
+        let points = [Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(3.0, 0.0, -10.0)];
+        let strip = quad_strip(&points[..2], false, 4.0, VIEW);
+        //about 10 units away, 4 px of 0.01 per unit distance
+        assert_abs_diff_eq!(strip.positions[0].distance(strip.positions[1]), 0.04 * VIEW.eye.distance(points[0]), epsilon = 1e-5);
+        //facing the eye, the offset is across the segment and the view
+        assert_abs_diff_eq!((strip.positions[0] - points[0]).dot(Vec3::X), 0.0, epsilon = 1e-6);
+        assert_abs_diff_eq!((strip.positions[0] - points[0]).dot(Vec3::Z), 0.0, epsilon = 1e-6);
+        let far = quad_strip(&[points[1], points[2]], false, 4.0, VIEW);
+        let distance = VIEW.eye.distance(points[2]);
+        assert_abs_diff_eq!(far.positions[2].distance(far.positions[3]), 4.0 * 0.01 * distance, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_miter_joins() {
+        //a right angle widens the join by sqrt 2, a hairpin is clamped to the limit
+        let corner = quad_strip(&[Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)], false, 2.0, VIEW);
+        let half_width = 0.5 * 2.0 * VIEW.pixel_scale * VIEW.eye.distance(Vec3::X);
+        assert_abs_diff_eq!(corner.positions[2].distance(Vec3::X), half_width * 2f32.sqrt(), epsilon = 1e-5);
+        let hairpin = quad_strip(&[Vec3::ZERO, Vec3::X, Vec3::new(0.0, 0.001, 0.0)], false, 2.0, VIEW);
+        assert!(hairpin.positions[2].distance(Vec3::X) <= half_width * MITER_LIMIT + 1e-5);
+    }
+
+    #[test]
+    fn test_style_per_quality_level() {
+        let settings = OrbitLineSettings::default();
+        assert_eq!(settings.style(0), OrbitLineStyle::WideMesh);
+        assert_eq!(settings.style(2), OrbitLineStyle::ThinMesh);
+        assert_eq!(settings.style(40), OrbitLineStyle::Gizmo);
+        assert_eq!(OrbitLineSettings { styles: vec![], ..settings }.style(0), OrbitLineStyle::Gizmo);
+    }
+}
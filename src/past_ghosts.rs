@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use bevy::{color::palettes::css::*, prelude::*};
+
+use crate::commands::{CommandDescriptor, RegisterCommand};
+use crate::floating_origin::FloatingOrigin;
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::propagation::{is_plausible_prediction, ElementsDiff, InGameElements, InvalidateDerivedState, PropagatableDuration, Propageted, SatelliteGroup};
+use crate::selection::SelectionSet;
+use crate::timed_history::{PushOutcome, TimedHistory};
+use crate::world_frame::WORLD_FRAME;
+
+//samples kept per satellite, the span keeps far fewer at usual propagation intervals
+const HISTORY_CAPACITY: usize = 512;
+//samples further apart than this many propagation intervals are a jump in time
+const MAX_GAP_INTERVALS: f64 = 3.0;
+
+/// Faded markers of satellites where they were a fixed simulation time ago, connected to the live ones.
+/// Shows the rotation of orbital planes and the phasing within constellations
+pub struct PastGhostsPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct PastGhostSettings {
+    pub enabled: bool,
+    /// How far in the past the ghosts are
+    pub offset: Duration,
+    /// Only the group of the primary selected satellite has ghosts
+    pub focused_group_only: bool,
+    pub color: Color
+}
+
+impl Default for PastGhostSettings {
+    fn default() -> Self {
+        Self { enabled: false, offset: Duration::from_secs(10 * 60), focused_group_only: false, color: Color::from(LIGHT_STEEL_BLUE).with_alpha(0.4) }
+    }
+}
+
+/// Recent world positions of a satellite, minutes since J2000
+#[derive(Component, Debug, Clone)]
+pub struct PositionHistory(pub TimedHistory<Vec3>);
+
+impl MemoryFootprint for PositionHistory {
+    fn estimated_bytes(&self) -> usize {
+        self.0.estimated_bytes()
+    }
+}
+
+/// Ghost position `offset` minutes before `now`, from the nearest stored sample if it's close enough to be meaningful
+pub fn ghost_position(history: &TimedHistory<Vec3>, now: f64, offset: f64) -> Option<Vec3> {
+    let tolerance = history.max_gap().unwrap_or(f64::INFINITY);
+    let (oldest, _) = history.first()?;
+    //a history not reaching that far back would pin the ghost to its oldest sample
+    if oldest > now - offset + tolerance {
+        return None;
+    }
+    history.nearest_within(now - offset, tolerance).map(|(_, position)| *position)
+}
+
+impl Plugin for PastGhostsPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = |settings: Option<Res<PastGhostSettings>>| settings.is_some_and(|s| s.enabled);
+        app
+            .init_resource::<PastGhostSettings>()
+            .init_resource::<SelectionSet>()
+            .add_event::<Propageted>()
+            .add_event::<ElementsDiff>()
+            .add_event::<InvalidateDerivedState>()
+            .account_components::<PositionHistory>("trails")
+            .register_command(CommandDescriptor::new("Toggle past ghosts", ActionCategory::Time, |_, world| {
+                let mut settings = world.resource_mut::<PastGhostSettings>();
+                settings.enabled = !settings.enabled;
+            }))
+            .add_systems(Update, (record_positions, clear_refreshed).chain().run_if(enabled).run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, drop_histories.run_if(resource_changed::<PastGhostSettings>.or_else(on_event::<InvalidateDerivedState>())))
+            .add_systems(Update, draw_past_ghosts.after(clear_refreshed).run_if(enabled).run_if(resource_exists::<GizmoConfigStore>));
+    }
+}
+
+fn record_positions(
+    mut propagated: EventReader<Propageted>,
+    ghost_settings: Res<PastGhostSettings>,
+    settings: Res<InGameSettings>,
+    mut satellites: Query<(&InGameElements, &PropagatableDuration, Option<&mut PositionHistory>)>,
+    mut commands: Commands
+) {
+    let offset = ghost_settings.offset.as_secs_f64() / 60.0;
+    let max_gap = settings.propagation.real_time_interval.as_secs_f64() * settings.simulation_speed as f64 / 60.0 * MAX_GAP_INTERVALS;
+    for (entity, prediction) in propagated.read().flat_map(|p| p.data()) {
+        let Ok((elements, elapsed, history)) = satellites.get_mut(*entity) else {
+            continue;
+        };
+        if !is_plausible_prediction(prediction, &settings.propagation.envelope) {
+            continue;
+        }
+        let time = elapsed.minutes_since_j2000(&elements.0);
+        let position = WORLD_FRAME.to_world(Vec3::from_array(prediction.position.map(|c| c as f32))) * settings.scale;
+        match history {
+            Some(mut history) => {
+                //the positions before a jump don't lead to the current one
+                if history.0.push(time, position) == PushOutcome::AfterGap {
+                    history.0.clear();
+                    history.0.push(time, position);
+                }
+            },
+            None => {
+                let mut history = TimedHistory::new(HISTORY_CAPACITY).with_span(offset + max_gap).with_max_gap(max_gap);
+                history.push(time, position);
+                commands.entity(*entity).insert(PositionHistory(history));
+            }
+        }
+    }
+}
+
+fn clear_refreshed(mut diffs: EventReader<ElementsDiff>, mut histories: Query<&mut PositionHistory>) {
+    for diff in diffs.read() {
+        if let Ok(mut history) = histories.get_mut(diff.entity) {
+            history.0.clear();
+        }
+    }
+}
+
+//a change of the offset or the propagation interval invalidates the spans, disabling frees the memory
+fn drop_histories(histories: Query<Entity, With<PositionHistory>>, mut commands: Commands) {
+    for entity in histories.iter() {
+        commands.entity(entity).remove::<PositionHistory>();
+    }
+}
+
+fn draw_past_ghosts(
+    mut gizmos: Gizmos,
+    ghost_settings: Res<PastGhostSettings>,
+    selection: Res<SelectionSet>,
+    origin: Res<FloatingOrigin>,
+    groups: Query<&SatelliteGroup>,
+    satellites: Query<(Entity, &Transform, &PositionHistory)>
+) {
+    let focused_group = selection.primary().and_then(|e| groups.get(e).ok());
+    if ghost_settings.focused_group_only && focused_group.is_none() {
+        return;
+    }
+    let offset = ghost_settings.offset.as_secs_f64() / 60.0;
+    let line = ghost_settings.color.with_alpha(ghost_settings.color.alpha() * 0.5);
+    for (entity, transform, history) in satellites.iter() {
+        if ghost_settings.focused_group_only && groups.get(entity).ok() != focused_group {
+            continue;
+        }
+        let Some((now, _)) = history.0.last() else {
+            continue;
+        };
+        let Some(ghost) = ghost_position(&history.0, now, offset) else {
+            continue;
+        };
+        let ghost = origin.to_render(ghost);
+        gizmos.sphere(ghost, Quat::IDENTITY, 0.4, ghost_settings.color);
+        gizmos.line(ghost, origin.to_render(transform.translation), line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ghost_from_nearest_sample() {
+        let mut history = TimedHistory::new(64).with_max_gap(1.0);
+        for minute in 0..=20 {
+            history.push(minute as f64, Vec3::X * minute as f32);
+        }
+        assert_eq!(ghost_position(&history, 20.0, 10.0), Some(Vec3::X * 10.0));
+        assert_eq!(ghost_position(&history, 20.0, 9.6), Some(Vec3::X * 10.0));
+        //not that far back yet
+        assert_eq!(ghost_position(&history, 20.0, 25.0), None);
+
+        //after a jump the history starts over, the ghost waits for it to fill up
+        history.clear();
+        history.push(100.0, Vec3::Y);
+        assert_eq!(ghost_position(&history, 100.0, 10.0), None);
+    }
+}
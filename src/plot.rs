@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use crate::memory::MemoryFootprint;
+use crate::timed_history::{PushOutcome, TimedHistory};
+
+//time values of the plots are minutes on any continuous scale
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotMarkerKind {
+    /// Elements of the plotted satellite were replaced
+    ElementsRefresh,
+    /// Samples before the marker are not continuous with the ones after it
+    TimeJump
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotMarker {
+    pub time: f64,
+    pub kind: PlotMarkerKind
+}
+
+/// Ring buffer of `(time, value)` samples ordered by time, with markers annotating the time axis
+#[derive(Debug, Clone)]
+pub struct TimeSeries {
+    samples: TimedHistory<f64>,
+    markers: VecDeque<PlotMarker>
+}
+
+impl TimeSeries {
+    pub fn new(capacity: usize) -> Self {
+        Self { samples: TimedHistory::new(capacity), markers: VecDeque::new() }
+    }
+
+    /// Samples older than `span` before the latest one are dropped
+    pub fn with_span(mut self, span: f64) -> Self {
+        self.samples = self.samples.with_span(span);
+        self
+    }
+
+    /// Samples further than `max_gap` after the previous one are annotated as a time jump
+    pub fn with_max_gap(mut self, max_gap: f64) -> Self {
+        self.samples = self.samples.with_max_gap(max_gap);
+        self
+    }
+
+    /// Appends a sample, going back in time restarts the series as the history no longer leads to it
+    pub fn push(&mut self, time: f64, value: f64) {
+        match self.samples.push(time, value) {
+            PushOutcome::Restarted => self.markers.clear(),
+            PushOutcome::AfterGap => self.mark(time, PlotMarkerKind::TimeJump),
+            PushOutcome::Appended => {}
+        }
+        let first = self.samples.first().map_or(time, |(t, _)| t);
+        while self.markers.front().is_some_and(|m| m.time < first) {
+            self.markers.pop_front();
+        }
+    }
+
+    pub fn mark(&mut self, time: f64, kind: PlotMarkerKind) {
+        self.markers.push_back(PlotMarker { time, kind });
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.markers.clear();
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.samples.iter().map(|(t, v)| (t, *v))
+    }
+
+    pub fn markers(&self) -> impl Iterator<Item = &PlotMarker> {
+        self.markers.iter()
+    }
+
+    pub fn last(&self) -> Option<(f64, f64)> {
+        self.samples.last().map(|(t, v)| (t, *v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Reduces time ordered samples to at most `2 * buckets`, the lowest and highest sample of equal time spans,
+/// so short extremes (a perigee pass) survive the reduction
+pub fn downsample(samples: &[(f64, f64)], buckets: usize) -> Vec<(f64, f64)> {
+    let (Some(&(start, _)), Some(&(end, _))) = (samples.first(), samples.last()) else {
+        return vec![];
+    };
+    if buckets == 0 || samples.len() <= 2 * buckets || end <= start {
+        return samples.to_vec();
+    }
+    let width = (end - start) / buckets as f64;
+    let mut reduced = Vec::with_capacity(2 * buckets);
+    let bucket_of = |t: f64| (((t - start) / width) as usize).min(buckets - 1);
+    let mut chunk_start = 0;
+    while chunk_start < samples.len() {
+        let bucket = bucket_of(samples[chunk_start].0);
+        let chunk_end = samples[chunk_start..].iter().position(|&(t, _)| bucket_of(t) != bucket).map_or(samples.len(), |i| chunk_start + i);
+        let chunk = &samples[chunk_start..chunk_end];
+        let min = chunk.iter().enumerate().min_by(|a, b| a.1.1.total_cmp(&b.1.1)).unwrap();
+        let max = chunk.iter().enumerate().max_by(|a, b| a.1.1.total_cmp(&b.1.1)).unwrap();
+        match min.0.cmp(&max.0) {
+            std::cmp::Ordering::Less => reduced.extend([*min.1, *max.1]),
+            std::cmp::Ordering::Greater => reduced.extend([*max.1, *min.1]),
+            std::cmp::Ordering::Equal => reduced.push(*min.1)
+        }
+        chunk_start = chunk_end;
+    }
+    reduced
+}
+
+/// Value axis with bounds rounded to a step of 1, 2 or 5 times a power of ten
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisRange {
+    pub min: f64,
+    pub max: f64,
+    pub step: f64
+}
+
+impl AxisRange {
+    /// Smallest rounded range covering the finite values with about `ticks` steps, `None` without any finite value
+    pub fn fit(values: impl IntoIterator<Item = f64>, ticks: usize) -> Option<Self> {
+        let (low, high) = values.into_iter()
+            .filter(|v| v.is_finite())
+            .fold(None, |range: Option<(f64, f64)>, v| Some(range.map_or((v, v), |(low, high)| (low.min(v), high.max(v)))))?;
+        //a flat series still gets a range around it
+        let span = if high > low { high - low } else { low.abs().max(1.0) * 0.1 };
+        let step = nice_step(span / ticks.max(1) as f64);
+        let (mut min, mut max) = ((low / step).floor() * step, (high / step).ceil() * step);
+        if max <= min {
+            min -= step;
+            max += step;
+        }
+        Some(Self { min, max, step })
+    }
+
+    /// Position of the value along the axis, 0 at `min` and 1 at `max`
+    pub fn normalize(&self, value: f64) -> f64 {
+        (value - self.min) / (self.max - self.min)
+    }
+
+    pub fn ticks(&self) -> Vec<f64> {
+        let count = ((self.max - self.min) / self.step).round() as usize;
+        (0..=count).map(|i| self.min + i as f64 * self.step).collect()
+    }
+}
+
+fn nice_step(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * magnitude).find(|step| *step >= raw).unwrap_or(10.0 * magnitude)
+}
+
+impl MemoryFootprint for TimeSeries {
+    fn estimated_bytes(&self) -> usize {
+        self.samples.estimated_bytes() + self.markers.len() * size_of::<PlotMarker>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_capacity_span_and_jumps() {
+        let mut series = TimeSeries::new(4).with_span(100.0).with_max_gap(10.0);
+        for t in 0..6 {
+            series.push(t as f64 * 5.0, t as f64);
+        }
+        assert_eq!(series.samples().map(|(t, _)| t).collect::<Vec<_>>(), vec![10.0, 15.0, 20.0, 25.0]);
+
+        series.mark(25.0, PlotMarkerKind::ElementsRefresh);
+        series.push(60.0, 1.0);
+        let markers: Vec<_> = series.markers().map(|m| (m.time, m.kind)).collect();
+        assert_eq!(markers, vec![(25.0, PlotMarkerKind::ElementsRefresh), (60.0, PlotMarkerKind::TimeJump)]);
+
+        //the span drops old samples together with their markers
+        series.push(130.0, 2.0);
+        assert_eq!(series.samples().map(|(t, _)| t).collect::<Vec<_>>(), vec![60.0, 130.0]);
+        assert_eq!(series.markers().map(|m| m.time).collect::<Vec<_>>(), vec![60.0, 130.0]);
+
+        series.push(50.0, 3.0);
+        assert_eq!(series.len(), 1);
+        assert_eq!(series.markers().count(), 0);
+        assert_eq!(series.last(), Some((50.0, 3.0)));
+    }
+
+    #[test]
+    fn test_downsampling_keeps_extremes() {
+        let samples: Vec<_> = (0..1000).map(|i| (i as f64, (i as f64 * 0.05).sin())).collect();
+        let mut spiked = samples.clone();
+        spiked[503].1 = -5.0;
+        let reduced = downsample(&spiked, 50);
+        assert!(reduced.len() <= 100);
+        assert!(reduced.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(reduced.contains(&(503.0, -5.0)));
+        let max = reduced.iter().map(|s| s.1).fold(f64::MIN, f64::max);
+        assert!(max > 0.999);
+        assert_eq!(downsample(&samples[..80], 50).len(), 80);
+    }
+
+    #[test]
+    fn test_axis_range_of_eccentric_orbit() {
+        //Molniya like, perigee 500 km and apogee 39 700 km above the surface
+        let altitudes = (0..200).map(|i| {
+            let mean_anomaly = i as f64 / 200.0 * std::f64::consts::TAU;
+            26_560.0 * (1.0 - 0.74 * mean_anomaly.cos()) - 6378.0
+        });
+        let range = AxisRange::fit(altitudes.chain([500.0, 39_700.0]), 5).unwrap();
+        assert_eq!(range, AxisRange { min: 0.0, max: 40_000.0, step: 10_000.0 });
+        assert_eq!(range.ticks().len(), 5);
+        assert_eq!(range.normalize(20_000.0), 0.5);
+
+        let circular = AxisRange::fit([550.0, 550.0, f64::NAN], 5).unwrap();
+        assert!(circular.min < 550.0 && circular.max > 550.0 && circular.max - circular.min <= 100.0);
+        assert_eq!(AxisRange::fit([f64::NAN], 5), None);
+    }
+}
@@ -0,0 +1,312 @@
+use std::any::type_name;
+use std::sync::Mutex;
+
+use bevy::{app::PluginGroupBuilder, prelude::*};
+
+use crate::camera::CameraFovPlugin;
+#[cfg(feature = "ui-panels")]
+use crate::command_palette::CommandPalettePlugin;
+#[cfg(feature = "ui-panels")]
+use crate::context_menu::ContextMenuPlugin;
+use crate::commands::CommandsPlugin;
+use crate::constellation_stats::ConstellationStatsPlugin;
+use crate::edits::EditsPlugin;
+#[cfg(feature = "earth-model")]
+use crate::earth::{Earth, LoadAndScaleEarthModelPlugin, ProceduralEarthPlugin, DEFAULT_EARTH_MODEL};
+use crate::ephemeris::EphemerisPlugin;
+use crate::future_marks::FutureMarksPlugin;
+use crate::global::InGameSettings;
+use crate::ground_track::GroundTrackPlugin;
+#[cfg(feature = "ui-panels")]
+use crate::help_overlay::HelpOverlayPlugin;
+#[cfg(feature = "ui-panels")]
+use crate::hud::HudPlugin;
+use crate::input::InputPlugin;
+use crate::prediction_window::PredictionWindowPlugin;
+#[cfg(feature = "ui-panels")]
+use crate::altitude_plot::AltitudePlotPlugin;
+use crate::node_drift::NodeDriftPlugin;
+use crate::formation::FormationPlugin;
+use crate::notes::NotesPlugin;
+use crate::observer::ObserversPlugin;
+use crate::observer_import::ObserverImportPlugin;
+use crate::quality::QualityPlugin;
+use crate::orbit_lines::OrbitLinesPlugin;
+use crate::launch::LaunchPlugin;
+use crate::cursor_readout::CursorReadoutPlugin;
+use crate::protractor::ProtractorPlugin;
+use crate::screensaver::ScreensaverPlugin;
+#[cfg(feature = "ui-panels")]
+use crate::data_quality::DataQualityPlugin;
+use crate::past_ghosts::PastGhostsPlugin;
+use crate::floating_origin::FloatingOriginPlugin;
+use crate::propagation::{
+    AltitudeBandsPlugin, ConjunctionScreeningPlugin, ElementsInternerPlugin, ElementsRefreshPlugin, EpochDataLoader, GroupColorsPlugin, GroupUnloadPlugin, InvalidationPlugin, LoadElementsPlugin,
+    LoadingPlaceholderPlugin, MarkerMeshCachePlugin, MarkerStylePlugin, ProgressiveVisualsPlugin, PropagateElementsPlugin, PropagateInGamePlugin, SatelliteTransitionsPlugin, StrictTransformsPlugin,
+    TimeOfInterestPlugin, UpdateResidualsPlugin
+};
+#[cfg(feature = "earth-model")]
+use crate::propagation::EARTH_RADIUS_KM;
+use crate::selection::SelectionPlugin;
+use crate::style_function::StyleFunctionPlugin;
+use crate::simulation_clock::SimulationClockPlugin;
+use crate::startup_check::StartupCheckPlugin;
+#[cfg(feature = "earth-model")]
+use crate::startup_check::StartupCheckSettings;
+
+/// Options of the assembled suite, for the systems of the embedding app
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct SkytracioOptions {
+    /// No Earth model and no interactive plugins (selection, camera, overlays)
+    pub headless: bool,
+    /// Keplerian demo moons, spawned by the app when there is no real Moon
+    pub demo_bodies: bool
+}
+
+#[cfg(feature = "file-loader")]
+type DefaultLoader = crate::propagation::ConstFileClient;
+#[cfg(not(feature = "file-loader"))]
+type DefaultLoader = crate::propagation::InjectedOnly;
+
+/// Every plugin of the simulation in the right order, with its client and settings.
+/// `C` is the client loading the elements, it's taken from the app when not given
+pub struct SkytracioPlugins<C = DefaultLoader> {
+    client: Option<C>,
+    settings: Option<InGameSettings>,
+    //model asset path, `None` for the procedural sphere
+    #[cfg(feature = "earth-model")]
+    earth: (Option<String>, f32),
+    options: SkytracioOptions
+}
+
+impl SkytracioPlugins {
+    pub fn new() -> Self {
+        Self {
+            client: None,
+            settings: None,
+            #[cfg(feature = "earth-model")]
+            earth: (Some(DEFAULT_EARTH_MODEL.to_owned()), EARTH_RADIUS_KM),
+            options: SkytracioOptions { headless: false, demo_bodies: true }
+        }
+    }
+}
+
+impl Default for SkytracioPlugins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <C> SkytracioPlugins<C> {
+    pub fn with_client<D>(self, client: D) -> SkytracioPlugins<D> {
+        SkytracioPlugins {
+            client: Some(client),
+            settings: self.settings,
+            #[cfg(feature = "earth-model")]
+            earth: self.earth,
+            options: self.options
+        }
+    }
+
+    pub fn with_settings(mut self, settings: InGameSettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Model of the Earth, scaled so that it has the given radius in the simulation
+    #[cfg(feature = "earth-model")]
+    pub fn with_earth(mut self, asset_path: impl Into<String>, radius_km: f32) -> Self {
+        self.earth = (Some(asset_path.into()), radius_km);
+        self
+    }
+
+    /// Plain sphere instead of the model, no assets needed
+    #[cfg(feature = "earth-model")]
+    pub fn with_procedural_earth(mut self) -> Self {
+        self.earth.0 = None;
+        self
+    }
+
+    pub fn headless(mut self) -> Self {
+        self.options.headless = true;
+        self
+    }
+
+    pub fn without_demo_bodies(mut self) -> Self {
+        self.options.demo_bodies = false;
+        self
+    }
+}
+
+impl <C: EpochDataLoader + Resource + Clone> PluginGroup for SkytracioPlugins<C> {
+    fn build(self) -> PluginGroupBuilder {
+        let headless = self.options.headless;
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(SkytracioResourcesPlugin {
+                client: Mutex::new(self.client),
+                settings: Mutex::new(self.settings),
+                #[cfg(feature = "earth-model")]
+                earth: (!headless).then_some(self.earth),
+                options: self.options
+            })
+            .add(InputPlugin)
+            .add(SimulationClockPlugin)
+            .add(CommandsPlugin)
+            .add(LoadElementsPlugin::<C>::new())
+            .add(PropagateElementsPlugin)
+            .add(PropagateInGamePlugin)
+            .add(AltitudeBandsPlugin)
+            .add(GroupColorsPlugin)
+            .add(StrictTransformsPlugin)
+            .add(MarkerStylePlugin)
+            .add(MarkerMeshCachePlugin)
+            .add(ElementsInternerPlugin)
+            .add(ConjunctionScreeningPlugin)
+            .add(UpdateResidualsPlugin)
+            .add(SatelliteTransitionsPlugin)
+            .add(InvalidationPlugin)
+            .add(GroupUnloadPlugin)
+            .add(ElementsRefreshPlugin)
+            .add(EphemerisPlugin)
+            .add(ConstellationStatsPlugin);
+        if headless {
+            return group;
+        }
+        let group = group
+            .add(StartupCheckPlugin)
+            .add(LoadingPlaceholderPlugin)
+            .add(ProgressiveVisualsPlugin)
+            .add(TimeOfInterestPlugin)
+            .add(SelectionPlugin)
+            .add(EditsPlugin)
+            .add(ObserversPlugin)
+            .add(ObserverImportPlugin)
+            .add(CameraFovPlugin)
+            .add(GroundTrackPlugin)
+            .add(FutureMarksPlugin)
+            .add(PredictionWindowPlugin)
+            .add(NodeDriftPlugin)
+            .add(FormationPlugin)
+            .add(NotesPlugin)
+            .add(StyleFunctionPlugin)
+            .add(PastGhostsPlugin)
+            .add(QualityPlugin)
+            .add(OrbitLinesPlugin)
+            .add(LaunchPlugin)
+            .add(CursorReadoutPlugin)
+            .add(ProtractorPlugin)
+            .add(ScreensaverPlugin)
+            .add(FloatingOriginPlugin);
+        #[cfg(feature = "ui-panels")]
+        let group = group
+            .add(HelpOverlayPlugin)
+            .add(AltitudePlotPlugin)
+            .add(DataQualityPlugin)
+            .add(CommandPalettePlugin)
+            .add(ContextMenuPlugin)
+            .add(HudPlugin);
+        group
+    }
+}
+
+//inserts the resources before any other plugin of the group is built, a plugin is only built once
+struct SkytracioResourcesPlugin<C> {
+    client: Mutex<Option<C>>,
+    settings: Mutex<Option<InGameSettings>>,
+    #[cfg(feature = "earth-model")]
+    earth: Option<(Option<String>, f32)>,
+    options: SkytracioOptions
+}
+
+impl <C: Resource> Plugin for SkytracioResourcesPlugin<C> {
+    fn build(&self, app: &mut App) {
+        if let Some(client) = self.client.lock().unwrap().take() {
+            app.insert_resource(client);
+        }
+        if let Some(settings) = self.settings.lock().unwrap().take() {
+            app.insert_resource(settings);
+        }
+
+        let mut missing = vec![];
+        if !app.world().contains_resource::<InGameSettings>() {
+            missing.push("InGameSettings (SkytracioPlugins::with_settings)".to_owned());
+        }
+        if !app.world().contains_resource::<C>() {
+            missing.push(format!("{} (SkytracioPlugins::with_client)", type_name::<C>()));
+        }
+        if !missing.is_empty() {
+            panic!("SkytracioPlugins is missing: {}, provide them to the builder or insert them before adding the plugins", missing.join(", "));
+        }
+
+        app.insert_resource(self.options.clone());
+        #[cfg(feature = "earth-model")]
+        if let Some((asset_path, radius_km)) = &self.earth {
+            //the startup check looks for the model that is going to be loaded
+            if !app.world().contains_resource::<StartupCheckSettings>() {
+                app.insert_resource(StartupCheckSettings { earth_model: asset_path.as_ref().map(Into::into), ..default() });
+            }
+            let diameter = 2.0 * radius_km * app.world().resource::<InGameSettings>().scale;
+            match asset_path {
+                Some(asset_path) => app.add_plugins(LoadAndScaleEarthModelPlugin::<Earth>::new(diameter).with_asset_path(asset_path.clone())),
+                None => app.add_plugins(ProceduralEarthPlugin::<Earth>::new(diameter))
+            };
+        }
+    }
+}
+
+#[cfg(all(test, feature = "file-loader", feature = "earth-model"))]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+    use crate::earth::AssetPrepared;
+    use crate::propagation::{ConstFileClient, GroupLoadStatus, LoadElements};
+    use crate::selection::SelectionSet;
+
+    fn client() -> ConstFileClient {
+        ConstFileClient::new("assets/".into())
+    }
+
+    #[test]
+    fn test_headless_suite() {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .add_plugins(SkytracioPlugins::new().with_client(client()).with_settings(InGameSettings::default()).headless().without_demo_bodies());
+        app.update();
+
+        assert!(app.world().contains_resource::<InGameSettings>());
+        assert!(app.world().contains_resource::<ConstFileClient>());
+        assert!(app.world().contains_resource::<GroupLoadStatus>());
+        assert!(app.world().contains_resource::<Events<LoadElements>>());
+        assert_eq!(app.world().resource::<SkytracioOptions>(), &SkytracioOptions { headless: true, demo_bodies: false });
+        assert!(app.is_plugin_added::<PropagateElementsPlugin>());
+        assert!(app.is_plugin_added::<PropagateInGamePlugin>());
+        //nothing interactive, no Earth
+        assert!(!app.is_plugin_added::<SelectionPlugin>());
+        assert!(!app.world().contains_resource::<SelectionSet>());
+        assert!(!app.is_plugin_added::<LoadAndScaleEarthModelPlugin<Earth>>());
+        assert!(!app.world().contains_resource::<Events<AssetPrepared>>());
+    }
+
+    #[test]
+    fn test_full_suite_with_preinserted_resources() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_resource(InGameSettings::default())
+            .insert_resource(client())
+            .add_plugins(SkytracioPlugins::new().with_earth("3d/other.glb", 3000.0));
+
+        assert!(app.is_plugin_added::<SelectionPlugin>());
+        assert!(app.is_plugin_added::<LoadAndScaleEarthModelPlugin<Earth>>());
+        assert!(app.world().contains_resource::<Events<AssetPrepared>>());
+        assert_eq!(app.world().resource::<SkytracioOptions>(), &SkytracioOptions { headless: false, demo_bodies: true });
+    }
+
+    #[test]
+    #[should_panic(expected = "SkytracioPlugins is missing: InGameSettings (SkytracioPlugins::with_settings), game::propagation::client::file::ConstFileClient")]
+    fn test_missing_resources_are_listed() {
+        App::new().add_plugins(MinimalPlugins).add_plugins(SkytracioPlugins::new().headless());
+    }
+}
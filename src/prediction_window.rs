@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use bevy::{color::palettes::css::*, prelude::*};
+use sgp4::Elements;
+
+use crate::floating_origin::FloatingOrigin;
+use crate::global::{InGameSettings, PropagationSettings};
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::propagation::{is_plausible_prediction, predict_at, InGameElements, PropagatableDuration};
+use crate::selection::SelectionSet;
+use crate::simulation_clock::{ensure_simulation_clock, SimulationClock};
+use crate::world_frame::WORLD_FRAME;
+
+/// Draws the SGP4 predicted path of the primary selected satellite over the next minutes.
+/// Unlike the Keplerian ellipse it follows the perturbations (J2, drag) of the propagator
+pub struct PredictionWindowPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct PredictionWindowSettings {
+    pub enabled: bool,
+    pub window: Duration,
+    /// Simulation time between the points of the path, the path is also recomputed this often
+    pub step: Duration,
+    pub color: Color
+}
+
+impl Default for PredictionWindowSettings {
+    fn default() -> Self {
+        Self { enabled: true, window: Duration::from_secs(90 * 60), step: Duration::from_secs(30), color: AQUA.into() }
+    }
+}
+
+/// Predicted path of the satellite `of`, scaled to world space
+#[derive(Resource, Debug, Default)]
+pub struct PredictionWindow {
+    pub of: Option<Entity>,
+    pub points: Vec<Vec3>
+}
+
+/// Inertial positions (in kilometers) every `step` from `from_minutes` since epoch until the end of the window, which is always sampled.
+/// Stops at the first implausible prediction, later ones are not any better
+pub fn predict_window(elements: &Elements, from_minutes: f64, window: Duration, step: Duration, settings: &PropagationSettings) -> Vec<Vec3> {
+    let (window, step) = (window.as_secs_f64() / 60.0, step.as_secs_f64().max(1.0) / 60.0);
+    let steps = (window / step).ceil() as usize;
+    let mut points = Vec::with_capacity(steps + 1);
+    for i in 0..=steps {
+        let minutes = from_minutes + (i as f64 * step).min(window);
+        let prediction = predict_at(elements, minutes, settings.numeric_fallback).filter(|p| is_plausible_prediction(p, &settings.envelope));
+        let Some(prediction) = prediction else {
+            break;
+        };
+        points.push(Vec3::from_array(prediction.position.map(|c| c as f32)));
+    }
+    points
+}
+
+impl MemoryFootprint for PredictionWindow {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.points.len() * size_of::<Vec3>()
+    }
+}
+
+impl Plugin for PredictionWindowPlugin {
+    fn build(&self, app: &mut App) {
+        ensure_simulation_clock(app);
+        app
+            .init_resource::<PredictionWindowSettings>()
+            .init_resource::<PredictionWindow>()
+            .account_resource::<PredictionWindow>("look_ahead")
+            .init_resource::<SelectionSet>()
+            .init_resource::<FloatingOrigin>()
+            .add_systems(Update, update_prediction_window.run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, draw_prediction_window.after(update_prediction_window).run_if(resource_exists::<GizmoConfigStore>));
+    }
+}
+
+fn update_prediction_window(
+    clock: Res<SimulationClock>,
+    window_settings: Res<PredictionWindowSettings>,
+    settings: Res<InGameSettings>,
+    selection: Res<SelectionSet>,
+    mut window: ResMut<PredictionWindow>,
+    mut since_update: Local<f32>,
+    loaded: Query<(Ref<InGameElements>, &PropagatableDuration)>
+) {
+    let focused = selection.primary().filter(|_| window_settings.enabled);
+    *since_update += clock.delta_seconds() as f32;
+    let elements_changed = focused.and_then(|e| loaded.get(e).ok()).is_some_and(|(elements, _)| elements.is_changed());
+    let stale = *since_update >= window_settings.step.as_secs_f32() || window_settings.is_changed() || settings.is_changed();
+    if focused == window.of && !elements_changed && !stale {
+        return;
+    }
+
+    window.of = focused;
+    *since_update = 0.0;
+    window.points.clear();
+    //only loaded satellites have SGP4 elements, Keplerian bodies follow their ellipse exactly
+    let Some((elements, elapsed)) = focused.and_then(|e| loaded.get(e).ok()) else {
+        return;
+    };
+    let points = predict_window(&elements.0, elapsed.minutes_since_epoch(), window_settings.window, window_settings.step, &settings.propagation);
+    window.points = points.into_iter().map(|p| WORLD_FRAME.to_world(p) * settings.scale).collect();
+}
+
+fn draw_prediction_window(mut gizmos: Gizmos, window_settings: Res<PredictionWindowSettings>, window: Res<PredictionWindow>, origin: Res<FloatingOrigin>) {
+    if window.points.len() > 1 {
+        gizmos.linestrip(window.points.iter().map(|p| origin.to_render(*p)), window_settings.color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use sgp4::MinutesSinceEpoch;
+
+    use super::*;
+    use crate::propagation::{DataSource, ElementsFormat, LoadElements, LoadElementsPlugin, LoadedElements};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    fn sgp4_position(elements: &Elements, minutes: f64) -> Vec3 {
+        let prediction = sgp4::Constants::from_elements(elements).unwrap().propagate(MinutesSinceEpoch(minutes)).unwrap();
+        Vec3::from_array(prediction.position.map(|c| c as f32))
+    }
+
+    #[test]
+    fn test_window_ends_at_direct_propagation() {
+        let elements = starlink_like_elements(1, 1465).remove(0);
+        //the window isn't a multiple of the step, its end is sampled anyway
+        let points = predict_window(&elements, 12.5, Duration::from_secs(95 * 60), Duration::from_secs(40), &PropagationSettings::default());
+        assert_eq!(points.len(), 144);
+        let end = sgp4_position(&elements, 12.5 + 95.0);
+        assert!(points.last().unwrap().distance(end) < 1e-3, "{:?} {end:?}", points.last());
+        assert!(points[0].distance(sgp4_position(&elements, 12.5)) < 1e-3);
+    }
+
+    #[test]
+    fn test_path_of_selected_satellite() {
+        let data = starlink_like_elements(3, 1465);
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<SyntheticClient>::new(), PredictionWindowPlugin))
+            .insert_resource(SyntheticClient(data.clone()))
+            .insert_resource(PredictionWindowSettings { window: Duration::from_secs(30 * 60), step: Duration::from_secs(60), ..default() })
+            .insert_resource(InGameSettings::default());
+        app.world_mut().send_event(LoadElements { groups: vec!["starlink".to_owned()], format: ElementsFormat::Json, source: DataSource::Gp, launch_animation: false, refresh: false });
+        let mut reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        let mut entities = vec![];
+        for _ in 0..20 {
+            app.update();
+            if let Some(loaded) = reader.read(app.world().resource::<Events<LoadedElements>>()).next() {
+                entities = loaded.entities().to_vec();
+                break;
+            }
+        }
+        app.world_mut().resource_mut::<SelectionSet>().select_single(entities[1]);
+        app.update();
+
+        let window = app.world().resource::<PredictionWindow>();
+        assert_eq!(window.of, Some(entities[1]));
+        assert_eq!(window.points.len(), 31);
+        let end = sgp4_position(&data[1], 30.0) * 0.01;
+        let last = window.points.last().unwrap();
+        assert_abs_diff_eq!(last.x, end.x, epsilon = 1e-3);
+        assert_abs_diff_eq!(last.y, end.y, epsilon = 1e-3);
+        assert_abs_diff_eq!(last.z, end.z, epsilon = 1e-3);
+    }
+}
@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use sgp4::Prediction;
+
+use crate::global::{AltitudeBand, InGameSettings};
+use crate::orbit::EARTH_EQUATORIAL_RADIUS;
+
+use super::bevy_integration::Propageted;
+use super::marker_style::{MarkerStyle, StyleLayer, StyleModifier};
+
+//equatorial radius, good enough for shell classification
+pub const EARTH_RADIUS_KM: f32 = EARTH_EQUATORIAL_RADIUS as f32;
+
+pub struct AltitudeBandsPlugin;
+
+#[derive(Event, Clone)]
+pub struct AddAltitudeBand(pub AltitudeBand);
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct EnteredBand {
+    pub entity: Entity,
+    /// Id of the band in [`AltitudeBands`], names don't have to be unique
+    pub band_id: usize,
+    pub band: String
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct LeftBand {
+    pub entity: Entity,
+    pub band_id: usize,
+    pub band: String
+}
+
+/// Band the satellite currently belongs to, identified by the band id in [`AltitudeBands`]
+#[derive(Component, Default, Debug)]
+pub struct AltitudeBandMembership(pub Option<usize>);
+
+/// Band rejected because it overlaps one already added
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlappingBands {
+    pub band: String,
+    pub existing: String
+}
+
+/// Disjoint bands kept sorted by their lower edge, so classification is a binary search
+#[derive(Resource, Default)]
+pub struct AltitudeBands {
+    bands: Vec<(usize, AltitudeBand)>,
+    next_id: usize
+}
+
+impl AltitudeBands {
+    pub fn new(bands: impl IntoIterator<Item = AltitudeBand>) -> Result<Self, OverlappingBands> {
+        let mut result = Self::default();
+        for band in bands {
+            result.insert(band)?;
+        }
+        Ok(result)
+    }
+
+    /// Adds the band and returns its id, a band overlapping an existing one is rejected.
+    /// Bands may share an edge
+    pub fn insert(&mut self, band: AltitudeBand) -> Result<usize, OverlappingBands> {
+        let position = self.bands.partition_point(|(_, b)| b.min_km <= band.min_km);
+        //the bands are disjoint, only the neighbours of the insertion point can overlap the new one
+        let overlapping = [position.checked_sub(1), Some(position)].into_iter()
+            .flatten()
+            .filter_map(|i| self.bands.get(i))
+            .find(|(_, b)| b.min_km < band.max_km && band.min_km < b.max_km);
+        if let Some((_, existing)) = overlapping {
+            return Err(OverlappingBands { band: band.name, existing: existing.name.clone() });
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.bands.insert(position, (id, band));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&AltitudeBand> {
+        self.bands.iter().find(|(band_id, _)| *band_id == id).map(|(_, b)| b)
+    }
+
+    /// Classifies the altitude (km above surface), members of a band keep their membership
+    /// until they move past the band edges by more than its hysteresis margin
+    pub fn classify(&self, altitude_km: f32, current: Option<usize>) -> Option<usize> {
+        if let Some(band) = current.and_then(|id| self.get(id)) {
+            if altitude_km >= band.min_km - band.hysteresis_km && altitude_km <= band.max_km + band.hysteresis_km {
+                return current;
+            }
+        }
+        let position = self.bands.partition_point(|(_, b)| b.min_km <= altitude_km);
+        if position == 0 {
+            return None;
+        }
+        let (id, band) = &self.bands[position - 1];
+        (altitude_km <= band.max_km).then_some(*id)
+    }
+}
+
+impl Plugin for AltitudeBandsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AltitudeBands>()
+            .add_event::<AddAltitudeBand>()
+            .add_event::<EnteredBand>()
+            .add_event::<LeftBand>()
+            .add_systems(Startup, setup_altitude_bands)
+            .add_systems(Update, (add_altitude_bands, classify_altitude_bands).chain())
+            .add_systems(Update, tint_band_members.after(classify_altitude_bands));
+    }
+}
+
+fn setup_altitude_bands(settings: Res<InGameSettings>, mut bands: ResMut<AltitudeBands>) {
+    for band in &settings.altitude_bands {
+        if let Err(err) = bands.insert(band.clone()) {
+            warn!("Ignoring altitude band {} of the settings, it overlaps {}", err.band, err.existing);
+        }
+    }
+}
+
+fn add_altitude_bands(mut events: EventReader<AddAltitudeBand>, mut bands: ResMut<AltitudeBands>) {
+    for AddAltitudeBand(band) in events.read() {
+        match bands.insert(band.clone()) {
+            Ok(_) => info!("Adding altitude band {} ({} - {} km)", band.name, band.min_km, band.max_km),
+            Err(err) => warn!("Ignoring altitude band {}, it overlaps {}", err.band, err.existing)
+        }
+    }
+}
+
+//uses the authoritative per-tick prediction, dead-reckoned transforms jitter too much
+fn classify_altitude_bands(
+    mut events: EventReader<Propageted>,
+    bands: Res<AltitudeBands>,
+    mut memberships: Query<Option<&mut AltitudeBandMembership>>,
+    mut entered: EventWriter<EnteredBand>,
+    mut left: EventWriter<LeftBand>,
+    mut commands: Commands
+) {
+    for propagated in events.read() {
+        for (entity, prediction) in &propagated.data {
+            let Ok(membership) = memberships.get_mut(*entity) else {
+                continue;
+            };
+            let current = membership.as_ref().and_then(|m| m.0);
+            let classified = bands.classify(prediction_altitude(prediction), current);
+            if classified == current {
+                continue;
+            }
+
+            if let Some((band_id, band)) = current.and_then(|id| Some((id, bands.get(id)?))) {
+                left.send(LeftBand { entity: *entity, band_id, band: band.name.clone() });
+            }
+            if let Some((band_id, band)) = classified.and_then(|id| Some((id, bands.get(id)?))) {
+                entered.send(EnteredBand { entity: *entity, band_id, band: band.name.clone() });
+            }
+
+            match membership {
+                Some(mut membership) => membership.0 = classified,
+                None => { commands.entity(*entity).insert(AltitudeBandMembership(classified)); }
+            }
+        }
+    }
+}
+
+fn tint_band_members(
+    mut entered: EventReader<EnteredBand>,
+    mut left: EventReader<LeftBand>,
+    bands: Res<AltitudeBands>,
+    mut styles: Query<&mut MarkerStyle>
+) {
+    for ev in left.read() {
+        if let Ok(mut style) = styles.get_mut(ev.entity) {
+            style.clear(StyleLayer::Band);
+        }
+    }
+    for ev in entered.read() {
+        let tint = bands.get(ev.band_id).and_then(|b| b.tint);
+        if let (Some(tint), Ok(mut style)) = (tint, styles.get_mut(ev.entity)) {
+            style.set(StyleLayer::Band, StyleModifier::base(tint));
+        }
+    }
+}
+
+fn prediction_altitude(prediction: &Prediction) -> f32 {
+    let [x, y, z] = prediction.position;
+    (x * x + y * y + z * z).sqrt() as f32 - EARTH_RADIUS_KM
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::event::ManualEventReader, prelude::*};
+    use sgp4::Prediction;
+
+    use super::*;
+    use crate::orbit::SatelliteOrbit;
+
+    fn band(name: &str, min_km: f32, max_km: f32) -> AltitudeBand {
+        AltitudeBand { name: name.to_owned(), min_km, max_km, hysteresis_km: 50.0, tint: None }
+    }
+
+    #[test]
+    fn test_classification_with_hysteresis() {
+        let bands = AltitudeBands::new(vec![band("high", 15000.0, 22000.0), band("low", 1000.0, 3000.0)]).unwrap();
+        let low = bands.classify(2000.0, None);
+        assert_eq!(bands.get(low.unwrap()).unwrap().name, "low");
+        assert_eq!(bands.classify(500.0, None), None);
+        assert_eq!(bands.classify(10000.0, None), None);
+        assert_eq!(bands.classify(40000.0, None), None);
+        //inside the margin the membership is kept, a fresh classification would reject it
+        assert_eq!(bands.classify(3030.0, low), low);
+        assert_eq!(bands.classify(3030.0, None), None);
+        assert_eq!(bands.classify(3060.0, low), None);
+    }
+
+    #[test]
+    fn test_overlapping_bands_are_rejected() {
+        let mut bands = AltitudeBands::new(vec![band("low", 1000.0, 3000.0), band("high", 15000.0, 22000.0)]).unwrap();
+        //sharing an edge with both neighbours is fine
+        let middle = bands.insert(band("middle", 3000.0, 15000.0)).unwrap();
+        assert_eq!(bands.classify(4000.0, None), Some(middle));
+
+        let overlap = OverlappingBands { band: "wide".to_owned(), existing: "low".to_owned() };
+        assert_eq!(bands.insert(band("wide", 2000.0, 4000.0)), Err(overlap));
+        assert_eq!(bands.insert(band("inner", 16000.0, 17000.0)).unwrap_err().existing, "high");
+        assert_eq!(bands.insert(band("outer", 500.0, 30000.0)).unwrap_err().existing, "low");
+        //the classification is the same whatever the order the rejected bands came in
+        assert_eq!(bands.classify(2500.0, None).and_then(|id| bands.get(id)).unwrap().name, "low");
+        assert!(AltitudeBands::new(vec![band("a", 100.0, 200.0), band("b", 150.0, 160.0)]).is_err());
+    }
+
+    #[test]
+    fn test_band_events_over_eccentric_orbit() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, AltitudeBandsPlugin))
+            .add_event::<Propageted>()
+            .insert_resource(InGameSettings {
+                altitude_bands: vec![band("low", 1000.0, 3000.0), band("high", 15000.0, 22000.0)],
+                ..default()
+            });
+
+        let entity = app.world_mut().spawn_empty().id();
+        //perigee altitude ~1600 km, apogee altitude ~25600 km
+        let orbit = SatelliteOrbit::new(20000.0, 0.6, 30.0, 0.0, 0.0, 0.0, 0.0);
+        let steps = 360;
+        let step = orbit.orbital_period() / steps as f64;
+
+        let mut entered_reader = ManualEventReader::<EnteredBand>::default();
+        let mut left_reader = ManualEventReader::<LeftBand>::default();
+        let mut sequence = vec![];
+        for i in 0..=steps {
+            let prediction = Prediction { position: orbit.propagate(step * i as f64).position().to_array(), velocity: [0.0; 3] };
+            app.world_mut().send_event(Propageted::new(vec![(entity, prediction)]));
+            app.update();
+
+            for ev in left_reader.read(app.world().resource::<Events<LeftBand>>()) {
+                sequence.push(format!("left {}", ev.band));
+            }
+            for ev in entered_reader.read(app.world().resource::<Events<EnteredBand>>()) {
+                sequence.push(format!("entered {}", ev.band));
+            }
+        }
+
+        assert_eq!(sequence, vec![
+            "entered low", "left low", "entered high", "left high", "entered high", "left high", "entered low"
+        ]);
+    }
+}
@@ -1,44 +1,214 @@
+use bevy::color::palettes::css::GRAY;
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
 use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
-use sgp4::{Elements, ElementsError, MinutesSinceEpoch, Prediction};
+use sgp4::{Elements, MinutesSinceEpoch, Prediction};
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::ops::{Add, AddAssign, Mul};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use crate::orbit::SatelliteOrbit;
+use std::time::{Duration, SystemTime};
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::error::SkytracioError;
+use crate::input::ActionCategory;
+use crate::memory::{AccountMemory, MemoryFootprint};
+use crate::orbit::{SatelliteOrbit, GRAVITATIONAL_CONSTANT};
 use crate::global::*;
-
-use super::{EpochDataLoader, OrbitalData};
-
-pub struct LoadElementsPlugin<C>(PhantomData<C>);
+use crate::simtime::{self, SimInstant};
+use crate::simulation_clock::{ensure_simulation_clock, SimulationClock, MAX_SPEED};
+use crate::world_frame::WORLD_FRAME;
+
+use super::{DataSource, ElementsFormat, EpochDataLoader, OrbitalData, PartialSets};
+use super::classification::OrbitClassification;
+use super::derived_cache::{DerivedData, DerivedDataCache, DerivedRecord};
+use super::fallback::{fallback_prediction, FallbackStates};
+use super::groups::SatelliteGroup;
+use super::index::{ensure_satellite_index, update_satellite_index, SatelliteIndex};
+use super::interning::ElementsInterner;
+use super::marker_mesh::{MarkerMeshCache, MarkerSize};
+use super::marker_style::{MarkerStyle, StyleLayer, StyleModifier};
+use super::progressive_visuals::{PointVisual, ProgressiveVisuals};
+use super::provenance::{Provenance, Resolution, SourcePrecedence};
+use super::revolutions::{count_nodal_revolutions, RevolutionCounter};
+use super::transitions::Despawning;
+use super::unload::PendingUnload;
+use super::validation::{is_plausible_prediction, BecameUnreliable, LostTrack, PredictionFailures, StalePrediction, TrackLost, Unreliable};
+
+pub struct LoadElementsPlugin<C> {
+    spawn_hooks: Vec<SpawnHook>,
+    phantom_data: PhantomData<C>
+}
 
 impl <C> LoadElementsPlugin<C> {
     pub fn new() -> Self {
-        Self(PhantomData)
+        Self { spawn_hooks: vec![], phantom_data: PhantomData }
+    }
+
+    /// Registers a hook invoked for every spawned satellite, right after the [`PropagatableSattelite`] bundle
+    /// is inserted. Hooks run in registration order, their commands are applied before [`LoadedElements`]
+    /// and [`SatelliteSpawned`] can be read by any system.
+    pub fn with_spawn_hook(mut self, hook: impl Fn(&Elements, &mut EntityCommands) + Send + Sync + 'static) -> Self {
+        self.spawn_hooks.push(Arc::new(hook));
+        self
     }
 }
 
-#[derive(Event, Default)]
+impl <C> Default for LoadElementsPlugin<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type SpawnHook = Arc<dyn Fn(&Elements, &mut EntityCommands) + Send + Sync>;
+
+#[derive(Resource, Default, Clone)]
+pub struct SatelliteSpawnHooks(Vec<SpawnHook>);
+
+/// Sent for every spawned satellite, always before the [`LoadedElements`] event of the same load
+#[derive(Event, Debug, Clone)]
+pub struct SatelliteSpawned {
+    pub entity: Entity,
+    pub norad_id: u64,
+    /// Asked for by the load, the satellite rises from the launch site before it's shown in its orbit
+    pub launch_animation: bool
+}
+
+/// Elements of a loaded satellite replaced in place by a newer set from the same source
+#[derive(Event, Debug, Clone)]
+pub struct ElementsDiff {
+    pub entity: Entity,
+    pub previous: Arc<Elements>,
+    pub current: Arc<Elements>,
+    /// Simulation time of the satellite when its elements were replaced
+    pub minutes_since_previous_epoch: f64
+}
+
+impl ElementsDiff {
+    /// Simulation time of the update
+    pub fn instant(&self) -> SimInstant {
+        SimInstant::epoch(&self.previous).after_minutes(self.minutes_since_previous_epoch)
+    }
+
+    /// The same instant relative to the epoch of the new elements
+    pub fn minutes_since_current_epoch(&self) -> f64 {
+        self.instant().minutes_since(SimInstant::epoch(&self.current))
+    }
+
+    /// The instant of the update as minutes since J2000, see [`PropagatableDuration::minutes_since_j2000`]
+    pub fn minutes_since_j2000(&self) -> f64 {
+        self.instant().minutes_since(SimInstant::J2000)
+    }
+}
+
+#[derive(Event, Debug, Clone, Default)]
 pub struct LoadElements {
+    /// Loaded together into one [`LoadedElements`], a satellite in several of the groups is spawned once, with the first
+    /// of them
+    pub groups: Vec<String>,
+    pub format: ElementsFormat,
+    pub source: DataSource,
+    /// Newly spawned satellites of the group are launched, see [`crate::launch`]
+    pub launch_animation: bool,
+    /// Fetched again past the caches of the loader, satellites already loaded from the same source get the newer
+    /// elements in place
+    pub refresh: bool
+}
+
+impl LoadElements {
+    pub fn group(group: impl Into<String>, format: ElementsFormat) -> Self {
+        Self { groups: vec![group.into()], format, ..default() }
+    }
+}
+
+/// Sent for every finished load of a group, even when nothing was received
+#[derive(Event, Debug, Clone)]
+pub struct ElementsFetched {
+    pub group: String,
+    pub format: ElementsFormat,
+    pub source: DataSource,
+    /// Element sets returned by the loader, zero when it failed
+    pub received: usize,
+    /// Why the load failed, shared by the readers of the event
+    pub error: Option<Arc<SkytracioError>>
+}
+
+/// Sent when the loader failed, [`LoadedElements`] is sent only for the loads that succeeded
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct LoadFailed {
     pub group: String,
-    pub format: String
+    pub format: ElementsFormat,
+    /// The whole error chain, see [`SkytracioError::report`]
+    pub reason: String
 }
 
 #[derive(Event, Default)]
 pub struct LoadedElements {
-    pub entities: Vec<Entity>,
-    pub data: OrbitalData
+    entities: Vec<Entity>,
+    data: OrbitalData
 }
 
+impl LoadedElements {
+    /// `entities` are the satellites spawned for `data`, in the same order
+    pub fn new(entities: Vec<Entity>, data: OrbitalData) -> Self {
+        Self { entities, data }
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn data(&self) -> &OrbitalData {
+        &self.data
+    }
+}
+
+//every group with its elements not in the groups before it and the number of sets received for it
+type GroupLoads = Vec<(String, Result<(OrbitalData, usize), SkytracioError>)>;
+
 #[derive(Component)]
 struct JobInExecution {
-    task: Task<OrbitalData>
+    format: ElementsFormat,
+    source: DataSource,
+    launch_animation: bool,
+    //sets of every group streamed before the group is in, spawned while the task runs
+    partial: Vec<(String, PartialSets)>,
+    //derived records are empty without a `DerivedDataCache`
+    task: Task<(GroupLoads, DerivedData)>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadStatus {
+    Pending,
+    Loaded
+}
+
+/// Load status of every group requested through [`LoadElements`]
+#[derive(Resource, Default, Debug)]
+pub struct GroupLoadStatus(HashMap<String, LoadStatus>);
+
+impl GroupLoadStatus {
+    pub fn get(&self, group: &str) -> Option<LoadStatus> {
+        self.0.get(group).copied()
+    }
+
+    pub fn set(&mut self, group: String, status: LoadStatus) {
+        self.0.insert(group, status);
+    }
+
+    /// The group was unloaded, it's neither pending nor loaded anymore
+    pub fn remove(&mut self, group: &str) {
+        self.0.remove(group);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &LoadStatus)> {
+        self.0.iter()
+    }
 }
 
 #[derive(Resource)]
-struct SateliteDisplayData {
-    mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>
+pub(super) struct SateliteDisplayData {
+    pub(super) mesh: Handle<Mesh>,
+    pub(super) material: Handle<StandardMaterial>
 }
 
 impl <C: EpochDataLoader + Resource + Clone> Plugin for LoadElementsPlugin<C> {
@@ -47,62 +217,301 @@ impl <C: EpochDataLoader + Resource + Clone> Plugin for LoadElementsPlugin<C> {
         app
           .add_event::<LoadElements>()
           .add_event::<LoadedElements>()
+          .add_event::<SatelliteSpawned>()
+          .add_event::<ElementsDiff>()
+          .add_event::<ElementsFetched>()
+          .add_event::<LoadFailed>()
+          .init_resource::<GroupLoadStatus>()
+          .init_resource::<MarkerMeshCache>()
+          .init_resource::<SourcePrecedence>()
+          .init_resource::<ElementsInterner>()
+          .init_resource::<SpawnPlacement>()
+          .insert_resource(SatelliteSpawnHooks(self.spawn_hooks.clone()))
+          .register_command(
+              CommandDescriptor::event("Load group", ActionCategory::General, |params| LoadElements {
+                  groups: params[0].as_text().unwrap_or_default().split(',').map(str::trim).filter(|group| !group.is_empty()).map(str::to_owned).collect(),
+                  format: ElementsFormat::Json,
+                  source: DataSource::Gp,
+                  launch_animation: false,
+                  refresh: false
+              })
+              .with_param("groups, comma separated", ParamKind::Text)
+          )
+          .register_command(
+              CommandDescriptor::new("Load group as", ActionCategory::General, |params, world| {
+                  let group = params[0].as_text().unwrap_or_default().to_owned();
+                  match params[1].as_text().unwrap_or_default().parse::<ElementsFormat>() {
+                      Ok(format) => {
+                          world.send_event(LoadElements { groups: vec![group], format, source: DataSource::Gp, launch_animation: false, refresh: false });
+                      },
+                      Err(err) => warn!("Not loading {group}: {}", err.report())
+                  }
+              })
+              .with_param("group", ParamKind::Text)
+              .with_param("JSON, TLE or CSV", ParamKind::Text)
+          )
           .add_systems(Startup, create_assets.run_if(rendering_condition.clone()))
-          .add_systems(PreUpdate, instantiate_satelite.run_if(rendering_condition))
+          .add_systems(PreUpdate, instantiate_satelite.run_if(rendering_condition.and_then(resource_exists::<InGameSettings>)))
           .add_systems(Update, move_to_loading::<C>)
           .add_systems(PostUpdate, execute_elements_loading);
     }
 }
 
-fn create_assets(mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, mut commands: Commands) {
-    let sphere = Sphere { radius: 1.5 };
-    let mesh = meshes.add(sphere.mesh());
+fn create_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MarkerMeshCache>,
+    mut commands: Commands
+) {
+    let mesh = cache.get_or_create(MarkerSize::default(), &mut meshes);
     let material = materials.add(Color::WHITE);
     commands.insert_resource(SateliteDisplayData { mesh, material });
 }
 
-fn move_to_loading<C: EpochDataLoader + Resource + Clone>(mut load_events: EventReader<LoadElements>, epoch_data_loader: Res<C>, mut commands: Commands) {
+fn move_to_loading<C: EpochDataLoader + Resource + Clone>(
+    mut load_events: EventReader<LoadElements>, epoch_data_loader: Res<C>, derived_cache: Option<Res<DerivedDataCache>>,
+    mut status: ResMut<GroupLoadStatus>, mut commands: Commands
+) {
     for ev in load_events.read() {
         debug!("Spawning");
         let thread_pool = AsyncComputeTaskPool::get();
         let local_loader = epoch_data_loader.clone();
-        let group = ev.group.clone();
-        let format = ev.format.clone();
-
+        let groups = ev.groups.clone();
+        let format = ev.format;
+        let source = ev.source;
+        let refresh = ev.refresh;
+        let derived_cache = derived_cache.as_deref().cloned();
+
+        for group in &groups {
+            status.set(group.clone(), LoadStatus::Pending);
+        }
+        let partial: Vec<_> = groups.iter().map(|group| (group.clone(), PartialSets::default())).collect();
+        let streamed = partial.iter().map(|(_, sets)| sets.clone()).collect::<Vec<_>>();
         let task = thread_pool.spawn(async move {
-            local_loader.load_or_empty(group, format).await
+            let mut seen = HashSet::new();
+            let mut derived = DerivedData::new();
+            let mut loads = Vec::with_capacity(groups.len());
+            //one after another, the loaders throttle their requests anyway
+            for (group, partial) in groups.into_iter().zip(streamed) {
+                let data = if refresh {
+                    local_loader.refresh_group(source, group.clone(), format).await
+                } else {
+                    local_loader.load_group_streamed(source, group.clone(), format, partial).await
+                };
+                let data = data.map(|data| {
+                    if let Some(cache) = derived_cache.as_ref().filter(|_| !data.is_empty()) {
+                        derived.extend(cache.derive_all(source, &group, &data));
+                    }
+                    let received = data.len();
+                    (data.into_iter().filter(|el| seen.insert(el.norad_id)).collect(), received)
+                });
+                loads.push((group, data));
+            }
+            (loads, derived)
         });
         commands.spawn_empty()
-            .insert(JobInExecution { task });
+            .insert(JobInExecution { format, source, launch_animation: ev.launch_animation, partial, task });
     }
 }
 
 fn execute_elements_loading(
-    mut loading_resources: Query<(Entity, &mut JobInExecution)>, mut loaded_data: EventWriter<LoadedElements>, 
+    mut loading_resources: Query<(Entity, &mut JobInExecution)>,
+    (mut loaded_data, mut spawned, mut diffs): (EventWriter<LoadedElements>, EventWriter<SatelliteSpawned>, EventWriter<ElementsDiff>),
+    (mut fetched, mut failed): (EventWriter<ElementsFetched>, EventWriter<LoadFailed>),
+    (mut status, mut interner): (ResMut<GroupLoadStatus>, ResMut<ElementsInterner>),
+    (hooks, precedence): (Res<SatelliteSpawnHooks>, Res<SourcePrecedence>),
+    (loaded, durations): (Query<(Entity, &InGameElements, &Provenance)>, Query<&PropagatableDuration>),
     mut commands: Commands
 ) {
+    //satellites loaded so far by NORAD id, kept up to date with the jobs finished in this run
+    let mut known: HashMap<u64, (Entity, DataSource)> = HashMap::new();
+    let current_elements: HashMap<Entity, &Arc<Elements>> = loaded.iter().map(|(e, el, _)| (e, &el.0)).collect();
     for (entity, mut job) in loading_resources.iter_mut() {
         debug!("Polling on: {entity}");
-        if let Some(data) = block_on(future::poll_once(&mut job.task)) {
-            let entities = data.iter().map(|el| {
-                let sattelite = PropagatableSattelite::new(InGameElements(el.clone()));
-                debug!("Spawning: {:?}", sattelite.orbit);
-                commands.spawn(sattelite).id()
-            }).collect();
-            loaded_data.send(LoadedElements { entities, data });
-            commands.get_entity(entity).unwrap().despawn();
+        //elements of every group that loaded, by the index of its provenance
+        let mut data = vec![];
+        let mut provenances = vec![];
+        let derived = match block_on(future::poll_once(&mut job.task)) {
+            Some((loads, derived)) => {
+                for (group, result) in loads {
+                    match result {
+                        Ok((elements, received)) => {
+                            fetched.send(ElementsFetched { group: group.clone(), format: job.format, source: job.source, received, error: None });
+                            data.extend(elements.into_iter().map(|el| (provenances.len(), el)));
+                            provenances.push(Provenance { source: job.source, group: group.clone(), retrieved_at: SystemTime::now() });
+                        },
+                        Err(err) => {
+                            let reason = err.report();
+                            error!("{reason}");
+                            failed.send(LoadFailed { group: group.clone(), format: job.format, reason });
+                            fetched.send(ElementsFetched { group: group.clone(), format: job.format, source: job.source, received: 0, error: Some(Arc::new(err)) });
+                        }
+                    }
+                    //nothing more is coming, whoever waits for the group stops waiting
+                    status.set(group, LoadStatus::Loaded);
+                }
+                commands.entity(entity).despawn();
+                derived
+            },
+            //what's streamed so far is spawned right away, the result of the task has it too and finds it loaded
+            None => {
+                for (group, partial) in &job.partial {
+                    let sets = partial.take();
+                    if !sets.is_empty() {
+                        data.extend(sets.into_iter().map(|el| (provenances.len(), el)));
+                        provenances.push(Provenance { source: job.source, group: group.clone(), retrieved_at: SystemTime::now() });
+                    }
+                }
+                DerivedData::new()
+            }
+        };
+        if provenances.is_empty() {
+            continue;
         }
+        if known.is_empty() {
+            known.extend(loaded.iter().map(|(e, el, p)| (el.0.norad_id, (e, p.source))));
+        }
+        //spawned and reported by NORAD id, whatever the order of the source
+        data.sort_by_key(|(_, el)| el.norad_id);
+        let mut accepted = Vec::with_capacity(data.len());
+        let mut entities = Vec::with_capacity(data.len());
+        for (group, el) in data {
+            let provenance = &provenances[group];
+            let el = interner.intern(el);
+            let existing = known.get(&el.norad_id).copied();
+            match precedence.resolve(existing.map(|(_, source)| source), job.source) {
+                Resolution::Skip => {
+                    //a refetch of the same source, newer sets replace the elements of the live satellite
+                    let (existing, source) = existing.unwrap();
+                    let previous = current_elements.get(&existing).filter(|previous| SimInstant::epoch(previous) < SimInstant::epoch(&el));
+                    match (previous, durations.get(existing)) {
+                        (Some(previous), Ok(duration)) if source == job.source => {
+                            debug!("Updating {} from {}", el.norad_id, job.source.label());
+                            let diff = ElementsDiff {
+                                entity: existing,
+                                previous: Arc::clone(previous),
+                                current: el.clone(),
+                                minutes_since_previous_epoch: duration.minutes_since_epoch()
+                            };
+                            let since_epoch = diff.instant().duration_since(SimInstant::epoch(&el)).unwrap_or(Duration::ZERO);
+                            let orbit = SatelliteOrbit::from(el.as_ref());
+                            commands.entity(existing).insert((
+                                InGameElements(el.clone()), SatelliteId::from(el.as_ref()), OrbitClassification::new(&el, &orbit), orbit, PropagatableDuration(since_epoch), provenance.clone()
+                            ));
+                            diffs.send(diff);
+                        },
+                        _ => debug!("Skipping {} from {}, already loaded", el.norad_id, job.source.label())
+                    }
+                    continue;
+                },
+                Resolution::Replace => {
+                    let (replaced, source) = existing.unwrap();
+                    debug!("Replacing {} from {} with {}", el.norad_id, source.label(), job.source.label());
+                    commands.entity(replaced).despawn_recursive();
+                },
+                Resolution::Spawn => {}
+            }
+            let entity = spawn_satellite(&mut commands, provenance, &el, derived.get(&el.norad_id), &hooks, &mut spawned, job.launch_animation);
+            known.insert(el.norad_id, (entity, job.source));
+            entities.push(entity);
+            accepted.push(el);
+        }
+        loaded_data.send(LoadedElements::new(entities, accepted));
     }
 }
 
-fn instantiate_satelite(mut loaded_data: EventReader<LoadedElements>, mut commands: Commands, display_data: Res<SateliteDisplayData>) {
+//every path spawning satellites must go through here, so the hooks and events are consistent
+pub(super) fn spawn_satellite(
+    commands: &mut Commands,
+    provenance: &Provenance,
+    elements: &Arc<Elements>,
+    derived: Option<&DerivedRecord>,
+    hooks: &SatelliteSpawnHooks,
+    spawned: &mut EventWriter<SatelliteSpawned>,
+    launch_animation: bool
+) -> Entity {
+    let sattelite = match derived.filter(|record| record.matches(elements)) {
+        Some(record) => PropagatableSattelite::with_classification(InGameElements(elements.clone()), record.classification.clone()),
+        None => PropagatableSattelite::new(InGameElements(elements.clone()))
+    };
+    debug!("Spawning: {:?}", sattelite.orbit);
+    let classification = &sattelite.classification;
+    if classification.has_period_mismatch() {
+        warn!(
+            "Period of {} from the orbit is {:+.1} s off the elements ({:.3} min against {:.3} min), its ellipse is unreliable",
+            elements.norad_id, classification.period_discrepancy_seconds(), classification.orbit_period_minutes, classification.period_minutes
+        );
+    } else {
+        debug!(
+            "Period of {}: {:.3} min from elements, {:.3} min from orbit ({:+.2} s)",
+            elements.norad_id, classification.period_minutes, classification.orbit_period_minutes, classification.period_discrepancy_seconds()
+        );
+    }
+    debug!(
+        "RAAN of {} drifts {:+.3}°/day, perigee {:+.3}°/day",
+        elements.norad_id, sattelite.orbit.nodal_regression_rate(), sattelite.orbit.apsidal_precession_rate()
+    );
+    let mut entity_commands = commands.spawn((sattelite, SatelliteGroup(provenance.group.clone()), provenance.clone()));
+    for hook in &hooks.0 {
+        hook(elements, &mut entity_commands);
+    }
+    let entity = entity_commands.id();
+    spawned.send(SatelliteSpawned { entity, norad_id: elements.norad_id, launch_animation });
+    entity
+}
+
+/// Where satellites are shown between spawning and their first prediction
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnPlacement {
+    /// At the position of the elements at their epoch, from the Keplerian orbit
+    #[default]
+    Epoch,
+    /// Hidden until the first plausible prediction arrives
+    Hidden
+}
+
+/// Satellite spawned hidden, shown with its first plausible prediction
+#[derive(Component)]
+struct AwaitingPrediction;
+
+fn instantiate_satelite(
+    mut loaded_data: EventReader<LoadedElements>,
+    mut commands: Commands,
+    display_data: Res<SateliteDisplayData>,
+    progressive: Option<Res<ProgressiveVisuals>>,
+    (placement, settings): (Res<SpawnPlacement>, Res<InGameSettings>),
+    (orbits, sizes): (Query<&SatelliteOrbit>, Query<&MarkerSize>),
+    (mut meshes, mut cache): (ResMut<Assets<Mesh>>, ResMut<MarkerMeshCache>)
+) {
     for ev in loaded_data.read() {
         for entity in &ev.entities {
+            //a default transform would put the satellite in the middle of the Earth until it's propagated
+            let transform = orbits.get(*entity)
+                .map(|orbit| Transform::from_translation(WORLD_FRAME.to_world(orbit.to_translation_and_rotation().position) * settings.scale))
+                .unwrap_or_default();
+            let visibility = match *placement {
+                SpawnPlacement::Epoch => Visibility::Inherited,
+                SpawnPlacement::Hidden => {
+                    commands.entity(*entity).insert(AwaitingPrediction);
+                    Visibility::Hidden
+                }
+            };
+            //the mesh and the material are materialized later, for the satellites that become relevant
+            if progressive.is_some() {
+                commands.entity(*entity).insert((SpatialBundle { transform, visibility, ..default() }, PointVisual));
+                continue;
+            }
+            let mesh = match sizes.get(*entity) {
+                Ok(size) => cache.get_or_create(*size, &mut meshes),
+                Err(_) => display_data.mesh.clone()
+            };
             commands
                 .entity(*entity)
                 .insert(PbrBundle {
-                    mesh: display_data.mesh.clone(),
+                    mesh,
                     material: display_data.material.clone(),
+                    transform,
+                    visibility,
                     ..default()
                 });
         }
@@ -115,19 +524,61 @@ pub struct PropagateElementsPlugin;
 #[derive(Clone, Component)]
 pub struct InGameElements(pub Arc<Elements>);
 
+/// Identity of a satellite as in its elements, kept in step with [`InGameElements`]
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SatelliteId {
+    pub norad_id: u64,
+    pub name: Option<String>,
+    pub international_designator: Option<String>
+}
+
+impl From<&Elements> for SatelliteId {
+    fn from(elements: &Elements) -> Self {
+        Self { norad_id: elements.norad_id, name: elements.object_name.clone(), international_designator: elements.international_designator.clone() }
+    }
+}
+
 #[derive(Component)]
-enum PropagationStatus {
+pub(super) enum PropagationStatus {
     Propagated {
         velocity: Velocity,
-        //not a translation of sattelite in-game, but a position as reported by propagator
-        position: Vec3,
-        just_propagated: bool
+        just_propagated: bool,
+        //set when the prediction is ahead of the simulation, see `PropagationSettings::lookahead`
+        segment: Option<Segment>,
+        //real time since the prediction, see `StalenessGuard`
+        since_prediction: Duration
     },
     NotPropagated
 }
 
+//cubic Hermite segment from the displayed state to a prediction ahead of the simulation,
+//states are in scaled world units and per simulated second, times on the simulation clock
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Segment {
+    start: (Vec3, Vec3),
+    end: (Vec3, Vec3),
+    start_time: f64,
+    end_time: f64
+}
+
+impl Segment {
+    //past the end the motion continues in a straight line until the next prediction
+    fn state_at(&self, time: f64) -> (Vec3, Vec3) {
+        let h = (self.end_time - self.start_time) as f32;
+        let ((p0, v0), (p1, v1)) = (self.start, self.end);
+        if time >= self.end_time || h <= 0.0 {
+            return (p1 + v1 * (time - self.end_time).max(0.0) as f32, v1);
+        }
+        let s = ((time - self.start_time) as f32 / h).max(0.0);
+        let (s2, s3) = (s * s, s * s * s);
+        let position = p0 * (2.0 * s3 - 3.0 * s2 + 1.0) + v0 * h * (s3 - 2.0 * s2 + s) + p1 * (3.0 * s2 - 2.0 * s3) + v1 * h * (s3 - s2);
+        let velocity = (p0 * (6.0 * s2 - 6.0 * s) + p1 * (6.0 * s - 6.0 * s2)) / h + v0 * (3.0 * s2 - 4.0 * s + 1.0) + v1 * (3.0 * s2 - 2.0 * s);
+        (position, velocity)
+    }
+}
+
 #[derive(Component)]
-struct Velocity(Vec3);
+pub(super) struct Velocity(Vec3);
 
 impl From<[f64; 3]> for Velocity {
     fn from(value: [f64; 3]) -> Self {
@@ -147,18 +598,59 @@ impl Mul<f32> for Velocity {
 #[derive(Bundle)]
 pub struct PropagatableSattelite {
     pub elements: InGameElements,
+    pub id: SatelliteId,
     pub orbit: SatelliteOrbit,
+    pub classification: OrbitClassification,
+    pub revolutions: RevolutionCounter,
+    pub style: MarkerStyle,
     status: PropagationStatus,
-    dt_acc: PropagatableDuration
+    dt_acc: PropagatableDuration,
+    correction: PendingCorrection,
+    failures: PredictionFailures
 }
 
+/// Simulation time elapsed for the satellite, it's propagated to this many minutes since its epoch
 #[derive(Component)]
-struct PropagatableDuration(Duration);
+pub struct PropagatableDuration(pub(super) Duration);
+
+impl PropagatableDuration {
+    pub fn minutes_since_epoch(&self) -> f64 {
+        simtime::minutes(self.0)
+    }
+
+    pub fn instant(&self, elements: &Elements) -> SimInstant {
+        SimInstant::epoch(elements) + self.0
+    }
+
+    /// The same time on a scale shared by all satellites, it stays continuous when elements are refreshed and across leap seconds
+    pub fn minutes_since_j2000(&self, elements: &Elements) -> f64 {
+        self.instant(elements).minutes_since(SimInstant::J2000)
+    }
+}
+
+//remainder of a smoothed correction, applied in equal parts over the remaining frames
+#[derive(Component, Default)]
+struct PendingCorrection {
+    offset: Vec3,
+    frames_left: u32
+}
 
 impl PropagatableSattelite {
-    fn new(elements: InGameElements) -> Self {
+    pub(super) fn new(elements: InGameElements) -> Self {
+        let orbit = elements.0.as_ref().into();
+        let classification = OrbitClassification::new(&elements.0, &orbit);
+        Self::from_parts(elements, orbit, classification)
+    }
+
+    //classification computed in an earlier session, see `DerivedDataCache`
+    pub(super) fn with_classification(elements: InGameElements, classification: OrbitClassification) -> Self {
         let orbit = elements.0.as_ref().into();
-        Self { elements, orbit, status: PropagationStatus::NotPropagated, dt_acc: PropagatableDuration(Duration::ZERO) }
+        Self::from_parts(elements, orbit, classification)
+    }
+
+    fn from_parts(elements: InGameElements, orbit: SatelliteOrbit, classification: OrbitClassification) -> Self {
+        let id = SatelliteId::from(elements.0.as_ref());
+        Self { elements, id, orbit, classification, revolutions: RevolutionCounter::default(), style: MarkerStyle::default(), status: PropagationStatus::NotPropagated, dt_acc: PropagatableDuration(Duration::ZERO), correction: PendingCorrection::default(), failures: PredictionFailures::default() }
     }
 }
 
@@ -176,92 +668,203 @@ impl AddAssign<Duration> for PropagatableDuration {
     }
 }
 
-#[derive(Event)]
+#[derive(Event, Clone)]
 pub struct Propagate {
-    pub data: Vec<(Entity, InGameElements)>,
-    pub dt_minutes: f64
+    /// Satellites with the minutes since their epoch they're propagated to, the epochs and loading times differ
+    pub data: Vec<(Entity, InGameElements, f64)>,
+    /// Simulation clock time the predictions are for, when they're computed ahead of the simulation
+    pub anchor_seconds: Option<f64>
 }
 
 #[derive(Debug, Event, Clone)]
 pub struct Propageted {
-    data: Vec<(Entity, Prediction)>
+    pub(super) data: Vec<(Entity, Prediction)>,
+    //entities in `data` predicted by the numeric fallback instead of SGP4
+    pub(super) fallback: Vec<Entity>,
+    pub(super) anchor_seconds: Option<f64>
+}
+
+impl Propageted {
+    pub fn new(data: Vec<(Entity, Prediction)>) -> Self {
+        Self { data, fallback: vec![], anchor_seconds: None }
+    }
+
+    /// Marks the predictions as made for a simulation clock time ahead of the simulation
+    pub fn with_anchor(mut self, anchor_seconds: Option<f64>) -> Self {
+        self.anchor_seconds = anchor_seconds;
+        self
+    }
+
+    pub fn anchor_seconds(&self) -> Option<f64> {
+        self.anchor_seconds
+    }
+
+    /// Marks entities of the data as predicted by the numeric fallback
+    pub fn with_fallback(mut self, fallback: Vec<Entity>) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn data(&self) -> &[(Entity, Prediction)] {
+        &self.data
+    }
+
+    pub fn fallback(&self) -> &[Entity] {
+        &self.fallback
+    }
 }
 
+/// Marks satellites whose latest position comes from the numeric fallback, SGP4 failed for them
+#[derive(Component, Debug)]
+pub struct FallbackPropagated;
+
 #[derive(Resource, Default)]
 struct PropagationResults(Arc<Mutex<Vec<Propageted>>>);
 
+impl MemoryFootprint for PropagationResults {
+    fn estimated_bytes(&self) -> usize {
+        let results = self.0.lock().unwrap();
+        let batches: usize = results.iter()
+            .map(|batch| batch.data.len() * size_of::<(Entity, Prediction)>() + batch.fallback.len() * size_of::<Entity>())
+            .sum();
+        size_of::<Vec<Propageted>>() + results.len() * size_of::<Propageted>() + batches
+    }
+}
+
 #[derive(Resource)]
 struct PropagationTimer {
-    timer: Timer
+    timer: Timer,
+    //simulated seconds since the last propagation
+    pending: f64
 }
 
 impl Plugin for PropagateElementsPlugin {
     fn build(&self, app: &mut App) {
-
+        ensure_simulation_clock(app);
+        ensure_satellite_index(app);
         app
             .insert_resource(PropagationResults::default())
+            .account_resource::<PropagationResults>("propagation_results")
+            .insert_resource(FallbackStates::default())
+            .account_resource::<FallbackStates>("fallback_states")
             .add_event::<Propagate>()
             .add_event::<Propageted>()
             .add_systems(Startup, setup_propagation_timer)
             .add_systems(PreUpdate, post_loadup_predictions)
-            .add_systems(Update, (accept_propagation, send_predictions))
-            .add_systems(PostUpdate, trigger_propagation);
+            .add_systems(Update, (accept_propagation, send_predictions, forget_fallback_states))
+            .add_systems(PostUpdate, trigger_propagation.after(update_satellite_index));
     }
 }
 
 fn setup_propagation_timer(settings: Res<InGameSettings>, mut commands: Commands) {
-    commands.insert_resource(PropagationTimer { timer: Timer::from_seconds(settings.propagation.real_time_interval.as_secs_f32(), TimerMode::Repeating) });
+    commands.insert_resource(PropagationTimer { timer: Timer::from_seconds(settings.propagation.real_time_interval.as_secs_f32(), TimerMode::Repeating), pending: 0.0 });
 }
 
-fn trigger_propagation(mut propagate_events: EventWriter<Propagate>, mut timer: ResMut<PropagationTimer>, time: Res<Time>, mut elements: Query<(Entity, &InGameElements, &mut PropagatableDuration)>, settings: Res<InGameSettings>) {
+fn trigger_propagation(mut propagate_events: EventWriter<Propagate>, mut timer: ResMut<PropagationTimer>, time: Res<Time>, clock: Res<SimulationClock>, mut elements: Query<(Entity, &InGameElements, &mut PropagatableDuration, Has<PendingUnload>), Without<Despawning>>, (settings, index): (Res<InGameSettings>, Res<SatelliteIndex>)) {
 
+    //the interval may be changed at runtime, by the quality fallback
+    if settings.is_changed() {
+        timer.timer.set_duration(settings.propagation.real_time_interval);
+    }
+    //nothing is pending while paused, the next propagation after resuming is as if the pause never happened
+    if clock.paused {
+        return;
+    }
     timer.timer.tick(time.delta());
+    //the simulated time since the last propagation, not the interval times the speed, the speed may be ramping
+    timer.pending += clock.delta_seconds();
 
     if timer.timer.finished() {
-        let dt_minutes = settings.propagation.real_time_interval.as_secs_f64() * (settings.simulation_speed as f64) / 60.0;
-        let mut data = elements.iter_mut().peekable();
-
-        while let Some((_, _, duration_acc)) = data.peek_mut() {
-            *duration_acc.as_mut() += Duration::from_secs_f64(dt_minutes * 60.0);
-            let dt_minutes = duration_acc.0.as_secs_f64() / 60.0;
-            let data = data.by_ref().take(settings.propagation.batch_size).map(|(entity, d, _)| (entity, d.clone())).collect();
-            propagate_events.send(Propagate { data, dt_minutes });
+        let dt_seconds = std::mem::take(&mut timer.pending);
+        //predicting ahead, the displayed position is interpolated towards the prediction instead of lagging behind
+        let lookahead = settings.propagation.lookahead.as_secs_f64() * clock.speed();
+        let anchor_seconds = (lookahead > 0.0).then(|| clock.elapsed_seconds() + lookahead);
+        //satellites of an unloaded group keep up with the time without being propagated, so they can be restored as they were
+        for (_, _, mut duration_acc, _) in elements.iter_mut().filter(|(.., pending)| *pending) {
+            *duration_acc += Duration::from_secs_f64(dt_seconds);
+        }
+        //batches follow the index, so they're the same in every run
+        let batch_size = settings.propagation.batch_size.max(1);
+        let mut batch = Vec::with_capacity(batch_size);
+        let lookahead = Duration::from_secs_f64(anchor_seconds.map_or(0.0, |_| lookahead));
+        for entity in index.iter() {
+            let Ok((_, d, mut duration_acc, false)) = elements.get_mut(entity) else {
+                continue;
+            };
+            *duration_acc += Duration::from_secs_f64(dt_seconds);
+            batch.push((entity, d.clone(), simtime::minutes(duration_acc.0 + lookahead)));
+            if batch.len() == batch_size {
+                propagate_events.send(Propagate { data: std::mem::replace(&mut batch, Vec::with_capacity(batch_size)), anchor_seconds });
+            }
+        }
+        if !batch.is_empty() {
+            propagate_events.send(Propagate { data: batch, anchor_seconds });
         }
     }
 
 }
 
-fn accept_propagation(mut propagate_events: EventReader<Propagate>, propagations: Res<PropagationResults>) {
+fn accept_propagation(mut propagate_events: EventReader<Propagate>, propagations: Res<PropagationResults>, fallback_states: Res<FallbackStates>, settings: Res<InGameSettings>) {
     let thread_pool = AsyncComputeTaskPool::get();
+    let numeric_fallback = settings.propagation.numeric_fallback;
     for ev in propagate_events.read() {
         let elements = ev.data.clone();
-        let dt = ev.dt_minutes;
+        let anchor_seconds = ev.anchor_seconds;
         let propagations = Res::clone(&propagations);
+        let fallback_states = fallback_states.clone();
         thread_pool.scope(|s| {
             s.spawn(async move {
-                do_propagate(propagations, elements, dt);
+                do_propagate(propagations, elements, anchor_seconds, numeric_fallback.then_some(&fallback_states));
             });
         });
     }
 
 }
 
-fn do_propagate(propagations: Res<PropagationResults>, elements: Vec<(Entity, InGameElements)>, dt: f64) {
-    let data: Result<Vec<(Entity, Prediction)>, PropagationError> = elements.iter().map(|(entity, el)| {
-        let constants = sgp4::Constants::from_elements(&el.0)?;
-        let prediction = constants.propagate(MinutesSinceEpoch(dt))?;
-        Ok((entity.clone(), prediction))
-    }).collect();
+//without fallback states there's no numeric fallback
+fn do_propagate(propagations: Res<PropagationResults>, elements: Vec<(Entity, InGameElements, f64)>, anchor_seconds: Option<f64>, fallback_states: Option<&FallbackStates>) {
+    let mut data = Vec::with_capacity(elements.len());
+    let mut fallback = vec![];
+    for (entity, el, dt) in &elements {
+        match (sgp4_prediction(&el.0, *dt), fallback_states) {
+            (Ok(prediction), _) => data.push((*entity, prediction)),
+            (Err(err), Some(states)) => match states.predict(*entity, &el.0, *dt) {
+                Some(prediction) => {
+                    debug!("{}, using numeric fallback", err.report());
+                    fallback.push(*entity);
+                    data.push((*entity, prediction));
+                },
+                None => error!("{}, fallback failed as well", err.report())
+            },
+            (Err(err), None) => error!("{}", err.report())
+        }
+    }
+
+    if !data.is_empty() {
+        let mut lock = propagations.0.lock().unwrap();
+        lock.push(Propageted::new(data).with_fallback(fallback).with_anchor(anchor_seconds));
+    }
+}
+
+/// Snapshot of a satellite state at any time since its epoch, the live simulation is not touched
+pub fn predict_at(elements: &Elements, minutes_since_epoch: f64, numeric_fallback: bool) -> Option<Prediction> {
+    match sgp4_prediction(elements, minutes_since_epoch) {
+        Ok(prediction) => Some(prediction),
+        Err(_) if numeric_fallback => fallback_prediction(elements, minutes_since_epoch),
+        Err(_) => None
+    }
+}
 
-    match data {
-        Ok(data) => {
-            let mut lock = propagations.0.lock().unwrap();
-            lock.push(Propageted { data });
-        },
-        Err(err) => {
-            error!("Failed to execute propagation: {:?}", err);
-        },
+fn sgp4_prediction(elements: &Elements, dt: f64) -> Result<Prediction, SkytracioError> {
+    let norad_id = elements.norad_id;
+    if !(elements.mean_motion.is_finite() && elements.mean_motion > 0.0) {
+        return Err(SkytracioError::InvalidElements { norad_id, reason: format!("mean motion {} is not a positive number", elements.mean_motion) });
     }
+    let constants = sgp4::Constants::from_elements(elements).map_err(|source| SkytracioError::Constants { norad_id, source })?;
+    constants.propagate(MinutesSinceEpoch(dt)).map_err(|source| match source {
+        sgp4::Error::NegativeSemiLatusRectum { .. } => SkytracioError::Decayed { norad_id, minutes: dt },
+        source => SkytracioError::Propagation { norad_id, minutes: dt, source }
+    })
 }
 
 fn send_predictions(mut propagated_predictions: EventWriter<Propageted>, propagations: Res<PropagationResults>) {
@@ -272,29 +875,18 @@ fn send_predictions(mut propagated_predictions: EventWriter<Propageted>, propaga
 }
 
 //blocking, limited in scope
-fn post_loadup_predictions(mut loaded: EventReader<LoadedElements>, elements: Query<&InGameElements>, propagations: Res<PropagationResults>) {
+fn post_loadup_predictions(mut loaded: EventReader<LoadedElements>, elements: Query<&InGameElements>, propagations: Res<PropagationResults>, fallback_states: Res<FallbackStates>, settings: Res<InGameSettings>) {
     //initial propagation is a hack
     for ev in loaded.read() {
-        let data = ev.entities.iter().filter_map(|e| elements.get(*e).ok().map(|el| (*e, el.clone()))).collect();
-        do_propagate(Res::clone(&propagations), data, 0.01);
+        let data = ev.entities.iter().filter_map(|e| elements.get(*e).ok().map(|el| (*e, el.clone(), 0.01))).collect();
+        do_propagate(Res::clone(&propagations), data, None, settings.propagation.numeric_fallback.then_some(&*fallback_states));
     }
 }
 
-#[derive(Debug)]
-enum PropagationError {
-    Elements(ElementsError),
-    Propagation(sgp4::Error)
-}
-
-impl From<ElementsError> for PropagationError {
-    fn from(value: ElementsError) -> Self {
-        Self::Elements(value)
-    }
-}
-
-impl From<sgp4::Error> for PropagationError {
-    fn from(value: sgp4::Error) -> Self {
-        Self::Propagation(value)
+//satellites back on SGP4 or despawned start over from their epoch if they ever fall back again
+fn forget_fallback_states(mut recovered: RemovedComponents<FallbackPropagated>, fallback_states: Res<FallbackStates>) {
+    for entity in recovered.read() {
+        fallback_states.forget(entity);
     }
 }
 
@@ -304,45 +896,160 @@ pub struct PropagateInGamePlugin;
 
 impl Plugin for PropagateInGamePlugin {
     fn build(&self, app: &mut App) {
-
+        ensure_simulation_clock(app);
         app
-           .add_systems(Update, adjust_transaltions_on_propagation)
-           .add_systems(Update, approximate_propagation);
+           .add_event::<BecameUnreliable>()
+           .add_event::<TrackLost>()
+           .register_command(
+               CommandDescriptor::new("Set simulation speed", ActionCategory::Time, |params, world| {
+                   let Some(speed) = params[0].as_number() else {
+                       return;
+                   };
+                   if !(0.0..=MAX_SPEED).contains(&speed) {
+                       warn!("Simulation speed {speed} is not between 0 and {MAX_SPEED}, keeping the current one");
+                   } else if let Some(mut settings) = world.get_resource_mut::<InGameSettings>() {
+                       settings.simulation_speed = speed as f32;
+                   }
+               })
+               .with_param("speed", ParamKind::Number)
+           )
+           .add_systems(Update, (adjust_transaltions_on_propagation, approximate_propagation, guard_stale_predictions).chain())
+           .add_systems(Update, count_nodal_revolutions);
     }
 }
 
-fn adjust_transaltions_on_propagation(mut positions: Query<(&mut Transform, &mut PropagationStatus, &SatelliteOrbit), With<InGameElements>>, mut events: EventReader<Propageted>, settings: Res<InGameSettings>) {
+type Corrected<'a> = (
+    &'a mut Transform, &'a mut PropagationStatus, &'a mut PendingCorrection, &'a mut PredictionFailures, &'a SatelliteOrbit, &'a InGameElements,
+    Has<FallbackPropagated>, Has<Unreliable>, Has<AwaitingPrediction>
+);
+
+fn adjust_transaltions_on_propagation(
+    mut positions: Query<Corrected>,
+    mut events: EventReader<Propageted>,
+    mut unreliable: EventWriter<BecameUnreliable>,
+    settings: Res<InGameSettings>,
+    clock: Res<SimulationClock>,
+    mut commands: Commands
+) {
+    let envelope = settings.propagation.envelope;
     for propagated in events.read() {
         for (entity, prediction) in &propagated.data {
-            let Ok((mut transform, mut status, orbit)) = positions.get_mut(entity.clone()) else {
+            let Ok((mut transform, mut status, mut correction, mut failures, orbit, elements, is_fallback, is_unreliable, is_awaiting)) = positions.get_mut(*entity) else {
                 continue;
             };
 
+            //the previous good state is kept as is, dead reckoning continues from it
+            if !is_plausible_prediction(prediction, &envelope) {
+                failures.0 += 1;
+                warn!("Rejected prediction of {} ({} in a row): {:?}", elements.0.norad_id, failures.0, prediction);
+                if failures.0 == envelope.max_rejections {
+                    commands.entity(*entity).insert((Unreliable, Visibility::Hidden));
+                    unreliable.send(BecameUnreliable { entity: *entity, norad_id: elements.0.norad_id });
+                }
+                continue;
+            }
+            failures.0 = 0;
+            if is_unreliable {
+                commands.entity(*entity).remove::<Unreliable>().insert(Visibility::Inherited);
+            }
+            if is_awaiting {
+                commands.entity(*entity).remove::<AwaitingPrediction>().insert(Visibility::Inherited);
+            }
+
+            match (propagated.fallback.contains(entity), is_fallback) {
+                (true, false) => { commands.entity(*entity).insert(FallbackPropagated); },
+                (false, true) => { commands.entity(*entity).remove::<FallbackPropagated>(); },
+                _ => {}
+            }
+
             let [x, y, z] = prediction.position;
-            let translation = Vec3 {
+            let translation = WORLD_FRAME.to_world(Vec3 {
                 x: x as f32,
                 y: y as f32,
                 z: z as f32,
-            };
+            });
             debug!("Got prediction: {:?}, orbit: {:?}", prediction.position, orbit);
             debug!("Distance: {}, orbit semi-major: {:?}", translation.length(), orbit.semi_major_axis);
 
-            transform.translation = translation * settings.scale;
-            debug!("In game translaction: {}, elipse params: {:?}", transform.translation.length(), orbit.bevy_elipse_parameters(settings.scale));
+            let anchor = translation * settings.scale;
+            let anchor_velocity = WORLD_FRAME.to_world(Velocity::from(prediction.velocity).0);
+            let now = clock.elapsed_seconds();
+            //a prediction ahead of the simulation is reached gradually, the displayed position stays continuous
+            if let Some(end_time) = propagated.anchor_seconds.filter(|t| *t > now) {
+                let end = (anchor, anchor_velocity * settings.scale);
+                //the displayed state is as of the previous frame, it's carried over to the current time first
+                let start = match &*status {
+                    PropagationStatus::Propagated { segment: Some(segment), .. } => segment.state_at(now),
+                    PropagationStatus::Propagated { velocity, .. } => {
+                        let velocity = velocity.0 * settings.scale;
+                        (transform.translation + velocity * clock.delta_seconds() as f32, velocity)
+                    },
+                    //nothing displayed yet, the start is extrapolated back from the prediction
+                    PropagationStatus::NotPropagated => (anchor - end.1 * (end_time - now) as f32, end.1)
+                };
+                transform.translation = start.0;
+                *correction = PendingCorrection::default();
+                *status = PropagationStatus::Propagated {
+                    velocity: Velocity(start.1 / settings.scale),
+                    just_propagated: false,
+                    segment: Some(Segment { start, end, start_time: now, end_time }),
+                    since_prediction: Duration::ZERO
+                };
+                continue;
+            }
+            let jump = anchor - transform.translation;
+            let smoothing = settings.propagation.smoothing
+                .filter(|s| s.frames > 0 && jump.length() > s.max_jump_km * settings.scale)
+                .filter(|_| matches!(*status, PropagationStatus::Propagated { .. }));
+            //with smoothing the dead reckoning continues from the current translation and the jump is paid off gradually
+            let just_propagated = match smoothing {
+                Some(smoothing) => {
+                    *correction = PendingCorrection { offset: jump, frames_left: smoothing.frames };
+                    false
+                },
+                None => {
+                    *correction = PendingCorrection::default();
+                    transform.translation = anchor;
+                    true
+                }
+            };
+            debug!("In game translaction: {}, elipse params: {:?}", transform.translation.length(), orbit.bevy_elipse_parameters(settings.scale as f64));
             *status = PropagationStatus::Propagated {
-                velocity: prediction.velocity.into(),
-                position: translation,
-                just_propagated: true,
+                velocity: Velocity(anchor_velocity),
+                just_propagated,
+                segment: None,
+                since_prediction: Duration::ZERO
             }
         }
     }
 }
 
-fn approximate_propagation(mut satelites: Query<(&mut Transform, &mut PropagationStatus), With<InGameElements>>, time: Res<Time>, settings: Res<InGameSettings>) {
-    for (mut t, mut status) in satelites.iter_mut() {
+//satellites on their way out are left where they are
+type DeadReckoned = (With<InGameElements>, Without<Despawning>, Without<PendingUnload>);
+
+fn approximate_propagation(mut satelites: Query<(&mut Transform, &mut PropagationStatus, &mut PendingCorrection), DeadReckoned>, clock: Res<SimulationClock>, time: Res<Time>, settings: Res<InGameSettings>) {
+    //held in place, a pause doesn't make the predictions stale either
+    if clock.paused {
+        return;
+    }
+    let freeze_after = settings.propagation.real_time_interval.mul_f32(settings.propagation.staleness.freeze_after);
+    for (mut t, mut status, mut correction) in satelites.iter_mut() {
+        //the velocity of an old prediction would carry the satellite off its orbit, the position is held instead
+        if let PropagationStatus::Propagated { since_prediction, .. } = status.as_mut() {
+            *since_prediction += time.delta();
+            if *since_prediction > freeze_after {
+                continue;
+            }
+        }
 
         let velocity = match status.as_mut() {
-            PropagationStatus::Propagated { velocity, position, just_propagated } => {
+            PropagationStatus::Propagated { velocity, segment: Some(segment), .. } => {
+                let (position, segment_velocity) = segment.state_at(clock.elapsed_seconds());
+                t.translation = position;
+                velocity.0 = segment_velocity / settings.scale;
+                continue;
+            },
+            PropagationStatus::Propagated { velocity, just_propagated, .. } => {
                 if *just_propagated {
                     *just_propagated = false;
                     continue;
@@ -354,28 +1061,74 @@ fn approximate_propagation(mut satelites: Query<(&mut Transform, &mut Propagatio
             },
         };
 
-        let delta_position = velocity.0 * (settings.scale * settings.simulation_speed * time.delta_seconds());
+        let delta_position = velocity.0 * (settings.scale * clock.delta_seconds() as f32);
         t.translation += delta_position;
+
+        if correction.frames_left > 0 {
+            let step = correction.offset / correction.frames_left as f32;
+            t.translation += step;
+            correction.offset -= step;
+            correction.frames_left -= 1;
+        }
+    }
+}
+
+type Guarded<'a> = (Entity, &'a PropagationStatus, &'a InGameElements, &'a mut MarkerStyle, Has<StalePrediction>, Has<LostTrack>);
+
+//the held satellites are shown as stale, the ones left without predictions for longer can't be followed anymore
+fn guard_stale_predictions(
+    mut satelites: Query<Guarded, Without<Despawning>>,
+    mut lost: EventWriter<TrackLost>,
+    settings: Res<InGameSettings>,
+    mut commands: Commands
+) {
+    let (interval, staleness) = (settings.propagation.real_time_interval, settings.propagation.staleness);
+    for (entity, status, elements, mut style, is_stale, is_lost) in satelites.iter_mut() {
+        let since_prediction = match status {
+            PropagationStatus::Propagated { since_prediction, .. } => *since_prediction,
+            //nothing displayed yet, see `AwaitingPrediction`
+            PropagationStatus::NotPropagated => Duration::ZERO
+        };
+        match (since_prediction > interval.mul_f32(staleness.freeze_after), is_stale) {
+            (true, false) => {
+                commands.entity(entity).insert(StalePrediction);
+                style.set(StyleLayer::Staleness, StyleModifier { tint: Some(GRAY.into()), alpha: Some(0.5), ..default() });
+            },
+            (false, true) => {
+                commands.entity(entity).remove::<StalePrediction>();
+                style.clear(StyleLayer::Staleness);
+            },
+            _ => {}
+        }
+        match (since_prediction > interval.mul_f32(staleness.release_after), is_lost) {
+            (true, false) => {
+                warn!("No prediction of {} for {:.0} s, it can't be followed until the next one", elements.0.norad_id, since_prediction.as_secs_f32());
+                commands.entity(entity).insert(LostTrack);
+                lost.send(TrackLost { entity, norad_id: elements.0.norad_id });
+            },
+            (false, true) => { commands.entity(entity).remove::<LostTrack>(); },
+            _ => {}
+        }
     }
 }
 
 impl From<&sgp4::Elements> for SatelliteOrbit {
     fn from(value: &sgp4::Elements) -> Self {
         SatelliteOrbit { 
-            semi_major_axis: calculate_semi_major_axis(value.mean_motion) as f32, 
-            eccentricity: value.eccentricity as f32, 
-            inclination: value.inclination as f32, 
-            raan: value.right_ascension as f32, 
-            argument_of_perigee: value.argument_of_perigee as f32, 
+            semi_major_axis: calculate_semi_major_axis(value.mean_motion), 
+            eccentricity: value.eccentricity, 
+            inclination: value.inclination, 
+            raan: value.right_ascension, 
+            argument_of_perigee: value.argument_of_perigee, 
             true_anomaly: 0.0, 
             epoch: 0.0 
-        }
+        }.with_mean_anomaly(value.mean_anomaly)
     }
 }
 
 fn calculate_semi_major_axis(mean_motion_revs_per_day: f64) -> f64 {
     // Constants
-    const MU: f64 = 3.986004418e14; // Gravitational parameter (m^3/s^2)
+    const MU: f64 = GRAVITATIONAL_CONSTANT * 1e9; // Gravitational parameter (m^3/s^2)
     const SECONDS_PER_DAY: f64 = 86400.0;
     
     // Convert mean motion from revolutions per day to radians per second
@@ -390,121 +1143,596 @@ fn calculate_semi_major_axis(mean_motion_revs_per_day: f64) -> f64 {
 
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, sync::Arc};
+    use std::sync::Arc;
 
     use approx::assert_abs_diff_eq;
-    use bevy::{app::PanicHandlerPlugin, log::LogPlugin, prelude::*, state::app::StatesPlugin};
+    use bevy::{prelude::*, time::TimeUpdateStrategy};
     use sgp4::Elements;
     use super::*;
-    use crate::propagation::client::ConstFileClient;
+    //the tests loading the files of the assets directory
+    #[cfg(feature = "file-loader")]
+    use {std::path::PathBuf, bevy::{app::PanicHandlerPlugin, log::LogPlugin, state::app::StatesPlugin}, crate::propagation::ConstFileClient, crate::test_support::load_group_as};
+    use crate::commands::{CommandRegistry, InvokeCommand};
+    use crate::input::{Action, ActionTriggered};
+    use crate::propagation::bands::EARTH_RADIUS_KM;
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+    use crate::test_support::{app_with, assert_golden, fixture_elements, load_group, run_until, EventLog, GoldenPosition, ScriptedClient, FIXTURES, LEO};
 
     #[test]
+    #[cfg(feature = "file-loader")]
     fn test_loading_of_celestial_elements() {
+        let mut app = app_with(LoadElementsPlugin::<ConstFileClient>::new());
+        app.insert_resource(ConstFileClient::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets")));
+
+        let entities = load_group(&mut app, "galileo", 100);
+        assert!(!entities.is_empty());
+        for entity in entities {
+            let elements = &app.world().get::<InGameElements>(entity).unwrap().0;
+            let id = app.world().get::<SatelliteId>(entity).unwrap();
+            assert_eq!((id.norad_id, &id.name, &id.international_designator), (elements.norad_id, &elements.object_name, &elements.international_designator));
+            assert!(id.name.as_ref().is_some_and(|name| name.starts_with("GSAT")), "{id:?}");
+            let orbit: SatelliteOrbit = elements.as_ref().into();
+            //the orbit keeps its angles in degrees
+            assert_abs_diff_eq!(orbit.inclination, 56.0, epsilon = 8.0);
+        }
+    }
 
-        let mut app = App::new();
+    #[test]
+    #[cfg(feature = "file-loader")]
+    fn test_tle_files_load_like_json() {
+        let elements = |format: ElementsFormat| {
+            let mut app = app_with(LoadElementsPlugin::<ConstFileClient>::new());
+            app.insert_resource(ConstFileClient::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets")));
+            let mut elements: Vec<Arc<Elements>> = load_group_as(&mut app, "galileo", format, 100).iter()
+                .map(|e| app.world().get::<InGameElements>(*e).unwrap().0.clone())
+                .collect();
+            elements.sort_by_key(|el| el.norad_id);
+            elements
+        };
+        let (json, tle) = (elements(ElementsFormat::Json), elements(ElementsFormat::Tle));
+        assert_eq!(json.len(), tle.len());
+        for (json, tle) in json.iter().zip(&tle) {
+            assert_eq!((json.norad_id, &json.object_name), (tle.norad_id, &tle.object_name));
+            //the TLE columns round the epoch to 1e-8 days
+            assert_abs_diff_eq!(json.epoch(), tle.epoch(), epsilon = 1e-9);
+            let (json, tle) = (SatelliteOrbit::from(json.as_ref()), SatelliteOrbit::from(tle.as_ref()));
+            assert_abs_diff_eq!(json.semi_major_axis, tle.semi_major_axis, epsilon = 1e-3);
+            assert_abs_diff_eq!(json.inclination, tle.inclination, epsilon = 1e-4);
+            assert_abs_diff_eq!(json.true_anomaly, tle.true_anomaly, epsilon = 1e-3);
+        }
+        //the CSV fixture has three of the sets, its malformed rows are skipped
+        let csv = elements(ElementsFormat::Csv);
+        assert_eq!(csv.iter().map(|el| el.norad_id).collect::<Vec<_>>(), vec![37846, 37847, 38857]);
+        assert!(csv.iter().all(|el| json.iter().any(|json| json.norad_id == el.norad_id && json.datetime == el.datetime)));
+    }
 
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("assets");
-        let client = ConstFileClient::new(d);
+    #[test]
+    fn test_failed_load_sends_load_failed() {
+        let mut app = app_with(LoadElementsPlugin::<ScriptedClient>::new());
+        app.insert_resource(ScriptedClient::default());
+        let (mut failures, mut fetches) = (EventLog::<LoadFailed>::new(&app), EventLog::<ElementsFetched>::new(&app));
+        let mut loaded = app.world().resource::<Events<LoadedElements>>().get_reader();
+        app.world_mut().send_event(LoadElements::group("missing", ElementsFormat::Json));
+
+        let (mut failed, mut fetched) = (vec![], vec![]);
+        run_until(&mut app, |app| {
+            assert_eq!(loaded.read(app.world().resource::<Events<LoadedElements>>()).count(), 0);
+            failed.extend(failures.read(app));
+            fetched.extend(fetches.read(app));
+            !failed.is_empty()
+        }, 100);
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].error.as_ref().and_then(|err| err.group()), Some("missing"));
+        assert_eq!(failed, vec![LoadFailed { group: "missing".to_owned(), format: ElementsFormat::Json, reason: "loading missing failed: missing is not scripted".to_owned() }]);
+        assert_eq!(app.world().resource::<GroupLoadStatus>().get("missing"), Some(LoadStatus::Loaded));
+        assert!(app.world_mut().query::<&JobInExecution>().iter(app.world()).next().is_none());
+    }
 
-        app
-            .add_plugins((MinimalPlugins, StatesPlugin, LogPlugin::default(), PanicHandlerPlugin, LoadElementsPlugin::<ConstFileClient>::new()))
-            .insert_resource(client.clone());
+    #[test]
+    fn test_overlapping_groups_load_together_once() {
+        let elements = starlink_like_elements(6, 2);
+        let mut app = app_with(LoadElementsPlugin::<ScriptedClient>::new());
+        //"active" contains "galileo", reversed so the merge has to sort
+        let mut active = elements.clone();
+        active.reverse();
+        app.insert_resource(ScriptedClient::default().with_group("galileo", elements[2..4].to_vec()).with_group("active", active));
+        let (mut fetches, mut failures) = (EventLog::<ElementsFetched>::new(&app), EventLog::<LoadFailed>::new(&app));
+        let mut loaded = app.world().resource::<Events<LoadedElements>>().get_reader();
+        app.world_mut().send_event(LoadElements {
+            groups: vec!["galileo".to_owned(), "missing".to_owned(), "active".to_owned()],
+            format: ElementsFormat::Json,
+            ..default()
+        });
 
-        let mut writer = app.world_mut().resource_mut::<Events<LoadElements>>();
-        writer.send(LoadElements { group: "galileo".to_owned(), format: "JSON".to_owned() });
-        drop(writer);
-        println!("Sent event");
+        let mut events = vec![];
+        run_until(&mut app, |app| {
+            events.extend(loaded.read(app.world().resource::<Events<LoadedElements>>()).map(|ev| (ev.entities().to_vec(), ev.data().clone())));
+            !events.is_empty()
+        }, 100);
+        app.update();
+        let (entities, data) = events.remove(0);
+        let norad_ids: Vec<u64> = data.iter().map(|el| el.norad_id).collect();
+        let mut expected: Vec<u64> = elements.iter().map(|el| el.norad_id).collect();
+        expected.sort();
+        assert_eq!(norad_ids, expected);
+        assert_eq!(entities.len(), 6);
+        //the satellites in both groups belong to the first one
+        let groups: Vec<(u64, String)> = entities.iter()
+            .map(|e| (app.world().get::<InGameElements>(*e).unwrap().0.norad_id, app.world().get::<SatelliteGroup>(*e).unwrap().0.clone()))
+            .collect();
+        for (norad_id, group) in groups {
+            let in_galileo = elements[2..4].iter().any(|el| el.norad_id == norad_id);
+            assert_eq!(group, if in_galileo { "galileo" } else { "active" });
+        }
+        let received: Vec<(String, usize)> = fetches.read(&app).into_iter().map(|ev| (ev.group, ev.received)).collect();
+        assert_eq!(received, vec![("galileo".to_owned(), 2), ("missing".to_owned(), 0), ("active".to_owned(), 6)]);
+        assert_eq!(failures.read(&app).len(), 1);
+        let status = app.world().resource::<GroupLoadStatus>();
+        assert!(["galileo", "missing", "active"].iter().all(|group| status.get(group) == Some(LoadStatus::Loaded)));
+    }
 
-        let mut res = vec![];
-        for _ in 0..1000 {
-            app.update();
+    #[test]
+    #[cfg(feature = "file-loader")]
+    fn test_missing_directory_fails_once_per_request() {
+        let mut app = app_with(LoadElementsPlugin::<ConstFileClient>::new());
+        app.insert_resource(ConstFileClient::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("no-such-assets")));
+        let mut failures = EventLog::<LoadFailed>::new(&app);
+        for group in ["galileo", "gps-ops"] {
+            app.world_mut().send_event(LoadElements { groups: vec![group.to_owned()], format: ElementsFormat::Json, source: DataSource::File, launch_animation: false, refresh: false });
+        }
 
-            let result_events = app.world().resource::<Events<LoadedElements>>();
-            let mut reader = result_events.get_reader();
+        let mut failed = vec![];
+        run_until(&mut app, |app| {
+            failed.extend(failures.read(app));
+            failed.len() == 2
+        }, 100);
+        for _ in 0..10 {
+            app.update();
+            failed.extend(failures.read(&app));
+        }
+        let mut groups: Vec<_> = failed.iter().map(|failure| failure.group.as_str()).collect();
+        groups.sort();
+        assert_eq!(groups, ["galileo", "gps-ops"]);
+        assert!(failed.iter().all(|failure| failure.reason.contains("cannot access") && failure.reason.contains("no-such-assets")), "{failed:?}");
+    }
 
-            let mut read = reader.read(&result_events);
-            if let Some(elements) = read.next() {
-                res = elements.data.clone();
+    #[test]
+    fn test_propagation_logic() {
+        let mut app = app_with((LoadElementsPlugin::<ScriptedClient>::new(), PropagateElementsPlugin));
+        app.insert_resource(ScriptedClient::default().with_group("fixtures", fixture_elements()));
+        //only the propagations sent by the test
+        app.world_mut().resource_mut::<InGameSettings>().propagation.real_time_interval = Duration::from_secs(3600);
+
+        let mut propagations = EventLog::<Propageted>::new(&app);
+        let entities = load_group(&mut app, "fixtures", 100);
+        run_until(&mut app, |app| !propagations.read(app).is_empty(), 10);
+
+        let data: Vec<(Entity, InGameElements)> = entities.iter().map(|e| (*e, app.world().get::<InGameElements>(*e).unwrap().clone())).collect();
+        for (entity, elements) in &data {
+            assert_eq!(app.world().get::<SatelliteId>(*entity).map(|id| id.norad_id), Some(elements.0.norad_id));
+        }
+        let mut positions = vec![];
+        for minutes in [0.0, 30.0, 90.0] {
+            let batch = data.iter().map(|(entity, elements)| (*entity, elements.clone(), minutes)).collect();
+            app.world_mut().send_event(Propagate { data: batch, anchor_seconds: None });
+            let mut propagated = vec![];
+            run_until(&mut app, |app| {
+                propagated = propagations.read(app);
+                !propagated.is_empty()
+            }, 10);
+            assert_eq!(propagated.len(), 1);
+            for (entity, prediction) in propagated[0].data() {
+                let index = entities.iter().position(|e| e == entity).unwrap();
+                positions.push(GoldenPosition {
+                    label: data[index].1.0.object_name.clone().unwrap(),
+                    minutes,
+                    position: prediction.position,
+                    tolerance_km: FIXTURES[index].golden_tolerance_km
+                });
             }
+        }
+        assert_eq!(positions.len(), 3 * FIXTURES.len());
+        assert_golden("propagation", &positions);
+    }
+
+    #[test]
+    fn test_batches_carry_the_time_of_each_satellite() {
+        let mut app = app_with((LoadElementsPlugin::<ScriptedClient>::new(), PropagateElementsPlugin));
+        app.insert_resource(ScriptedClient::default()
+            .with_group("early", vec![LEO.builder().norad_id(70101).build()])
+            .with_group("late", vec![LEO.builder().norad_id(70102).build()]));
+
+        let early = load_group(&mut app, "early", 10)[0];
+        for _ in 0..20 {
+            app.update();
+        }
+        let late = load_group(&mut app, "late", 10)[0];
+        let mut batches = EventLog::<Propagate>::new(&app);
+        let mut batch = vec![];
+        run_until(&mut app, |app| {
+            batch = batches.read(app).into_iter().flat_map(|propagate| propagate.data).collect();
+            !batch.is_empty()
+        }, 10);
+
+        //both groups in one batch, each propagated to its own time
+        let minutes: Vec<(Entity, f64)> = batch.iter().map(|(entity, _, minutes)| (*entity, *minutes)).collect();
+        let own_minutes = |entity: Entity| app.world().get::<PropagatableDuration>(entity).unwrap().minutes_since_epoch();
+        assert_eq!(minutes, vec![(early, own_minutes(early)), (late, own_minutes(late))]);
+        assert!(minutes[0].1 > minutes[1].1, "{minutes:?}");
+    }
+
+    #[test]
+    fn test_speed_command_rejects_negative_and_huge_speeds() {
+        let mut app = App::new();
+        app.add_plugins(PropagateInGamePlugin).insert_resource(InGameSettings::default());
+        let mut set_speed = |speed: &str| {
+            let registry = app.world().resource::<CommandRegistry>().clone();
+            registry.invoke(&InvokeCommand { name: "Set simulation speed".to_owned(), arguments: vec![speed.to_owned()] }, app.world_mut()).unwrap();
+            app.world().resource::<InGameSettings>().simulation_speed
         };
+        assert_eq!(set_speed("600"), 600.0);
+        assert_eq!(set_speed("-1"), 600.0);
+        assert_eq!(set_speed("1e20"), 600.0);
+        assert_eq!(set_speed("0"), 0.0);
+    }
+
+    #[test]
+    fn test_numeric_fallback_when_sgp4_fails() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, PropagateElementsPlugin, PropagateInGamePlugin))
+            .add_event::<LoadedElements>()
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { real_time_interval: Duration::from_secs(3600), numeric_fallback: true, ..default() },
+                ..default()
+            });
+
+        //negative eccentricity is rejected by SGP4, the fallback still has a usable near-circular orbit
+        let elements = LEO.builder().eccentricity(-0.0001).build();
+        assert!(sgp4::Constants::from_elements(&elements).is_err());
 
-        println!("{:?}", display_elements(&res));
+        let elements = InGameElements(elements);
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(elements.clone()), Transform::default())).id();
+        app.world_mut().send_event(Propagate { data: vec![(entity, elements, 30.0)], anchor_seconds: None });
 
-        for elems in &res {
-            let orbit: SatelliteOrbit = elems.as_ref().into();
-            assert_abs_diff_eq!(orbit.inclination, 56.0f32.to_radians(), epsilon = 8.0f32.to_radians());
+        for _ in 0..10 {
+            app.update();
         }
 
-        assert!(!res.is_empty());
+        let translation = app.world().get::<Transform>(entity).unwrap().translation;
+        assert!(translation.is_finite());
+        assert_abs_diff_eq!(translation.length() / 0.01, 6796.0, epsilon = 50.0);
+        assert!(app.world().get::<FallbackPropagated>(entity).is_some());
+        assert_eq!(app.world().resource::<FallbackStates>().len(), 1);
+
+        app.world_mut().despawn(entity);
+        app.update();
+        assert_eq!(app.world().resource::<FallbackStates>().len(), 0);
     }
 
     #[test]
-    fn test_propagation_logic() {
+    fn test_large_corrections_are_smoothed() {
         let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, PropagateInGamePlugin))
+            .add_event::<Propageted>()
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { real_time_interval: Duration::from_secs(3600), smoothing: Some(CorrectionSmoothing { max_jump_km: 100.0, frames: 5 }), ..default() },
+                ..default()
+            });
 
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("assets");
-        let client = ConstFileClient::new(d);
+        let elements = LEO.elements();
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements)), Transform::default())).id();
+        let propagate_to = |app: &mut App, position: [f64; 3]| {
+            app.world_mut().send_event(Propageted::new(vec![(entity, Prediction { position, velocity: [0.0; 3] })]));
+            app.update();
+            app.world().get::<Transform>(entity).unwrap().translation
+        };
+
+        //the first prediction always snaps
+        let translation = propagate_to(&mut app, [7000.0, 0.0, 0.0]);
+        assert_abs_diff_eq!(translation.x, 70.0, epsilon = 1e-4);
+
+        let translation = propagate_to(&mut app, [7000.0, 500.0, 0.0]);
+        assert_abs_diff_eq!(translation.y, 1.0, epsilon = 1e-4);
+        let mut frames = 1;
+        while app.world().get::<Transform>(entity).unwrap().translation.y < 5.0 - 1e-4 {
+            app.update();
+            frames += 1;
+            assert!(frames <= 5);
+        }
+        assert_eq!(frames, 5);
 
+        //small corrections still snap
+        let translation = propagate_to(&mut app, [7000.0, 550.0, 0.0]);
+        assert_abs_diff_eq!(translation.y, 5.5, epsilon = 1e-4);
+    }
+
+    //largest distance of the displayed position from SGP4 evaluated at the simulation time of the frame, in km
+    fn displayed_error(lookahead: Duration) -> f32 {
+        let mut app = App::new();
         app
-            .add_plugins((MinimalPlugins, StatesPlugin, LogPlugin::default(), PanicHandlerPlugin, LoadElementsPlugin::<ConstFileClient>::new(), PropagateElementsPlugin))
-            .insert_resource(client.clone());
+            .add_plugins((MinimalPlugins, PropagateElementsPlugin, PropagateInGamePlugin))
+            .add_event::<LoadedElements>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(50)))
+            .insert_resource(InGameSettings {
+                simulation_speed: 30.0,
+                propagation: PropagationSettings {
+                    real_time_interval: Duration::from_secs(1), batch_size: 10, numeric_fallback: false, smoothing: None,
+                    envelope: PredictionEnvelope::default(),
+                    lookahead, staleness: StalenessGuard::default()
+                },
+                ..default()
+            });
 
-        let mut writer = app.world_mut().resource_mut::<Events<LoadElements>>();
-        writer.send(LoadElements { group: "galileo".to_owned(), format: "JSON".to_owned() });
-        drop(writer);
+        let elements: Arc<Elements> = starlink_like_elements(1, 1475).pop().unwrap();
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements.clone())), Transform::default())).id();
+        let mut worst: f32 = 0.0;
+        for frame in 0..200 {
+            app.update();
+            //the first predictions are still settling in
+            if frame < 60 {
+                continue;
+            }
+            let minutes = app.world().resource::<SimulationClock>().elapsed_seconds() / 60.0;
+            let expected = predict_at(&elements, minutes, false).unwrap().position.map(|c| c as f32);
+            let displayed = app.world().get::<Transform>(entity).unwrap().translation / 0.01;
+            worst = worst.max(displayed.distance(WORLD_FRAME.to_world(Vec3::from_array(expected))));
+        }
+        worst
+    }
 
-        let mut res = vec![];
-        for _ in 0..1000 {
+    #[test]
+    fn test_lookahead_compensates_latency() {
+        let lagging = displayed_error(Duration::ZERO);
+        let compensated = displayed_error(Duration::from_secs(1));
+        assert!(lagging > 1.0, "{lagging}");
+        assert!(compensated * 10.0 < lagging, "{compensated} vs {lagging}");
+    }
+
+    #[test]
+    fn test_nan_predictions_mark_satellite_unreliable() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, PropagateElementsPlugin, PropagateInGamePlugin))
+            .add_event::<LoadedElements>()
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { real_time_interval: Duration::from_secs(3600), envelope: PredictionEnvelope { max_rejections: 3, ..default() }, ..default() },
+                ..default()
+            });
+
+        let elements = LEO.elements();
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements.clone())), Transform::default())).id();
+        let push = |app: &mut App, position: [f64; 3]| {
+            let results = app.world().resource::<PropagationResults>().0.clone();
+            results.lock().unwrap().push(Propageted::new(vec![(entity, Prediction { position, velocity: [0.0, 7.5, 0.0] })]));
+            //draining the queue and applying the event are not ordered, one of the frames may only forward it
+            app.update();
             app.update();
+        };
+        let mut unreliable_reader = app.world().resource::<Events<BecameUnreliable>>().get_reader();
+
+        push(&mut app, [6800.0, 0.0, 0.0]);
+        for i in 1..=3 {
+            assert!(app.world().get::<Unreliable>(entity).is_none(), "marked after {} rejections", i - 1);
+            push(&mut app, [f64::NAN, 0.0, 0.0]);
+            let translation = app.world().get::<Transform>(entity).unwrap().translation;
+            assert!(translation.is_finite());
+            assert_abs_diff_eq!(translation.x, 68.0, epsilon = 1.0);
+        }
+        assert!(app.world().get::<Unreliable>(entity).is_some());
+        let events: Vec<_> = unreliable_reader.read(app.world().resource::<Events<BecameUnreliable>>()).cloned().collect();
+        assert_eq!(events, vec![BecameUnreliable { entity, norad_id: elements.norad_id }]);
 
-            let result_events = app.world().resource::<Events<LoadedElements>>();
-            let mut reader = result_events.get_reader();
+        //a good prediction restores the satellite
+        push(&mut app, [6800.0, 10.0, 0.0]);
+        assert!(app.world().get::<Unreliable>(entity).is_none());
+    }
 
-            let mut read = reader.read(&result_events);
-            if let Some(elements) = read.next() {
-                res = elements.data.clone();
-            }
+    #[test]
+    fn test_starved_satellite_is_held_then_released() {
+        //predictions every 500 ms, held after 1.5 s and released after 5 s without one
+        let mut app = app_with(PropagateInGamePlugin);
+        app.add_event::<Propageted>();
+        let elements = starlink_like_elements(1, 1508).pop().unwrap();
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements.clone())), Transform::default())).id();
+        let prediction = predict_at(&elements, 0.0, false).unwrap();
+        app.world_mut().send_event(Propageted::new(vec![(entity, prediction)]));
+        app.update();
+        let mut lost = EventLog::<TrackLost>::new(&app);
+        let translation = |app: &App| app.world().get::<Transform>(entity).unwrap().translation;
+        let followable = |app: &mut App| app.world_mut().query_filtered::<&Transform, (Without<Unreliable>, Without<LostTrack>)>().get(app.world(), entity).is_ok();
+
+        //dead reckoning until the prediction is stale
+        let before = translation(&app);
+        app.update();
+        assert_ne!(translation(&app), before);
+        let held = run_until(&mut app, |app| app.world().get::<StalePrediction>(entity).is_some(), 20);
+        assert!((13..=16).contains(&held), "{held}");
+        let frozen = translation(&app);
+        for _ in 0..10 {
+            app.update();
+            assert_eq!(translation(&app), frozen);
+        }
+        let style = app.world().get::<MarkerStyle>(entity).unwrap();
+        assert!(style.get(StyleLayer::Staleness).is_some());
+        assert!(followable(&mut app));
+        assert!(lost.read(&app).is_empty());
+
+        run_until(&mut app, |app| app.world().get::<LostTrack>(entity).is_some(), 40);
+        assert!(!followable(&mut app));
+        assert_eq!(translation(&app), frozen);
+        assert_eq!(lost.read(&app), vec![TrackLost { entity, norad_id: elements.norad_id }]);
+
+        //the next prediction brings it back
+        app.world_mut().send_event(Propageted::new(vec![(entity, predict_at(&elements, 1.0, false).unwrap())]));
+        app.update();
+        app.update();
+        assert!(app.world().get::<StalePrediction>(entity).is_none());
+        assert!(followable(&mut app));
+        assert!(app.world().get::<MarkerStyle>(entity).unwrap().get(StyleLayer::Staleness).is_none());
+    }
+
+    #[test]
+    fn test_paused_satellites_resume_where_they_stopped() {
+        let mut app = app_with((PropagateElementsPlugin, PropagateInGamePlugin));
+        app.add_event::<LoadedElements>();
+        app.world_mut().resource_mut::<InGameSettings>().simulation_speed = 60.0;
+        let elements = starlink_like_elements(1, 1509).pop().unwrap();
+        let entity = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements.clone())), Transform::default())).id();
+        let translation = |app: &App| app.world().get::<Transform>(entity).unwrap().translation;
+        let duration = |app: &App| app.world().get::<PropagatableDuration>(entity).unwrap().0;
+        let sgp4_error = |app: &App| {
+            let minutes = app.world().resource::<SimulationClock>().elapsed_seconds() / 60.0;
+            let expected = WORLD_FRAME.to_world(Vec3::from_array(predict_at(&elements, minutes, false).unwrap().position.map(|c| c as f32)));
+            expected.distance(translation(app) / 0.01)
         };
+        for _ in 0..20 {
+            app.update();
+        }
+        let before = translation(&app);
+        app.update();
+        let step = translation(&app).distance(before);
+        assert!(step > 0.0);
+        //without lookahead the displayed position lags behind SGP4 by up to an interval
+        let lag = sgp4_error(&app);
+
+        app.world_mut().send_event(ActionTriggered(Action::TogglePause));
+        app.update();
+        assert!(app.world().resource::<SimulationClock>().paused);
+        //a prediction triggered before the pause may still arrive
+        app.update();
+        app.update();
+        let (held, propagated, elapsed) = (translation(&app), duration(&app), app.world().resource::<SimulationClock>().elapsed_seconds());
+        //longer than the satellites are released after without predictions
+        for _ in 0..80 {
+            app.update();
+            assert_eq!(translation(&app), held);
+            assert_eq!(duration(&app), propagated);
+        }
+        assert_eq!(app.world().resource::<SimulationClock>().elapsed_seconds(), elapsed);
+        assert!(app.world().get::<StalePrediction>(entity).is_none());
 
-        let mut data = vec![];
-        for elements in &res {
-            let elements = InGameElements(elements.clone());
-            let entity = app.world_mut().spawn(elements.clone());
-            data.push((entity.id(), elements));
+        app.world_mut().send_event(ActionTriggered(Action::TogglePause));
+        let mut previous = held;
+        for _ in 0..20 {
+            app.update();
+            let moved = translation(&app).distance(previous);
+            assert!(moved < step * 2.0, "{moved} after a step of {step}");
+            previous = translation(&app);
         }
-        let mut writer = app.world_mut().resource_mut::<Events<Propagate>>();
-        writer.send(Propagate { data, dt_minutes: 30.0 });
+        //the pause is left out of the simulated time, without a catch up the lag stays about the same
+        let error = sgp4_error(&app);
+        assert!(error < lag * 2.0, "{error} km after {lag} km");
+    }
+
+    #[cfg(feature = "file-loader")]
+    #[derive(Component)]
+    struct HookMarker(u64);
+
+    #[test]
+    #[cfg(feature = "file-loader")]
+    fn test_spawn_hook_runs_for_every_satellite() {
+        let mut app = App::new();
+
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("assets");
+        let client = ConstFileClient::new(d);
+
+        let plugin = LoadElementsPlugin::<ConstFileClient>::new()
+            .with_spawn_hook(|elements, entity| { entity.insert(HookMarker(elements.norad_id)); });
+        app
+            .add_plugins((MinimalPlugins, StatesPlugin, LogPlugin::default(), PanicHandlerPlugin, plugin))
+            .insert_resource(client);
+
+        app.world_mut().send_event(LoadElements::group("galileo", ElementsFormat::Json));
 
-        let mut res: Option<Propageted> = None;
+        let mut spawned_reader = app.world().resource::<Events<SatelliteSpawned>>().get_reader();
+        let mut loaded_reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        let mut spawned = vec![];
+        let mut loaded = vec![];
         for _ in 0..1000 {
             app.update();
-            let result_events = app.world().resource::<Events<Propageted>>();
-            let mut reader = result_events.get_reader();
-            let mut read = reader.read(&result_events);
-            if let Some(propageted) = read.next() {
-                res = Some(propageted.clone());
+            spawned.extend(spawned_reader.read(app.world().resource::<Events<SatelliteSpawned>>()).cloned());
+            loaded.extend(loaded_reader.read(app.world().resource::<Events<LoadedElements>>()).map(|ev| ev.entities().to_vec()));
+            if !loaded.is_empty() {
+                break;
             }
         }
 
-        if let Some(res) = res {
-            println!("{:?}", res);
-        } else {
-            panic!("Failed no event");
+        let entities = loaded.concat();
+        assert!(!entities.is_empty());
+        assert_eq!(spawned.len(), entities.len());
+        for ev in &spawned {
+            let marker = app.world().get::<HookMarker>(ev.entity).expect("hook marker must be present");
+            assert_eq!(marker.0, ev.norad_id);
         }
     }
 
-    fn display_elements(elements: &Vec<Arc<Elements>>) -> String {
-        let res: Vec<_> = elements.iter().map(|els| format!("object_name={:?},international_designator={:?},norad_id={},classification={:?},datetime={:?},inclination={}", els.object_name, els.international_designator, els.norad_id, display_clasification(&els), els.datetime, els.inclination)).collect();
-        res.join("\n")
+    #[test]
+    fn test_events_built_through_public_api() {
+        let mut app = App::new();
+        app.add_event::<Propageted>().add_event::<LoadedElements>();
+        let (a, b) = (app.world_mut().spawn_empty().id(), app.world_mut().spawn_empty().id());
+        let prediction = |x: f64| Prediction { position: [x, 0.0, 0.0], velocity: [0.0, 7.5, 0.0] };
+
+        app.world_mut().send_event(Propageted::new(vec![(a, prediction(7000.0)), (b, prediction(7100.0))]).with_fallback(vec![b]));
+        app.world_mut().send_event(LoadedElements::new(vec![a], vec![]));
+        let propagated = app.world().resource::<Events<Propageted>>();
+        let read: Vec<_> = propagated.get_reader().read(propagated).cloned().collect();
+        assert_eq!(read.len(), 1);
+        let data: Vec<_> = read[0].data().iter().map(|(e, p)| (*e, p.position[0])).collect();
+        assert_eq!(data, vec![(a, 7000.0), (b, 7100.0)]);
+        assert_eq!(read[0].fallback(), &[b]);
+
+        let loaded = app.world().resource::<Events<LoadedElements>>();
+        let (entities, data): (Vec<_>, Vec<_>) = loaded.get_reader().read(loaded).map(|ev| (ev.entities().to_vec(), ev.data().len())).unzip();
+        assert_eq!(entities, vec![vec![a]]);
+        assert_eq!(data, vec![0]);
     }
 
-    fn display_clasification(elem: &Elements) -> String {
-        match elem.classification {
-            sgp4::Classification::Unclassified => "unclassified".to_owned(),
-            sgp4::Classification::Classified => "classified".to_owned(),
-            sgp4::Classification::Secret => "secret".to_owned(),
+    fn spawn_fixture(placement: SpawnPlacement) -> (App, Vec<Entity>, Vec<Arc<Elements>>) {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<SyntheticClient>::new()))
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .insert_resource(placement)
+            .insert_resource(SyntheticClient(starlink_like_elements(300, 1473)))
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { batch_size: 100, ..default() },
+                ..default()
+            });
+        app.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+
+        let mut reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        for _ in 0..100 {
+            app.update();
+            let loaded = reader.read(app.world().resource::<Events<LoadedElements>>()).next().map(|ev| (ev.entities().to_vec(), ev.data().to_vec()));
+            if let Some((entities, elements)) = loaded {
+                //the visuals are inserted in the next PreUpdate
+                app.update();
+                return (app, entities, elements);
+            }
         }
+        panic!("fixture never loaded");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unpropagated_satellites_placed_at_epoch() {
+        let (app, entities, elements) = spawn_fixture(SpawnPlacement::Epoch);
+        assert_eq!(entities.len(), 300);
+        for (entity, el) in entities.iter().zip(&elements) {
+            let translation = app.world().get::<Transform>(*entity).unwrap().translation / 0.01;
+            assert!(translation.length() > EARTH_RADIUS_KM, "{} spawned inside the Earth: {}", el.norad_id, translation);
+
+            let prediction = predict_at(el, 0.0, false).unwrap();
+            let expected = WORLD_FRAME.to_world(Vec3::from_array(prediction.position.map(|c| c as f32)));
+            assert!(translation.distance(expected) < 50.0, "{}: {} vs SGP4 {}", el.norad_id, translation, expected);
+            assert_eq!(app.world().get::<Visibility>(*entity), Some(&Visibility::Inherited));
+        }
+
+        let (app, entities, _) = spawn_fixture(SpawnPlacement::Hidden);
+        assert!(entities.iter().all(|e| app.world().get::<Visibility>(*e) == Some(&Visibility::Hidden)));
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use sgp4::Elements;
+
+use crate::orbit::{SatelliteOrbit, GRAVITATIONAL_CONSTANT};
+
+pub trait ElementsExt {
+    /// Orbital period straight from the mean motion (revolutions per day)
+    fn period_minutes(&self) -> f64;
+    /// Semi-major axis from Kepler's third law on the mean motion (in kilometers)
+    fn semi_major_axis_km(&self) -> f64;
+}
+
+impl ElementsExt for Elements {
+    fn period_minutes(&self) -> f64 {
+        MINUTES_PER_DAY / self.mean_motion
+    }
+
+    fn semi_major_axis_km(&self) -> f64 {
+        let mean_motion_rad_per_sec = self.mean_motion * std::f64::consts::TAU / (MINUTES_PER_DAY * 60.0);
+        (GRAVITATIONAL_CONSTANT / mean_motion_rad_per_sec.powi(2)).cbrt()
+    }
+}
+
+/// Periods further apart than this (in seconds) point at elements the Keplerian conversion handles badly
+pub const PERIOD_MISMATCH_SECONDS: f64 = 10.0;
+
+const MINUTES_PER_DAY: f64 = 1440.0;
+//km^3/s^2
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrbitClass {
+    Leo,
+    Meo,
+    Geo,
+    Heo
+}
+
+impl OrbitClass {
+    //thresholds in minutes, GEO is a window around the sidereal day
+    const LEO_MAX_PERIOD: f64 = 128.0;
+    const GEO_MIN_PERIOD: f64 = 1400.0;
+    const GEO_MAX_PERIOD: f64 = 1480.0;
+    const HEO_MIN_ECCENTRICITY: f64 = 0.25;
+
+    pub fn classify(period_minutes: f64, eccentricity: f64) -> Self {
+        if eccentricity >= Self::HEO_MIN_ECCENTRICITY || period_minutes > Self::GEO_MAX_PERIOD {
+            Self::Heo
+        } else if period_minutes < Self::LEO_MAX_PERIOD {
+            Self::Leo
+        } else if period_minutes < Self::GEO_MIN_PERIOD {
+            Self::Meo
+        } else {
+            Self::Geo
+        }
+    }
+}
+
+/// Classification of a loaded satellite, keeps both period estimates since their
+/// discrepancy is a useful data quality indicator
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct OrbitClassification {
+    pub class: OrbitClass,
+    /// Period derived from the element set mean motion
+    pub period_minutes: f64,
+    /// Period recomputed from the converted [`SatelliteOrbit`] semi-major axis
+    pub orbit_period_minutes: f64
+}
+
+impl OrbitClassification {
+    pub fn new(elements: &Elements, orbit: &SatelliteOrbit) -> Self {
+        let period_minutes = elements.period_minutes();
+        Self {
+            class: OrbitClass::classify(period_minutes, elements.eccentricity),
+            period_minutes,
+            orbit_period_minutes: orbit.period_minutes()
+        }
+    }
+
+    pub fn period_discrepancy_seconds(&self) -> f64 {
+        (self.orbit_period_minutes - self.period_minutes) * 60.0
+    }
+
+    pub fn has_period_mismatch(&self) -> bool {
+        self.period_discrepancy_seconds().abs() > PERIOD_MISMATCH_SECONDS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::test_support::{GEO, LEO};
+
+    #[test]
+    fn test_geo_period_from_elements() {
+        let geo = GEO.builder().mean_motion(1.00273791).build();
+        assert_abs_diff_eq!(geo.period_minutes(), 1436.07, epsilon = 0.1);
+
+        let orbit: SatelliteOrbit = (&*geo).into();
+        let classification = OrbitClassification::new(&geo, &orbit);
+        assert_eq!(classification.class, OrbitClass::Geo);
+        //the converted orbit agrees within a few seconds, the elements value is the reference
+        assert!(classification.period_discrepancy_seconds().abs() < 5.0);
+    }
+
+    #[test]
+    fn test_period_mismatch() {
+        let leo = LEO.builder().mean_motion(15.5).build();
+        let orbit: SatelliteOrbit = (&*leo).into();
+        assert!(!OrbitClassification::new(&leo, &orbit).has_period_mismatch());
+
+        //an orbit left over from older elements, a minute slower
+        let stale: SatelliteOrbit = (&*LEO.builder().mean_motion(15.33).build()).into();
+        let classification = OrbitClassification::new(&leo, &stale);
+        assert!(classification.has_period_mismatch());
+        assert_abs_diff_eq!(classification.period_discrepancy_seconds(), 61.8, epsilon = 0.5);
+    }
+
+    #[test]
+    fn test_classification_of_fixtures() {
+        for (mean_motion, eccentricity, expected) in [
+            (15.5, 0.0005, OrbitClass::Leo),
+            (1.70475826, 0.0003, OrbitClass::Meo),
+            (2.0056, 0.01, OrbitClass::Meo),
+            (1.00273791, 0.0002, OrbitClass::Geo),
+            (2.006, 0.72, OrbitClass::Heo),
+        ] {
+            let elements = GEO.builder().mean_motion(mean_motion).eccentricity(eccentricity).build();
+            let orbit: SatelliteOrbit = (&*elements).into();
+            assert_eq!(OrbitClassification::new(&elements, &orbit).class, expected);
+            assert_abs_diff_eq!(elements.period_minutes(), orbit.period_minutes(), epsilon = 0.1);
+        }
+    }
+}
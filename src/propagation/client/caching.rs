@@ -0,0 +1,187 @@
+use std::{fs, path::PathBuf, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use bevy::{log::{info, warn}, prelude::Resource};
+
+use crate::error::SkytracioError;
+
+use super::{DataSource, ElementsFormat, ElementsStream, EpochDataLoader, OrbitalData, PartialSets};
+
+pub(super) type CacheKey = (DataSource, String, ElementsFormat);
+//the sets and when they were fetched
+pub(super) type Cached = (OrbitalData, SystemTime);
+
+/// Directory of the fetched sets, `<directory>/<group>.<format>.json` (the group prefixed with the source outside of GP)
+/// with a sidecar holding the fetch time in seconds since the Unix epoch
+#[derive(Clone, Debug)]
+pub(super) struct CacheDirectory(PathBuf);
+
+impl CacheDirectory {
+    pub(super) fn new(directory: PathBuf) -> Self {
+        Self(directory)
+    }
+
+    //the sidecar and the sets
+    pub(super) fn files(&self, (source, group, format): &CacheKey) -> (PathBuf, PathBuf) {
+        let stem = match source {
+            DataSource::Gp => group.clone(),
+            source => format!("{}-{group}", source.label())
+        };
+        let stem: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        let data = self.0.join(format!("{stem}.{}.json", format.label().to_ascii_lowercase()));
+        (data.with_extension("json.fetched"), data)
+    }
+
+    //a missing, corrupt or partially written file is a miss
+    pub(super) fn read(&self, key: &CacheKey) -> Option<Cached> {
+        let (sidecar, data) = self.files(key);
+        let fetched = UNIX_EPOCH + Duration::from_secs(fs::read_to_string(sidecar).ok()?.trim().parse().ok()?);
+        let mut stream = ElementsStream::default();
+        let parsed = fs::read(&data).map_err(|err| SkytracioError::io(&data, err))
+            .and_then(|bytes| stream.feed(&bytes))
+            .and_then(|elements| stream.finish().map(|_| elements));
+        match parsed {
+            Ok(elements) => Some((elements.into_iter().map(Arc::new).collect(), fetched)),
+            Err(err) => {
+                warn!("Ignoring the cached {}: {}", data.display(), err.report());
+                None
+            }
+        }
+    }
+
+    //the sidecar is written last, without it the sets are never read
+    pub(super) fn write(&self, key: &CacheKey, elements: &OrbitalData) -> Result<(), SkytracioError> {
+        let (sidecar, data) = self.files(key);
+        fs::create_dir_all(&self.0).map_err(|err| SkytracioError::io(&self.0, err))?;
+        match fs::remove_file(&sidecar) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(SkytracioError::io(&sidecar, err)),
+            _ => {}
+        }
+        let elements: Vec<&sgp4::Elements> = elements.iter().map(Arc::as_ref).collect();
+        let json = serde_json::to_vec(&elements).map_err(|err| SkytracioError::io(&data, err.into()))?;
+        fs::write(&data, json).map_err(|err| SkytracioError::io(&data, err))?;
+        let fetched = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs();
+        fs::write(&sidecar, fetched.to_string()).map_err(|err| SkytracioError::io(&sidecar, err))
+    }
+}
+
+/// Loader keeping every set `C` loaded on disk, in the files of [`super::DefaultClient::with_cache_dir`].
+/// When `C` fails a load gets the sets of an earlier run however old they are, a refresh still fails
+#[derive(Clone, Debug, Resource)]
+pub struct CachingClient<C> {
+    inner: C,
+    directory: CacheDirectory
+}
+
+impl <C> CachingClient<C> {
+    pub fn new(inner: C, directory: PathBuf) -> Self {
+        Self { inner, directory: CacheDirectory::new(directory) }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl <C: EpochDataLoader> CachingClient<C> {
+    fn keep(&self, key: CacheKey, loaded: Result<OrbitalData, C::Error>, fall_back: bool) -> Result<OrbitalData, SkytracioError> {
+        let err = match loaded {
+            Ok(data) => {
+                if let Err(err) = self.directory.write(&key, &data) {
+                    warn!("Failed to cache {}: {}", key.1, err.report());
+                }
+                return Ok(data);
+            },
+            Err(err) => err.into()
+        };
+        let Some((data, fetched)) = self.directory.read(&key).filter(|_| fall_back) else {
+            return Err(err);
+        };
+        let age = fetched.elapsed().unwrap_or(Duration::ZERO).as_secs_f64() / 3600.0;
+        info!("Loading {} failed, using the sets cached {age:.1} h ago: {}", key.1, err.report());
+        Ok(data)
+    }
+}
+
+#[async_trait::async_trait]
+impl <C: EpochDataLoader + Send + Sync> EpochDataLoader for CachingClient<C> {
+    type Error = SkytracioError;
+
+    async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        let loaded = self.inner.load(group.clone(), format).await;
+        self.keep((DataSource::Gp, group, format), loaded, true)
+    }
+
+    async fn load_from(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        let loaded = self.inner.load_from(source, group.clone(), format).await;
+        self.keep((source, group, format), loaded, true)
+    }
+
+    async fn load_streamed(&self, source: DataSource, group: String, format: ElementsFormat, partial: PartialSets) -> Result<OrbitalData, Self::Error> {
+        let loaded = self.inner.load_streamed(source, group.clone(), format, partial).await;
+        self.keep((source, group, format), loaded, true)
+    }
+
+    async fn refresh_from(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        let loaded = self.inner.refresh_from(source, group.clone(), format).await;
+        self.keep((source, group, format), loaded, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::futures_lite::future::block_on;
+
+    use super::*;
+    use crate::test_support::{fixture_elements, ScriptedClient};
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("skytracio-caching-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn norad_ids(data: &OrbitalData) -> Vec<u64> {
+        data.iter().map(|el| el.norad_id).collect()
+    }
+
+    #[test]
+    fn test_failed_loads_fall_back_to_the_cached_sets() {
+        let directory = directory("fallback");
+        let online = CachingClient::new(ScriptedClient::default().with_group("fixtures", fixture_elements()), directory.clone());
+        let loaded = block_on(online.load_group(DataSource::Gp, "fixtures".to_owned(), ElementsFormat::Json)).unwrap();
+        let key = (DataSource::Gp, "fixtures".to_owned(), ElementsFormat::Json);
+        let (sidecar, data) = online.directory.files(&key);
+        assert!(sidecar.exists() && data.exists());
+
+        //the next run is offline
+        let offline = CachingClient::new(ScriptedClient::default(), directory.clone());
+        let cached = block_on(offline.load_group(DataSource::Gp, "fixtures".to_owned(), ElementsFormat::Json)).unwrap();
+        assert_eq!(norad_ids(&cached), norad_ids(&loaded));
+        //keyed by the source and the format too
+        assert!(block_on(offline.load_from(DataSource::Supplemental, "fixtures".to_owned(), ElementsFormat::Json)).is_err());
+        assert!(block_on(offline.load("fixtures".to_owned(), ElementsFormat::Tle)).is_err());
+        let refreshed = block_on(offline.refresh_group(DataSource::Gp, "fixtures".to_owned(), ElementsFormat::Json)).unwrap_err();
+        assert_eq!(refreshed.report(), "loading fixtures failed: fixtures is not scripted");
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let directory = directory("corrupt");
+        let online = CachingClient::new(ScriptedClient::default().with_group("fixtures", fixture_elements()), directory.clone());
+        block_on(online.load("fixtures".to_owned(), ElementsFormat::Json)).unwrap();
+        let (sidecar, data) = online.directory.files(&(DataSource::Gp, "fixtures".to_owned(), ElementsFormat::Json));
+        let json = fs::read(&data).unwrap();
+
+        let offline = CachingClient::new(ScriptedClient::default(), directory.clone());
+        let load = || block_on(offline.load("fixtures".to_owned(), ElementsFormat::Json));
+        fs::write(&data, &json[..json.len() / 2]).unwrap();
+        assert_eq!(load().unwrap_err().to_string(), "fixtures is not scripted");
+        fs::write(&data, b"[{\"OBJECT_NAME\": 7}]").unwrap();
+        assert!(load().is_err());
+        fs::write(&data, &json).unwrap();
+        fs::write(&sidecar, "yesterday").unwrap();
+        assert!(load().is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use bevy::log::warn;
+use serde_json::{Map, Value};
+
+use super::OrbitalData;
+
+//columns kept as text, every other one is a number
+const TEXT_COLUMNS: [&str; 4] = ["OBJECT_NAME", "OBJECT_ID", "EPOCH", "CLASSIFICATION_TYPE"];
+
+/// Element sets of an OMM CSV text
+#[derive(Debug, Default)]
+pub struct CsvElements {
+    pub elements: OrbitalData,
+    /// Rows with a missing or malformed field, skipped with a warning
+    pub skipped: usize
+}
+
+impl CsvElements {
+    /// The element sets, with a warning counting the skipped rows of the group
+    pub fn into_elements(self, group: &str) -> OrbitalData {
+        if self.skipped > 0 {
+            warn!("Skipped {} malformed rows of {group}, loaded {}", self.skipped, self.elements.len());
+        }
+        self.elements
+    }
+}
+
+/// Element sets of a CSV text as Celestrak serves it, a header with the OMM field names (`OBJECT_NAME`, `NORAD_CAT_ID`,
+/// `EPOCH`, `MEAN_MOTION`...) and a record per line. Every record is read as the same OMM record in JSON would be
+pub fn parse_csv(text: &str) -> CsvElements {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return CsvElements::default();
+    };
+    let header: Vec<String> = split_fields(header).iter().map(|column| column.trim().to_ascii_uppercase()).collect();
+    let mut parsed = CsvElements::default();
+    for (record, line) in lines.enumerate() {
+        match parse_record(&header, line) {
+            Ok(elements) => parsed.elements.push(Arc::new(elements)),
+            Err(reason) => {
+                warn!("Skipping record {record} of the CSV data, {reason}");
+                parsed.skipped += 1;
+            }
+        }
+    }
+    parsed
+}
+
+fn parse_record(header: &[String], line: &str) -> Result<sgp4::Elements, String> {
+    let fields = split_fields(line);
+    if fields.len() != header.len() {
+        return Err(format!("it has {} fields for {} columns", fields.len(), header.len()));
+    }
+    let mut record = Map::new();
+    for (column, value) in header.iter().zip(fields) {
+        let value = value.trim();
+        //left out, the deserialization tells whether the field is required
+        if value.is_empty() {
+            continue;
+        }
+        let value = if TEXT_COLUMNS.contains(&column.as_str()) {
+            Value::from(value)
+        } else if let Ok(integer) = value.parse::<u64>() {
+            Value::from(integer)
+        } else {
+            value.parse::<f64>().ok().filter(|number| number.is_finite()).map(Value::from)
+                .ok_or_else(|| format!("{column} is not a number ({value})"))?
+        };
+        record.insert(column.clone(), value);
+    }
+    serde_json::from_value(Value::Object(record)).map_err(|err| err.to_string())
+}
+
+//fields of a line, quoted fields may hold commas and doubled quotes
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c)
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_rows_are_skipped() {
+        let parsed = parse_csv(&std::fs::read_to_string("assets/data/galileo.csv").unwrap());
+        assert_eq!(parsed.skipped, 2);
+        let norad_ids: Vec<u64> = parsed.elements.iter().map(|el| el.norad_id).collect();
+        assert_eq!(norad_ids, vec![37846, 37847, 38857]);
+        //the same sets as in JSON
+        let json: Vec<sgp4::Elements> = serde_json::from_str(&std::fs::read_to_string("assets/data/galileo.json").unwrap()).unwrap();
+        let first = json.iter().find(|el| el.norad_id == 37846).unwrap();
+        let csv = &parsed.elements[0];
+        assert_eq!(csv.object_name, first.object_name);
+        assert_eq!(csv.international_designator, first.international_designator);
+        assert_eq!(csv.datetime, first.datetime);
+        assert_eq!((csv.mean_motion, csv.eccentricity, csv.inclination), (first.mean_motion, first.eccentricity, first.inclination));
+        assert_eq!((csv.right_ascension, csv.argument_of_perigee, csv.mean_anomaly), (first.right_ascension, first.argument_of_perigee, first.mean_anomaly));
+        assert_eq!((csv.drag_term, csv.mean_motion_dot, csv.revolution_number), (first.drag_term, first.mean_motion_dot, first.revolution_number));
+        assert_eq!(parsed.into_elements("galileo").len(), 3);
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        assert_eq!(split_fields(r#"a,"b, c","say ""hi""",,"#), vec!["a", "b, c", "say \"hi\"", "", ""]);
+        assert_eq!(parse_csv("").elements.len(), 0);
+        assert_eq!(parse_csv("OBJECT_NAME,NORAD_CAT_ID\n").skipped, 0);
+    }
+}
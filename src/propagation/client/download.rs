@@ -0,0 +1,328 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::log::{debug, warn};
+use sgp4::Elements;
+
+use crate::error::SkytracioError;
+
+use super::network::http_error;
+use super::{ElementsStream, OrbitalData, PartialSets};
+
+//bytes read from the response between writes to the partial file
+const CHUNK: usize = 64 * 1024;
+
+/// Download of a body into `<name>.part`, resumed from the bytes already there when the server honors ranges.
+/// `<name>.part.meta` keeps the length and the validator (ETag or Last-Modified) of the body being downloaded.
+/// The body is parsed as it arrives, so only the set being received is buffered. With [`ResumableDownload::with_partial_sets`]
+/// every parsed set is handed over right away, [`ResumableDownload::finish`] returns them all once the whole body is in
+pub struct ResumableDownload {
+    url: String,
+    body: PathBuf,
+    meta: PathBuf,
+    stream: ElementsStream,
+    elements: Vec<Arc<Elements>>,
+    partial: Option<PartialSets>,
+    received: u64,
+    total: Option<u64>,
+    validator: Option<String>
+}
+
+impl ResumableDownload {
+    /// Picks up a partial body left by an earlier run, it's parsed again so the element sets are complete
+    pub fn new(url: String, directory: &Path, name: &str) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let mut download = Self {
+            url,
+            body: directory.join(format!("{name}.part")),
+            meta: directory.join(format!("{name}.part.meta")),
+            stream: ElementsStream::default(),
+            elements: vec![],
+            partial: None,
+            received: 0,
+            total: None,
+            validator: None
+        };
+        if let Err(err) = download.reparse() {
+            debug!("Discarding the partial download of {}: {}", download.url, err.report());
+            download.restart()?;
+        }
+        Ok(download)
+    }
+
+    fn reparse(&mut self) -> Result<(), SkytracioError> {
+        let meta = match fs::read_to_string(&self.meta) {
+            Ok(meta) => meta,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(SkytracioError::io(&self.meta, err))
+        };
+        let mut lines = meta.lines();
+        self.total = lines.next().and_then(|total| total.parse().ok());
+        self.validator = lines.next().filter(|v| !v.is_empty()).map(str::to_owned);
+        let mut file = File::open(&self.body).map_err(|err| SkytracioError::io(&self.body, err))?;
+        let mut chunk = vec![0; CHUNK];
+        loop {
+            let read = file.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.accept(&chunk[..read])?;
+        }
+    }
+
+    /// Hands the sets over as they're parsed, those of the partial body picked up by `new` first.
+    /// A body downloaded again from the start hands its sets over again
+    pub fn with_partial_sets(mut self, partial: PartialSets) -> Self {
+        partial.push(self.elements.iter().cloned());
+        self.partial = Some(partial);
+        self
+    }
+
+    fn accept(&mut self, bytes: &[u8]) -> Result<(), SkytracioError> {
+        let parsed: Vec<_> = self.stream.feed(bytes)?.into_iter().map(Arc::new).collect();
+        if let Some(partial) = self.partial.as_ref().filter(|_| !parsed.is_empty()) {
+            partial.push(parsed.iter().cloned());
+        }
+        self.elements.extend(parsed);
+        self.received += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        self.stream = ElementsStream::default();
+        self.elements.clear();
+        (self.received, self.total, self.validator) = (0, None, None);
+        for path in [&self.body, &self.meta] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes of the body on disk
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Requests the rest of the body, what arrives before a dropped connection stays on disk for the next attempt
+    pub fn attempt(&mut self) -> Result<(), SkytracioError> {
+        if self.is_complete() {
+            return Ok(());
+        }
+        let mut request = ureq::get(&self.url);
+        if self.received > 0 {
+            request = request.set("Range", &format!("bytes={}-", self.received));
+            if let Some(validator) = &self.validator {
+                request = request.set("If-Range", validator);
+            }
+        }
+        let response = match request.call() {
+            //the partial body doesn't fit the current one, it starts over
+            Err(ureq::Error::Status(416, _)) => {
+                self.restart()?;
+                return Err(SkytracioError::Incomplete { expected: None, received: 0 });
+            },
+            response => response.map_err(|err| http_error(&self.url, err))?
+        };
+        match response.status() {
+            206 => {
+                let start = response.header("Content-Range").and_then(content_range);
+                match start {
+                    Some((start, total)) if start == self.received => self.total = total.or(self.total),
+                    _ => {
+                        warn!("Unusable range response from {}, downloading from the start", self.url);
+                        self.restart()?;
+                        return Err(SkytracioError::Incomplete { expected: None, received: 0 });
+                    }
+                }
+            },
+            //ranges aren't supported (or the body changed), the full body replaces the partial one
+            _ => {
+                if self.received > 0 {
+                    debug!("{} sent the full body instead of the rest, discarding {} bytes", self.url, self.received);
+                }
+                self.restart()?;
+                self.total = response.header("Content-Length").and_then(|length| length.parse().ok());
+                self.validator = response.header("ETag").or(response.header("Last-Modified")).map(str::to_owned);
+            }
+        }
+        fs::write(&self.meta, format!("{}\n{}\n", self.total.map_or(String::new(), |t| t.to_string()), self.validator.as_deref().unwrap_or("")))?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.body)?;
+        let mut reader = response.into_reader();
+        let mut chunk = vec![0; CHUNK];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    file.flush()?;
+                    return Err(err.into());
+                }
+            };
+            file.write_all(&chunk[..read])?;
+            if let Err(err) = self.accept(&chunk[..read]) {
+                //a body that isn't an element array won't get better by resuming
+                self.restart()?;
+                return Err(err);
+            }
+        }
+        file.flush()?;
+        if !self.is_complete() {
+            return Err(SkytracioError::Incomplete { expected: self.total, received: self.received });
+        }
+        Ok(())
+    }
+
+    //by the length when the server told it, the end of the array is the only sign otherwise
+    fn is_complete(&self) -> bool {
+        match self.total {
+            Some(total) => self.received == total,
+            None => self.received > 0 && self.stream.finish().is_ok()
+        }
+    }
+
+    /// Element sets of the complete body, the partial files are removed
+    pub fn finish(mut self) -> Result<OrbitalData, SkytracioError> {
+        if !self.is_complete() {
+            return Err(SkytracioError::Incomplete { expected: self.total, received: self.received });
+        }
+        let parsed = self.stream.finish();
+        let elements = std::mem::take(&mut self.elements);
+        self.restart()?;
+        parsed?;
+        Ok(elements)
+    }
+}
+
+//start and total of `bytes <start>-<end>/<total>`, the total may be `*`
+fn content_range(header: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = header.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use crate::error::ParseError;
+    use crate::test_support::LEO;
+
+    use super::*;
+
+    fn body(count: u64) -> String {
+        let elements: Vec<_> = (0..count)
+            .map(|i| LEO.builder().name(format!("STREAM-{i}")).norad_id(40000 + i).mean_anomaly(i as f64).json())
+            .collect();
+        format!("[\n  {}\n]\n", elements.join(",\n  "))
+    }
+
+    #[test]
+    fn test_streaming_parse_of_truncated_then_completed_body() {
+        let body = body(5);
+        for split in [1, 7, body.len() / 3, body.len() / 2, body.len() - 3] {
+            let mut stream = ElementsStream::default();
+            let first = stream.feed(&body.as_bytes()[..split]).unwrap();
+            assert!(matches!(stream.finish(), Err(SkytracioError::Incomplete { .. })));
+            let rest = stream.feed(&body.as_bytes()[split..]).unwrap();
+            let ids: Vec<_> = first.iter().chain(&rest).map(|el| el.norad_id).collect();
+            assert_eq!(ids, (40000..40005).collect::<Vec<_>>(), "split at {split}");
+            stream.finish().unwrap();
+        }
+
+        let mut stream = ElementsStream::default();
+        assert!(stream.feed(b"[]").unwrap().is_empty());
+        stream.finish().unwrap();
+        assert!(matches!(ElementsStream::default().feed(b"{\"a\": 1}"), Err(SkytracioError::Parse { record: Some(0), offset: Some(0), source: ParseError::UnexpectedByte(b'{'), .. })));
+        assert!(matches!(ElementsStream::default().feed(b"[{\"a\": 1}]"), Err(SkytracioError::Parse { offset: Some(1), source: ParseError::Json(_), .. })));
+    }
+
+    //serves the scripted responses one connection each, records the requests
+    fn serve(responses: Vec<Vec<u8>>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/gp.php", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for (response, stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                while reader.read_line(&mut request).unwrap() > 2 {}
+                recorded.lock().unwrap().push(request);
+                //closing the connection right after the response, mid-body for the dropped ones
+                let _ = stream.write_all(&response);
+            }
+        });
+        (url, requests)
+    }
+
+    fn response(status: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+        for (name, value) in headers {
+            response += &format!("{name}: {value}\r\n");
+        }
+        let mut response = (response + "\r\n").into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("skytracio-download-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_dropped_download_resumes() {
+        let body = body(40);
+        let (bytes, half) = (body.as_bytes(), body.len() / 2);
+        let (url, requests) = serve(vec![
+            response("200 OK", &[("Content-Length", bytes.len().to_string()), ("Accept-Ranges", "bytes".to_owned()), ("ETag", "\"v1\"".to_owned())], &bytes[..half]),
+            response("206 Partial Content", &[
+                ("Content-Length", (bytes.len() - half).to_string()),
+                ("Content-Range", format!("bytes {half}-{}/{}", bytes.len() - 1, bytes.len()))
+            ], &bytes[half..])
+        ]);
+        let directory = directory("resume");
+        let mut download = ResumableDownload::new(url.clone(), &directory, "gp").unwrap();
+        assert!(matches!(download.attempt(), Err(SkytracioError::Io { .. })));
+        assert_eq!(download.received(), half as u64);
+
+        //a new run picks up the partial body from the disk
+        let mut download = ResumableDownload::new(url, &directory, "gp").unwrap();
+        assert_eq!(download.received(), half as u64);
+        download.attempt().unwrap();
+        let elements = download.finish().unwrap();
+        assert_eq!(elements.iter().map(|el| el.norad_id).collect::<Vec<_>>(), (40000..40040).collect::<Vec<_>>());
+
+        let requests = requests.lock().unwrap();
+        assert!(!requests[0].to_lowercase().contains("range:"));
+        assert!(requests[1].contains(&format!("bytes={half}-")), "{}", requests[1]);
+        assert!(requests[1].contains("\"v1\""), "{}", requests[1]);
+        assert!(!directory.join("gp.part").exists());
+    }
+
+    #[test]
+    fn test_full_download_without_range_support() {
+        let body = body(10);
+        let (bytes, cut) = (body.as_bytes(), body.len() - 10);
+        let (url, _) = serve(vec![
+            response("200 OK", &[("Content-Length", bytes.len().to_string())], &bytes[..cut]),
+            response("200 OK", &[("Content-Length", bytes.len().to_string())], bytes)
+        ]);
+        let mut download = ResumableDownload::new(url, &directory("full"), "gp").unwrap();
+        assert!(download.attempt().is_err());
+        download.attempt().unwrap();
+        assert_eq!(download.finish().unwrap().len(), 10);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use bevy::prelude::Resource;
+
+use crate::error::SkytracioError;
+
+use super::{parse_csv, parse_tles, ElementsFormat, ElementsStream, EpochDataLoader, OrbitalData};
+
+#[derive(Clone, Debug, Resource)]
+pub struct ConstFileClient {
+    top_path: PathBuf
+}
+
+impl ConstFileClient {
+    pub fn new(top_path: PathBuf) -> Self {
+        Self { top_path }
+    }
+}
+
+#[async_trait::async_trait]
+impl EpochDataLoader for ConstFileClient {
+    type Error = SkytracioError;
+
+    async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error>  {
+        //TLE files are the text files of Celestrak or Space-Track, with or without the name lines
+        let extension = match format {
+            ElementsFormat::Json => "json",
+            ElementsFormat::Tle => "tle",
+            ElementsFormat::Csv => "csv"
+        };
+
+        let mut path = self.top_path.clone();
+        path.push("data");
+        path.push(format!("{}.{}", group, extension));
+        let bytes = fs::read(&path).map_err(|err| SkytracioError::io(&path, err))?;
+        match format {
+            ElementsFormat::Tle => parse_tles(&String::from_utf8_lossy(&bytes)),
+            ElementsFormat::Csv => Ok(parse_csv(&String::from_utf8_lossy(&bytes)).into_elements(&group)),
+            ElementsFormat::Json => {
+                //streamed to report the record that doesn't parse
+                let mut stream = ElementsStream::default();
+                let data = stream.feed(&bytes)?;
+                stream.finish()?;
+                Ok(data.into_iter().map(Arc::new).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bevy::tasks::futures_lite::future::block_on;
+
+    use super::*;
+    use crate::error::ParseError;
+    use crate::propagation::{DataSource, EpochDataLoader};
+
+    #[test]
+    fn test_errors_name_the_file_and_the_record() {
+        let top = std::env::temp_dir().join(format!("skytracio-file-errors-{}", std::process::id()));
+        fs::create_dir_all(top.join("data")).unwrap();
+        let valid = fs::read_to_string("assets/data/galileo.json").unwrap();
+        let first = valid.trim().trim_start_matches('[').split("},").next().unwrap().to_owned() + "}";
+        fs::write(top.join("data/broken.json"), format!("[{first}, {{\"OBJECT_NAME\": 7}}]")).unwrap();
+        let client = ConstFileClient::new(top.clone());
+
+        let err = block_on(client.load_group(DataSource::File, "broken".to_owned(), ElementsFormat::Json)).unwrap_err();
+        assert_eq!(err.group(), Some("broken"));
+        let Some(SkytracioError::Parse { record: Some(1), offset: Some(_), source: ParseError::Json(_), .. }) = err.source().and_then(|source| source.downcast_ref()) else {
+            panic!("{err:?}");
+        };
+        assert!(err.report().starts_with("loading broken failed: record 1 of the JSON data at byte "), "{}", err.report());
+        let json = err.source().unwrap().source().unwrap();
+        assert!(json.downcast_ref::<serde_json::Error>().is_some());
+
+        let missing = block_on(client.load("missing".to_owned(), ElementsFormat::Json)).unwrap_err();
+        assert!(missing.to_string().starts_with("cannot access ") && missing.to_string().ends_with("missing.json"), "{missing}");
+        fs::remove_dir_all(top).unwrap();
+    }
+
+    #[test]
+    fn test_tle_files_name_the_malformed_block() {
+        let top = std::env::temp_dir().join(format!("skytracio-tle-errors-{}", std::process::id()));
+        fs::create_dir_all(top.join("data")).unwrap();
+        let valid = fs::read_to_string("assets/data/galileo.tle").unwrap();
+        let sets: Vec<&str> = valid.lines().take(6).collect();
+        let broken = format!("{}\n{}\n{}\nGSAT-BROKEN\n1 99999U garbage\n{}\n", sets[0], sets[1], sets[2], sets[5]);
+        fs::write(top.join("data/broken.tle"), broken).unwrap();
+
+        let client = ConstFileClient::new(top.clone());
+        let err = block_on(client.load("broken".to_owned(), ElementsFormat::Tle)).unwrap_err();
+        let SkytracioError::Parse { record: Some(1), offset: Some(offset), source: ParseError::Tle { block, .. }, .. } = &err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(*offset, sets[..3].iter().map(|line| line.len() + 1).sum::<usize>());
+        assert_eq!(block, &format!("GSAT-BROKEN\n1 99999U garbage\n{}", sets[5]));
+        assert!(err.to_string().contains("(GSAT-BROKEN | 1 99999U garbage | 2 "), "{err}");
+        assert!(err.source().unwrap().downcast_ref::<sgp4::TleError>().is_some());
+        fs::remove_dir_all(top).unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt::Debug, str::FromStr, sync::{Arc, Mutex}};
+
+use bevy::{log::error, prelude::Resource};
+
+use crate::error::{ParseError, SkytracioError};
+
+#[cfg(feature = "network")]
+mod network;
+#[cfg(feature = "network")]
+mod download;
+#[cfg(feature = "file-loader")]
+mod file;
+mod stream;
+mod caching;
+mod csv;
+
+#[cfg(feature = "network")]
+pub use network::{DefaultClient, RetryPolicy};
+#[cfg(feature = "network")]
+pub use download::ResumableDownload;
+#[cfg(feature = "file-loader")]
+pub use file::ConstFileClient;
+pub use stream::ElementsStream;
+pub use caching::CachingClient;
+pub use csv::{parse_csv, CsvElements};
+
+//need to wrap in ARC
+pub type OrbitalData = Vec<Arc<sgp4::Elements>>;
+
+const CELESTRAK_ELEMENTS: &str = "https://celestrak.com/NORAD/elements";
+
+/// Where a load takes its element sets from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DataSource {
+    /// Celestrak GP groups
+    #[default]
+    Gp,
+    /// Celestrak supplemental sets, derived from operator ephemerides
+    Supplemental,
+    /// Local files
+    File,
+    /// Inserted programmatically, not loaded from anywhere
+    Injected
+}
+
+impl DataSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DataSource::Gp => "GP",
+            DataSource::Supplemental => "supplemental",
+            DataSource::File => "file",
+            DataSource::Injected => "injected"
+        }
+    }
+}
+
+/// Format of the element sets a load asks for, names of other formats fail to parse instead of reaching a loader
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ElementsFormat {
+    /// Array of OMM records
+    #[default]
+    Json,
+    /// Two-line element sets, optionally with a name line before each
+    Tle,
+    /// OMM records as comma separated values with a header, smaller than JSON
+    Csv
+}
+
+impl ElementsFormat {
+    /// Name of the format in the Celestrak API
+    pub fn label(&self) -> &'static str {
+        match self {
+            ElementsFormat::Json => "JSON",
+            ElementsFormat::Tle => "TLE",
+            ElementsFormat::Csv => "CSV"
+        }
+    }
+}
+
+/// Case insensitive [`ElementsFormat::label`]
+impl FromStr for ElementsFormat {
+    type Err = SkytracioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "JSON" => Ok(ElementsFormat::Json),
+            "TLE" => Ok(ElementsFormat::Tle),
+            "CSV" => Ok(ElementsFormat::Csv),
+            _ => Err(SkytracioError::UnsupportedFormat(s.trim().to_owned()))
+        }
+    }
+}
+
+/// Celestrak URL of the group for the source, `None` for sources not served by Celestrak
+pub fn celestrak_url(source: DataSource, group: &str, format: ElementsFormat) -> Option<String> {
+    elements_url(CELESTRAK_ELEMENTS, source, group, format)
+}
+
+//URL of the group under the elements directory of a Celestrak API, Celestrak itself or a mirror
+pub(super) fn elements_url(base: &str, source: DataSource, group: &str, format: ElementsFormat) -> Option<String> {
+    let format = format.label();
+    match source {
+        DataSource::Gp => Some(format!("{base}/gp.php?GROUP={group}&FORMAT={format}")),
+        //supplemental sets are published per file, not per group
+        DataSource::Supplemental => Some(format!("{base}/supplemental/sup-gp.php?FILE={group}&FORMAT={format}")),
+        DataSource::File | DataSource::Injected => None
+    }
+}
+
+/// Element sets of a TLE text, every set with a name line (3LE) before it or without one (2LE).
+/// A set that doesn't parse fails the whole text, the error has its index, offset and lines
+pub fn parse_tles(text: &str) -> Result<OrbitalData, SkytracioError> {
+    let mut lines = text.split_inclusive('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some((start, line.trim_end()))
+        })
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let mut elements = vec![];
+    while let Some((offset, first)) = lines.next() {
+        //Space-Track prefixes the name lines with 0
+        let name = (!first.starts_with("1 ")).then(|| first.strip_prefix("0 ").unwrap_or(first).trim().to_owned());
+        let line1 = match name {
+            Some(_) => lines.next().map_or("", |(_, line)| line),
+            None => first
+        };
+        let line2 = lines.next_if(|(_, line)| line.starts_with("2 ")).map_or("", |(_, line)| line);
+        let parsed = sgp4::Elements::from_tle(name.clone(), line1.as_bytes(), line2.as_bytes()).map_err(|source| {
+            let block = [name.as_deref().map(|_| first), Some(line1), Some(line2)].into_iter().flatten().filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n");
+            SkytracioError::Parse { format: "TLE".to_owned(), record: Some(elements.len()), offset: Some(offset), source: ParseError::Tle { block, source } }
+        })?;
+        elements.push(Arc::new(parsed));
+    }
+    Ok(elements)
+}
+
+/// Sets of a group handed over by the loader before the whole group is in, see [`EpochDataLoader::load_streamed`].
+/// The loading job spawns them while the rest is on its way
+#[derive(Clone, Debug, Default)]
+pub struct PartialSets(Arc<Mutex<OrbitalData>>);
+
+impl PartialSets {
+    pub fn push(&self, sets: impl IntoIterator<Item = Arc<sgp4::Elements>>) {
+        self.0.lock().unwrap().extend(sets);
+    }
+
+    /// Sets pushed since the previous call
+    pub fn take(&self) -> OrbitalData {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EpochDataLoader {
+    /// Converted into a [`SkytracioError`] by `load_group` and `load_or_empty`
+    type Error: Debug + Into<SkytracioError>;
+    async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error>;
+    /// Loaders knowing several sources override this one, by default every source is the same
+    async fn load_from(&self, _source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.load(group, format).await
+    }
+    /// `load_from` with the error wrapped in [`SkytracioError::Load`] of the group
+    async fn load_group(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, SkytracioError> {
+        self.load_from(source, group.clone(), format).await.map_err(|er| SkytracioError::Load { group, source: Box::new(er.into()) })
+    }
+    /// `load_from` pushing the sets to `partial` as they're parsed. Loaders streaming their bodies override this one,
+    /// by default the sets only come with the result. The result has every set, the pushed ones included
+    async fn load_streamed(&self, source: DataSource, group: String, format: ElementsFormat, _partial: PartialSets) -> Result<OrbitalData, Self::Error> {
+        self.load_from(source, group, format).await
+    }
+    /// `load_streamed` with the error wrapped like in `load_group`
+    async fn load_group_streamed(&self, source: DataSource, group: String, format: ElementsFormat, partial: PartialSets) -> Result<OrbitalData, SkytracioError> {
+        self.load_streamed(source, group.clone(), format, partial).await.map_err(|er| SkytracioError::Load { group, source: Box::new(er.into()) })
+    }
+    /// Loaders keeping what they fetched override this one to fetch again, by default it's `load_from`
+    async fn refresh_from(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.load_from(source, group, format).await
+    }
+    /// `refresh_from` with the error wrapped like in `load_group`
+    async fn refresh_group(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, SkytracioError> {
+        self.refresh_from(source, group.clone(), format).await.map_err(|er| SkytracioError::Load { group, source: Box::new(er.into()) })
+    }
+    async fn load_or_empty(&self, source: DataSource, group: String, format: ElementsFormat) -> OrbitalData {
+        self.load_group(source, group, format).await.unwrap_or_else(|er| {
+            error!("{}", er.report());
+            vec![]
+        })
+    }
+}
+
+/// Loader of apps inserting their elements programmatically, every load fails with the requested source.
+/// The default client of builds without the `file-loader` feature
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct InjectedOnly;
+
+#[async_trait::async_trait]
+impl EpochDataLoader for InjectedOnly {
+    type Error = DataSource;
+
+    async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.load_from(DataSource::Gp, group, format).await
+    }
+
+    async fn load_from(&self, source: DataSource, _group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        Err(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_url_per_source() {
+        assert_eq!(celestrak_url(DataSource::Gp, "galileo", ElementsFormat::Json).unwrap(), "https://celestrak.com/NORAD/elements/gp.php?GROUP=galileo&FORMAT=JSON");
+        assert_eq!(
+            celestrak_url(DataSource::Supplemental, "starlink", ElementsFormat::Json).unwrap(),
+            "https://celestrak.com/NORAD/elements/supplemental/sup-gp.php?FILE=starlink&FORMAT=JSON"
+        );
+        assert_eq!(celestrak_url(DataSource::Gp, "starlink", ElementsFormat::Csv).unwrap(), "https://celestrak.com/NORAD/elements/gp.php?GROUP=starlink&FORMAT=CSV");
+        assert_eq!(celestrak_url(DataSource::File, "galileo", ElementsFormat::Json), None);
+        assert_eq!(celestrak_url(DataSource::Injected, "galileo", ElementsFormat::Json), None);
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(" json ".parse::<ElementsFormat>().unwrap(), ElementsFormat::Json);
+        assert_eq!("TLE".parse::<ElementsFormat>().unwrap(), ElementsFormat::Tle);
+        assert_eq!("csv".parse::<ElementsFormat>().unwrap(), ElementsFormat::Csv);
+        let err = "garbage".parse::<ElementsFormat>().unwrap_err();
+        assert!(matches!(&err, SkytracioError::UnsupportedFormat(format) if format == "garbage"));
+        assert_eq!(err.to_string(), "garbage is not a supported elements format");
+    }
+}
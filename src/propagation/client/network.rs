@@ -0,0 +1,500 @@
+use std::{collections::HashMap, path::PathBuf, sync::{Arc, Mutex, RwLock}, time::{Duration, SystemTime}};
+
+use bevy::{log::{debug, info, warn}, prelude::Resource, tasks::{IoTaskPool, TaskPool}};
+
+use crate::error::{ParseError, SkytracioError};
+
+use super::caching::{CacheDirectory, CacheKey, Cached};
+use super::download::ResumableDownload;
+use super::{elements_url, parse_csv, parse_tles, DataSource, ElementsFormat, EpochDataLoader, OrbitalData, PartialSets, CELESTRAK_ELEMENTS};
+
+//age of the cached sets still served without a request
+const CACHE_TTL: Duration = Duration::from_secs(2 * 3600);
+
+/// How often a failing request is repeated. The delay doubles after every attempt, a download resumes the partial body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 3, base_delay: Duration::from_millis(500) }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the attempt following `attempt` (counted from 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+    }
+
+    /// Dropped connections and server errors may pass on the next attempt, a rejected request (4xx) won't
+    pub fn is_retryable(error: &SkytracioError) -> bool {
+        match error {
+            SkytracioError::Http { status: Some(status), .. } => *status >= 500,
+            SkytracioError::Http { status: None, .. } | SkytracioError::Incomplete { .. } => true,
+            //reading the body, local files have a path
+            SkytracioError::Io { path: None, .. } => true,
+            _ => false
+        }
+    }
+
+    //the last error once the attempts are exhausted
+    fn run<T>(&self, what: &str, mut attempt: impl FnMut() -> Result<T, SkytracioError>) -> Result<T, SkytracioError> {
+        let mut attempts = 1;
+        loop {
+            match attempt() {
+                Err(err) if attempts < self.max_attempts && Self::is_retryable(&err) => {
+                    let delay = self.delay(attempts);
+                    warn!("{what} failed, attempt {attempts} of {}, retrying in {}ms: {}", self.max_attempts, delay.as_millis(), err.report());
+                    std::thread::sleep(delay);
+                    attempts += 1;
+                },
+                result => return result
+            }
+        }
+    }
+}
+type KeyLocks = Arc<Mutex<HashMap<CacheKey, Arc<Mutex<()>>>>>;
+
+/// `Status` answers keep their code, transport failures their source
+pub(super) fn http_error(url: &str, error: ureq::Error) -> SkytracioError {
+    match error {
+        ureq::Error::Status(status, response) => SkytracioError::Http { url: url.to_owned(), status: Some(status), message: response.status_text().to_owned(), source: None },
+        ureq::Error::Transport(transport) => SkytracioError::Http { url: url.to_owned(), status: None, message: transport.kind().to_string(), source: Some(Box::new(transport)) }
+    }
+}
+
+#[derive(Clone, Resource)]
+pub struct DefaultClient {
+    cache: Arc<RwLock<HashMap<CacheKey, Cached>>>,
+    //one load of a key at a time, a concurrent one waits for it and gets the cached sets
+    loading: KeyLocks,
+    downloads: PathBuf,
+    api: String,
+    cache_dir: Option<CacheDirectory>,
+    ttl: Duration,
+    retry: RetryPolicy
+}
+
+impl Default for DefaultClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultClient {
+    pub fn new() -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(HashMap::default())),
+            loading: Arc::new(Mutex::new(HashMap::default())),
+            downloads: std::env::temp_dir().join("skytracio-downloads"),
+            api: CELESTRAK_ELEMENTS.to_owned(),
+            cache_dir: None,
+            ttl: CACHE_TTL,
+            retry: RetryPolicy::default()
+        }
+    }
+
+    /// Keeps the fetched sets in `<directory>/<group>.<format>.json` (the group prefixed with the source outside of GP),
+    /// a later run loads them from there while they're younger than the TTL
+    pub fn with_cache_dir(mut self, directory: PathBuf) -> Self {
+        self.cache_dir = Some(CacheDirectory::new(directory));
+        self
+    }
+
+    /// Age of the fetched sets, in memory and on disk, after which a load fetches them again. 2 hours by default
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Elements directory of the Celestrak API the groups are fetched from, Celestrak's own by default
+    pub fn with_api(mut self, url: String) -> Self {
+        self.api = url;
+        self
+    }
+
+    //a stale file is a miss too
+    fn read_cached(&self, key: &CacheKey) -> Option<Cached> {
+        let (data, fetched) = self.cache_dir.as_ref()?.read(key)?;
+        let age = SystemTime::now().duration_since(fetched).unwrap_or(Duration::ZERO);
+        if age > self.ttl {
+            debug!("Cached {} is {}s old, fetching again", key.1, age.as_secs());
+            return None;
+        }
+        Some((data, fetched))
+    }
+
+    fn write_cached(&self, key: &CacheKey, elements: &OrbitalData) -> Result<(), SkytracioError> {
+        match &self.cache_dir {
+            Some(directory) => directory.write(key, elements),
+            None => Ok(())
+        }
+    }
+
+    /// Directory of partial downloads, a load interrupted in one run is resumed in the next
+    pub fn with_download_directory(mut self, directory: PathBuf) -> Self {
+        self.downloads = directory;
+        self
+    }
+
+    fn download(&self, url: String, name: &str, partial: Option<PartialSets>) -> Result<OrbitalData, SkytracioError> {
+        let mut download = ResumableDownload::new(url, &self.downloads, name).map_err(|err| SkytracioError::io(&self.downloads, err))?;
+        if let Some(partial) = partial {
+            download = download.with_partial_sets(partial);
+        }
+        self.retry.run(&format!("Download of {name}"), || download.attempt())?;
+        download.finish()
+    }
+
+    //TLE and CSV bodies are small and downloaded at once, an error may still come as JSON
+    fn download_text(&self, url: String, group: &str, format: ElementsFormat) -> Result<OrbitalData, SkytracioError> {
+        let response = self.retry.run(&format!("Request to {url}"), || ureq::get(&url).call().map_err(|err| http_error(&url, err)))?;
+        if response.content_type() == "application/json" {
+            let data: Vec<sgp4::Elements> = serde_json::from_reader(response.into_reader())
+                .map_err(|err| SkytracioError::Parse { format: "JSON".to_owned(), record: None, offset: None, source: ParseError::Json(err) })?;
+            return Ok(data.into_iter().map(Arc::new).collect());
+        }
+        let text = response.into_string()?;
+        match format {
+            ElementsFormat::Csv => Ok(parse_csv(&text).into_elements(group)),
+            _ => parse_tles(&text)
+        }
+    }
+
+    //sets in memory younger than the TTL
+    fn fresh(&self, key: &CacheKey) -> Option<OrbitalData> {
+        let cache = self.cache.read().unwrap();
+        let (data, fetched) = cache.get(key)?;
+        (fetched.elapsed().unwrap_or(Duration::ZERO) <= self.ttl).then(|| data.clone())
+    }
+
+    //blocks on the requests, runs on the IO pool. One load of a key at a time, the last one removes the lock of the key
+    fn load_blocking(&self, key: CacheKey, refresh: bool, partial: Option<PartialSets>) -> Result<OrbitalData, SkytracioError> {
+        let lock = self.loading.lock().unwrap().entry(key.clone()).or_default().clone();
+        let loaded = {
+            let _loading = lock.lock().unwrap();
+            self.load_locked(&key, refresh, partial)
+        };
+        //a waiting load holds a clone too, and new ones can't take one while the map is locked
+        let mut locks = self.loading.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(&key);
+        }
+        loaded
+    }
+
+    //a failed fetch keeps the cached sets, a plain load even gets them
+    fn load_locked(&self, key: &CacheKey, refresh: bool, partial: Option<PartialSets>) -> Result<OrbitalData, SkytracioError> {
+        if !refresh {
+            if let Some(data) = self.fresh(key) {
+                return Ok(data);
+            }
+            if let Some((data, fetched)) = self.read_cached(key) {
+                debug!("Loaded {} from the disk cache", key.1);
+                self.cache.write().unwrap().insert(key.clone(), (data.clone(), fetched));
+                return Ok(data);
+            }
+        }
+        match self.fetch(key, partial) {
+            Ok(data) => {
+                self.cache.write().unwrap().insert(key.clone(), (data.clone(), SystemTime::now()));
+                Ok(data)
+            },
+            Err(err) => {
+                let Some((stale, fetched)) = self.cache.read().unwrap().get(key).cloned() else {
+                    return Err(err);
+                };
+                let age = fetched.elapsed().unwrap_or(Duration::ZERO).as_secs_f64() / 3600.0;
+                warn!("Fetching {} again failed, keeping the sets from {age:.1} h ago: {}", key.1, err.report());
+                if refresh { Err(err) } else { Ok(stale) }
+            }
+        }
+    }
+
+    //ureq blocks for the whole round trip, on the IO pool it doesn't hold up the propagation on the compute one
+    async fn load_on_io_pool(&self, key: CacheKey, partial: Option<PartialSets>) -> Result<OrbitalData, SkytracioError> {
+        if let Some(data) = self.fresh(&key) {
+            return Ok(data);
+        }
+        let client = self.clone();
+        IoTaskPool::get_or_init(TaskPool::new).spawn(async move { client.load_blocking(key, false, partial) }).await
+    }
+
+    fn fetch(&self, key: &CacheKey, partial: Option<PartialSets>) -> Result<OrbitalData, SkytracioError> {
+        let (source, group, format) = key;
+        let (source, format) = (*source, *format);
+        let url = elements_url(&self.api, source, group, format).ok_or(SkytracioError::UnsupportedSource(source))?;
+        info!("Calling API");
+        let name: String = format!("{}-{group}-{}", source.label(), format.label()).chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let elements_vec = match format {
+            ElementsFormat::Json => self.download(url, &name, partial)?,
+            ElementsFormat::Tle | ElementsFormat::Csv => self.download_text(url, group, format)?
+        };
+
+        if let Err(err) = self.write_cached(key, &elements_vec) {
+            warn!("Failed to cache {group}: {}", err.report());
+        }
+        Ok(elements_vec)
+    }
+}
+
+#[async_trait::async_trait]
+impl EpochDataLoader for DefaultClient {
+    type Error = SkytracioError;
+
+    async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.load_from(DataSource::Gp, group, format).await
+    }
+
+    async fn load_from(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.load_on_io_pool((source, group, format), None).await
+    }
+
+    //only JSON bodies are streamed, the sets of a cache hit come at once
+    async fn load_streamed(&self, source: DataSource, group: String, format: ElementsFormat, partial: PartialSets) -> Result<OrbitalData, Self::Error> {
+        self.load_on_io_pool((source, group, format), Some(partial)).await
+    }
+
+    //past both caches, the sets stay cached when the fetch fails
+    async fn refresh_from(&self, source: DataSource, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        let client = self.clone();
+        IoTaskPool::get_or_init(TaskPool::new).spawn(async move { client.load_blocking((source, group, format), true, None) }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::{fs, thread, time::UNIX_EPOCH};
+
+    use super::*;
+    use bevy::prelude::App;
+    use bevy::tasks::futures_lite::future::block_on;
+    use sgp4::Elements;
+    use crate::propagation::{GroupLoadStatus, InGameElements, LoadElements, LoadStatus};
+    use crate::test_support::{headless_app, run_until, LEO};
+
+    fn cached_client(name: &str, ttl: Duration) -> (DefaultClient, CacheKey) {
+        let directory = std::env::temp_dir().join(format!("skytracio-cache-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        (DefaultClient::new().with_cache_dir(directory).with_ttl(ttl), (DataSource::Gp, "galileo".to_owned(), ElementsFormat::Json))
+    }
+
+    fn fixture() -> OrbitalData {
+        let elements: Vec<Elements> = serde_json::from_str(&fs::read_to_string("assets/data/galileo.json").unwrap()).unwrap();
+        elements.into_iter().map(Arc::new).collect()
+    }
+
+    #[test]
+    fn test_disk_cache_round_trip() {
+        let (client, key) = cached_client("round-trip", CACHE_TTL);
+        assert!(client.read_cached(&key).is_none());
+        client.write_cached(&key, &fixture()).unwrap();
+        let (sidecar, data) = client.cache_dir.as_ref().unwrap().files(&key);
+        assert!(data.ends_with("galileo.json.json"), "{}", data.display());
+        assert!(sidecar.exists());
+
+        //served without a request, the in-memory map is filled for the next loads
+        let loaded = block_on(client.load("galileo".to_owned(), ElementsFormat::Json)).unwrap();
+        assert_eq!(loaded.iter().map(|el| el.norad_id).collect::<Vec<_>>(), fixture().iter().map(|el| el.norad_id).collect::<Vec<_>>());
+        assert!(client.cache.read().unwrap().contains_key(&key));
+        fs::remove_dir_all(data.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_stale_or_corrupt_cache_is_a_miss() {
+        let (client, key) = cached_client("stale", Duration::ZERO);
+        client.write_cached(&key, &fixture()).unwrap();
+        let (sidecar, data) = client.cache_dir.as_ref().unwrap().files(&key);
+        fs::write(&sidecar, "0").unwrap();
+        assert!(client.read_cached(&key).is_none());
+
+        let client = client.with_ttl(CACHE_TTL);
+        fs::write(&sidecar, SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string()).unwrap();
+        assert_eq!(client.read_cached(&key).unwrap().0.len(), fixture().len());
+        let json = fs::read(&data).unwrap();
+        fs::write(&data, &json[..json.len() / 2]).unwrap();
+        assert!(client.read_cached(&key).is_none());
+        fs::write(&data, b"[{\"OBJECT_NAME\": 7}]").unwrap();
+        assert!(client.read_cached(&key).is_none());
+        fs::write(&sidecar, "yesterday").unwrap();
+        assert!(client.read_cached(&key).is_none());
+        fs::remove_dir_all(data.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_loads_of_a_group_get_the_same_sets() {
+        let (release, hold) = mpsc::channel();
+        let (api, requests) = serve(20, hold);
+        let client = DefaultClient::new().with_api(api).with_download_directory(downloads("concurrent"));
+        let key: CacheKey = (DataSource::Gp, "galileo".to_owned(), ElementsFormat::Json);
+        let loads: Vec<_> = (0..2).map(|_| {
+            let (client, key) = (client.clone(), key.clone());
+            thread::spawn(move || client.load_blocking(key, false, None).unwrap())
+        }).collect();
+        //both loads are past the cache while the first response is held back
+        thread::sleep(Duration::from_millis(200));
+        release.send(()).unwrap();
+        let loaded: Vec<_> = loads.into_iter().map(|load| load.join().unwrap()).collect();
+        assert_eq!(loaded[0].len(), 20);
+        assert!(loaded[0].iter().zip(&loaded[1]).all(|(a, b)| Arc::ptr_eq(a, b)));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        //the lock of the group went with its last load
+        assert!(client.loading.lock().unwrap().is_empty());
+    }
+
+    //answers every request with an array of `count` sets, the first answer stops halfway until `hold` receives.
+    //The elements directory of the API and the number of requests
+    fn serve(count: u64, hold: Receiver<()>) -> (String, Arc<AtomicUsize>) {
+        let elements: Vec<_> = (0..count).map(|i| LEO.builder().name(format!("SERVED-{i}")).norad_id(41000 + i).json()).collect();
+        let body = format!("[\n  {}\n]\n", elements.join(",\n  "));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        thread::spawn(move || {
+            let mut hold = Some(hold);
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                while reader.read_line(&mut request).unwrap() > 2 {}
+                counted.fetch_add(1, Ordering::SeqCst);
+                let (head, tail) = body.as_bytes().split_at(body.len() / 2);
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n", body.len());
+                let _ = stream.write_all(head).and_then(|_| stream.flush());
+                if let Some(hold) = hold.take() {
+                    let _ = hold.recv();
+                }
+                let _ = stream.write_all(tail);
+            }
+        });
+        (api, requests)
+    }
+
+    fn downloads(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("skytracio-downloads-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn satellites(app: &mut App) -> usize {
+        //the download runs in real time, the updates of the test apps don't wait for it
+        thread::sleep(Duration::from_millis(10));
+        app.world_mut().query::<&InGameElements>().iter(app.world()).count()
+    }
+
+    #[test]
+    fn test_streamed_sets_spawn_before_the_body_is_in() {
+        let (release, hold) = mpsc::channel();
+        let (api, _) = serve(40, hold);
+        let mut app = headless_app(DefaultClient::new().with_api(api).with_download_directory(downloads("streamed")));
+        app.update();
+        app.world_mut().send_event(LoadElements::group("streamed", ElementsFormat::Json));
+
+        //the server holds the second half of the body back
+        run_until(&mut app, |app| satellites(app) > 0, 500);
+        let spawned = satellites(&mut app);
+        assert!((1..40).contains(&spawned), "{spawned}");
+        assert_eq!(app.world().resource::<GroupLoadStatus>().get("streamed"), Some(LoadStatus::Pending));
+
+        release.send(()).unwrap();
+        run_until(&mut app, |app| app.world().resource::<GroupLoadStatus>().get("streamed") == Some(LoadStatus::Loaded), 500);
+        app.update();
+        assert_eq!(satellites(&mut app), 40);
+    }
+
+    #[test]
+    fn test_expired_sets_are_fetched_again_and_kept_when_that_fails() {
+        let client = DefaultClient::new().with_retry(RetryPolicy { max_attempts: 1, base_delay: Duration::ZERO });
+        //not on Celestrak, every fetch fails without a request
+        let key = (DataSource::File, "galileo".to_owned(), ElementsFormat::Json);
+        let fetched = SystemTime::now() - Duration::from_secs(3600);
+        client.cache.write().unwrap().insert(key.clone(), (fixture(), fetched));
+        let load = |client: &DefaultClient| block_on(client.load_from(DataSource::File, "galileo".to_owned(), ElementsFormat::Json));
+        let refresh = |client: &DefaultClient| block_on(client.refresh_from(DataSource::File, "galileo".to_owned(), ElementsFormat::Json));
+
+        //fresh enough, a refresh still goes past it
+        assert_eq!(load(&client).unwrap().len(), fixture().len());
+        assert!(matches!(refresh(&client), Err(SkytracioError::UnsupportedSource(DataSource::File))));
+        assert_eq!(client.cache.read().unwrap()[&key].1, fetched);
+
+        //expired, the stale sets are better than none
+        let client = client.with_ttl(Duration::from_secs(60));
+        assert!(client.fresh(&key).is_none());
+        assert_eq!(load(&client).unwrap().len(), fixture().len());
+        assert!(refresh(&client).is_err());
+        assert_eq!(client.cache.read().unwrap()[&key].0.len(), fixture().len());
+
+        client.cache.write().unwrap().clear();
+        assert!(load(&client).is_err());
+    }
+
+    #[test]
+    fn test_retries_transient_errors_with_backoff() {
+        let policy = RetryPolicy { max_attempts: 4, base_delay: Duration::from_millis(1) };
+        assert_eq!((1..=4).map(|attempt| policy.delay(attempt).as_millis()).collect::<Vec<_>>(), vec![1, 2, 4, 8]);
+
+        let http = |status| SkytracioError::Http { url: "https://celestrak.com/x".to_owned(), status, message: String::new(), source: None };
+        assert!(RetryPolicy::is_retryable(&http(Some(503))));
+        assert!(RetryPolicy::is_retryable(&http(None)));
+        assert!(!RetryPolicy::is_retryable(&http(Some(404))));
+        assert!(!RetryPolicy::is_retryable(&SkytracioError::io("cache.json", std::io::ErrorKind::PermissionDenied.into())));
+
+        //passes on the third attempt
+        let mut calls = 0;
+        let result = policy.run("test", || {
+            calls += 1;
+            if calls < 3 { Err(http(Some(503))) } else { Ok(calls) }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        //a bad group isn't requested again, a server that keeps failing gives up with the last error
+        calls = 0;
+        assert!(matches!(policy.run("test", || { calls += 1; Err::<(), _>(http(Some(404))) }), Err(SkytracioError::Http { status: Some(404), .. })));
+        assert_eq!(calls, 1);
+        calls = 0;
+        let result = policy.run("test", || { calls += 1; Err::<(), _>(http(Some(500 + calls))) });
+        assert!(matches!(result, Err(SkytracioError::Http { status: Some(504), .. })));
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn test_integration() {
+
+        let client = DefaultClient::new();
+
+        let res = block_on(client.load("galileo".to_owned(), ElementsFormat::Json)).unwrap();
+
+        println!("{}", display_elements(&res));
+        assert!(res.len() > 1);        
+    }
+
+    fn display_elements(elements: &[Arc<Elements>]) -> String {
+        let res: Vec<_> = elements.iter().map(|els| format!("object_name={:?},international_designator={:?},norad_id={},classification={:?},datetime={:?}", els.object_name, els.international_designator, els.norad_id, display_clasification(els), els.datetime)).collect();
+        res.join("\n")
+    }
+
+    fn display_clasification(elem: &Elements) -> String {
+        match elem.classification {
+            sgp4::Classification::Unclassified => "unclassified".to_owned(),
+            sgp4::Classification::Classified => "classified".to_owned(),
+            sgp4::Classification::Secret => "secret".to_owned(),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,80 @@
+use sgp4::Elements;
+
+use crate::error::{ParseError, SkytracioError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum ArrayPosition {
+    #[default]
+    Start,
+    FirstElement,
+    Element,
+    Separator,
+    End
+}
+
+/// Parses a JSON array of element sets from bytes as they arrive, only the element being received is buffered
+#[derive(Debug, Default)]
+pub struct ElementsStream {
+    buffer: Vec<u8>,
+    //offset of the buffer start in the body
+    consumed: usize,
+    //element sets parsed so far, the index of the next one in errors
+    records: usize,
+    position: ArrayPosition
+}
+
+impl ElementsStream {
+    /// Element sets completed by the bytes
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<Elements>, SkytracioError> {
+        self.buffer.extend_from_slice(bytes);
+        let mut parsed = vec![];
+        let mut at = 0;
+        loop {
+            while self.buffer.get(at).is_some_and(u8::is_ascii_whitespace) {
+                at += 1;
+            }
+            let Some(&byte) = self.buffer.get(at) else {
+                break;
+            };
+            let offset = self.consumed + at;
+            match (self.position, byte) {
+                (ArrayPosition::Start, b'[') => self.position = ArrayPosition::FirstElement,
+                (ArrayPosition::FirstElement | ArrayPosition::Separator, b']') => self.position = ArrayPosition::End,
+                (ArrayPosition::Separator, b',') => self.position = ArrayPosition::Element,
+                (ArrayPosition::FirstElement | ArrayPosition::Element, _) => {
+                    let mut elements = serde_json::Deserializer::from_slice(&self.buffer[at..]).into_iter::<Elements>();
+                    match elements.next() {
+                        Some(Ok(el)) => {
+                            at += elements.byte_offset();
+                            parsed.push(el);
+                            self.records += 1;
+                            self.position = ArrayPosition::Separator;
+                            continue;
+                        },
+                        //the rest of the element is still on its way
+                        Some(Err(err)) if err.is_eof() => break,
+                        Some(Err(err)) => return Err(self.parse_error(offset, ParseError::Json(err))),
+                        None => break
+                    }
+                },
+                _ => return Err(self.parse_error(offset, ParseError::UnexpectedByte(byte)))
+            }
+            at += 1;
+        }
+        self.buffer.drain(..at);
+        self.consumed += at;
+        Ok(parsed)
+    }
+
+    /// Fails unless the whole array was fed
+    pub fn finish(&self) -> Result<(), SkytracioError> {
+        match self.position {
+            ArrayPosition::End => Ok(()),
+            _ => Err(SkytracioError::Incomplete { expected: None, received: (self.consumed + self.buffer.len()) as u64 })
+        }
+    }
+
+    fn parse_error(&self, offset: usize, source: ParseError) -> SkytracioError {
+        SkytracioError::Parse { format: "JSON".to_owned(), record: Some(self.records), offset: Some(offset), source }
+    }
+}
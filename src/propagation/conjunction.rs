@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task}
+};
+use sgp4::Elements;
+
+use crate::global::InGameSettings;
+use crate::simulation_clock::{ensure_simulation_clock, SimulationClock};
+
+use super::bevy_integration::{predict_at, InGameElements, PropagatableDuration};
+use super::validation::Unreliable;
+
+//head-on LEO crossings, no pair closes faster than this
+const MAX_RELATIVE_SPEED_KM_S: f64 = 16.0;
+//the golden-section search stops once the bracket is this narrow (in seconds)
+const TCA_TOLERANCE: f64 = 1e-3;
+//step of the central difference giving the relative velocity at TCA (in seconds)
+const VELOCITY_STEP: f64 = 0.5;
+
+/// Screens all reliable satellites for close approaches on the compute pool
+pub struct ConjunctionScreeningPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ConjunctionSettings {
+    pub enabled: bool,
+    /// Simulated time between screenings, every screening covers the time since the previous one
+    pub interval: Duration,
+    pub miss_distance_km: f64,
+    /// Repeated approaches of a pair within this simulated time are reported once
+    pub cooldown: Duration,
+    /// Range samples bracketing the closest approach before the refinement
+    pub samples: usize
+}
+
+impl Default for ConjunctionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(60),
+            miss_distance_km: 5.0,
+            cooldown: Duration::from_secs(30 * 60),
+            samples: 16
+        }
+    }
+}
+
+/// Closest approach of two satellites, `a` is the lower entity of the pair
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct CloseApproach {
+    pub a: Entity,
+    pub b: Entity,
+    /// Time of closest approach, in minutes since the epoch of `a` and of `b`
+    pub tca_minutes: [f64; 2],
+    pub miss_distance_km: f64,
+    pub relative_speed_km_s: f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Approach {
+    /// Time of closest approach, relative to the sampled function
+    pub t: f64,
+    pub miss_distance: f64,
+    pub relative_speed: f64
+}
+
+/// Finds the closest approach within `[start, end]` of a relative position given as a function of time.
+/// Coarse samples of the range bracket the minimum, a golden-section search on the range squared refines it
+pub fn refine_closest_approach(relative_position: impl Fn(f64) -> DVec3, start: f64, end: f64, samples: usize) -> Approach {
+    let samples = samples.max(2);
+    let step = (end - start) / samples as f64;
+    let range_squared = |t: f64| relative_position(t).length_squared();
+    let closest = (0..=samples)
+        .map(|i| (i, range_squared(start + step * i as f64)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+
+    let (mut low, mut high) = (start + step * closest.saturating_sub(1) as f64, start + step * (closest + 1).min(samples) as f64);
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut x1, mut x2) = (high - ratio * (high - low), low + ratio * (high - low));
+    let (mut f1, mut f2) = (range_squared(x1), range_squared(x2));
+    while high - low > TCA_TOLERANCE {
+        if f1 < f2 {
+            high = x2;
+            (x2, f2) = (x1, f1);
+            x1 = high - ratio * (high - low);
+            f1 = range_squared(x1);
+        } else {
+            low = x1;
+            (x1, f1) = (x2, f2);
+            x2 = low + ratio * (high - low);
+            f2 = range_squared(x2);
+        }
+    }
+
+    let t = (low + high) / 2.0;
+    let velocity = (relative_position(t + VELOCITY_STEP) - relative_position(t - VELOCITY_STEP)) / (2.0 * VELOCITY_STEP);
+    Approach { t, miss_distance: relative_position(t).length(), relative_speed: velocity.length() }
+}
+
+#[derive(Clone)]
+struct Screened {
+    entity: Entity,
+    elements: Arc<Elements>,
+    minutes: f64
+}
+
+#[derive(Resource, Default)]
+pub struct ConjunctionScreener {
+    //simulated seconds since the last screening started
+    since_screening: f64,
+    task: Option<Task<Vec<CloseApproach>>>,
+    //time of the last reported approach of every pair, in minutes since the epoch of `a`
+    reported: HashMap<(Entity, Entity), f64>
+}
+
+impl Plugin for ConjunctionScreeningPlugin {
+    fn build(&self, app: &mut App) {
+        ensure_simulation_clock(app);
+        app
+            .add_event::<CloseApproach>()
+            .init_resource::<ConjunctionSettings>()
+            .init_resource::<ConjunctionScreener>()
+            .add_systems(Update, (start_screening, collect_screening).chain().run_if(resource_exists::<InGameSettings>));
+    }
+}
+
+fn start_screening(
+    clock: Res<SimulationClock>,
+    settings: Res<InGameSettings>,
+    conjunction_settings: Res<ConjunctionSettings>,
+    mut screener: ResMut<ConjunctionScreener>,
+    satellites: Query<(Entity, &InGameElements, &PropagatableDuration), Without<Unreliable>>
+) {
+    screener.since_screening += clock.delta_seconds();
+    let span = screener.since_screening;
+    if !conjunction_settings.enabled || screener.task.is_some() || span < conjunction_settings.interval.as_secs_f64() {
+        return;
+    }
+
+    let screened: Vec<_> = satellites.iter()
+        .map(|(entity, elements, elapsed)| Screened { entity, elements: elements.0.clone(), minutes: elapsed.minutes_since_epoch() })
+        .collect();
+    let (conjunction_settings, numeric_fallback) = (conjunction_settings.clone(), settings.propagation.numeric_fallback);
+    screener.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        screen(&screened, span, &conjunction_settings, numeric_fallback)
+    }));
+    screener.since_screening = 0.0;
+}
+
+//the task is only polled, screening a large catalog never stalls a frame
+fn collect_screening(
+    conjunction_settings: Res<ConjunctionSettings>,
+    mut screener: ResMut<ConjunctionScreener>,
+    mut approaches: EventWriter<CloseApproach>
+) {
+    let Some(found) = screener.task.as_mut().and_then(|task| block_on(future::poll_once(task))) else {
+        return;
+    };
+    screener.task = None;
+    let cooldown_minutes = conjunction_settings.cooldown.as_secs_f64() / 60.0;
+    for approach in found {
+        let key = (approach.a, approach.b);
+        if screener.reported.get(&key).is_some_and(|last| (approach.tca_minutes[0] - last).abs() < cooldown_minutes) {
+            debug!("Suppressing repeated approach of {} and {}", approach.a, approach.b);
+            continue;
+        }
+        screener.reported.insert(key, approach.tca_minutes[0]);
+        info!("Close approach of {} and {}: {:.3} km at {:.2} km/s", approach.a, approach.b, approach.miss_distance_km, approach.relative_speed_km_s);
+        approaches.send(approach);
+    }
+}
+
+fn position_at(satellite: &Screened, minutes: f64, numeric_fallback: bool) -> Option<DVec3> {
+    predict_at(&satellite.elements, minutes, numeric_fallback).map(|p| DVec3::from_array(p.position))
+}
+
+//every satellite covers the `span` seconds up to its current time
+fn screen(satellites: &[Screened], span: f64, settings: &ConjunctionSettings, numeric_fallback: bool) -> Vec<CloseApproach> {
+    //no pair further apart than this in the middle of the span can get within the miss distance
+    let screening_distance = settings.miss_distance_km + MAX_RELATIVE_SPEED_KM_S * span / 2.0;
+    let span_minutes = span / 60.0;
+
+    let mut grid: HashMap<IVec3, Vec<(usize, DVec3)>> = HashMap::new();
+    for (index, satellite) in satellites.iter().enumerate() {
+        if let Some(position) = position_at(satellite, satellite.minutes - span_minutes / 2.0, numeric_fallback) {
+            let cell = (position / screening_distance).floor().as_ivec3();
+            grid.entry(cell).or_default().push((index, position));
+        }
+    }
+
+    let mut found = vec![];
+    for (cell, members) in &grid {
+        for &(i, position) in members {
+            for offset in (-1..=1).flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z)))) {
+                let Some(neighbours) = grid.get(&(*cell + offset)) else {
+                    continue;
+                };
+                for &(j, other) in neighbours {
+                    if j <= i || position.distance(other) > screening_distance {
+                        continue;
+                    }
+                    let (a, b) = if satellites[i].entity < satellites[j].entity { (&satellites[i], &satellites[j]) } else { (&satellites[j], &satellites[i]) };
+                    let relative_position = |t: f64| {
+                        let offset = (t - span) / 60.0;
+                        match (position_at(a, a.minutes + offset, numeric_fallback), position_at(b, b.minutes + offset, numeric_fallback)) {
+                            (Some(a), Some(b)) => b - a,
+                            _ => DVec3::INFINITY
+                        }
+                    };
+                    let approach = refine_closest_approach(relative_position, 0.0, span, settings.samples);
+                    if approach.miss_distance <= settings.miss_distance_km {
+                        let offset = (approach.t - span) / 60.0;
+                        found.push(CloseApproach {
+                            a: a.entity,
+                            b: b.entity,
+                            tca_minutes: [a.minutes + offset, b.minutes + offset],
+                            miss_distance_km: approach.miss_distance,
+                            relative_speed_km_s: approach.relative_speed
+                        });
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::orbit::{SatelliteOrbit, GRAVITATIONAL_CONSTANT};
+    use crate::propagation::bevy_integration::PropagatableSattelite;
+    use crate::test_support::LEO;
+
+    #[test]
+    fn test_refined_tca_of_crossing_orbits() {
+        //an equatorial and a polar orbit, both reaching the ascending node at `tca` with radii 1 km apart
+        let tca = 250.0;
+        let crossing = |radius: f64, inclination: f64| {
+            let orbit = SatelliteOrbit::new(radius, 0.0, inclination, 0.0, 0.0, 0.0, 0.0);
+            let mean_motion = 360.0 / orbit.orbital_period();
+            SatelliteOrbit { true_anomaly: -mean_motion * tca, ..orbit }
+        };
+        let (a, b) = (crossing(7000.0, 0.0), crossing(7001.0, 90.0));
+        let position = |orbit: &SatelliteOrbit, t: f64| orbit.propagate(t).position();
+        let approach = refine_closest_approach(|t| position(&b, t) - position(&a, t), 0.0, 600.0, 16);
+
+        let speed = |orbit: &SatelliteOrbit| (GRAVITATIONAL_CONSTANT / orbit.semi_major_axis).sqrt();
+        assert_abs_diff_eq!(approach.t, tca, epsilon = 0.05);
+        assert_abs_diff_eq!(approach.miss_distance, 1.0, epsilon = 0.01);
+        assert_abs_diff_eq!(approach.relative_speed, speed(&a).hypot(speed(&b)), epsilon = 0.05);
+    }
+
+    fn satellite(mean_anomaly: f64) -> PropagatableSattelite {
+        PropagatableSattelite::new(InGameElements(LEO.builder().mean_anomaly(mean_anomaly).build()))
+    }
+
+    #[test]
+    fn test_repeated_approaches_are_suppressed() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, ConjunctionScreeningPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)))
+            //a screening on every frame
+            .insert_resource(InGameSettings {
+                simulation_speed: 60.0,
+                ..default()
+            });
+        //trailing each other by about a kilometer, the third one is on the other side of the orbit
+        let a = app.world_mut().spawn(satellite(40.0)).id();
+        let b = app.world_mut().spawn(satellite(40.008)).id();
+        app.world_mut().spawn(satellite(220.0));
+
+        let mut reader = app.world().resource::<Events<CloseApproach>>().get_reader();
+        let mut received = vec![];
+        let mut run = |app: &mut App, frames: usize| {
+            for _ in 0..frames {
+                app.update();
+                received.extend(reader.read(app.world().resource::<Events<CloseApproach>>()).cloned());
+            }
+            std::mem::take(&mut received)
+        };
+
+        let approaches = run(&mut app, 20);
+        assert_eq!(approaches.len(), 1, "{approaches:?}");
+        assert_eq!((approaches[0].a, approaches[0].b), (a.min(b), a.max(b)));
+        assert!(approaches[0].miss_distance_km < 2.0);
+
+        app.world_mut().resource_mut::<ConjunctionSettings>().cooldown = Duration::ZERO;
+        assert!(!run(&mut app, 20).is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use sgp4::Elements;
+
+use crate::orbit::{SatelliteOrbit, EARTH_EQUATORIAL_RADIUS, GRAVITATIONAL_CONSTANT};
+
+use super::classification::{OrbitClass, OrbitClassification};
+use super::client::{DataSource, OrbitalData};
+
+const HEADER: &str = "skytracio-derived-v1";
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Everything derived from the elements of a satellite, cached between sessions
+#[derive(Clone, Debug, PartialEq)]
+pub struct DerivedRecord {
+    pub norad_id: u64,
+    /// Epoch of the elements the record was derived from, as returned by `Elements::epoch`
+    pub epoch: f64,
+    pub classification: OrbitClassification,
+    /// Altitudes above the Earth surface (in kilometers)
+    pub perigee_km: f64,
+    pub apogee_km: f64,
+    pub group: String
+}
+
+impl DerivedRecord {
+    pub fn derive(elements: &Elements, group: &str) -> Self {
+        let orbit = SatelliteOrbit::from(elements);
+        let mean_motion = elements.mean_motion * std::f64::consts::TAU / SECONDS_PER_DAY;
+        let semi_major_axis = (GRAVITATIONAL_CONSTANT / mean_motion.powi(2)).cbrt();
+        Self {
+            norad_id: elements.norad_id,
+            epoch: elements.epoch(),
+            classification: OrbitClassification::new(elements, &orbit),
+            perigee_km: semi_major_axis * (1.0 - elements.eccentricity) - EARTH_EQUATORIAL_RADIUS,
+            apogee_km: semi_major_axis * (1.0 + elements.eccentricity) - EARTH_EQUATORIAL_RADIUS,
+            group: group.to_owned()
+        }
+    }
+
+    /// Whether the record belongs to these elements
+    pub fn matches(&self, elements: &Elements) -> bool {
+        self.norad_id == elements.norad_id && self.epoch == elements.epoch()
+    }
+}
+
+/// Records of a load by NORAD id
+pub type DerivedData = HashMap<u64, DerivedRecord>;
+
+#[derive(Debug)]
+pub enum DerivedCacheError {
+    IO(io::Error),
+    Corrupt(String)
+}
+
+impl From<io::Error> for DerivedCacheError {
+    fn from(value: io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheLookup {
+    Hit(DerivedData),
+    /// Derived from a different version of the source data
+    Stale,
+    Missing,
+    Corrupt(DerivedCacheError)
+}
+
+/// Enables the cross-session cache of derived data, one file per group and source in `directory`
+#[derive(Resource, Clone, Debug)]
+pub struct DerivedDataCache {
+    pub directory: PathBuf
+}
+
+impl DerivedDataCache {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    fn path(&self, source: DataSource, group: &str) -> PathBuf {
+        let group: String = group.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        self.directory.join(format!("{}-{}.derived", source.label(), group))
+    }
+
+    pub fn lookup(&self, source: DataSource, group: &str, hash: u64) -> CacheLookup {
+        match fs::read_to_string(self.path(source, group)) {
+            Ok(content) => match parse(&content, hash) {
+                Ok(Some(data)) => CacheLookup::Hit(data),
+                Ok(None) => CacheLookup::Stale,
+                Err(err) => CacheLookup::Corrupt(err)
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => CacheLookup::Missing,
+            Err(err) => CacheLookup::Corrupt(err.into())
+        }
+    }
+
+    pub fn store(&self, source: DataSource, group: &str, hash: u64, records: &DerivedData) -> Result<(), DerivedCacheError> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.path(source, group), serialize(hash, records))?;
+        Ok(())
+    }
+
+    /// Records of every loaded satellite, straight from the cache when it was written for identical data.
+    /// Stale, missing or corrupt caches are rebuilt
+    pub fn derive_all(&self, source: DataSource, group: &str, data: &OrbitalData) -> DerivedData {
+        let hash = source_hash(data);
+        let reason = match self.lookup(source, group, hash) {
+            CacheLookup::Hit(records) if data.iter().all(|el| records.get(&el.norad_id).is_some_and(|r| r.matches(el))) => {
+                debug!("Derived data of {group} from {} taken from the cache", source.label());
+                return records;
+            },
+            CacheLookup::Hit(_) => "incomplete".to_owned(),
+            CacheLookup::Stale => "stale".to_owned(),
+            CacheLookup::Missing => "missing".to_owned(),
+            CacheLookup::Corrupt(err) => {
+                warn!("Derived data cache of {group} is corrupt, recomputing: {err:?}");
+                "corrupt".to_owned()
+            }
+        };
+        debug!("Derived data cache of {group} from {} is {reason}, recomputing", source.label());
+        let records: DerivedData = data.iter().map(|el| (el.norad_id, DerivedRecord::derive(el, group))).collect();
+        if let Err(err) = self.store(source, group, hash, &records) {
+            warn!("Failed to store derived data of {group}: {err:?}");
+        }
+        records
+    }
+}
+
+/// Stable FNV-1a hash of the loaded element sets, in their order
+pub fn source_hash(data: &OrbitalData) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for elements in data {
+        let values = [
+            elements.norad_id, elements.epoch().to_bits(), elements.mean_motion.to_bits(), elements.eccentricity.to_bits(),
+            elements.inclination.to_bits(), elements.right_ascension.to_bits(), elements.argument_of_perigee.to_bits(),
+            elements.mean_anomaly.to_bits(), elements.drag_term.to_bits()
+        ];
+        for byte in values.iter().flat_map(|v| v.to_le_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn class_label(class: OrbitClass) -> &'static str {
+    match class {
+        OrbitClass::Leo => "LEO",
+        OrbitClass::Meo => "MEO",
+        OrbitClass::Geo => "GEO",
+        OrbitClass::Heo => "HEO"
+    }
+}
+
+fn parse_class(label: &str) -> Option<OrbitClass> {
+    match label {
+        "LEO" => Some(OrbitClass::Leo),
+        "MEO" => Some(OrbitClass::Meo),
+        "GEO" => Some(OrbitClass::Geo),
+        "HEO" => Some(OrbitClass::Heo),
+        _ => None
+    }
+}
+
+//header line with the source hash and the record count, then one comma separated record per line, the group last
+fn serialize(hash: u64, records: &DerivedData) -> String {
+    let mut out = format!("{HEADER} {hash:016x} {}\n", records.len());
+    for record in records.values() {
+        let _ = writeln!(
+            out, "{},{},{},{},{},{},{},{}",
+            record.norad_id, record.epoch, class_label(record.classification.class), record.classification.period_minutes,
+            record.classification.orbit_period_minutes, record.perigee_km, record.apogee_km, record.group
+        );
+    }
+    out
+}
+
+//`None` when the cache was written for other source data
+fn parse(content: &str, hash: u64) -> Result<Option<DerivedData>, DerivedCacheError> {
+    let corrupt = |what: &str| DerivedCacheError::Corrupt(what.to_owned());
+    let mut lines = content.lines();
+    let header: Vec<_> = lines.next().ok_or_else(|| corrupt("empty file"))?.split(' ').collect();
+    let [HEADER, stored_hash, count] = header[..] else {
+        return Err(corrupt("unknown header"));
+    };
+    if u64::from_str_radix(stored_hash, 16).map_err(|_| corrupt("hash"))? != hash {
+        return Ok(None);
+    }
+    let count: usize = count.parse().map_err(|_| corrupt("record count"))?;
+
+    let mut records = DerivedData::with_capacity(count);
+    for line in lines {
+        let fields: Vec<_> = line.splitn(8, ',').collect();
+        let [norad_id, epoch, class, period, orbit_period, perigee, apogee, group] = fields[..] else {
+            return Err(corrupt(line));
+        };
+        let number = |value: &str| value.parse::<f64>().map_err(|_| corrupt(line));
+        let record = DerivedRecord {
+            norad_id: norad_id.parse().map_err(|_| corrupt(line))?,
+            epoch: number(epoch)?,
+            classification: OrbitClassification {
+                class: parse_class(class).ok_or_else(|| corrupt(line))?,
+                period_minutes: number(period)?,
+                orbit_period_minutes: number(orbit_period)?
+            },
+            perigee_km: number(perigee)?,
+            apogee_km: number(apogee)?,
+            group: group.to_owned()
+        };
+        records.insert(record.norad_id, record);
+    }
+    //a truncated file still parses line by line
+    if records.len() != count {
+        return Err(corrupt("record count mismatch"));
+    }
+    Ok(Some(records))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::propagation::{ElementsFormat, InGameElements, LoadElements, LoadElementsPlugin, LoadedElements};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    fn cache(name: &str) -> DerivedDataCache {
+        let directory = std::env::temp_dir().join(format!("skytracio-derived-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        DerivedDataCache::new(directory)
+    }
+
+    #[test]
+    fn test_hash_invalidated_by_one_record() {
+        let cache = cache("hash");
+        let data = starlink_like_elements(200, 1462);
+        let records = cache.derive_all(DataSource::Gp, "starlink", &data);
+        assert_eq!(records.len(), 200);
+        assert!(matches!(cache.lookup(DataSource::Gp, "starlink", source_hash(&data)), CacheLookup::Hit(ref cached) if *cached == records));
+
+        let mut changed = data.clone();
+        let mut elements = (*changed[17]).clone();
+        elements.mean_motion += 0.01;
+        changed[17] = Arc::new(elements);
+        assert_ne!(source_hash(&changed), source_hash(&data));
+        assert!(matches!(cache.lookup(DataSource::Gp, "starlink", source_hash(&changed)), CacheLookup::Stale));
+
+        //recomputed for the new data and rewritten
+        let recomputed = cache.derive_all(DataSource::Gp, "starlink", &changed);
+        assert_eq!(recomputed[&changed[17].norad_id], DerivedRecord::derive(&changed[17], "starlink"));
+        assert_ne!(recomputed[&changed[17].norad_id], records[&data[17].norad_id]);
+        assert!(matches!(cache.lookup(DataSource::Gp, "starlink", source_hash(&changed)), CacheLookup::Hit(_)));
+        //other sources of the same group have their own file
+        assert!(matches!(cache.lookup(DataSource::Supplemental, "starlink", source_hash(&changed)), CacheLookup::Missing));
+    }
+
+    #[test]
+    fn test_corrupt_cache_degrades_to_computation() {
+        let cache = cache("corrupt");
+        let data = starlink_like_elements(50, 1462);
+        let expected = cache.derive_all(DataSource::Gp, "starlink", &data);
+        let path = cache.path(DataSource::Gp, "starlink");
+        let content = fs::read_to_string(&path).unwrap();
+
+        let truncated = &content[..content.len() / 2];
+        let garbage = content.replacen("LEO", "???", 1);
+        for corrupted in [truncated, garbage.as_str(), "", "\u{0}\u{1}binary"] {
+            fs::write(&path, corrupted).unwrap();
+            assert!(matches!(cache.lookup(DataSource::Gp, "starlink", source_hash(&data)), CacheLookup::Corrupt(_)), "{corrupted:.40}");
+            assert_eq!(cache.derive_all(DataSource::Gp, "starlink", &data), expected);
+        }
+        //and the cache is healthy again
+        assert!(matches!(cache.lookup(DataSource::Gp, "starlink", source_hash(&data)), CacheLookup::Hit(_)));
+    }
+
+    #[test]
+    fn test_spawned_satellites_use_cached_records() {
+        let cache = cache("spawn");
+        let data = starlink_like_elements(20, 1462);
+        let mut records = cache.derive_all(DataSource::Gp, "starlink", &data);
+        //doctored, a cache hit is the only way it can end up on the entity
+        records.get_mut(&data[3].norad_id).unwrap().classification.class = OrbitClass::Heo;
+        cache.store(DataSource::Gp, "starlink", source_hash(&data), &records).unwrap();
+
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<SyntheticClient>::new()))
+            .insert_resource(SyntheticClient(data.clone()))
+            .insert_resource(cache);
+        app.world_mut().send_event(LoadElements { groups: vec!["starlink".to_owned()], format: ElementsFormat::Json, source: DataSource::Gp, launch_animation: false, refresh: false });
+        let mut reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        for _ in 0..20 {
+            app.update();
+            if reader.read(app.world().resource::<Events<LoadedElements>>()).next().is_some() {
+                break;
+            }
+        }
+
+        let mut satellites = app.world_mut().query::<(&InGameElements, &OrbitClassification)>();
+        assert_eq!(satellites.iter(app.world()).count(), 20);
+        for (elements, classification) in satellites.iter(app.world()) {
+            let expected = if elements.0.norad_id == data[3].norad_id { OrbitClass::Heo } else { OrbitClass::Leo };
+            assert_eq!(classification.class, expected);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use sgp4::{Elements, Prediction};
+
+use crate::memory::MemoryFootprint;
+use crate::orbit::{SatelliteOrbit, GRAVITATIONAL_CONSTANT};
+
+//Earth's gravitational parameter (km^3/s^2)
+//fixed integration step, two-body motion is smooth enough at this resolution
+const STEP_SECONDS: f64 = 30.0;
+//about a week of steps, longer integrations take longer steps instead of more of them
+const MAX_STEPS: usize = 20_000;
+
+/// Two-body prediction used when SGP4 can't handle the elements, seeded from the element-derived [`SatelliteOrbit`].
+/// Only an approximation: no J2, no drag. Integrates from the epoch, see [`FallbackStates`] for repeated predictions
+pub fn fallback_prediction(elements: &Elements, minutes_since_epoch: f64) -> Option<Prediction> {
+    let (position, velocity) = epoch_state(elements)?;
+    let (position, velocity) = integrate_two_body(position, velocity, minutes_since_epoch * 60.0);
+    prediction(position, velocity)
+}
+
+fn epoch_state(elements: &Elements) -> Option<(DVec3, DVec3)> {
+    let orbit: SatelliteOrbit = elements.into();
+    let (position, velocity) = (orbit.position(), orbit.velocity());
+    (position.is_finite() && velocity.is_finite() && position.length() > 0.0).then_some((position, velocity))
+}
+
+fn prediction(position: DVec3, velocity: DVec3) -> Option<Prediction> {
+    (position.is_finite() && velocity.is_finite()).then(|| Prediction {
+        position: position.to_array(),
+        velocity: velocity.to_array()
+    })
+}
+
+//the state is only valid for the elements it was integrated from, refreshed elements are a new allocation
+#[derive(Clone, Debug)]
+struct IntegratedState {
+    elements: Arc<Elements>,
+    minutes_since_epoch: f64,
+    position: DVec3,
+    velocity: DVec3
+}
+
+/// Last integrated state of every satellite on the numeric fallback. Each prediction steps from it,
+/// so the cost doesn't grow with the time since the epoch and the error isn't accumulated again every tick
+#[derive(Resource, Clone, Default)]
+pub struct FallbackStates(Arc<Mutex<HashMap<Entity, IntegratedState>>>);
+
+impl FallbackStates {
+    pub fn predict(&self, entity: Entity, elements: &Arc<Elements>, minutes_since_epoch: f64) -> Option<Prediction> {
+        let last = self.0.lock().unwrap().get(&entity)
+            .filter(|state| Arc::ptr_eq(&state.elements, elements))
+            .cloned();
+        let start = match last {
+            Some(state) => state,
+            None => {
+                let (position, velocity) = epoch_state(elements)?;
+                IntegratedState { elements: elements.clone(), minutes_since_epoch: 0.0, position, velocity }
+            }
+        };
+
+        let (position, velocity) = integrate_two_body(start.position, start.velocity, (minutes_since_epoch - start.minutes_since_epoch) * 60.0);
+        let prediction = prediction(position, velocity);
+        let mut states = self.0.lock().unwrap();
+        match prediction {
+            Some(_) => { states.insert(entity, IntegratedState { minutes_since_epoch, position, velocity, ..start }); },
+            None => { states.remove(&entity); }
+        }
+        prediction
+    }
+
+    pub fn forget(&self, entity: Entity) {
+        self.0.lock().unwrap().remove(&entity);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+impl MemoryFootprint for FallbackStates {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.len() * size_of::<(Entity, IntegratedState)>()
+    }
+}
+
+/// Integrates the two-body problem with classic RK4, `dt_seconds` may be negative
+pub fn integrate_two_body(mut position: DVec3, mut velocity: DVec3, dt_seconds: f64) -> (DVec3, DVec3) {
+    let mut steps = (dt_seconds.abs() / STEP_SECONDS).ceil().max(1.0) as usize;
+    if steps > MAX_STEPS {
+        warn!("Integrating {:.0} s in {MAX_STEPS} steps instead of {steps}, the numeric fallback loses accuracy", dt_seconds);
+        steps = MAX_STEPS;
+    }
+    let h = dt_seconds / steps as f64;
+    for _ in 0..steps {
+        (position, velocity) = rk4_step(position, velocity, h);
+    }
+    (position, velocity)
+}
+
+fn rk4_step(position: DVec3, velocity: DVec3, h: f64) -> (DVec3, DVec3) {
+    let acceleration = |r: DVec3| -r * (GRAVITATIONAL_CONSTANT / r.length().powi(3));
+
+    let k1_r = velocity;
+    let k1_v = acceleration(position);
+    let k2_r = velocity + k1_v * (h / 2.0);
+    let k2_v = acceleration(position + k1_r * (h / 2.0));
+    let k3_r = velocity + k2_v * (h / 2.0);
+    let k3_v = acceleration(position + k2_r * (h / 2.0));
+    let k4_r = velocity + k3_v * h;
+    let k4_v = acceleration(position + k3_r * h);
+
+    (
+        position + (k1_r + k2_r * 2.0 + k3_r * 2.0 + k4_r) * (h / 6.0),
+        velocity + (k1_v + k2_v * 2.0 + k3_v * 2.0 + k4_v) * (h / 6.0)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::math::DVec3;
+
+    use super::*;
+    use crate::test_support::LEO;
+
+    #[test]
+    fn test_circular_orbit_closes_after_one_period() {
+        let radius = 7000.0;
+        let speed = (GRAVITATIONAL_CONSTANT / radius).sqrt();
+        let period = 2.0 * std::f64::consts::PI * (radius.powi(3) / GRAVITATIONAL_CONSTANT).sqrt();
+
+        let (position, velocity) = integrate_two_body(DVec3::new(radius, 0.0, 0.0), DVec3::new(0.0, speed, 0.0), period);
+        assert_abs_diff_eq!(position.x, radius, epsilon = 0.1);
+        assert_abs_diff_eq!(position.y, 0.0, epsilon = 0.1);
+        assert_abs_diff_eq!(velocity.length(), speed, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_states_step_from_the_last_prediction() {
+        let states = FallbackStates::default();
+        let entity = Entity::from_raw(1);
+        let el = LEO.elements();
+        let distance = |a: Prediction, b: Prediction| DVec3::from_array(a.position).distance(DVec3::from_array(b.position));
+
+        //a day of ticks, each one only integrates the minute since the previous one
+        for minute in 1..=1440 {
+            states.predict(entity, &el, minute as f64).unwrap();
+        }
+        let state = states.0.lock().unwrap()[&entity].clone();
+        assert_eq!(state.minutes_since_epoch, 1440.0);
+        let stepped = states.predict(entity, &el, 1441.0).unwrap();
+        assert!(distance(stepped, fallback_prediction(&el, 1441.0).unwrap()) < 1.0);
+
+        //refreshed elements of the same satellite start over from their epoch
+        let updated = LEO.builder().mean_anomaly(100.0).build();
+        let restarted = states.predict(entity, &updated, 10.0).unwrap();
+        assert!(distance(restarted, fallback_prediction(&updated, 10.0).unwrap()) < 1e-6);
+
+        states.forget(entity);
+        assert_eq!(states.len(), 0);
+    }
+}
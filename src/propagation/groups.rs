@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+/// Group the satellite was loaded with (the `group` of [`super::LoadElements`])
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SatelliteGroup(pub String);
+
+/// Orbit color palette keyed by group, groups without an entry use the default color
+#[derive(Resource, Clone, Debug)]
+pub struct GroupColors {
+    pub colors: HashMap<String, Color>,
+    pub default: Color
+}
+
+impl Default for GroupColors {
+    fn default() -> Self {
+        Self { colors: HashMap::new(), default: Color::linear_rgb(1.0, 0.0, 0.0) }
+    }
+}
+
+impl GroupColors {
+    pub fn with(mut self, group: &str, color: impl Into<Color>) -> Self {
+        self.colors.insert(group.to_owned(), color.into());
+        self
+    }
+
+    pub fn color_for(&self, group: &str) -> Color {
+        self.colors.get(group).copied().unwrap_or(self.default)
+    }
+}
+
+/// Color the orbit of the satellite is drawn with
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct OrbitColor(pub Color);
+
+pub struct GroupColorsPlugin;
+
+impl Plugin for GroupColorsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GroupColors>()
+            .add_systems(Update, (recolor_all_orbits.run_if(resource_changed::<GroupColors>), color_new_orbits).chain());
+    }
+}
+
+fn color_new_orbits(colors: Res<GroupColors>, satellites: Query<(Entity, &SatelliteGroup), Added<SatelliteGroup>>, mut commands: Commands) {
+    for (entity, group) in satellites.iter() {
+        commands.entity(entity).insert(OrbitColor(colors.color_for(&group.0)));
+    }
+}
+
+fn recolor_all_orbits(colors: Res<GroupColors>, satellites: Query<(Entity, &SatelliteGroup)>, mut commands: Commands) {
+    for (entity, group) in satellites.iter() {
+        commands.entity(entity).insert(OrbitColor(colors.color_for(&group.0)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use bevy::{color::palettes::css::*, prelude::*};
+
+    use super::*;
+    use crate::propagation::{ElementsFormat, EpochDataLoader, LoadElements, LoadElementsPlugin, LoadedElements, OrbitalData};
+    use crate::test_support::MEO;
+
+    #[derive(Clone, Resource)]
+    struct PerGroupClient;
+
+    #[async_trait::async_trait]
+    impl EpochDataLoader for PerGroupClient {
+        type Error = Infallible;
+
+        async fn load(&self, group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+            let norad_id = if group == "galileo" { 40000 } else { 50000 };
+            Ok(vec![MEO.builder().name(group).norad_id(norad_id).build()])
+        }
+    }
+
+    #[test]
+    fn test_orbit_color_follows_group() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, LoadElementsPlugin::<PerGroupClient>::new(), GroupColorsPlugin))
+            .insert_resource(PerGroupClient)
+            .insert_resource(GroupColors::default().with("galileo", BLUE).with("gps-ops", GREEN));
+
+        for group in ["galileo", "gps-ops"] {
+            app.world_mut().send_event(LoadElements::group(group, ElementsFormat::Json));
+        }
+        let mut loaded_reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        let mut loaded = 0;
+        for _ in 0..1000 {
+            app.update();
+            loaded += loaded_reader.read(app.world().resource::<Events<LoadedElements>>()).count();
+            if loaded == 2 {
+                break;
+            }
+        }
+        app.update();
+
+        let mut colors: Vec<_> = app.world_mut().query::<(&SatelliteGroup, &OrbitColor)>().iter(app.world())
+            .map(|(group, color)| (group.0.clone(), color.0))
+            .collect();
+        colors.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(colors, vec![("galileo".to_owned(), BLUE.into()), ("gps-ops".to_owned(), GREEN.into())]);
+
+        //changing the palette recolors loaded orbits
+        app.world_mut().resource_mut::<GroupColors>().colors.insert("galileo".to_owned(), WHITE.into());
+        app.update();
+        let galileo = app.world_mut().query::<(&SatelliteGroup, &OrbitColor)>().iter(app.world())
+            .find(|(group, _)| group.0 == "galileo")
+            .map(|(_, color)| color.0);
+        assert_eq!(galileo, Some(WHITE.into()));
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use super::bevy_integration::InGameElements;
+
+/// Keeps the [`SatelliteIndex`] up to date with the spawned and despawned satellites
+pub struct SatelliteIndexPlugin;
+
+/// Satellites by NORAD id, the canonical order of everything user-visible or reproducible: exports, propagation batches.
+/// Queries iterate in archetype order, which differs between runs
+#[derive(Resource, Debug, Default)]
+pub struct SatelliteIndex {
+    //by NORAD id, then entity
+    sorted: Vec<(u64, Entity)>,
+    norad_ids: HashMap<Entity, u64>
+}
+
+impl SatelliteIndex {
+    pub fn insert(&mut self, entity: Entity, norad_id: u64) {
+        if self.norad_ids.insert(entity, norad_id).is_some() {
+            return;
+        }
+        let at = self.sorted.partition_point(|key| *key < (norad_id, entity));
+        self.sorted.insert(at, (norad_id, entity));
+    }
+
+    pub fn remove(&mut self, entities: &HashSet<Entity>) {
+        self.norad_ids.retain(|entity, _| !entities.contains(entity));
+        self.sorted.retain(|(_, entity)| !entities.contains(entity));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.sorted.iter().map(|(_, entity)| *entity)
+    }
+
+    pub fn norad_id(&self, entity: Entity) -> Option<u64> {
+        self.norad_ids.get(&entity).copied()
+    }
+
+    /// First satellite with the NORAD id
+    pub fn get(&self, norad_id: u64) -> Option<Entity> {
+        let at = self.sorted.partition_point(|(id, _)| *id < norad_id);
+        self.sorted.get(at).filter(|(id, _)| *id == norad_id).map(|(_, entity)| *entity)
+    }
+
+    /// Puts the entities in the canonical order, the ones not in the index last
+    pub fn sort(&self, entities: &mut [Entity]) {
+        entities.sort_by_key(|entity| (self.norad_id(*entity).unwrap_or(u64::MAX), *entity));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+}
+
+impl Plugin for SatelliteIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SatelliteIndex>()
+            //before the propagation batches are assembled, the loading spawns satellites later in the schedule
+            //so they are indexed in the next frame
+            .add_systems(PostUpdate, update_satellite_index);
+    }
+}
+
+pub fn ensure_satellite_index(app: &mut App) {
+    if !app.is_plugin_added::<SatelliteIndexPlugin>() {
+        app.add_plugins(SatelliteIndexPlugin);
+    }
+}
+
+pub(super) fn update_satellite_index(
+    added: Query<(Entity, &InGameElements), Added<InGameElements>>,
+    mut removed: RemovedComponents<InGameElements>,
+    mut index: ResMut<SatelliteIndex>
+) {
+    let removed: HashSet<Entity> = removed.read().collect();
+    if !removed.is_empty() {
+        index.remove(&removed);
+    }
+    for (entity, elements) in added.iter() {
+        index.insert(entity, elements.0.norad_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+    use super::super::bevy_integration::{LoadedElements, Propagate};
+    use crate::global::InGameSettings;
+    use crate::stress::starlink_like_elements;
+    use crate::test_support::{headless_app, load_group, FRAME, ScriptedClient};
+
+    //NORAD ids as loaded, as indexed and in the batches of the first propagation
+    fn run() -> (Vec<u64>, Vec<u64>, Vec<Vec<u64>>) {
+        let mut data = starlink_like_elements(25, 3);
+        //deterministically out of order
+        data.reverse();
+        data.rotate_left(7);
+        let mut app = headless_app(ScriptedClient::default().with_group("shuffled", data));
+        app.world_mut().resource_mut::<InGameSettings>().propagation.real_time_interval = FRAME * 2;
+        let norad_ids = |app: &App, entities: &[Entity]| -> Vec<u64> {
+            let index = app.world().resource::<SatelliteIndex>();
+            entities.iter().map(|e| index.norad_id(*e).unwrap()).collect()
+        };
+
+        let mut loaded_events = ManualEventReader::<LoadedElements>::default();
+        let mut batches = ManualEventReader::<Propagate>::default();
+        let entities = load_group(&mut app, "shuffled", 50);
+        //spawned at the end of the frame, indexed in the next one
+        app.update();
+        let loaded = app.world().resource::<Events<LoadedElements>>();
+        let emitted: Vec<u64> = loaded_events.read(loaded).flat_map(|loaded| loaded.data().iter().map(|el| el.norad_id)).collect();
+        assert_eq!(norad_ids(&app, &entities), emitted);
+        let indexed = norad_ids(&app, &app.world().resource::<SatelliteIndex>().iter().collect::<Vec<_>>());
+
+        let mut composition = vec![];
+        for _ in 0..4 {
+            app.update();
+            let events = app.world().resource::<Events<Propagate>>();
+            composition.extend(batches.read(events).map(|batch| batch.data.iter().map(|(_, el, _)| el.0.norad_id).collect::<Vec<_>>()));
+        }
+        (emitted, indexed, composition)
+    }
+
+    #[test]
+    fn test_satellites_are_ordered_by_norad_id() {
+        let (emitted, indexed, batches) = run();
+        let mut sorted = emitted.clone();
+        sorted.sort();
+        assert_eq!(emitted.len(), 25);
+        assert_eq!(emitted, sorted);
+        assert_eq!(indexed, sorted);
+        //batches of 10 in the same order, every propagation
+        let first: Vec<Vec<u64>> = sorted.chunks(10).map(<[u64]>::to_vec).collect();
+        assert!(batches.len() >= 3 && batches.len() % 3 == 0, "{}", batches.len());
+        for propagation in batches.chunks(3) {
+            assert_eq!(propagation, first.as_slice());
+        }
+        assert_eq!(run(), (emitted, indexed, batches));
+    }
+
+    #[test]
+    fn test_index_follows_spawns_and_despawns() {
+        let mut app = App::new();
+        app.add_plugins(SatelliteIndexPlugin);
+        let elements = starlink_like_elements(3, 1);
+        let [a, b, c] = [2, 0, 1].map(|i| app.world_mut().spawn(InGameElements(elements[i].clone())).id());
+        app.update();
+        let index = app.world().resource::<SatelliteIndex>();
+        assert_eq!(index.iter().collect::<Vec<_>>(), vec![b, c, a]);
+        assert_eq!(index.get(elements[1].norad_id), Some(c));
+        //an export of the selection in the order it was made
+        let mut selection = vec![a, Entity::PLACEHOLDER, b];
+        index.sort(&mut selection);
+        assert_eq!(selection, vec![b, a, Entity::PLACEHOLDER]);
+
+        app.world_mut().despawn(c);
+        //replaced elements of the same satellite don't add it twice
+        app.world_mut().entity_mut(a).insert(InGameElements(elements[2].clone()));
+        app.update();
+        assert_eq!(app.world().resource::<SatelliteIndex>().iter().collect::<Vec<_>>(), vec![b, a]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*
+};
+use sgp4::Elements;
+
+use crate::memory::{AccountMemory, MemoryFootprint};
+
+pub const INTERNED_ELEMENTS: DiagnosticPath = DiagnosticPath::const_new("elements_interner/entries");
+pub const ELEMENTS_INTERN_HIT_RATIO: DiagnosticPath = DiagnosticPath::const_new("elements_interner/hit_ratio");
+
+//elements sets of the same object and epoch are the same set, the epoch is keyed by its bits
+type InternKey = (u64, u64);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ElementsInternerStats {
+    pub entries: usize,
+    pub requests: u64,
+    pub hits: u64
+}
+
+/// Shares a single `Arc` between identical elements of repeated loads.
+/// Only weak references are kept, an entry lives as long as some satellite or event holds the elements
+#[derive(Resource)]
+pub struct ElementsInterner {
+    entries: HashMap<InternKey, Weak<Elements>>,
+    /// Past this many live entries new elements are passed through without being interned
+    pub max_entries: usize,
+    requests: u64,
+    hits: u64
+}
+
+impl Default for ElementsInterner {
+    fn default() -> Self {
+        Self::with_limit(100_000)
+    }
+}
+
+impl ElementsInterner {
+    pub fn with_limit(max_entries: usize) -> Self {
+        Self { entries: HashMap::new(), max_entries, requests: 0, hits: 0 }
+    }
+
+    pub fn intern(&mut self, elements: Arc<Elements>) -> Arc<Elements> {
+        self.requests += 1;
+        let key = (elements.norad_id, elements.epoch().to_bits());
+        if let Some(existing) = self.entries.get(&key).and_then(Weak::upgrade) {
+            self.hits += 1;
+            return existing;
+        }
+        if self.entries.len() >= self.max_entries {
+            self.prune();
+            if self.entries.len() >= self.max_entries {
+                return elements;
+            }
+        }
+        self.entries.insert(key, Arc::downgrade(&elements));
+        elements
+    }
+
+    //drops entries of elements nobody holds anymore
+    pub fn prune(&mut self) {
+        self.entries.retain(|_, elements| elements.strong_count() > 0);
+    }
+
+    pub fn stats(&self) -> ElementsInternerStats {
+        ElementsInternerStats { entries: self.entries.len(), requests: self.requests, hits: self.hits }
+    }
+}
+
+impl MemoryFootprint for ElementsInterner {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.entries.len() * size_of::<(InternKey, Weak<Elements>)>()
+    }
+}
+
+pub struct ElementsInternerPlugin;
+
+impl Plugin for ElementsInternerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ElementsInterner>()
+            .account_resource::<ElementsInterner>("element_cache")
+            .register_diagnostic(Diagnostic::new(INTERNED_ELEMENTS))
+            .register_diagnostic(Diagnostic::new(ELEMENTS_INTERN_HIT_RATIO))
+            .add_systems(Last, report_elements_interner);
+    }
+}
+
+fn report_elements_interner(interner: Res<ElementsInterner>, mut diagnostics: Diagnostics) {
+    let stats = interner.stats();
+    diagnostics.add_measurement(&INTERNED_ELEMENTS, || stats.entries as f64);
+    if stats.requests > 0 {
+        diagnostics.add_measurement(&ELEMENTS_INTERN_HIT_RATIO, || stats.hits as f64 / stats.requests as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::propagation::{DataSource, ElementsFormat, EpochDataLoader, InGameElements, LoadElements, LoadElementsPlugin, LoadedElements, OrbitalData};
+    use crate::test_support::LEO;
+
+    //every load deserializes fresh copies of the same sets, like a live tracker polling a group
+    #[derive(Clone, Resource)]
+    struct ReloadingClient;
+
+    fn elements(norad_id: u64) -> Arc<Elements> {
+        LEO.builder().name(format!("SAT-{norad_id}")).norad_id(norad_id).build()
+    }
+
+    #[async_trait::async_trait]
+    impl EpochDataLoader for ReloadingClient {
+        type Error = Infallible;
+
+        async fn load(&self, _group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+            Ok((1..=3).map(elements).collect())
+        }
+    }
+
+    fn load(app: &mut App, source: DataSource) -> OrbitalData {
+        app.world_mut().send_event(LoadElements { groups: vec!["starlink".to_owned()], format: ElementsFormat::Json, source, launch_animation: false, refresh: false });
+        let mut reader = app.world().resource::<Events<LoadedElements>>().get_reader_current();
+        for _ in 0..20 {
+            app.update();
+            let events = app.world().resource::<Events<LoadedElements>>();
+            if let Some(loaded) = reader.read(events).next() {
+                return loaded.data().clone();
+            }
+        }
+        panic!("elements not loaded");
+    }
+
+    #[test]
+    fn test_interner_limit() {
+        let mut interner = ElementsInterner::with_limit(2);
+        let (a, b) = (elements(1), elements(2));
+        interner.intern(a.clone());
+        interner.intern(b.clone());
+        let c = elements(3);
+        //full of live entries, passed through
+        assert!(Arc::ptr_eq(&interner.intern(c.clone()), &c));
+        assert!(!Arc::ptr_eq(&interner.intern(elements(3)), &c));
+        //dead entries make room
+        drop(a);
+        interner.intern(c.clone());
+        assert!(Arc::ptr_eq(&interner.intern(elements(3)), &c));
+        assert_eq!(interner.stats(), ElementsInternerStats { entries: 2, requests: 6, hits: 1 });
+    }
+
+    #[test]
+    fn test_reload_reuses_interned_elements() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, HierarchyPlugin, ElementsInternerPlugin, LoadElementsPlugin::<ReloadingClient>::new()))
+            .insert_resource(ReloadingClient);
+        let first = load(&mut app, DataSource::Gp);
+        //the supplemental sets replace the loaded ones, with identical data
+        let second = load(&mut app, DataSource::Supplemental);
+
+        assert_eq!(first.len(), 3);
+        assert_eq!(second.len(), 3);
+        for (a, b) in first.iter().zip(&second) {
+            assert!(Arc::ptr_eq(a, b));
+        }
+        for elements in app.world_mut().query::<&InGameElements>().iter(app.world()) {
+            assert!(first.iter().any(|el| Arc::ptr_eq(el, &elements.0)));
+        }
+        let stats = app.world().resource::<ElementsInterner>().stats();
+        assert_eq!((stats.entries, stats.requests, stats.hits), (3, 6, 3));
+    }
+}
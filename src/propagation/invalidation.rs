@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::orbit::SatelliteOrbit;
+
+use super::bevy_integration::{predict_at, InGameElements, PropagatableDuration, PropagationStatus, Propageted};
+use super::classification::OrbitClassification;
+use super::transitions::Despawning;
+use super::unload::PendingUnload;
+
+//a re-propagation pass is spread over at most this many frames
+const MAX_FRAMES: usize = 3;
+
+//satellites being removed are left as they are
+type Invalidated = (With<InGameElements>, Without<Despawning>, Without<PendingUnload>);
+
+/// Re-propagates every satellite right away when a settings change leaves the displayed state stale,
+/// instead of waiting for the next scheduled propagation
+pub struct InvalidationPlugin;
+
+/// What changed, handlers only redo the work the reason requires
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidationReason {
+    /// `InGameSettings::scale` changed, world positions are stale
+    Scale { previous: f32, current: f32 },
+    /// Simulation time moved discontinuously, positions and histories are stale
+    TimeJump,
+    /// The inertial frame or its mapping to the world changed, orbit frames are stale as well
+    FrameConvention,
+    /// Gravitational constants changed, orbits and their classifications are stale as well
+    CentralBody,
+    /// Elements of a single satellite were replaced (an edit or a maneuver), only its derived state is stale
+    Elements(Entity),
+    /// A single satellite was left out of the propagation for a while (a restored unload), only its position is stale
+    Resumed(Entity)
+}
+
+impl InvalidationReason {
+    /// Orbits and everything derived from them have to be rebuilt from the elements
+    pub fn reshapes_orbits(&self) -> bool {
+        matches!(self, Self::FrameConvention | Self::CentralBody | Self::Elements(_))
+    }
+}
+
+/// Sent by any system changing settings the derived state depends on
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct InvalidateDerivedState {
+    pub reason: InvalidationReason
+}
+
+/// Satellite waiting in the re-propagation pass
+#[derive(Component, Debug)]
+pub struct DerivedStateDirty {
+    rebuild_orbit: bool
+}
+
+#[derive(Resource, Default)]
+struct RepropagationQueue {
+    pending: VecDeque<Entity>,
+    per_frame: usize
+}
+
+impl Plugin for InvalidationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RepropagationQueue>()
+            .add_event::<InvalidateDerivedState>()
+            .add_event::<Propageted>()
+            .register_command(
+                CommandDescriptor::new("Set scale", ActionCategory::General, |params, world| {
+                    let Some(current) = params[0].as_number().map(|s| s as f32).filter(|s| *s > 0.0) else {
+                        return;
+                    };
+                    let Some(mut settings) = world.get_resource_mut::<InGameSettings>() else {
+                        return;
+                    };
+                    let previous = std::mem::replace(&mut settings.scale, current);
+                    world.send_event(InvalidateDerivedState { reason: InvalidationReason::Scale { previous, current } });
+                })
+                .with_param("scale", ParamKind::Number)
+            )
+            .add_systems(PostUpdate, mark_dirty)
+            .add_systems(PreUpdate, repropagate_dirty.run_if(resource_exists::<InGameSettings>));
+    }
+}
+
+fn mark_dirty(
+    mut events: EventReader<InvalidateDerivedState>,
+    satellites: Query<Entity, Invalidated>,
+    dirty: Query<&DerivedStateDirty>,
+    mut queue: ResMut<RepropagationQueue>,
+    mut commands: Commands
+) {
+    //`None` when only single satellites were edited or resumed
+    let mut everything = None;
+    let mut edited = vec![];
+    for event in events.read() {
+        match event.reason {
+            InvalidationReason::Elements(entity) => edited.push((entity, true)),
+            InvalidationReason::Resumed(entity) => edited.push((entity, false)),
+            reason => everything = Some(everything.unwrap_or(false) || reason.reshapes_orbits())
+        }
+    }
+    if everything.is_none() && edited.is_empty() {
+        return;
+    }
+    let targets: Vec<_> = match everything {
+        Some(rebuild_orbit) => {
+            queue.pending.clear();
+            satellites.iter().map(|e| (e, rebuild_orbit || edited.contains(&(e, true)))).collect()
+        },
+        None => edited.into_iter().filter(|(e, _)| satellites.contains(*e)).collect()
+    };
+    for (entity, rebuild_orbit) in targets {
+        //a pass still in progress may have been rebuilding the orbits already
+        let rebuild_orbit = rebuild_orbit || dirty.get(entity).is_ok_and(|d| d.rebuild_orbit);
+        commands.entity(entity).insert(DerivedStateDirty { rebuild_orbit });
+        if everything.is_some() || !queue.pending.contains(&entity) {
+            queue.pending.push_back(entity);
+        }
+    }
+    queue.per_frame = queue.pending.len().div_ceil(MAX_FRAMES);
+}
+
+//blocking like the initial propagation, but bounded per frame
+fn repropagate_dirty(
+    mut queue: ResMut<RepropagationQueue>,
+    mut satellites: Query<(&InGameElements, &PropagatableDuration, &mut PropagationStatus, &DerivedStateDirty)>,
+    mut propagated: EventWriter<Propageted>,
+    settings: Res<InGameSettings>,
+    mut commands: Commands
+) {
+    let mut data = vec![];
+    let count = queue.per_frame.min(queue.pending.len());
+    for entity in queue.pending.drain(..count) {
+        let Ok((elements, duration, mut status, dirty)) = satellites.get_mut(entity) else {
+            continue;
+        };
+        if dirty.rebuild_orbit {
+            let orbit = SatelliteOrbit::from(elements.0.as_ref());
+            commands.entity(entity).insert((OrbitClassification::new(&elements.0, &orbit), orbit));
+        }
+        commands.entity(entity).remove::<DerivedStateDirty>();
+        //a satellite without a prediction snaps to the next one, no smoothing or interpolation from the stale state
+        *status = PropagationStatus::NotPropagated;
+        if let Some(prediction) = predict_at(&elements.0, duration.minutes_since_epoch(), settings.propagation.numeric_fallback) {
+            data.push((entity, prediction));
+        }
+    }
+    if !data.is_empty() {
+        propagated.send(Propageted::new(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::commands::{CommandsPlugin, InvokeCommand};
+    use crate::global::{CorrectionSmoothing, PropagationSettings};
+    use crate::propagation::bevy_integration::{PropagatableSattelite, PropagateInGamePlugin};
+    use crate::stress::starlink_like_elements;
+    use crate::world_frame::WORLD_FRAME;
+
+    fn app(count: usize) -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, CommandsPlugin, PropagateInGamePlugin, InvalidationPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { real_time_interval: Duration::from_secs(3600), smoothing: Some(CorrectionSmoothing { max_jump_km: 1.0, frames: 30 }), ..default() },
+                ..default()
+            });
+        let entities = starlink_like_elements(count, 1477).into_iter()
+            .map(|el| app.world_mut().spawn((PropagatableSattelite::new(InGameElements(el)), Transform::default())).id())
+            .collect();
+        app.update();
+        (app, entities)
+    }
+
+    //distance of the displayed position from the prediction at the satellite's time under the current settings, in km
+    fn worst_offset(app: &App, entities: &[Entity]) -> f32 {
+        let scale = app.world().resource::<InGameSettings>().scale;
+        entities.iter().map(|e| {
+            let elements = &app.world().get::<InGameElements>(*e).unwrap().0;
+            let minutes = app.world().get::<PropagatableDuration>(*e).unwrap().minutes_since_epoch();
+            let position = predict_at(elements, minutes, false).unwrap().position.map(|c| c as f32);
+            let expected = WORLD_FRAME.to_world(Vec3::from_array(position));
+            (app.world().get::<Transform>(*e).unwrap().translation / scale).distance(expected)
+        }).fold(0.0, f32::max)
+    }
+
+    fn invalidate(app: &mut App, entities: &[Entity], reason: InvalidationReason) {
+        app.world_mut().send_event(InvalidateDerivedState { reason });
+        app.update();
+        for frame in 1..=MAX_FRAMES {
+            app.update();
+            let dirty = app.world_mut().query::<&DerivedStateDirty>().iter(app.world()).count();
+            assert!(frame < MAX_FRAMES || dirty == 0, "{dirty} still dirty after {frame} frames");
+        }
+        assert!(worst_offset(app, entities) < 1.0, "{reason:?}");
+    }
+
+    #[test]
+    fn test_every_reason_repropagates_within_frames() {
+        let (mut app, entities) = app(30);
+        invalidate(&mut app, &entities, InvalidationReason::TimeJump);
+
+        app.world_mut().resource_mut::<InGameSettings>().scale = 0.02;
+        invalidate(&mut app, &entities, InvalidationReason::Scale { previous: 0.01, current: 0.02 });
+
+        for e in &entities {
+            app.world_mut().get_mut::<PropagatableDuration>(*e).unwrap().0 += Duration::from_secs(90 * 60);
+        }
+        invalidate(&mut app, &entities, InvalidationReason::TimeJump);
+
+        for reason in [InvalidationReason::FrameConvention, InvalidationReason::CentralBody] {
+            for e in &entities {
+                app.world_mut().get_mut::<SatelliteOrbit>(*e).unwrap().semi_major_axis = 0.0;
+            }
+            invalidate(&mut app, &entities, reason);
+            for e in &entities {
+                let expected = SatelliteOrbit::from(app.world().get::<InGameElements>(*e).unwrap().0.as_ref());
+                assert_eq!(app.world().get::<SatelliteOrbit>(*e), Some(&expected), "{reason:?}");
+            }
+        }
+
+        //only the edited satellite is rebuilt
+        for e in &entities {
+            app.world_mut().get_mut::<SatelliteOrbit>(*e).unwrap().semi_major_axis = 0.0;
+        }
+        invalidate(&mut app, &entities, InvalidationReason::Elements(entities[0]));
+        assert_ne!(app.world().get::<SatelliteOrbit>(entities[0]).unwrap().semi_major_axis, 0.0);
+        assert_eq!(app.world().get::<SatelliteOrbit>(entities[1]).unwrap().semi_major_axis, 0.0);
+    }
+
+    #[test]
+    fn test_scale_command_rescales_satellites() {
+        let (mut app, entities) = app(5);
+        app.world_mut().get_mut::<SatelliteOrbit>(entities[0]).unwrap().semi_major_axis = 0.0;
+        app.world_mut().send_event(InvokeCommand { name: "Set scale".to_owned(), arguments: vec!["0.05".to_owned()] });
+        for _ in 0..=MAX_FRAMES + 1 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<InGameSettings>().scale, 0.05);
+        assert!(worst_offset(&app, &entities) < 1.0);
+        //orbits are not rebuilt, they don't depend on the scale
+        assert_eq!(app.world().get::<SatelliteOrbit>(entities[0]).unwrap().semi_major_axis, 0.0);
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use super::bevy_integration::{GroupLoadStatus, LoadStatus};
+
+/// Shows a "loading <group>…" text for every group that is still pending
+pub struct LoadingPlaceholderPlugin;
+
+#[derive(Component, Debug)]
+pub struct LoadingPlaceholder {
+    pub group: String
+}
+
+impl Plugin for LoadingPlaceholderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GroupLoadStatus>()
+            .add_systems(Update, sync_loading_placeholders.run_if(resource_changed::<GroupLoadStatus>));
+    }
+}
+
+fn sync_loading_placeholders(
+    status: Res<GroupLoadStatus>,
+    placeholders: Query<(Entity, &LoadingPlaceholder)>,
+    mut commands: Commands
+) {
+    for (entity, placeholder) in placeholders.iter() {
+        if status.get(&placeholder.group) != Some(LoadStatus::Pending) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let pending = status.iter().filter(|(_, s)| **s == LoadStatus::Pending).map(|(group, _)| group);
+    for (index, group) in pending.enumerate() {
+        if placeholders.iter().any(|(_, p)| &p.group == group) {
+            continue;
+        }
+        commands.spawn((
+            TextBundle::from_section(format!("loading {group}…"), TextStyle { font_size: 18.0, ..default() })
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(12.0 + 22.0 * index as f32),
+                    left: Val::Percent(45.0),
+                    ..default()
+                }),
+            LoadingPlaceholder { group: group.clone() }
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    fn placeholders(app: &mut App) -> Vec<String> {
+        app.world_mut().query::<&LoadingPlaceholder>().iter(app.world()).map(|p| p.group.clone()).collect()
+    }
+
+    #[test]
+    fn test_placeholder_follows_load_status() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, HierarchyPlugin, LoadingPlaceholderPlugin));
+
+        app.world_mut().resource_mut::<GroupLoadStatus>().set("galileo".to_owned(), LoadStatus::Pending);
+        app.update();
+        assert_eq!(placeholders(&mut app), vec!["galileo".to_owned()]);
+
+        //unrelated updates keep a single placeholder
+        app.update();
+        assert_eq!(placeholders(&mut app).len(), 1);
+
+        app.world_mut().resource_mut::<GroupLoadStatus>().set("galileo".to_owned(), LoadStatus::Loaded);
+        app.update();
+        assert!(placeholders(&mut app).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages}
+};
+
+pub const MARKER_MESHES: DiagnosticPath = DiagnosticPath::const_new("marker_mesh_cache/meshes");
+pub const MARKER_MESH_HIT_RATIO: DiagnosticPath = DiagnosticPath::const_new("marker_mesh_cache/hit_ratio");
+
+//radii are snapped to quarter steps of a power of two ladder, so nearby sizes share a mesh
+const STEPS_PER_DOUBLING: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MarkerShape {
+    #[default]
+    Sphere,
+    /// Low-poly, cheap for very large constellations
+    Octahedron,
+    /// Box body with a solar panel
+    BoxSat
+}
+
+/// Visual size class of a satellite marker, satellites without it use [`MarkerSize::default`]
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct MarkerSize {
+    pub shape: MarkerShape,
+    pub radius: f32
+}
+
+impl Default for MarkerSize {
+    fn default() -> Self {
+        Self { shape: MarkerShape::Sphere, radius: 1.5 }
+    }
+}
+
+impl MarkerSize {
+    pub fn new(shape: MarkerShape, radius: f32) -> Self {
+        Self { shape, radius }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct MarkerMeshKey {
+    shape: MarkerShape,
+    step: i32
+}
+
+impl MarkerMeshKey {
+    fn new(size: MarkerSize) -> Self {
+        let step = (size.radius.max(f32::EPSILON).log2() * STEPS_PER_DOUBLING).round() as i32;
+        Self { shape: size.shape, step }
+    }
+
+    fn radius(&self) -> f32 {
+        (self.step as f32 / STEPS_PER_DOUBLING).exp2()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarkerMeshCacheStats {
+    pub meshes: usize,
+    pub requests: u64,
+    pub hits: u64
+}
+
+/// Shared marker meshes, created lazily once per shape and quantized radius
+#[derive(Resource, Default)]
+pub struct MarkerMeshCache {
+    meshes: HashMap<MarkerMeshKey, Handle<Mesh>>,
+    requests: u64,
+    hits: u64
+}
+
+impl MarkerMeshCache {
+    pub fn get_or_create(&mut self, size: MarkerSize, meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+        self.requests += 1;
+        let key = MarkerMeshKey::new(size);
+        if let Some(handle) = self.meshes.get(&key) {
+            self.hits += 1;
+            return handle.clone();
+        }
+        let handle = meshes.add(marker_mesh(key.shape, key.radius()));
+        self.meshes.insert(key, handle.clone());
+        handle
+    }
+
+    pub fn stats(&self) -> MarkerMeshCacheStats {
+        MarkerMeshCacheStats { meshes: self.meshes.len(), requests: self.requests, hits: self.hits }
+    }
+}
+
+fn marker_mesh(shape: MarkerShape, radius: f32) -> Mesh {
+    match shape {
+        MarkerShape::Sphere => Sphere { radius }.mesh().build(),
+        MarkerShape::Octahedron => octahedron(radius),
+        MarkerShape::BoxSat => {
+            let mut body = Cuboid::new(radius, radius, radius).mesh().build();
+            let panel = Cuboid::new(radius * 3.0, radius * 0.05, radius * 0.8).mesh().build();
+            body.merge(&panel);
+            body
+        }
+    }
+}
+
+fn octahedron(radius: f32) -> Mesh {
+    let [x, y, z] = [Vec3::X * radius, Vec3::Y * radius, Vec3::Z * radius];
+    let mut positions = vec![];
+    for (a, b) in [(x, z), (z, -x), (-x, -z), (-z, x)] {
+        positions.extend([b, a, y, a, b, -y]);
+    }
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_computed_flat_normals()
+}
+
+pub struct MarkerMeshCachePlugin;
+
+impl Plugin for MarkerMeshCachePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MarkerMeshCache>()
+            .register_diagnostic(Diagnostic::new(MARKER_MESHES))
+            .register_diagnostic(Diagnostic::new(MARKER_MESH_HIT_RATIO))
+            .add_systems(Last, report_marker_mesh_cache);
+    }
+}
+
+fn report_marker_mesh_cache(cache: Res<MarkerMeshCache>, mut diagnostics: Diagnostics) {
+    let stats = cache.stats();
+    diagnostics.add_measurement(&MARKER_MESHES, || stats.meshes as f64);
+    if stats.requests > 0 {
+        diagnostics.add_measurement(&MARKER_MESH_HIT_RATIO, || stats.hits as f64 / stats.requests as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::global::{InGameSettings, PropagationSettings};
+    use crate::propagation::{ElementsFormat, LoadElements, LoadElementsPlugin, LoadedElements};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    #[test]
+    fn test_quantized_radii_share_meshes() {
+        let mut meshes = Assets::<Mesh>::default();
+        let mut cache = MarkerMeshCache::default();
+        let a = cache.get_or_create(MarkerSize::new(MarkerShape::Sphere, 1.5), &mut meshes);
+        let b = cache.get_or_create(MarkerSize::new(MarkerShape::Sphere, 1.52), &mut meshes);
+        let c = cache.get_or_create(MarkerSize::new(MarkerShape::Octahedron, 1.5), &mut meshes);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(cache.stats(), MarkerMeshCacheStats { meshes: 2, requests: 3, hits: 1 });
+        assert_eq!(meshes.len(), 2);
+    }
+
+    #[test]
+    fn test_thousand_satellites_in_three_size_classes() {
+        let sizes = [
+            MarkerSize::default(),
+            MarkerSize::new(MarkerShape::Sphere, 3.0),
+            MarkerSize::new(MarkerShape::BoxSat, 1.5)
+        ];
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, MarkerMeshCachePlugin))
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .add_plugins(LoadElementsPlugin::<SyntheticClient>::new().with_spawn_hook(move |elements, commands| {
+                commands.insert(sizes[elements.norad_id as usize % 3]);
+            }))
+            .insert_resource(SyntheticClient(starlink_like_elements(1000, 1457)))
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { batch_size: 100, ..default() },
+                ..default()
+            });
+        app.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+
+        let mut loaded = 0;
+        for _ in 0..100 {
+            app.update();
+            loaded += app.world().resource::<Events<LoadedElements>>().get_reader().read(app.world().resource::<Events<LoadedElements>>()).map(|ev| ev.entities().len()).sum::<usize>();
+            if loaded > 0 {
+                break;
+            }
+        }
+        app.update();
+
+        assert_eq!(loaded, 1000);
+        let with_mesh = app.world_mut().query::<&Handle<Mesh>>().iter(app.world()).count();
+        assert_eq!(with_mesh, 1000);
+        assert_eq!(app.world().resource::<Assets<Mesh>>().len(), 3);
+        assert_eq!(app.world().resource::<MarkerMeshCache>().stats().meshes, 3);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use super::bevy_integration::SateliteDisplayData;
+
+/// Layers of a [`MarkerStyle`], in priority order: later layers are applied on top of earlier ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StyleLayer {
+    Theme,
+    Band,
+    Filter,
+    Illumination,
+    Staleness,
+    Watchlist,
+    Override,
+    Selected,
+    Hover
+}
+
+/// Modification of the marker look, every part is optional
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StyleModifier {
+    /// Replaces the base color, the highest priority layer with a base color wins
+    pub base_color: Option<Color>,
+    /// Multiplies the color, tints of all layers are combined
+    pub tint: Option<Color>,
+    /// Emissive strength added on top, relative to the resolved color
+    pub emissive_boost: f32,
+    /// Multiplies the opacity
+    pub alpha: Option<f32>
+}
+
+impl StyleModifier {
+    pub fn base(color: impl Into<Color>) -> Self {
+        Self { base_color: Some(color.into()), ..default() }
+    }
+
+    pub fn tint(color: impl Into<Color>) -> Self {
+        Self { tint: Some(color.into()), ..default() }
+    }
+
+    pub fn glow(emissive_boost: f32) -> Self {
+        Self { emissive_boost, ..default() }
+    }
+
+    pub fn alpha(alpha: f32) -> Self {
+        Self { alpha: Some(alpha), ..default() }
+    }
+}
+
+/// Active style modifiers of a satellite marker, features set and clear their own layer
+/// and never touch the material, the resolver composes all of them
+#[derive(Component, Clone, Debug, Default)]
+pub struct MarkerStyle {
+    layers: BTreeMap<StyleLayer, StyleModifier>
+}
+
+impl MarkerStyle {
+    pub fn set(&mut self, layer: StyleLayer, modifier: StyleModifier) {
+        self.layers.insert(layer, modifier);
+    }
+
+    pub fn clear(&mut self, layer: StyleLayer) {
+        self.layers.remove(&layer);
+    }
+
+    pub fn get(&self, layer: StyleLayer) -> Option<&StyleModifier> {
+        self.layers.get(&layer)
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn resolve(&self, base: Color) -> ResolvedStyle {
+        compose(base, self.layers.values())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResolvedStyle {
+    pub color: LinearRgba,
+    pub emissive: LinearRgba
+}
+
+/// Composes modifiers given in priority order (lowest first)
+pub fn compose<'a>(base: Color, modifiers: impl IntoIterator<Item = &'a StyleModifier>) -> ResolvedStyle {
+    let mut base = base.to_linear();
+    let mut tint = LinearRgba::WHITE;
+    let mut emissive_boost = 0.0;
+    let mut alpha = 1.0;
+    for modifier in modifiers {
+        if let Some(color) = modifier.base_color {
+            base = color.to_linear();
+        }
+        if let Some(color) = modifier.tint {
+            let color = color.to_linear();
+            tint = LinearRgba::rgb(tint.red * color.red, tint.green * color.green, tint.blue * color.blue);
+        }
+        emissive_boost += modifier.emissive_boost;
+        alpha *= modifier.alpha.unwrap_or(1.0);
+    }
+
+    let color = LinearRgba::new(base.red * tint.red, base.green * tint.green, base.blue * tint.blue, base.alpha * alpha);
+    ResolvedStyle {
+        color,
+        emissive: LinearRgba::rgb(color.red * emissive_boost, color.green * emissive_boost, color.blue * emissive_boost)
+    }
+}
+
+//material owned by a single marker, created the first time its style differs from the default
+#[derive(Component)]
+struct MarkerMaterial(Handle<StandardMaterial>);
+
+pub struct MarkerStylePlugin;
+
+impl Plugin for MarkerStylePlugin {
+    fn build(&self, app: &mut App) {
+        let rendering_condition = resource_exists::<Assets<StandardMaterial>>.and_then(resource_exists::<SateliteDisplayData>);
+        app.add_systems(PostUpdate, resolve_marker_styles.run_if(rendering_condition));
+    }
+}
+
+type StyledMarker<'a> = (Entity, &'a MarkerStyle, &'a mut Handle<StandardMaterial>, Option<&'a MarkerMaterial>);
+//the marker material is inserted after spawning, a style set before that is resolved once it arrives
+type Restyled = Or<(Changed<MarkerStyle>, Added<Handle<StandardMaterial>>)>;
+
+fn resolve_marker_styles(
+    display_data: Res<SateliteDisplayData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut markers: Query<StyledMarker, Restyled>,
+    mut commands: Commands
+) {
+    let Some(base) = materials.get(&display_data.material).map(|m| m.base_color) else {
+        return;
+    };
+    for (entity, style, mut material, owned) in markers.iter_mut() {
+        if style.is_default() {
+            *material = display_data.material.clone();
+            continue;
+        }
+
+        let resolved = style.resolve(base);
+        let handle = match owned {
+            Some(MarkerMaterial(handle)) => handle.clone(),
+            None => {
+                let handle = materials.add(StandardMaterial::default());
+                commands.entity(entity).insert(MarkerMaterial(handle.clone()));
+                handle
+            }
+        };
+        if let Some(owned_material) = materials.get_mut(&handle) {
+            owned_material.base_color = resolved.color.into();
+            owned_material.emissive = resolved.emissive;
+            owned_material.alpha_mode = if resolved.color.alpha < 1.0 { AlphaMode::Blend } else { AlphaMode::Opaque };
+        }
+        *material = handle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{color::palettes::css::*, prelude::*};
+
+    use super::*;
+
+    #[test]
+    fn test_composition_priorities() {
+        let base = Color::WHITE;
+        assert_eq!(compose(base, []).color, LinearRgba::WHITE);
+
+        //the highest priority base color wins, tints multiply
+        let mut style = MarkerStyle::default();
+        style.set(StyleLayer::Selected, StyleModifier::base(BLUE));
+        style.set(StyleLayer::Band, StyleModifier::base(RED));
+        style.set(StyleLayer::Illumination, StyleModifier::tint(Color::linear_rgb(0.5, 0.5, 0.5)));
+        let resolved = style.resolve(base);
+        assert_eq!(resolved.color, LinearRgba::rgb(0.0, 0.0, 0.5));
+        assert_eq!(resolved.emissive, LinearRgba::rgb(0.0, 0.0, 0.0));
+
+        style.set(StyleLayer::Hover, StyleModifier::glow(2.0));
+        style.set(StyleLayer::Filter, StyleModifier::alpha(0.25));
+        let resolved = style.resolve(base);
+        assert_eq!(resolved.emissive, LinearRgba::rgb(0.0, 0.0, 1.0));
+        assert_eq!(resolved.color.alpha, 0.25);
+    }
+
+    #[test]
+    fn test_clearing_hover_restores_selected_look() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, MarkerStylePlugin))
+            .init_resource::<Assets<StandardMaterial>>();
+        let shared = app.world_mut().resource_mut::<Assets<StandardMaterial>>().add(Color::WHITE);
+        let mesh = Handle::<Mesh>::default();
+        app.insert_resource(SateliteDisplayData { mesh, material: shared.clone() });
+        let entity = app.world_mut().spawn((MarkerStyle::default(), shared.clone())).id();
+
+        let color = |app: &App| {
+            let handle = app.world().get::<Handle<StandardMaterial>>(entity).unwrap();
+            let material = app.world().resource::<Assets<StandardMaterial>>().get(handle).unwrap();
+            (material.base_color.to_linear(), material.emissive)
+        };
+        let set_style = |app: &mut App, f: &dyn Fn(&mut MarkerStyle)| {
+            f(&mut app.world_mut().get_mut::<MarkerStyle>(entity).unwrap());
+            app.update();
+        };
+
+        set_style(&mut app, &|s| s.set(StyleLayer::Selected, StyleModifier { base_color: Some(SKY_BLUE.into()), emissive_boost: 0.3, ..default() }));
+        let selected = color(&app);
+        assert_eq!(selected.0, Color::from(SKY_BLUE).to_linear());
+
+        set_style(&mut app, &|s| s.set(StyleLayer::Hover, StyleModifier::glow(1.0)));
+        assert_ne!(color(&app), selected);
+
+        set_style(&mut app, &|s| s.clear(StyleLayer::Hover));
+        assert_eq!(color(&app), selected);
+
+        set_style(&mut app, &|s| s.clear(StyleLayer::Selected));
+        assert_eq!(app.world().get::<Handle<StandardMaterial>>(entity), Some(&shared));
+    }
+}
@@ -1,5 +1,50 @@
 mod client;
 mod bevy_integration;
+mod bands;
+mod classification;
+mod fallback;
+mod groups;
+mod loading_indicator;
+mod validation;
+mod revolutions;
+mod marker_style;
+mod time_of_interest;
+mod marker_mesh;
+mod provenance;
+mod interning;
+mod conjunction;
+mod derived_cache;
+mod residuals;
+mod transitions;
+mod progressive_visuals;
+mod invalidation;
+mod refresh;
+mod unload;
+mod index;
 
-pub use client::{EpochDataLoader, OrbitalData, DefaultClient, ConstFileClient};
-pub use bevy_integration::{LoadElementsPlugin, PropagateElementsPlugin, PropagateInGamePlugin, LoadElements, LoadedElements, Propageted};
\ No newline at end of file
+pub use client::{EpochDataLoader, OrbitalData, InjectedOnly, CachingClient, PartialSets, DataSource, ElementsFormat, ElementsStream, CsvElements, celestrak_url, parse_csv, parse_tles};
+#[cfg(feature = "network")]
+pub use client::{DefaultClient, ResumableDownload, RetryPolicy};
+#[cfg(feature = "file-loader")]
+pub use client::ConstFileClient;
+pub use bevy_integration::{LoadElementsPlugin, PropagateElementsPlugin, PropagateInGamePlugin, LoadElements, LoadedElements, ElementsFetched, LoadFailed, InGameElements, SatelliteId, Propageted, GroupLoadStatus, LoadStatus, SatelliteSpawned, SpawnHook, SpawnPlacement, FallbackPropagated, PropagatableDuration, ElementsDiff, predict_at};
+pub use bands::{EARTH_RADIUS_KM, AltitudeBandsPlugin, AltitudeBands, AltitudeBandMembership, AddAltitudeBand, EnteredBand, LeftBand, OverlappingBands};
+pub use loading_indicator::{LoadingPlaceholderPlugin, LoadingPlaceholder};
+pub use classification::{ElementsExt, OrbitClass, OrbitClassification};
+pub use validation::{is_plausible_prediction, PredictionFailures, Unreliable, BecameUnreliable, StalePrediction, LostTrack, TrackLost, StrictTransformsPlugin, StrictTransforms, LastValidTranslation};
+pub use groups::{SatelliteGroup, GroupColors, GroupColorsPlugin, OrbitColor};
+pub use revolutions::{RevolutionCounter, crosses_ascending_node};
+pub use marker_style::{MarkerStylePlugin, MarkerStyle, StyleLayer, StyleModifier, ResolvedStyle, compose};
+pub use time_of_interest::{TimeOfInterestPlugin, TimeOfInterest, Ghost};
+pub use marker_mesh::{MarkerMeshCachePlugin, MarkerMeshCache, MarkerMeshCacheStats, MarkerShape, MarkerSize, MARKER_MESHES, MARKER_MESH_HIT_RATIO};
+pub use provenance::{Provenance, SourcePrecedence, Resolution};
+pub use interning::{ElementsInternerPlugin, ElementsInterner, ElementsInternerStats, INTERNED_ELEMENTS, ELEMENTS_INTERN_HIT_RATIO};
+pub use conjunction::{ConjunctionScreeningPlugin, ConjunctionSettings, ConjunctionScreener, CloseApproach, Approach, refine_closest_approach};
+pub use derived_cache::{DerivedDataCache, DerivedRecord, DerivedData, DerivedCacheError, CacheLookup, source_hash};
+pub use residuals::{UpdateResidualsPlugin, ElementUpdateResidual, update_residual};
+pub use transitions::{SatelliteTransitionsPlugin, TransitionSettings, DespawnSatellite, SpawningIn, Despawning, advance_transition, spawn_scale, despawn_scale};
+pub use progressive_visuals::{ProgressiveVisualsPlugin, ProgressiveVisuals, PointVisual, FullVisual};
+pub use invalidation::{InvalidationPlugin, InvalidateDerivedState, InvalidationReason, DerivedStateDirty};
+pub use refresh::{ElementsRefreshPlugin, RefreshSettings, RefreshConsent, RefreshScheduler, GroupRefresh, RefreshState, RefreshStep, RefreshPrompted, ApproveRefresh};
+pub use index::{SatelliteIndexPlugin, SatelliteIndex, ensure_satellite_index};
+pub use unload::{GroupUnloadPlugin, UnloadSettings, UnloadGroup, RestoreGroup, GroupUnloaded, PendingUnload, GroupUnloads};
@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::camera::OverlayCamera;
+use crate::floating_origin::FloatingOrigin;
+use crate::selection::{HoveredSatellite, SelectionSet, Watchlist};
+
+use super::bevy_integration::{InGameElements, SateliteDisplayData};
+use super::marker_mesh::{MarkerMeshCache, MarkerSize};
+use super::validation::Unreliable;
+
+//relevance is left a bit further than it's entered, a satellite at the threshold doesn't flip every frame
+const DEMOTION_HYSTERESIS: f32 = 1.2;
+
+/// Loaded satellites start as points drawn in one batch, full visuals (mesh, material) are only materialized
+/// for satellites close to the camera, focused, watchlisted or hovered, and dropped again once they aren't
+pub struct ProgressiveVisualsPlugin;
+
+/// Presence of the resource makes loaded satellites spawn as [`PointVisual`]s
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ProgressiveVisuals {
+    /// Satellites closer to the camera are materialized, in world units
+    pub distance: f32,
+    /// Satellites materialized per frame for being close, focused, watchlisted and hovered ones don't wait for it
+    pub budget: usize,
+    pub point_color: Color,
+    /// Half the extent of a point, in world units
+    pub point_size: f32
+}
+
+impl Default for ProgressiveVisuals {
+    fn default() -> Self {
+        Self { distance: 40.0, budget: 200, point_color: Color::srgb(0.8, 0.8, 0.8), point_size: 0.3 }
+    }
+}
+
+/// Satellite drawn as a point, without a mesh or a material of its own
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PointVisual;
+
+/// Satellite with its full visuals, eligible for labels
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct FullVisual;
+
+impl Plugin for ProgressiveVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        let rendering_condition = resource_exists::<Assets<Mesh>>.and_then(resource_exists::<SateliteDisplayData>);
+        app
+            .init_resource::<ProgressiveVisuals>()
+            .add_systems(Update, update_materialization.run_if(rendering_condition))
+            .add_systems(Update, draw_points.run_if(resource_exists::<GizmoConfigStore>));
+    }
+}
+
+//satellites materialized regardless of the distance and the budget
+fn forced(selection: Option<&SelectionSet>, watchlist: Option<&Watchlist>, hovered: Option<&HoveredSatellite>) -> HashSet<Entity> {
+    selection.and_then(SelectionSet::primary).into_iter()
+        .chain(watchlist.into_iter().flat_map(Watchlist::iter))
+        .chain(hovered.and_then(|h| h.0))
+        .collect()
+}
+
+type ForcedBy<'w> = (Option<Res<'w, SelectionSet>>, Option<Res<'w, Watchlist>>, Option<Res<'w, HoveredSatellite>>);
+type Point<'a> = (Entity, &'a Transform, Option<&'a MarkerSize>);
+type LoadedPoint = (With<PointVisual>, With<InGameElements>);
+type LoadedFull = (With<FullVisual>, With<InGameElements>);
+
+fn update_materialization(
+    (visuals, display_data): (Res<ProgressiveVisuals>, Res<SateliteDisplayData>),
+    (selection, watchlist, hovered): ForcedBy,
+    cameras: Query<&Transform, (With<Camera3d>, Without<OverlayCamera>)>,
+    points: Query<Point, LoadedPoint>,
+    full: Query<(Entity, &Transform), LoadedFull>,
+    (mut meshes, mut cache): (ResMut<Assets<Mesh>>, ResMut<MarkerMeshCache>),
+    mut commands: Commands
+) {
+    let forced = forced(selection.as_deref(), watchlist.as_deref(), hovered.as_deref());
+    let camera = cameras.iter().next().map(|t| t.translation);
+    let distance_to = |transform: &Transform| camera.map_or(f32::INFINITY, |c| c.distance(transform.translation));
+
+    let mut close = vec![];
+    for (entity, transform, size) in points.iter() {
+        let distance = distance_to(transform);
+        if forced.contains(&entity) {
+            materialize(&mut commands, entity, size, &display_data, &mut cache, &mut meshes);
+        } else if distance <= visuals.distance {
+            close.push((distance, entity, size));
+        }
+    }
+    //the closest ones first, the rest waits for the next frames
+    close.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (_, entity, size) in close.into_iter().take(visuals.budget) {
+        materialize(&mut commands, entity, size, &display_data, &mut cache, &mut meshes);
+    }
+
+    for (entity, transform) in full.iter() {
+        if !forced.contains(&entity) && distance_to(transform) > visuals.distance * DEMOTION_HYSTERESIS {
+            commands.entity(entity)
+                .remove::<(Handle<Mesh>, Handle<StandardMaterial>, FullVisual)>()
+                .insert(PointVisual);
+        }
+    }
+}
+
+fn materialize(
+    commands: &mut Commands,
+    entity: Entity,
+    size: Option<&MarkerSize>,
+    display_data: &SateliteDisplayData,
+    cache: &mut MarkerMeshCache,
+    meshes: &mut Assets<Mesh>
+) {
+    let mesh = match size {
+        Some(size) => cache.get_or_create(*size, meshes),
+        None => display_data.mesh.clone()
+    };
+    //the marker style resolves the material as soon as it's added
+    commands.entity(entity)
+        .remove::<PointVisual>()
+        .insert((mesh, display_data.material.clone(), FullVisual));
+}
+
+type DrawnPoint = (With<PointVisual>, Without<Unreliable>);
+
+//gizmos are batched, every point is drawn in the same draw call
+fn draw_points(
+    mut gizmos: Gizmos,
+    visuals: Res<ProgressiveVisuals>,
+    origin: Option<Res<FloatingOrigin>>,
+    points: Query<(&Transform, &InheritedVisibility), DrawnPoint>
+) {
+    let extent = visuals.point_size;
+    for (transform, visibility) in points.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        let center = origin.as_deref().map_or(transform.translation, |o| o.to_render(transform.translation));
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            gizmos.line(center - axis * extent, center + axis * extent, visuals.point_color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global::{InGameSettings, PropagationSettings};
+    use crate::propagation::{ElementsFormat, LoadElements, LoadElementsPlugin, LoadedElements};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    fn progressive_app(visuals: ProgressiveVisuals, count: usize) -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<SelectionSet>()
+            .add_plugins((LoadElementsPlugin::<SyntheticClient>::new(), ProgressiveVisualsPlugin))
+            .insert_resource(visuals)
+            .insert_resource(SyntheticClient(starlink_like_elements(count, 1470)))
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { batch_size: 100, ..default() },
+                ..default()
+            });
+        app.world_mut().spawn((Camera3d::default(), Transform::default()));
+        app.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+
+        let mut entities = vec![];
+        let mut reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+        for _ in 0..100 {
+            app.update();
+            entities.extend(reader.read(app.world().resource::<Events<LoadedElements>>()).flat_map(|ev| ev.entities().to_vec()));
+            if !entities.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(entities.len(), count);
+        (app, entities)
+    }
+
+    fn count<C: Component>(app: &mut App) -> usize {
+        app.world_mut().query_filtered::<(), With<C>>().iter(app.world()).count()
+    }
+
+    #[test]
+    fn test_materialization_budget() {
+        //every satellite is within reach of the camera, only the budget holds them back
+        let (mut app, _) = progressive_app(ProgressiveVisuals { budget: 64, distance: 1e4, ..default() }, 500);
+        let mut previous = count::<FullVisual>(&mut app);
+        assert_eq!(previous, 0);
+        for _ in 0..10 {
+            app.update();
+            let materialized = count::<FullVisual>(&mut app);
+            assert!(materialized - previous <= 64, "{previous} -> {materialized}");
+            assert_eq!(materialized + count::<PointVisual>(&mut app), 500);
+            previous = materialized;
+        }
+        assert_eq!(previous, 500);
+        let with_mesh = app.world_mut().query_filtered::<(), With<Handle<Mesh>>>().iter(app.world()).count();
+        assert_eq!(with_mesh, 500);
+    }
+
+    #[test]
+    fn test_focused_far_satellite_materialized_at_once() {
+        let (mut app, entities) = progressive_app(ProgressiveVisuals { budget: 0, ..default() }, 50);
+        app.world_mut().query_filtered::<&mut Transform, With<Camera3d>>().single_mut(app.world_mut()).translation = Vec3::splat(1e5);
+        app.update();
+        assert_eq!(count::<FullVisual>(&mut app), 0);
+
+        let far = entities[17];
+        app.world_mut().resource_mut::<SelectionSet>().select_single(far);
+        app.update();
+        assert!(app.world().get::<FullVisual>(far).is_some());
+        assert!(app.world().get::<Handle<Mesh>>(far).is_some());
+        assert_eq!(count::<FullVisual>(&mut app), 1);
+
+        //losing focus demotes it back to a point
+        app.world_mut().resource_mut::<SelectionSet>().clear();
+        app.update();
+        assert!(app.world().get::<PointVisual>(far).is_some());
+        assert!(app.world().get::<Handle<Mesh>>(far).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use super::client::DataSource;
+
+/// Where the elements of a satellite come from
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: DataSource,
+    pub group: String,
+    pub retrieved_at: SystemTime
+}
+
+impl Provenance {
+    pub fn display(&self) -> String {
+        let retrieved = self.retrieved_at.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs();
+        format!("{} ({}), retrieved at {} s unix time", self.source.label(), self.group, retrieved)
+    }
+}
+
+/// Sources in order of preference, elements of the same NORAD id from a preferred source replace the others
+#[derive(Resource, Debug, Clone)]
+pub struct SourcePrecedence(pub Vec<DataSource>);
+
+impl Default for SourcePrecedence {
+    fn default() -> Self {
+        Self(vec![DataSource::Supplemental, DataSource::Gp, DataSource::File, DataSource::Injected])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Spawn,
+    /// The loaded satellite comes from a less preferred source
+    Replace,
+    /// Already loaded from the same or a preferred source
+    Skip
+}
+
+impl SourcePrecedence {
+    fn rank(&self, source: DataSource) -> usize {
+        self.0.iter().position(|s| *s == source).unwrap_or(self.0.len())
+    }
+
+    pub fn resolve(&self, existing: Option<DataSource>, incoming: DataSource) -> Resolution {
+        match existing {
+            None => Resolution::Spawn,
+            Some(existing) if self.rank(incoming) < self.rank(existing) => Resolution::Replace,
+            Some(_) => Resolution::Skip
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::propagation::{ElementsFormat, EpochDataLoader, InGameElements, LoadElements, LoadElementsPlugin, OrbitalData};
+    use crate::test_support::LEO;
+
+    #[derive(Clone, Resource)]
+    struct PerSourceClient;
+
+    #[async_trait::async_trait]
+    impl EpochDataLoader for PerSourceClient {
+        type Error = Infallible;
+
+        async fn load(&self, group: String, format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+            self.load_from(DataSource::Gp, group, format).await
+        }
+
+        async fn load_from(&self, source: DataSource, _group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+            let ids = if source == DataSource::Supplemental { [2, 3, 4] } else { [1, 2, 3] };
+            Ok(ids.into_iter().map(|norad_id| LEO.builder().norad_id(norad_id).build()).collect())
+        }
+    }
+
+    fn load(app: &mut App, source: DataSource) {
+        app.world_mut().send_event(LoadElements { groups: vec!["starlink".to_owned()], format: ElementsFormat::Json, source, launch_animation: false, refresh: false });
+        for _ in 0..20 {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn test_precedence_resolution() {
+        let precedence = SourcePrecedence::default();
+        assert_eq!(precedence.resolve(None, DataSource::Gp), Resolution::Spawn);
+        assert_eq!(precedence.resolve(Some(DataSource::Gp), DataSource::Supplemental), Resolution::Replace);
+        assert_eq!(precedence.resolve(Some(DataSource::Supplemental), DataSource::Gp), Resolution::Skip);
+        assert_eq!(precedence.resolve(Some(DataSource::Gp), DataSource::Gp), Resolution::Skip);
+
+        let gp_first = SourcePrecedence(vec![DataSource::Gp]);
+        assert_eq!(gp_first.resolve(Some(DataSource::Supplemental), DataSource::Gp), Resolution::Replace);
+        //sources missing from the list are the least preferred
+        assert_eq!(gp_first.resolve(Some(DataSource::Gp), DataSource::File), Resolution::Skip);
+    }
+
+    #[test]
+    fn test_provenance_of_overlapping_loads() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, HierarchyPlugin, LoadElementsPlugin::<PerSourceClient>::new()))
+            .insert_resource(PerSourceClient);
+        let before = SystemTime::now();
+        load(&mut app, DataSource::Gp);
+        load(&mut app, DataSource::Supplemental);
+        //a later GP load doesn't override the supplemental sets
+        load(&mut app, DataSource::Gp);
+
+        let mut loaded: Vec<_> = app.world_mut().query::<(&InGameElements, &Provenance)>().iter(app.world())
+            .map(|(el, p)| (el.0.norad_id, p.clone()))
+            .collect();
+        loaded.sort_by_key(|(id, _)| *id);
+        let sources: Vec<_> = loaded.iter().map(|(id, p)| (*id, p.source)).collect();
+        assert_eq!(sources, vec![(1, DataSource::Gp), (2, DataSource::Supplemental), (3, DataSource::Supplemental), (4, DataSource::Supplemental)]);
+        for (_, provenance) in &loaded {
+            assert_eq!(provenance.group, "starlink");
+            assert!(provenance.retrieved_at >= before);
+        }
+    }
+}
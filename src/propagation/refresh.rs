@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::input::ActionCategory;
+
+use super::{DataSource, ElementsFormat};
+use super::bevy_integration::{ElementsFetched, LoadElements};
+use super::unload::GroupUnloaded;
+
+/// Refreshes the elements of groups loaded from Celestrak periodically, for sessions running for days.
+/// Network access without anyone at the screen has to be allowed, see [`RefreshConsent`]
+pub struct ElementsRefreshPlugin;
+
+/// Whether a due refresh may go to the network on its own
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefreshConsent {
+    Auto,
+    /// Every due refresh waits for an [`ApproveRefresh`]
+    #[default]
+    Prompt,
+    Never
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct RefreshSettings {
+    pub consent: RefreshConsent,
+    pub period: Duration,
+    /// Periods of groups refreshed more or less often than the others
+    pub group_periods: HashMap<String, Duration>,
+    /// Refreshes are moved by up to this fraction of the period, so groups loaded together don't refresh together
+    pub jitter: f32,
+    /// Delay after the first failure, doubled after every next one
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration
+}
+
+impl Default for RefreshSettings {
+    fn default() -> Self {
+        Self {
+            consent: RefreshConsent::default(),
+            period: Duration::from_secs(12 * 3600),
+            group_periods: HashMap::new(),
+            jitter: 0.1,
+            retry_delay: Duration::from_secs(5 * 60),
+            max_retry_delay: Duration::from_secs(2 * 3600)
+        }
+    }
+}
+
+impl RefreshSettings {
+    pub fn period_of(&self, group: &str) -> Duration {
+        self.group_periods.get(group).copied().unwrap_or(self.period)
+    }
+
+    pub fn retry_delay_after(&self, failures: u32) -> Duration {
+        self.retry_delay.saturating_mul(1 << failures.saturating_sub(1).min(16)).min(self.max_retry_delay)
+    }
+
+    //the same for a group and cycle, different between groups
+    fn jittered(&self, delay: Duration, group: &str, cycle: u32) -> Duration {
+        let mut hasher = DefaultHasher::new();
+        (group, cycle).hash(&mut hasher);
+        let unit = (hasher.finish() % 2001) as f64 / 1000.0 - 1.0;
+        delay.mul_f64((1.0 + unit * self.jitter.clamp(0.0, 1.0) as f64).max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshState {
+    /// Time of the next refresh, since the start of the session
+    Scheduled { due: Duration },
+    AwaitingConsent,
+    InFlight
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRefresh {
+    pub format: ElementsFormat,
+    pub source: DataSource,
+    pub state: RefreshState,
+    /// Failed refreshes since the last successful one
+    pub failures: u32,
+    /// Time of the last successful load, since the start of the session
+    pub refreshed_at: Duration,
+    cycle: u32
+}
+
+/// What the scheduler wants done for a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefreshStep {
+    Start(String),
+    Prompt(String)
+}
+
+/// Refresh state of every group loaded from the network, driven by the session time so it can be tested
+/// with any clock
+#[derive(Resource, Debug, Default)]
+pub struct RefreshScheduler {
+    groups: BTreeMap<String, GroupRefresh>
+}
+
+impl RefreshScheduler {
+    pub fn get(&self, group: &str) -> Option<&GroupRefresh> {
+        self.groups.get(group)
+    }
+
+    /// Time since the last successful load of the group
+    pub fn age(&self, group: &str, now: Duration) -> Option<Duration> {
+        self.groups.get(group).map(|refresh| now.saturating_sub(refresh.refreshed_at))
+    }
+
+    /// A load of the group finished, scheduled or not. Groups start to be refreshed after their first
+    /// successful load from the network
+    pub fn fetched(&mut self, fetched: &ElementsFetched, now: Duration, settings: &RefreshSettings) {
+        let succeeded = fetched.received > 0;
+        let Some(refresh) = self.groups.get_mut(&fetched.group) else {
+            if succeeded && matches!(fetched.source, DataSource::Gp | DataSource::Supplemental) {
+                let due = now + settings.jittered(settings.period_of(&fetched.group), &fetched.group, 0);
+                self.groups.insert(fetched.group.clone(), GroupRefresh {
+                    format: fetched.format, source: fetched.source, state: RefreshState::Scheduled { due }, failures: 0, refreshed_at: now, cycle: 0
+                });
+            }
+            return;
+        };
+        refresh.cycle += 1;
+        let delay = if succeeded {
+            refresh.failures = 0;
+            refresh.refreshed_at = now;
+            settings.period_of(&fetched.group)
+        } else {
+            refresh.failures += 1;
+            settings.retry_delay_after(refresh.failures)
+        };
+        refresh.state = RefreshState::Scheduled { due: now + settings.jittered(delay, &fetched.group, refresh.cycle) };
+    }
+
+    /// Groups due for a refresh, started or waiting for consent. Without consent they are skipped until the next period
+    pub fn poll(&mut self, now: Duration, settings: &RefreshSettings) -> Vec<RefreshStep> {
+        let mut steps = vec![];
+        for (group, refresh) in self.groups.iter_mut() {
+            let RefreshState::Scheduled { due } = refresh.state else {
+                continue;
+            };
+            if due > now {
+                continue;
+            }
+            match settings.consent {
+                RefreshConsent::Auto => {
+                    refresh.state = RefreshState::InFlight;
+                    steps.push(RefreshStep::Start(group.clone()));
+                },
+                RefreshConsent::Prompt => {
+                    refresh.state = RefreshState::AwaitingConsent;
+                    steps.push(RefreshStep::Prompt(group.clone()));
+                },
+                RefreshConsent::Never => {
+                    refresh.cycle += 1;
+                    refresh.state = RefreshState::Scheduled { due: now + settings.jittered(settings.period_of(group), group, refresh.cycle) };
+                }
+            }
+        }
+        steps
+    }
+
+    /// The group was unloaded, it's not refreshed anymore
+    pub fn forget(&mut self, group: &str) {
+        self.groups.remove(group);
+    }
+
+    /// Starts the refreshes waiting for consent, of a single group or all of them
+    pub fn approve(&mut self, group: Option<&str>) -> Vec<String> {
+        self.groups.iter_mut()
+            .filter(|(name, refresh)| refresh.state == RefreshState::AwaitingConsent && group.is_none_or(|group| group == name.as_str()))
+            .map(|(name, refresh)| {
+                refresh.state = RefreshState::InFlight;
+                name.clone()
+            })
+            .collect()
+    }
+}
+
+/// A refresh is due and waits for an [`ApproveRefresh`]
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct RefreshPrompted {
+    pub group: String,
+    /// Time since the group was last loaded
+    pub age: Duration
+}
+
+/// Allows the refreshes waiting for consent, `None` for every group
+#[derive(Event, Debug, Clone, Default, PartialEq)]
+pub struct ApproveRefresh {
+    pub group: Option<String>
+}
+
+impl Plugin for ElementsRefreshPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RefreshSettings>()
+            .init_resource::<RefreshScheduler>()
+            .add_event::<ElementsFetched>()
+            .add_event::<LoadElements>()
+            .add_event::<RefreshPrompted>()
+            .add_event::<ApproveRefresh>()
+            .add_event::<GroupUnloaded>()
+            .register_command(
+                CommandDescriptor::event("Approve elements refresh", ActionCategory::General, |params| ApproveRefresh {
+                    group: params[0].as_text().filter(|group| !group.is_empty()).map(str::to_owned)
+                })
+                .with_param("group, empty for all", ParamKind::Text)
+            )
+            .add_systems(Update, (track_fetches, forget_unloaded, schedule_refreshes).chain());
+    }
+}
+
+fn track_fetches(mut fetched: EventReader<ElementsFetched>, time: Res<Time<Real>>, settings: Res<RefreshSettings>, mut scheduler: ResMut<RefreshScheduler>) {
+    for ev in fetched.read() {
+        if ev.received == 0 && scheduler.get(&ev.group).is_some() {
+            warn!("Refresh of {} from {} failed, retrying later", ev.group, ev.source.label());
+        }
+        scheduler.fetched(ev, time.elapsed(), &settings);
+    }
+}
+
+fn forget_unloaded(mut unloaded: EventReader<GroupUnloaded>, mut scheduler: ResMut<RefreshScheduler>) {
+    for ev in unloaded.read() {
+        scheduler.forget(&ev.group);
+    }
+}
+
+fn schedule_refreshes(
+    mut approvals: EventReader<ApproveRefresh>,
+    time: Res<Time<Real>>,
+    settings: Res<RefreshSettings>,
+    mut scheduler: ResMut<RefreshScheduler>,
+    mut prompts: EventWriter<RefreshPrompted>,
+    mut loads: EventWriter<LoadElements>
+) {
+    let now = time.elapsed();
+    let mut started: Vec<String> = approvals.read().flat_map(|approval| scheduler.approve(approval.group.as_deref())).collect();
+    for step in scheduler.poll(now, &settings) {
+        match step {
+            RefreshStep::Start(group) => started.push(group),
+            RefreshStep::Prompt(group) => {
+                let age = scheduler.age(&group, now).unwrap_or_default();
+                warn!("Elements of {group} are {:.1} h old, approve the refresh to download them again", age.as_secs_f64() / 3600.0);
+                prompts.send(RefreshPrompted { group, age });
+            }
+        }
+    }
+    for group in started {
+        let Some(refresh) = scheduler.get(&group) else {
+            continue;
+        };
+        info!("Refreshing elements of {group} from {}", refresh.source.label());
+        loads.send(LoadElements { groups: vec![group], format: refresh.format, source: refresh.source, launch_animation: false, refresh: true });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagation::{InGameElements, SatelliteGroup};
+    use crate::test_support::{fixture_elements, headless_app, load_group, run_until, EventLog, ScriptedClient};
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn fetched(group: &str, received: usize) -> ElementsFetched {
+        ElementsFetched { group: group.to_owned(), format: ElementsFormat::Json, source: DataSource::Gp, received, error: None }
+    }
+
+    fn due(scheduler: &RefreshScheduler, group: &str) -> Duration {
+        match scheduler.get(group).unwrap().state {
+            RefreshState::Scheduled { due } => due,
+            state => panic!("{group} is {state:?}")
+        }
+    }
+
+    #[test]
+    fn test_refreshes_follow_the_schedule_and_back_off() {
+        let settings = RefreshSettings { consent: RefreshConsent::Auto, group_periods: HashMap::from([("galileo".to_owned(), 24 * HOUR)]), ..default() };
+        let mut scheduler = RefreshScheduler::default();
+        scheduler.fetched(&fetched("gps-ops", 30), Duration::ZERO, &settings);
+        scheduler.fetched(&fetched("galileo", 28), Duration::ZERO, &settings);
+        //failed loads and local files are never refreshed
+        scheduler.fetched(&fetched("typo", 0), Duration::ZERO, &settings);
+        scheduler.fetched(&ElementsFetched { source: DataSource::File, ..fetched("local", 5) }, Duration::ZERO, &settings);
+        assert!(scheduler.get("typo").is_none() && scheduler.get("local").is_none());
+
+        //within the jitter of the period of each group
+        let now = due(&scheduler, "gps-ops");
+        assert!(now.abs_diff(12 * HOUR) <= Duration::from_secs(72 * 60), "{now:?}");
+        assert!(due(&scheduler, "galileo").abs_diff(24 * HOUR) <= Duration::from_secs(144 * 60));
+        assert!(scheduler.poll(now - Duration::from_secs(1), &settings).is_empty());
+        assert_eq!(scheduler.poll(now, &settings), vec![RefreshStep::Start("gps-ops".to_owned())]);
+        assert!(scheduler.poll(now, &settings).is_empty());
+
+        //failures retry after 5, 10, 20 minutes, up to 2 hours
+        let mut now = now;
+        for failures in 1..=7 {
+            scheduler.fetched(&fetched("gps-ops", 0), now, &settings);
+            let delay = due(&scheduler, "gps-ops") - now;
+            let expected = Duration::from_secs(5 * 60 * (1 << (failures - 1))).min(2 * HOUR);
+            assert!(delay.abs_diff(expected) <= expected.mul_f32(0.1), "{failures} failures, {delay:?}");
+            now = due(&scheduler, "gps-ops");
+            assert_eq!(scheduler.poll(now, &settings), vec![RefreshStep::Start("gps-ops".to_owned())]);
+        }
+        assert_eq!(scheduler.get("gps-ops").unwrap().failures, 7);
+        assert_eq!(scheduler.age("gps-ops", now), Some(now));
+        scheduler.fetched(&fetched("gps-ops", 30), now, &settings);
+        assert_eq!(scheduler.get("gps-ops").unwrap().failures, 0);
+        assert_eq!(scheduler.age("gps-ops", now + HOUR), Some(HOUR));
+        assert!(due(&scheduler, "gps-ops") - now > 10 * HOUR);
+
+        //nothing goes to the network without consent
+        let prompt = RefreshSettings { consent: RefreshConsent::Prompt, ..settings.clone() };
+        let now = due(&scheduler, "galileo");
+        assert_eq!(scheduler.poll(now, &prompt), vec![RefreshStep::Prompt("galileo".to_owned())]);
+        assert!(scheduler.poll(now + HOUR, &prompt).is_empty());
+        assert!(scheduler.approve(Some("gps-ops")).is_empty());
+        assert_eq!(scheduler.approve(None), vec!["galileo".to_owned()]);
+        assert_eq!(scheduler.get("galileo").unwrap().state, RefreshState::InFlight);
+
+        let never = RefreshSettings { consent: RefreshConsent::Never, ..settings };
+        let now = due(&scheduler, "gps-ops");
+        assert!(scheduler.poll(now, &never).is_empty());
+        assert!(due(&scheduler, "gps-ops") > now + 10 * HOUR);
+    }
+
+    fn app(consent: RefreshConsent) -> App {
+        let mut app = headless_app(ScriptedClient::default().with_group("fixtures", fixture_elements()));
+        app.insert_resource(RefreshSettings { consent, period: Duration::from_secs(2), jitter: 0.0, ..default() });
+        app
+    }
+
+    fn satellites(app: &mut App) -> usize {
+        app.world_mut().query_filtered::<&SatelliteGroup, With<InGameElements>>().iter(app.world()).count()
+    }
+
+    #[test]
+    fn test_automatic_refresh_reloads_the_group() {
+        let mut app = app(RefreshConsent::Auto);
+        load_group(&mut app, "fixtures", 100);
+        app.update();
+        let loaded_at = app.world().resource::<RefreshScheduler>().get("fixtures").unwrap().refreshed_at;
+        let mut loads = EventLog::<LoadElements>::new(&app);
+        let mut requested = vec![];
+        run_until(&mut app, |app| {
+            requested.extend(loads.read(app).into_iter().map(|load| (load.groups, load.source)));
+            !requested.is_empty()
+        }, 30);
+        assert_eq!(requested, vec![(vec!["fixtures".to_owned()], DataSource::Gp)]);
+
+        run_until(&mut app, |app| matches!(app.world().resource::<RefreshScheduler>().get("fixtures").unwrap().state, RefreshState::Scheduled { .. }), 10);
+        let refresh = app.world().resource::<RefreshScheduler>().get("fixtures").unwrap().clone();
+        assert!(refresh.refreshed_at >= loaded_at + Duration::from_secs(2));
+        assert_eq!(refresh.failures, 0);
+        //the same satellites, refreshed in place
+        assert_eq!(satellites(&mut app), 5);
+    }
+
+    #[test]
+    fn test_prompted_refresh_waits_for_approval() {
+        let mut app = app(RefreshConsent::Prompt);
+        load_group(&mut app, "fixtures", 100);
+        app.update();
+        let mut prompts = EventLog::<RefreshPrompted>::new(&app);
+        let mut loads = EventLog::<LoadElements>::new(&app);
+        let mut prompted = vec![];
+        run_until(&mut app, |app| {
+            prompted.extend(prompts.read(app));
+            !prompted.is_empty()
+        }, 30);
+        assert_eq!(prompted[0].group, "fixtures");
+        assert!(prompted[0].age >= Duration::from_secs(2));
+        for _ in 0..30 {
+            app.update();
+        }
+        assert!(loads.read(&app).is_empty());
+        assert!(prompts.read(&app).is_empty());
+
+        app.world_mut().send_event(ApproveRefresh { group: Some("fixtures".to_owned()) });
+        app.update();
+        assert_eq!(loads.read(&app).into_iter().flat_map(|load| load.groups).collect::<Vec<_>>(), vec!["fixtures".to_owned()]);
+        run_until(&mut app, |app| matches!(app.world().resource::<RefreshScheduler>().get("fixtures").unwrap().state, RefreshState::Scheduled { .. }), 10);
+        assert_eq!(satellites(&mut app), 5);
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use crate::global::InGameSettings;
+
+use super::bevy_integration::{predict_at, ElementsDiff};
+
+/// How far a satellite jumped when its elements were updated, both sets propagated to the time of the update.
+/// Large residuals mean a significant revision of the orbit, a maneuver or refined tracking
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ElementUpdateResidual {
+    pub entity: Entity,
+    pub delta_km: f64
+}
+
+pub struct UpdateResidualsPlugin;
+
+impl Plugin for UpdateResidualsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ElementsDiff>()
+            .add_event::<ElementUpdateResidual>()
+            .add_systems(Update, report_update_residuals);
+    }
+}
+
+/// Distance between the predictions of both sets of the update, `None` when either can't be propagated
+pub fn update_residual(diff: &ElementsDiff, numeric_fallback: bool) -> Option<f64> {
+    let previous = predict_at(&diff.previous, diff.minutes_since_previous_epoch, numeric_fallback)?;
+    let current = predict_at(&diff.current, diff.minutes_since_current_epoch(), numeric_fallback)?;
+    let [dx, dy, dz] = [0, 1, 2].map(|i| previous.position[i] - current.position[i]);
+    Some((dx * dx + dy * dy + dz * dz).sqrt())
+}
+
+fn report_update_residuals(mut diffs: EventReader<ElementsDiff>, mut residuals: EventWriter<ElementUpdateResidual>, settings: Res<InGameSettings>) {
+    for diff in diffs.read() {
+        match update_residual(diff, settings.propagation.numeric_fallback) {
+            Some(delta_km) => {
+                debug!("Elements of {} updated, residual {:.3} km", diff.current.norad_id, delta_km);
+                residuals.send(ElementUpdateResidual { entity: diff.entity, delta_km });
+            },
+            None => warn!("Residual of the update of {} can't be computed", diff.current.norad_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::propagation::{DataSource, ElementsFormat, EpochDataLoader, InGameElements, LoadElements, LoadElementsPlugin, OrbitalData};
+    use crate::test_support::LEO;
+
+    //every fetch publishes a set an hour newer than the previous one
+    #[derive(Clone, Resource, Default)]
+    struct RefetchingClient(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl EpochDataLoader for RefetchingClient {
+        type Error = Infallible;
+
+        async fn load(&self, _group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+            let fetch = self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![LEO.builder().epoch(&format!("2024-12-28T{:02}:00:00", 10 + fetch)).build()])
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, UpdateResidualsPlugin))
+            .insert_resource(InGameSettings::default());
+        app
+    }
+
+    #[test]
+    fn test_shifted_orbit_residual() {
+        let mut app = app();
+        let entity = app.world_mut().spawn_empty().id();
+        //the revised set places the satellite 1° further along a near circular orbit
+        let previous = LEO.elements();
+        let current = LEO.builder().mean_anomaly(41.0).build();
+        let at = 30.0;
+        app.world_mut().send_event(ElementsDiff { entity, previous: previous.clone(), current, minutes_since_previous_epoch: at });
+        app.update();
+
+        let radius = {
+            let [x, y, z] = predict_at(&previous, at, false).unwrap().position;
+            (x * x + y * y + z * z).sqrt()
+        };
+        let expected = 2.0 * radius * 0.5f64.to_radians().sin();
+        let events = app.world().resource::<Events<ElementUpdateResidual>>();
+        let residuals: Vec<_> = events.get_reader().read(events).cloned().collect();
+        assert_eq!(residuals.len(), 1);
+        assert_eq!(residuals[0].entity, entity);
+        assert_abs_diff_eq!(residuals[0].delta_km, expected, epsilon = expected * 0.01);
+    }
+
+    #[test]
+    fn test_residual_at_the_same_instant() {
+        //a day later epoch, the time of the update is expressed relative to both
+        let diff = ElementsDiff {
+            entity: Entity::PLACEHOLDER,
+            previous: LEO.builder().epoch("2024-12-28T00:00:00").build(),
+            current: LEO.builder().epoch("2024-12-29T00:00:00").build(),
+            minutes_since_previous_epoch: 1500.0
+        };
+        assert_abs_diff_eq!(diff.minutes_since_current_epoch(), 60.0, epsilon = 1e-3);
+        //the same set at a later epoch only agrees after a whole number of revolutions, 15.5 per day don't
+        assert!(update_residual(&diff, false).unwrap() > 100.0);
+        let unchanged = ElementsDiff { current: diff.previous.clone(), ..diff };
+        assert_abs_diff_eq!(update_residual(&unchanged, false).unwrap(), 0.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_refetch_updates_elements_in_place() {
+        let mut app = app();
+        app
+            .add_plugins((HierarchyPlugin, LoadElementsPlugin::<RefetchingClient>::new()))
+            .insert_resource(RefetchingClient::default());
+        let (mut diff_reader, mut residual_reader) = (
+            app.world().resource::<Events<ElementsDiff>>().get_reader(),
+            app.world().resource::<Events<ElementUpdateResidual>>().get_reader()
+        );
+        let (mut diffs, mut residuals) = (vec![], vec![]);
+        for _ in 0..2 {
+            app.world_mut().send_event(LoadElements { groups: vec!["starlink".to_owned()], format: ElementsFormat::Json, source: DataSource::Gp, launch_animation: false, refresh: false });
+            for _ in 0..20 {
+                app.update();
+                diffs.extend(diff_reader.read(app.world().resource::<Events<ElementsDiff>>()).cloned());
+                residuals.extend(residual_reader.read(app.world().resource::<Events<ElementUpdateResidual>>()).cloned());
+            }
+        }
+
+        let satellites: Vec<_> = app.world_mut().query::<(Entity, &InGameElements)>().iter(app.world()).map(|(e, el)| (e, el.0.clone())).collect();
+        assert_eq!(satellites.len(), 1);
+        let (entity, current) = &satellites[0];
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].entity, *entity);
+        assert!(Arc::ptr_eq(&diffs[0].current, current));
+        assert!(diffs[0].previous.epoch() < current.epoch());
+        assert_eq!(residuals.len(), 1);
+        assert_eq!(residuals[0].entity, *entity);
+    }
+}
@@ -0,0 +1,131 @@
+use std::f64::consts::TAU;
+
+use bevy::{math::DVec3, prelude::*};
+
+use crate::global::InGameSettings;
+use crate::orbit::SatelliteOrbit;
+
+use super::bevy_integration::Propageted;
+use super::validation::is_plausible_prediction;
+
+/// Revolutions since epoch, continuous (the fractional part is the progress of the current one)
+#[derive(Component, Debug, Default, Clone)]
+pub struct RevolutionCounter {
+    revolutions: f64,
+    nodal: Option<NodalTracking>
+}
+
+#[derive(Debug, Clone)]
+struct NodalTracking {
+    epoch_latitude: f64,
+    last_z: f64,
+    crossings: u64
+}
+
+impl RevolutionCounter {
+    pub fn revolutions(&self) -> f64 {
+        self.revolutions
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.revolutions.max(0.0).floor() as u64
+    }
+
+    pub fn display(&self) -> String {
+        format!("rev {} since epoch", self.completed())
+    }
+
+    /// Keplerian satellites, `after` is `before` propagated by `dt_seconds`.
+    /// The wrapped true anomaly only resolves the fraction, whole turns come from the elapsed time
+    pub fn advance_keplerian(&mut self, before: &SatelliteOrbit, after: &SatelliteOrbit, dt_seconds: f64) {
+        let turns = dt_seconds / before.orbital_period();
+        let fraction = ((after.true_anomaly - before.true_anomaly) / 360.0).rem_euclid(1.0);
+        self.revolutions += (turns - fraction).round() + fraction;
+    }
+
+    /// SGP4 satellites don't expose the anomaly, revolutions are nodal: counted on ascending node crossings
+    /// between consecutive states, with the fraction estimated from the argument of latitude
+    pub fn observe_state(&mut self, position: DVec3, velocity: DVec3) {
+        let latitude = argument_of_latitude(position, velocity);
+        let tracking = self.nodal.get_or_insert(NodalTracking { epoch_latitude: latitude, last_z: position.z, crossings: 0 });
+        if crosses_ascending_node(tracking.last_z, position.z, velocity.z) {
+            tracking.crossings += 1;
+        }
+        tracking.last_z = position.z;
+        self.revolutions = tracking.crossings as f64 + (latitude - tracking.epoch_latitude) / TAU;
+    }
+}
+
+/// Northbound through the equatorial plane between two consecutive states
+pub fn crosses_ascending_node(last_z: f64, z: f64, velocity_z: f64) -> bool {
+    last_z < 0.0 && z >= 0.0 && velocity_z > 0.0
+}
+
+/// Angle from the ascending node to the position, in radians within [0, 2π)
+fn argument_of_latitude(position: DVec3, velocity: DVec3) -> f64 {
+    let angular_momentum = position.cross(velocity);
+    let node = DVec3::Z.cross(angular_momentum);
+    //equatorial orbits have no node, the longitude is the closest equivalent
+    if node.length() < 1e-9 * angular_momentum.length() {
+        return position.y.atan2(position.x).rem_euclid(TAU);
+    }
+    let latitude = (node.dot(position) / (node.length() * position.length())).clamp(-1.0, 1.0).acos();
+    if position.z < 0.0 { TAU - latitude } else { latitude }
+}
+
+pub(super) fn count_nodal_revolutions(
+    mut events: EventReader<Propageted>,
+    mut counters: Query<&mut RevolutionCounter>,
+    settings: Res<InGameSettings>
+) {
+    for propagated in events.read() {
+        for (entity, prediction) in &propagated.data {
+            if !is_plausible_prediction(prediction, &settings.propagation.envelope) {
+                continue;
+            }
+            if let Ok(mut counter) = counters.get_mut(*entity) {
+                counter.observe_state(DVec3::from_array(prediction.position), DVec3::from_array(prediction.velocity));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_three_and_half_revolutions_both_methods() {
+        let mut orbit = SatelliteOrbit::new(7000.0, 0.0001, 51.6, 30.0, 0.0, 10.0, 0.0);
+        let steps = 140;
+        let dt = orbit.orbital_period() / 40.0;
+
+        let mut keplerian = RevolutionCounter::default();
+        let mut nodal = RevolutionCounter::default();
+        nodal.observe_state(orbit.position(), orbit.velocity());
+        for _ in 0..steps {
+            let propagated = orbit.propagate(dt);
+            keplerian.advance_keplerian(&orbit, &propagated, dt);
+            orbit = propagated;
+            nodal.observe_state(orbit.position(), orbit.velocity());
+        }
+
+        assert_abs_diff_eq!(keplerian.revolutions(), 3.5, epsilon = 0.05);
+        assert_abs_diff_eq!(nodal.revolutions(), 3.5, epsilon = 0.05);
+        assert_eq!(nodal.display(), "rev 3 since epoch");
+    }
+
+    #[test]
+    fn test_keplerian_steps_longer_than_a_period() {
+        let orbit = SatelliteOrbit::new(7000.0, 0.1, 51.6, 30.0, 40.0, 0.0, 0.0);
+        let dt = orbit.orbital_period() * 1.25;
+        let propagated = orbit.propagate(dt);
+        let mut counter = RevolutionCounter::default();
+        counter.advance_keplerian(&orbit, &propagated, dt);
+        //the fraction follows the true anomaly, ahead of the mean one after the perigee pass
+        assert_abs_diff_eq!(counter.revolutions(), 1.0 + propagated.true_anomaly / 360.0, epsilon = 1e-6);
+        assert!(counter.revolutions() > 1.25);
+    }
+}
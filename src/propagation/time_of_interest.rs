@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::world_frame::WORLD_FRAME;
+
+use super::bevy_integration::{predict_at, InGameElements, PropagatableDuration};
+use super::marker_mesh::{MarkerMeshCache, MarkerSize};
+use super::validation::{is_plausible_prediction, Unreliable};
+
+/// Renders translucent ghosts of every satellite at a moment ahead of the simulation,
+/// the live state is never advanced
+pub struct TimeOfInterestPlugin;
+
+/// Moment of interest, as simulation time ahead of every satellite's current one
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TimeOfInterest {
+    pub ahead: Duration,
+    pub show_ghosts: bool
+}
+
+impl Default for TimeOfInterest {
+    fn default() -> Self {
+        Self { ahead: Duration::from_secs(30 * 60), show_ghosts: false }
+    }
+}
+
+impl TimeOfInterest {
+    pub fn later(&mut self, step: Duration) {
+        self.ahead += step;
+    }
+
+    pub fn earlier(&mut self, step: Duration) {
+        self.ahead = self.ahead.saturating_sub(step);
+    }
+
+    pub fn display(&self) -> String {
+        let minutes = self.ahead.as_secs() / 60;
+        format!("time of interest: T+{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Position of the satellite `of` at the [`TimeOfInterest`]
+#[derive(Component, Debug)]
+pub struct Ghost {
+    pub of: Entity
+}
+
+#[derive(Resource)]
+struct GhostDisplayData {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>
+}
+
+#[derive(Component)]
+struct TimeOfInterestLabel;
+
+impl Plugin for TimeOfInterestPlugin {
+    fn build(&self, app: &mut App) {
+        let rendering_condition = resource_exists::<Assets<Mesh>>.and_then(resource_exists::<Assets<StandardMaterial>>);
+        app
+            .init_resource::<TimeOfInterest>()
+            .init_resource::<MarkerMeshCache>()
+            .register_command(
+                CommandDescriptor::new("Set time of interest", ActionCategory::Time, |params, world| {
+                    if let Some(minutes) = params[0].as_number() {
+                        match Duration::try_from_secs_f64(minutes * 60.0) {
+                            Ok(ahead) => world.resource_mut::<TimeOfInterest>().ahead = ahead,
+                            Err(err) => warn!("Time of interest {minutes} minutes ahead is out of range: {err}")
+                        }
+                    }
+                })
+                .with_param("minutes ahead", ParamKind::Number)
+            )
+            .add_systems(Startup, create_ghost_assets.run_if(rendering_condition))
+            //satellite times only change on propagation ticks, ghosts follow them
+            .add_systems(Update, update_ghosts.run_if(resource_changed::<TimeOfInterest>.or_else(satellite_time_changed)))
+            .add_systems(Update, update_time_of_interest_label.run_if(resource_changed::<TimeOfInterest>));
+    }
+}
+
+fn create_ghost_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MarkerMeshCache>,
+    mut commands: Commands
+) {
+    let mesh = cache.get_or_create(MarkerSize::default(), &mut meshes);
+    let material = materials.add(StandardMaterial {
+        base_color: Color::srgba(1.0, 1.0, 1.0, 0.3),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    });
+    commands.insert_resource(GhostDisplayData { mesh, material });
+}
+
+fn satellite_time_changed(satellites: Query<(), Changed<PropagatableDuration>>) -> bool {
+    !satellites.is_empty()
+}
+
+fn update_ghosts(
+    time_of_interest: Res<TimeOfInterest>,
+    satellites: Query<(Entity, &InGameElements, &PropagatableDuration), Without<Unreliable>>,
+    mut ghosts: Query<(Entity, &Ghost, &mut Transform)>,
+    settings: Res<InGameSettings>,
+    display_data: Option<Res<GhostDisplayData>>,
+    mut commands: Commands
+) {
+    if !time_of_interest.show_ghosts {
+        for (entity, _, _) in ghosts.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let mut existing: HashMap<Entity, Entity> = ghosts.iter().map(|(entity, ghost, _)| (ghost.of, entity)).collect();
+    for (entity, elements, elapsed) in satellites.iter() {
+        let minutes = (elapsed.0 + time_of_interest.ahead).as_secs_f64() / 60.0;
+        let prediction = predict_at(&elements.0, minutes, settings.propagation.numeric_fallback)
+            .filter(|p| is_plausible_prediction(p, &settings.propagation.envelope));
+        let Some(prediction) = prediction else {
+            continue;
+        };
+        let translation = WORLD_FRAME.to_world(Vec3::from_array(prediction.position.map(|c| c as f32))) * settings.scale;
+
+        match existing.remove(&entity).and_then(|ghost| ghosts.get_mut(ghost).ok()) {
+            Some((_, _, mut transform)) => transform.translation = translation,
+            None => {
+                let mut ghost = commands.spawn((Ghost { of: entity }, TransformBundle::from_transform(Transform::from_translation(translation))));
+                if let Some(display_data) = &display_data {
+                    ghost.insert((display_data.mesh.clone(), display_data.material.clone(), VisibilityBundle::default()));
+                }
+            }
+        }
+    }
+    //left over ghosts belong to despawned or unreliable satellites
+    for ghost in existing.into_values() {
+        commands.entity(ghost).despawn();
+    }
+}
+
+fn update_time_of_interest_label(
+    time_of_interest: Res<TimeOfInterest>,
+    labels: Query<Entity, With<TimeOfInterestLabel>>,
+    mut commands: Commands
+) {
+    for entity in labels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !time_of_interest.show_ghosts {
+        return;
+    }
+    commands.spawn((
+        TextBundle::from_section(time_of_interest.display(), TextStyle { font_size: 18.0, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(12.0),
+                left: Val::Px(12.0),
+                ..default()
+            }),
+        TimeOfInterestLabel
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::commands::{CommandRegistry, InvokeCommand};
+    use crate::propagation::bevy_integration::PropagatableSattelite;
+    use crate::test_support::LEO;
+
+    fn ghosts(app: &mut App) -> Vec<(Entity, Vec3)> {
+        app.world_mut().query::<(&Ghost, &Transform)>().iter(app.world()).map(|(g, t)| (g.of, t.translation)).collect()
+    }
+
+    #[test]
+    fn test_ghosts_at_predicted_positions() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, HierarchyPlugin, TimeOfInterestPlugin))
+            .insert_resource(InGameSettings::default());
+
+        let elements = LEO.elements();
+        let satellite = app.world_mut().spawn((PropagatableSattelite::new(InGameElements(elements.clone())), Transform::default())).id();
+        app.update();
+        assert!(ghosts(&mut app).is_empty());
+
+        app.world_mut().insert_resource(TimeOfInterest { ahead: Duration::from_secs(45 * 60), show_ghosts: true });
+        app.update();
+        let expected = predict_at(&elements, 45.0, false).unwrap().position.map(|c| c as f32);
+        let placed = ghosts(&mut app);
+        assert_eq!(placed.len(), 1);
+        assert_eq!(placed[0].0, satellite);
+        assert_abs_diff_eq!(placed[0].1.x, expected[0] * 0.01, epsilon = 1e-3);
+        assert_abs_diff_eq!(placed[0].1.y, expected[1] * 0.01, epsilon = 1e-3);
+        assert_abs_diff_eq!(placed[0].1.z, expected[2] * 0.01, epsilon = 1e-3);
+        //the live satellite stays where it was
+        assert_eq!(app.world().get::<Transform>(satellite).unwrap().translation, Vec3::ZERO);
+
+        app.world_mut().resource_mut::<TimeOfInterest>().show_ghosts = false;
+        app.update();
+        assert!(ghosts(&mut app).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_range_times_are_ignored() {
+        let mut app = App::new();
+        app.add_plugins(TimeOfInterestPlugin);
+        let mut set_ahead = |minutes: &str| {
+            let registry = app.world().resource::<CommandRegistry>().clone();
+            registry.invoke(&InvokeCommand { name: "Set time of interest".to_owned(), arguments: vec![minutes.to_owned()] }, app.world_mut()).unwrap();
+            app.world().resource::<TimeOfInterest>().ahead
+        };
+        assert_eq!(set_ahead("90"), Duration::from_secs(90 * 60));
+        assert_eq!(set_ahead("1e20"), Duration::from_secs(90 * 60));
+        assert_eq!(set_ahead("-5"), Duration::from_secs(90 * 60));
+    }
+}
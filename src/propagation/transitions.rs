@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::bevy_integration::SatelliteSpawned;
+
+//a zero scale makes the normal matrix of the marker singular
+const MIN_SCALE: f32 = 1e-3;
+
+/// Spawned satellites grow in, satellites removed through [`DespawnSatellite`] shrink out before they are despawned
+pub struct SatelliteTransitionsPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TransitionSettings {
+    /// Real time a new satellite grows in, zero to show it at once
+    pub spawn: Duration,
+    /// Real time a removed satellite shrinks out
+    pub despawn: Duration
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self { spawn: Duration::from_millis(400), despawn: Duration::from_millis(300) }
+    }
+}
+
+/// The sanctioned way of removing a satellite, unloads, decay and evictions all send it
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DespawnSatellite {
+    pub entity: Entity,
+    /// Skips the transition, for teardown
+    pub instant: bool
+}
+
+impl DespawnSatellite {
+    pub fn animated(entity: Entity) -> Self {
+        Self { entity, instant: false }
+    }
+
+    pub fn instant(entity: Entity) -> Self {
+        Self { entity, instant: true }
+    }
+}
+
+/// A satellite growing in, `t` is the progress of the transition from 0 to 1
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SpawningIn {
+    pub t: f32
+}
+
+/// A satellite on its way out, `t` is the progress of the transition from 0 to 1.
+/// It's neither picked nor propagated anymore
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Despawning {
+    pub t: f32
+}
+
+pub fn ease_out_cubic(x: f32) -> f32 {
+    1.0 - (1.0 - x.clamp(0.0, 1.0)).powi(3)
+}
+
+pub fn ease_in_cubic(x: f32) -> f32 {
+    x.clamp(0.0, 1.0).powi(3)
+}
+
+/// Progress of a transition lasting `duration` after another `delta`, a zero duration completes at once
+pub fn advance_transition(t: f32, delta: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return 1.0;
+    }
+    (t + delta.as_secs_f32() / duration.as_secs_f32()).min(1.0)
+}
+
+/// Marker scale while growing in, quick at first and settling at full size
+pub fn spawn_scale(t: f32) -> f32 {
+    ease_out_cubic(t).max(MIN_SCALE)
+}
+
+/// Marker scale while shrinking out, slow at first and vanishing at the end
+pub fn despawn_scale(t: f32) -> f32 {
+    (1.0 - ease_in_cubic(t)).max(MIN_SCALE)
+}
+
+impl Plugin for SatelliteTransitionsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TransitionSettings>()
+            .add_event::<SatelliteSpawned>()
+            .add_event::<DespawnSatellite>()
+            .add_systems(Update, (start_spawn_transitions, start_despawn_transitions, animate_transitions).chain());
+    }
+}
+
+fn start_spawn_transitions(settings: Res<TransitionSettings>, mut spawned: EventReader<SatelliteSpawned>, mut commands: Commands) {
+    for ev in spawned.read() {
+        if settings.spawn.is_zero() {
+            continue;
+        }
+        if let Some(mut entity) = commands.get_entity(ev.entity) {
+            entity.insert(SpawningIn { t: 0.0 });
+        }
+    }
+}
+
+fn start_despawn_transitions(
+    settings: Res<TransitionSettings>,
+    mut requests: EventReader<DespawnSatellite>,
+    despawning: Query<(), With<Despawning>>,
+    mut commands: Commands
+) {
+    for request in requests.read() {
+        let Some(mut entity) = commands.get_entity(request.entity) else {
+            continue;
+        };
+        if request.instant || settings.despawn.is_zero() {
+            entity.despawn_recursive();
+        } else if !despawning.contains(request.entity) {
+            entity.remove::<SpawningIn>().insert(Despawning { t: 0.0 });
+        }
+    }
+}
+
+//real time, a transition looks the same at any simulation speed
+fn animate_transitions(
+    time: Res<Time>,
+    settings: Res<TransitionSettings>,
+    mut spawning: Query<(Entity, &mut SpawningIn, Option<&mut Transform>), Without<Despawning>>,
+    mut despawning: Query<(Entity, &mut Despawning, Option<&mut Transform>)>,
+    mut commands: Commands
+) {
+    for (entity, mut transition, transform) in spawning.iter_mut() {
+        transition.t = advance_transition(transition.t, time.delta(), settings.spawn);
+        if let Some(mut transform) = transform {
+            transform.scale = Vec3::splat(spawn_scale(transition.t));
+        }
+        if transition.t >= 1.0 {
+            commands.entity(entity).remove::<SpawningIn>();
+        }
+    }
+    for (entity, mut transition, transform) in despawning.iter_mut() {
+        transition.t = advance_transition(transition.t, time.delta(), settings.despawn);
+        if transition.t >= 1.0 {
+            commands.entity(entity).despawn_recursive();
+        } else if let Some(mut transform) = transform {
+            transform.scale = Vec3::splat(despawn_scale(transition.t));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::global::InGameSettings;
+    use crate::input::{Action, ActionTriggered};
+    use crate::propagation::InGameElements;
+    use crate::selection::{SelectionPlugin, SelectionSet};
+    use crate::stress::starlink_like_elements;
+
+    #[test]
+    fn test_transition_easing() {
+        assert_eq!(spawn_scale(0.0), MIN_SCALE);
+        assert_eq!(spawn_scale(1.0), 1.0);
+        assert_eq!(despawn_scale(0.0), 1.0);
+        assert_eq!(despawn_scale(1.0), MIN_SCALE);
+        let steps: Vec<f32> = (0..=20).map(|i| i as f32 / 20.0).collect();
+        assert!(steps.windows(2).all(|w| spawn_scale(w[0]) <= spawn_scale(w[1]) && despawn_scale(w[0]) >= despawn_scale(w[1])));
+        //growing in is mostly done half way, shrinking out has barely started
+        assert!(spawn_scale(0.5) > 0.8);
+        assert!(despawn_scale(0.5) > 0.8);
+
+        let duration = Duration::from_millis(400);
+        let t = advance_transition(0.0, Duration::from_millis(100), duration);
+        assert_eq!(t, 0.25);
+        assert_eq!(advance_transition(t, Duration::from_secs(1), duration), 1.0);
+        assert_eq!(advance_transition(0.0, Duration::ZERO, Duration::ZERO), 1.0);
+    }
+
+    #[test]
+    fn test_despawning_satellite_not_picked_and_removed() {
+        let frame = Duration::from_millis(50);
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, SelectionPlugin, SatelliteTransitionsPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(frame))
+            .insert_resource(InGameSettings::default());
+        let satellites: Vec<Entity> = starlink_like_elements(3, 7).into_iter()
+            .map(|el| app.world_mut().spawn((InGameElements(el), Transform::default())).id())
+            .collect();
+        app.update();
+
+        app.world_mut().send_event(DespawnSatellite::animated(satellites[0]));
+        app.world_mut().send_event(DespawnSatellite::instant(satellites[1]));
+        app.update();
+        assert!(app.world().get::<Despawning>(satellites[0]).is_some());
+        assert!(app.world().get_entity(satellites[1]).is_none());
+
+        app.world_mut().send_event(ActionTriggered(Action::SelectGroup));
+        app.update();
+        let selection = app.world().resource::<SelectionSet>();
+        assert!(!selection.contains(satellites[0]));
+        assert!(selection.contains(satellites[2]));
+        //still on screen, shrinking
+        let scale = app.world().get::<Transform>(satellites[0]).unwrap().scale.x;
+        assert!(scale < 1.0 && scale > MIN_SCALE, "{scale}");
+
+        let despawn = app.world().resource::<TransitionSettings>().despawn;
+        for _ in 0..(despawn.as_millis() / frame.as_millis()) {
+            app.update();
+        }
+        assert!(app.world().get_entity(satellites[0]).is_none());
+        assert!(app.world().get_entity(satellites[2]).is_some());
+    }
+}
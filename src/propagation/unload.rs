@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::input::ActionCategory;
+
+use super::bevy_integration::{GroupLoadStatus, LoadElements};
+use super::groups::SatelliteGroup;
+use super::interning::ElementsInterner;
+use super::invalidation::{InvalidateDerivedState, InvalidationReason};
+use super::transitions::{DespawnSatellite, Despawning};
+
+/// Unloads whole groups over several frames: the satellites are hidden at once, and despawned under a per-frame
+/// budget once the grace period is over. Until then a [`RestoreGroup`] brings them back without loading anything
+pub struct GroupUnloadPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct UnloadSettings {
+    /// Real time an unloaded group can be restored before its satellites start to be despawned
+    pub grace: Duration,
+    /// Satellites despawned in a frame, over all the unloaded groups
+    pub despawns_per_frame: usize
+}
+
+impl Default for UnloadSettings {
+    fn default() -> Self {
+        Self { grace: Duration::from_secs(10), despawns_per_frame: 500 }
+    }
+}
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct UnloadGroup {
+    pub group: String
+}
+
+/// Cancels the unload of the group, the satellites not despawned yet are shown again. Loading the group does it too
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct RestoreGroup {
+    pub group: String
+}
+
+/// The last satellite of an unloaded group is gone
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct GroupUnloaded {
+    pub group: String
+}
+
+/// Satellite of an unloaded group, hidden and neither propagated nor picked. Its time goes on, so it's restored
+/// where it would have been
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PendingUnload {
+    //visibility to restore
+    visibility: Option<Visibility>
+}
+
+/// Groups being unloaded, with the real time of their unload
+#[derive(Resource, Debug, Default)]
+pub struct GroupUnloads {
+    groups: HashMap<String, Duration>
+}
+
+impl GroupUnloads {
+    pub fn contains(&self, group: &str) -> bool {
+        self.groups.contains_key(group)
+    }
+
+    /// The grace period of the group is over, its satellites are being despawned
+    pub fn is_despawning(&self, group: &str, now: Duration, settings: &UnloadSettings) -> bool {
+        self.groups.get(group).is_some_and(|since| now.saturating_sub(*since) >= settings.grace)
+    }
+}
+
+impl Plugin for GroupUnloadPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<UnloadSettings>()
+            .init_resource::<GroupUnloads>()
+            .init_resource::<GroupLoadStatus>()
+            .init_resource::<ElementsInterner>()
+            .add_event::<UnloadGroup>()
+            .add_event::<RestoreGroup>()
+            .add_event::<GroupUnloaded>()
+            .add_event::<LoadElements>()
+            .add_event::<DespawnSatellite>()
+            .add_event::<InvalidateDerivedState>()
+            .register_command(
+                CommandDescriptor::event("Unload group", ActionCategory::General, |params| UnloadGroup {
+                    group: params[0].as_text().unwrap_or_default().to_owned()
+                })
+                .with_param("group", ParamKind::Text)
+            )
+            .register_command(
+                CommandDescriptor::event("Restore group", ActionCategory::General, |params| RestoreGroup {
+                    group: params[0].as_text().unwrap_or_default().to_owned()
+                })
+                .with_param("group", ParamKind::Text)
+            )
+            //before the transitions, a satellite is despawned in the frame it's picked
+            .add_systems(PreUpdate, despawn_unloaded)
+            .add_systems(Update, (start_unloads, restore_groups).chain());
+    }
+}
+
+//satellites of the group not on their way out already
+type Unloadable = (Without<PendingUnload>, Without<Despawning>);
+
+fn start_unloads(
+    mut requests: EventReader<UnloadGroup>,
+    time: Res<Time<Real>>,
+    satellites: Query<(Entity, &SatelliteGroup, Option<&Visibility>), Unloadable>,
+    mut unloads: ResMut<GroupUnloads>,
+    mut commands: Commands
+) {
+    for request in requests.read() {
+        let mut members = 0;
+        for (entity, _, visibility) in satellites.iter().filter(|(_, group, _)| group.0 == request.group) {
+            let mut entity = commands.entity(entity);
+            entity.insert(PendingUnload { visibility: visibility.copied() });
+            if visibility.is_some() {
+                entity.insert(Visibility::Hidden);
+            }
+            members += 1;
+        }
+        if members == 0 && !unloads.contains(&request.group) {
+            warn!("No satellites of group {} to unload", request.group);
+            continue;
+        }
+        info!("Unloading {members} satellites of {}", request.group);
+        unloads.groups.entry(request.group.clone()).or_insert(time.elapsed());
+    }
+}
+
+fn restore_groups(
+    mut requests: EventReader<RestoreGroup>,
+    mut loads: EventReader<LoadElements>,
+    satellites: Query<(Entity, &SatelliteGroup, &PendingUnload)>,
+    mut unloads: ResMut<GroupUnloads>,
+    mut invalidations: EventWriter<InvalidateDerivedState>,
+    mut commands: Commands
+) {
+    let groups: Vec<String> = requests.read().map(|r| r.group.clone()).chain(loads.read().flat_map(|l| l.groups.clone())).collect();
+    for group in groups {
+        if unloads.groups.remove(&group).is_none() {
+            continue;
+        }
+        let mut restored = 0;
+        for (entity, _, pending) in satellites.iter().filter(|(_, g, _)| g.0 == group) {
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.remove::<PendingUnload>();
+            if let Some(visibility) = pending.visibility {
+                entity_commands.insert(visibility);
+            }
+            //the displayed state is from before the unload
+            invalidations.send(InvalidateDerivedState { reason: InvalidationReason::Resumed(entity) });
+            restored += 1;
+        }
+        info!("Restored {restored} satellites of {group}");
+    }
+}
+
+//the budget is shared by the groups, the oldest unloads go first
+fn despawn_unloaded(
+    time: Res<Time<Real>>,
+    settings: Res<UnloadSettings>,
+    satellites: Query<(Entity, &SatelliteGroup), With<PendingUnload>>,
+    mut unloads: ResMut<GroupUnloads>,
+    (mut status, mut interner): (ResMut<GroupLoadStatus>, ResMut<ElementsInterner>),
+    mut despawns: EventWriter<DespawnSatellite>,
+    mut unloaded: EventWriter<GroupUnloaded>
+) {
+    let now = time.elapsed();
+    let mut due: Vec<(String, Duration)> = unloads.groups.iter()
+        .filter(|(group, _)| unloads.is_despawning(group, now, &settings))
+        .map(|(group, since)| (group.clone(), *since))
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+    due.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    let mut budget = settings.despawns_per_frame;
+    for (group, _) in due {
+        let mut members = satellites.iter().filter(|(_, g)| g.0 == group).map(|(entity, _)| entity).peekable();
+        if members.peek().is_none() {
+            //whatever the satellites shared is released with the last of them
+            unloads.groups.remove(&group);
+            status.remove(&group);
+            interner.prune();
+            info!("Unloaded {group}");
+            unloaded.send(GroupUnloaded { group });
+            continue;
+        }
+        for entity in members.take(budget) {
+            despawns.send(DespawnSatellite::instant(entity));
+            budget -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::propagation::{ElementsFetched, InGameElements, PropagatableDuration, Propageted};
+    use crate::stress::starlink_like_elements;
+    use crate::test_support::{fixture_elements, headless_app, load_group, EventLog, ScriptedClient};
+
+    const SHELL: usize = 1200;
+
+    fn app(settings: UnloadSettings) -> (App, Vec<Entity>, Vec<Entity>) {
+        let client = ScriptedClient::default()
+            .with_group("fixtures", fixture_elements())
+            .with_group("shell", starlink_like_elements(SHELL, 1501));
+        let mut app = headless_app(client);
+        app.insert_resource(settings);
+        let fixtures = load_group(&mut app, "fixtures", 100);
+        let shell = load_group(&mut app, "shell", 100);
+        app.update();
+        (app, fixtures, shell)
+    }
+
+    fn pending(app: &mut App) -> usize {
+        app.world_mut().query_filtered::<(), With<PendingUnload>>().iter(app.world()).count()
+    }
+
+    fn propagated(log: &mut EventLog<Propageted>, app: &App) -> Vec<Entity> {
+        log.read(app).iter().flat_map(|p| p.data().iter().map(|(e, _)| *e)).collect()
+    }
+
+    #[test]
+    fn test_restore_during_grace_period() {
+        let (mut app, fixtures, shell) = app(UnloadSettings { grace: Duration::from_secs(2), despawns_per_frame: 300 });
+        let elements: Vec<_> = shell.iter().map(|e| app.world().get::<InGameElements>(*e).unwrap().0.clone()).collect();
+
+        app.world_mut().send_event(UnloadGroup { group: "shell".to_owned() });
+        app.update();
+        let duration = |app: &App| app.world().get::<PropagatableDuration>(shell[0]).unwrap().0;
+        let before = duration(&app);
+        assert_eq!(pending(&mut app), SHELL);
+        assert!(shell.iter().all(|e| app.world().get::<Visibility>(*e) == Some(&Visibility::Hidden)));
+        assert_eq!(app.world().get::<Visibility>(fixtures[0]), Some(&Visibility::Inherited));
+
+        //half of the grace period without propagating the group
+        let mut propagations = EventLog::<Propageted>::new(&app);
+        let mut fetches = EventLog::<ElementsFetched>::new(&app);
+        let mut seen = vec![];
+        for _ in 0..10 {
+            app.update();
+            seen.extend(propagated(&mut propagations, &app));
+        }
+        assert!(seen.contains(&fixtures[0]));
+        assert!(!shell.iter().any(|e| seen.contains(e)));
+        //the time of the group goes on, a second with propagations every half a second
+        assert!(duration(&app) - before >= Duration::from_millis(500), "{:?}", duration(&app) - before);
+
+        app.world_mut().send_event(RestoreGroup { group: "shell".to_owned() });
+        app.update();
+        app.update();
+        assert_eq!(pending(&mut app), 0);
+        assert!(fetches.read(&app).is_empty());
+        for (entity, elements) in shell.iter().zip(&elements) {
+            assert!(Arc::ptr_eq(&app.world().get::<InGameElements>(*entity).unwrap().0, elements));
+            assert_eq!(app.world().get::<Visibility>(*entity), Some(&Visibility::Inherited));
+        }
+        //repropagated right away
+        let mut seen = propagated(&mut propagations, &app);
+        app.update();
+        seen.extend(propagated(&mut propagations, &app));
+        assert!(shell.iter().all(|e| seen.contains(e)));
+
+        //past the grace period nothing is despawned
+        for _ in 0..30 {
+            app.update();
+        }
+        assert!(shell.iter().all(|e| app.world().get_entity(*e).is_some()));
+    }
+
+    #[test]
+    fn test_group_despawned_under_budget() {
+        let budget = 300;
+        let (mut app, fixtures, shell) = app(UnloadSettings { grace: Duration::from_millis(500), despawns_per_frame: budget });
+        let mut unloaded = EventLog::<GroupUnloaded>::new(&app);
+        app.world_mut().send_event(UnloadGroup { group: "shell".to_owned() });
+
+        let alive = |app: &App| shell.iter().filter(|e| app.world().get_entity(**e).is_some()).count();
+        let mut counts = vec![];
+        let mut done = vec![];
+        for _ in 0..30 {
+            app.update();
+            counts.push(alive(&app));
+            done.extend(unloaded.read(&app));
+        }
+        assert!(counts[..4].iter().all(|count| *count == SHELL), "{counts:?}");
+        assert!(counts.windows(2).all(|w| w[0] - w[1] <= budget), "{counts:?}");
+        assert_eq!(counts.windows(2).filter(|w| w[0] > w[1]).count(), SHELL.div_ceil(budget));
+        assert_eq!(counts.last(), Some(&0));
+
+        assert_eq!(done, vec![GroupUnloaded { group: "shell".to_owned() }]);
+        assert_eq!(app.world().resource::<GroupLoadStatus>().get("shell"), None);
+        assert!(app.world().resource::<GroupLoadStatus>().get("fixtures").is_some());
+        assert!(!app.world().resource::<GroupUnloads>().contains("shell"));
+        assert!(fixtures.iter().all(|e| app.world().get_entity(*e).is_some()));
+    }
+}
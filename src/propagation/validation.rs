@@ -0,0 +1,135 @@
+use bevy::{prelude::*, transform::TransformSystem};
+use sgp4::Prediction;
+
+use crate::global::PredictionEnvelope;
+use crate::orbit::SatelliteOrbit;
+
+/// Consecutive rejected predictions of a satellite
+#[derive(Component, Default, Debug)]
+pub struct PredictionFailures(pub u32);
+
+/// Satellite kept producing rejected predictions, it is hidden and can't be focused
+#[derive(Component, Debug)]
+pub struct Unreliable;
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct BecameUnreliable {
+    pub entity: Entity,
+    pub norad_id: u64
+}
+
+/// Satellite without a prediction for longer than `StalenessGuard::freeze_after`, its position is held until the next one
+#[derive(Component, Debug)]
+pub struct StalePrediction;
+
+/// Satellite without a prediction for longer than `StalenessGuard::release_after`, it can't be focused until the next one
+#[derive(Component, Debug)]
+pub struct LostTrack;
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TrackLost {
+    pub entity: Entity,
+    pub norad_id: u64
+}
+
+/// SGP4 doesn't always error on corrupt elements, it may happily return NaN or positions far outside any orbit
+pub fn is_plausible_prediction(prediction: &Prediction, envelope: &PredictionEnvelope) -> bool {
+    let finite = prediction.position.iter().chain(prediction.velocity.iter()).all(|c| c.is_finite());
+    if !finite {
+        return false;
+    }
+    let [x, y, z] = prediction.position;
+    let radius = (x * x + y * y + z * z).sqrt();
+    radius >= envelope.min_radius_km && radius <= envelope.max_radius_km
+}
+
+/// Guard against NaN/inf satellite translations reaching the renderer, enabled by default in debug builds
+#[derive(Resource, Debug)]
+pub struct StrictTransforms {
+    pub enabled: bool,
+    /// Number of translations reset so far
+    pub corrections: u64
+}
+
+impl Default for StrictTransforms {
+    fn default() -> Self {
+        Self { enabled: cfg!(debug_assertions), corrections: 0 }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct LastValidTranslation(pub Vec3);
+
+pub struct StrictTransformsPlugin;
+
+impl Plugin for StrictTransformsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<StrictTransforms>()
+            .add_systems(PostUpdate, guard_satellite_transforms
+                .before(TransformSystem::TransformPropagate)
+                .run_if(|strict: Res<StrictTransforms>| strict.enabled));
+    }
+}
+
+fn guard_satellite_transforms(
+    mut strict: ResMut<StrictTransforms>,
+    mut satellites: Query<(Entity, &mut Transform, Option<&mut LastValidTranslation>), With<SatelliteOrbit>>,
+    mut commands: Commands
+) {
+    for (entity, mut transform, last_valid) in satellites.iter_mut() {
+        let translation = transform.translation;
+        match (translation.is_finite(), last_valid) {
+            (true, Some(mut last_valid)) => last_valid.0 = translation,
+            (true, None) => { commands.entity(entity).insert(LastValidTranslation(translation)); },
+            (false, last_valid) => {
+                let reset_to = last_valid.map(|l| l.0).unwrap_or(Vec3::ZERO);
+                strict.corrections += 1;
+                warn!("Non-finite translation {} of {}, resetting to {} ({} corrections so far)", translation, entity, reset_to, strict.corrections);
+                transform.translation = reset_to;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prediction(position: [f64; 3]) -> Prediction {
+        Prediction { position, velocity: [0.0, 7.5, 0.0] }
+    }
+
+    #[test]
+    fn test_nan_translation_is_reset() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, StrictTransformsPlugin))
+            .insert_resource(StrictTransforms { enabled: true, corrections: 0 });
+        let orbit = SatelliteOrbit::new(7000.0, 0.001, 51.6, 0.0, 0.0, 0.0, 0.0);
+        let entity = app.world_mut().spawn((Transform::from_xyz(1.0, 2.0, 3.0), orbit)).id();
+        app.update();
+        app.update();
+
+        app.world_mut().get_mut::<Transform>(entity).unwrap().translation.y = f32::NAN;
+        app.update();
+        assert_eq!(app.world().get::<Transform>(entity).unwrap().translation, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(app.world().resource::<StrictTransforms>().corrections, 1);
+
+        app.update();
+        assert_eq!(app.world().resource::<StrictTransforms>().corrections, 1);
+    }
+
+    #[test]
+    fn test_plausibility_envelope() {
+        let envelope = PredictionEnvelope::default();
+        assert!(is_plausible_prediction(&prediction([6800.0, 0.0, 0.0]), &envelope));
+        assert!(is_plausible_prediction(&prediction([0.0, 42164.0, 0.0]), &envelope));
+        assert!(!is_plausible_prediction(&prediction([f64::NAN, 0.0, 0.0]), &envelope));
+        assert!(!is_plausible_prediction(&prediction([f64::INFINITY, 0.0, 0.0]), &envelope));
+        assert!(!is_plausible_prediction(&Prediction { position: [6800.0, 0.0, 0.0], velocity: [f64::NAN; 3] }, &envelope));
+        //below the surface
+        assert!(!is_plausible_prediction(&prediction([1000.0, 0.0, 0.0]), &envelope));
+        assert!(!is_plausible_prediction(&prediction([0.0, 0.0, 3.0e6]), &envelope));
+    }
+}
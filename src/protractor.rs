@@ -0,0 +1,330 @@
+use bevy::{color::palettes::css::{GOLD, WHITE}, prelude::*, window::PrimaryWindow};
+
+use crate::camera::OverlayCamera;
+use crate::input::{Action, ActionTriggered};
+use crate::propagation::{Despawning, InGameElements, PendingUnload, Unreliable};
+
+//segments of a drawn arc, whatever its length
+const ARC_SEGMENTS: usize = 48;
+
+/// Measures angular separations as seen from the camera: in the protractor mode two clicks, on satellites or on the sky,
+/// make a measurement drawn as an arc with its angle. Measurements stay until cleared, and the clicks don't change the
+/// selection nor the focus while the mode is on
+pub struct ProtractorPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ProtractorSettings {
+    /// Clicks this close to a satellite on the screen snap to it
+    pub snap_radius_px: f32,
+    /// Arcs are drawn at this distance from the camera (in world units), in front of every body
+    pub arc_distance: f32
+}
+
+impl Default for ProtractorSettings {
+    fn default() -> Self {
+        Self { snap_radius_px: 12.0, arc_distance: 5.0 }
+    }
+}
+
+/// End of a measurement, a satellite is followed as it moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeasurePoint {
+    Satellite(Entity),
+    /// Direction in the world, it's the same point of the sky from anywhere
+    Sky(Vec3)
+}
+
+impl MeasurePoint {
+    /// Direction from the camera, `None` once the satellite is gone
+    pub fn direction_from(&self, camera: Vec3, position_of: impl Fn(Entity) -> Option<Vec3>) -> Option<Vec3> {
+        match self {
+            MeasurePoint::Satellite(entity) => position_of(*entity).and_then(|position| (position - camera).try_normalize()),
+            MeasurePoint::Sky(direction) => Some(*direction)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub from: MeasurePoint,
+    pub to: MeasurePoint
+}
+
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Protractor {
+    pub active: bool,
+    /// First point of the measurement in progress
+    pub pending: Option<MeasurePoint>,
+    pub measurements: Vec<Measurement>
+}
+
+impl Protractor {
+    /// Every other point completes a measurement
+    pub fn add_point(&mut self, point: MeasurePoint) {
+        match self.pending.take() {
+            Some(from) => self.measurements.push(Measurement { from, to: point }),
+            None => self.pending = Some(point)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending = None;
+        self.measurements.clear();
+    }
+}
+
+/// Run condition, the protractor takes the clicks
+pub fn protractor_active(protractor: Option<Res<Protractor>>) -> bool {
+    protractor.is_some_and(|protractor| protractor.active)
+}
+
+/// Between two directions, in degrees
+pub fn angular_separation(a: Vec3, b: Vec3) -> f32 {
+    a.angle_between(b).to_degrees()
+}
+
+/// Nearest candidate within the radius of the cursor, candidates are on the screen
+pub fn snap_target(cursor: Vec2, candidates: impl Iterator<Item = (Entity, Vec2)>, radius_px: f32) -> Option<Entity> {
+    candidates
+        .map(|(entity, position)| (entity, position.distance(cursor)))
+        .filter(|(_, distance)| *distance <= radius_px)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity)
+}
+
+/// The camera as a pinhole, from the cursor to directions in the world and back. Directions are projected as
+/// infinitely far points, so they stay on the same star while the camera moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenProjection {
+    world_from_view: Mat4,
+    clip_from_view: Mat4,
+    /// Logical size of the viewport
+    size: Vec2
+}
+
+impl ScreenProjection {
+    pub fn from_parts(world_from_view: Mat4, clip_from_view: Mat4, size: Vec2) -> Self {
+        Self { world_from_view, clip_from_view, size }
+    }
+
+    /// `None` until the camera knows its viewport
+    pub fn new(camera: &Camera, transform: &GlobalTransform) -> Option<Self> {
+        Some(Self::from_parts(transform.compute_matrix(), camera.clip_from_view(), camera.logical_viewport_size()?))
+    }
+
+    fn viewport_position(&self, ndc: Vec2) -> Vec2 {
+        let position = (ndc + Vec2::ONE) / 2.0 * self.size;
+        Vec2::new(position.x, self.size.y - position.y)
+    }
+
+    /// Direction of the ray through the cursor, like `Camera::viewport_to_world`
+    pub fn direction_at(&self, cursor: Vec2) -> Option<Vec3> {
+        let ndc = Vec2::new(cursor.x, self.size.y - cursor.y) * 2.0 / self.size - Vec2::ONE;
+        let ndc_to_world = self.world_from_view * self.clip_from_view.inverse();
+        let near = ndc_to_world.project_point3(ndc.extend(1.0));
+        let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+        (far - near).try_normalize()
+    }
+
+    /// Where the direction is on the screen, `None` behind the camera
+    pub fn project_direction(&self, direction: Vec3) -> Option<Vec2> {
+        let view = self.world_from_view.inverse().transform_vector3(direction);
+        let clip = self.clip_from_view * view.extend(0.0);
+        (clip.w > f32::EPSILON).then(|| self.viewport_position(clip.xy() / clip.w))
+    }
+
+    /// Where the point is on the screen, `None` outside of the depth range
+    pub fn project_point(&self, point: Vec3) -> Option<Vec2> {
+        let ndc = (self.clip_from_view * self.world_from_view.inverse()).project_point3(point);
+        (ndc.z > 0.0 && ndc.z <= 1.0).then(|| self.viewport_position(ndc.xy()))
+    }
+}
+
+//along the great circle between the directions
+fn arc_direction(from: Vec3, to: Vec3, t: f32) -> Vec3 {
+    Quat::IDENTITY.slerp(Quat::from_rotation_arc(from, to), t) * from
+}
+
+//satellites a click can snap to
+type Measurable = (With<InGameElements>, Without<Unreliable>, Without<Despawning>, Without<PendingUnload>);
+
+//angle of the measurement with the same index
+#[derive(Component)]
+struct MeasurementLabel(usize);
+
+impl Plugin for ProtractorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ProtractorSettings>()
+            .init_resource::<Protractor>()
+            .add_event::<ActionTriggered>()
+            .add_systems(Update, (
+                protractor_keyboard,
+                measure_click.run_if(resource_exists::<ButtonInput<MouseButton>>),
+                draw_measurements
+            ).chain());
+    }
+}
+
+fn protractor_keyboard(mut actions: EventReader<ActionTriggered>, mut protractor: ResMut<Protractor>) {
+    for ActionTriggered(action) in actions.read() {
+        match action {
+            Action::ToggleProtractor => {
+                protractor.active = !protractor.active;
+                protractor.pending = None;
+            },
+            Action::CloseOverlay if protractor.active => {
+                protractor.active = false;
+                protractor.pending = None;
+            },
+            Action::ClearMeasurements => protractor.clear(),
+            _ => {}
+        }
+    }
+}
+
+fn measure_click(
+    buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    satellites: Query<(Entity, &GlobalTransform), Measurable>,
+    settings: Res<ProtractorSettings>,
+    mut protractor: ResMut<Protractor>
+) {
+    if !protractor.active || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let Some(view) = cameras.iter().next().and_then(|(camera, transform)| ScreenProjection::new(camera, transform)) else {
+        return;
+    };
+    let on_screen = satellites.iter().filter_map(|(entity, transform)| view.project_point(transform.translation()).map(|p| (entity, p)));
+    let point = match snap_target(cursor, on_screen, settings.snap_radius_px) {
+        Some(entity) => MeasurePoint::Satellite(entity),
+        None => match view.direction_at(cursor) {
+            Some(direction) => MeasurePoint::Sky(direction),
+            None => return
+        }
+    };
+    protractor.add_point(point);
+}
+
+//arcs in front of the camera look the same from where they're measured, the labels are at their middle
+fn draw_measurements(
+    mut gizmos: Gizmos,
+    cameras: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    satellites: Query<&GlobalTransform>,
+    mut labels: Query<(Entity, &MeasurementLabel, &mut Text, &mut Style, &mut Visibility)>,
+    (protractor, settings): (Res<Protractor>, Res<ProtractorSettings>),
+    mut commands: Commands
+) {
+    for index in labels.iter().count()..protractor.measurements.len() {
+        commands.spawn((
+            TextBundle::from_section("", TextStyle { font_size: 14.0, color: GOLD.into(), ..default() })
+                .with_style(Style { position_type: PositionType::Absolute, ..default() }),
+            MeasurementLabel(index)
+        ));
+    }
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    let eye = camera_transform.translation();
+    let view = ScreenProjection::new(camera, camera_transform);
+    let position_of = |entity| satellites.get(entity).ok().map(GlobalTransform::translation);
+    for (entity, MeasurementLabel(index), mut text, mut style, mut visibility) in labels.iter_mut() {
+        let Some(measurement) = protractor.measurements.get(*index) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let directions = measurement.from.direction_from(eye, position_of).zip(measurement.to.direction_from(eye, position_of));
+        let Some((from, to)) = directions else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        gizmos.linestrip((0..=ARC_SEGMENTS).map(|i| eye + arc_direction(from, to, i as f32 / ARC_SEGMENTS as f32) * settings.arc_distance), GOLD);
+        match view.and_then(|view| view.project_direction(arc_direction(from, to, 0.5))) {
+            Some(position) => {
+                text.sections[0].value = format!("{:.2}°", angular_separation(from, to));
+                style.left = Val::Px(position.x + 6.0);
+                style.top = Val::Px(position.y - 18.0);
+                *visibility = Visibility::Inherited;
+            },
+            None => *visibility = Visibility::Hidden
+        }
+    }
+    //the first point of the measurement in progress
+    if let Some(direction) = protractor.pending.and_then(|point| point.direction_from(eye, position_of)) {
+        let center = eye + direction * settings.arc_distance;
+        gizmos.sphere(center, Quat::IDENTITY, settings.arc_distance * 0.004, WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::render::camera::CameraProjection;
+
+    use super::*;
+
+    const SIZE: Vec2 = Vec2::new(1280.0, 720.0);
+
+    fn projection(transform: Transform) -> ScreenProjection {
+        let perspective = PerspectiveProjection { fov: 60.0f32.to_radians(), aspect_ratio: SIZE.x / SIZE.y, ..default() };
+        ScreenProjection::from_parts(transform.compute_matrix(), perspective.get_clip_from_view(), SIZE)
+    }
+
+    #[test]
+    fn test_clicks_snap_to_the_nearest_satellite() {
+        let mut world = World::new();
+        let [near, far, other] = [(); 3].map(|_| world.spawn_empty().id());
+        let candidates = [(far, Vec2::new(110.0, 100.0)), (near, Vec2::new(104.0, 97.0)), (other, Vec2::new(300.0, 300.0))];
+        assert_eq!(snap_target(Vec2::new(100.0, 100.0), candidates.into_iter(), 12.0), Some(near));
+        assert_eq!(snap_target(Vec2::new(100.0, 100.0), candidates.into_iter().filter(|(e, _)| *e != near), 12.0), Some(far));
+        assert_eq!(snap_target(Vec2::new(200.0, 200.0), candidates.into_iter(), 12.0), None);
+
+        //a snapped satellite is followed, a click on the sky is a fixed direction
+        let mut protractor = Protractor::default();
+        protractor.add_point(MeasurePoint::Satellite(near));
+        assert!(protractor.measurements.is_empty());
+        protractor.add_point(MeasurePoint::Sky(Vec3::X));
+        protractor.add_point(MeasurePoint::Sky(Vec3::Y));
+        assert_eq!(protractor.measurements, vec![Measurement { from: MeasurePoint::Satellite(near), to: MeasurePoint::Sky(Vec3::X) }]);
+        assert_eq!(protractor.pending, Some(MeasurePoint::Sky(Vec3::Y)));
+        let position_of = |entity| (entity == near).then_some(Vec3::new(0.0, 10.0, 0.0));
+        let direction = protractor.measurements[0].from.direction_from(Vec3::new(0.0, 0.0, 10.0), position_of).unwrap();
+        assert_abs_diff_eq!(angular_separation(direction, Vec3::X), 90.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(angular_separation(direction, Vec3::Y), 45.0, epsilon = 1e-4);
+        assert_eq!(MeasurePoint::Satellite(far).direction_from(Vec3::ZERO, position_of), None);
+    }
+
+    #[test]
+    fn test_sky_directions_are_reprojected_after_camera_motion() {
+        let camera = Transform::from_xyz(0.0, 0.0, 500.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let cursor = Vec2::new(300.0, 200.0);
+        let direction = projection(camera).direction_at(cursor).unwrap();
+        let round_trip = projection(camera).project_direction(direction).unwrap();
+        assert!(round_trip.distance(cursor) < 0.01, "{round_trip}");
+
+        //moving doesn't move the sky, turning does
+        let moved = Transform { translation: Vec3::new(120.0, -40.0, 300.0), ..camera };
+        assert!(projection(moved).project_direction(direction).unwrap().distance(cursor) < 0.01);
+        let turned = Transform::from_translation(camera.translation).looking_to(direction, Vec3::Y);
+        assert!(projection(turned).project_direction(direction).unwrap().distance(SIZE / 2.0) < 0.01);
+        let away = Transform::from_translation(camera.translation).looking_to(-direction, Vec3::Y);
+        assert_eq!(projection(away).project_direction(direction), None);
+
+        //from the center to the side is half of the horizontal field of view
+        let view = projection(camera);
+        let center = view.direction_at(SIZE / 2.0).unwrap();
+        let side = view.direction_at(Vec2::new(SIZE.x, SIZE.y / 2.0)).unwrap();
+        let half_horizontal = ((30.0f32.to_radians()).tan() * SIZE.x / SIZE.y).atan().to_degrees();
+        assert_abs_diff_eq!(angular_separation(center, side), half_horizontal, epsilon = 1e-3);
+        assert_abs_diff_eq!(angular_separation(arc_direction(center, side, 0.5), center), half_horizontal / 2.0, epsilon = 1e-3);
+
+        //points are projected where their directions are
+        let point = camera.translation + direction * 200.0;
+        assert!(view.project_point(point).unwrap().distance(cursor) < 0.01);
+    }
+}
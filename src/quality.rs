@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*
+};
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::future_marks::FutureMarksSettings;
+use crate::global::InGameSettings;
+use crate::input::ActionCategory;
+use crate::past_ghosts::PastGhostSettings;
+use crate::propagation::ProgressiveVisuals;
+
+pub const QUALITY_LEVEL: DiagnosticPath = DiagnosticPath::const_new("quality/level");
+pub const SMOOTHED_FRAME_TIME: DiagnosticPath = DiagnosticPath::const_new("quality/smoothed_frame_time_ms");
+
+/// Watches the smoothed frame time and walks down the [`QualitySettings::ladder`] while the frames stay over budget,
+/// back up once there is headroom again. The level can be pinned from the palette
+pub struct QualityPlugin;
+
+/// One rung of the fallback ladder, a level applies every step up to it
+#[derive(Debug, Clone, PartialEq)]
+pub enum QualityStep {
+    /// Segments of the drawn orbit ellipses
+    OrbitResolution(usize),
+    /// Only the orbit of the primary selected satellite is drawn
+    FocusedOrbitOnly,
+    /// Past ghosts and future marks are hidden
+    HideTrails,
+    /// Satellites are materialized from this fraction of the usual distance, the rest stay batched points
+    PointsBeyond(f32),
+    /// Real time between propagations
+    PropagationInterval(Duration)
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct QualitySettings {
+    pub enabled: bool,
+    /// Frame time the app aims for
+    pub budget: Duration,
+    /// Weight of the latest frame in the smoothed frame time
+    pub smoothing: f32,
+    /// Real time the smoothed frame time has to stay over budget before stepping down
+    pub degrade_after: Duration,
+    /// Fraction of the budget the smoothed frame time has to stay under before stepping back up,
+    /// the band between it and the budget keeps the level from oscillating
+    pub headroom: f32,
+    pub recover_after: Duration,
+    pub ladder: Vec<QualityStep>
+}
+
+impl Default for QualitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            budget: Duration::from_micros(33_333),
+            smoothing: 0.1,
+            degrade_after: Duration::from_secs(2),
+            headroom: 0.6,
+            recover_after: Duration::from_secs(5),
+            ladder: vec![
+                QualityStep::OrbitResolution(24),
+                QualityStep::FocusedOrbitOnly,
+                QualityStep::HideTrails,
+                QualityStep::PointsBeyond(0.25),
+                QualityStep::PropagationInterval(Duration::from_secs(5))
+            ]
+        }
+    }
+}
+
+/// Steps of the ladder applied, 0 is full quality
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct QualityLevel {
+    pub level: usize,
+    /// The controller leaves a pinned level alone
+    pub pinned: bool
+}
+
+/// How orbit ellipses are drawn
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct OrbitDetail {
+    pub resolution: usize,
+    pub focused_only: bool
+}
+
+impl Default for OrbitDetail {
+    fn default() -> Self {
+        Self { resolution: 64, focused_only: false }
+    }
+}
+
+/// Smoothed frame time with the time spent over budget and under the headroom
+#[derive(Resource, Debug, Clone, Default)]
+pub struct QualityController {
+    smoothed: Option<f32>,
+    over_budget: Duration,
+    under_headroom: Duration
+}
+
+impl QualityController {
+    /// Smoothed frame time in seconds
+    pub fn smoothed(&self) -> Option<f32> {
+        self.smoothed
+    }
+
+    /// Feeds one frame, the new level when the frame times were out of the band long enough
+    pub fn observe(&mut self, frame_time: Duration, settings: &QualitySettings, level: usize) -> Option<usize> {
+        let sample = frame_time.as_secs_f32();
+        let smoothed = self.smoothed.map_or(sample, |s| s + (sample - s) * settings.smoothing);
+        self.smoothed = Some(smoothed);
+        let budget = settings.budget.as_secs_f32();
+        if smoothed > budget {
+            self.over_budget += frame_time;
+            self.under_headroom = Duration::ZERO;
+        } else if smoothed < budget * settings.headroom {
+            self.under_headroom += frame_time;
+            self.over_budget = Duration::ZERO;
+        } else {
+            self.over_budget = Duration::ZERO;
+            self.under_headroom = Duration::ZERO;
+        }
+
+        let next = if self.over_budget >= settings.degrade_after && level < settings.ladder.len() {
+            level + 1
+        } else if self.under_headroom >= settings.recover_after && level > 0 {
+            level - 1
+        } else {
+            return None;
+        };
+        //the next step is judged on its own frames
+        self.over_budget = Duration::ZERO;
+        self.under_headroom = Duration::ZERO;
+        Some(next)
+    }
+}
+
+//values the ladder overrides, taken when leaving full quality and put back when returning to it
+#[derive(Debug, Clone)]
+struct Baseline {
+    orbit_detail: OrbitDetail,
+    ghosts: Option<bool>,
+    future_marks: Option<bool>,
+    points_distance: Option<f32>,
+    propagation_interval: Option<Duration>
+}
+
+impl Plugin for QualityPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<QualitySettings>()
+            .init_resource::<QualityLevel>()
+            .init_resource::<QualityController>()
+            .init_resource::<OrbitDetail>()
+            .register_diagnostic(Diagnostic::new(QUALITY_LEVEL))
+            .register_diagnostic(Diagnostic::new(SMOOTHED_FRAME_TIME))
+            .register_command(
+                CommandDescriptor::new("Pin quality level", ActionCategory::General, |params, world| {
+                    let max = world.resource::<QualitySettings>().ladder.len();
+                    let level = params[0].as_number().unwrap_or_default().max(0.0) as usize;
+                    *world.resource_mut::<QualityLevel>() = QualityLevel { level: level.min(max), pinned: true };
+                })
+                .with_param("level", ParamKind::Number)
+            )
+            .register_command(CommandDescriptor::new("Unpin quality level", ActionCategory::General, |_, world| {
+                world.resource_mut::<QualityLevel>().pinned = false;
+            }))
+            .add_systems(Update, (control_quality, apply_quality_level.run_if(resource_changed::<QualityLevel>)).chain())
+            .add_systems(Last, report_quality);
+    }
+}
+
+fn control_quality(time: Res<Time<Real>>, settings: Res<QualitySettings>, mut controller: ResMut<QualityController>, mut level: ResMut<QualityLevel>) {
+    if !settings.enabled || level.pinned || time.delta().is_zero() {
+        return;
+    }
+    if let Some(next) = controller.observe(time.delta(), &settings, level.level) {
+        info!("Frame time {:.1} ms, quality level {} -> {}", controller.smoothed().unwrap_or_default() * 1000.0, level.level, next);
+        level.level = next;
+    }
+}
+
+fn apply_quality_level(
+    level: Res<QualityLevel>,
+    settings: Res<QualitySettings>,
+    mut baseline: Local<Option<Baseline>>,
+    mut orbit_detail: ResMut<OrbitDetail>,
+    (mut ghosts, mut future_marks): (Option<ResMut<PastGhostSettings>>, Option<ResMut<FutureMarksSettings>>),
+    mut visuals: Option<ResMut<ProgressiveVisuals>>,
+    mut game_settings: Option<ResMut<InGameSettings>>
+) {
+    let base = baseline.get_or_insert_with(|| Baseline {
+        orbit_detail: *orbit_detail,
+        ghosts: ghosts.as_ref().map(|g| g.enabled),
+        future_marks: future_marks.as_ref().map(|f| f.enabled),
+        points_distance: visuals.as_ref().map(|v| v.distance),
+        propagation_interval: game_settings.as_ref().map(|s| s.propagation.real_time_interval)
+    });
+
+    //everything from the baseline, then the steps of the level on top of it
+    let mut detail = base.orbit_detail;
+    let mut trails = true;
+    let mut distance = base.points_distance;
+    let mut interval = base.propagation_interval;
+    for step in settings.ladder.iter().take(level.level) {
+        match step {
+            QualityStep::OrbitResolution(resolution) => detail.resolution = detail.resolution.min(*resolution),
+            QualityStep::FocusedOrbitOnly => detail.focused_only = true,
+            QualityStep::HideTrails => trails = false,
+            QualityStep::PointsBeyond(fraction) => distance = distance.map(|d| d * fraction),
+            QualityStep::PropagationInterval(longer) => interval = interval.map(|i| i.max(*longer))
+        }
+    }
+
+    *orbit_detail = detail;
+    if let (Some(ghosts), Some(enabled)) = (ghosts.as_mut(), base.ghosts) {
+        ghosts.enabled = enabled && trails;
+    }
+    if let (Some(marks), Some(enabled)) = (future_marks.as_mut(), base.future_marks) {
+        marks.enabled = enabled && trails;
+    }
+    if let (Some(visuals), Some(distance)) = (visuals.as_mut(), distance) {
+        visuals.distance = distance;
+    }
+    if let (Some(game_settings), Some(interval)) = (game_settings.as_mut(), interval) {
+        if game_settings.propagation.real_time_interval != interval {
+            game_settings.propagation.real_time_interval = interval;
+        }
+    }
+    //back at full quality, changes made from now on are the new baseline
+    if level.level == 0 {
+        *baseline = None;
+    }
+}
+
+fn report_quality(level: Res<QualityLevel>, controller: Res<QualityController>, mut diagnostics: Diagnostics) {
+    diagnostics.add_measurement(&QUALITY_LEVEL, || level.level as f64);
+    if let Some(smoothed) = controller.smoothed() {
+        diagnostics.add_measurement(&SMOOTHED_FRAME_TIME, || smoothed as f64 * 1000.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    fn run(controller: &mut QualityController, settings: &QualitySettings, level: &mut usize, frame_ms: u64, frames: usize) -> Vec<usize> {
+        let mut levels = vec![];
+        for _ in 0..frames {
+            if let Some(next) = controller.observe(Duration::from_millis(frame_ms), settings, *level) {
+                *level = next;
+                levels.push(next);
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn test_controller_steps_with_hysteresis() {
+        let settings = QualitySettings::default();
+        let mut controller = QualityController::default();
+        let mut level = 0;
+
+        //short spikes are smoothed out
+        for _ in 0..10 {
+            assert_eq!(run(&mut controller, &settings, &mut level, 22, 30), Vec::<usize>::new());
+            assert_eq!(run(&mut controller, &settings, &mut level, 200, 1), Vec::<usize>::new());
+        }
+        //a sustained 10 fps walks down one step per 2 s, stopping at the bottom of the ladder
+        assert_eq!(run(&mut controller, &settings, &mut level, 100, 200), vec![1, 2, 3, 4, 5]);
+        //25 fps is within the band, nothing changes either way
+        assert_eq!(run(&mut controller, &settings, &mut level, 25, 1000), Vec::<usize>::new());
+        //60 fps climbs back up one step per 5 s
+        assert_eq!(run(&mut controller, &settings, &mut level, 16, 340), vec![4]);
+        assert_eq!(run(&mut controller, &settings, &mut level, 16, 2000), vec![3, 2, 1, 0]);
+        assert_eq!(level, 0);
+    }
+
+    #[test]
+    fn test_levels_toggle_settings() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, QualityPlugin))
+            .init_resource::<PastGhostSettings>()
+            .init_resource::<FutureMarksSettings>()
+            .init_resource::<ProgressiveVisuals>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)));
+        app.world_mut().resource_mut::<PastGhostSettings>().enabled = true;
+        let distance = ProgressiveVisuals::default().distance;
+        let state = |app: &App| (
+            app.world().resource::<QualityLevel>().level,
+            *app.world().resource::<OrbitDetail>(),
+            app.world().resource::<PastGhostSettings>().enabled,
+            app.world().resource::<FutureMarksSettings>().enabled,
+            app.world().resource::<ProgressiveVisuals>().distance
+        );
+
+        //10 fps, a step down every 2 s
+        for _ in 0..25 {
+            app.update();
+        }
+        assert_eq!(state(&app), (1, OrbitDetail { resolution: 24, focused_only: false }, true, true, distance));
+        for _ in 0..40 {
+            app.update();
+        }
+        assert_eq!(state(&app), (3, OrbitDetail { resolution: 24, focused_only: true }, false, false, distance));
+        for _ in 0..20 {
+            app.update();
+        }
+        assert_eq!(state(&app), (4, OrbitDetail { resolution: 24, focused_only: true }, false, false, distance * 0.25));
+
+        //pinned, the frame rate doesn't matter
+        app.world_mut().resource_mut::<QualityLevel>().pinned = true;
+        app.world_mut().resource_mut::<QualityLevel>().level = 0;
+        for _ in 0..40 {
+            app.update();
+        }
+        assert_eq!(state(&app), (0, OrbitDetail::default(), true, true, distance));
+    }
+}
@@ -0,0 +1,122 @@
+//Scenarios of the whole headless simulation, on the fixtures of `test_support`
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::global::InGameSettings;
+use crate::propagation::{
+    predict_at, DerivedStateDirty, DespawnSatellite, InGameElements, InvalidateDerivedState, InvalidationReason, PropagatableDuration, Propageted, SatelliteGroup
+};
+use crate::simulation_clock::SimulationClock;
+use crate::stress::starlink_like_elements;
+use crate::test_support::{fixture_elements, headless_app, load_group, run_until, EventLog, ScriptedClient};
+use crate::world_frame::WORLD_FRAME;
+
+fn client() -> ScriptedClient {
+    ScriptedClient::default()
+        .with_group("fixtures", fixture_elements())
+        .with_group("shell", starlink_like_elements(20, 1497))
+}
+
+fn translations(app: &mut App, entities: &[Entity]) -> Vec<Vec3> {
+    entities.iter().map(|e| app.world().get::<Transform>(*e).unwrap().translation).collect()
+}
+
+//distance of the displayed positions from SGP4 at the time of each satellite, in km
+fn worst_offset(app: &App, entities: &[Entity]) -> f32 {
+    let scale = app.world().resource::<InGameSettings>().scale;
+    entities.iter().map(|e| {
+        let elements = &app.world().get::<InGameElements>(*e).unwrap().0;
+        let minutes = app.world().get::<PropagatableDuration>(*e).unwrap().minutes_since_epoch();
+        let expected = WORLD_FRAME.to_world(Vec3::from_array(predict_at(elements, minutes, false).unwrap().position.map(|c| c as f32)));
+        (app.world().get::<Transform>(*e).unwrap().translation / scale).distance(expected)
+    }).fold(0.0, f32::max)
+}
+
+#[test]
+fn test_load_and_unload_a_group() {
+    let mut app = headless_app(client());
+    let fixtures = load_group(&mut app, "fixtures", 100);
+    let shell = load_group(&mut app, "shell", 100);
+    assert_eq!((fixtures.len(), shell.len()), (5, 20));
+
+    let mut propagations = EventLog::<Propageted>::new(&app);
+    let mut propagated = vec![];
+    run_until(&mut app, |app| {
+        propagated.extend(propagations.read(app).iter().flat_map(|p| p.data().iter().map(|(e, _)| *e)));
+        fixtures.iter().chain(&shell).all(|e| propagated.contains(e))
+    }, 20);
+
+    for entity in &fixtures {
+        app.world_mut().send_event(DespawnSatellite::animated(*entity));
+    }
+    run_until(&mut app, |app| fixtures.iter().all(|e| app.world().get_entity(*e).is_none()), 20);
+    let groups: Vec<String> = app.world_mut().query::<&SatelliteGroup>().iter(app.world()).map(|g| g.0.clone()).collect();
+    assert_eq!(groups, vec!["shell".to_owned(); 20]);
+
+    //the rest of the simulation goes on without them
+    propagated.clear();
+    for _ in 0..20 {
+        app.update();
+        propagated.extend(propagations.read(&app).iter().flat_map(|p| p.data().iter().map(|(e, _)| *e)));
+    }
+    assert!(shell.iter().all(|e| propagated.contains(e)));
+    assert!(!fixtures.iter().any(|e| propagated.contains(e)));
+}
+
+#[test]
+fn test_time_jump_repropagates_at_once() {
+    let mut app = headless_app(client());
+    //no scheduled propagation, only the one of the jump
+    app.world_mut().resource_mut::<InGameSettings>().propagation.real_time_interval = Duration::from_secs(3600);
+    let entities = load_group(&mut app, "fixtures", 100);
+    for _ in 0..5 {
+        app.update();
+    }
+    assert!(worst_offset(&app, &entities) < 10.0);
+
+    for entity in &entities {
+        *app.world_mut().get_mut::<PropagatableDuration>(*entity).unwrap() += Duration::from_secs(6 * 3600);
+    }
+    //6 hours later the displayed positions are far from the new ones
+    assert!(worst_offset(&app, &entities) > 1000.0);
+    app.world_mut().send_event(InvalidateDerivedState { reason: InvalidationReason::TimeJump });
+    let updates = run_until(&mut app, |app| app.world_mut().query::<&DerivedStateDirty>().iter(app.world()).count() == 0, 10);
+    assert!(updates <= 4);
+    app.update();
+    //a few frames of dead reckoning at most
+    assert!(worst_offset(&app, &entities) < 10.0, "{}", worst_offset(&app, &entities));
+}
+
+#[test]
+fn test_pause_and_resume() {
+    let mut app = headless_app(client());
+    let entities = load_group(&mut app, "fixtures", 100);
+    for _ in 0..10 {
+        app.update();
+    }
+
+    app.world_mut().resource_mut::<InGameSettings>().simulation_speed = 0.0;
+    run_until(&mut app, |app| app.world().resource::<SimulationClock>().speed() == 0.0, 20);
+    //a propagation at the paused time settles the dead reckoning
+    for _ in 0..10 {
+        app.update();
+    }
+    let elapsed = app.world().resource::<SimulationClock>().elapsed_seconds();
+    let paused = translations(&mut app, &entities);
+    for _ in 0..20 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<SimulationClock>().elapsed_seconds(), elapsed);
+    for (before, after) in paused.iter().zip(translations(&mut app, &entities)) {
+        assert!(before.distance(after) < 1e-4, "{before} moved to {after} while paused");
+    }
+
+    app.world_mut().resource_mut::<InGameSettings>().simulation_speed = 1.0;
+    for _ in 0..20 {
+        app.update();
+    }
+    assert!(app.world().resource::<SimulationClock>().elapsed_seconds() > elapsed + 1.0);
+    //the LEO fixture moves 7.6 km, 0.076 units, per simulated second
+    assert!(paused[0].distance(translations(&mut app, &entities)[0]) > 0.05);
+}
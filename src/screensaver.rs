@@ -0,0 +1,344 @@
+use std::time::Duration;
+
+use bevy::{input::mouse::{MouseMotion, MouseWheel}, prelude::*};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::camera::{CameraBookmark, CameraFov, OverlayCamera};
+use crate::propagation::{Despawning, ElementsExt, InGameElements, OrbitClass, PendingUnload, Unreliable};
+use crate::quality::QualityLevel;
+use crate::selection::Watchlist;
+use crate::world_frame::WORLD_FRAME;
+
+//how quickly (per second) the camera turns towards a new target
+const TURN_RATE: f32 = 0.8;
+
+/// Ambient tour after a while without input: the camera circles the Earth and looks at one interesting satellite
+/// after another, showing its name and a line of stats. Any input puts the camera back where it was
+pub struct ScreensaverPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ScreensaverSettings {
+    pub enabled: bool,
+    /// Real time without input before the tour starts
+    pub idle_after: Duration,
+    /// Real time each satellite is shown
+    pub dwell: Duration,
+    /// Camera rotation around the Earth axis (radians per real second)
+    pub rotation_speed: f32,
+    /// Watchlisted satellites are this many times more likely to be shown
+    pub watchlist_weight: f64,
+    /// Quality level pinned during the tour, a lighter scene for an unattended screen
+    pub quality_level: Option<usize>,
+    /// Seed of the picks, from the OS when `None`
+    pub seed: Option<u64>
+}
+
+impl Default for ScreensaverSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            idle_after: Duration::from_secs(5 * 60),
+            dwell: Duration::from_secs(20),
+            rotation_speed: 0.03,
+            watchlist_weight: 5.0,
+            quality_level: Some(2),
+            seed: None
+        }
+    }
+}
+
+/// State of the tour, [`Screensaver::is_active`] while it runs
+#[derive(Resource, Debug, Default)]
+pub struct Screensaver {
+    idle: Duration,
+    //input seen this frame
+    woken: bool,
+    tour: Option<AmbientTour>
+}
+
+#[derive(Debug)]
+struct AmbientTour {
+    camera: Option<CameraBookmark>,
+    quality: Option<QualityLevel>,
+    target: Option<Entity>,
+    shown: Duration
+}
+
+impl Screensaver {
+    pub fn is_active(&self) -> bool {
+        self.tour.is_some()
+    }
+
+    /// Satellite the tour is showing
+    pub fn target(&self) -> Option<Entity> {
+        self.tour.as_ref().and_then(|tour| tour.target)
+    }
+}
+
+/// Run condition of the systems steering the camera themselves
+pub fn screensaver_active(screensaver: Option<Res<Screensaver>>) -> bool {
+    screensaver.is_some_and(|s| s.is_active())
+}
+
+#[derive(Component)]
+struct ScreensaverCaption;
+
+impl Plugin for ScreensaverPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ScreensaverSettings>()
+            .init_resource::<Screensaver>()
+            .add_event::<MouseMotion>()
+            .add_event::<MouseWheel>()
+            .add_systems(Startup, spawn_caption)
+            .add_systems(Update, (detect_input, track_idle, pick_targets, move_ambient_camera, update_caption).chain());
+    }
+}
+
+/// Pick weights: every orbit class is as likely as any other however many satellites it has, watchlisted satellites
+/// get `watchlist_weight` times more
+pub fn tour_weights(candidates: &[(Entity, OrbitClass, bool)], watchlist_weight: f64) -> Vec<(Entity, f64)> {
+    let class_size = |class: OrbitClass| candidates.iter().filter(|(_, c, _)| *c == class).count() as f64;
+    candidates.iter()
+        .map(|(entity, class, watched)| (*entity, if *watched { watchlist_weight } else { 1.0 } / class_size(*class)))
+        .collect()
+}
+
+/// Weighted random pick, `None` without candidates
+pub fn pick_weighted(weights: &[(Entity, f64)], rng: &mut impl Rng) -> Option<Entity> {
+    let index = WeightedIndex::new(weights.iter().map(|(_, weight)| *weight)).ok()?;
+    Some(weights[index.sample(rng)].0)
+}
+
+fn detect_input(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    buttons: Option<Res<ButtonInput<MouseButton>>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut screensaver: ResMut<Screensaver>
+) {
+    //every event is read, the next frame only sees new ones
+    let moved = motion.read().count() > 0;
+    let scrolled = wheel.read().count() > 0;
+    screensaver.woken = moved || scrolled
+        || keys.is_some_and(|k| k.get_pressed().next().is_some())
+        || buttons.is_some_and(|b| b.get_pressed().next().is_some());
+}
+
+fn track_idle(
+    time: Res<Time<Real>>,
+    settings: Res<ScreensaverSettings>,
+    mut screensaver: ResMut<Screensaver>,
+    mut cameras: Query<&mut Transform, (With<Camera3d>, Without<OverlayCamera>)>,
+    mut fov: Option<ResMut<CameraFov>>,
+    mut quality: Option<ResMut<QualityLevel>>
+) {
+    if screensaver.woken {
+        screensaver.idle = Duration::ZERO;
+        //back to the exact state the tour started from
+        if let Some(tour) = screensaver.tour.take() {
+            info!("Input, leaving the ambient tour");
+            if let (Some(bookmark), Ok(mut transform), Some(fov)) = (tour.camera, cameras.get_single_mut(), fov.as_deref_mut()) {
+                bookmark.restore(&mut transform, fov);
+            }
+            if let (Some(saved), Some(quality)) = (tour.quality, quality.as_deref_mut()) {
+                *quality = saved;
+            }
+        }
+        return;
+    }
+    screensaver.idle += time.delta();
+    if screensaver.is_active() || !settings.enabled || screensaver.idle < settings.idle_after {
+        return;
+    }
+    info!("Idle for {:?}, starting the ambient tour", screensaver.idle);
+    let camera = cameras.get_single().ok().zip(fov.as_deref()).map(|(transform, fov)| CameraBookmark::capture(transform, fov));
+    let saved_quality = quality.as_deref().copied();
+    if let (Some(level), Some(quality)) = (settings.quality_level, quality.as_deref_mut()) {
+        *quality = QualityLevel { level, pinned: true };
+    }
+    screensaver.tour = Some(AmbientTour { camera, quality: saved_quality, target: None, shown: Duration::ZERO });
+}
+
+//satellites on their way out aren't shown anymore
+type Showable = (Without<Despawning>, Without<PendingUnload>);
+
+fn pick_targets(
+    time: Res<Time<Real>>,
+    settings: Res<ScreensaverSettings>,
+    mut screensaver: ResMut<Screensaver>,
+    mut rng: Local<Option<ChaCha8Rng>>,
+    satellites: Query<(Entity, &InGameElements, Has<Unreliable>), Showable>,
+    watchlist: Option<Res<Watchlist>>
+) {
+    let Some(tour) = screensaver.tour.as_mut() else {
+        return;
+    };
+    tour.shown += time.delta();
+    let gone = tour.target.is_some_and(|target| !satellites.get(target).is_ok_and(|(_, _, unreliable)| !unreliable));
+    if tour.target.is_some() && !gone && tour.shown < settings.dwell {
+        return;
+    }
+    let reliable = || satellites.iter().filter(|(_, _, unreliable)| !unreliable);
+    let count = reliable().count();
+    let candidates: Vec<_> = reliable()
+        //the next one is a different satellite, unless it's the only one
+        .filter(|(entity, _, _)| Some(*entity) != tour.target || count == 1)
+        .map(|(entity, elements, _)| {
+            let class = OrbitClass::classify(elements.0.period_minutes(), elements.0.eccentricity);
+            (entity, class, watchlist.as_ref().is_some_and(|w| w.contains(entity)))
+        })
+        .collect();
+    let rng = rng.get_or_insert_with(|| settings.seed.map_or_else(ChaCha8Rng::from_entropy, ChaCha8Rng::seed_from_u64));
+    tour.target = pick_weighted(&tour_weights(&candidates, settings.watchlist_weight), rng);
+    tour.shown = Duration::ZERO;
+}
+
+//circles the Earth axis at the current distance, turning towards the target
+fn move_ambient_camera(
+    time: Res<Time<Real>>,
+    settings: Res<ScreensaverSettings>,
+    screensaver: Res<Screensaver>,
+    targets: Query<&Transform, Without<Camera3d>>,
+    mut cameras: Query<&mut Transform, (With<Camera3d>, Without<OverlayCamera>)>
+) {
+    if !screensaver.is_active() {
+        return;
+    }
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    let orbit = Quat::from_axis_angle(WORLD_FRAME.north, settings.rotation_speed * dt);
+    camera.translation = orbit * camera.translation;
+    let focus = screensaver.target().and_then(|t| targets.get(t).ok()).map_or(Vec3::ZERO, |t| t.translation);
+    let Some(forward) = (focus - camera.translation).try_normalize() else {
+        return;
+    };
+    //north up, unless looking along the axis
+    let up = if forward.dot(WORLD_FRAME.north).abs() > 0.99 { WORLD_FRAME.equinox } else { WORLD_FRAME.north };
+    let target_rotation = Transform::default().looking_to(forward, up).rotation;
+    camera.rotation = camera.rotation.slerp(target_rotation, (dt * TURN_RATE).min(1.0));
+}
+
+fn spawn_caption(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_section("", TextStyle { font_size: 22.0, color: Color::WHITE, ..default() })
+                .with_style(Style { position_type: PositionType::Absolute, top: Val::Px(24.0), left: Val::Px(24.0), ..default() })
+        },
+        ScreensaverCaption
+    ));
+}
+
+/// Name and a line of stats of a satellite shown by the tour
+pub fn caption(elements: &sgp4::Elements) -> String {
+    let period = elements.period_minutes();
+    let class = OrbitClass::classify(period, elements.eccentricity);
+    format!(
+        "{}\n{} · {:?} · {:.1} min · {:.1}° · e {:.4}",
+        elements.object_name.as_deref().unwrap_or("Unnamed"), elements.norad_id, class, period, elements.inclination, elements.eccentricity
+    )
+}
+
+fn update_caption(
+    screensaver: Res<Screensaver>,
+    satellites: Query<&InGameElements>,
+    mut shown: Local<Option<Entity>>,
+    mut captions: Query<(&mut Text, &mut Visibility), With<ScreensaverCaption>>
+) {
+    if !screensaver.is_changed() {
+        return;
+    }
+    let target = screensaver.target();
+    let visible = target.is_some() && screensaver.is_active();
+    for (mut text, mut visibility) in captions.iter_mut() {
+        visibility.set_if_neq(if visible { Visibility::Inherited } else { Visibility::Hidden });
+        if *shown != target {
+            text.sections[0].value = target.and_then(|t| satellites.get(t).ok()).map(|el| caption(&el.0)).unwrap_or_default();
+        }
+    }
+    *shown = target;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::stress::starlink_like_elements;
+
+    #[test]
+    fn test_picks_follow_the_weights() {
+        let entities: Vec<Entity> = (0..4).map(Entity::from_raw).collect();
+        //three LEO satellites, one of them watched, and a lone GEO one
+        let candidates = [
+            (entities[0], OrbitClass::Leo, false),
+            (entities[1], OrbitClass::Leo, true),
+            (entities[2], OrbitClass::Leo, false),
+            (entities[3], OrbitClass::Geo, false)
+        ];
+        let weights = tour_weights(&candidates, 5.0);
+        let mut rng = ChaCha8Rng::seed_from_u64(1489);
+        let mut counts: HashMap<Entity, usize> = HashMap::new();
+        for _ in 0..20_000 {
+            *counts.entry(pick_weighted(&weights, &mut rng).unwrap()).or_default() += 1;
+        }
+        //1/3, 5/3, 1/3 and 1 out of 10/3
+        for (entity, expected) in entities.iter().zip([0.1, 0.5, 0.1, 0.3]) {
+            let share = counts[entity] as f64 / 20_000.0;
+            assert!((share - expected).abs() < 0.02, "{entity:?}: {share}");
+        }
+        assert_eq!(pick_weighted(&[], &mut rng), None);
+    }
+
+    #[test]
+    fn test_idle_tour_and_restore_on_input() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, ScreensaverPlugin))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<CameraFov>()
+            .init_resource::<QualityLevel>()
+            .insert_resource(ScreensaverSettings { idle_after: Duration::from_secs(10), dwell: Duration::from_secs(3), seed: Some(1489), ..default() })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(500)));
+        for elements in starlink_like_elements(5, 1489) {
+            app.world_mut().spawn((InGameElements(elements), Transform::from_xyz(0.0, 80.0, 0.0)));
+        }
+        let start = Transform::from_xyz(0.0, 0.0, 500.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let camera = app.world_mut().spawn((Camera3d::default(), start)).id();
+        app.world_mut().resource_mut::<CameraFov>().set(35.0);
+
+        //9 s idle
+        for _ in 0..19 {
+            app.update();
+        }
+        assert!(!app.world().resource::<Screensaver>().is_active());
+        let mut targets = vec![];
+        for _ in 0..120 {
+            app.update();
+            let target = app.world().resource::<Screensaver>().target();
+            if targets.last() != target.as_ref() {
+                targets.extend(target);
+            }
+        }
+        assert!(app.world().resource::<Screensaver>().is_active());
+        assert_eq!(*app.world().resource::<QualityLevel>(), QualityLevel { level: 2, pinned: true });
+        //a new satellite every 3 s, never the same twice in a row
+        assert!(targets.len() >= 15, "{targets:?}");
+        assert!(targets.windows(2).all(|pair| pair[0] != pair[1]));
+        assert_ne!(*app.world().get::<Transform>(camera).unwrap(), start);
+        app.world_mut().resource_mut::<CameraFov>().set(80.0);
+
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Space);
+        app.update();
+        assert!(!app.world().resource::<Screensaver>().is_active());
+        assert_eq!(*app.world().get::<Transform>(camera).unwrap(), start);
+        assert_eq!(app.world().resource::<CameraFov>().degrees, 35.0);
+        assert_eq!(*app.world().resource::<QualityLevel>(), QualityLevel::default());
+    }
+}
@@ -1,8 +1,47 @@
 use bevy::prelude::*;
 use super::orbit::*;
+use super::world_frame::WORLD_FRAME;
 
 pub trait Selectable {
-    fn is_selected(&self, camera_ray: Ray3d) -> bool;
+    /// Angle between the ray and the direction to the body, relative to the angular radius of the body widened by `min_angle`.
+    /// Hits score below 1.0, the lowest score is the best hit
+    fn pick_score(&self, camera_ray: Ray3d, min_angle: f32) -> Option<f32>;
+
+    fn is_selected(&self, camera_ray: Ray3d) -> bool {
+        self.pick_score(camera_ray, 0.0).is_some()
+    }
+}
+
+/// Score of a sphere seen along the ray, independent of the field of view and the resolution since both are angles
+pub fn angular_pick_score(camera_ray: Ray3d, center: Vec3, radius: f32, min_angle: f32) -> Option<f32> {
+    let to_center = center - camera_ray.origin;
+    let distance = to_center.length();
+    //the camera inside the body sees it everywhere
+    if distance <= radius {
+        return Some(0.0);
+    }
+    let separation = angle(*camera_ray.direction, to_center);
+    let angular_radius = (radius / distance).asin();
+    let score = separation / (angular_radius + min_angle);
+    (score < 1.0).then_some(score)
+}
+
+//acos loses the small angles of a few pixels to rounding
+fn angle(a: Vec3, b: Vec3) -> f32 {
+    a.cross(b).length().atan2(a.dot(b))
+}
+
+/// Angle the pixel tolerance spans around the cursor. Perspective shrinks pixels towards the screen edges,
+/// more along the radial axis, so it's measured there as the geometric mean of both axes
+pub fn pixel_tolerance_angle(cursor: Vec2, pixels: f32, ray_through: impl Fn(Vec2) -> Option<Ray3d>) -> f32 {
+    let Some(ray) = ray_through(cursor) else {
+        return 0.0;
+    };
+    [Vec2::X, Vec2::Y].into_iter()
+        .filter_map(|axis| ray_through(cursor + axis * pixels))
+        .map(|offset| angle(*ray.direction, *offset.direction))
+        .reduce(|a, b| (a * b).sqrt())
+        .unwrap_or(0.0)
 }
 
 #[derive(Default, Debug, Clone)]
@@ -15,30 +54,22 @@ pub struct SelectableCelestialBody<D> {
 
 impl <D> Selectable for SelectableCelestialBody<D> {
 
-    fn is_selected(&self, camera_ray: Ray3d) -> bool {
-        let plane_origin = self.transform.translation;
-        let Some(distance) = camera_ray.intersect_plane(plane_origin, self.orbital_plane) else {
-            return false;
-        };
-        let global_cursor = camera_ray.get_point(distance);
-        global_cursor.distance(self.transform.translation) < self.radius * 1.5
+    fn pick_score(&self, camera_ray: Ray3d, min_angle: f32) -> Option<f32> {
+        angular_pick_score(camera_ray, self.transform.translation, self.radius, min_angle)
     }
 }
 
 impl <D> Propagatable for SelectableCelestialBody<D> {
     fn position_for(&mut self, orbit: &SatelliteOrbit, scale: f32) {
         let SatellitePose { position, .. } = orbit.to_translation_and_rotation();
-        self.transform = Transform::from_translation(position * scale);
+        self.transform = Transform::from_translation(WORLD_FRAME.to_world(position) * scale);
     }
 }
 
 impl <D> SelectableCelestialBody<D> {
 
     pub fn initialize_from_orbit(radius: f32, data: D, orbit: &SatelliteOrbit, scale: f32) -> Self {
-        let v1 = orbit.get_right_ascention_vector();
-        let v2 = orbit.get_encentricity_vector();
-        let normal = v1.cross(v2);
-        let orbital_plane = InfinitePlane3d::new(normal);
+        let orbital_plane = InfinitePlane3d::new(WORLD_FRAME.to_world(orbit.orbit_normal().as_vec3()));
         let radius = radius * scale;
 
         let mut value = Self {
@@ -65,13 +96,68 @@ impl <T> ManySelectables<T> {
 }
 
 impl <T: Selectable> ManySelectables<T> {
-    pub fn select(&self, camera_ray: Ray3d) -> Option<&T> {
-        self.0.iter().find(|s| s.is_selected(camera_ray))
+    pub fn select(&self, camera_ray: Ray3d, min_angle: f32) -> Option<&T> {
+        self.0.iter()
+            .filter_map(|s| s.pick_score(camera_ray, min_angle).map(|score| (s, score)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(s, _)| s)
     }
 }
 
 impl <C, T: Selectable> ManySelectables<(C, T)> {
-    pub fn select_with_context(self, camera_ray: Ray3d) -> Option<(C, T)> {
-        self.0.into_iter().find(|(_, t)| t.is_selected(camera_ray)).map(|(c, t)| (c, t))
+    pub fn select_with_context(self, camera_ray: Ray3d, min_angle: f32) -> Option<(C, T)> {
+        self.0.into_iter()
+            .filter_map(|(c, t)| t.pick_score(camera_ray, min_angle).map(|score| (c, t, score)))
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+            .map(|(c, t, _)| (c, t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //ultrawide window, the camera at the origin looking down -Z
+    const VIEWPORT: Vec2 = Vec2::new(3440.0, 1440.0);
+    const TOLERANCE_PX: f32 = 6.0;
+
+    fn ray_through(fov_degrees: f32) -> impl Fn(Vec2) -> Option<Ray3d> {
+        let half_height = (fov_degrees.to_radians() / 2.0).tan();
+        move |cursor: Vec2| {
+            let ndc = Vec2::new(2.0 * cursor.x / VIEWPORT.x - 1.0, 1.0 - 2.0 * cursor.y / VIEWPORT.y);
+            let direction = Vec3::new(ndc.x * half_height * VIEWPORT.x / VIEWPORT.y, ndc.y * half_height, -1.0);
+            Some(Ray3d::new(Vec3::ZERO, direction))
+        }
+    }
+
+    fn body(name: &'static str, center: Vec3, radius: f32) -> SelectableCelestialBody<&'static str> {
+        SelectableCelestialBody { transform: Transform::from_translation(center), orbital_plane: InfinitePlane3d::new(Vec3::Z), radius, data: name }
+    }
+
+    #[test]
+    fn test_edge_of_screen_picking_at_any_fov() {
+        for fov in [30.0, 60.0, 100.0] {
+            let ray_through = ray_through(fov);
+            for cursor in [VIEWPORT / 2.0, Vec2::new(VIEWPORT.x - 20.0, 40.0), Vec2::new(15.0, VIEWPORT.y / 2.0)] {
+                let min_angle = pixel_tolerance_angle(cursor, TOLERANCE_PX, &ray_through);
+                let ray = ray_through(cursor).unwrap();
+                let at = |offset: Vec2, distance: f32| ray_through(cursor + offset).unwrap().get_point(distance);
+
+                //a distant satellite under the cursor, a close one a few pixels off and partly covering it
+                let candidates = ManySelectables::new(vec![
+                    body("near", at(Vec2::new(9.0, 0.0), 60.0), 1.5),
+                    body("target", at(Vec2::ZERO, 900.0), 1.5),
+                    body("far", at(Vec2::new(0.0, 3.0), 9000.0), 1.5)
+                ]);
+                assert_eq!(candidates.select(ray, min_angle).map(|b| b.data), Some("target"), "{fov}° at {cursor}");
+
+                //tiny bodies are hit within the pixel tolerance, wherever they are on the screen
+                let tiny = |offset: Vec2| ManySelectables::new(vec![body("tiny", at(offset, 5000.0), 0.01)]);
+                for offset in [Vec2::new(3.0, 0.0), Vec2::new(0.0, -3.0)] {
+                    assert!(tiny(offset).select(ray, min_angle).is_some(), "{fov}° at {cursor}");
+                    assert!(tiny(offset * 5.0).select(ray, min_angle).is_none(), "{fov}° at {cursor}");
+                }
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,661 @@
+use std::collections::BTreeSet;
+#[cfg(feature = "export")]
+use std::{fs::File, io::{self, Write}, path::{Path, PathBuf}};
+
+use bevy::{color::palettes::css::*, prelude::*, window::PrimaryWindow};
+
+use crate::camera::OverlayCamera;
+use crate::commands::{fuzzy_score, CommandDescriptor, ParamKind, ParamValue, RegisterCommand};
+use crate::edits::{Change, Edit};
+#[cfg(feature = "export")]
+use crate::error::SkytracioError;
+use crate::global::InGameSettings;
+use crate::input::{Action, ActionCategory, ActionTriggered};
+use crate::notes::CustomTags;
+#[cfg(feature = "export")]
+use crate::notes::Notes;
+use crate::propagation::{Despawning, DespawnSatellite, InGameElements, MarkerStyle, PendingUnload, SatelliteGroup, StyleLayer, StyleModifier, Unreliable, EARTH_RADIUS_KM};
+#[cfg(feature = "export")]
+use crate::propagation::{ensure_satellite_index, SatelliteIndex};
+use crate::protractor::protractor_active;
+#[cfg(feature = "export")]
+use crate::world_frame::WORLD_FRAME;
+
+//below this cursor travel (px) a press-release is a click, not a rectangle
+const DRAG_THRESHOLD: f32 = 5.0;
+//cursor distance (px) from a projected satellite to hover it
+const HOVER_RADIUS: f32 = 8.0;
+
+/// Multi-selection of satellites, the last added member is the primary one (camera lock)
+pub struct SelectionPlugin;
+
+#[derive(Resource, Default, Debug)]
+pub struct SelectionSet {
+    members: Vec<Entity>
+}
+
+impl SelectionSet {
+    pub fn select_single(&mut self, entity: Entity) {
+        self.members.clear();
+        self.members.push(entity);
+    }
+
+    /// Adds the entity as the new primary member, already selected entities are moved to the end
+    pub fn add(&mut self, entity: Entity) {
+        self.members.retain(|e| *e != entity);
+        self.members.push(entity);
+    }
+
+    /// Adds entities without changing the primary member
+    pub fn extend(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let primary = self.primary();
+        for entity in entities {
+            if !self.members.contains(&entity) {
+                self.members.push(entity);
+            }
+        }
+        if let Some(primary) = primary {
+            self.add(primary);
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.members.retain(|e| *e != entity);
+    }
+
+    pub fn clear(&mut self) {
+        self.members.clear();
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.members.contains(&entity)
+    }
+
+    pub fn primary(&self) -> Option<Entity> {
+        self.members.last().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.members.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct Watchlist(BTreeSet<Entity>);
+
+impl Watchlist {
+    pub fn insert(&mut self, entity: Entity) -> bool {
+        self.0.insert(entity)
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        self.0.remove(&entity)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+/// Sent on a click that wasn't a rectangle drag, picking itself is up to the game since it knows what is selectable
+#[derive(Event, Debug, Clone)]
+pub struct PickRequest {
+    pub cursor: Vec2,
+    //shift held, the picked entity is added to the selection instead of replacing it
+    pub additive: bool
+}
+
+/// Operations applied to every member of the [`SelectionSet`]
+#[derive(Event, Debug, Clone)]
+pub enum BulkOperation {
+    AddToWatchlist,
+    RemoveFromWatchlist,
+    SetOrbitDisplay(bool),
+    OverrideColor(Color),
+    #[cfg(feature = "export")]
+    ExportStates(PathBuf),
+    Despawn
+}
+
+/// Sent when a satellite is focused by name through the command palette, the camera follows it
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct FocusSatellite {
+    pub entity: Entity
+}
+
+/// Orbit of the satellite is not drawn
+#[derive(Component, Debug)]
+pub struct OrbitHidden;
+
+/// Satellite under the cursor
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct HoveredSatellite(pub Option<Entity>);
+
+#[derive(Resource, Default, Debug)]
+struct DragSelection {
+    start: Option<Vec2>,
+    current: Option<Vec2>
+}
+
+#[derive(Component)]
+struct SelectionRectangle;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        let input_condition = resource_exists::<ButtonInput<MouseButton>>
+            .and_then(resource_exists::<ButtonInput<KeyCode>>);
+        app
+            .init_resource::<SelectionSet>()
+            .init_resource::<Watchlist>()
+            .init_resource::<DragSelection>()
+            .init_resource::<HoveredSatellite>()
+            .add_event::<PickRequest>()
+            .add_event::<BulkOperation>()
+            .add_event::<FocusSatellite>()
+            .add_event::<DespawnSatellite>()
+            .add_event::<ActionTriggered>()
+            .register_command(CommandDescriptor::new("Focus satellite", ActionCategory::Selection, focus_satellite).with_param("name or NORAD id", ParamKind::Text))
+            .register_command(CommandDescriptor::new("Select matching", ActionCategory::Selection, select_matching).with_param("name, NORAD id or tag", ParamKind::Text))
+            .register_command(CommandDescriptor::new("Focus selection", ActionCategory::Selection, |_, world| {
+                if let Some(entity) = world.resource::<SelectionSet>().primary() {
+                    world.send_event(FocusSatellite { entity });
+                }
+            }).satellite_scoped())
+            .register_command(CommandDescriptor::event("Remove selection from watchlist", ActionCategory::Selection, |_| BulkOperation::RemoveFromWatchlist).satellite_scoped())
+            .add_systems(Update, select_group)
+            .add_systems(Startup, spawn_selection_rectangle)
+            .add_systems(Update, (selection_input.run_if(input_condition.and_then(not(protractor_active))), update_selection_rectangle).chain())
+            .add_systems(Update, update_hover.run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, apply_bulk_operations)
+            .add_systems(Update, (
+                style_selection.run_if(resource_changed::<SelectionSet>),
+                style_hover.run_if(resource_changed::<HoveredSatellite>)
+            ).after(apply_bulk_operations).after(update_hover))
+            .add_systems(Update, highlight_selection.run_if(resource_exists::<GizmoConfigStore>));
+        #[cfg(feature = "export")]
+        ensure_satellite_index(app);
+        #[cfg(feature = "export")]
+        app
+            .register_command(
+                CommandDescriptor::event("Export selection to", ActionCategory::Selection, |params| BulkOperation::ExportStates(params[0].as_path().cloned().unwrap_or_default()))
+                    .with_param("file", ParamKind::Path)
+                    .satellite_scoped()
+            )
+            //before the operations changing the selection
+            .add_systems(Update, export_selection.run_if(resource_exists::<InGameSettings>).before(apply_bulk_operations));
+    }
+}
+
+//satellites the selection can take, reliable ones that aren't going away
+type Pickable = (With<InGameElements>, Without<Unreliable>, Without<Despawning>, Without<PendingUnload>);
+
+fn spawn_selection_rectangle(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            border_color: Color::srgba(0.6, 0.8, 1.0, 0.8).into(),
+            background_color: Color::srgba(0.6, 0.8, 1.0, 0.1).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        SelectionRectangle
+    ));
+}
+
+fn selection_input(
+    (buttons, keys): (Res<ButtonInput<MouseButton>>, Res<ButtonInput<KeyCode>>),
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    candidates: Query<(Entity, &GlobalTransform), Pickable>,
+    settings: Res<InGameSettings>,
+    (mut drag, mut selection): (ResMut<DragSelection>, ResMut<SelectionSet>),
+    mut picks: EventWriter<PickRequest>
+) {
+    let additive = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    let cursor = window.cursor_position();
+    if buttons.just_pressed(MouseButton::Left) {
+        drag.start = cursor;
+    }
+    drag.current = drag.start.and(cursor);
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+
+    let (Some(start), Some(end)) = (drag.start.take(), cursor) else {
+        return;
+    };
+    drag.current = None;
+    if start.distance(end) < DRAG_THRESHOLD {
+        picks.send(PickRequest { cursor: end, additive });
+        return;
+    }
+
+    let Ok((camera, camera_transform)) = q_camera.get_single() else {
+        return;
+    };
+    let hits = rectangle_hits(
+        Rect::from_corners(start, end),
+        camera_transform.translation(),
+        EARTH_RADIUS_KM * settings.scale,
+        candidates.iter().map(|(entity, transform)| (entity, transform.translation())),
+        |position| camera.world_to_viewport(camera_transform, position)
+    );
+    if !additive {
+        selection.clear();
+    }
+    selection.extend(hits);
+}
+
+fn select_group(
+    mut actions: EventReader<ActionTriggered>,
+    candidates: Query<Entity, Pickable>,
+    groups: Query<&SatelliteGroup>,
+    mut selection: ResMut<SelectionSet>
+) {
+    if !actions.read().any(|ActionTriggered(action)| *action == Action::SelectGroup) {
+        return;
+    }
+    //the group of the primary member, everything when nothing with a group is focused
+    let focused_group = selection.primary().and_then(|e| groups.get(e).ok()).cloned();
+    let in_group = |entity: &Entity| focused_group.as_ref().is_none_or(|g| groups.get(*entity).is_ok_and(|group| group == g));
+    selection.extend(candidates.iter().filter(in_group));
+}
+
+/// Satellite by its NORAD id or its name (ignoring case), like the palette commands take them
+pub fn find_satellite(world: &mut World, name: &str) -> Option<Entity> {
+    let norad_id: Option<u64> = name.parse().ok();
+    world.query::<(Entity, &InGameElements)>().iter(world)
+        .find(|(_, elements)| Some(elements.0.norad_id) == norad_id || elements.0.object_name.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+        .map(|(entity, _)| entity)
+}
+
+/// How well the query matches a satellite, exact NORAD ids, names and tags first and then fuzzy name or tag matches
+pub fn satellite_score(query: &str, name: Option<&str>, norad_id: u64, tags: &[String]) -> Option<i32> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+    if query.parse() == Ok(norad_id) {
+        return Some(3000);
+    }
+    if name.is_some_and(|n| n.eq_ignore_ascii_case(query)) {
+        return Some(2000);
+    }
+    if tags.iter().any(|t| t.eq_ignore_ascii_case(query)) {
+        return Some(1000);
+    }
+    name.into_iter()
+        .chain(tags.iter().map(String::as_str))
+        .filter_map(|candidate| fuzzy_score(query, candidate))
+        .max()
+}
+
+/// Satellites matching the query, best match first
+pub fn search_satellites(world: &mut World, query: &str) -> Vec<Entity> {
+    let mut matches: Vec<(i32, Entity)> = world.query::<(Entity, &InGameElements, Option<&CustomTags>)>().iter(world)
+        .filter_map(|(entity, elements, tags)| {
+            let tags = tags.map_or(&[][..], |t| &t.0);
+            satellite_score(query, elements.0.object_name.as_deref(), elements.0.norad_id, tags).map(|score| (score, entity))
+        })
+        .collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, entity)| entity).collect()
+}
+
+fn focus_satellite(params: &[ParamValue], world: &mut World) {
+    let Some(name) = params.first().and_then(ParamValue::as_text) else {
+        return;
+    };
+    //exact matches first, then the best tag or fuzzy match
+    let found = find_satellite(world, name).or_else(|| search_satellites(world, name).first().copied());
+    match found {
+        Some(entity) => {
+            world.resource_mut::<SelectionSet>().select_single(entity);
+            world.send_event(FocusSatellite { entity });
+        },
+        None => warn!("No satellite named {name}")
+    }
+}
+
+fn select_matching(params: &[ParamValue], world: &mut World) {
+    let Some(query) = params.first().and_then(ParamValue::as_text) else {
+        return;
+    };
+    let matches = search_satellites(world, query);
+    let mut selection = world.resource_mut::<SelectionSet>();
+    selection.clear();
+    //the best match becomes the primary member
+    selection.extend(matches.iter().rev().copied());
+}
+
+/// Candidates projected inside the screen rectangle and not hidden behind the planet (a sphere at the origin)
+fn rectangle_hits(
+    rect: Rect,
+    eye: Vec3,
+    occluder_radius: f32,
+    candidates: impl Iterator<Item = (Entity, Vec3)>,
+    project: impl Fn(Vec3) -> Option<Vec2>
+) -> Vec<Entity> {
+    candidates
+        .filter(|(_, position)| project(*position).is_some_and(|p| rect.contains(p)))
+        .filter(|(_, position)| !occluded_by_sphere(eye, *position, occluder_radius))
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Candidate projected closest to the cursor, within `max_distance` and not hidden behind the planet
+fn nearest_hit(
+    cursor: Vec2,
+    max_distance: f32,
+    eye: Vec3,
+    occluder_radius: f32,
+    candidates: impl Iterator<Item = (Entity, Vec3)>,
+    project: impl Fn(Vec3) -> Option<Vec2>
+) -> Option<Entity> {
+    candidates
+        .filter(|(_, position)| !occluded_by_sphere(eye, *position, occluder_radius))
+        .filter_map(|(entity, position)| project(position).map(|p| (entity, p.distance(cursor))))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+fn occluded_by_sphere(eye: Vec3, target: Vec3, radius: f32) -> bool {
+    let to_target = target - eye;
+    let length = to_target.length();
+    if length == 0.0 {
+        return false;
+    }
+    let direction = to_target / length;
+    //closest approach of the line of sight to the sphere center
+    let t = (-eye).dot(direction).clamp(0.0, length);
+    t < length && (eye + direction * t).length() < radius
+}
+
+fn update_hover(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), Without<OverlayCamera>>,
+    candidates: Query<(Entity, &GlobalTransform), Pickable>,
+    settings: Res<InGameSettings>,
+    mut hovered: ResMut<HoveredSatellite>
+) {
+    let cursor = q_window.get_single().ok().and_then(|w| w.cursor_position());
+    let (Some(cursor), Ok((camera, camera_transform))) = (cursor, q_camera.get_single()) else {
+        hovered.set_if_neq(HoveredSatellite(None));
+        return;
+    };
+    let hit = nearest_hit(
+        cursor,
+        HOVER_RADIUS,
+        camera_transform.translation(),
+        EARTH_RADIUS_KM * settings.scale,
+        candidates.iter().map(|(entity, transform)| (entity, transform.translation())),
+        |position| camera.world_to_viewport(camera_transform, position)
+    );
+    hovered.set_if_neq(HoveredSatellite(hit));
+}
+
+fn style_selection(selection: Res<SelectionSet>, mut previous: Local<Vec<Entity>>, mut styles: Query<&mut MarkerStyle>) {
+    for entity in previous.drain(..).filter(|e| !selection.contains(*e)) {
+        if let Ok(mut style) = styles.get_mut(entity) {
+            style.clear(StyleLayer::Selected);
+        }
+    }
+    for entity in selection.iter() {
+        if let Ok(mut style) = styles.get_mut(entity) {
+            style.set(StyleLayer::Selected, StyleModifier { base_color: Some(LIGHT_SKY_BLUE.into()), emissive_boost: 0.3, ..default() });
+        }
+        previous.push(entity);
+    }
+}
+
+fn style_hover(hovered: Res<HoveredSatellite>, mut previous: Local<Option<Entity>>, mut styles: Query<&mut MarkerStyle>) {
+    if let Some(mut style) = previous.take().and_then(|e| styles.get_mut(e).ok()) {
+        style.clear(StyleLayer::Hover);
+    }
+    if let Some(mut style) = hovered.0.and_then(|e| styles.get_mut(e).ok()) {
+        style.set(StyleLayer::Hover, StyleModifier::glow(0.8));
+    }
+    *previous = hovered.0;
+}
+
+fn update_selection_rectangle(drag: Res<DragSelection>, mut rectangles: Query<(&mut Style, &mut Visibility), With<SelectionRectangle>>) {
+    let Ok((mut style, mut visibility)) = rectangles.get_single_mut() else {
+        return;
+    };
+    match (drag.start, drag.current) {
+        (Some(start), Some(current)) if start.distance(current) >= DRAG_THRESHOLD => {
+            let rect = Rect::from_corners(start, current);
+            style.left = Val::Px(rect.min.x);
+            style.top = Val::Px(rect.min.y);
+            style.width = Val::Px(rect.width());
+            style.height = Val::Px(rect.height());
+            *visibility = Visibility::Visible;
+        },
+        _ => *visibility = Visibility::Hidden
+    }
+}
+
+fn apply_bulk_operations(
+    mut events: EventReader<BulkOperation>,
+    mut selection: ResMut<SelectionSet>,
+    mut watchlist: ResMut<Watchlist>,
+    mut styles: Query<&mut MarkerStyle>,
+    hidden: Query<Has<OrbitHidden>>,
+    mut despawns: EventWriter<DespawnSatellite>,
+    mut commands: Commands
+) {
+    for operation in events.read() {
+        debug!("Applying {:?} to {} satellites", operation, selection.len());
+        match operation {
+            //recorded for undo, only the satellites actually changing
+            BulkOperation::AddToWatchlist => {
+                let changes = selection.iter().filter(|e| !watchlist.contains(*e)).map(|entity| Change::Watchlist { entity, added: true }).collect();
+                commands.add(Edit::new("Add to watchlist", changes));
+            },
+            BulkOperation::RemoveFromWatchlist => {
+                let changes = selection.iter().filter(|e| watchlist.contains(*e)).map(|entity| Change::Watchlist { entity, added: false }).collect();
+                commands.add(Edit::new("Remove from watchlist", changes));
+            },
+            BulkOperation::SetOrbitDisplay(show) => {
+                let changes = selection.iter()
+                    .filter(|e| hidden.get(*e).is_ok_and(|h| h == *show))
+                    .map(|entity| Change::OrbitDisplay { entity, hidden: !show })
+                    .collect();
+                commands.add(Edit::new(if *show { "Show orbits" } else { "Hide orbits" }, changes));
+            },
+            BulkOperation::OverrideColor(color) => {
+                for entity in selection.iter() {
+                    if let Ok(mut style) = styles.get_mut(entity) {
+                        style.set(StyleLayer::Override, StyleModifier::base(*color));
+                    }
+                }
+            },
+            //written by export_selection
+            #[cfg(feature = "export")]
+            BulkOperation::ExportStates(_) => {},
+            BulkOperation::Despawn => {
+                for entity in selection.iter() {
+                    watchlist.remove(entity);
+                    despawns.send(DespawnSatellite::animated(entity));
+                }
+                selection.clear();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "export")]
+fn export_selection(
+    mut events: EventReader<BulkOperation>,
+    selection: Res<SelectionSet>,
+    index: Res<SatelliteIndex>,
+    satellites: Query<(&Transform, &InGameElements, Option<&Notes>, Option<&CustomTags>)>,
+    settings: Res<InGameSettings>
+) {
+    for operation in events.read() {
+        let BulkOperation::ExportStates(path) = operation else {
+            continue;
+        };
+        //rows by NORAD id, not in the order of selecting
+        let mut selected: Vec<Entity> = selection.iter().collect();
+        index.sort(&mut selected);
+        let states = selected.into_iter().filter_map(|e| satellites.get(e).ok());
+        match export_states(path, states, settings.scale) {
+            Ok(count) => info!("Exported {} satellite states to {:?}", count, path),
+            Err(err) => error!("{}", err.report())
+        }
+    }
+}
+
+#[cfg(feature = "export")]
+fn export_states<'a>(
+    path: &Path,
+    states: impl Iterator<Item = (&'a Transform, &'a InGameElements, Option<&'a Notes>, Option<&'a CustomTags>)>,
+    scale: f32
+) -> Result<usize, SkytracioError> {
+    write_states(path, states, scale).map_err(|source| SkytracioError::Export { path: path.to_owned(), source })
+}
+
+#[cfg(feature = "export")]
+fn write_states<'a>(
+    path: &Path,
+    states: impl Iterator<Item = (&'a Transform, &'a InGameElements, Option<&'a Notes>, Option<&'a CustomTags>)>,
+    scale: f32
+) -> io::Result<usize> {
+    let mut file = File::create(path)?;
+    writeln!(file, "norad_id,object_name,x_km,y_km,z_km,note,tags")?;
+    let mut count = 0;
+    for (transform, elements, note, tags) in states {
+        let position = WORLD_FRAME.to_inertial(transform.translation) / scale;
+        let name = elements.0.object_name.as_deref().unwrap_or_default();
+        let note = note.map(|n| n.0.as_str()).unwrap_or_default();
+        let tags = tags.map(|t| t.0.join(";")).unwrap_or_default();
+        writeln!(
+            file, "{},{},{:.3},{:.3},{:.3},{},{}",
+            elements.0.norad_id, csv_field(name), position.x, position.y, position.z, csv_field(note), csv_field(&tags)
+        )?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+//free text is quoted when it would break the row
+#[cfg(feature = "export")]
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_owned()
+    }
+}
+
+fn highlight_selection(mut gizmos: Gizmos, selection: Res<SelectionSet>, transforms: Query<&GlobalTransform>) {
+    let primary = selection.primary();
+    for entity in selection.iter() {
+        let Ok(transform) = transforms.get(entity) else {
+            continue;
+        };
+        if Some(entity) == primary {
+            gizmos.sphere(transform.translation(), Quat::IDENTITY, 4.0, WHITE).resolution(32);
+        } else {
+            gizmos.sphere(transform.translation(), Quat::IDENTITY, 3.0, Color::srgba(0.6, 0.8, 1.0, 0.35)).resolution(16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    #[cfg(feature = "export")]
+    use crate::test_support::LEO;
+
+    fn entities(n: usize) -> Vec<Entity> {
+        (0..n as u32).map(Entity::from_raw).collect()
+    }
+
+    #[test]
+    fn test_rectangle_hits_projection_and_occlusion() {
+        let e = entities(5);
+        let eye = Vec3::new(0.0, 0.0, 500.0);
+        //orthographic view down the -Z axis, nothing behind the eye is visible
+        let project = |p: Vec3| (p.z < eye.z).then_some(p.truncate());
+        let candidates = vec![
+            (e[0], Vec3::new(10.0, 10.0, 100.0)),
+            (e[1], Vec3::new(200.0, 0.0, 0.0)),
+            //right behind the planet
+            (e[2], Vec3::new(5.0, 5.0, -100.0)),
+            //just above the near surface
+            (e[3], Vec3::new(0.0, 0.0, 70.0)),
+            (e[4], Vec3::new(0.0, 0.0, 600.0)),
+        ];
+
+        //dragged from bottom-right to top-left
+        let rect = Rect::from_corners(Vec2::new(50.0, 50.0), Vec2::new(-50.0, -50.0));
+        let hits = rectangle_hits(rect, eye, 63.78, candidates.into_iter(), project);
+        assert_eq!(hits, vec![e[0], e[3]]);
+    }
+
+    #[test]
+    fn test_occlusion_by_sphere() {
+        let eye = Vec3::new(0.0, 0.0, 500.0);
+        assert!(occluded_by_sphere(eye, Vec3::new(0.0, 0.0, -100.0), 60.0));
+        assert!(!occluded_by_sphere(eye, Vec3::new(100.0, 0.0, -100.0), 60.0));
+        assert!(!occluded_by_sphere(eye, Vec3::new(0.0, 0.0, 61.0), 60.0));
+    }
+
+    #[test]
+    fn test_bulk_watchlist_add() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, SelectionPlugin))
+            .insert_resource(InGameSettings::default());
+
+        let satellites: Vec<_> = (0..3).map(|_| app.world_mut().spawn(Transform::default()).id()).collect();
+        let mut selection = app.world_mut().resource_mut::<SelectionSet>();
+        selection.add(satellites[0]);
+        selection.add(satellites[2]);
+        assert_eq!(selection.primary(), Some(satellites[2]));
+
+        app.world_mut().send_event(BulkOperation::AddToWatchlist);
+        app.update();
+
+        let watchlist = app.world().resource::<Watchlist>();
+        assert_eq!(watchlist.iter().collect::<Vec<_>>(), vec![satellites[0], satellites[2]]);
+        assert!(!watchlist.contains(satellites[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "export")]
+    fn test_export_quotes_names() {
+        let path = std::env::temp_dir().join(format!("skytracio-selection-{}.csv", std::process::id()));
+        let elements = InGameElements(LEO.builder().name("DEB, \"FRAGMENT\" 7").build());
+        let tags = CustomTags(vec!["debris".to_owned(), "a,b".to_owned()]);
+        let transform = Transform::from_translation(WORLD_FRAME.to_world(Vec3::new(70.0, 0.0, 0.0)));
+        assert_eq!(write_states(&path, [(&transform, &elements, None, Some(&tags))].into_iter(), 0.01).unwrap(), 1);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.lines().nth(1), Some("70001,\"DEB, \"\"FRAGMENT\"\" 7\",7000.000,0.000,0.000,,\"debris;a,b\""));
+    }
+}
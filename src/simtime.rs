@@ -0,0 +1,367 @@
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sgp4::Elements;
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const NANOS_PER_MINUTE: f64 = 60e9;
+const SECONDS_PER_DAY: i64 = 86400;
+//2000-01-01T12:00:00 UTC as Unix time
+const J2000_UNIX_SECONDS: i64 = 946_728_000;
+//TAI - UTC at J2000
+const J2000_LEAP_SECONDS: i64 = 32;
+//`Elements::epoch` is in Julian years since J2000
+const MILLIS_PER_JULIAN_YEAR: f64 = 365.25 * 86400.0 * 1000.0;
+
+//(Unix time of the UTC midnight from which TAI - UTC has the value, the value),
+//a leap second is inserted right before each entry. Complete through 2026, the last one was at the end of 2016
+const LEAP_SECONDS: [(i64, i64); 28] = [
+    (63072000, 10), //1972-01-01
+    (78796800, 11), //1972-07-01
+    (94694400, 12), //1973-01-01
+    (126230400, 13), //1974-01-01
+    (157766400, 14), //1975-01-01
+    (189302400, 15), //1976-01-01
+    (220924800, 16), //1977-01-01
+    (252460800, 17), //1978-01-01
+    (283996800, 18), //1979-01-01
+    (315532800, 19), //1980-01-01
+    (362793600, 20), //1981-07-01
+    (394329600, 21), //1982-07-01
+    (425865600, 22), //1983-07-01
+    (489024000, 23), //1985-07-01
+    (567993600, 24), //1988-01-01
+    (631152000, 25), //1990-01-01
+    (662688000, 26), //1991-01-01
+    (709948800, 27), //1992-07-01
+    (741484800, 28), //1993-07-01
+    (773020800, 29), //1994-07-01
+    (820454400, 30), //1996-01-01
+    (867715200, 31), //1997-07-01
+    (915148800, 32), //1999-01-01
+    (1136073600, 33), //2006-01-01
+    (1230768000, 34), //2009-01-01
+    (1341100800, 35), //2012-07-01
+    (1435708800, 36), //2015-07-01
+    (1483228800, 37) //2017-01-01
+];
+
+/// Instant on a continuous time scale, nanoseconds since 2000-01-01T12:00:00 UTC counting leap seconds like TAI does.
+///
+/// Element epochs, dates typed by the user and `SystemTime` are UTC, which repeats (or would skip) a second at every leap second.
+/// Converting them here makes every difference between two instants the physical time between them, which is what
+/// SGP4 expects as minutes since the epoch. The leap seconds are taken from a table complete through 2026; instants before
+/// 1972 use the offset of 1972 and later leap seconds, if any are announced, are not known.
+/// Only Earth rotation needs UTC back, see [`SimInstant::ut_minutes_since_j2000`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SimInstant(i128);
+
+/// Calendar date and time in UTC, `second` is 60 during a leap second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtcDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanosecond: u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimTimeError {
+    /// Not `YYYY-MM-DDTHH:MM:SS[.fffffffff][Z]`
+    Format(String),
+    /// Out of range fields, or second 60 on a day without a leap second
+    InvalidDate(UtcDateTime)
+}
+
+/// Minutes in a duration, the one conversion every minutes-since-epoch computation goes through
+pub fn minutes(duration: Duration) -> f64 {
+    nanos_to_minutes(duration.as_nanos() as i128)
+}
+
+fn nanos_to_minutes(nanos: i128) -> f64 {
+    nanos as f64 / NANOS_PER_MINUTE
+}
+
+//TAI - UTC in effect at the UTC instant (Unix time)
+fn leap_seconds_at_unix(unix_seconds: i64) -> i64 {
+    match LEAP_SECONDS.partition_point(|(start, _)| *start <= unix_seconds) {
+        0 => LEAP_SECONDS[0].1,
+        i => LEAP_SECONDS[i - 1].1
+    }
+}
+
+impl SimInstant {
+    /// 2000-01-01T12:00:00 UTC, the origin of the scale. `Elements::epoch` counts from the same UTC instant
+    pub const J2000: SimInstant = SimInstant(0);
+
+    pub fn from_nanos_since_j2000(nanos: i128) -> Self {
+        Self(nanos)
+    }
+
+    pub fn nanos_since_j2000(&self) -> i128 {
+        self.0
+    }
+
+    //instant of a Unix time, a leap second can't be expressed in it
+    fn from_unix(seconds: i64, nanos: u32) -> Self {
+        let seconds = seconds - J2000_UNIX_SECONDS + leap_seconds_at_unix(seconds) - J2000_LEAP_SECONDS;
+        Self(seconds as i128 * NANOS_PER_SECOND + nanos as i128)
+    }
+
+    //Unix time, with whether the instant is within a leap second (the Unix time is then the start of the next day)
+    fn to_unix(self) -> (i64, u32, bool) {
+        let (seconds, nanos) = (self.0.div_euclid(NANOS_PER_SECOND) as i64, self.0.rem_euclid(NANOS_PER_SECOND) as u32);
+        //seconds on the scale of the table, Unix time plus TAI - UTC
+        let tai = seconds + J2000_UNIX_SECONDS + J2000_LEAP_SECONDS;
+        let index = LEAP_SECONDS.partition_point(|(start, offset)| start + offset <= tai);
+        let offset = if index == 0 { LEAP_SECONDS[0].1 } else { LEAP_SECONDS[index - 1].1 };
+        let unix = tai - offset;
+        let in_leap_second = LEAP_SECONDS.get(index).is_some_and(|(start, _)| unix >= *start);
+        (unix, nanos, in_leap_second)
+    }
+
+    /// `None` for a second 60 not followed by a leap second and for fields out of range
+    pub fn from_utc(utc: &UtcDateTime) -> Option<Self> {
+        if !(1..=12).contains(&utc.month) || !(1..=days_in_month(utc.year, utc.month)).contains(&utc.day)
+            || utc.hour > 23 || utc.minute > 59 || utc.second > 60 || utc.nanosecond >= NANOS_PER_SECOND as u32 {
+            return None;
+        }
+        let unix = days_from_civil(utc.year, utc.month, utc.day) * SECONDS_PER_DAY + (utc.hour * 3600 + utc.minute * 60 + utc.second.min(59)) as i64;
+        if utc.second < 60 {
+            return Some(Self::from_unix(unix, utc.nanosecond));
+        }
+        let leap = utc.hour == 23 && utc.minute == 59 && LEAP_SECONDS[1..].iter().any(|(start, _)| *start == unix + 1);
+        leap.then(|| Self(Self::from_unix(unix, utc.nanosecond).0 + NANOS_PER_SECOND))
+    }
+
+    pub fn to_utc(&self) -> UtcDateTime {
+        let (unix, nanosecond, in_leap_second) = self.to_unix();
+        let unix = if in_leap_second { unix - 1 } else { unix };
+        let (days, seconds) = (unix.div_euclid(SECONDS_PER_DAY), unix.rem_euclid(SECONDS_PER_DAY) as u32);
+        let (year, month, day) = civil_from_days(days);
+        let second = if in_leap_second { 60 } else { seconds % 60 };
+        UtcDateTime { year, month, day, hour: seconds / 3600, minute: seconds / 60 % 60, second, nanosecond }
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => Self::from_unix(since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                let nanos = (before.as_nanos() as i128).rem_euclid(NANOS_PER_SECOND);
+                let seconds = -(before.as_secs() as i64) - (nanos > 0) as i64;
+                Self::from_unix(seconds, ((NANOS_PER_SECOND - nanos) % NANOS_PER_SECOND) as u32)
+            }
+        }
+    }
+
+    /// A leap second maps onto the first second of the next day, like Unix clocks repeat it
+    pub fn to_system_time(&self) -> SystemTime {
+        let (unix, nanos, _) = self.to_unix();
+        let since = Duration::new(unix.unsigned_abs(), 0);
+        let whole = if unix >= 0 { UNIX_EPOCH + since } else { UNIX_EPOCH - since };
+        whole + Duration::from_nanos(nanos as u64)
+    }
+
+    /// Epoch of the elements, `Elements::epoch` has a millisecond resolution and so do the instants
+    pub fn epoch(elements: &Elements) -> Self {
+        let millis = (elements.epoch() * MILLIS_PER_JULIAN_YEAR).round() as i64 + J2000_UNIX_SECONDS * 1000;
+        Self::from_unix(millis.div_euclid(1000), millis.rem_euclid(1000) as u32 * 1_000_000)
+    }
+
+    /// Physical time since `earlier` (in minutes), negative when `earlier` is later
+    pub fn minutes_since(&self, earlier: SimInstant) -> f64 {
+        nanos_to_minutes(self.0 - earlier.0)
+    }
+
+    /// Instant the given minutes after this one, rounded to a nanosecond
+    pub fn after_minutes(&self, minutes: f64) -> Self {
+        Self(self.0 + (minutes * NANOS_PER_MINUTE).round() as i128)
+    }
+
+    /// `None` when `earlier` is later
+    pub fn duration_since(&self, earlier: SimInstant) -> Option<Duration> {
+        let nanos = u64::try_from(self.0 - earlier.0).ok()?;
+        Some(Duration::from_nanos(nanos))
+    }
+
+    /// TAI - UTC (in seconds) at the instant
+    pub fn leap_seconds(&self) -> i64 {
+        let (unix, _, in_leap_second) = self.to_unix();
+        leap_seconds_at_unix(unix) - in_leap_second as i64
+    }
+
+    /// Minutes since J2000 on the UTC scale, without the leap seconds. Earth rotation follows UT1, which UTC stays within
+    /// a second of, so sidereal time and the Julian dates of the ephemerides are computed from this
+    pub fn ut_minutes_since_j2000(&self) -> f64 {
+        let (unix, nanos, _) = self.to_unix();
+        nanos_to_minutes((unix - J2000_UNIX_SECONDS) as i128 * NANOS_PER_SECOND + nanos as i128)
+    }
+}
+
+impl Add<Duration> for SimInstant {
+    type Output = SimInstant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self(self.0 + rhs.as_nanos() as i128)
+    }
+}
+
+impl Sub<Duration> for SimInstant {
+    type Output = SimInstant;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Self(self.0 - rhs.as_nanos() as i128)
+    }
+}
+
+impl fmt::Display for UtcDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)?;
+        if self.nanosecond > 0 {
+            let fraction = format!("{:09}", self.nanosecond);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        write!(f, "Z")
+    }
+}
+
+impl FromStr for UtcDateTime {
+    type Err = SimTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = || SimTimeError::Format(s.to_owned());
+        let (date, time) = s.trim().trim_end_matches('Z').split_once(['T', ' ']).ok_or_else(format)?;
+        let (time, fraction) = match time.split_once('.') {
+            Some((time, fraction)) if (1..=9).contains(&fraction.len()) && fraction.bytes().all(|b| b.is_ascii_digit()) => (time, fraction),
+            Some(_) => return Err(format()),
+            None => (time, "0")
+        };
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<u32> = time.split(':').map(|p| p.parse().ok()).collect::<Option<_>>().ok_or_else(format)?;
+        let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+            return Err(format());
+        };
+        let nanosecond = format!("{fraction:0<9}").parse().map_err(|_| format())?;
+        Ok(UtcDateTime {
+            year: year.parse().map_err(|_| format())?, month: month.parse().map_err(|_| format())?, day: day.parse().map_err(|_| format())?,
+            hour, minute, second, nanosecond
+        })
+    }
+}
+
+/// ISO 8601 in UTC, the form instants are written in scenarios and exports
+impl fmt::Display for SimInstant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_utc(), f)
+    }
+}
+
+impl FromStr for SimInstant {
+    type Err = SimTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let utc: UtcDateTime = s.parse()?;
+        SimInstant::from_utc(&utc).ok_or(SimTimeError::InvalidDate(utc))
+    }
+}
+
+pub(crate) fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+//days since the unix epoch of a proleptic gregorian date, after Howard Hinnant's algorithm
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+//inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    (year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::stress::starlink_like_elements;
+
+    fn instant(s: &str) -> SimInstant {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_conversions_across_a_leap_second() {
+        let before = instant("2016-12-31T23:59:59Z");
+        let leap = instant("2016-12-31T23:59:60.5Z");
+        let after = instant("2017-01-01T00:00:00Z");
+        assert_eq!(leap.duration_since(before), Some(Duration::from_millis(1500)));
+        assert_eq!(after.duration_since(before), Some(Duration::from_secs(2)));
+        assert_eq!((before.leap_seconds(), leap.leap_seconds(), after.leap_seconds()), (36, 36, 37));
+        assert_eq!(leap.to_string(), "2016-12-31T23:59:60.5Z");
+        //a day without a leap second has no second 60
+        assert!("2016-12-30T23:59:60Z".parse::<SimInstant>().is_err());
+        //Unix time doesn't count the leap second, the UTC scale repeats it
+        assert_abs_diff_eq!(after.ut_minutes_since_j2000() - before.ut_minutes_since_j2000(), 1.0 / 60.0, epsilon = 1e-9);
+        assert_eq!(leap.to_system_time(), UNIX_EPOCH + Duration::from_millis(1483228800500));
+        //J2000 is 32 leap seconds in
+        assert_eq!(instant("2000-01-01T12:00:00Z"), SimInstant::J2000);
+        assert_eq!(SimInstant::J2000.leap_seconds(), 32);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for text in ["1969-07-20T20:17:40Z", "1972-06-30T23:59:60Z", "2000-01-01T12:00:00Z", "2024-02-29T23:59:59.999999999Z", "2124-12-31T00:00:00.25Z"] {
+            let parsed = instant(text);
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(SimInstant::from_utc(&parsed.to_utc()), Some(parsed));
+        }
+        for millis in [-1_500i64, 0, 946_728_000_000, 1_735_420_273_237] {
+            let time = if millis < 0 { UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs()) } else { UNIX_EPOCH + Duration::from_millis(millis as u64) };
+            assert_eq!(SimInstant::from_system_time(time).to_system_time(), time);
+        }
+        //every day of a few centuries
+        for days in -40_000..80_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_minutes_since_epoch_agree_between_paths() {
+        let elements = &starlink_like_elements(1, 1482)[0];
+        let epoch = SimInstant::epoch(elements);
+        assert_eq!(epoch.to_string(), "2024-12-28T21:11:13.237Z");
+        for elapsed in [Duration::ZERO, Duration::from_nanos(1), Duration::from_secs_f64(5400.123), Duration::from_secs(400 * 86400)] {
+            //what a satellite propagated for `elapsed` uses, and the same instant reached through the time scale
+            let instant = epoch + elapsed;
+            assert_eq!(minutes(elapsed), instant.minutes_since(epoch));
+            assert_eq!(instant.duration_since(epoch), Some(elapsed));
+        }
+    }
+}
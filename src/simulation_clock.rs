@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeSystem};
+
+use crate::global::InGameSettings;
+use crate::input::{Action, ActionTriggered};
+
+/// Speed presets and the actions selecting them, in simulated seconds per real second
+pub const SPEED_PRESETS: [(Action, f32); 5] = [
+    (Action::RealTimeSpeed, 1.0),
+    (Action::MinutePerSecondSpeed, 60.0),
+    (Action::TenMinutesPerSecondSpeed, 600.0),
+    (Action::HourPerSecondSpeed, 3600.0),
+    (Action::DayPerSecondSpeed, 86400.0)
+];
+
+/// Fastest speed the commands accept, ten simulated days per real second
+pub const MAX_SPEED: f64 = 864000.0;
+
+/// Simulated time, advanced every frame by the integral of the simulation speed.
+/// `InGameSettings::simulation_speed` is the target, the clock ramps towards it instead of jumping.
+/// [`Action::TogglePause`] stops and resumes the clock
+pub struct SimulationClockPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpeedRamp {
+    from: f64,
+    to: f64,
+    //real seconds since the ramp started
+    progress: f64,
+    duration: f64
+}
+
+/// Speed `t` real seconds into a ramp from `old` to `new` lasting `duration`.
+/// Smoothstep, so the speed doesn't kink at either end of the ramp
+pub fn ramp_speed(old: f64, new: f64, t: f64, duration: f64) -> f64 {
+    if duration <= 0.0 {
+        return new;
+    }
+    let x = (t / duration).clamp(0.0, 1.0);
+    old + (new - old) * x * x * (3.0 - 2.0 * x)
+}
+
+/// Simulated seconds elapsed over the first `t` real seconds of the ramp, the exact integral of [`ramp_speed`].
+/// After the ramp the speed stays at `new`
+pub fn ramp_integral(old: f64, new: f64, t: f64, duration: f64) -> f64 {
+    if duration <= 0.0 {
+        return new * t.max(0.0);
+    }
+    let ramped = t.clamp(0.0, duration);
+    let x = ramped / duration;
+    old * ramped + (new - old) * duration * (x.powi(3) - x.powi(4) / 2.0) + new * (t - duration).max(0.0)
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SimulationClock {
+    elapsed: f64,
+    delta: f64,
+    //effective speed, unknown until the first frame
+    speed: Option<f64>,
+    ramp: Option<SpeedRamp>,
+    /// Real time a change of speed is spread over
+    pub ramp_duration: Duration,
+    /// Frames of a paused clock last no simulated time, a ramp in progress waits as well
+    pub paused: bool
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self { elapsed: 0.0, delta: 0.0, speed: None, ramp: None, ramp_duration: Duration::from_secs(1), paused: false }
+    }
+}
+
+impl SimulationClock {
+    /// Simulated seconds since the start
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Simulated seconds of the last frame, use it instead of scaling the real frame time by the speed
+    pub fn delta_seconds(&self) -> f64 {
+        self.delta
+    }
+
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(self.delta)
+    }
+
+    /// Speed at the end of the last frame, between the old and the new one while ramping
+    pub fn speed(&self) -> f64 {
+        self.speed.unwrap_or(0.0)
+    }
+
+    pub fn target_speed(&self) -> Option<f64> {
+        self.ramp.map(|r| r.to).or(self.speed)
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.ramp.is_some()
+    }
+
+    /// Ramps from the current speed to `speed`, the very first speed is taken as is
+    pub fn set_target_speed(&mut self, speed: f64) {
+        match self.speed {
+            None => self.speed = Some(speed),
+            Some(current) if self.target_speed() != Some(speed) => {
+                self.ramp = Some(SpeedRamp { from: current, to: speed, progress: 0.0, duration: self.ramp_duration.as_secs_f64() });
+            },
+            Some(_) => {}
+        }
+    }
+
+    pub fn advance(&mut self, real_seconds: f64) {
+        if self.paused {
+            self.delta = 0.0;
+            return;
+        }
+        self.delta = match &mut self.ramp {
+            Some(ramp) => {
+                let start = ramp.progress;
+                ramp.progress += real_seconds;
+                let delta = ramp_integral(ramp.from, ramp.to, ramp.progress, ramp.duration) - ramp_integral(ramp.from, ramp.to, start, ramp.duration);
+                self.speed = Some(ramp_speed(ramp.from, ramp.to, ramp.progress, ramp.duration));
+                if ramp.progress >= ramp.duration {
+                    self.ramp = None;
+                }
+                delta
+            },
+            None => self.speed() * real_seconds
+        };
+        self.elapsed += self.delta;
+    }
+}
+
+impl Plugin for SimulationClockPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimulationClock>()
+            .add_event::<ActionTriggered>()
+            .add_systems(First, advance_simulation_clock.after(TimeSystem).run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, apply_speed_presets.run_if(resource_exists::<InGameSettings>))
+            .add_systems(Update, toggle_pause);
+    }
+}
+
+/// Adds the [`SimulationClockPlugin`] unless already added, for plugins reading the [`SimulationClock`]
+pub fn ensure_simulation_clock(app: &mut App) {
+    if !app.is_plugin_added::<SimulationClockPlugin>() {
+        app.add_plugins(SimulationClockPlugin);
+    }
+}
+
+fn advance_simulation_clock(time: Res<Time>, settings: Res<InGameSettings>, mut clock: ResMut<SimulationClock>) {
+    clock.set_target_speed(settings.simulation_speed as f64);
+    clock.advance(time.delta_seconds_f64());
+}
+
+fn apply_speed_presets(mut actions: EventReader<ActionTriggered>, mut settings: ResMut<InGameSettings>) {
+    for ActionTriggered(action) in actions.read() {
+        if let Some((_, speed)) = SPEED_PRESETS.iter().find(|(preset, _)| preset == action) {
+            settings.simulation_speed = *speed;
+        }
+    }
+}
+
+fn toggle_pause(mut actions: EventReader<ActionTriggered>, mut clock: ResMut<SimulationClock>) {
+    for ActionTriggered(action) in actions.read() {
+        if *action == Action::TogglePause {
+            clock.paused = !clock.paused;
+            info!("Simulation {} at {:.0} s", if clock.paused { "paused" } else { "resumed" }, clock.elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    #[test]
+    fn test_ramp_integral() {
+        let (old, new, duration) = (60.0, 3600.0, 1.0);
+        //numeric integration of the speed agrees with the closed form at every point of the ramp
+        let steps = 10_000;
+        let mut numeric = 0.0;
+        for i in 0..steps {
+            let t = 1.5 * (i as f64 + 0.5) / steps as f64;
+            numeric += ramp_speed(old, new, t, duration) * 1.5 / steps as f64;
+            if (i + 1) % 2500 == 0 {
+                let end = 1.5 * (i + 1) as f64 / steps as f64;
+                assert_abs_diff_eq!(ramp_integral(old, new, end, duration), numeric, epsilon = 1e-3);
+            }
+        }
+        //half way through a symmetric ramp, the average of both speeds
+        assert_abs_diff_eq!(ramp_integral(old, new, 1.0, duration), (old + new) / 2.0, epsilon = 1e-9);
+        assert_eq!(ramp_speed(old, new, 0.0, duration), old);
+        assert_eq!(ramp_speed(old, new, 2.0, duration), new);
+        assert_eq!(ramp_integral(old, new, 3.0, 0.0), 3.0 * new);
+    }
+
+    #[test]
+    fn test_paused_clock_keeps_the_ramp() {
+        let mut clock = SimulationClock::default();
+        clock.set_target_speed(60.0);
+        clock.advance(1.0);
+        clock.set_target_speed(3600.0);
+        clock.advance(0.25);
+        let (elapsed, speed) = (clock.elapsed_seconds(), clock.speed());
+
+        clock.paused = true;
+        for _ in 0..10 {
+            clock.advance(1.0);
+        }
+        assert_eq!((clock.elapsed_seconds(), clock.delta_seconds(), clock.speed()), (elapsed, 0.0, speed));
+        assert!(clock.is_ramping());
+
+        //the ramp goes on from where it stopped
+        clock.paused = false;
+        clock.advance(0.25);
+        assert_abs_diff_eq!(clock.elapsed_seconds(), elapsed + ramp_integral(60.0, 3600.0, 0.5, 1.0) - ramp_integral(60.0, 3600.0, 0.25, 1.0), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_preset_change_keeps_clock_continuous() {
+        let frame = 0.05;
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, SimulationClockPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(frame)))
+            .insert_resource(InGameSettings {
+                simulation_speed: 60.0,
+                ..default()
+            });
+        //the first frame measures no time
+        app.update();
+        for _ in 0..10 {
+            app.update();
+        }
+        let before = app.world().resource::<SimulationClock>().elapsed_seconds();
+        assert_abs_diff_eq!(before, 10.0 * frame * 60.0, epsilon = 1e-6);
+
+        app.world_mut().send_event(ActionTriggered(Action::HourPerSecondSpeed));
+        //the preset is applied in this frame, the clock ramps from the next one
+        app.update();
+        let mut deltas = vec![];
+        for _ in 0..30 {
+            app.update();
+            deltas.push(app.world().resource::<SimulationClock>().delta_seconds());
+        }
+
+        let clock = app.world().resource::<SimulationClock>();
+        let expected = before + 60.0 * frame + ramp_integral(60.0, 3600.0, 30.0 * frame, 1.0);
+        assert_abs_diff_eq!(clock.elapsed_seconds(), expected, epsilon = 1e-6);
+        assert!(!clock.is_ramping());
+        assert_eq!(clock.speed(), 3600.0);
+        assert_abs_diff_eq!(clock.delta_seconds(), 3600.0 * frame, epsilon = 1e-9);
+        //no frame advances more than the steepest part of the ramp allows over the one before it
+        let max_acceleration = 1.5 * (3600.0 - 60.0);
+        assert!(deltas.windows(2).all(|w| (0.0..=max_acceleration * frame * frame + 1e-9).contains(&(w[1] - w[0]))), "{deltas:?}");
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use crate::orbit::SatelliteOrbit;
+use crate::world_frame::WORLD_FRAME;
+
+//hue of the slowest (blue) point of an orbit, the fastest one is red
+const SLOW_HUE: f32 = 240.0;
+
+/// How orbits are drawn
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrbitRenderMode {
+    #[default]
+    Ellipse,
+    /// Polyline colored by the local speed, red near periapsis and blue near apoapsis
+    SpeedHeatmap
+}
+
+impl OrbitRenderMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            OrbitRenderMode::Ellipse => OrbitRenderMode::SpeedHeatmap,
+            OrbitRenderMode::SpeedHeatmap => OrbitRenderMode::Ellipse
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatmapPoint {
+    /// Scaled, in world space
+    pub position: Vec3,
+    /// Vis-viva speed (in kilometers per second)
+    pub speed: f32,
+    pub color: Color
+}
+
+/// Maps a speed between the apoapsis and periapsis speeds onto the blue to red scale
+pub fn speed_color(speed: f32, slowest: f32, fastest: f32) -> Color {
+    //circular orbits only differ by rounding
+    let fraction = if fastest - slowest > fastest * 1e-4 { ((speed - slowest) / (fastest - slowest)).clamp(0.0, 1.0) } else { 0.5 };
+    Color::hsl(SLOW_HUE * (1.0 - fraction), 1.0, 0.5)
+}
+
+/// Closed polyline of the orbit, every point colored by the speed at its true anomaly
+pub fn speed_heatmap(orbit: &SatelliteOrbit, samples: usize, scale: f32) -> Vec<HeatmapPoint> {
+    let fastest = SatelliteOrbit { true_anomaly: 0.0, ..*orbit }.velocity().length() as f32;
+    let slowest = SatelliteOrbit { true_anomaly: 180.0, ..*orbit }.velocity().length() as f32;
+    let mut points: Vec<_> = orbit.sample_points(samples)
+        .map(|sample| {
+            let speed = sample.velocity().length() as f32;
+            HeatmapPoint {
+                position: WORLD_FRAME.to_world(sample.to_translation_and_rotation().position) * scale,
+                speed,
+                color: speed_color(speed, slowest, fastest)
+            }
+        })
+        .collect();
+    if let Some(first) = points.first().copied() {
+        points.push(first);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perigee_segment_faster_and_redder() {
+        let orbit = SatelliteOrbit::new(26600.0, 0.74, 63.4, 40.0, 270.0, 90.0, 0.0);
+        let points = speed_heatmap(&orbit, 64, 0.01);
+        assert_eq!(points.len(), 65);
+        assert_eq!(points.first(), points.last());
+
+        let (perigee, apogee) = (points[0], points[32]);
+        assert!(perigee.speed > apogee.speed);
+        let (perigee_color, apogee_color) = (perigee.color.to_srgba(), apogee.color.to_srgba());
+        assert!(perigee_color.red > 0.9 && perigee_color.blue < 0.1, "{perigee_color:?}");
+        assert!(apogee_color.blue > 0.9 && apogee_color.red < 0.1, "{apogee_color:?}");
+        //from apoapsis back to periapsis the satellite speeds up and the hue falls towards red
+        let hue = |point: &HeatmapPoint| Hsla::from(point.color).hue;
+        assert!(points[32..].windows(2).all(|w| w[0].speed <= w[1].speed && hue(&w[0]) >= hue(&w[1]) - 1e-3));
+    }
+
+    #[test]
+    fn test_circular_orbit_is_uniform() {
+        let orbit = SatelliteOrbit::new(7000.0, 0.0, 51.6, 0.0, 0.0, 0.0, 0.0);
+        let points = speed_heatmap(&orbit, 16, 1.0);
+        assert!(points.iter().all(|p| p.color == points[0].color));
+    }
+}
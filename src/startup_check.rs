@@ -0,0 +1,292 @@
+use std::{fmt, fs, net::{TcpStream, ToSocketAddrs}, path::{Path, PathBuf}, time::Duration};
+
+use bevy::{
+    color::palettes::css::{GOLD, WHITE},
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task}
+};
+
+/// Checks at startup that the assets and at least one source of elements are there. When something is missing
+/// the app enters [`StartupState::SetupError`] and shows what it is and how to fix it, instead of loading forever
+pub struct StartupCheckPlugin;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct StartupCheckSettings {
+    pub asset_root: PathBuf,
+    /// Relative to the asset root, `None` when the Earth doesn't come from a model
+    pub earth_model: Option<PathBuf>,
+    /// Relative to the asset root, any JSON file in it is a source of elements
+    pub element_directory: PathBuf,
+    /// `host:port` tried when there are no element files, `None` in offline mode
+    pub network_probe: Option<String>,
+    pub probe_timeout: Duration,
+    /// The elements are built into the binary (`--demo`), the element directory isn't needed
+    pub bundled_elements: bool
+}
+
+impl Default for StartupCheckSettings {
+    fn default() -> Self {
+        Self {
+            asset_root: PathBuf::from("assets"),
+            #[cfg(feature = "earth-model")]
+            earth_model: Some(PathBuf::from(crate::earth::DEFAULT_EARTH_MODEL)),
+            #[cfg(not(feature = "earth-model"))]
+            earth_model: None,
+            element_directory: PathBuf::from("data"),
+            network_probe: cfg!(feature = "network").then(|| "celestrak.org:443".to_owned()),
+            probe_timeout: Duration::from_secs(2),
+            bundled_elements: false
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupProblem {
+    MissingAssetRoot(PathBuf),
+    MissingEarthModel(PathBuf),
+    NoElementSource {
+        directory: PathBuf,
+        /// Probed address, `None` when offline
+        network: Option<String>
+    }
+}
+
+impl StartupProblem {
+    /// The options that fix it
+    pub fn remedy(&self) -> &'static str {
+        match self {
+            StartupProblem::MissingAssetRoot(_) => "run from the directory containing `assets/`, or pass `--assets <dir>`",
+            StartupProblem::MissingEarthModel(_) => "put the model there, pass `--assets <dir>`, or `--procedural-earth` for a plain sphere",
+            StartupProblem::NoElementSource { .. } => "add element files (e.g. `data/galileo.json` or `data/galileo.tle`) to the assets, or connect to the network; `--offline` skips the probe"
+        }
+    }
+}
+
+impl fmt::Display for StartupProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupProblem::MissingAssetRoot(path) => write!(f, "asset folder {} does not exist", path.display()),
+            StartupProblem::MissingEarthModel(path) => write!(f, "Earth model {} does not exist", path.display()),
+            StartupProblem::NoElementSource { directory, network: Some(address) } =>
+                write!(f, "no element files in {} and {address} is unreachable", directory.display()),
+            StartupProblem::NoElementSource { directory, network: None } =>
+                write!(f, "no element files in {} and the network is off", directory.display())
+        }
+    }
+}
+
+/// Outcome of the startup check
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct StartupReport {
+    pub problems: Vec<StartupProblem>
+}
+
+impl StartupReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(States, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum StartupState {
+    #[default]
+    Checking,
+    Ready,
+    SetupError
+}
+
+//text of the problems, despawned with the state
+#[derive(Component)]
+struct SetupErrorNode;
+
+#[derive(Resource)]
+struct StartupCheck(Task<StartupReport>);
+
+impl Plugin for StartupCheckPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<StartupCheckSettings>()
+            .init_state::<StartupState>()
+            .add_systems(Startup, start_check)
+            .add_systems(Update, collect_check.run_if(in_state(StartupState::Checking)))
+            .add_systems(OnEnter(StartupState::SetupError), show_problems)
+            .add_systems(OnExit(StartupState::SetupError), hide_problems);
+    }
+}
+
+//JSON element sets or TLE text files, the formats of `ConstFileClient`
+fn has_element_files(directory: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return false;
+    };
+    entries.flatten().any(|entry| entry.path().extension().is_some_and(|e| e.eq_ignore_ascii_case("json") || e.eq_ignore_ascii_case("tle")))
+}
+
+fn reachable(address: &str, timeout: Duration) -> bool {
+    let Ok(addresses) = address.to_socket_addrs() else {
+        return false;
+    };
+    addresses.into_iter().any(|address| TcpStream::connect_timeout(&address, timeout).is_ok())
+}
+
+/// Checks the files, blocks for up to the probe timeout when there is no element file
+pub fn check_startup(settings: &StartupCheckSettings) -> StartupReport {
+    let mut problems = vec![];
+    let root_exists = settings.asset_root.is_dir();
+    //nothing is read from the root with bundled elements and a procedural Earth
+    let root_needed = settings.earth_model.is_some() || !settings.bundled_elements;
+    if !root_exists && root_needed {
+        problems.push(StartupProblem::MissingAssetRoot(settings.asset_root.clone()));
+    }
+    //everything under a missing root is missing too, the root is the one problem to report
+    if let Some(model) = settings.earth_model.as_ref().map(|m| settings.asset_root.join(m)) {
+        if root_exists && !model.is_file() {
+            problems.push(StartupProblem::MissingEarthModel(model));
+        }
+    }
+    let directory = settings.asset_root.join(&settings.element_directory);
+    if !settings.bundled_elements && !has_element_files(&directory) && !settings.network_probe.as_ref().is_some_and(|a| reachable(a, settings.probe_timeout)) {
+        problems.push(StartupProblem::NoElementSource { directory, network: settings.network_probe.clone() });
+    }
+    StartupReport { problems }
+}
+
+fn start_check(settings: Res<StartupCheckSettings>, mut commands: Commands) {
+    let settings = settings.clone();
+    commands.insert_resource(StartupCheck(IoTaskPool::get().spawn(async move { check_startup(&settings) })));
+}
+
+fn collect_check(mut check: ResMut<StartupCheck>, mut next_state: ResMut<NextState<StartupState>>, mut commands: Commands) {
+    let Some(report) = block_on(future::poll_once(&mut check.0)) else {
+        return;
+    };
+    commands.remove_resource::<StartupCheck>();
+    for problem in &report.problems {
+        error!("Setup problem: {problem}, {}", problem.remedy());
+    }
+    next_state.set(if report.is_ok() { StartupState::Ready } else { StartupState::SetupError });
+    commands.insert_resource(report);
+}
+
+fn show_problems(report: Res<StartupReport>, mut commands: Commands) {
+    let mut sections = vec![TextSection::new("The simulation can't start\n", TextStyle { font_size: 20.0, color: GOLD.into(), ..default() })];
+    for problem in &report.problems {
+        sections.push(TextSection::new(format!("\n{problem}\n"), TextStyle { font_size: 16.0, color: WHITE.into(), ..default() }));
+        sections.push(TextSection::new(format!("  {}\n", problem.remedy()), TextStyle { font_size: 14.0, color: Color::srgb(0.7, 0.7, 0.7), ..default() }));
+    }
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.3, 0.0, 0.0, 0.85).into(),
+                ..default()
+            },
+            SetupErrorNode
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(sections));
+        });
+}
+
+fn hide_problems(nodes: Query<Entity, With<SetupErrorNode>>, mut commands: Commands) {
+    for entity in nodes.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+
+    fn assets(name: &str, model: bool, elements: bool) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("skytracio-startup-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("data")).unwrap();
+        if model {
+            fs::create_dir_all(root.join("3d")).unwrap();
+            fs::write(root.join("3d/earth.glb"), b"glTF").unwrap();
+        }
+        if elements {
+            fs::write(root.join("data/galileo.json"), b"[]").unwrap();
+        }
+        root
+    }
+
+    fn settings(asset_root: PathBuf, network_probe: Option<String>) -> StartupCheckSettings {
+        StartupCheckSettings {
+            asset_root,
+            earth_model: Some(PathBuf::from("3d/earth.glb")),
+            element_directory: PathBuf::from("data"),
+            network_probe,
+            probe_timeout: Duration::from_millis(500),
+            bundled_elements: false
+        }
+    }
+
+    #[test]
+    fn test_diagnosis_lists_the_missing_pieces() {
+        let complete = assets("complete", true, true);
+        assert_eq!(check_startup(&settings(complete.clone(), None)), StartupReport::default());
+
+        let missing = complete.join("nowhere");
+        assert_eq!(check_startup(&settings(missing.clone(), None)).problems, vec![
+            StartupProblem::MissingAssetRoot(missing.clone()),
+            StartupProblem::NoElementSource { directory: missing.join("data"), network: None }
+        ]);
+
+        let no_model = assets("no-model", false, true);
+        assert_eq!(check_startup(&settings(no_model.clone(), None)).problems, vec![StartupProblem::MissingEarthModel(no_model.join("3d/earth.glb"))]);
+        let procedural = StartupCheckSettings { earth_model: None, ..settings(no_model.clone(), None) };
+        assert!(check_startup(&procedural).is_ok());
+        //the demo reads nothing from the assets
+        let demo = StartupCheckSettings { earth_model: None, bundled_elements: true, ..settings(missing.clone(), None) };
+        assert!(check_startup(&demo).is_ok());
+
+        //without element files the network has to be reachable
+        let no_elements = assets("no-elements", true, false);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().to_string();
+        assert!(check_startup(&settings(no_elements.clone(), Some(open))).is_ok());
+        let closed = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        assert_eq!(check_startup(&settings(no_elements.clone(), Some(closed.clone()))).problems, vec![
+            StartupProblem::NoElementSource { directory: no_elements.join("data"), network: Some(closed) }
+        ]);
+
+        for root in [complete, no_model, no_elements] {
+            let _ = fs::remove_dir_all(root);
+        }
+    }
+
+    #[test]
+    fn test_missing_assets_enter_setup_error() {
+        let root = assets("app", false, true);
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_resource(settings(root.clone(), None))
+            .add_plugins(StartupCheckPlugin);
+        for _ in 0..100 {
+            app.update();
+            if *app.world().resource::<State<StartupState>>().get() != StartupState::Checking {
+                break;
+            }
+        }
+        assert_eq!(*app.world().resource::<State<StartupState>>().get(), StartupState::SetupError);
+        assert_eq!(app.world().resource::<StartupReport>().problems, vec![StartupProblem::MissingEarthModel(root.join("3d/earth.glb"))]);
+        assert_eq!(app.world_mut().query::<&SetupErrorNode>().iter(app.world()).count(), 1);
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,196 @@
+//Starlink-scale stress scenario, run with `cargo test --release stress -- --ignored --nocapture`
+use std::{convert::Infallible, fs, time::{Duration, Instant}};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::demo::{synthetic_elements_at, MeanElements};
+use crate::global::{AltitudeBand, InGameSettings, PropagationSettings};
+use crate::propagation::{self, DerivedDataCache, ElementsFormat, EpochDataLoader, LoadElements, LoadedElements, OrbitalData};
+
+//tunables of the scenario, the budgets are what a refactor has to keep passing
+const SATELLITE_COUNT: usize = 8000;
+const SEED: u64 = 1452;
+const LOADING_UPDATES: usize = 1000;
+const MEASURED_UPDATES: usize = 600;
+const FIXED_STEP: Duration = Duration::from_micros(16_667);
+const AVERAGE_UPDATE_BUDGET: Duration = Duration::from_millis(8);
+const PEAK_RSS_BUDGET_MB: u64 = 2048;
+
+//(altitude km, inclination deg, share of the constellation), loosely after the Starlink filings
+const SHELLS: [(f64, f64, f64); 5] = [
+    (550.0, 53.0, 0.4),
+    (540.0, 53.2, 0.3),
+    (570.0, 70.0, 0.1),
+    (560.0, 97.6, 0.1),
+    (530.0, 43.0, 0.1)
+];
+const PLANES_PER_SHELL: usize = 72;
+const EPOCH: &str = "2024-12-28T21:11:13.237440";
+
+
+/// Synthetic element sets spread over the shells, planes evenly spaced in RAAN and slots in mean anomaly
+pub fn starlink_like_elements(count: usize, seed: u64) -> OrbitalData {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut result = Vec::with_capacity(count);
+    for (shell_index, (altitude, inclination, share)) in SHELLS.iter().enumerate() {
+        let in_shell = if shell_index == SHELLS.len() - 1 { count - result.len() } else { (count as f64 * share) as usize };
+        let per_plane = in_shell.div_ceil(PLANES_PER_SHELL);
+        for i in 0..in_shell {
+            let (plane, slot) = (i / per_plane, i % per_plane);
+            let circular = MeanElements::circular(altitude + rng.gen_range(-2.0..2.0), *inclination);
+            let elements = MeanElements {
+                eccentricity: rng.gen_range(0.0001..0.0003),
+                raan: 360.0 * plane as f64 / PLANES_PER_SHELL as f64,
+                argument_of_perigee: rng.gen_range(0.0..360.0),
+                mean_anomaly: 360.0 * slot as f64 / per_plane as f64,
+                ..circular
+            };
+            let norad_id = 44000 + result.len() as u64;
+            result.push(synthetic_elements_at(&format!("STARLINK-{norad_id}"), norad_id, EPOCH, rng.gen_range(0.0001..0.0005), elements));
+        }
+    }
+    result
+}
+
+#[derive(Clone, Resource)]
+pub struct SyntheticClient(pub OrbitalData);
+
+#[async_trait::async_trait]
+impl EpochDataLoader for SyntheticClient {
+    type Error = Infallible;
+
+    async fn load(&self, _group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        Ok(self.0.clone())
+    }
+}
+
+struct StressReport {
+    satellites: usize,
+    average_update: Duration,
+    worst_update: Duration,
+    peak_rss_mb: Option<u64>
+}
+
+fn stress_app(elements: OrbitalData) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FIXED_STEP))
+        .insert_resource(InGameSettings {
+            simulation_speed: 1000.0,
+            propagation: PropagationSettings { real_time_interval: Duration::from_secs(2), batch_size: 50, numeric_fallback: true, ..default() },
+            altitude_bands: vec![
+                AltitudeBand { name: "lower shells".to_owned(), min_km: 520.0, max_km: 555.0, hysteresis_km: 2.0, tint: None },
+                AltitudeBand { name: "upper shells".to_owned(), min_km: 555.0, max_km: 580.0, hysteresis_km: 2.0, tint: None }
+            ],
+            ..default()
+        })
+        .insert_resource(SyntheticClient(elements))
+        //headless, nothing else gives the satellites a transform
+        .add_plugins(propagation::LoadElementsPlugin::<SyntheticClient>::new()
+            .with_spawn_hook(|_, entity| { entity.insert(TransformBundle::default()); }))
+        .add_plugins(propagation::PropagateElementsPlugin)
+        .add_plugins(propagation::PropagateInGamePlugin)
+        .add_plugins(propagation::AltitudeBandsPlugin);
+    app
+}
+
+//updates the app until the starlink group is spawned
+fn load_starlink(app: &mut App, satellites: usize) {
+    app.world_mut().send_event(LoadElements::group("starlink", ElementsFormat::Json));
+
+    let mut loaded_reader = app.world().resource::<Events<LoadedElements>>().get_reader();
+    let mut loaded = 0;
+    for _ in 0..LOADING_UPDATES {
+        app.update();
+        loaded += loaded_reader.read(app.world().resource::<Events<LoadedElements>>()).map(|ev| ev.entities().len()).sum::<usize>();
+        if loaded > 0 {
+            break;
+        }
+    }
+    assert_eq!(loaded, satellites, "scenario satellites were not loaded");
+}
+
+fn run_scenario(elements: OrbitalData) -> StressReport {
+    let satellites = elements.len();
+    let mut app = stress_app(elements);
+    load_starlink(&mut app, satellites);
+
+    let mut total = Duration::ZERO;
+    let mut worst = Duration::ZERO;
+    for _ in 0..MEASURED_UPDATES {
+        let start = Instant::now();
+        app.update();
+        let elapsed = start.elapsed();
+        total += elapsed;
+        worst = worst.max(elapsed);
+    }
+
+    StressReport { satellites, average_update: total / MEASURED_UPDATES as u32, worst_update: worst, peak_rss_mb: peak_rss_mb() }
+}
+
+//wall time from the load request to the spawned satellites of a fresh app
+fn startup_time(elements: OrbitalData, cache: &DerivedDataCache) -> Duration {
+    let satellites = elements.len();
+    let mut app = stress_app(elements);
+    app.insert_resource(cache.clone());
+    let start = Instant::now();
+    load_starlink(&mut app, satellites);
+    start.elapsed()
+}
+
+//VmHWM is the peak resident set size, only available on linux
+fn peak_rss_mb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagation::{ElementsExt, OrbitClass};
+
+    #[test]
+    fn test_generated_shells() {
+        let elements = starlink_like_elements(1000, SEED);
+        assert_eq!(elements.len(), 1000);
+        for el in &elements {
+            assert_eq!(OrbitClass::classify(el.period_minutes(), el.eccentricity), OrbitClass::Leo);
+            assert!((94.0..97.0).contains(&el.period_minutes()));
+        }
+        let inclinations = elements.iter().filter(|el| el.inclination == 97.6).count();
+        assert_eq!(inclinations, 100);
+    }
+
+    #[test]
+    #[ignore = "performance gate, run in release"]
+    fn stress_starlink_scale() {
+        let report = run_scenario(starlink_like_elements(SATELLITE_COUNT, SEED));
+        println!("{} satellites: {:?} average and {:?} worst update", report.satellites, report.average_update, report.worst_update);
+
+        assert!(
+            report.average_update <= AVERAGE_UPDATE_BUDGET,
+            "average update {:?} over the {:?} budget", report.average_update, AVERAGE_UPDATE_BUDGET
+        );
+        if let Some(peak_rss_mb) = report.peak_rss_mb {
+            assert!(peak_rss_mb <= PEAK_RSS_BUDGET_MB, "peak RSS {} MB over the {} MB budget", peak_rss_mb, PEAK_RSS_BUDGET_MB);
+        }
+    }
+
+    #[test]
+    #[ignore = "performance report, run in release"]
+    fn stress_derived_cache_startup() {
+        let cache = DerivedDataCache::new(std::env::temp_dir().join(format!("skytracio-stress-{}", std::process::id())));
+        let _ = fs::remove_dir_all(&cache.directory);
+        let elements = starlink_like_elements(SATELLITE_COUNT, SEED);
+
+        let cold = startup_time(elements.clone(), &cache);
+        let warm = startup_time(elements, &cache);
+        println!("startup of {SATELLITE_COUNT} satellites: {cold:?} computed, {warm:?} from the derived cache ({:?} saved)", cold.saturating_sub(warm));
+        let _ = fs::remove_dir_all(&cache.directory);
+    }
+}
@@ -0,0 +1,717 @@
+use std::{fmt, sync::Arc};
+
+use bevy::{color::palettes::css, ecs::query::QueryItem, math::DVec3, prelude::*};
+
+use crate::commands::{CommandDescriptor, ParamKind, RegisterCommand};
+use crate::ephemeris::{sun_position, SimulationDate};
+use crate::input::ActionCategory;
+use crate::notes::CustomTags;
+use crate::propagation::{
+    InGameElements, InvalidateDerivedState, MarkerStyle, OrbitClass, OrbitClassification, PropagatableDuration, Propageted, SatelliteGroup, StyleLayer,
+    StyleModifier, EARTH_RADIUS_KM
+};
+use crate::selection::Watchlist;
+
+/// Colors satellites with a user function, on the theme layer of their [`MarkerStyle`] so every other layer
+/// (bands, selection, hover) still applies on top. The function is either given programmatically or parsed from
+/// a [`StyleExpression`]
+pub struct StyleFunctionPlugin;
+
+/// What a style function knows about a satellite
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatelliteStyleInput<'a> {
+    pub name: &'a str,
+    pub norad_id: u64,
+    pub group: &'a str,
+    pub class: OrbitClass,
+    /// Above the mean equatorial radius, from the last prediction. NaN before the first one, only `!=` holds
+    pub altitude_km: f32,
+    /// Age of the elements at the simulation time, in days
+    pub age_days: f64,
+    /// Outside of the shadow of the Earth, also when the date of the Sun is unknown
+    pub sunlit: bool,
+    pub watchlisted: bool,
+    pub tags: &'a [String]
+}
+
+pub type StyleFn = Arc<dyn Fn(&SatelliteStyleInput) -> Option<StyleModifier> + Send + Sync>;
+
+/// The function coloring every satellite, `None` from the function (or no function) leaves the default colors
+#[derive(Resource, Clone, Default)]
+pub struct StyleFunction(Option<StyleFn>);
+
+impl StyleFunction {
+    pub fn new(function: impl Fn(&SatelliteStyleInput) -> Option<StyleModifier> + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(function)))
+    }
+
+    pub fn from_expression(source: &str) -> Result<Self, StyleExpressionError> {
+        let expression = StyleExpression::parse(source)?;
+        Ok(Self::new(move |input| expression.evaluate(input)))
+    }
+
+    /// Parses the expression, a malformed one is logged and leaves the default colors
+    pub fn from_expression_or_default(source: &str) -> Self {
+        Self::from_expression(source).unwrap_or_else(|error| {
+            warn!("Invalid style expression, using the default colors: {error}");
+            Self::default()
+        })
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn evaluate(&self, input: &SatelliteStyleInput) -> Option<StyleModifier> {
+        self.0.as_ref().and_then(|function| function(input))
+    }
+}
+
+impl fmt::Debug for StyleFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StyleFunction").field(&self.is_set()).finish()
+    }
+}
+
+/// Altitude and illumination at the last prediction, the inputs changing with the position
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct StyleSample {
+    pub altitude_km: f32,
+    pub sunlit: bool
+}
+
+/// Outside of the cylindrical shadow of the Earth, positions in km
+pub fn is_sunlit(position: DVec3, sun: DVec3) -> bool {
+    let sun = sun.normalize();
+    let along = position.dot(sun);
+    along > 0.0 || (position - sun * along).length() > EARTH_RADIUS_KM as f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleExpressionError {
+    /// Of the offending token, counted in characters from 1
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for StyleExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for StyleExpressionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    NoradId,
+    Group,
+    Class,
+    Altitude,
+    Age,
+    Sunlit,
+    Watchlisted,
+    Tags
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    Text,
+    Bool,
+    TextList
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 9] = [
+        ("name", Field::Name), ("norad_id", Field::NoradId), ("group", Field::Group), ("class", Field::Class), ("altitude", Field::Altitude),
+        ("age", Field::Age), ("sunlit", Field::Sunlit), ("watchlisted", Field::Watchlisted), ("tags", Field::Tags)
+    ];
+
+    fn named(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|(_, field)| *field)
+    }
+
+    fn value_type(&self) -> Type {
+        match self {
+            Field::NoradId | Field::Altitude | Field::Age => Type::Number,
+            Field::Name | Field::Group | Field::Class => Type::Text,
+            Field::Sunlit | Field::Watchlisted => Type::Bool,
+            Field::Tags => Type::TextList
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'a> {
+    Number(f64),
+    Text(&'a str),
+    Bool(bool),
+    TextList(&'a [String])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Field(Field),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    //text in a list, or a substring of a text
+    In(Box<Expr>, Box<Expr>)
+}
+
+impl Expr {
+    fn value<'a>(&'a self, input: &SatelliteStyleInput<'a>) -> Value<'a> {
+        match self {
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Text(text) => Value::Text(text),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Field(field) => match field {
+                Field::Name => Value::Text(input.name),
+                Field::NoradId => Value::Number(input.norad_id as f64),
+                Field::Group => Value::Text(input.group),
+                Field::Class => Value::Text(match input.class {
+                    OrbitClass::Leo => "leo",
+                    OrbitClass::Meo => "meo",
+                    OrbitClass::Geo => "geo",
+                    OrbitClass::Heo => "heo"
+                }),
+                Field::Altitude => Value::Number(input.altitude_km as f64),
+                Field::Age => Value::Number(input.age_days),
+                Field::Sunlit => Value::Bool(input.sunlit),
+                Field::Watchlisted => Value::Bool(input.watchlisted),
+                Field::Tags => Value::TextList(input.tags)
+            },
+            Expr::Not(e) => Value::Bool(!e.holds(input)),
+            Expr::And(a, b) => Value::Bool(a.holds(input) && b.holds(input)),
+            Expr::Or(a, b) => Value::Bool(a.holds(input) || b.holds(input)),
+            Expr::Compare(comparison, a, b) => Value::Bool(match (a.value(input), b.value(input)) {
+                (Value::Number(a), Value::Number(b)) => match comparison {
+                    Comparison::Less => a < b,
+                    Comparison::LessOrEqual => a <= b,
+                    Comparison::Greater => a > b,
+                    Comparison::GreaterOrEqual => a >= b,
+                    Comparison::Equal => a == b,
+                    Comparison::NotEqual => a != b
+                },
+                //texts are compared ignoring the case, like searches
+                (Value::Text(a), Value::Text(b)) => a.eq_ignore_ascii_case(b) == (*comparison == Comparison::Equal),
+                (a, b) => (a == b) == (*comparison == Comparison::Equal)
+            }),
+            Expr::In(a, b) => Value::Bool(match (a.value(input), b.value(input)) {
+                (Value::Text(a), Value::TextList(list)) => list.iter().any(|item| item.eq_ignore_ascii_case(a)),
+                (Value::Text(a), Value::Text(b)) => b.to_lowercase().contains(&a.to_lowercase()),
+                _ => false
+            })
+        }
+    }
+
+    fn holds(&self, input: &SatelliteStyleInput) -> bool {
+        self.value(input) == Value::Bool(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Rule {
+    Style(Option<StyleModifier>),
+    If { condition: Expr, then: Box<Rule>, otherwise: Box<Rule> }
+}
+
+/// Rules of the style mini-language, a color or a chain of conditions choosing one:
+/// `if altitude < 600 { red } else if group == "starlink" { #88ccff } else { none }`.
+/// Conditions compare the fields `name`, `norad_id`, `group`, `class` (`"leo"`, `"meo"`, `"geo"`, `"heo"`), `altitude` (km),
+/// `age` (days), `sunlit`, `watchlisted` and `tags` (`"debris" in tags`) with `<`, `<=`, `>`, `>=`, `==`, `!=`, combined with
+/// `!`, `&&` and `||` in this order of precedence. Colors are CSS names or `#rrggbb`, `none` keeps the default color
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleExpression(Rule);
+
+impl StyleExpression {
+    pub fn parse(source: &str) -> Result<Self, StyleExpressionError> {
+        let mut parser = Parser { tokens: tokenize(source)?, position: 0, end: source.chars().count() + 1 };
+        let rule = parser.rule()?;
+        match parser.peek() {
+            Some((token, column)) => Err(StyleExpressionError { column, message: format!("unexpected {token} after the style") }),
+            None => Ok(Self(rule))
+        }
+    }
+
+    pub fn evaluate(&self, input: &SatelliteStyleInput) -> Option<StyleModifier> {
+        let mut rule = &self.0;
+        loop {
+            match rule {
+                Rule::Style(style) => return *style,
+                Rule::If { condition, then, otherwise } => rule = if condition.holds(input) { then } else { otherwise }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(f64),
+    Text(String),
+    Hex(String),
+    Symbol(&'static str)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{word}`"),
+            Token::Number(n) => write!(f, "`{n}`"),
+            Token::Text(text) => write!(f, "{text:?}"),
+            Token::Hex(hex) => write!(f, "`#{hex}`"),
+            Token::Symbol(symbol) => write!(f, "`{symbol}`")
+        }
+    }
+}
+
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("<", Comparison::Less), ("<=", Comparison::LessOrEqual), (">", Comparison::Greater), (">=", Comparison::GreaterOrEqual),
+    ("==", Comparison::Equal), ("!=", Comparison::NotEqual)
+];
+
+//longer symbols first, so `<=` isn't read as `<`
+const SYMBOLS: [&str; 13] = ["<=", ">=", "==", "!=", "&&", "||", "<", ">", "!", "{", "}", "(", ")"];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, StyleExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let error = |message: String| StyleExpressionError { column, message };
+        let take_while = |from: usize, f: &dyn Fn(char) -> bool| chars[from..].iter().take_while(|c| f(**c)).count();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let length = take_while(i, &|c| c.is_ascii_alphanumeric() || c == '_');
+            tokens.push((Token::Word(chars[i..i + length].iter().collect()), column));
+            i += length;
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let length = 1 + take_while(i + 1, &|c| c.is_ascii_digit() || c == '.');
+            let text: String = chars[i..i + length].iter().collect();
+            let number = text.parse().map_err(|_| error(format!("invalid number `{text}`")))?;
+            tokens.push((Token::Number(number), column));
+            i += length;
+        } else if c == '"' {
+            let length = take_while(i + 1, &|c| c != '"');
+            if i + 1 + length >= chars.len() {
+                return Err(error("unterminated text".to_owned()));
+            }
+            tokens.push((Token::Text(chars[i + 1..i + 1 + length].iter().collect()), column));
+            i += length + 2;
+        } else if c == '#' {
+            let length = take_while(i + 1, &|c| c.is_ascii_alphanumeric());
+            tokens.push((Token::Hex(chars[i + 1..i + 1 + length].iter().collect()), column));
+            i += length + 1;
+        } else {
+            let rest: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)).ok_or_else(|| error(format!("unexpected `{c}`")))?;
+            tokens.push((Token::Symbol(symbol), column));
+            i += symbol.len();
+        }
+    }
+    Ok(tokens)
+}
+
+fn named_color(name: &str) -> Option<Srgba> {
+    let color = match name.to_lowercase().as_str() {
+        "red" => css::RED,
+        "green" => css::GREEN,
+        "lime" => css::LIME,
+        "blue" => css::BLUE,
+        "white" => css::WHITE,
+        "black" => css::BLACK,
+        "gray" | "grey" => css::GRAY,
+        "yellow" => css::YELLOW,
+        "gold" => css::GOLD,
+        "orange" => css::ORANGE,
+        "purple" => css::PURPLE,
+        "magenta" | "fuchsia" => css::FUCHSIA,
+        "cyan" | "aqua" => css::AQUA,
+        "pink" => css::PINK,
+        _ => return None
+    };
+    Some(color)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    //column reported for a missing token
+    end: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(Token, usize)> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(Token, usize), StyleExpressionError> {
+        let token = self.peek().ok_or_else(|| StyleExpressionError { column: self.end, message: format!("expected {expected}, found the end") })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek().is_some_and(|(t, _)| t == *token);
+        self.position += matches as usize;
+        matches
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), StyleExpressionError> {
+        match self.next(&format!("`{symbol}`"))? {
+            (Token::Symbol(s), _) if s == symbol => Ok(()),
+            (token, column) => Err(StyleExpressionError { column, message: format!("expected `{symbol}`, found {token}") })
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, StyleExpressionError> {
+        if !self.eat(&Token::Word("if".to_owned())) {
+            return self.style().map(Rule::Style);
+        }
+        let column = self.peek().map_or(self.end, |(_, column)| column);
+        let (condition, condition_type) = self.or()?;
+        if condition_type != Type::Bool {
+            return Err(StyleExpressionError { column, message: "the condition of `if` must be true or false".to_owned() });
+        }
+        let then = self.block()?;
+        let otherwise = if !self.eat(&Token::Word("else".to_owned())) {
+            Rule::Style(None)
+        } else if self.peek().is_some_and(|(token, _)| token == Token::Word("if".to_owned())) {
+            self.rule()?
+        } else {
+            self.block()?
+        };
+        Ok(Rule::If { condition, then: Box::new(then), otherwise: Box::new(otherwise) })
+    }
+
+    fn block(&mut self) -> Result<Rule, StyleExpressionError> {
+        self.expect("{")?;
+        let rule = self.rule()?;
+        self.expect("}")?;
+        Ok(rule)
+    }
+
+    fn style(&mut self) -> Result<Option<StyleModifier>, StyleExpressionError> {
+        match self.next("a color")? {
+            (Token::Word(word), _) if word == "none" => Ok(None),
+            (Token::Word(word), column) => named_color(&word)
+                .map(|color| Some(StyleModifier::base(color)))
+                .ok_or_else(|| StyleExpressionError { column, message: format!("unknown color `{word}`") }),
+            (Token::Hex(hex), column) => Srgba::hex(&hex)
+                .map(|color| Some(StyleModifier::base(color)))
+                .map_err(|_| StyleExpressionError { column, message: format!("invalid color `#{hex}`") }),
+            (token, column) => Err(StyleExpressionError { column, message: format!("expected a color, found {token}") })
+        }
+    }
+
+    fn or(&mut self) -> Result<(Expr, Type), StyleExpressionError> {
+        let mut left = self.and()?;
+        while let Some((_, column)) = self.peek().filter(|(token, _)| *token == Token::Symbol("||")) {
+            self.position += 1;
+            let right = self.and()?;
+            left = (Expr::Or(Box::new(boolean(left, column)?), Box::new(boolean(right, column)?)), Type::Bool);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<(Expr, Type), StyleExpressionError> {
+        let mut left = self.unary()?;
+        while let Some((_, column)) = self.peek().filter(|(token, _)| *token == Token::Symbol("&&")) {
+            self.position += 1;
+            let right = self.unary()?;
+            left = (Expr::And(Box::new(boolean(left, column)?), Box::new(boolean(right, column)?)), Type::Bool);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<(Expr, Type), StyleExpressionError> {
+        match self.peek() {
+            Some((Token::Symbol("!"), column)) => {
+                self.position += 1;
+                let operand = self.unary()?;
+                Ok((Expr::Not(Box::new(boolean(operand, column)?)), Type::Bool))
+            },
+            _ => self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, Type), StyleExpressionError> {
+        let (left, left_type) = self.operand()?;
+        let Some((token, column)) = self.peek() else {
+            return Ok((left, left_type));
+        };
+        if token == Token::Word("in".to_owned()) {
+            self.position += 1;
+            let (right, right_type) = self.operand()?;
+            if left_type != Type::Text || !matches!(right_type, Type::Text | Type::TextList) {
+                return Err(StyleExpressionError { column, message: "`in` looks for a text in `tags` or in another text".to_owned() });
+            }
+            return Ok((Expr::In(Box::new(left), Box::new(right)), Type::Bool));
+        }
+        let Some((symbol, comparison)) = COMPARISONS.iter().copied().find(|(symbol, _)| token == Token::Symbol(symbol)) else {
+            return Ok((left, left_type));
+        };
+        self.position += 1;
+        let (right, right_type) = self.operand()?;
+        if left_type != right_type || left_type == Type::TextList {
+            return Err(StyleExpressionError { column, message: format!("`{symbol}` compares values of the same kind, {left_type:?} and {right_type:?} given") });
+        }
+        if left_type != Type::Number && !matches!(comparison, Comparison::Equal | Comparison::NotEqual) {
+            return Err(StyleExpressionError { column, message: format!("`{symbol}` compares numbers, {left_type:?} given") });
+        }
+        Ok((Expr::Compare(comparison, Box::new(left), Box::new(right)), Type::Bool))
+    }
+
+    fn operand(&mut self) -> Result<(Expr, Type), StyleExpressionError> {
+        match self.next("a value")? {
+            (Token::Number(n), _) => Ok((Expr::Number(n), Type::Number)),
+            (Token::Text(text), _) => Ok((Expr::Text(text), Type::Text)),
+            (Token::Word(word), _) if word == "true" || word == "false" => Ok((Expr::Bool(word == "true"), Type::Bool)),
+            (Token::Word(word), column) => Field::named(&word)
+                .map(|field| (Expr::Field(field), field.value_type()))
+                .ok_or_else(|| StyleExpressionError {
+                    column,
+                    message: format!("unknown field `{word}`, known are {}", Field::ALL.map(|(name, _)| name).join(", "))
+                }),
+            (Token::Symbol("("), _) => {
+                let inner = self.or()?;
+                self.expect(")")?;
+                Ok(inner)
+            },
+            (token, column) => Err(StyleExpressionError { column, message: format!("expected a value, found {token}") })
+        }
+    }
+}
+
+fn boolean((expr, value_type): (Expr, Type), column: usize) -> Result<Expr, StyleExpressionError> {
+    match value_type {
+        Type::Bool => Ok(expr),
+        _ => Err(StyleExpressionError { column, message: format!("`!`, `&&` and `||` combine conditions, {value_type:?} given") })
+    }
+}
+
+//spawned, or moved or tagged since the last evaluation
+type InputsChanged = Or<(Changed<StyleSample>, Changed<CustomTags>, Added<InGameElements>)>;
+
+type StyledSatellite<'a> = (
+    Entity, &'a InGameElements, &'a SatelliteGroup, &'a OrbitClassification, &'a PropagatableDuration, Option<&'a StyleSample>,
+    Option<&'a CustomTags>, &'a mut MarkerStyle
+);
+
+impl Plugin for StyleFunctionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<StyleFunction>()
+            .add_event::<Propageted>()
+            .add_event::<InvalidateDerivedState>()
+            .register_command(
+                CommandDescriptor::new("Set style expression", ActionCategory::General, |params, world| {
+                    world.insert_resource(StyleFunction::from_expression_or_default(params[0].as_text().unwrap_or_default()));
+                })
+                .with_param("expression", ParamKind::Text)
+            )
+            .add_systems(Update, (sample_predictions.run_if(style_function_set), apply_style_function).chain());
+    }
+}
+
+fn style_function_set(function: Res<StyleFunction>) -> bool {
+    function.is_set()
+}
+
+//from the authoritative predictions, like the altitude bands
+fn sample_predictions(
+    mut propagated: EventReader<Propageted>,
+    satellites: Query<(&InGameElements, &PropagatableDuration)>,
+    mut samples: Query<&mut StyleSample>,
+    mut commands: Commands
+) {
+    for (entity, prediction) in propagated.read().flat_map(|p| p.data()) {
+        let Ok((elements, duration)) = satellites.get(*entity) else {
+            continue;
+        };
+        let position = DVec3::from_array(prediction.position);
+        let sun = sun_position(SimulationDate::from_instant(duration.instant(&elements.0)).0);
+        let sample = StyleSample { altitude_km: position.length() as f32 - EARTH_RADIUS_KM, sunlit: is_sunlit(position, sun) };
+        match samples.get_mut(*entity) {
+            Ok(mut current) => current.set_if_neq(sample),
+            Err(_) => { commands.entity(*entity).insert(sample); false }
+        };
+    }
+}
+
+fn apply_style_function(
+    function: Res<StyleFunction>,
+    watchlist: Option<Res<Watchlist>>,
+    mut invalidations: EventReader<InvalidateDerivedState>,
+    changed: Query<Entity, InputsChanged>,
+    mut removed_tags: RemovedComponents<CustomTags>,
+    mut satellites: Query<StyledSatellite>
+) {
+    let refresh_all = function.is_changed() || watchlist.as_ref().is_some_and(|w| w.is_changed()) || invalidations.read().count() > 0;
+    let removed: Vec<Entity> = removed_tags.read().collect();
+    if !function.is_set() && !function.is_changed() {
+        return;
+    }
+    let mut apply = |(entity, elements, group, classification, duration, sample, tags, mut style): QueryItem<StyledSatellite>| {
+        let input = SatelliteStyleInput {
+            name: elements.0.object_name.as_deref().unwrap_or_default(),
+            norad_id: elements.0.norad_id,
+            group: &group.0,
+            class: classification.class,
+            altitude_km: sample.map_or(f32::NAN, |s| s.altitude_km),
+            age_days: duration.minutes_since_epoch() / 1440.0,
+            sunlit: sample.is_none_or(|s| s.sunlit),
+            watchlisted: watchlist.as_ref().is_some_and(|w| w.contains(entity)),
+            tags: tags.map_or(&[], |t| t.0.as_slice())
+        };
+        let modifier = function.evaluate(&input);
+        if style.get(StyleLayer::Theme) != modifier.as_ref() {
+            match modifier {
+                Some(modifier) => style.set(StyleLayer::Theme, modifier),
+                None => style.clear(StyleLayer::Theme)
+            }
+        }
+    };
+    if refresh_all {
+        satellites.iter_mut().for_each(&mut apply);
+    } else {
+        for entity in changed.iter().chain(removed) {
+            if let Ok(satellite) = satellites.get_mut(entity) {
+                apply(satellite);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::color::palettes::css::*;
+
+    use super::*;
+    use crate::commands::InvokeCommand;
+    use crate::test_support::{fixture_elements, headless_app, load_group, run_until, ScriptedClient};
+
+    fn input<'a>(group: &'a str, altitude_km: f32, tags: &'a [String]) -> SatelliteStyleInput<'a> {
+        SatelliteStyleInput {
+            name: "STARLINK-1007", norad_id: 44713, group, class: OrbitClass::Leo, altitude_km, age_days: 1.5, sunlit: true, watchlisted: false, tags
+        }
+    }
+
+    fn color(expression: &str, input: &SatelliteStyleInput) -> Option<Color> {
+        StyleExpression::parse(expression).unwrap().evaluate(input).and_then(|modifier| modifier.base_color)
+    }
+
+    #[test]
+    fn test_expressions_choose_colors() {
+        let expression = r#"if altitude < 600 { red } else if group == "starlink" { #88ccff }"#;
+        assert_eq!(color(expression, &input("other", 550.0, &[])), Some(RED.into()));
+        assert_eq!(color(expression, &input("Starlink", 700.0, &[])), Some(Srgba::hex("88ccff").unwrap().into()));
+        assert_eq!(color(expression, &input("other", 700.0, &[])), None);
+        assert_eq!(color("none", &input("other", 700.0, &[])), None);
+        assert_eq!(color("#0f0", &input("other", 700.0, &[])), Some(LIME.into()));
+
+        //`&&` binds tighter than `||`, `!` looser than comparisons, parentheses first
+        let shadowed = SatelliteStyleInput { sunlit: false, ..input("starlink", 700.0, &[]) };
+        assert_eq!(color(r#"if altitude < 600 || group == "starlink" && sunlit { red } else { blue }"#, &shadowed), Some(BLUE.into()));
+        assert_eq!(color(r#"if (altitude > 600 || group == "x") && !sunlit { red } else { blue }"#, &shadowed), Some(RED.into()));
+        assert_eq!(color(r#"if !altitude < 600 && class == "leo" { red } else { blue }"#, &shadowed), Some(RED.into()));
+        assert_eq!(color("if !sunlit || watchlisted && false { red } else { blue }", &shadowed), Some(RED.into()));
+        assert_eq!(color("if age >= 1.5 && age <= 1.5 && norad_id != 1 { red } else { blue }", &shadowed), Some(RED.into()));
+
+        let tags = vec!["Debris".to_owned()];
+        assert_eq!(color(r#"if "debris" in tags { gray } else if "link" in name { gold }"#, &input("x", 700.0, &tags)), Some(GRAY.into()));
+        assert_eq!(color(r#"if "debris" in tags { gray } else if "link" in name { gold }"#, &input("x", 700.0, &[])), Some(GOLD.into()));
+        //without a prediction only `!=` holds
+        assert_eq!(color("if altitude < 600 || altitude >= 600 { red } else { blue }", &input("x", f32::NAN, &[])), Some(BLUE.into()));
+    }
+
+    fn error(expression: &str) -> String {
+        StyleExpression::parse(expression).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_malformed_expressions_are_reported() {
+        assert_eq!(error("if altitde < 600 { red }"), "column 4: unknown field `altitde`, known are name, norad_id, group, class, altitude, age, sunlit, watchlisted, tags");
+        assert_eq!(error("if altitude < 600 red"), "column 19: expected `{`, found `red`");
+        assert_eq!(error("if altitude < 600 { red"), "column 24: expected `}`, found the end");
+        assert_eq!(error("if altitude { red }"), "column 4: the condition of `if` must be true or false");
+        assert_eq!(error(r#"if altitude == "low" { red }"#), "column 13: `==` compares values of the same kind, Number and Text given");
+        assert_eq!(error(r#"if group < "m" { red }"#), "column 10: `<` compares numbers, Text given");
+        assert_eq!(error("if altitude < 600 && 3 { red }"), "column 19: `!`, `&&` and `||` combine conditions, Number given");
+        assert_eq!(error(r#"if group == "starlink { red }"#), "column 13: unterminated text");
+        assert_eq!(error("if sunlit { #12345 }"), "column 13: invalid color `#12345`");
+        assert_eq!(error("if sunlit { reddish }"), "column 13: unknown color `reddish`");
+        assert_eq!(error("red blue"), "column 5: unexpected `blue` after the style");
+        assert_eq!(error("if sunlit { red } @"), "column 19: unexpected `@`");
+        assert_eq!(error(""), "column 1: expected a color, found the end");
+        assert_eq!(error(r#"if 3 in tags { red }"#), "column 6: `in` looks for a text in `tags` or in another text");
+
+        assert!(!StyleFunction::from_expression_or_default("if { red }").is_set());
+        assert!(StyleFunction::from_expression_or_default("red").is_set());
+    }
+
+    #[test]
+    fn test_satellites_are_restyled_by_the_function() {
+        let mut app = headless_app(ScriptedClient::default().with_group("fixtures", fixture_elements()));
+        app.add_plugins(StyleFunctionPlugin).init_resource::<Watchlist>();
+        let [leo, meo, geo, ..] = load_group(&mut app, "fixtures", 100)[..] else {
+            panic!("every fixture is loaded");
+        };
+        let theme = |app: &App, entity| app.world().get::<MarkerStyle>(entity).unwrap().get(StyleLayer::Theme).and_then(|m| m.base_color);
+        app.world_mut().send_event(InvokeCommand {
+            name: "Set style expression".to_owned(),
+            arguments: vec![r#"if altitude < 2000 { red } else if class == "geo" { #88ccff } else if sunlit || !sunlit { none }"#.to_owned()]
+        });
+        run_until(&mut app, |app| app.world().get::<StyleSample>(leo).is_some() && app.world().get::<StyleSample>(geo).is_some(), 20);
+        app.update();
+        assert_eq!(theme(&app, leo), Some(RED.into()));
+        assert_eq!(theme(&app, meo), None);
+        assert_eq!(theme(&app, geo), Some(Srgba::hex("88ccff").unwrap().into()));
+        let geo_sample = *app.world().get::<StyleSample>(geo).unwrap();
+        assert!((geo_sample.altitude_km - 35786.0).abs() < 100.0, "{geo_sample:?}");
+
+        //the watchlist refreshes every satellite
+        app.insert_resource(StyleFunction::new(|input| input.watchlisted.then(|| StyleModifier::base(GOLD))));
+        app.update();
+        assert_eq!(theme(&app, leo), None);
+        app.world_mut().resource_mut::<Watchlist>().insert(meo);
+        app.update();
+        assert_eq!(theme(&app, meo), Some(GOLD.into()));
+
+        //a malformed expression falls back to the default colors
+        app.world_mut().send_event(InvokeCommand { name: "Set style expression".to_owned(), arguments: vec!["if watchlisted { gold".to_owned()] });
+        app.update();
+        app.update();
+        assert!(!app.world().resource::<StyleFunction>().is_set());
+        assert_eq!(theme(&app, meo), None);
+    }
+
+    #[test]
+    fn test_earth_shadow() {
+        let sun = DVec3::new(1.5e8, 0.0, 0.0);
+        assert!(is_sunlit(DVec3::new(7000.0, 0.0, 0.0), sun));
+        assert!(!is_sunlit(DVec3::new(-7000.0, 0.0, 0.0), sun));
+        assert!(is_sunlit(DVec3::new(-7000.0, 6500.0, 0.0), sun));
+        assert!(is_sunlit(DVec3::new(0.0, 0.0, 7000.0), sun));
+    }
+}
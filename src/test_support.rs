@@ -0,0 +1,327 @@
+//Shared by the tests of the crate: headless apps, a scripted client, element fixtures of the usual orbit regimes
+//and golden files of propagated positions
+use std::{collections::HashMap, fmt::Write as _, fs, path::PathBuf, sync::Arc, time::Duration};
+
+use bevy::{app::Plugins, ecs::event::ManualEventReader, prelude::*, time::TimeUpdateStrategy};
+use serde_json::{Map, Value};
+use sgp4::Elements;
+
+use crate::global::{InGameSettings, PropagationSettings};
+use crate::propagation::{ElementsFormat, EpochDataLoader, LoadElements, LoadedElements, OrbitalData};
+use crate::SkytracioPlugins;
+
+/// Real time of a frame of the test apps
+pub const FRAME: Duration = Duration::from_millis(100);
+
+/// Set to rewrite the golden files from the current results instead of comparing with them
+pub const UPDATE_GOLDEN_ENV: &str = "SKYTRACIO_UPDATE_GOLDEN";
+
+/// Element set of a regime, named `FIXTURE-<regime>`
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    pub json: &'static str,
+    /// Distance from the golden positions still accepted, SGP4 perturbations of the regime included
+    pub golden_tolerance_km: f64
+}
+
+impl Fixture {
+    pub fn elements(&self) -> Arc<Elements> {
+        Arc::new(serde_json::from_str(self.json).expect("fixture elements must parse"))
+    }
+
+    /// Variation of the fixture, tests replace only the fields they exercise
+    pub fn builder(&self) -> FixtureBuilder {
+        let fields = serde_json::from_str(self.json).expect("fixture elements must be an object");
+        FixtureBuilder { fields }
+    }
+}
+
+/// Element set of a [`Fixture`] with some of its fields replaced, angles in degrees
+#[derive(Debug, Clone)]
+pub struct FixtureBuilder {
+    fields: Map<String, Value>
+}
+
+impl FixtureBuilder {
+    fn set(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.to_owned(), value.into());
+        self
+    }
+
+    pub fn name(self, name: impl Into<String>) -> Self {
+        self.set("OBJECT_NAME", name.into())
+    }
+
+    pub fn norad_id(self, norad_id: u64) -> Self {
+        self.set("NORAD_CAT_ID", norad_id)
+    }
+
+    /// UTC date time in the OMM format, `2024-12-28T21:11:13.237440`
+    pub fn epoch(self, epoch: &str) -> Self {
+        self.set("EPOCH", epoch)
+    }
+
+    /// Revolutions per day
+    pub fn mean_motion(self, mean_motion: f64) -> Self {
+        self.set("MEAN_MOTION", mean_motion)
+    }
+
+    pub fn eccentricity(self, eccentricity: f64) -> Self {
+        self.set("ECCENTRICITY", eccentricity)
+    }
+
+    pub fn inclination(self, inclination: f64) -> Self {
+        self.set("INCLINATION", inclination)
+    }
+
+    pub fn raan(self, raan: f64) -> Self {
+        self.set("RA_OF_ASC_NODE", raan)
+    }
+
+    pub fn arg_of_pericenter(self, arg_of_pericenter: f64) -> Self {
+        self.set("ARG_OF_PERICENTER", arg_of_pericenter)
+    }
+
+    pub fn mean_anomaly(self, mean_anomaly: f64) -> Self {
+        self.set("MEAN_ANOMALY", mean_anomaly)
+    }
+
+    /// Drag term of SGP4, zero leaves the orbit unperturbed by the atmosphere
+    pub fn bstar(self, bstar: f64) -> Self {
+        self.set("BSTAR", bstar)
+    }
+
+    /// The set as OMM JSON, like the Celestrak responses
+    pub fn json(&self) -> String {
+        Value::Object(self.fields.clone()).to_string()
+    }
+
+    pub fn build(&self) -> Arc<Elements> {
+        Arc::new(serde_json::from_value(Value::Object(self.fields.clone())).expect("fixture elements must parse"))
+    }
+}
+
+pub const LEO: Fixture = Fixture {
+    json: r#"{"OBJECT_NAME":"FIXTURE-LEO","OBJECT_ID":"2024-900A","EPOCH":"2024-12-28T21:11:13.237440","MEAN_MOTION":15.4995,"ECCENTRICITY":0.0005,"INCLINATION":51.64,"RA_OF_ASC_NODE":80.0,"ARG_OF_PERICENTER":120.0,"MEAN_ANOMALY":40.0,"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":70001,"ELEMENT_SET_NO":999,"REV_AT_EPOCH":100,"BSTAR":0.0003,"MEAN_MOTION_DOT":0.0001,"MEAN_MOTION_DDOT":0}"#,
+    golden_tolerance_km: 150.0
+};
+
+pub const MEO: Fixture = Fixture {
+    json: r#"{"OBJECT_NAME":"FIXTURE-MEO","OBJECT_ID":"2024-900B","EPOCH":"2024-12-28T21:11:13.237440","MEAN_MOTION":2.0056,"ECCENTRICITY":0.005,"INCLINATION":55.0,"RA_OF_ASC_NODE":140.0,"ARG_OF_PERICENTER":30.0,"MEAN_ANOMALY":200.0,"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":70002,"ELEMENT_SET_NO":999,"REV_AT_EPOCH":100,"BSTAR":0,"MEAN_MOTION_DOT":0,"MEAN_MOTION_DDOT":0}"#,
+    golden_tolerance_km: 50.0
+};
+
+pub const GEO: Fixture = Fixture {
+    json: r#"{"OBJECT_NAME":"FIXTURE-GEO","OBJECT_ID":"2024-900C","EPOCH":"2024-12-28T21:11:13.237440","MEAN_MOTION":1.0027,"ECCENTRICITY":0.0002,"INCLINATION":0.05,"RA_OF_ASC_NODE":0.0,"ARG_OF_PERICENTER":0.0,"MEAN_ANOMALY":100.0,"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":70003,"ELEMENT_SET_NO":999,"REV_AT_EPOCH":100,"BSTAR":0,"MEAN_MOTION_DOT":0,"MEAN_MOTION_DDOT":0}"#,
+    golden_tolerance_km: 50.0
+};
+
+//starts at the apogee, far from the perigee where the propagators differ the most
+pub const MOLNIYA: Fixture = Fixture {
+    json: r#"{"OBJECT_NAME":"FIXTURE-MOLNIYA","OBJECT_ID":"2024-900D","EPOCH":"2024-12-28T21:11:13.237440","MEAN_MOTION":2.0059,"ECCENTRICITY":0.72,"INCLINATION":63.4,"RA_OF_ASC_NODE":60.0,"ARG_OF_PERICENTER":270.0,"MEAN_ANOMALY":180.0,"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":70004,"ELEMENT_SET_NO":999,"REV_AT_EPOCH":100,"BSTAR":0,"MEAN_MOTION_DOT":0,"MEAN_MOTION_DDOT":0}"#,
+    golden_tolerance_km: 200.0
+};
+
+//low and with a strong drag term, the regime of reentries
+pub const DECAYING: Fixture = Fixture {
+    json: r#"{"OBJECT_NAME":"FIXTURE-DECAYING","OBJECT_ID":"2024-900E","EPOCH":"2024-12-28T21:11:13.237440","MEAN_MOTION":16.15,"ECCENTRICITY":0.0008,"INCLINATION":51.6,"RA_OF_ASC_NODE":10.0,"ARG_OF_PERICENTER":0.0,"MEAN_ANOMALY":0.0,"EPHEMERIS_TYPE":0,"CLASSIFICATION_TYPE":"U","NORAD_CAT_ID":70005,"ELEMENT_SET_NO":999,"REV_AT_EPOCH":100,"BSTAR":0.0015,"MEAN_MOTION_DOT":0.02,"MEAN_MOTION_DDOT":0}"#,
+    golden_tolerance_km: 150.0
+};
+
+pub const FIXTURES: [Fixture; 5] = [LEO, MEO, GEO, MOLNIYA, DECAYING];
+
+/// Element sets of every fixture, in the order of [`FIXTURES`]
+pub fn fixture_elements() -> OrbitalData {
+    FIXTURES.iter().map(Fixture::elements).collect()
+}
+
+/// Settings of the test apps, propagating twice a second in real time and without smoothing
+pub fn settings() -> InGameSettings {
+    InGameSettings {
+        propagation: PropagationSettings { real_time_interval: Duration::from_millis(500), ..default() },
+        ..default()
+    }
+}
+
+/// Serves the groups it was given, any other group fails to load
+#[derive(Clone, Default, Resource)]
+pub struct ScriptedClient {
+    groups: HashMap<String, OrbitalData>
+}
+
+impl ScriptedClient {
+    pub fn with_group(mut self, group: &str, data: OrbitalData) -> Self {
+        self.groups.insert(group.to_owned(), data);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl EpochDataLoader for ScriptedClient {
+    type Error = String;
+
+    async fn load(&self, group: String, _format: ElementsFormat) -> Result<OrbitalData, Self::Error> {
+        self.groups.get(&group).cloned().ok_or_else(|| format!("{group} is not scripted"))
+    }
+}
+
+/// App with the minimal plugins, [`settings`] and the given plugins, every update advances the time by [`FRAME`]
+pub fn app_with<M>(plugins: impl Plugins<M>) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .insert_resource(settings())
+        .add_plugins(plugins);
+    app
+}
+
+/// App with the headless [`SkytracioPlugins`] loading from `client`, every update advances the time by [`FRAME`].
+/// The settings can still be changed before the first update
+pub fn headless_app<C: EpochDataLoader + Resource + Clone>(client: C) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        //the satellites get their markers, and with them transforms, without the renderer
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<StandardMaterial>>()
+        .add_plugins(SkytracioPlugins::new().with_client(client).with_settings(settings()).headless().without_demo_bodies());
+    app
+}
+
+/// Updates the app until `condition` holds, returns the number of updates it took.
+/// Panics when it doesn't hold after `max_updates`
+pub fn run_until(app: &mut App, mut condition: impl FnMut(&mut App) -> bool, max_updates: usize) -> usize {
+    for updates in 1..=max_updates {
+        app.update();
+        if condition(app) {
+            return updates;
+        }
+    }
+    panic!("the condition didn't hold after {max_updates} updates");
+}
+
+/// Events of a type sent since the previous read, it has to be read at least every other update
+pub struct EventLog<E: Event> {
+    reader: ManualEventReader<E>
+}
+
+impl <E: Event + Clone> EventLog<E> {
+    /// Starts with the events sent after now
+    pub fn new(app: &App) -> Self {
+        let events = app.world().resource::<Events<E>>();
+        let mut reader = events.get_reader();
+        reader.clear(events);
+        Self { reader }
+    }
+
+    pub fn read(&mut self, app: &App) -> Vec<E> {
+        self.reader.read(app.world().resource::<Events<E>>()).cloned().collect()
+    }
+}
+
+/// Loads the group and waits for it, returns the spawned satellites
+pub fn load_group(app: &mut App, group: &str, max_updates: usize) -> Vec<Entity> {
+    load_group_as(app, group, ElementsFormat::Json, max_updates)
+}
+
+/// [`load_group`] in another format
+pub fn load_group_as(app: &mut App, group: &str, format: ElementsFormat, max_updates: usize) -> Vec<Entity> {
+    let events = app.world().resource::<Events<LoadedElements>>();
+    let mut reader = events.get_reader();
+    reader.clear(events);
+    app.world_mut().send_event(LoadElements::group(group, format));
+    let mut entities = None;
+    run_until(app, |app| {
+        entities = reader.read(app.world().resource::<Events<LoadedElements>>()).next().map(|loaded| loaded.entities().to_vec());
+        entities.is_some()
+    }, max_updates);
+    entities.unwrap()
+}
+
+/// Position of a satellite at a time since its epoch, in km in the inertial frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenPosition {
+    pub label: String,
+    pub minutes: f64,
+    pub position: [f64; 3],
+    pub tolerance_km: f64
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/fixtures/golden").join(format!("{name}.txt"))
+}
+
+fn parse_golden(content: &str) -> Vec<GoldenPosition> {
+    content.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| fields.get(i).and_then(|f| f.parse::<f64>().ok()).unwrap_or_else(|| panic!("malformed golden line {line:?}"));
+            GoldenPosition { label: fields[0].to_owned(), minutes: number(1), position: [number(2), number(3), number(4)], tolerance_km: number(5) }
+        })
+        .collect()
+}
+
+/// Compares the positions with the golden file `assets/fixtures/golden/<name>.txt`, with the tolerances of the file.
+/// With [`UPDATE_GOLDEN_ENV`] set the file is rewritten instead, with the tolerances of `actual`
+pub fn assert_golden(name: &str, actual: &[GoldenPosition]) {
+    let path = golden_path(name);
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        let mut content = "# label minutes x_km y_km z_km tolerance_km\n".to_owned();
+        for p in actual {
+            let [x, y, z] = p.position;
+            writeln!(content, "{} {} {x:.6} {y:.6} {z:.6} {}", p.label, p.minutes, p.tolerance_km).unwrap();
+        }
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        return;
+    }
+    let content = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("missing golden file {}, run with {UPDATE_GOLDEN_ENV}=1 to create it: {err}", path.display()));
+    let expected = parse_golden(&content);
+    let mut mismatches = vec![];
+    for golden in &expected {
+        let Some(position) = actual.iter().find(|p| p.label == golden.label && (p.minutes - golden.minutes).abs() < 1e-9) else {
+            mismatches.push(format!("{} at {} min is missing", golden.label, golden.minutes));
+            continue;
+        };
+        let distance = golden.position.iter().zip(position.position).map(|(g, a)| (g - a).powi(2)).sum::<f64>().sqrt();
+        if distance > golden.tolerance_km {
+            mismatches.push(format!("{} at {} min is {distance:.3} km off, {} km allowed", golden.label, golden.minutes, golden.tolerance_km));
+        }
+    }
+    if actual.len() != expected.len() {
+        mismatches.push(format!("{} positions, the golden file has {}", actual.len(), expected.len()));
+    }
+    assert!(mismatches.is_empty(), "{} differs from {}:\n{}", name, path.display(), mismatches.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_lines_round_trip() {
+        let positions = vec![
+            GoldenPosition { label: "FIXTURE-LEO".to_owned(), minutes: 30.0, position: [1.5, -6800.25, 12.0], tolerance_km: 150.0 },
+            GoldenPosition { label: "FIXTURE-GEO".to_owned(), minutes: 0.5, position: [42164.0, 0.0, -3.125], tolerance_km: 50.0 }
+        ];
+        let content = "# label minutes x_km y_km z_km tolerance_km\n\nFIXTURE-LEO 30 1.500000 -6800.250000 12.000000 150\nFIXTURE-GEO 0.5 42164.000000 0.000000 -3.125000 50\n";
+        assert_eq!(parse_golden(content), positions);
+        assert_eq!(FIXTURES.map(|f| f.elements().object_name.clone().unwrap()), ["FIXTURE-LEO", "FIXTURE-MEO", "FIXTURE-GEO", "FIXTURE-MOLNIYA", "FIXTURE-DECAYING"]);
+    }
+
+    #[test]
+    fn test_builder_replaces_only_the_given_fields() {
+        let built = LEO.builder().norad_id(40001).mean_anomaly(12.5).epoch("2024-12-29T00:00:00").build();
+        let leo = LEO.elements();
+        assert_eq!((built.norad_id, built.mean_anomaly), (40001, 12.5));
+        assert_eq!(built.datetime.to_string(), "2024-12-29 00:00:00");
+        assert_eq!((built.object_name.as_deref(), built.mean_motion, built.inclination), (Some("FIXTURE-LEO"), leo.mean_motion, leo.inclination));
+
+        let parsed: Elements = serde_json::from_str(&GEO.builder().name("GEO-2").json()).unwrap();
+        assert_eq!((parsed.object_name.as_deref(), parsed.norad_id), (Some("GEO-2"), 70003));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+use crate::memory::MemoryFootprint;
+
+/// What appending a sample did to the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushOutcome {
+    Appended,
+    /// The sample went back in time, the previous samples were dropped
+    Restarted,
+    /// The sample is further than the max gap after the previous one
+    AfterGap
+}
+
+/// Bounded ring buffer of `(time, value)` samples ordered by time, times are minutes on any continuous scale.
+/// Lookups return stored samples, nothing is interpolated across gaps
+#[derive(Debug, Clone)]
+pub struct TimedHistory<T> {
+    capacity: usize,
+    span: Option<f64>,
+    max_gap: Option<f64>,
+    samples: VecDeque<(f64, T)>
+}
+
+impl <T> TimedHistory<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), span: None, max_gap: None, samples: VecDeque::new() }
+    }
+
+    /// Samples older than `span` before the latest one are evicted
+    pub fn with_span(mut self, span: f64) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Samples further than `max_gap` after the previous one are reported as [`PushOutcome::AfterGap`]
+    pub fn with_max_gap(mut self, max_gap: f64) -> Self {
+        self.max_gap = Some(max_gap);
+        self
+    }
+
+    pub fn max_gap(&self) -> Option<f64> {
+        self.max_gap
+    }
+
+    pub fn push(&mut self, time: f64, value: T) -> PushOutcome {
+        let outcome = match self.samples.back() {
+            Some((last, _)) if time < *last => {
+                self.samples.clear();
+                PushOutcome::Restarted
+            },
+            Some((last, _)) if self.max_gap.is_some_and(|gap| time - last > gap) => PushOutcome::AfterGap,
+            _ => PushOutcome::Appended
+        };
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, value));
+        if let Some(span) = self.span {
+            while self.samples.front().is_some_and(|(t, _)| *t < time - span) {
+                self.samples.pop_front();
+            }
+        }
+        outcome
+    }
+
+    /// Stored sample closest to `time`, the earlier one on a tie
+    pub fn nearest(&self, time: f64) -> Option<(f64, &T)> {
+        let after = self.samples.partition_point(|(t, _)| *t < time);
+        let before = after.checked_sub(1).and_then(|i| self.samples.get(i));
+        let candidate = match (before, self.samples.get(after)) {
+            (Some(b), Some(a)) => if time - b.0 <= a.0 - time { b } else { a },
+            (Some(b), None) => b,
+            (None, a) => a?
+        };
+        Some((candidate.0, &candidate.1))
+    }
+
+    /// Closest stored sample, only if it's within `tolerance` of `time`
+    pub fn nearest_within(&self, time: f64, tolerance: f64) -> Option<(f64, &T)> {
+        self.nearest(time).filter(|(t, _)| (t - time).abs() <= tolerance)
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (f64, &T)> {
+        self.samples.iter().map(|(t, v)| (*t, v))
+    }
+
+    pub fn first(&self) -> Option<(f64, &T)> {
+        self.samples.front().map(|(t, v)| (*t, v))
+    }
+
+    pub fn last(&self) -> Option<(f64, &T)> {
+        self.samples.back().map(|(t, v)| (*t, v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+impl <T> MemoryFootprint for TimedHistory<T> {
+    fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + self.samples.len() * size_of::<(f64, T)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_and_eviction() {
+        let mut history = TimedHistory::new(4);
+        assert_eq!(history.nearest(0.0), None);
+        for t in [0.0, 1.0, 2.0, 3.0, 4.0] {
+            assert_eq!(history.push(t, t * 10.0), PushOutcome::Appended);
+        }
+        //the oldest sample made room
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.first(), Some((1.0, &10.0)));
+        assert_eq!(history.nearest(2.4), Some((2.0, &20.0)));
+        assert_eq!(history.nearest(2.5), Some((2.0, &20.0)));
+        assert_eq!(history.nearest(2.6), Some((3.0, &30.0)));
+        assert_eq!(history.nearest(-5.0), Some((1.0, &10.0)));
+        assert_eq!(history.nearest(9.0), Some((4.0, &40.0)));
+        assert_eq!(history.nearest_within(9.0, 1.0), None);
+
+        let mut spanned = TimedHistory::new(100).with_span(2.0);
+        for t in 0..10 {
+            spanned.push(t as f64, ());
+        }
+        assert_eq!(spanned.iter().map(|(t, _)| t).collect::<Vec<_>>(), vec![7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn test_estimate_follows_growth_and_eviction() {
+        let mut history = TimedHistory::new(8).with_span(10.0);
+        let empty = history.estimated_bytes();
+        for t in 0..8 {
+            history.push(t as f64, [0.0f32; 3]);
+        }
+        let full = history.estimated_bytes();
+        assert_eq!(full, empty + 8 * size_of::<(f64, [f32; 3])>());
+        //at capacity, pushing evicts
+        history.push(8.0, [0.0; 3]);
+        assert_eq!(history.estimated_bytes(), full);
+        //the span evicts all but two
+        history.push(18.0, [0.0; 3]);
+        assert_eq!(history.estimated_bytes(), empty + 2 * size_of::<(f64, [f32; 3])>());
+        history.clear();
+        assert_eq!(history.estimated_bytes(), empty);
+    }
+
+    #[test]
+    fn test_gaps_and_jumps_back() {
+        let mut history = TimedHistory::new(16).with_max_gap(1.5);
+        history.push(0.0, 'a');
+        history.push(1.0, 'b');
+        assert_eq!(history.push(5.0, 'c'), PushOutcome::AfterGap);
+        //the samples around the gap are kept, lookups inside it fall back to the nearest one
+        assert_eq!(history.nearest(2.0), Some((1.0, &'b')));
+        assert_eq!(history.nearest(4.0), Some((5.0, &'c')));
+        assert_eq!(history.nearest_within(3.0, 1.5), None);
+
+        assert_eq!(history.push(2.0, 'd'), PushOutcome::Restarted);
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![(2.0, &'d')]);
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde_json::{json, Value};
+
+use crate::commands::{CommandDescriptor, InvokeCommand, ParamKind, RegisterCommand};
+use crate::error::{ParseError, SkytracioError};
+use crate::input::ActionCategory;
+use crate::propagation::{GroupLoadStatus, LoadStatus};
+
+//step keys standing for a command with a single argument
+const SHORTHANDS: [(&str, &str); 4] = [
+    ("focus", "Focus satellite"),
+    ("speed", "Set simulation speed"),
+    ("date", "Set Sun and Moon date"),
+    ("load", "Load group")
+];
+
+/// Plays scripted tours for presentations, a tour is a JSON array of steps run in order:
+/// `{"load": "gps-ops"}`, `{"wait_for_group": "gps-ops"}`, `{"focus": "ISS (ZARYA)"}`, `{"wait": 10}`,
+/// `{"speed": 600}`, `{"date": "2024-04-08T18:00"}` or any registered command
+/// `{"command": "Toggle chase camera", "arguments": []}`
+#[derive(Default)]
+pub struct TourPlugin {
+    start: Option<PathBuf>
+}
+
+impl TourPlugin {
+    /// Plays the tour in the file right after startup, `--tour <file>` on the command line
+    pub fn starting(mut self, path: Option<PathBuf>) -> Self {
+        self.start = path;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TourStep {
+    /// Runs the command like the palette would
+    Invoke(InvokeCommand),
+    /// Waits this much real time
+    Wait(Duration),
+    /// Waits until the group is loaded, it has to be requested by an earlier step
+    WaitForGroup(String)
+}
+
+impl TourStep {
+    //the form `parse_step` reads back, commands spelled out instead of their shorthands
+    fn to_json(&self) -> Value {
+        match self {
+            TourStep::Invoke(invocation) => json!({"command": invocation.name, "arguments": invocation.arguments}),
+            TourStep::Wait(duration) => json!({"wait": duration.as_secs_f64()}),
+            TourStep::WaitForGroup(group) => json!({"wait_for_group": group})
+        }
+    }
+}
+
+impl fmt::Display for TourStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TourStep::Invoke(invocation) if invocation.arguments.is_empty() => write!(f, "{}", invocation.name),
+            TourStep::Invoke(invocation) => write!(f, "{} {}", invocation.name, invocation.arguments.join(" ")),
+            TourStep::Wait(duration) => write!(f, "wait {:.0} s", duration.as_secs_f64()),
+            TourStep::WaitForGroup(group) => write!(f, "wait for {group}")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tour {
+    pub steps: Vec<TourStep>
+}
+
+impl Tour {
+    pub fn parse(content: &str) -> Result<Self, SkytracioError> {
+        let value: Value = serde_json::from_str(content)
+            .map_err(|err| SkytracioError::Parse { format: "tour".to_owned(), record: None, offset: None, source: ParseError::Json(err) })?;
+        let Some(steps) = value.as_array() else {
+            return Err(SkytracioError::Scenario { step: 0, reason: "a tour is an array of steps".to_owned() });
+        };
+        let steps = steps.iter().enumerate()
+            .map(|(step, value)| parse_step(value).map_err(|reason| SkytracioError::Scenario { step, reason }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { steps })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SkytracioError> {
+        Self::parse(&fs::read_to_string(path).map_err(|err| SkytracioError::io(path, err))?)
+    }
+
+    /// The tour on a single line, as [`Tour::parse`] reads it
+    pub fn to_json(&self) -> String {
+        Value::Array(self.steps.iter().map(TourStep::to_json).collect()).to_string()
+    }
+}
+
+//commands parse their own arguments, numbers are passed as typed
+fn argument(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None
+    }
+}
+
+fn parse_step(step: &Value) -> Result<TourStep, String> {
+    let Some(fields) = step.as_object() else {
+        return Err("a step is an object".to_owned());
+    };
+    if let Some(name) = fields.get("command") {
+        let name = name.as_str().ok_or("the command is a name")?.to_owned();
+        let arguments = match fields.get("arguments") {
+            None => vec![],
+            Some(Value::Array(values)) => values.iter().map(argument).collect::<Option<_>>().ok_or("arguments are strings or numbers")?,
+            Some(_) => return Err("arguments are an array".to_owned())
+        };
+        return Ok(TourStep::Invoke(InvokeCommand { name, arguments }));
+    }
+    if fields.len() != 1 {
+        return Err(format!("expected a single key, got {}", fields.len()));
+    }
+    let (key, value) = fields.iter().next().unwrap();
+    match key.as_str() {
+        "wait" => value.as_f64()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .map(TourStep::Wait)
+            .ok_or_else(|| "wait takes seconds".to_owned()),
+        "wait_for_group" => value.as_str()
+            .map(|group| TourStep::WaitForGroup(group.to_owned()))
+            .ok_or_else(|| "wait_for_group takes a group".to_owned()),
+        key => {
+            let Some((_, name)) = SHORTHANDS.iter().find(|(shorthand, _)| *shorthand == key) else {
+                return Err(format!("unknown step {key}"));
+            };
+            let argument = argument(value).ok_or_else(|| format!("{key} takes a string or a number"))?;
+            Ok(TourStep::Invoke(InvokeCommand { name: name.to_string(), arguments: vec![argument] }))
+        }
+    }
+}
+
+/// Tour being played, removed to stop it
+#[derive(Resource, Debug, Default)]
+pub struct TourPlayer {
+    tour: Tour,
+    next: usize,
+    waited: Duration,
+    pub paused: bool
+}
+
+impl TourPlayer {
+    pub fn new(tour: Tour) -> Self {
+        Self { tour, ..default() }
+    }
+
+    pub fn tour(&self) -> &Tour {
+        &self.tour
+    }
+
+    /// Steps done and all steps
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next, self.tour.steps.len())
+    }
+
+    pub fn current(&self) -> Option<&TourStep> {
+        self.tour.steps.get(self.next)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.tour.steps.len()
+    }
+
+    /// Moves past the current step without running it
+    pub fn skip(&mut self) {
+        self.next = (self.next + 1).min(self.tour.steps.len());
+        self.waited = Duration::ZERO;
+    }
+
+    /// Runs steps until one has to wait, returns the commands to invoke in order
+    pub fn advance(&mut self, delta: Duration, group_loaded: impl Fn(&str) -> bool) -> Vec<InvokeCommand> {
+        let mut invocations = vec![];
+        if self.paused {
+            return invocations;
+        }
+        //the frame time only counts towards a wait started before it
+        let current = self.next;
+        while let Some(step) = self.tour.steps.get(self.next) {
+            match step {
+                TourStep::Invoke(invocation) => invocations.push(invocation.clone()),
+                TourStep::Wait(duration) => {
+                    if self.next == current {
+                        self.waited += delta;
+                    }
+                    if self.waited < *duration {
+                        break;
+                    }
+                },
+                TourStep::WaitForGroup(group) => if !group_loaded(group) {
+                    break;
+                }
+            }
+            self.next += 1;
+            self.waited = Duration::ZERO;
+        }
+        invocations
+    }
+}
+
+#[derive(Resource)]
+struct StartTour(Option<PathBuf>);
+
+#[derive(Component)]
+struct TourProgressText;
+
+impl Plugin for TourPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(StartTour(self.start.clone()))
+            .init_resource::<GroupLoadStatus>()
+            .add_event::<InvokeCommand>()
+            .register_command(
+                CommandDescriptor::new("Play tour", ActionCategory::General, |params, world| {
+                    if let Some(path) = params[0].as_path() {
+                        play(world, path);
+                    }
+                })
+                .with_param("file", ParamKind::Path)
+            )
+            .register_command(CommandDescriptor::new("Pause or resume tour", ActionCategory::General, |_, world| {
+                if let Some(mut player) = world.get_resource_mut::<TourPlayer>() {
+                    player.paused = !player.paused;
+                }
+            }))
+            .register_command(CommandDescriptor::new("Skip tour step", ActionCategory::General, |_, world| {
+                if let Some(mut player) = world.get_resource_mut::<TourPlayer>() {
+                    player.skip();
+                }
+            }))
+            .register_command(CommandDescriptor::new("Stop tour", ActionCategory::General, |_, world| {
+                world.remove_resource::<TourPlayer>();
+            }))
+            .add_systems(Startup, start_tour)
+            .add_systems(Update, (play_tour.run_if(resource_exists::<TourPlayer>), update_progress_text).chain());
+    }
+}
+
+fn play(world: &mut World, path: &Path) {
+    match Tour::load(path) {
+        Ok(tour) => world.insert_resource(TourPlayer::new(tour)),
+        Err(err) => warn!("Failed to load the tour {}: {}", path.display(), err.report())
+    }
+}
+
+fn start_tour(world: &mut World) {
+    if let Some(path) = world.resource_mut::<StartTour>().0.take() {
+        play(world, &path);
+    }
+}
+
+fn play_tour(
+    time: Res<Time<Real>>,
+    mut player: ResMut<TourPlayer>,
+    status: Res<GroupLoadStatus>,
+    mut invocations: EventWriter<InvokeCommand>
+) {
+    let loaded = |group: &str| status.get(group) == Some(LoadStatus::Loaded);
+    invocations.send_batch(player.advance(time.delta(), loaded));
+}
+
+fn update_progress_text(player: Option<Res<TourPlayer>>, texts: Query<Entity, With<TourProgressText>>, mut commands: Commands) {
+    let changed = player.as_ref().map_or(!texts.is_empty(), |player| player.is_changed());
+    if !changed {
+        return;
+    }
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(player) = player else {
+        return;
+    };
+    let (done, total) = player.progress();
+    let text = match player.current() {
+        Some(step) => format!("Tour {}/{total}: {step}{}", done + 1, if player.paused { " (paused)" } else { "" }),
+        None => format!("Tour finished, {total} steps")
+    };
+    commands.spawn((
+        TextBundle::from_section(text, TextStyle { font_size: 16.0, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                ..default()
+            }),
+        TourProgressText
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::commands::CommandsPlugin;
+    use crate::global::{InGameSettings, PropagationSettings};
+    use crate::propagation::{InGameElements, LoadElementsPlugin, PropagateInGamePlugin, Propageted};
+    use crate::selection::{FocusSatellite, SelectionPlugin, SelectionSet};
+    use crate::stress::{starlink_like_elements, SyntheticClient};
+
+    const TOUR: &str = r#"[
+        {"load": "starlink"},
+        {"wait_for_group": "starlink"},
+        {"focus": "STARLINK-44003"},
+        {"wait": 1.5},
+        {"speed": 600},
+        {"command": "Set simulation speed", "arguments": ["60"]},
+        {"wait": 0.5}
+    ]"#;
+
+    #[test]
+    fn test_tour_parsing() {
+        let tour = Tour::parse(TOUR).unwrap();
+        assert_eq!(tour.steps.len(), 7);
+        assert_eq!(tour.steps[1], TourStep::WaitForGroup("starlink".to_owned()));
+        assert_eq!(tour.steps[3], TourStep::Wait(Duration::from_millis(1500)));
+        assert_eq!(tour.steps[4], TourStep::Invoke(InvokeCommand { name: "Set simulation speed".to_owned(), arguments: vec!["600".to_owned()] }));
+        assert_eq!(tour.steps[4].to_string(), "Set simulation speed 600");
+
+        assert!(matches!(Tour::parse("[{\"fly\": 1}]"), Err(SkytracioError::Scenario { step: 0, .. })));
+        let invalid = Tour::parse("[{\"wait\": 1}, {\"wait\": -1}]").unwrap_err();
+        assert!(matches!(invalid, SkytracioError::Scenario { step: 1, .. }));
+        assert!(invalid.to_string().starts_with("step 1 of the tour is invalid, "), "{invalid}");
+        assert!(matches!(Tour::parse("[{\"wait\": 1e30}]"), Err(SkytracioError::Scenario { step: 0, .. })));
+        assert!(matches!(Tour::parse("[{\"wait\": 1,"), Err(SkytracioError::Parse { source: ParseError::Json(_), .. })));
+
+        assert_eq!(Tour::parse(&tour.to_json()).unwrap(), tour);
+        assert!(!tour.to_json().contains('\n'));
+    }
+
+    #[test]
+    fn test_scripted_tour_runs_headless() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, CommandsPlugin, LoadElementsPlugin::<SyntheticClient>::new(), PropagateInGamePlugin, SelectionPlugin, TourPlugin::default()))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+            .insert_resource(SyntheticClient(starlink_like_elements(20, 1478)))
+            .insert_resource(InGameSettings {
+                propagation: PropagationSettings { batch_size: 100, ..default() },
+                ..default()
+            })
+            .add_event::<Propageted>()
+            .insert_resource(TourPlayer::new(Tour::parse(TOUR).unwrap()));
+
+        let mut invoked = vec![];
+        let mut reader = app.world().resource::<Events<InvokeCommand>>().get_reader();
+        let mut focused_frame = None;
+        for frame in 0..200 {
+            app.update();
+            invoked.extend(reader.read(app.world().resource::<Events<InvokeCommand>>()).map(|ev| (frame, TourStep::Invoke(ev.clone()).to_string())));
+            let events = app.world().resource::<Events<FocusSatellite>>();
+            if focused_frame.is_none() && !events.is_empty() {
+                focused_frame = Some(frame);
+            }
+            if app.world().resource::<TourPlayer>().is_finished() {
+                break;
+            }
+        }
+        //the last commands run in the next frame
+        app.update();
+        let names: Vec<_> = invoked.iter().map(|(_, step)| step.as_str()).collect();
+        assert_eq!(names, vec![
+            "Load group starlink", "Focus satellite STARLINK-44003", "Set simulation speed 600", "Set simulation speed 60"
+        ]);
+        //the focus waits for the group, the speed waits 1.5 s of 100 ms frames after it
+        assert!(invoked[1].0 > invoked[0].0);
+        assert_eq!(invoked[2].0 - invoked[1].0, 15);
+        assert_eq!(invoked[3].0, invoked[2].0);
+        assert!(focused_frame.is_some());
+
+        let (done, total) = app.world().resource::<TourPlayer>().progress();
+        assert_eq!(done, total);
+        assert_eq!(app.world().resource::<InGameSettings>().simulation_speed, 60.0);
+        let focused = app.world().resource::<SelectionSet>().primary().unwrap();
+        assert_eq!(app.world().get::<InGameElements>(focused).unwrap().0.norad_id, 44003);
+    }
+}
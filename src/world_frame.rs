@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+/// Axes of the world space, right handed.
+/// Inertial positions (SGP4 TEME, Keplerian orbits, the ephemeris) are computed with X towards the vernal equinox
+/// and Z towards the celestial north pole, every producer of world space positions or rotations converts them with
+/// [`WorldFrame::to_world`] (or [`WorldFrame::rotation`]), so the convention is only defined here
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldFrame {
+    /// World direction of the vernal equinox
+    pub equinox: Vec3,
+    /// World direction of the celestial north pole
+    pub north: Vec3
+}
+
+/// The world is the inertial frame itself: X vernal equinox, Z north
+pub const WORLD_FRAME: WorldFrame = WorldFrame { equinox: Vec3::X, north: Vec3::Z };
+
+impl WorldFrame {
+    /// Rotation of inertial axes onto the world ones
+    pub fn rotation(&self) -> Quat {
+        Quat::from_mat3(&Mat3::from_cols(self.equinox, self.north.cross(self.equinox), self.north))
+    }
+
+    /// Inertial position or direction in world space, the adapter used for everything rendered
+    pub fn to_world(&self, inertial: Vec3) -> Vec3 {
+        self.rotation() * inertial
+    }
+
+    /// World position back in the inertial frame, for orbit math on rendered positions
+    pub fn to_inertial(&self, world: Vec3) -> Vec3 {
+        self.rotation().inverse() * world
+    }
+
+    /// Orientation of models authored with Y up (glTF), their Y axis ends at the north pole
+    pub fn y_up_model_rotation(&self) -> Quat {
+        self.rotation() * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)
+    }
+
+    /// Camera position and up direction of the overview, above the north pole with the equinox up the screen
+    pub fn overview(&self, distance: f32) -> (Vec3, Vec3) {
+        (self.north * distance, self.equinox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::orbit::SatelliteOrbit;
+
+    //the Earth model is a Y up globe, rendered with the same rotation as in `earth.rs`
+    fn rendered_pole() -> Vec3 {
+        WORLD_FRAME.y_up_model_rotation() * Vec3::Y
+    }
+
+    fn rendered(orbit: &SatelliteOrbit, true_anomaly: f64) -> Vec3 {
+        WORLD_FRAME.to_world(SatelliteOrbit { true_anomaly, ..orbit.clone() }.to_translation_and_rotation().position)
+    }
+
+    #[test]
+    fn test_equatorial_orbit_in_the_plane_of_the_rendered_equator() {
+        let orbit = SatelliteOrbit::new(7000.0, 0.01, 0.0, 40.0, 10.0, 0.0, 0.0);
+        for true_anomaly in (0..360).step_by(30) {
+            let position = rendered(&orbit, true_anomaly as f64);
+            assert_abs_diff_eq!(position.normalize().dot(rendered_pole()), 0.0, epsilon = 1e-5);
+        }
+        assert!(WORLD_FRAME.to_world(orbit.orbit_normal().as_vec3()).dot(rendered_pole()) > 0.9999);
+    }
+
+    #[test]
+    fn test_polar_orbit_over_the_rendered_poles() {
+        //with the perigee at the ascending node, a quarter of the orbit later the satellite is above the north pole
+        let orbit = SatelliteOrbit::new(7000.0, 0.0, 90.0, 25.0, 0.0, 0.0, 0.0);
+        assert!(rendered(&orbit, 90.0).normalize().dot(rendered_pole()) > 0.9999);
+        assert!(rendered(&orbit, 270.0).normalize().dot(rendered_pole()) < -0.9999);
+        let inertial = SatelliteOrbit { true_anomaly: 37.0, ..orbit.clone() }.to_translation_and_rotation().position;
+        assert!(WORLD_FRAME.to_inertial(rendered(&orbit, 37.0)).distance(inertial) < 1e-2);
+    }
+}